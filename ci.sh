@@ -32,6 +32,8 @@ function run_tests {
     # Optimizations change diagnostics (mostly backtraces), so we don't check them
     #FIXME(#2155): we want to only run the pass and panic tests here, not the fail tests.
     MIRIFLAGS="${MIRIFLAGS:-} -O -Zmir-opt-level=4" MIRI_SKIP_UI_CHECKS=1 ./miri test -- tests/{pass,panic}
+    # The tag index of `-Zmiri-sb-tag-index` must not change the outcome of any Stacked Borrows test.
+    MIRIFLAGS="${MIRIFLAGS:-} -Zmiri-sb-tag-index" ./miri test -- stacked-borrows stacked_borrows
   fi
 
   ## test-cargo-miri
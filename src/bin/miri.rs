@@ -16,8 +16,10 @@ extern crate rustc_session;
 
 use std::env;
 use std::num::NonZeroU64;
+use std::ops::Range;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 use log::debug;
 
@@ -33,10 +35,12 @@ use rustc_middle::{
 };
 use rustc_session::{config::CrateType, search_paths::PathKind, CtfeBacktrace};
 
-use miri::{BacktraceStyle, ProvenanceMode};
+use miri::{BacktraceStyle, ProvenanceMode, SchedulerPolicy};
 
 struct MiriCompilerCalls {
     miri_config: miri::MiriConfig,
+    /// With `-Zmiri-many-seeds`, the seeds to run the program with.
+    many_seeds: Option<Range<u64>>,
 }
 
 impl rustc_driver::Callbacks for MiriCompilerCalls {
@@ -83,7 +87,51 @@ impl rustc_driver::Callbacks for MiriCompilerCalls {
                 env::set_current_dir(cwd).unwrap();
             }
 
-            if let Some(return_code) = miri::eval_entry(tcx, entry_def_id, entry_type, config) {
+            // The summary covers all the runs of the program, so it is printed once they are done.
+            let extern_call_summary = config.external_call_summary.clone();
+            let print_extern_call_summary = || {
+                if let Some(summary) = &extern_call_summary {
+                    summary.lock().unwrap().print();
+                }
+            };
+
+            if let Some(many_seeds) = self.many_seeds.clone() {
+                // Run the program once per seed, reusing the MIR we already have.
+                let mut failed_seeds = vec![];
+                for seed in many_seeds {
+                    eprintln!("Trying seed: {seed:x}");
+                    let mut config = config.clone();
+                    config.seed = Some(seed);
+                    let return_code = miri::eval_entry(tcx, entry_def_id, entry_type, config);
+                    if return_code != Some(0) {
+                        eprintln!("Failing seed: {seed:x}");
+                        failed_seeds.push(seed);
+                    }
+                }
+                print_extern_call_summary();
+                if failed_seeds.is_empty() {
+                    std::process::exit(0);
+                }
+                let seeds = failed_seeds
+                    .iter()
+                    .map(|seed| format!("{seed:x}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                tcx.sess.err(&format!(
+                    "the program failed with {} of the seeds: {seeds}",
+                    failed_seeds.len()
+                ));
+                tcx.sess.note_without_error(&format!(
+                    "use `-Zmiri-seed={:x}` to reproduce the first failure",
+                    failed_seeds[0]
+                ));
+                tcx.sess.abort_if_errors();
+                unreachable!();
+            }
+
+            let return_code = miri::eval_entry(tcx, entry_def_id, entry_type, config);
+            print_extern_call_summary();
+            if let Some(return_code) = return_code {
                 std::process::exit(
                     i32::try_from(return_code).expect("Return value was too large!"),
                 );
@@ -354,6 +402,7 @@ fn main() {
 
     // If user has explicitly enabled/disabled isolation
     let mut isolation_enabled: Option<bool> = None;
+    let mut many_seeds: Option<Range<u64>> = None;
     for arg in env::args() {
         if rustc_args.is_empty() {
             // Very first arg: binary name.
@@ -394,6 +443,20 @@ fn main() {
             miri_config.weak_memory_emulation = false;
         } else if arg == "-Zmiri-track-weak-memory-loads" {
             miri_config.track_outdated_loads = true;
+        } else if arg == "-Zmiri-data-race-backtraces" {
+            miri_config.data_race_backtraces = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-data-race-history=") {
+            let len = match param.parse::<usize>() {
+                Ok(len) if len > 0 => len,
+                _ => show_error!("-Zmiri-data-race-history requires a positive `usize`"),
+            };
+            miri_config.data_race_history = Some(len);
+        } else if arg == "-Zmiri-race-report-only" {
+            miri_config.race_report_only = true;
+        } else if arg == "-Zmiri-lock-order-check" {
+            miri_config.lock_order_check = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-export-hb=") {
+            miri_config.export_hb = Some(PathBuf::from(param));
         } else if let Some(param) = arg.strip_prefix("-Zmiri-isolation-error=") {
             if matches!(isolation_enabled, Some(false)) {
                 show_error!(
@@ -416,8 +479,14 @@ fn main() {
             };
         } else if arg == "-Zmiri-ignore-leaks" {
             miri_config.ignore_leaks = true;
+        } else if arg == "-Zmiri-check-fd-leaks" {
+            miri_config.check_fd_leaks = true;
         } else if arg == "-Zmiri-panic-on-unsupported" {
             miri_config.panic_on_unsupported = true;
+        } else if arg == "-Zmiri-extern-call-summary" {
+            miri_config.external_call_summary = Some(Default::default());
+        } else if arg == "-Zmiri-extern-stub-missing" {
+            miri_config.extern_stub_missing = true;
         } else if arg == "-Zmiri-tag-raw-pointers" {
             eprintln!("WARNING: `-Zmiri-tag-raw-pointers` has no effect; it is enabled by default");
         } else if arg == "-Zmiri-strict-provenance" {
@@ -426,6 +495,117 @@ fn main() {
             miri_config.provenance_mode = ProvenanceMode::Permissive;
         } else if arg == "-Zmiri-mute-stdout-stderr" {
             miri_config.mute_stdout_stderr = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-stdin-file=") {
+            let input = std::fs::read(param).unwrap_or_else(|err| {
+                show_error!("-Zmiri-stdin-file: failed to read `{}`: {}", param, err)
+            });
+            miri_config.stdin = Some(input);
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-fs-fail-nth=") {
+            let nth = match param.parse::<u64>() {
+                Ok(nth) if nth > 0 => nth,
+                _ => show_error!("-Zmiri-fs-fail-nth requires a positive `u64`"),
+            };
+            miri_config.fs_fail_nth = Some(nth);
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-fs-fail-rate=") {
+            let rate = match param.parse::<f64>() {
+                Ok(rate) if (0.0..=1.0).contains(&rate) => rate,
+                _ => show_error!("-Zmiri-fs-fail-rate must be between `0.0` and `1.0`"),
+            };
+            miri_config.fs_fail_rate = rate;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-fs-fail-errno=") {
+            if !miri::FS_FAIL_ERRNOS.contains(&param) {
+                show_error!(
+                    "-Zmiri-fs-fail-errno must be one of {}",
+                    miri::FS_FAIL_ERRNOS.join(", ")
+                );
+            }
+            miri_config.fs_fail_errno = param.to_owned();
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-isatty=") {
+            miri_config.isatty = Some(match param {
+                "true" => true,
+                "false" => false,
+                _ => show_error!("-Zmiri-isatty must be `true` or `false`"),
+            });
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-terminal-size=") {
+            let size = param.split_once('x').and_then(|(cols, rows)| {
+                Some((cols.parse::<u16>().ok()?, rows.parse::<u16>().ok()?))
+            });
+            match size {
+                Some((cols, rows)) if cols > 0 && rows > 0 =>
+                    miri_config.terminal_size = Some((cols, rows)),
+                _ =>
+                    show_error!(
+                        "-Zmiri-terminal-size requires the number of columns and rows, like `80x24`"
+                    ),
+            }
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-rlimit=") {
+            let parse_limit = |limit: &str| match limit {
+                "unlimited" => Some(u64::MAX),
+                _ => limit.parse::<u64>().ok(),
+            };
+            let rlimit = param.split_once('=').and_then(|(resource, limits)| {
+                let resource = format!("RLIMIT_{}", resource.to_uppercase());
+                let (resource, ..) = miri::RLIMITS.iter().find(|(name, ..)| *name == resource)?;
+                let (soft, hard) = match limits.split_once(':') {
+                    Some((soft, hard)) => (parse_limit(soft)?, parse_limit(hard)?),
+                    None => (parse_limit(limits)?, parse_limit(limits)?),
+                };
+                (soft <= hard).then_some((*resource, soft, hard))
+            });
+            match rlimit {
+                Some(rlimit) => miri_config.rlimits.push(rlimit),
+                None =>
+                    show_error!(
+                        "-Zmiri-rlimit requires a resource and a soft and optional hard limit, like `nofile=256:1024`"
+                    ),
+            }
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-uid=") {
+            miri_config.uid = match param.parse::<u32>() {
+                Ok(uid) => uid,
+                Err(err) => show_error!("-Zmiri-uid requires a `u32`: {}", err),
+            };
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-gid=") {
+            miri_config.gid = match param.parse::<u32>() {
+                Ok(gid) => gid,
+                Err(err) => show_error!("-Zmiri-gid requires a `u32`: {}", err),
+            };
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-tz-offset=") {
+            miri_config.tz_offset = match param.parse::<i64>() {
+                Ok(offset) if offset.abs() < 24 * 60 * 60 => offset,
+                _ => show_error!("-Zmiri-tz-offset requires a number of seconds of less than a day"),
+            };
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-num-cpus=") {
+            miri_config.num_cpus = match param.parse::<u32>() {
+                Ok(num_cpus) if num_cpus > 0 => num_cpus,
+                _ => show_error!("-Zmiri-num-cpus requires a positive `u32`"),
+            };
+        } else if arg == "-Zmiri-virtual-clock" {
+            miri_config.virtual_clock = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-clock-tick=") {
+            miri_config.clock_tick_per_block = match param.parse::<u64>() {
+                Ok(tick) => tick,
+                Err(err) => show_error!("-Zmiri-clock-tick requires a `u64`: {}", err),
+            };
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-clock-read-tick=") {
+            miri_config.clock_tick_per_read = match param.parse::<u64>() {
+                Ok(tick) => tick,
+                Err(err) => show_error!("-Zmiri-clock-read-tick requires a `u64`: {}", err),
+            };
+        } else if arg == "-Zmiri-fork-fails" {
+            miri_config.fork_fails = true;
+        } else if arg == "-Zmiri-loopback-network" {
+            miri_config.loopback_network = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-host=") {
+            let Some((name, ip)) = param.split_once('=') else {
+                show_error!("-Zmiri-host requires an argument of the form `<name>=<ipv4 address>`");
+            };
+            let ip = match ip.parse() {
+                Ok(ip) => ip,
+                Err(err) => show_error!("-Zmiri-host requires an IPv4 address: {}", err),
+            };
+            miri_config.hosts.push((name.to_owned(), ip));
+        } else if arg == "-Zmiri-enforce-rlimit-nofile" {
+            miri_config.enforce_rlimit_nofile = true;
         } else if arg == "-Zmiri-retag-fields" {
             miri_config.retag_fields = true;
         } else if arg == "-Zmiri-track-raw-pointers" {
@@ -441,10 +621,40 @@ fn main() {
                             "-Zmiri-seed should only contain valid hex digits [0-9a-fA-F] and must fit into a u64 (max 16 characters)"
                         ));
             miri_config.seed = Some(seed);
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-many-seeds=") {
+            if many_seeds.is_some() {
+                show_error!("Cannot specify -Zmiri-many-seeds multiple times!");
+            }
+            let (from, to) = param.split_once("..").unwrap_or_else(|| {
+                show_error!("-Zmiri-many-seeds requires a range of the form `FROM..TO` or `..TO`")
+            });
+            // Like `-Zmiri-seed`, the seeds are given in hexadecimal.
+            let from = if from.is_empty() {
+                0
+            } else {
+                u64::from_str_radix(from, 16).unwrap_or_else(|err| {
+                    show_error!(
+                        "-Zmiri-many-seeds requires a hexadecimal `u64` start of the range: {}",
+                        err
+                    )
+                })
+            };
+            let to = u64::from_str_radix(to, 16).unwrap_or_else(|err| {
+                show_error!(
+                    "-Zmiri-many-seeds requires a hexadecimal `u64` end of the range: {}",
+                    err
+                )
+            });
+            if from >= to {
+                show_error!("-Zmiri-many-seeds requires a non-empty range of seeds");
+            }
+            many_seeds = Some(from..to);
         } else if let Some(param) = arg.strip_prefix("-Zmiri-env-exclude=") {
             miri_config.excluded_env_vars.push(param.to_owned());
         } else if let Some(param) = arg.strip_prefix("-Zmiri-env-forward=") {
             miri_config.forwarded_env_vars.push(param.to_owned());
+        } else if arg == "-Zmiri-env-write-through" {
+            miri_config.env_write_through = true;
         } else if let Some(param) = arg.strip_prefix("-Zmiri-track-pointer-tag=") {
             let ids: Vec<u64> = match parse_comma_list(param) {
                 Ok(ids) => ids,
@@ -461,6 +671,10 @@ fn main() {
                     show_error!("-Zmiri-track-pointer-tag requires nonzero arguments");
                 }
             }
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-track-pointer-tags-at=") {
+            let sites = parse_comma_list::<miri::TagCreationSite>(param)
+                .unwrap_or_else(|never| match never {});
+            miri_config.tracked_tag_sites.extend(sites);
         } else if let Some(param) = arg.strip_prefix("-Zmiri-track-call-id=") {
             let ids: Vec<u64> = match parse_comma_list(param) {
                 Ok(ids) => ids,
@@ -512,6 +726,44 @@ fn main() {
                     ),
             };
             miri_config.preemption_rate = rate;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-scheduler=") {
+            miri_config.scheduler_policy = match param {
+                "round-robin" => SchedulerPolicy::RoundRobin,
+                "fifo" => SchedulerPolicy::Fifo,
+                "random" => SchedulerPolicy::Random,
+                "priority" => SchedulerPolicy::Priority,
+                "delay" => SchedulerPolicy::Delay,
+                _ =>
+                    show_error!(
+                        "-Zmiri-scheduler may only be round-robin, fifo, random, priority, or delay"
+                    ),
+            };
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-livelock-threshold=") {
+            let threshold = match param.parse::<u64>() {
+                Ok(threshold) => threshold,
+                Err(err) => show_error!("-Zmiri-livelock-threshold requires a `u64`: {}", err),
+            };
+            miri_config.livelock_threshold = threshold;
+        } else if arg == "-Zmiri-random-tls-dtor-order" {
+            miri_config.random_tls_dtor_order = true;
+        } else if arg == "-Zmiri-dpor" {
+            miri_config.dpor = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-dpor-max-executions=") {
+            let max = match param.parse::<u64>() {
+                Ok(max) if max > 0 => max,
+                _ => show_error!("-Zmiri-dpor-max-executions requires a positive `u64`"),
+            };
+            miri_config.dpor_max_executions = max;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-schedule=") {
+            let schedule = match parse_comma_list::<u32>(param) {
+                Ok(schedule) => schedule,
+                Err(err) =>
+                    show_error!(
+                        "-Zmiri-schedule requires a comma separated list of thread ids: {}",
+                        err
+                    ),
+            };
+            miri_config.replay_schedule = Some(schedule);
         } else if arg == "-Zmiri-report-progress" {
             // This makes it take a few seconds between progress reports on my laptop.
             miri_config.report_progress = Some(1_000_000);
@@ -527,6 +779,36 @@ fn main() {
                 Err(err) => show_error!("-Zmiri-tag-gc requires a `u32`: {}", err),
             };
             miri_config.gc_interval = interval;
+        } else if arg == "-Zmiri-tag-gc-stats" {
+            miri_config.tag_gc_stats = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-stacked-borrows-history-limit=") {
+            let limit = match param.parse::<usize>() {
+                Ok(limit) if limit > 0 => limit,
+                _ =>
+                    show_error!(
+                        "-Zmiri-stacked-borrows-history-limit requires a positive `usize` argument"
+                    ),
+            };
+            miri_config.stacked_borrows_history_limit = Some(limit);
+        } else if arg == "-Zmiri-verbose-stacked-borrows" {
+            miri_config.verbose_stacked_borrows = true;
+        } else if arg == "-Zmiri-stacked-borrows-json" {
+            miri_config.stacked_borrows_json = Some(None);
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-stacked-borrows-json=") {
+            miri_config.stacked_borrows_json = Some(Some(PathBuf::from(param)));
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-disable-retag-for-crate=") {
+            let crates = parse_comma_list::<String>(param).unwrap_or_else(|never| match never {});
+            miri_config.retag_disabled_crates.extend(crates);
+        } else if arg == "-Zmiri-sb-report-only" {
+            miri_config.sb_report_only = true;
+        } else if arg == "-Zmiri-sb-permissive-raw-ptrs" {
+            miri_config.sb_permissive_raw_ptrs = true;
+        } else if arg == "-Zmiri-sb-tag-index" {
+            miri_config.sb_tag_index = true;
+        } else if arg == "-Zmiri-sb-stats" {
+            miri_config.sb_stats = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-sb-trace-out=") {
+            miri_config.sb_trace_out = Some(PathBuf::from(param));
         } else if let Some(param) = arg.strip_prefix("-Zmiri-measureme=") {
             miri_config.measureme_out = Some(param.to_string());
         } else if let Some(param) = arg.strip_prefix("-Zmiri-backtrace=") {
@@ -549,13 +831,48 @@ fn main() {
             } else {
                 show_error!("-Zmiri-extern-so-file `{}` does not exist", filename);
             }
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-extern-so-long-double=") {
+            miri_config.external_so_long_double = Some(param.to_string());
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-extern-call-timeout=") {
+            let (num, unit): (_, fn(u64) -> Duration) =
+                if let Some(ms) = param.strip_suffix("ms") {
+                    (ms, Duration::from_millis)
+                } else {
+                    (param.strip_suffix('s').unwrap_or(param), Duration::from_secs)
+                };
+            let timeout = match num.parse::<u64>() {
+                Ok(t) if t > 0 => unit(t),
+                _ =>
+                    show_error!(
+                        "-Zmiri-extern-call-timeout requires a positive number of seconds (`30s`) or milliseconds (`500ms`)"
+                    ),
+            };
+            miri_config.external_call_timeout = Some(timeout);
         } else {
             // Forward to rustc.
             rustc_args.push(arg);
         }
     }
 
+    if many_seeds.is_some() && miri_config.seed.is_some() {
+        show_error!("Only one of -Zmiri-seed and -Zmiri-many-seeds can be specified!");
+    }
+
+    // `-Zmiri-stdin-file` takes precedence over the input given in the environment.
+    if miri_config.stdin.is_none() {
+        if let Some(input) = env::var_os("MIRI_STDIN") {
+            let input = input.into_string().unwrap_or_else(|_| {
+                show_error!("MIRI_STDIN must be valid UTF-8, use -Zmiri-stdin-file for other input")
+            });
+            miri_config.stdin = Some(input.into_bytes());
+        }
+    }
+
     debug!("rustc arguments: {:?}", rustc_args);
     debug!("crate arguments: {:?}", miri_config.args);
-    run_compiler(rustc_args, /* target_crate: */ true, &mut MiriCompilerCalls { miri_config })
+    run_compiler(
+        rustc_args,
+        /* target_crate: */ true,
+        &mut MiriCompilerCalls { miri_config, many_seeds },
+    )
 }
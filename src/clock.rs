@@ -0,0 +1,133 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant as StdInstant};
+
+/// A point in time of a [`Clock`]. Instants of a host clock and a virtual clock are never
+/// compared with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant {
+    kind: InstantKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum InstantKind {
+    Host(StdInstant),
+    Virtual { nanoseconds: u64 },
+}
+
+impl Instant {
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        match self.kind {
+            InstantKind::Host(instant) =>
+                instant.checked_add(duration).map(|i| Instant { kind: InstantKind::Host(i) }),
+            InstantKind::Virtual { nanoseconds } =>
+                u64::try_from(duration.as_nanos())
+                    .ok()
+                    .and_then(|duration| nanoseconds.checked_add(duration))
+                    .map(|nanoseconds| Instant { kind: InstantKind::Virtual { nanoseconds } }),
+        }
+    }
+
+    /// Returns the time elapsed since `earlier`, or zero if `earlier` is later than `self`.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        match (&self.kind, earlier.kind) {
+            (InstantKind::Host(instant), InstantKind::Host(earlier)) =>
+                instant.saturating_duration_since(earlier),
+            (
+                InstantKind::Virtual { nanoseconds },
+                InstantKind::Virtual { nanoseconds: earlier },
+            ) => Duration::from_nanos(nanoseconds.saturating_sub(earlier)),
+            _ => panic!("all `Instant` must be of the same kind"),
+        }
+    }
+}
+
+/// A monotone clock used for `Instant` simulation and for timeouts. Clones share the current time,
+/// so that file descriptions like timerfds can keep one to look at the time.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    kind: ClockKind,
+}
+
+#[derive(Debug, Clone)]
+enum ClockKind {
+    /// The host clock, used when isolation is disabled.
+    Host {
+        /// The "time anchor" for this machine's monotone clock.
+        time_anchor: StdInstant,
+    },
+    /// A deterministic clock, used under isolation or with `-Zmiri-virtual-clock`. It advances by
+    /// a fixed amount per basic block and whenever the program reads it, and jumps ahead to the
+    /// next timeout when all threads are blocked.
+    Virtual {
+        /// The "current virtual time".
+        nanoseconds: Rc<Cell<u64>>,
+        /// How many nanoseconds we pretend are passing for each basic block.
+        tick_per_block: u64,
+        /// How many nanoseconds pass whenever the program reads the clock.
+        tick_per_read: u64,
+    },
+}
+
+impl Clock {
+    /// Create a new clock that uses the host's time, or a virtual clock that advances by the given
+    /// number of nanoseconds per basic block and per read.
+    pub fn new(host: bool, tick_per_block: u64, tick_per_read: u64) -> Self {
+        let kind = if host {
+            ClockKind::Host { time_anchor: StdInstant::now() }
+        } else {
+            ClockKind::Virtual { nanoseconds: Rc::new(0.into()), tick_per_block, tick_per_read }
+        };
+
+        Self { kind }
+    }
+
+    /// Let the time pass for a small interval.
+    pub fn tick(&self) {
+        match &self.kind {
+            ClockKind::Host { .. } => {
+                // Time will pass without us doing anything.
+            }
+            ClockKind::Virtual { nanoseconds, tick_per_block, .. } => {
+                nanoseconds.set(nanoseconds.get().saturating_add(*tick_per_block));
+            }
+        }
+    }
+
+    /// Sleep for the desired duration.
+    pub fn sleep(&self, duration: Duration) {
+        match &self.kind {
+            ClockKind::Host { .. } => std::thread::sleep(duration),
+            ClockKind::Virtual { nanoseconds, .. } => {
+                // Just pretend that we have slept for some time.
+                let duration = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+                nanoseconds.set(nanoseconds.get().saturating_add(duration));
+            }
+        }
+    }
+
+    /// Return the `anchor` instant, to convert between monotone instants and durations.
+    pub fn anchor(&self) -> Instant {
+        match &self.kind {
+            ClockKind::Host { time_anchor } => Instant { kind: InstantKind::Host(*time_anchor) },
+            ClockKind::Virtual { .. } => Instant { kind: InstantKind::Virtual { nanoseconds: 0 } },
+        }
+    }
+
+    pub fn now(&self) -> Instant {
+        match &self.kind {
+            ClockKind::Host { .. } => Instant { kind: InstantKind::Host(StdInstant::now()) },
+            ClockKind::Virtual { nanoseconds, .. } =>
+                Instant { kind: InstantKind::Virtual { nanoseconds: nanoseconds.get() } },
+        }
+    }
+
+    /// Return the current time for the program. A virtual clock advances by its tick per read
+    /// first, so that every read can see a different time.
+    pub fn read(&self) -> Instant {
+        if let ClockKind::Virtual { nanoseconds, tick_per_read, .. } = &self.kind {
+            nanoseconds.set(nanoseconds.get().saturating_add(*tick_per_read));
+        }
+        self.now()
+    }
+}
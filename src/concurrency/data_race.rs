@@ -42,16 +42,20 @@
 
 use std::{
     cell::{Cell, Ref, RefCell, RefMut},
+    collections::VecDeque,
     fmt::Debug,
-    mem,
+    path::Path,
+    rc::Rc,
 };
 
 use rustc_ast::Mutability;
-use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use rustc_data_structures::fx::{FxHashMap, FxHashSet, FxIndexMap};
 use rustc_index::vec::{Idx, IndexVec};
 use rustc_middle::{mir, ty::layout::TyAndLayout};
+use rustc_span::{source_map::SourceMap, Span, SpanData};
 use rustc_target::abi::{Align, Size};
 
+use crate::helpers::escape_dot;
 use crate::*;
 
 use super::{
@@ -214,6 +218,50 @@ impl WriteType {
     }
 }
 
+/// The kind of a memory access, as seen by the data-race detector.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum AccessType {
+    /// Non-atomic read.
+    NaRead,
+
+    /// Non-atomic write, allocation or deallocation.
+    NaWrite(WriteType),
+
+    /// Atomic load.
+    AtomicLoad,
+
+    /// Atomic store.
+    AtomicStore,
+
+    /// Atomic read-modify-write.
+    AtomicRmw,
+}
+impl AccessType {
+    fn get_descriptor(self) -> &'static str {
+        match self {
+            AccessType::NaRead => "Read",
+            AccessType::NaWrite(write_type) => write_type.get_descriptor(),
+            AccessType::AtomicLoad => "Atomic Load",
+            AccessType::AtomicStore => "Atomic Store",
+            AccessType::AtomicRmw => "Atomic RMW",
+        }
+    }
+
+    fn is_atomic(self) -> bool {
+        matches!(self, AccessType::AtomicLoad | AccessType::AtomicStore | AccessType::AtomicRmw)
+    }
+
+    /// Whether the access reads the memory. Read-modify-writes both read and write.
+    fn is_read(self) -> bool {
+        matches!(self, AccessType::NaRead | AccessType::AtomicLoad | AccessType::AtomicRmw)
+    }
+
+    /// Whether the access writes the memory.
+    fn is_write(self) -> bool {
+        !matches!(self, AccessType::NaRead | AccessType::AtomicLoad)
+    }
+}
+
 /// Memory Cell vector clock metadata
 /// for data-race detection.
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -240,6 +288,52 @@ struct MemoryCellClocks {
     /// For non-atomic memory in the common case this
     /// value is set to None.
     atomic_ops: Option<Box<AtomicMemoryCellClocks>>,
+
+    /// Backtraces of the accesses represented by the clocks above,
+    /// only recorded with `-Zmiri-data-race-backtraces`.
+    backtraces: Option<Box<AccessBacktraces>>,
+
+    /// The last accesses to this memory cell, oldest first,
+    /// only recorded with `-Zmiri-data-race-history`.
+    history: Option<Box<VecDeque<AccessRecord>>>,
+}
+
+/// An entry of the access history of a memory cell, for `-Zmiri-data-race-history`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct AccessRecord {
+    /// The thread that performed the access.
+    thread: ThreadId,
+
+    /// The kind of access, including the ordering for atomic accesses.
+    action: Rc<str>,
+
+    /// The value of the location after an atomic access. With weak memory emulation,
+    /// a load might have returned an older value.
+    value: Option<Rc<str>>,
+
+    /// The span of the access, in the local crate if possible.
+    span: SpanData,
+}
+
+/// A backtrace of a memory access, innermost frame first, consisting of the
+/// function name and span of each frame and whether it is in the local crate.
+type AccessBacktrace = Rc<[(String, Span, bool)]>;
+
+/// The backtraces of the last accesses to a memory cell, used to point
+/// at the earlier access when a data race is reported.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+struct AccessBacktraces {
+    /// The backtrace of the last non-atomic write, allocation or deallocation.
+    write: Option<AccessBacktrace>,
+
+    /// The backtrace of the last non-atomic read of each thread since the last write.
+    reads: FxHashMap<VectorIdx, AccessBacktrace>,
+
+    /// The backtrace of the last atomic store of each thread.
+    atomic_writes: FxHashMap<VectorIdx, AccessBacktrace>,
+
+    /// The backtrace of the last atomic load of each thread.
+    atomic_reads: FxHashMap<VectorIdx, AccessBacktrace>,
 }
 
 impl MemoryCellClocks {
@@ -252,6 +346,47 @@ impl MemoryCellClocks {
             write_index: alloc_index,
             write_type: WriteType::Allocate,
             atomic_ops: None,
+            backtraces: None,
+            history: None,
+        }
+    }
+
+    /// Add a successful access to the history, forgetting the oldest
+    /// access if there are more than `limit`.
+    fn record_access(&mut self, record: &AccessRecord, limit: usize) {
+        let history = self.history.get_or_insert_with(Default::default);
+        if history.len() == limit {
+            history.pop_front();
+        }
+        history.push_back(record.clone());
+    }
+
+    /// Remember the backtrace of a successful non-atomic access.
+    fn record_backtrace(&mut self, index: VectorIdx, is_write: bool, backtrace: &AccessBacktrace) {
+        let backtraces = self.backtraces.get_or_insert_with(Default::default);
+        if is_write {
+            // The read clock was reset by the write, so the old reads can no longer race.
+            backtraces.reads.clear();
+            backtraces.write = Some(backtrace.clone());
+        } else {
+            backtraces.reads.insert(index, backtrace.clone());
+        }
+    }
+
+    /// Remember the backtrace of a successful atomic access, RMW operations
+    /// are both an atomic load and an atomic store.
+    fn record_atomic_backtrace(
+        &mut self,
+        index: VectorIdx,
+        access: AccessType,
+        backtrace: &AccessBacktrace,
+    ) {
+        let backtraces = self.backtraces.get_or_insert_with(Default::default);
+        if access.is_read() {
+            backtraces.atomic_reads.insert(index, backtrace.clone());
+        }
+        if access.is_write() {
+            backtraces.atomic_writes.insert(index, backtrace.clone());
         }
     }
 
@@ -276,11 +411,11 @@ impl MemoryCellClocks {
         clocks: &mut ThreadClockSet,
         index: VectorIdx,
     ) -> Result<(), DataRace> {
-        self.atomic_read_detect(clocks, index)?;
+        let result = self.atomic_read_detect(clocks, index);
         if let Some(atomic) = self.atomic() {
             clocks.clock.join(&atomic.sync_vector);
         }
-        Ok(())
+        result
     }
 
     /// Checks if the memory cell access is ordered with all prior atomic reads and writes
@@ -300,26 +435,26 @@ impl MemoryCellClocks {
         clocks: &mut ThreadClockSet,
         index: VectorIdx,
     ) -> Result<(), DataRace> {
-        self.atomic_read_detect(clocks, index)?;
+        let result = self.atomic_read_detect(clocks, index);
         if let Some(atomic) = self.atomic() {
             clocks.fence_acquire.join(&atomic.sync_vector);
         }
-        Ok(())
+        result
     }
 
     /// Update the memory cell data-race tracking for atomic
     /// store release semantics.
     fn store_release(&mut self, clocks: &ThreadClockSet, index: VectorIdx) -> Result<(), DataRace> {
-        self.atomic_write_detect(clocks, index)?;
+        let result = self.atomic_write_detect(clocks, index);
         let atomic = self.atomic_mut();
         atomic.sync_vector.clone_from(&clocks.clock);
-        Ok(())
+        result
     }
 
     /// Update the memory cell data-race tracking for atomic
     /// store relaxed semantics.
     fn store_relaxed(&mut self, clocks: &ThreadClockSet, index: VectorIdx) -> Result<(), DataRace> {
-        self.atomic_write_detect(clocks, index)?;
+        let result = self.atomic_write_detect(clocks, index);
 
         // The handling of release sequences was changed in C++20 and so
         // the code here is different to the paper since now all relaxed
@@ -327,59 +462,55 @@ impl MemoryCellClocks {
         // relaxed stores has been removed.
         let atomic = self.atomic_mut();
         atomic.sync_vector.clone_from(&clocks.fence_release);
-        Ok(())
+        result
     }
 
     /// Update the memory cell data-race tracking for atomic
     /// store release semantics for RMW operations.
     fn rmw_release(&mut self, clocks: &ThreadClockSet, index: VectorIdx) -> Result<(), DataRace> {
-        self.atomic_write_detect(clocks, index)?;
+        let result = self.atomic_write_detect(clocks, index);
         let atomic = self.atomic_mut();
         atomic.sync_vector.join(&clocks.clock);
-        Ok(())
+        result
     }
 
     /// Update the memory cell data-race tracking for atomic
     /// store relaxed semantics for RMW operations.
     fn rmw_relaxed(&mut self, clocks: &ThreadClockSet, index: VectorIdx) -> Result<(), DataRace> {
-        self.atomic_write_detect(clocks, index)?;
+        let result = self.atomic_write_detect(clocks, index);
         let atomic = self.atomic_mut();
         atomic.sync_vector.join(&clocks.fence_release);
-        Ok(())
+        result
     }
 
     /// Detect data-races with an atomic read, caused by a non-atomic write that does
-    /// not happen-before the atomic-read.
+    /// not happen-before the atomic-read. The read is recorded even if it races, so
+    /// that execution can continue when races are only reported.
     fn atomic_read_detect(
         &mut self,
         clocks: &ThreadClockSet,
         index: VectorIdx,
     ) -> Result<(), DataRace> {
         log::trace!("Atomic read with vectors: {:#?} :: {:#?}", self, clocks);
-        if self.write <= clocks.clock[self.write_index] {
-            let atomic = self.atomic_mut();
-            atomic.read_vector.set_at_index(&clocks.clock, index);
-            Ok(())
-        } else {
-            Err(DataRace)
-        }
+        let race_free = self.write <= clocks.clock[self.write_index];
+        let atomic = self.atomic_mut();
+        atomic.read_vector.set_at_index(&clocks.clock, index);
+        if race_free { Ok(()) } else { Err(DataRace) }
     }
 
     /// Detect data-races with an atomic write, either with a non-atomic read or with
-    /// a non-atomic write.
+    /// a non-atomic write. The write is recorded even if it races, so that execution
+    /// can continue when races are only reported.
     fn atomic_write_detect(
         &mut self,
         clocks: &ThreadClockSet,
         index: VectorIdx,
     ) -> Result<(), DataRace> {
         log::trace!("Atomic write with vectors: {:#?} :: {:#?}", self, clocks);
-        if self.write <= clocks.clock[self.write_index] && self.read <= clocks.clock {
-            let atomic = self.atomic_mut();
-            atomic.write_vector.set_at_index(&clocks.clock, index);
-            Ok(())
-        } else {
-            Err(DataRace)
-        }
+        let race_free = self.write <= clocks.clock[self.write_index] && self.read <= clocks.clock;
+        let atomic = self.atomic_mut();
+        atomic.write_vector.set_at_index(&clocks.clock, index);
+        if race_free { Ok(()) } else { Err(DataRace) }
     }
 
     /// Detect races for non-atomic read operations at the current memory cell
@@ -397,7 +528,7 @@ impl MemoryCellClocks {
                 true
             };
             if race_free {
-                self.read.set_at_index(&clocks.clock, index);
+                self.record_read(clocks, index);
                 Ok(())
             } else {
                 Err(DataRace)
@@ -423,10 +554,7 @@ impl MemoryCellClocks {
                 true
             };
             if race_free {
-                self.write = clocks.clock[index];
-                self.write_index = index;
-                self.write_type = write_type;
-                self.read.set_zero_vector();
+                self.record_write(clocks, index, write_type);
                 Ok(())
             } else {
                 Err(DataRace)
@@ -435,6 +563,19 @@ impl MemoryCellClocks {
             Err(DataRace)
         }
     }
+
+    /// Update the memory cell for a non-atomic read, without checking for races.
+    fn record_read(&mut self, clocks: &ThreadClockSet, index: VectorIdx) {
+        self.read.set_at_index(&clocks.clock, index);
+    }
+
+    /// Update the memory cell for a non-atomic write, without checking for races.
+    fn record_write(&mut self, clocks: &ThreadClockSet, index: VectorIdx, write_type: WriteType) {
+        self.write = clocks.clock[index];
+        self.write_index = index;
+        self.write_type = write_type;
+        self.read.set_zero_vector();
+    }
 }
 
 /// Evaluation context extensions.
@@ -648,6 +789,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
     /// Update the data-race detector for an atomic fence on the current thread.
     fn atomic_fence(&mut self, atomic: AtomicFenceOrd) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Fence, true);
         if let Some(data_race) = &mut this.machine.data_race {
             data_race.maybe_perform_sync_operation(&this.machine.threads, |index, mut clocks| {
                 log::trace!("Atomic fence on {:?} with ordering {:?}", index, atomic);
@@ -668,6 +810,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
                     clocks.fence_seqcst.join(&data_race.last_sc_fence.borrow());
                     clocks.write_seqcst.join(&data_race.last_sc_write.borrow());
                 }
+                data_race.record_hb_event(
+                    this.machine.threads.get_active_thread_id(),
+                    index,
+                    &clocks.clock,
+                    || format!("Fence {atomic:?}"),
+                );
 
                 // Increment timestamp in case of release semantics.
                 Ok(atomic != AtomicFenceOrd::Acquire)
@@ -707,7 +855,10 @@ impl VClockAlloc {
         let (alloc_timestamp, alloc_index) = match kind {
             // User allocated and stack memory should track allocation.
             MemoryKind::Machine(
-                MiriMemoryKind::Rust | MiriMemoryKind::C | MiriMemoryKind::WinHeap,
+                MiriMemoryKind::Rust
+                | MiriMemoryKind::C
+                | MiriMemoryKind::WinHeap
+                | MiriMemoryKind::Mmap,
             )
             | MemoryKind::Stack => {
                 let (alloc_index, clocks) = global.current_thread_state(thread_mgr);
@@ -767,37 +918,36 @@ impl VClockAlloc {
     /// This finds the two racing threads and the type
     /// of data-race that occurred. This will also
     /// return info about the memory location the data-race
-    /// occurred in.
+    /// occurred in. `current_clock` is the clock of the
+    /// current thread at the time of the racing access.
+    /// In report-only mode, this returns `Ok` after reporting.
     #[cold]
     #[inline(never)]
     fn report_data_race<'tcx>(
         global: &GlobalState,
         thread_mgr: &ThreadManager<'_, '_>,
+        current_index: VectorIdx,
+        current_clock: &VClock,
         range: &MemoryCellClocks,
-        action: &str,
-        is_atomic: bool,
+        action: AccessType,
         ptr_dbg: Pointer<AllocId>,
     ) -> InterpResult<'tcx> {
-        let (current_index, current_clocks) = global.current_thread_state(thread_mgr);
         let write_clock;
         let (other_action, other_thread, _other_clock) = if range.write
-            > current_clocks.clock[range.write_index]
+            > current_clock[range.write_index]
         {
             // Convert the write action into the vector clock it
             // represents for diagnostic purposes.
             write_clock = VClock::new_with_index(range.write_index, range.write);
-            (range.write_type.get_descriptor(), range.write_index, &write_clock)
-        } else if let Some(idx) = Self::find_gt_index(&range.read, &current_clocks.clock) {
-            ("Read", idx, &range.read)
-        } else if !is_atomic {
+            (AccessType::NaWrite(range.write_type), range.write_index, &write_clock)
+        } else if let Some(idx) = Self::find_gt_index(&range.read, current_clock) {
+            (AccessType::NaRead, idx, &range.read)
+        } else if !action.is_atomic() {
             if let Some(atomic) = range.atomic() {
-                if let Some(idx) = Self::find_gt_index(&atomic.write_vector, &current_clocks.clock)
-                {
-                    ("Atomic Store", idx, &atomic.write_vector)
-                } else if let Some(idx) =
-                    Self::find_gt_index(&atomic.read_vector, &current_clocks.clock)
-                {
-                    ("Atomic Load", idx, &atomic.read_vector)
+                if let Some(idx) = Self::find_gt_index(&atomic.write_vector, current_clock) {
+                    (AccessType::AtomicStore, idx, &atomic.write_vector)
+                } else if let Some(idx) = Self::find_gt_index(&atomic.read_vector, current_clock) {
+                    (AccessType::AtomicLoad, idx, &atomic.read_vector)
                 } else {
                     unreachable!(
                         "Failed to report data-race for non-atomic operation: no race found"
@@ -816,15 +966,138 @@ impl VClockAlloc {
         let current_thread_info = global.print_thread_metadata(thread_mgr, current_index);
         let other_thread_info = global.print_thread_metadata(thread_mgr, other_thread);
 
-        // Throw the data-race detection.
-        throw_ub_format!(
+        // Find the backtrace of the other access, if it was recorded.
+        let other_backtrace = range.backtraces.as_ref().and_then(|backtraces| {
+            match other_action {
+                AccessType::NaRead => backtraces.reads.get(&other_thread),
+                AccessType::NaWrite(_) => backtraces.write.as_ref(),
+                AccessType::AtomicStore => backtraces.atomic_writes.get(&other_thread),
+                AccessType::AtomicLoad => backtraces.atomic_reads.get(&other_thread),
+                AccessType::AtomicRmw => unreachable!("racing accesses are never reported as RMWs"),
+            }
+        });
+
+        let msg = format!(
             "Data race detected between {} on {} and {} on {} at {:?}",
-            action,
+            action.get_descriptor(),
             current_thread_info,
-            other_action,
+            other_action.get_descriptor(),
             other_thread_info,
             ptr_dbg,
-        )
+        );
+        let other_access = format!("{} on {other_thread_info}", other_action.get_descriptor());
+        let other_backtrace: Option<Vec<_>> = other_backtrace.map(|other_backtrace| {
+            other_backtrace
+                .iter()
+                .map(|(instance, span, local)| (instance.clone(), span.data(), *local))
+                .collect()
+        });
+        let history: Vec<_> = range
+            .history
+            .iter()
+            .flat_map(|history| history.iter())
+            .map(|record| {
+                let thread = thread_mgr.describe_thread(record.thread);
+                let mut access = format!("{} on {thread}", record.action);
+                if let Some(value) = &record.value {
+                    access.push_str(&format!(", value {value}"));
+                }
+                (access, record.span)
+            })
+            .collect();
+
+        if global.report_only {
+            // Races in a loop would otherwise be reported in every iteration,
+            // so only show the first race between the same two places.
+            let site = thread_mgr
+                .active_thread_stack()
+                .iter()
+                .rev()
+                .map(|frame| (frame.current_span().data(), frame.instance.def_id().is_local()));
+            let key = (
+                Self::access_site(site),
+                other_backtrace.as_ref().and_then(|other_backtrace| {
+                    Self::access_site(
+                        other_backtrace.iter().map(|(_, span, local)| (*span, *local)),
+                    )
+                }),
+            );
+            let mut reported_races = global.reported_races.borrow_mut();
+            let (_msg, count) = reported_races.entry(key).or_insert_with(|| (msg.clone(), 0));
+            *count += 1;
+            if *count == 1 {
+                register_diagnostic(NonHaltingDiagnostic::DataRace {
+                    msg,
+                    other_access,
+                    other_backtrace: other_backtrace.unwrap_or_default(),
+                    history,
+                });
+            }
+            return Ok(());
+        }
+
+        if other_backtrace.is_some() || !history.is_empty() {
+            throw_machine_stop!(TerminationInfo::DataRace {
+                msg,
+                other_access,
+                other_backtrace: other_backtrace.unwrap_or_default(),
+                history,
+            })
+        }
+
+        // Throw the data-race detection.
+        throw_ub_format!("{msg}")
+    }
+
+    /// The span of an access for deduplicating race reports: the innermost frame
+    /// of the local crate, or the innermost frame if there is none.
+    fn access_site(mut frames: impl Iterator<Item = (SpanData, bool)> + Clone) -> Option<SpanData> {
+        let innermost = frames.clone().next();
+        frames.find(|(_, local)| *local).or(innermost).map(|(span, _)| span)
+    }
+
+    /// Capture the backtrace of the current access, if backtraces are being recorded.
+    fn capture_backtrace(
+        global: &GlobalState,
+        thread_mgr: &ThreadManager<'_, '_>,
+    ) -> Option<AccessBacktrace> {
+        global.record_backtraces.then(|| {
+            thread_mgr
+                .active_thread_stack()
+                .iter()
+                .rev()
+                .map(|frame| {
+                    (
+                        frame.instance.to_string(),
+                        frame.current_span(),
+                        frame.instance.def_id().is_local(),
+                    )
+                })
+                .collect()
+        })
+    }
+
+    /// Describe the current access for the access history, if it is being recorded.
+    fn capture_access_record(
+        global: &GlobalState,
+        thread_mgr: &ThreadManager<'_, '_>,
+        action: impl FnOnce() -> String,
+        value: impl FnOnce() -> Option<String>,
+    ) -> Option<(AccessRecord, usize)> {
+        let limit = global.history_len?;
+        let frames = thread_mgr
+            .active_thread_stack()
+            .iter()
+            .rev()
+            .map(|frame| (frame.current_span().data(), frame.instance.def_id().is_local()));
+        let span = Self::access_site(frames)?;
+        let record = AccessRecord {
+            thread: thread_mgr.get_active_thread_id(),
+            action: action().into(),
+            value: value().map(Into::into),
+            span,
+        };
+        Some((record, limit))
     }
 
     /// Detect racing atomic read and writes (not data races)
@@ -861,18 +1134,34 @@ impl VClockAlloc {
     ) -> InterpResult<'tcx> {
         if global.race_detecting() {
             let (index, clocks) = global.current_thread_state(thread_mgr);
+            let backtrace = Self::capture_backtrace(global, thread_mgr);
+            let record =
+                Self::capture_access_record(global, thread_mgr, || "Read".to_owned(), || None);
             let mut alloc_ranges = self.alloc_ranges.borrow_mut();
+            let mut race_reported = false;
             for (offset, range) in alloc_ranges.iter_mut(range.start, range.size) {
                 if let Err(DataRace) = range.read_race_detect(&clocks, index) {
                     // Report data-race.
-                    return Self::report_data_race(
-                        global,
-                        thread_mgr,
-                        range,
-                        "Read",
-                        false,
-                        Pointer::new(alloc_id, offset),
-                    );
+                    if !race_reported {
+                        Self::report_data_race(
+                            global,
+                            thread_mgr,
+                            index,
+                            &clocks.clock,
+                            range,
+                            AccessType::NaRead,
+                            Pointer::new(alloc_id, offset),
+                        )?;
+                        race_reported = true;
+                    }
+                    // Races are only reported, so perform the read anyway.
+                    range.record_read(&clocks, index);
+                }
+                if let Some(backtrace) = &backtrace {
+                    range.record_backtrace(index, false, backtrace);
+                }
+                if let Some((record, limit)) = &record {
+                    range.record_access(record, *limit);
                 }
             }
             Ok(())
@@ -892,17 +1181,37 @@ impl VClockAlloc {
     ) -> InterpResult<'tcx> {
         if global.race_detecting() {
             let (index, clocks) = global.current_thread_state(thread_mgr);
+            let backtrace = Self::capture_backtrace(global, thread_mgr);
+            let record = Self::capture_access_record(
+                global,
+                thread_mgr,
+                || write_type.get_descriptor().to_owned(),
+                || None,
+            );
+            let mut race_reported = false;
             for (offset, range) in self.alloc_ranges.get_mut().iter_mut(range.start, range.size) {
                 if let Err(DataRace) = range.write_race_detect(&clocks, index, write_type) {
                     // Report data-race
-                    return Self::report_data_race(
-                        global,
-                        thread_mgr,
-                        range,
-                        write_type.get_descriptor(),
-                        false,
-                        Pointer::new(alloc_id, offset),
-                    );
+                    if !race_reported {
+                        Self::report_data_race(
+                            global,
+                            thread_mgr,
+                            index,
+                            &clocks.clock,
+                            range,
+                            AccessType::NaWrite(write_type),
+                            Pointer::new(alloc_id, offset),
+                        )?;
+                        race_reported = true;
+                    }
+                    // Races are only reported, so perform the write anyway.
+                    range.record_write(&clocks, index, write_type);
+                }
+                if let Some(backtrace) = &backtrace {
+                    range.record_backtrace(index, true, backtrace);
+                }
+                if let Some((record, limit)) = &record {
+                    range.record_access(record, *limit);
                 }
             }
             Ok(())
@@ -1031,7 +1340,7 @@ trait EvalContextPrivExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
         this.validate_atomic_op(
             place,
             atomic,
-            "Atomic Load",
+            AccessType::AtomicLoad,
             move |memory, clocks, index, atomic| {
                 if atomic == AtomicReadOrd::Relaxed {
                     memory.load_relaxed(&mut *clocks, index)
@@ -1054,7 +1363,7 @@ trait EvalContextPrivExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
         this.validate_atomic_op(
             place,
             atomic,
-            "Atomic Store",
+            AccessType::AtomicStore,
             move |memory, clocks, index, atomic| {
                 if atomic == AtomicWriteOrd::Relaxed {
                     memory.store_relaxed(clocks, index)
@@ -1077,18 +1386,24 @@ trait EvalContextPrivExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
         let release = matches!(atomic, Release | AcqRel | SeqCst);
         let this = self.eval_context_mut();
         this.validate_overlapping_atomic(place)?;
-        this.validate_atomic_op(place, atomic, "Atomic RMW", move |memory, clocks, index, _| {
-            if acquire {
-                memory.load_acquire(clocks, index)?;
-            } else {
-                memory.load_relaxed(clocks, index)?;
-            }
-            if release {
-                memory.rmw_release(clocks, index)
-            } else {
-                memory.rmw_relaxed(clocks, index)
-            }
-        })
+        this.validate_atomic_op(
+            place,
+            atomic,
+            AccessType::AtomicRmw,
+            move |memory, clocks, index, _| {
+                let load = if acquire {
+                    memory.load_acquire(clocks, index)
+                } else {
+                    memory.load_relaxed(clocks, index)
+                };
+                let store = if release {
+                    memory.rmw_release(clocks, index)
+                } else {
+                    memory.rmw_relaxed(clocks, index)
+                };
+                load.and(store)
+            },
+        )
     }
 
     /// Generic atomic operation implementation
@@ -1096,7 +1411,7 @@ trait EvalContextPrivExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
         &self,
         place: &MPlaceTy<'tcx, Provenance>,
         atomic: A,
-        description: &str,
+        access: AccessType,
         mut op: impl FnMut(
             &mut MemoryCellClocks,
             &mut ThreadClockSet,
@@ -1105,41 +1420,80 @@ trait EvalContextPrivExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
         ) -> Result<(), DataRace>,
     ) -> InterpResult<'tcx> {
         let this = self.eval_context_ref();
+        let (alloc_id, base_offset, _prov) = this.ptr_get_alloc_id(place.ptr)?;
+        let write = access.is_write();
+        this.dpor_visible_op(SyncObject::Memory(alloc_id, base_offset), write);
         if let Some(data_race) = &this.machine.data_race {
             if data_race.race_detecting() {
                 let size = place.layout.size;
-                let (alloc_id, base_offset, _prov) = this.ptr_get_alloc_id(place.ptr)?;
                 // Load and log the atomic operation.
                 // Note that atomic loads are possible even from read-only allocations, so `get_alloc_extra_mut` is not an option.
                 let alloc_meta = this.get_alloc_extra(alloc_id)?.data_race.as_ref().unwrap();
                 log::trace!(
                     "Atomic op({}) with ordering {:?} on {:?} (size={})",
-                    description,
+                    access.get_descriptor(),
                     &atomic,
                     place.ptr,
                     size.bytes()
                 );
 
+                let backtrace = VClockAlloc::capture_backtrace(data_race, &this.machine.threads);
+                let record = VClockAlloc::capture_access_record(
+                    data_race,
+                    &this.machine.threads,
+                    || format!("{} ({atomic:?})", access.get_descriptor()),
+                    || {
+                        // The memory has already been updated by the operation.
+                        let value =
+                            this.allow_data_races_ref(|this| this.read_scalar(&place.into()));
+                        value.ok().map(|value| value.to_string())
+                    },
+                );
+
                 // Perform the atomic operation.
                 data_race.maybe_perform_sync_operation(
                     &this.machine.threads,
                     |index, mut clocks| {
+                        let mut race_reported = false;
                         for (offset, range) in
                             alloc_meta.alloc_ranges.borrow_mut().iter_mut(base_offset, size)
                         {
+                            // The operation is performed even if it races, so remember the
+                            // clock the race has to be reported with.
+                            let clock_before = clocks.clock.clone();
                             if let Err(DataRace) = op(range, &mut clocks, index, atomic) {
-                                mem::drop(clocks);
-                                return VClockAlloc::report_data_race(
-                                    data_race,
-                                    &this.machine.threads,
-                                    range,
-                                    description,
-                                    true,
-                                    Pointer::new(alloc_id, offset),
-                                )
-                                .map(|_| true);
+                                if !race_reported {
+                                    VClockAlloc::report_data_race(
+                                        data_race,
+                                        &this.machine.threads,
+                                        index,
+                                        &clock_before,
+                                        range,
+                                        access,
+                                        Pointer::new(alloc_id, offset),
+                                    )?;
+                                    race_reported = true;
+                                }
+                            }
+                            if let Some(backtrace) = &backtrace {
+                                range.record_atomic_backtrace(index, access, backtrace);
+                            }
+                            if let Some((record, limit)) = &record {
+                                range.record_access(record, *limit);
                             }
                         }
+                        data_race.record_hb_event(
+                            this.machine.threads.get_active_thread_id(),
+                            index,
+                            &clocks.clock,
+                            || {
+                                format!(
+                                    "{} {atomic:?} at {:?}",
+                                    access.get_descriptor(),
+                                    Pointer::new(alloc_id, base_offset)
+                                )
+                            },
+                        );
 
                         // This conservatively assumes all operations have release semantics
                         Ok(true)
@@ -1181,6 +1535,28 @@ struct ThreadExtraState {
     termination_vector_clock: Option<VClock>,
 }
 
+/// For `-Zmiri-export-hb`: an event of a thread that takes part in synchronization.
+#[derive(Debug, Clone)]
+struct HbEvent {
+    /// The thread performing the event.
+    thread: ThreadId,
+
+    /// The vector index of the thread.
+    index: VectorIdx,
+
+    /// The vector clock of the thread at the event, before it is
+    /// incremented for release semantics.
+    clock: VClock,
+
+    /// A description of the event.
+    label: String,
+
+    /// The event of another thread that this event synchronizes with, if that cannot be
+    /// derived from the vector clocks. This is the case when spawning a thread before the
+    /// spawning thread advanced its clock.
+    source: Option<usize>,
+}
+
 /// Global data-race detection state, contains the currently
 /// executing thread as well as the vector-clocks associated
 /// with each of the threads.
@@ -1237,6 +1613,24 @@ pub struct GlobalState {
 
     /// Track when an outdated (weak memory) load happens.
     pub track_outdated_loads: bool,
+
+    /// Record the backtrace of every access, to show both sides of a data race.
+    record_backtraces: bool,
+
+    /// For `-Zmiri-data-race-history`: how many of the last accesses to each
+    /// location are remembered to be shown when a data race is reported.
+    history_len: Option<usize>,
+
+    /// Report data races as warnings and continue execution.
+    report_only: bool,
+
+    /// For `-Zmiri-race-report-only`: the races that were found, keyed by the
+    /// span of both accesses, with the message of the first occurrence and
+    /// how often they occurred.
+    reported_races: RefCell<FxIndexMap<(Option<SpanData>, Option<SpanData>), (String, u64)>>,
+
+    /// For `-Zmiri-export-hb`: all synchronization events in the order they happened.
+    hb_events: Option<RefCell<Vec<HbEvent>>>,
 }
 
 impl GlobalState {
@@ -1254,6 +1648,12 @@ impl GlobalState {
             last_sc_fence: RefCell::new(VClock::default()),
             last_sc_write: RefCell::new(VClock::default()),
             track_outdated_loads: config.track_outdated_loads,
+            // Race reports are deduplicated by the span of both accesses, so we need the backtraces.
+            record_backtraces: config.data_race_backtraces || config.race_report_only,
+            history_len: config.data_race_history,
+            report_only: config.race_report_only,
+            reported_races: RefCell::new(FxIndexMap::default()),
+            hb_events: config.export_hb.is_some().then(|| RefCell::new(Vec::new())),
         };
 
         // Setup the main-thread since it is not explicitly created:
@@ -1268,6 +1668,142 @@ impl GlobalState {
         global_state
     }
 
+    /// For `-Zmiri-race-report-only`: list all data races that were found, with the place
+    /// of both accesses and how often they occurred.
+    pub fn print_race_report(&self, source_map: &SourceMap) {
+        let reported_races = self.reported_races.borrow();
+        if reported_races.is_empty() {
+            return;
+        }
+        eprintln!("Data races found during this run:");
+        let span_str = |span: &Option<SpanData>| {
+            span.map_or_else(
+                || "<unknown location>".to_string(),
+                |span| source_map.span_to_diagnostic_string(span.span()),
+            )
+        };
+        for ((site, other_site), (msg, count)) in reported_races.iter() {
+            let times = if *count == 1 { "once".to_string() } else { format!("{count} times") };
+            eprintln!("  {} with {}: {times}: {msg}", span_str(site), span_str(other_site));
+        }
+    }
+
+    /// For `-Zmiri-export-hb`: write the happens-before graph of the recorded events to `out`
+    /// as a Graphviz graph. Every thread is a cluster of its events in program order, and
+    /// synchronization is shown as edges between threads, derived from the vector clocks and
+    /// the sources recorded for thread spawns and joins.
+    pub fn write_hb_graph(
+        &self,
+        out: &Path,
+        thread_mgr: &ThreadManager<'_, '_>,
+    ) -> std::io::Result<()> {
+        let Some(hb_events) = &self.hb_events else { return Ok(()) };
+        let events = hb_events.borrow();
+        let mut dot = String::new();
+
+        // The events of every thread, and of every vector index, in the order they happened.
+        let mut thread_events: FxIndexMap<ThreadId, Vec<usize>> = FxIndexMap::default();
+        let mut index_events: FxHashMap<VectorIdx, Vec<usize>> = FxHashMap::default();
+        for (i, event) in events.iter().enumerate() {
+            thread_events.entry(event.thread).or_default().push(i);
+            index_events.entry(event.index).or_default().push(i);
+        }
+        thread_events.sort_keys();
+
+        // The previous event of the same thread, if any.
+        let mut prev_events = vec![None; events.len()];
+        for (&thread, events_of_thread) in &thread_events {
+            let name = String::from_utf8_lossy(thread_mgr.get_thread_name(thread));
+            dot.push_str(&format!("  subgraph \"cluster_{}\" {{\n", thread.to_u32()));
+            dot.push_str(&format!(
+                "    label=\"{}\";\n",
+                escape_dot(&format!("thread `{name}` ({thread:?})"))
+            ));
+            let mut prev = None;
+            for &i in events_of_thread {
+                let event = &events[i];
+                dot.push_str(&format!(
+                    "    e{i} [label=\"{}\\n{:?}\"];\n",
+                    escape_dot(&event.label),
+                    event.clock.as_slice()
+                ));
+                if let Some(prev) = prev {
+                    dot.push_str(&format!("    e{prev} -> e{i};\n"));
+                }
+                prev_events[i] = prev;
+                prev = Some(i);
+            }
+            dot.push_str("  }\n");
+        }
+
+        for (i, event) in events.iter().enumerate() {
+            let prev = prev_events[i].map(|prev| &events[prev]);
+            // For every other vector index this event learned about, the latest event of
+            // that index that it now knows about is a synchronization source.
+            let mut sources: Vec<usize> = Vec::new();
+            for idx in (0..event.clock.as_slice().len()).map(VectorIdx::new) {
+                let known = prev.map_or(0, |prev| prev.clock[idx]);
+                if idx == event.index || event.clock[idx] <= known {
+                    continue;
+                }
+                let Some(candidates) = index_events.get(&idx) else { continue };
+                // The timestamps of the events of a vector index only grow, even when the index
+                // is reused by another thread, so the candidates are found by binary search.
+                let earlier = &candidates[..candidates.partition_point(|&source| source < i)];
+                let known_by_event = earlier
+                    .partition_point(|&source| events[source].clock[idx] <= event.clock[idx]);
+                let source = earlier[..known_by_event]
+                    .iter()
+                    .rev()
+                    .copied()
+                    .find(|&source| events[source].thread != event.thread);
+                if let Some(source) = source.filter(|&source| events[source].clock[idx] > known) {
+                    sources.push(source);
+                }
+            }
+            if let Some(source) = event.source {
+                if !sources.contains(&source) {
+                    sources.push(source);
+                }
+            }
+            // Leave out sources that are already implied by another source. A source at
+            // timestamp 0 cannot be told apart from no knowledge at all, so it is always kept.
+            for &source in &sources {
+                let source_event = &events[source];
+                let timestamp = source_event.clock[source_event.index];
+                let implied = sources.iter().any(|&other| {
+                    other != source
+                        && timestamp > 0
+                        && events[other].clock[source_event.index] >= timestamp
+                });
+                if !implied {
+                    dot.push_str(&format!("  e{source} -> e{i} [color=blue];\n"));
+                }
+            }
+        }
+
+        std::fs::write(out, format!("digraph happens_before {{\n{dot}}}\n"))
+    }
+
+    /// For `-Zmiri-export-hb`: record an event of the given thread at the given clock.
+    fn record_hb_event(
+        &self,
+        thread: ThreadId,
+        index: VectorIdx,
+        clock: &VClock,
+        label: impl FnOnce() -> String,
+    ) {
+        if let Some(hb_events) = &self.hb_events {
+            hb_events.borrow_mut().push(HbEvent {
+                thread,
+                index,
+                clock: clock.clone(),
+                label: label(),
+                source: None,
+            });
+        }
+    }
+
     // We perform data race detection when there are more than 1 active thread
     // and we have not temporarily disabled race detection to perform something
     // data race free
@@ -1372,6 +1908,25 @@ impl GlobalState {
         // previous actions happen-before the created thread.
         created.join_with(current);
 
+        if let Some(hb_events) = &self.hb_events {
+            let mut hb_events = hb_events.borrow_mut();
+            let spawn = hb_events.len();
+            hb_events.push(HbEvent {
+                thread: thread_mgr.get_active_thread_id(),
+                index: current_index,
+                clock: current.clock.clone(),
+                label: format!("Spawn {thread:?}"),
+                source: None,
+            });
+            hb_events.push(HbEvent {
+                thread,
+                index: created_index,
+                clock: created.clock.clone(),
+                label: "Thread start".to_string(),
+                source: Some(spawn),
+            });
+        }
+
         // Advance both threads after the synchronized operation.
         // Both operations are considered to have release semantics.
         current.increment_clock(current_index);
@@ -1408,6 +1963,18 @@ impl GlobalState {
         // Is not a release operation so the clock is not incremented.
         current.clock.join(join_clock);
 
+        if let Some(hb_events) = &self.hb_events {
+            let mut hb_events = hb_events.borrow_mut();
+            let end = hb_events.iter().rposition(|event| event.thread == joinee);
+            hb_events.push(HbEvent {
+                thread: joiner,
+                index: current_index,
+                clock: current.clock.clone(),
+                label: format!("Join {joinee:?}"),
+                source: end,
+            });
+        }
+
         // Check the number of live threads, if the value is 1
         // then test for potentially disabling multi-threaded execution.
         if thread_mgr.get_live_thread_count() == 1 {
@@ -1460,6 +2027,16 @@ impl GlobalState {
         let current = &mut thread_info[current_thread];
         current.termination_vector_clock = Some(current_clocks.clock.clone());
 
+        if let Some(hb_events) = &self.hb_events {
+            hb_events.borrow_mut().push(HbEvent {
+                thread: current_thread,
+                index: current_index,
+                clock: current_clocks.clock.clone(),
+                label: "Thread end".to_string(),
+                source: None,
+            });
+        }
+
         // Add this thread as a candidate for re-use after a thread join
         // occurs.
         let termination = self.terminated_threads.get_mut();
@@ -1496,8 +2073,7 @@ impl GlobalState {
         vector: VectorIdx,
     ) -> String {
         let thread = self.vector_info.borrow()[vector];
-        let thread_name = thread_mgr.get_thread_name(thread);
-        format!("thread `{}`", String::from_utf8_lossy(thread_name))
+        thread_mgr.describe_thread(thread)
     }
 
     /// Acquire a lock, express that the previous call of
@@ -1505,8 +2081,9 @@ impl GlobalState {
     /// As this is an acquire operation, the thread timestamp is not
     /// incremented.
     pub fn validate_lock_acquire(&self, lock: &VClock, thread: ThreadId) {
-        let (_, mut clocks) = self.load_thread_state_mut(thread);
+        let (index, mut clocks) = self.load_thread_state_mut(thread);
         clocks.clock.join(lock);
+        self.record_hb_event(thread, index, &clocks.clock, || "Lock acquire".to_string());
     }
 
     /// Release a lock handle, express that this happens-before
@@ -1518,6 +2095,7 @@ impl GlobalState {
     pub fn validate_lock_release(&self, lock: &mut VClock, thread: ThreadId) {
         let (index, mut clocks) = self.load_thread_state_mut(thread);
         lock.clone_from(&clocks.clock);
+        self.record_hb_event(thread, index, &clocks.clock, || "Lock release".to_string());
         clocks.increment_clock(index);
     }
 
@@ -1531,6 +2109,7 @@ impl GlobalState {
     pub fn validate_lock_release_shared(&self, lock: &mut VClock, thread: ThreadId) {
         let (index, mut clocks) = self.load_thread_state_mut(thread);
         lock.join(&clocks.clock);
+        self.record_hb_event(thread, index, &clocks.clock, || "Shared lock release".to_string());
         clocks.increment_clock(index);
     }
 
@@ -0,0 +1,268 @@
+//! Systematic exploration of thread schedules with dynamic partial-order reduction (DPOR),
+//! based on "Dynamic Partial-Order Reduction for Model Checking Software" (Flanagan and
+//! Godefroid, POPL 2005): <https://dl.acm.org/doi/10.1145/1040305.1040315>
+//!
+//! With `-Zmiri-dpor`, the program is executed repeatedly. Instead of preempting threads at random,
+//! every atomic access, fence and operation on a synchronization primitive ends the scheduling step
+//! of the active thread, and the scheduler follows a [`Schedule`] that records which thread was
+//! picked at each scheduling point. After an execution, every pair of conflicting operations
+//! (operations of different threads on the same object, at least one of which writes it) adds the
+//! later thread to the backtracking set of the scheduling point before the earlier operation, and
+//! the next execution replays the schedule up to the deepest point that still has a thread left to
+//! explore. Exploration stops when there are no such points left, when an execution fails, or
+//! after `-Zmiri-dpor-max-executions` executions.
+//!
+//! We do not track the happens-before relation between the conflicting operations, so more
+//! backtracking points than necessary are explored. The choices of the weak memory emulation are
+//! not explored either, they are made by the random number generator like in a normal execution.
+//!
+//! A schedule can be replayed with `-Zmiri-schedule=<thread ids>`, which lists the thread picked at
+//! each scheduling point; this is printed when an execution fails.
+
+use rand::rngs::StdRng;
+
+use rustc_data_structures::fx::FxHashSet;
+use rustc_session::Session;
+use rustc_target::abi::Size;
+
+use crate::*;
+
+/// An object that atomic and synchronization operations act on.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SyncObject {
+    /// An atomic access at the given offset of an allocation.
+    Memory(AllocId, Size),
+    /// Fences are only ordered with other fences.
+    Fence,
+    Mutex(MutexId),
+    RwLock(RwLockId),
+    Condvar(CondvarId),
+    Futex(u64),
+    Semaphore(SemaphoreId),
+    Event(EventId),
+}
+
+/// A scheduling decision of an execution.
+#[derive(Debug)]
+struct SchedulingPoint {
+    /// The threads that were enabled at this point.
+    enabled: Vec<ThreadId>,
+    /// The thread that was picked.
+    chosen: ThreadId,
+    /// The operations of the chosen thread until the next scheduling point,
+    /// and whether they write the object.
+    ops: Vec<(SyncObject, bool)>,
+    /// The threads that have to be explored at this point.
+    backtrack: FxHashSet<ThreadId>,
+    /// The threads that have already been explored at this point.
+    done: FxHashSet<ThreadId>,
+}
+
+impl SchedulingPoint {
+    fn new(chosen: ThreadId) -> Self {
+        SchedulingPoint {
+            enabled: Vec::new(),
+            chosen,
+            ops: Vec::new(),
+            backtrack: FxHashSet::default(),
+            done: FxHashSet::default(),
+        }
+    }
+}
+
+/// The schedule of the current execution, and the exploration state of all executions so far.
+#[derive(Debug, Default)]
+pub struct Schedule {
+    /// The scheduling points of the current execution. Beyond `position`, these are the
+    /// choices of the previous execution that are being replayed.
+    points: Vec<SchedulingPoint>,
+    /// The number of scheduling points reached in the current execution.
+    position: usize,
+    /// Whether the active thread performed an operation that ends its scheduling step.
+    step_done: bool,
+    /// The number of executions that were started.
+    executions: u64,
+    /// The maximal number of executions of the exploration, or `None` if the schedule is only
+    /// replayed once.
+    max_executions: Option<u64>,
+}
+
+impl Schedule {
+    /// The schedule of the first execution of an exploration of at most `max_executions`
+    /// executions.
+    pub fn new(max_executions: u64) -> Self {
+        Schedule { executions: 1, max_executions: Some(max_executions), ..Default::default() }
+    }
+
+    /// A schedule that replays the given choices, e.g. from `-Zmiri-schedule`.
+    pub fn replay(choices: &[u32]) -> Self {
+        Schedule {
+            points: choices
+                .iter()
+                .map(|&thread| SchedulingPoint::new(ThreadId::new(thread as usize)))
+                .collect(),
+            executions: 1,
+            ..Default::default()
+        }
+    }
+
+    /// The number of executions that were started.
+    pub fn executions(&self) -> u64 {
+        self.executions
+    }
+
+    /// The choices made in the current execution, in the format of `-Zmiri-schedule`.
+    pub fn choices(&self) -> String {
+        self.points[..self.position]
+            .iter()
+            .map(|point| point.chosen.to_u32().to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Compute the backtracking points of the finished execution and prepare the schedule of the
+    /// next one. Returns `false` if all schedules have been explored.
+    fn next_execution(&mut self) -> bool {
+        self.points.truncate(self.position);
+        for later in 0..self.points.len() {
+            let thread = self.points[later].chosen;
+            for op_idx in 0..self.points[later].ops.len() {
+                let (object, write) = self.points[later].ops[op_idx];
+                // The last operation of another thread that conflicts with this one.
+                let earlier = (0..later).rev().find(|&earlier| {
+                    let point = &self.points[earlier];
+                    point.chosen != thread
+                        && point.ops.iter().any(|&(other, other_write)| {
+                            other == object && (write || other_write)
+                        })
+                });
+                if let Some(earlier) = earlier {
+                    // Try to run this thread before the earlier operation. If it is not enabled
+                    // at that point, we do not know which thread leads to it, so try all of them.
+                    let point = &mut self.points[earlier];
+                    if point.enabled.contains(&thread) {
+                        point.backtrack.insert(thread);
+                    } else {
+                        point.backtrack.extend(point.enabled.iter().copied());
+                    }
+                }
+            }
+        }
+
+        // Continue with the deepest scheduling point that has a thread left to explore.
+        while let Some(point) = self.points.last_mut() {
+            point.done.insert(point.chosen);
+            let next = point.backtrack.iter().copied().filter(|t| !point.done.contains(t)).min();
+            if let Some(thread) = next {
+                point.chosen = thread;
+                self.position = 0;
+                self.step_done = false;
+                self.executions += 1;
+                return true;
+            }
+            self.points.pop();
+        }
+        false
+    }
+}
+
+impl<'tcx> SchedulingBackend<'tcx> for Schedule {
+    fn next_thread(
+        &mut self,
+        threads: &ThreadManager<'_, 'tcx>,
+        explicit_yield: bool,
+        _rng: &mut StdRng,
+    ) -> InterpResult<'tcx, Option<ThreadId>> {
+        let active = threads.get_active_thread_id();
+        let enabled = threads.enabled_threads();
+        if enabled.is_empty() {
+            return Ok(None);
+        }
+        let position = self.position;
+        self.position += 1;
+        if let Some(point) = self.points.get_mut(position) {
+            // Replay the choice of an earlier execution.
+            if !enabled.contains(&point.chosen) {
+                throw_unsup_format!(
+                    "the schedule cannot be replayed: {:?} is not enabled at scheduling point {}",
+                    point.chosen,
+                    position,
+                );
+            }
+            point.enabled = enabled;
+            point.ops.clear();
+            Ok(Some(point.chosen))
+        } else {
+            // Keep running the active thread if possible, to explore the schedules
+            // with few context switches first. If it yielded, we move on to the next
+            // thread like the normal scheduler does, so that spin loops make progress.
+            let chosen = if !explicit_yield && enabled.contains(&active) {
+                active
+            } else {
+                enabled.iter().copied().find(|&thread| thread > active).unwrap_or(enabled[0])
+            };
+            let mut point = SchedulingPoint::new(chosen);
+            point.enabled = enabled;
+            self.points.push(point);
+            Ok(Some(chosen))
+        }
+    }
+
+    fn visible_op(&mut self, object: SyncObject, write: bool) {
+        // Operations before the first scheduling point cannot race with anything.
+        if let Some(point) = self.position.checked_sub(1).map(|idx| &mut self.points[idx]) {
+            point.ops.push((object, write));
+        }
+        self.step_done = true;
+    }
+
+    fn step_done(&mut self) -> bool {
+        std::mem::replace(&mut self.step_done, false)
+    }
+
+    fn allows_preemption(&self) -> bool {
+        // Threads are only switched at synchronization operations.
+        false
+    }
+
+    fn execution_end(&mut self, sess: &Session, failed: bool) -> bool {
+        let Some(max_executions) = self.max_executions else {
+            // A replayed schedule is only executed once.
+            return false;
+        };
+        if failed {
+            sess.note_without_error(format!(
+                "execution {} of the schedule exploration failed; pass `-Zmiri-schedule={}` to replay it",
+                self.executions,
+                self.choices(),
+            ));
+            return false;
+        }
+        if !self.next_execution() {
+            sess.note_without_error(format!(
+                "the schedule exploration finished after {} executions",
+                self.executions,
+            ));
+            return false;
+        }
+        if self.executions > max_executions {
+            sess.warn(format!(
+                "stopped the schedule exploration after {max_executions} executions; not all schedules were explored",
+            ));
+            return false;
+        }
+        true
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Tell the scheduling backend about an atomic or synchronization operation of the active
+    /// thread on `object`; with `-Zmiri-dpor`, this ends the scheduling step of the thread. This
+    /// is also where livelock detection learns about the operation.
+    fn dpor_visible_op(&self, object: SyncObject, write: bool) {
+        let this = self.eval_context_ref();
+        this.machine.threads.record_sync_op(write);
+        this.machine.scheduler.borrow_mut().visible_op(object, write);
+    }
+}
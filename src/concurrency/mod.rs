@@ -1,6 +1,8 @@
 pub mod data_race;
+pub mod dpor;
 mod range_object_map;
+pub mod scheduler;
 pub mod sync;
 pub mod thread;
-mod vector_clock;
+pub mod vector_clock;
 pub mod weak_memory;
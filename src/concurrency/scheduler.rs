@@ -0,0 +1,154 @@
+//! The interface between the interpreter and whatever drives the exploration of thread
+//! interleavings.
+//!
+//! A [`SchedulingBackend`] picks the thread that runs at every scheduling point, learns about the
+//! operations of the active thread that other threads can observe (atomic accesses, fences and
+//! operations on synchronization primitives), and decides at the end of an execution whether the
+//! program is executed again. The default backend is the [`PolicyScheduler`], which runs the
+//! program once and picks threads according to `-Zmiri-scheduler`, with random preemption. The
+//! schedule exploration of `-Zmiri-dpor` is another backend, see [`Schedule`]. External model
+//! checkers can drive the exploration by passing their own backend to
+//! [`eval_entry_with_backend`](crate::eval_entry_with_backend).
+
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use rustc_data_structures::fx::FxHashMap;
+use rustc_session::Session;
+
+use crate::*;
+
+/// Decides which thread runs next and how often the program is executed.
+pub trait SchedulingBackend<'tcx>: fmt::Debug {
+    /// Pick the thread that runs next at a scheduling point, which is reached when the active
+    /// thread blocked, terminated, yielded, was preempted or ended its step (see `step_done`).
+    /// Returns `None` if there is no thread to run, in which case the active thread keeps running
+    /// if it is still enabled.
+    fn next_thread(
+        &mut self,
+        threads: &ThreadManager<'_, 'tcx>,
+        explicit_yield: bool,
+        rng: &mut StdRng,
+    ) -> InterpResult<'tcx, Option<ThreadId>>;
+
+    /// The active thread performed an operation on `object` that other threads can observe.
+    fn visible_op(&mut self, _object: SyncObject, _write: bool) {}
+
+    /// Whether the active thread has to stop at the end of its current step because of the
+    /// operations it performed, which makes the next step a scheduling point.
+    fn step_done(&mut self) -> bool {
+        false
+    }
+
+    /// Whether the active thread may be preempted at random with `-Zmiri-preemption-rate`.
+    fn allows_preemption(&self) -> bool {
+        true
+    }
+
+    /// Called at the end of every execution; `failed` says whether it ended with an error.
+    /// Returns `true` if the program should be executed again.
+    fn execution_end(&mut self, _sess: &Session, _failed: bool) -> bool {
+        false
+    }
+}
+
+/// How the scheduler picks the next thread when the active thread blocks or yields.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchedulerPolicy {
+    /// Run the enabled threads in turn, starting just after the active thread.
+    RoundRobin,
+    /// Run the thread that has been ready to run for the longest time. Threads are never
+    /// preempted, they only switch when they block or yield.
+    Fifo,
+    /// Pick one of the other enabled threads at random.
+    Random,
+    /// Pick the enabled thread with the highest priority, as set by `miri_set_thread_priority`.
+    /// Threads with the same priority run in turn.
+    Priority,
+    /// Like `RoundRobin`, but a thread that yields is kept from running again for a random
+    /// number of scheduling decisions, as long as other threads can run instead.
+    Delay,
+}
+
+/// With `SchedulerPolicy::Delay`, the maximal number of scheduling decisions that a yielding
+/// thread is delayed for.
+const MAX_YIELD_DELAY: u32 = 8;
+
+/// The default backend: runs the active thread as long as it can, like stateless model checkers
+/// such as Loom do, and switches only when it has to (the active thread was blocked, terminated,
+/// or has explicitly asked to be preempted). The `SchedulerPolicy` decides which thread runs next.
+#[derive(Debug)]
+pub struct PolicyScheduler {
+    policy: SchedulerPolicy,
+    /// For `SchedulerPolicy::Delay`: for how many more scheduling decisions each thread is not
+    /// picked.
+    delays: FxHashMap<ThreadId, u32>,
+}
+
+impl PolicyScheduler {
+    pub fn new(policy: SchedulerPolicy) -> Self {
+        PolicyScheduler { policy, delays: FxHashMap::default() }
+    }
+}
+
+impl<'tcx> SchedulingBackend<'tcx> for PolicyScheduler {
+    fn next_thread(
+        &mut self,
+        threads: &ThreadManager<'_, 'tcx>,
+        _explicit_yield: bool,
+        rng: &mut StdRng,
+    ) -> InterpResult<'tcx, Option<ThreadId>> {
+        let active = threads.get_active_thread_id();
+        let enabled = threads.enabled_threads();
+        // Crucially, we start searching at the current active thread ID, rather than at 0, since we
+        // want to avoid always scheduling threads 0 and 1 without ever making progress in thread 2.
+        let candidates: Vec<ThreadId> = enabled
+            .iter()
+            .copied()
+            .filter(|&id| id > active)
+            .chain(enabled.iter().copied().filter(|&id| id < active))
+            .collect();
+        let next = match self.policy {
+            SchedulerPolicy::RoundRobin => candidates.first().copied(),
+            SchedulerPolicy::Fifo =>
+                candidates.iter().copied().min_by_key(|&id| threads.ready_since(id)),
+            SchedulerPolicy::Random => candidates.choose(rng).copied(),
+            SchedulerPolicy::Priority => {
+                // `max_by_key` returns the last maximum, but we want the first one.
+                candidates
+                    .iter()
+                    .copied()
+                    .rev()
+                    .max_by_key(|&id| threads.thread_priority(id))
+                    .filter(|&next| {
+                        // Unless nobody else is important enough to replace the active thread.
+                        !enabled.contains(&active)
+                            || threads.thread_priority(active) <= threads.thread_priority(next)
+                    })
+            }
+            SchedulerPolicy::Delay => {
+                if enabled.contains(&active) {
+                    // The active thread yielded, keep it from running again for a while.
+                    self.delays.insert(active, rng.gen_range(1..=MAX_YIELD_DELAY));
+                }
+                for delay in self.delays.values_mut() {
+                    *delay = delay.saturating_sub(1);
+                }
+                candidates
+                    .iter()
+                    .copied()
+                    .min_by_key(|id| self.delays.get(id).copied().unwrap_or(0))
+            }
+        };
+        // If there is no other thread to run, the active thread keeps running.
+        Ok(next.or_else(|| enabled.contains(&active).then_some(active)))
+    }
+
+    fn allows_preemption(&self) -> bool {
+        // With the FIFO policy, threads are never preempted.
+        self.policy != SchedulerPolicy::Fifo
+    }
+}
@@ -4,8 +4,9 @@ use std::ops::Not;
 
 use log::trace;
 
-use rustc_data_structures::fx::FxHashMap;
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
 use rustc_index::vec::{Idx, IndexVec};
+use rustc_span::Span;
 
 use super::vector_clock::VClock;
 use crate::*;
@@ -47,6 +48,10 @@ macro_rules! declare_id {
             pub fn to_u32_scalar<'tcx>(&self) -> Scalar<Provenance> {
                 Scalar::from_u32(self.0.get())
             }
+
+            pub fn to_u32(self) -> u32 {
+                self.0.get()
+            }
         }
     };
 }
@@ -62,6 +67,8 @@ struct Mutex {
     lock_count: usize,
     /// The queue of threads waiting for this mutex.
     queue: VecDeque<ThreadId>,
+    /// Where the owner acquired the lock, for deadlock reports.
+    acquired_at: Option<Span>,
     /// Data race handle, this tracks the happens-before
     /// relationship between each mutex access. It is
     /// released to during unlock and acquired from during
@@ -84,6 +91,8 @@ struct RwLock {
     writer_queue: VecDeque<ThreadId>,
     /// The queue of reader threads waiting for this lock.
     reader_queue: VecDeque<ThreadId>,
+    /// Where the writer and each of the readers acquired the lock, for deadlock reports.
+    acquired_at: FxHashMap<ThreadId, Span>,
     /// Data race handle for writers, tracks the happens-before
     /// ordering between each write access to a rwlock and is updated
     /// after a sequence of concurrent readers to track the happens-
@@ -107,13 +116,28 @@ struct RwLock {
 
 declare_id!(CondvarId);
 
+/// The mode in which a read-write lock was held.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RwLockMode {
+    Read,
+    Write,
+}
+
+/// The lock that a thread released when it started waiting on a conditional variable, and that it
+/// has to reacquire when it is woken up.
+#[derive(Debug, Copy, Clone)]
+pub enum CondvarLock {
+    Mutex(MutexId),
+    RwLock { id: RwLockId, mode: RwLockMode },
+}
+
 /// A thread waiting on a conditional variable.
 #[derive(Debug)]
 struct CondvarWaiter {
     /// The thread that is waiting on this variable.
     thread: ThreadId,
-    /// The mutex on which the thread is waiting.
-    mutex: MutexId,
+    /// The lock on which the thread is waiting.
+    lock: CondvarLock,
 }
 
 /// The conditional variable state.
@@ -149,6 +173,46 @@ struct FutexWaiter {
     bitset: u32,
 }
 
+declare_id!(SemaphoreId);
+
+/// The counting semaphore state.
+#[derive(Default, Debug)]
+struct Semaphore {
+    /// The number of available units.
+    count: u64,
+    /// The threads waiting for a unit, in the order in which they started waiting.
+    waiters: VecDeque<ThreadId>,
+    /// Tracks the happens-before relationship between releasing a unit and acquiring it.
+    /// Contains the clocks of all threads that released a unit.
+    data_race: VClock,
+}
+
+declare_id!(EventId);
+
+/// The state of an event object, as created by `CreateEventW` on Windows.
+#[derive(Default, Debug)]
+struct Event {
+    /// Whether the event is set.
+    signaled: bool,
+    /// Whether the event stays set when it releases a waiting thread. Otherwise, releasing a
+    /// thread resets it.
+    manual_reset: bool,
+    /// Tracks the happens-before relationship between setting the event and being released by it.
+    /// Contains the clocks of all threads that set the event.
+    data_race: VClock,
+}
+
+/// Two mutexes that were acquired one after the other, for `-Zmiri-lock-order-check`.
+#[derive(Debug)]
+struct LockOrderEdge {
+    /// The thread that acquired both mutexes.
+    thread: ThreadId,
+    /// Where it acquired the first mutex.
+    first: Span,
+    /// Where it acquired the second mutex while holding the first one.
+    second: Span,
+}
+
 /// The state of all synchronization variables.
 #[derive(Default, Debug)]
 pub(crate) struct SynchronizationState {
@@ -156,6 +220,47 @@ pub(crate) struct SynchronizationState {
     rwlocks: IndexVec<RwLockId, RwLock>,
     condvars: IndexVec<CondvarId, Condvar>,
     futexes: FxHashMap<u64, Futex>,
+    semaphores: IndexVec<SemaphoreId, Semaphore>,
+    events: IndexVec<EventId, Event>,
+    /// For `-Zmiri-lock-order-check`: the first time that the second mutex of the key was
+    /// acquired while holding the first one.
+    lock_order: FxHashMap<(MutexId, MutexId), LockOrderEdge>,
+    /// The pairs of mutexes for which a lock-order inversion was already reported, smallest first.
+    reported_lock_order_inversions: FxHashSet<(MutexId, MutexId)>,
+}
+
+impl SynchronizationState {
+    /// If `thread` is blocked on a synchronization primitive, describe what it is waiting for,
+    /// and return the threads that hold it together with where they acquired it.
+    pub(super) fn blocked_on(
+        &self,
+        thread: ThreadId,
+    ) -> Option<(&'static str, Vec<(ThreadId, Option<Span>)>)> {
+        if let Some(mutex) = self.mutexes.iter().find(|mutex| mutex.queue.contains(&thread)) {
+            let holders = mutex.owner.map(|owner| (owner, mutex.acquired_at));
+            return Some(("a mutex", holders.into_iter().collect()));
+        }
+        for rwlock in self.rwlocks.iter() {
+            let holder = |thread: ThreadId| (thread, rwlock.acquired_at.get(&thread).copied());
+            if rwlock.writer_queue.contains(&thread) {
+                let mut readers: Vec<_> = rwlock.readers.keys().copied().collect();
+                readers.sort();
+                let holders = rwlock.writer.into_iter().chain(readers).map(holder).collect();
+                return Some(("a write lock of an rwlock", holders));
+            }
+            if rwlock.reader_queue.contains(&thread) {
+                let holders = rwlock.writer.into_iter().map(holder).collect();
+                return Some(("a read lock of an rwlock", holders));
+            }
+        }
+        if self.condvars.iter().any(|condvar| condvar.waiters.iter().any(|w| w.thread == thread)) {
+            return Some(("a condition variable", vec![]));
+        }
+        if self.futexes.values().any(|futex| futex.waiters.iter().any(|w| w.thread == thread)) {
+            return Some(("a futex", vec![]));
+        }
+        None
+    }
 }
 
 // Private extension trait for local helper methods
@@ -168,6 +273,8 @@ trait EvalContextExtPriv<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_mut();
         if let Some(reader) = this.machine.threads.sync.rwlocks[id].reader_queue.pop_front() {
             this.unblock_thread(reader);
+            // The reader might have been waiting with a timeout.
+            this.unregister_timeout_callback_if_exists(reader);
             this.rwlock_reader_lock(id, reader);
             true
         } else {
@@ -182,6 +289,8 @@ trait EvalContextExtPriv<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_mut();
         if let Some(writer) = this.machine.threads.sync.rwlocks[id].writer_queue.pop_front() {
             this.unblock_thread(writer);
+            // The writer might have been waiting with a timeout.
+            this.unregister_timeout_callback_if_exists(writer);
             this.rwlock_writer_lock(id, writer);
             true
         } else {
@@ -252,6 +361,8 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     /// Lock by setting the mutex owner and increasing the lock count.
     fn mutex_lock(&mut self, id: MutexId, thread: ThreadId) {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Mutex(id), true);
+        let span = this.machine.thread_span(thread);
         let mutex = &mut this.machine.threads.sync.mutexes[id];
         if let Some(current_owner) = mutex.owner {
             assert_eq!(thread, current_owner, "mutex already locked by another thread");
@@ -261,6 +372,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             );
         } else {
             mutex.owner = Some(thread);
+            mutex.acquired_at = Some(span);
         }
         mutex.lock_count = mutex.lock_count.checked_add(1).unwrap();
         if let Some(data_race) = &this.machine.data_race {
@@ -274,6 +386,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     /// return `None`.
     fn mutex_unlock(&mut self, id: MutexId, expected_owner: ThreadId) -> Option<usize> {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Mutex(id), true);
         let mutex = &mut this.machine.threads.sync.mutexes[id];
         if let Some(current_owner) = mutex.owner {
             // Mutex is locked.
@@ -287,6 +400,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 .expect("invariant violation: lock_count == 0 iff the thread is unlocked");
             if mutex.lock_count == 0 {
                 mutex.owner = None;
+                mutex.acquired_at = None;
                 // The mutex is completely unlocked. Try transfering ownership
                 // to another thread.
                 if let Some(data_race) = &this.machine.data_race {
@@ -301,6 +415,46 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         }
     }
 
+    /// For `-Zmiri-lock-order-check`: called when the active thread tries to lock the mutex.
+    /// Records that the mutexes held by the thread were acquired before this one, and warns if
+    /// this one was acquired before one of them earlier.
+    fn mutex_check_lock_order(&mut self, id: MutexId) {
+        let this = self.eval_context_mut();
+        if !this.machine.lock_order_check {
+            return;
+        }
+        let thread = this.get_active_thread();
+        let span = this.machine.thread_span(thread);
+        let sync = &mut this.machine.threads.sync;
+        let held: Vec<_> = sync
+            .mutexes
+            .iter_enumerated()
+            .filter(|(held, mutex)| *held != id && mutex.owner == Some(thread))
+            .map(|(held, mutex)| (held, mutex.acquired_at.unwrap()))
+            .collect();
+        let mut inversions = vec![];
+        for (held, held_at) in held {
+            sync.lock_order
+                .entry((held, id))
+                .or_insert(LockOrderEdge { thread, first: held_at, second: span });
+            if let Some(edge) = sync.lock_order.get(&(id, held)) {
+                if sync.reported_lock_order_inversions.insert((held.min(id), held.max(id))) {
+                    inversions.push((held_at, edge.thread, edge.first, edge.second));
+                }
+            }
+        }
+        for (held_at, other_thread, other_first, other_second) in inversions {
+            let name = |thread| String::from_utf8_lossy(this.get_thread_name(thread)).into_owned();
+            register_diagnostic(NonHaltingDiagnostic::LockOrderInversion {
+                thread: name(thread),
+                held_at: held_at.data(),
+                other_thread: name(other_thread),
+                other_first: other_first.data(),
+                other_second: other_second.data(),
+            });
+        }
+    }
+
     #[inline]
     /// Put the thread into the queue waiting for the mutex.
     fn mutex_enqueue_and_block(&mut self, id: MutexId, thread: ThreadId) {
@@ -365,9 +519,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     /// this lock.
     fn rwlock_reader_lock(&mut self, id: RwLockId, reader: ThreadId) {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::RwLock(id), false);
         assert!(!this.rwlock_is_write_locked(id), "the lock is write locked");
         trace!("rwlock_reader_lock: {:?} now also held (one more time) by {:?}", id, reader);
+        let span = this.machine.thread_span(reader);
         let rwlock = &mut this.machine.threads.sync.rwlocks[id];
+        rwlock.acquired_at.entry(reader).or_insert(span);
         let count = rwlock.readers.entry(reader).or_insert(0);
         *count = count.checked_add(1).expect("the reader counter overflowed");
         if let Some(data_race) = &this.machine.data_race {
@@ -379,6 +536,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     /// Returns `true` if succeeded, `false` if this `reader` did not hold the lock.
     fn rwlock_reader_unlock(&mut self, id: RwLockId, reader: ThreadId) -> bool {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::RwLock(id), false);
         let rwlock = &mut this.machine.threads.sync.rwlocks[id];
         match rwlock.readers.entry(reader) {
             Entry::Occupied(mut entry) => {
@@ -388,6 +546,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 if *count == 0 {
                     trace!("rwlock_reader_unlock: {:?} no longer held by {:?}", id, reader);
                     entry.remove();
+                    rwlock.acquired_at.remove(&reader);
                 } else {
                     trace!("rwlock_reader_unlock: {:?} held one less time by {:?}", id, reader);
                 }
@@ -423,10 +582,13 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     /// Lock by setting the writer that owns the lock.
     fn rwlock_writer_lock(&mut self, id: RwLockId, writer: ThreadId) {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::RwLock(id), true);
         assert!(!this.rwlock_is_locked(id), "the rwlock is already locked");
         trace!("rwlock_writer_lock: {:?} now held by {:?}", id, writer);
+        let span = this.machine.thread_span(writer);
         let rwlock = &mut this.machine.threads.sync.rwlocks[id];
         rwlock.writer = Some(writer);
+        rwlock.acquired_at.insert(writer, span);
         if let Some(data_race) = &this.machine.data_race {
             data_race.validate_lock_acquire(&rwlock.data_race, writer);
         }
@@ -436,6 +598,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     /// Try to unlock by removing the writer.
     fn rwlock_writer_unlock(&mut self, id: RwLockId, expected_writer: ThreadId) -> bool {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::RwLock(id), true);
         let rwlock = &mut this.machine.threads.sync.rwlocks[id];
         if let Some(current_writer) = rwlock.writer {
             if current_writer != expected_writer {
@@ -443,6 +606,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 return false;
             }
             rwlock.writer = None;
+            rwlock.acquired_at.remove(&current_writer);
             trace!("rwlock_writer_unlock: {:?} unlocked by {:?}", id, expected_writer);
             // Release memory to both reader and writer vector clocks
             //  since this writer happens-before both the union of readers once they are finished
@@ -479,6 +643,15 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         this.block_thread(writer);
     }
 
+    #[inline]
+    /// Remove the thread from the queues of threads waiting for this lock.
+    fn rwlock_remove_waiter(&mut self, id: RwLockId, thread: ThreadId) {
+        let this = self.eval_context_mut();
+        let rwlock = &mut this.machine.threads.sync.rwlocks[id];
+        rwlock.reader_queue.retain(|&reader| reader != thread);
+        rwlock.writer_queue.retain(|&writer| writer != thread);
+    }
+
     #[inline]
     /// Create state for a new conditional variable.
     fn condvar_create(&mut self) -> CondvarId {
@@ -515,17 +688,19 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     }
 
     /// Mark that the thread is waiting on the conditional variable.
-    fn condvar_wait(&mut self, id: CondvarId, thread: ThreadId, mutex: MutexId) {
+    fn condvar_wait(&mut self, id: CondvarId, thread: ThreadId, lock: CondvarLock) {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Condvar(id), true);
         let waiters = &mut this.machine.threads.sync.condvars[id].waiters;
         assert!(waiters.iter().all(|waiter| waiter.thread != thread), "thread is already waiting");
-        waiters.push_back(CondvarWaiter { thread, mutex });
+        waiters.push_back(CondvarWaiter { thread, lock });
     }
 
     /// Wake up some thread (if there is any) sleeping on the conditional
     /// variable.
-    fn condvar_signal(&mut self, id: CondvarId) -> Option<(ThreadId, MutexId)> {
+    fn condvar_signal(&mut self, id: CondvarId) -> Option<(ThreadId, CondvarLock)> {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Condvar(id), true);
         let current_thread = this.get_active_thread();
         let condvar = &mut this.machine.threads.sync.condvars[id];
         let data_race = &this.machine.data_race;
@@ -538,7 +713,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             if let Some(data_race) = data_race {
                 data_race.validate_lock_acquire(&condvar.data_race, waiter.thread);
             }
-            (waiter.thread, waiter.mutex)
+            (waiter.thread, waiter.lock)
         })
     }
 
@@ -551,6 +726,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
     fn futex_wait(&mut self, addr: u64, thread: ThreadId, bitset: u32) {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Futex(addr), true);
         let futex = &mut this.machine.threads.sync.futexes.entry(addr).or_default();
         let waiters = &mut futex.waiters;
         assert!(waiters.iter().all(|waiter| waiter.thread != thread), "thread is already waiting");
@@ -559,6 +735,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
     fn futex_wake(&mut self, addr: u64, bitset: u32) -> Option<ThreadId> {
         let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Futex(addr), true);
         let current_thread = this.get_active_thread();
         let futex = &mut this.machine.threads.sync.futexes.get_mut(&addr)?;
         let data_race = &this.machine.data_race;
@@ -578,10 +755,137 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         })
     }
 
-    fn futex_remove_waiter(&mut self, addr: u64, thread: ThreadId) {
+    /// Move up to `count` threads waiting on the futex at `addr` to the futex at `addr2`.
+    /// Returns the number of threads that were moved.
+    fn futex_requeue(&mut self, addr: u64, addr2: u64, count: usize) -> usize {
         let this = self.eval_context_mut();
-        if let Some(futex) = this.machine.threads.sync.futexes.get_mut(&addr) {
+        this.dpor_visible_op(SyncObject::Futex(addr), true);
+        this.dpor_visible_op(SyncObject::Futex(addr2), true);
+        let futexes = &mut this.machine.threads.sync.futexes;
+        let Some(futex) = futexes.get_mut(&addr) else { return 0 };
+        let count = count.min(futex.waiters.len());
+        let requeued: Vec<_> = futex.waiters.drain(..count).collect();
+        futexes.entry(addr2).or_default().waiters.extend(requeued);
+        count
+    }
+
+    /// Remove the thread from the futex it is waiting on. This is not necessarily the futex it
+    /// started waiting on, since it might have been requeued since.
+    fn futex_remove_waiter(&mut self, thread: ThreadId) {
+        let this = self.eval_context_mut();
+        for futex in this.machine.threads.sync.futexes.values_mut() {
             futex.waiters.retain(|waiter| waiter.thread != thread);
         }
     }
+
+    /// Create a counting semaphore with `count` available units.
+    fn semaphore_create(&mut self, count: u64) -> SemaphoreId {
+        let this = self.eval_context_mut();
+        this.machine.threads.sync.semaphores.push(Semaphore { count, ..Default::default() })
+    }
+
+    /// Take a unit from the semaphore if one is available. Returns `false` if there is none.
+    fn semaphore_try_acquire(&mut self, id: SemaphoreId, thread: ThreadId) -> bool {
+        let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Semaphore(id), true);
+        let semaphore = &mut this.machine.threads.sync.semaphores[id];
+        if semaphore.count == 0 {
+            return false;
+        }
+        semaphore.count -= 1;
+        if let Some(data_race) = &this.machine.data_race {
+            data_race.validate_lock_acquire(&semaphore.data_race, thread);
+        }
+        true
+    }
+
+    /// Put the thread in the queue waiting for a unit of the semaphore.
+    fn semaphore_enqueue_and_block(&mut self, id: SemaphoreId, thread: ThreadId) {
+        let this = self.eval_context_mut();
+        this.machine.threads.sync.semaphores[id].waiters.push_back(thread);
+        this.block_thread(thread);
+    }
+
+    /// Return a unit to the semaphore. If a thread is waiting, the unit is directly handed to it
+    /// and the thread is woken up.
+    fn semaphore_release(&mut self, id: SemaphoreId) -> Option<ThreadId> {
+        let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Semaphore(id), true);
+        let current_thread = this.get_active_thread();
+        let semaphore = &mut this.machine.threads.sync.semaphores[id];
+        let data_race = &this.machine.data_race;
+        if let Some(data_race) = data_race {
+            data_race.validate_lock_release_shared(&mut semaphore.data_race, current_thread);
+        }
+        let Some(thread) = semaphore.waiters.pop_front() else {
+            semaphore.count = semaphore.count.checked_add(1).unwrap();
+            return None;
+        };
+        if let Some(data_race) = data_race {
+            data_race.validate_lock_acquire(&semaphore.data_race, thread);
+        }
+        this.unblock_thread(thread);
+        // The thread might have been waiting with a timeout.
+        this.unregister_timeout_callback_if_exists(thread);
+        Some(thread)
+    }
+
+    /// Check if there are threads waiting for a unit of the semaphore.
+    fn semaphore_is_awaited(&self, id: SemaphoreId) -> bool {
+        let this = self.eval_context_ref();
+        !this.machine.threads.sync.semaphores[id].waiters.is_empty()
+    }
+
+    /// Remove the thread from the queue of threads waiting for the semaphore.
+    fn semaphore_remove_waiter(&mut self, id: SemaphoreId, thread: ThreadId) {
+        let this = self.eval_context_mut();
+        this.machine.threads.sync.semaphores[id].waiters.retain(|&waiter| waiter != thread);
+    }
+
+    /// Create an event, which is set if `signaled` is true.
+    fn event_create(&mut self, manual_reset: bool, signaled: bool) -> EventId {
+        let this = self.eval_context_mut();
+        let event = Event { signaled, manual_reset, ..Default::default() };
+        this.machine.threads.sync.events.push(event)
+    }
+
+    /// Check if the event is set.
+    fn event_is_signaled(&self, id: EventId) -> bool {
+        let this = self.eval_context_ref();
+        this.machine.threads.sync.events[id].signaled
+    }
+
+    /// Release `thread` from waiting for the event, which has to be set. Unless the event is a
+    /// manual-reset event, this resets it.
+    fn event_acquire(&mut self, id: EventId, thread: ThreadId) {
+        let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Event(id), true);
+        let event = &mut this.machine.threads.sync.events[id];
+        assert!(event.signaled, "acquiring an event that is not set");
+        if !event.manual_reset {
+            event.signaled = false;
+        }
+        if let Some(data_race) = &this.machine.data_race {
+            data_race.validate_lock_acquire(&event.data_race, thread);
+        }
+    }
+
+    /// Set the event. The caller has to release the threads that wait for it.
+    fn event_set(&mut self, id: EventId) {
+        let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Event(id), true);
+        let current_thread = this.get_active_thread();
+        let event = &mut this.machine.threads.sync.events[id];
+        event.signaled = true;
+        if let Some(data_race) = &this.machine.data_race {
+            data_race.validate_lock_release_shared(&mut event.data_race, current_thread);
+        }
+    }
+
+    /// Reset the event.
+    fn event_reset(&mut self, id: EventId) {
+        let this = self.eval_context_mut();
+        this.dpor_visible_op(SyncObject::Event(id), true);
+        this.machine.threads.sync.events[id].signaled = false;
+    }
 }
@@ -1,22 +1,27 @@
 //! Implements threads.
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::hash_map::Entry;
 use std::num::TryFromIntError;
-use std::time::{Duration, Instant, SystemTime};
+use std::time::{Duration, SystemTime};
 
 use log::trace;
+use rand::rngs::StdRng;
+use rand::Rng;
 
-use rustc_data_structures::fx::FxHashMap;
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
 use rustc_hir::def_id::DefId;
 use rustc_index::vec::{Idx, IndexVec};
 use rustc_middle::mir::Mutability;
 use rustc_middle::ty::layout::TyAndLayout;
+use rustc_span::Span;
 use rustc_target::spec::abi::Abi;
 
 use crate::concurrency::data_race;
 use crate::concurrency::sync::SynchronizationState;
 use crate::*;
+use shims::unix::FsEvalContextExt as _;
+use shims::windows::WindowsSyncEvalContextExt as _;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SchedulingAction {
@@ -40,7 +45,7 @@ type TimeoutCallback<'mir, 'tcx> =
 pub struct ThreadId(u32);
 
 /// The main thread. When it terminates, the whole application terminates.
-const MAIN_THREAD: ThreadId = ThreadId(0);
+pub(crate) const MAIN_THREAD: ThreadId = ThreadId(0);
 
 impl ThreadId {
     pub fn to_u32(self) -> u32 {
@@ -94,6 +99,26 @@ pub enum ThreadState {
     Terminated,
 }
 
+/// What a blocked thread is waiting for, for deadlock reports.
+#[derive(Debug)]
+pub enum BlockedOn {
+    /// The termination of the given thread.
+    Join(ThreadId),
+    /// A synchronization primitive, described by the string. The threads that hold it are given
+    /// together with where they acquired it.
+    Sync(&'static str, Vec<(ThreadId, Option<Span>)>),
+}
+
+impl BlockedOn {
+    /// The threads that have to make progress for the blocked thread to be woken up.
+    fn holders(&self) -> Vec<ThreadId> {
+        match self {
+            BlockedOn::Join(thread) => vec![*thread],
+            BlockedOn::Sync(_, holders) => holders.iter().map(|(thread, _)| *thread).collect(),
+        }
+    }
+}
+
 /// The join status of a thread.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum ThreadJoinStatus {
@@ -106,6 +131,18 @@ enum ThreadJoinStatus {
     Joined,
 }
 
+/// For livelock detection: what a thread did since it last made synchronization progress, i.e.,
+/// since it last wrote to an atomic, used a synchronization primitive or yielded.
+#[derive(Debug, Default)]
+struct SyncProgress {
+    /// The number of basic blocks that the thread executed.
+    steps: Cell<u64>,
+    /// Whether the thread loaded from an atomic, which means it might be spinning on it.
+    atomic_load: Cell<bool>,
+    /// Whether a possible livelock was already reported.
+    reported: Cell<bool>,
+}
+
 /// A thread.
 pub struct Thread<'mir, 'tcx> {
     state: ThreadState,
@@ -116,6 +153,11 @@ pub struct Thread<'mir, 'tcx> {
     /// The virtual call stack.
     stack: Vec<Frame<'mir, 'tcx, Provenance, FrameData<'tcx>>>,
 
+    /// The indices of the frames of `stack` that are local (see `CurrentSpan`), innermost last.
+    /// This is kept up to date as frames are pushed and popped so that finding the span of a
+    /// thread does not require searching its stack.
+    local_frames: Vec<usize>,
+
     /// The join status.
     join_status: ThreadJoinStatus,
 
@@ -126,6 +168,23 @@ pub struct Thread<'mir, 'tcx> {
 
     /// Last OS error location in memory. It is a 32-bit integer.
     pub(crate) last_error: Option<MPlaceTy<'tcx, Provenance>>,
+
+    /// The preemption rate of this thread, set by `miri_set_thread_preemption_rate`.
+    /// If `None`, the global preemption rate is used.
+    pub(crate) preemption_rate: Option<f64>,
+
+    /// The priority of this thread, set by `miri_set_thread_priority`. Only used by
+    /// `SchedulerPolicy::Priority`.
+    pub(crate) priority: i32,
+
+    /// When the thread last became ready to run, for `SchedulerPolicy::Fifo`.
+    ready_since: u64,
+
+    /// What the thread did since it last made synchronization progress.
+    progress: SyncProgress,
+
+    /// Whether the thread called `ExitThread`. Its frames are popped before it runs again.
+    exit_requested: bool,
 }
 
 impl<'mir, 'tcx> Thread<'mir, 'tcx> {
@@ -165,9 +224,15 @@ impl<'mir, 'tcx> Default for Thread<'mir, 'tcx> {
             state: ThreadState::Enabled,
             thread_name: None,
             stack: Vec::new(),
+            local_frames: Vec::new(),
             join_status: ThreadJoinStatus::Joinable,
             panic_payload: None,
             last_error: None,
+            preemption_rate: None,
+            priority: 0,
+            ready_since: 0,
+            progress: SyncProgress::default(),
+            exit_requested: false,
         }
     }
 }
@@ -189,9 +254,9 @@ pub enum Time {
 
 impl Time {
     /// How long do we have to wait from now until the specified time?
-    fn get_wait_time(&self) -> Duration {
+    fn get_wait_time(&self, clock: &Clock) -> Duration {
         match self {
-            Time::Monotonic(instant) => instant.saturating_duration_since(Instant::now()),
+            Time::Monotonic(instant) => instant.duration_since(clock.now()),
             Time::RealTime(time) =>
                 time.duration_since(SystemTime::now()).unwrap_or(Duration::new(0, 0)),
         }
@@ -234,10 +299,18 @@ pub struct ThreadManager<'mir, 'tcx> {
     yield_active_thread: bool,
     /// Callbacks that are called once the specified time passes.
     timeout_callbacks: FxHashMap<ThreadId, TimeoutCallbackInfo<'mir, 'tcx>>,
+    /// Points in time at which a file description becomes ready by itself, like a timerfd that
+    /// expires. The threads waiting for file descriptions are woken up when they pass.
+    io_deadlines: Vec<Instant>,
+    /// Incremented whenever a thread becomes ready to run, see `Thread::ready_since`.
+    ready_counter: u64,
+    /// The ID that `gettid` returns for the main thread, once the program has asked for a thread
+    /// ID. From then on, diagnostics show the thread IDs as well.
+    os_id_base: Option<u32>,
 }
 
-impl<'mir, 'tcx> Default for ThreadManager<'mir, 'tcx> {
-    fn default() -> Self {
+impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
+    pub(crate) fn new() -> Self {
         let mut threads = IndexVec::new();
         // Create the main thread and add it to the list of threads.
         threads.push(Thread::new("main"));
@@ -248,11 +321,12 @@ impl<'mir, 'tcx> Default for ThreadManager<'mir, 'tcx> {
             thread_local_alloc_ids: Default::default(),
             yield_active_thread: false,
             timeout_callbacks: FxHashMap::default(),
+            io_deadlines: Vec::new(),
+            ready_counter: 0,
+            os_id_base: None,
         }
     }
-}
 
-impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
     pub(crate) fn init(ecx: &mut MiriEvalContext<'mir, 'tcx>) {
         if ecx.tcx.sess.target.os.as_ref() != "windows" {
             // The main thread can *not* be joined on except on windows.
@@ -282,6 +356,33 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
         &self.threads[self.active_thread].stack
     }
 
+    /// Borrow the stack of the given thread.
+    pub fn thread_stack(
+        &self,
+        thread: ThreadId,
+    ) -> &[Frame<'mir, 'tcx, Provenance, FrameData<'tcx>>] {
+        &self.threads[thread].stack
+    }
+
+    /// Get the index of the innermost local frame of the given thread, or 0 if there is none.
+    pub fn local_frame_index(&self, thread: ThreadId) -> usize {
+        self.threads[thread].local_frames.last().copied().unwrap_or(0)
+    }
+
+    /// Record that the frame on top of the stack of the active thread is local.
+    pub(crate) fn push_local_frame(&mut self) {
+        let thread = &mut self.threads[self.active_thread];
+        thread.local_frames.push(thread.stack.len().checked_sub(1).unwrap());
+    }
+
+    /// Forget the local frames that are no longer on the stack of the active thread.
+    pub(crate) fn pop_local_frames(&mut self) {
+        let thread = &mut self.threads[self.active_thread];
+        while thread.local_frames.last().map_or(false, |&idx| idx >= thread.stack.len()) {
+            thread.local_frames.pop();
+        }
+    }
+
     /// Mutably borrow the stack of the active thread.
     fn active_thread_stack_mut(
         &mut self,
@@ -303,9 +404,48 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
     fn create_thread(&mut self) -> ThreadId {
         let new_thread_id = ThreadId::new(self.threads.len());
         self.threads.push(Default::default());
+        self.mark_ready(new_thread_id);
         new_thread_id
     }
 
+    /// Remember that the thread just became ready to run.
+    fn mark_ready(&mut self, thread: ThreadId) {
+        self.ready_counter += 1;
+        self.threads[thread].ready_since = self.ready_counter;
+    }
+
+    /// The threads that can run, ordered by their id.
+    pub fn enabled_threads(&self) -> Vec<ThreadId> {
+        self.threads
+            .iter_enumerated()
+            .filter(|(_, thread)| thread.state == ThreadState::Enabled)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// The priority of the thread, set by `miri_set_thread_priority`.
+    pub fn thread_priority(&self, thread: ThreadId) -> i32 {
+        self.threads[thread].priority
+    }
+
+    /// When the thread last became ready to run. Smaller values mean earlier.
+    pub fn ready_since(&self, thread: ThreadId) -> u64 {
+        self.threads[thread].ready_since
+    }
+
+    /// Record an atomic or synchronization operation of the active thread for livelock detection.
+    /// Only writes count as progress; a thread that keeps loading the same atomic is spinning.
+    pub(crate) fn record_sync_op(&self, write: bool) {
+        let progress = &self.active_thread_ref().progress;
+        if write {
+            progress.steps.set(0);
+            progress.atomic_load.set(false);
+            progress.reported.set(false);
+        } else {
+            progress.atomic_load.set(true);
+        }
+    }
+
     /// Set an active thread and return the id of the thread that was active before.
     fn set_active_thread_id(&mut self, id: ThreadId) -> ThreadId {
         let active_thread_id = self.active_thread;
@@ -344,6 +484,7 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
     fn enable_thread(&mut self, thread_id: ThreadId) {
         assert!(self.has_terminated(thread_id));
         self.threads[thread_id].state = ThreadState::Enabled;
+        self.mark_ready(thread_id);
     }
 
     /// Get a mutable borrow of the currently active thread.
@@ -412,6 +553,20 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
         Ok(())
     }
 
+    /// Stop the thread from waiting for the termination of the thread it joins, because the join
+    /// timed out. No happens-before edge is added in this case.
+    fn cancel_join(&mut self, thread: ThreadId) {
+        let ThreadState::BlockedOnJoin(joined_thread_id) = self.threads[thread].state else {
+            panic!("thread is not joining");
+        };
+        self.threads[thread].state = ThreadState::Enabled;
+        self.mark_ready(thread);
+        // If nobody else is waiting, the thread can still be joined or detached later.
+        if self.threads.iter().all(|t| t.state != ThreadState::BlockedOnJoin(joined_thread_id)) {
+            self.threads[joined_thread_id].join_status = ThreadJoinStatus::Joinable;
+        }
+    }
+
     /// Mark that the active thread tries to exclusively join the thread with `joined_thread_id`.
     /// If the thread is already joined by another thread, it will throw UB
     fn join_thread_exclusive(
@@ -447,6 +602,73 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
         self.threads[thread].thread_name()
     }
 
+    /// The ID that the program sees for `thread`, where the main thread has the ID `base` and the
+    /// other threads count up from there.
+    pub fn os_thread_id(&mut self, thread: ThreadId, base: u32) -> u32 {
+        self.os_id_base = Some(base);
+        base.wrapping_add(thread.to_u32())
+    }
+
+    /// How diagnostics refer to `thread`: by its name, and by its ID if the program has seen
+    /// thread IDs, so that the program's own logs can be matched up with the diagnostics.
+    pub fn describe_thread(&self, thread: ThreadId) -> String {
+        let name = String::from_utf8_lossy(self.get_thread_name(thread));
+        match self.os_id_base {
+            Some(base) => format!("thread `{name}` (tid {})", base.wrapping_add(thread.to_u32())),
+            None => format!("thread `{name}`"),
+        }
+    }
+
+    /// What the given thread is waiting for, if it is blocked.
+    pub fn blocked_on(&self, thread: ThreadId) -> Option<BlockedOn> {
+        match self.threads[thread].state {
+            ThreadState::BlockedOnJoin(joined) => Some(BlockedOn::Join(joined)),
+            ThreadState::BlockedOnSync =>
+                self.sync.blocked_on(thread).map(|(what, holders)| BlockedOn::Sync(what, holders)),
+            ThreadState::Enabled | ThreadState::Terminated => None,
+        }
+    }
+
+    /// All threads that are blocked, and what they are waiting for.
+    pub fn blocked_threads(&self) -> Vec<(ThreadId, BlockedOn)> {
+        self.threads
+            .indices()
+            .filter_map(|thread| Some((thread, self.blocked_on(thread)?)))
+            .collect()
+    }
+
+    /// Find a cycle of blocked threads in which every thread waits for the next one, starting the
+    /// search at the active thread.
+    pub fn wait_cycle(&self) -> Option<Vec<ThreadId>> {
+        fn visit(
+            threads: &ThreadManager<'_, '_>,
+            thread: ThreadId,
+            path: &mut Vec<ThreadId>,
+            visited: &mut FxHashSet<ThreadId>,
+        ) -> Option<Vec<ThreadId>> {
+            if let Some(pos) = path.iter().position(|&t| t == thread) {
+                return Some(path[pos..].to_vec());
+            }
+            if !visited.insert(thread) {
+                return None;
+            }
+            let holders = threads.blocked_on(thread)?.holders();
+            path.push(thread);
+            for holder in holders {
+                if let Some(cycle) = visit(threads, holder, path, visited) {
+                    return Some(cycle);
+                }
+            }
+            path.pop();
+            None
+        }
+
+        let mut visited = FxHashSet::default();
+        std::iter::once(self.active_thread)
+            .chain(self.threads.indices())
+            .find_map(|thread| visit(self, thread, &mut Vec::new(), &mut visited))
+    }
+
     /// Put the thread into the blocked state.
     fn block_thread(&mut self, thread: ThreadId) {
         let state = &mut self.threads[thread].state;
@@ -459,6 +681,7 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
         let state = &mut self.threads[thread].state;
         assert_eq!(*state, ThreadState::BlockedOnSync);
         *state = ThreadState::Enabled;
+        self.mark_ready(thread);
     }
 
     /// Change the active thread to some enabled thread.
@@ -489,14 +712,30 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
         self.timeout_callbacks.remove(&thread);
     }
 
+    /// Register a point in time at which the readiness of a file description changes.
+    fn register_io_deadline(&mut self, deadline: Instant) {
+        self.io_deadlines.push(deadline);
+    }
+
+    /// Remove the I/O deadlines that have passed, and return whether there were any.
+    fn take_passed_io_deadlines(&mut self, clock: &Clock) -> bool {
+        let now = clock.now();
+        let len = self.io_deadlines.len();
+        self.io_deadlines.retain(|&deadline| deadline > now);
+        self.io_deadlines.len() != len
+    }
+
     /// Get a callback that is ready to be called.
-    fn get_ready_callback(&mut self) -> Option<(ThreadId, TimeoutCallback<'mir, 'tcx>)> {
+    fn get_ready_callback(
+        &mut self,
+        clock: &Clock,
+    ) -> Option<(ThreadId, TimeoutCallback<'mir, 'tcx>)> {
         // We iterate over all threads in the order of their indices because
         // this allows us to have a deterministic scheduler.
         for thread in self.threads.indices() {
             match self.timeout_callbacks.entry(thread) {
                 Entry::Occupied(entry) =>
-                    if entry.get().call_time.get_wait_time() == Duration::new(0, 0) {
+                    if entry.get().call_time.get_wait_time(clock) == Duration::new(0, 0) {
                         return Some((thread, entry.remove().callback));
                     },
                 Entry::Vacant(_) => {}
@@ -533,27 +772,33 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
         let mut joined_threads = vec![]; // store which threads joined, we'll need it
         for (i, thread) in self.threads.iter_enumerated_mut() {
             if thread.state == ThreadState::BlockedOnJoin(self.active_thread) {
-                // The thread has terminated, mark happens-before edge to joining thread
-                if data_race.is_some() {
-                    joined_threads.push(i);
-                }
                 trace!("unblocking {:?} because {:?} terminated", i, self.active_thread);
                 thread.state = ThreadState::Enabled;
+                joined_threads.push(i);
             }
         }
         for &i in &joined_threads {
-            data_race.as_mut().unwrap().thread_joined(self, i, self.active_thread);
+            self.mark_ready(i);
+            // The joining thread might have been waiting with a timeout.
+            self.unregister_timeout_callback_if_exists(i);
+            // The thread has terminated, mark happens-before edge to joining thread
+            if let Some(data_race) = data_race.as_mut() {
+                data_race.thread_joined(self, i, self.active_thread);
+            }
         }
         free_tls_statics
     }
 
     /// Decide which action to take next and on which thread.
     ///
-    /// The currently implemented scheduling policy is the one that is commonly
-    /// used in stateless model checkers such as Loom: run the active thread as
-    /// long as we can and switch only when we have to (the active thread was
-    /// blocked, terminated, or has explicitly asked to be preempted).
-    fn schedule(&mut self) -> InterpResult<'tcx, SchedulingAction> {
+    /// The active thread keeps running until it blocks, terminates, yields, or the `backend` ends
+    /// its step. Then the `backend` decides which thread runs next.
+    fn schedule(
+        &mut self,
+        backend: &mut dyn SchedulingBackend<'tcx>,
+        clock: &Clock,
+        rng: &mut StdRng,
+    ) -> InterpResult<'tcx, SchedulingAction> {
         // Check whether the thread has **just** terminated (`check_terminated`
         // checks whether the thread has popped all its stack and if yes, sets
         // the thread state to terminated).
@@ -567,6 +812,10 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
             return Ok(SchedulingAction::Stop);
         }
         // This thread and the program can keep going.
+        let explicit_yield = self.yield_active_thread;
+        if backend.step_done() {
+            self.yield_active_thread = true;
+        }
         if self.threads[self.active_thread].state == ThreadState::Enabled
             && !self.yield_active_thread
         {
@@ -579,30 +828,25 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
         // error is returned if [...] the absolute time specified by abstime has already been passed
         // at the time of the call".
         // <https://pubs.opengroup.org/onlinepubs/9699919799/functions/pthread_cond_timedwait.html>
-        let potential_sleep_time =
-            self.timeout_callbacks.values().map(|info| info.call_time.get_wait_time()).min();
+        let now = clock.now();
+        let potential_sleep_time = self
+            .timeout_callbacks
+            .values()
+            .map(|info| info.call_time.get_wait_time(clock))
+            .chain(self.io_deadlines.iter().map(|&deadline| deadline.duration_since(now)))
+            .min();
         if potential_sleep_time == Some(Duration::new(0, 0)) {
             return Ok(SchedulingAction::ExecuteTimeoutCallback);
         }
         // No callbacks scheduled, pick a regular thread to execute.
         // The active thread blocked or yielded. So we go search for another enabled thread.
-        // Crucially, we start searching at the current active thread ID, rather than at 0, since we
-        // want to avoid always scheduling threads 0 and 1 without ever making progress in thread 2.
-        //
-        // `skip(N)` means we start iterating at thread N, so we skip 1 more to start just *after*
-        // the active thread. Then after that we look at `take(N)`, i.e., the threads *before* the
-        // active thread.
-        let threads = self
-            .threads
-            .iter_enumerated()
-            .skip(self.active_thread.index() + 1)
-            .chain(self.threads.iter_enumerated().take(self.active_thread.index()));
-        for (id, thread) in threads {
-            debug_assert_ne!(self.active_thread, id);
-            if thread.state == ThreadState::Enabled {
-                self.active_thread = id;
-                break;
-            }
+        if self.threads[self.active_thread].state == ThreadState::Enabled {
+            // The active thread yielded, so it is ready to run again.
+            self.mark_ready(self.active_thread);
+        }
+        if let Some(id) = backend.next_thread(self, explicit_yield, rng)? {
+            assert_eq!(self.threads[id].state, ThreadState::Enabled);
+            self.active_thread = id;
         }
         self.yield_active_thread = false;
         if self.threads[self.active_thread].state == ThreadState::Enabled {
@@ -614,8 +858,9 @@ impl<'mir, 'tcx: 'mir> ThreadManager<'mir, 'tcx> {
         } else if let Some(sleep_time) = potential_sleep_time {
             // All threads are currently blocked, but we have unexecuted
             // timeout_callbacks, which may unblock some of the threads. Hence,
-            // sleep until the first callback.
-            std::thread::sleep(sleep_time);
+            // sleep until the first callback. Under isolation, this just advances
+            // the virtual clock.
+            clock.sleep(sleep_time);
             Ok(SchedulingAction::ExecuteTimeoutCallback)
         } else {
             throw_machine_stop!(TerminationInfo::Deadlock);
@@ -732,6 +977,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(())
     }
 
+    #[inline]
+    fn cancel_join(&mut self, thread: ThreadId) {
+        let this = self.eval_context_mut();
+        this.machine.threads.cancel_join(thread);
+    }
+
     #[inline]
     fn join_thread_exclusive(&mut self, joined_thread_id: ThreadId) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
@@ -841,19 +1092,50 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         this.machine.threads.unblock_thread(thread);
     }
 
+    /// Let another thread run. This counts as synchronization progress for livelock detection.
     #[inline]
     fn yield_active_thread(&mut self) {
         let this = self.eval_context_mut();
+        this.machine.threads.record_sync_op(true);
         this.machine.threads.yield_active_thread();
     }
 
     #[inline]
     fn maybe_preempt_active_thread(&mut self) {
-        use rand::Rng as _;
-
         let this = self.eval_context_mut();
-        if this.machine.rng.get_mut().gen_bool(this.machine.preemption_rate) {
-            this.yield_active_thread();
+        let rate = this.active_thread_ref().preemption_rate.unwrap_or(this.machine.preemption_rate);
+        if this.machine.scheduler.get_mut().allows_preemption()
+            && this.machine.rng.get_mut().gen_bool(rate)
+        {
+            this.machine.threads.yield_active_thread();
+        }
+    }
+
+    /// Count a step of the active thread, and report a possible livelock if it has been loading
+    /// atomics without making synchronization progress for too long while other threads could
+    /// run.
+    #[inline]
+    fn check_livelock(&self) {
+        let this = self.eval_context_ref();
+        let threshold = this.machine.livelock_threshold;
+        if threshold == 0 {
+            return;
+        }
+        let threads = &this.machine.threads;
+        let progress = &threads.active_thread_ref().progress;
+        let steps = progress.steps.get() + 1;
+        progress.steps.set(steps);
+        if steps < threshold || !progress.atomic_load.get() || progress.reported.get() {
+            return;
+        }
+        let others_enabled = threads.threads.iter_enumerated().any(|(id, thread)| {
+            id != threads.active_thread && thread.state == ThreadState::Enabled
+        });
+        if others_enabled {
+            progress.reported.set(true);
+            let thread = threads.get_thread_name(threads.active_thread);
+            let thread = String::from_utf8_lossy(thread).into_owned();
+            register_diagnostic(NonHaltingDiagnostic::Livelock { thread, steps });
         }
     }
 
@@ -874,22 +1156,68 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         this.machine.threads.unregister_timeout_callback_if_exists(thread);
     }
 
+    /// Block the thread until it is woken up, or until the `deadline` passes. In the latter case,
+    /// `on_timeout` is called, which has to unblock the thread. Whoever wakes up the thread
+    /// earlier has to unregister the timeout callback. Without a deadline, the thread waits until
+    /// it is woken up.
+    fn block_thread_until(
+        &mut self,
+        thread: ThreadId,
+        deadline: Option<Time>,
+        on_timeout: TimeoutCallback<'mir, 'tcx>,
+    ) {
+        let this = self.eval_context_mut();
+        this.block_thread(thread);
+        if let Some(deadline) = deadline {
+            this.register_timeout_callback(thread, deadline, on_timeout);
+        }
+    }
+
+    /// The deadline `duration` from now on the monotonic clock. Returns `None` if the deadline is
+    /// too far in the future to be represented, which means that it is never reached.
+    fn monotonic_deadline_after(&self, duration: Duration) -> Option<Time> {
+        let this = self.eval_context_ref();
+        this.machine.clock.now().checked_add(duration).map(Time::Monotonic)
+    }
+
+    /// The deadline `duration` after the start of the monotonic clock, as used by the `timespec`s
+    /// of absolute `CLOCK_MONOTONIC` timeouts. Returns `None` if the deadline is too far in the
+    /// future to be represented, which means that it is never reached.
+    fn monotonic_deadline_since_anchor(&self, duration: Duration) -> Option<Time> {
+        let this = self.eval_context_ref();
+        this.machine.clock.anchor().checked_add(duration).map(Time::Monotonic)
+    }
+
+    /// Wake up the threads waiting for file descriptions at `deadline`, see
+    /// `FileDescriptor::epoll_readiness`.
+    #[inline]
+    fn register_io_deadline(&mut self, deadline: Instant) {
+        let this = self.eval_context_mut();
+        this.machine.threads.register_io_deadline(deadline);
+    }
+
     /// Execute a timeout callback on the callback's thread.
     #[inline]
     fn run_timeout_callback(&mut self) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
-        let (thread, callback) =
-            if let Some((thread, callback)) = this.machine.threads.get_ready_callback() {
-                (thread, callback)
-            } else {
-                // get_ready_callback can return None if the computer's clock
-                // was shifted after calling the scheduler and before the call
-                // to get_ready_callback (see issue
-                // https://github.com/rust-lang/miri/issues/1763). In this case,
-                // just do nothing, which effectively just returns to the
-                // scheduler.
-                return Ok(());
-            };
+        // When a file description became ready, the waiting threads get callbacks to retry their
+        // operation, which run next time.
+        if this.machine.threads.take_passed_io_deadlines(&this.machine.clock) {
+            return this.wake_blocked_io();
+        }
+        let (thread, callback) = if let Some((thread, callback)) =
+            this.machine.threads.get_ready_callback(&this.machine.clock)
+        {
+            (thread, callback)
+        } else {
+            // get_ready_callback can return None if the computer's clock
+            // was shifted after calling the scheduler and before the call
+            // to get_ready_callback (see issue
+            // https://github.com/rust-lang/miri/issues/1763). In this case,
+            // just do nothing, which effectively just returns to the
+            // scheduler.
+            return Ok(());
+        };
         // This back-and-forth with `set_active_thread` is here because of two
         // design decisions:
         // 1. Make the caller and not the callback responsible for changing
@@ -906,11 +1234,18 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     #[inline]
     fn schedule(&mut self) -> InterpResult<'tcx, SchedulingAction> {
         let this = self.eval_context_mut();
-        this.machine.threads.schedule()
+        if this.active_thread_ref().exit_requested {
+            this.exit_active_thread()?;
+        }
+        this.machine.threads.schedule(
+            &mut **this.machine.scheduler.get_mut(),
+            &this.machine.clock,
+            this.machine.rng.get_mut(),
+        )
     }
 
-    /// Handles thread termination of the active thread: wakes up threads joining on this one,
-    /// and deallocated thread-local statics.
+    /// Handles thread termination of the active thread: wakes up threads joining on this one or
+    /// waiting for it in `WaitForMultipleObjects`, and deallocated thread-local statics.
     ///
     /// This is called from `tls.rs` after handling the TLS dtors.
     #[inline]
@@ -919,6 +1254,42 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         for ptr in this.machine.threads.thread_terminated(this.machine.data_race.as_mut()) {
             this.deallocate_ptr(ptr.into(), None, MiriMemoryKind::Tls.into())?;
         }
+        this.wake_object_waiters()
+    }
+
+    /// Make the active thread terminate without returning from any of its frames, like
+    /// `ExitThread` does. This is called from a shim while the interpreter is still executing the
+    /// call, so the frames are only popped by `schedule` right after the current step.
+    fn request_thread_exit(&mut self) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        if this.get_active_thread() == MAIN_THREAD {
+            throw_unsup_format!("exiting the main thread is not supported");
+        }
+        this.active_thread_mut().exit_requested = true;
+        // The call never returns, but the interpreter expects the frame to have moved on.
+        let span = this.frame().current_span();
+        this.frame_mut().loc = Err(span);
+        Ok(())
+    }
+
+    /// Pops all frames of the active thread without returning from them: no code runs in any of
+    /// the frames, but they are cleaned up as if they were unwound, except that `catch_unwind`
+    /// does not stop the exit. Once the scheduler sees the empty stack, the thread terminates as
+    /// usual, including running its TLS destructors.
+    fn exit_active_thread(&mut self) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        this.active_thread_mut().exit_requested = false;
+        while let Some(mut frame) = this.active_thread_stack_mut().pop() {
+            frame.extra.catch_unwind = None;
+            for local in &frame.locals {
+                if let LocalValue::Live(Operand::Indirect(MemPlace { ptr, .. })) = local.value {
+                    this.deallocate_ptr(ptr, None, MemoryKind::Stack)?;
+                }
+            }
+            // Nothing catches the unwinding, so the frame does not return anywhere.
+            let jump = Evaluator::after_stack_pop(this, frame, /* unwinding */ true)?;
+            assert!(matches!(jump, StackPopJump::Normal));
+        }
         Ok(())
     }
 }
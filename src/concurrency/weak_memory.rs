@@ -16,6 +16,22 @@
 //! an earlier store in the location's modification order. This is to prevent creating a backwards S edge from the second
 //! load to the first, as a result of C++20's coherence-ordered before rules.
 //!
+//! Another modification is made for SC fences. If a thread loads from a store before an SC fence, then a load by another
+//! thread after a later SC fence cannot read from an earlier store in the location's modification order. This
+//! implements the `[F_sc]; hb; eco; hb; [F_sc]` part of `psc_fence` from Repairing Sequential Consistency in C/C++11
+//! by Ori Lahav et al. (<https://plv.mpi-sws.org/scfix/paper.pdf>) for two loads, which is what makes the IRIW
+//! litmus test with SC fences between the loads behave sequentially consistent. Stores before SC fences are handled
+//! by the paper's model already. The `eco` paths that end in a store, i.e. a load reading from a store that is earlier
+//! in modification order than a store after the other fence, or two stores in modification order, are not taken into
+//! account: Miri can execute the fences in the other order, so it may still produce some outcomes that `psc_fence`
+//! forbids.
+//!
+//! Read-modify-write operations always read from the latest store in modification order, which is allowed since that
+//! is the store right before their own write, so the rules above only restrict plain loads. The load information the
+//! SC fence rule relies on is discarded when a mixed-size or non-atomic access resets the store buffers of a location
+//! (see below). Such an access cannot race with the earlier atomic accesses, so they all happen-before it, and later
+//! loads can only read from the latest store anyway.
+//!
 //! Rust follows the C++20 memory model (except for the Consume ordering and some operations not performable through C++'s
 //! std::atomic<T> API). It is therefore possible for this implementation to generate behaviours never observable when the
 //! same program is compiled and run natively. Unfortunately, no literature exists at the time of writing which proposes
@@ -352,6 +368,16 @@ impl<'mir, 'tcx: 'mir> StoreBuffer {
                     // the last store sequenced-before an SC fence in another thread.
                     // C++17 §32.4 [atomics.order] paragraph 6
                     false
+                } else if store_elem.load_info.borrow().timestamps.iter().any(
+                    |(&load_index, &load_timestamp)| {
+                        load_timestamp <= clocks.fence_seqcst[load_index]
+                    },
+                ) {
+                    // The current load, which may be sequenced-after an SC fence, cannot read-before
+                    // a store that was loaded from before an earlier SC fence in another thread.
+                    // Otherwise the two loads would order the fences the other way round.
+                    // C++20 §31.4 [atomics.order] paragraph 4.4
+                    false
                 } else if store_elem.timestamp <= clocks.write_seqcst[store_elem.store_index]
                     && store_elem.is_seqcst
                 {
@@ -5,10 +5,17 @@ use std::num::NonZeroU64;
 use log::trace;
 
 use rustc_middle::ty;
-use rustc_span::{source_map::DUMMY_SP, Span, SpanData, Symbol};
+use rustc_span::{
+    source_map::{SourceMap, DUMMY_SP},
+    FileName, Span, SpanData, Symbol,
+};
 use rustc_target::abi::{Align, Size};
 
-use crate::stacked_borrows::{diagnostics::TagHistory, AccessKind};
+use crate::concurrency::thread::MAIN_THREAD;
+use crate::stacked_borrows::{
+    diagnostics::{sb_error_json, SbErrorDetails, TagHistory},
+    AccessKind,
+};
 use crate::*;
 
 /// Details of premature program termination.
@@ -20,6 +27,16 @@ pub enum TerminationInfo {
         msg: String,
         help: Option<String>,
         history: Option<TagHistory>,
+        details: SbErrorDetails,
+    },
+    /// A data race for which the backtrace of the other access was recorded.
+    DataRace {
+        msg: String,
+        other_access: String,
+        /// The frames of the other access, and whether they are in the local crate.
+        other_backtrace: Vec<(String, SpanData, bool)>,
+        /// For `-Zmiri-data-race-history`: the last accesses to the location, oldest first.
+        history: Vec<(String, SpanData)>,
     },
     Int2PtrWithStrictProvenance,
     Deadlock,
@@ -49,6 +66,7 @@ impl fmt::Display for TerminationInfo {
                     "integer-to-pointer casts and `ptr::from_exposed_addr` are not supported with `-Zmiri-strict-provenance`"
                 ),
             StackedBorrowsUb { msg, .. } => write!(f, "{msg}"),
+            DataRace { msg, .. } => write!(f, "{msg}"),
             Deadlock => write!(f, "the evaluated program deadlocked"),
             MultipleSymbolDefinitions { link_name, .. } =>
                 write!(f, "multiple definitions of symbol `{link_name}`"),
@@ -77,6 +95,44 @@ pub enum NonHaltingDiagnostic {
         details: bool,
     },
     WeakMemoryOutdatedLoad,
+    /// A foreign function that is not supported was made to return zero.
+    StubbedForeignItem(Symbol),
+    /// A file system call was made to fail with the given error code by `-Zmiri-fs-fail-nth` or
+    /// `-Zmiri-fs-fail-rate`.
+    InjectedFsFailure(Symbol, String),
+    /// A function that would have created a process failed due to `-Zmiri-fork-fails`.
+    FailedProcessCreation(Symbol),
+    /// A message logged with `syslog`.
+    Syslog(String),
+    /// A Stacked Borrows error that did not halt execution due to `-Zmiri-sb-report-only`.
+    StackedBorrowsUb {
+        msg: String,
+        help: Option<String>,
+        history: Option<TagHistory>,
+        details: SbErrorDetails,
+    },
+    /// A data race that did not halt execution due to `-Zmiri-race-report-only`.
+    DataRace {
+        msg: String,
+        other_access: String,
+        other_backtrace: Vec<(String, SpanData, bool)>,
+        history: Vec<(String, SpanData)>,
+    },
+    /// `thread` is locking a mutex while holding another one that it acquired at `held_at`, but
+    /// `other_thread` acquired them in the opposite order.
+    LockOrderInversion {
+        thread: String,
+        held_at: SpanData,
+        other_thread: String,
+        other_first: SpanData,
+        other_second: SpanData,
+    },
+    /// `thread` executed `steps` basic blocks in which it loaded from atomics, but did not make
+    /// synchronization progress, while other threads were waiting to run.
+    Livelock {
+        thread: String,
+        steps: u64,
+    },
 }
 
 /// Level of Miri specific diagnostics
@@ -144,6 +200,145 @@ fn prune_stacktrace<'mir, 'tcx>(
     }
 }
 
+/// The help messages for a Stacked Borrows error, whether it halts execution or not.
+#[rustfmt::skip]
+fn stacked_borrows_helps<'tcx, 'mir>(
+    ecx: &InterpCx<'mir, 'tcx, Evaluator<'mir, 'tcx>>,
+    history: &Option<TagHistory>,
+    details: &SbErrorDetails,
+) -> Vec<(Option<SpanData>, String)> {
+    let url = "https://github.com/rust-lang/unsafe-code-guidelines/blob/master/wip/stacked-borrows.md";
+    let mut helps = vec![
+        (None, format!("this indicates a potential bug in the program: it performed an invalid operation, but the Stacked Borrows rules it violated are still experimental")),
+        (None, format!("see {url} for further information")),
+    ];
+    if let Some(TagHistory {created, invalidated, protected, protector_call}) = history.clone() {
+        helps.push((Some(created.1), created.0));
+        if let Some((msg, span)) = invalidated {
+            helps.push((Some(span), msg));
+        }
+        if let Some((protector_msg, protector_span)) = protected {
+            helps.push((Some(protector_span), protector_msg));
+        }
+        if let Some((call_msg, call_span)) = protector_call {
+            helps.push((Some(call_span), call_msg));
+        }
+    }
+    for (msg, span) in &details.exposures {
+        helps.push((Some(*span), msg.clone()));
+    }
+    if details.history_truncated {
+        helps.push((None, format!("some of the history of this allocation was discarded due to `-Zmiri-stacked-borrows-history-limit`, so the information above may be incomplete")));
+    }
+    if ecx.machine.verbose_stacked_borrows {
+        if let Some(stack) = &details.stack {
+            helps.push((None, format!(
+                "the borrow stack at {:?}[{:#x}] is, from bottom to top:",
+                details.alloc_id,
+                details.offset.bytes(),
+            )));
+            if let Some(bottom) = stack.unknown_bottom {
+                helps.push((None, format!("    unknown items with tags below {bottom:?}")));
+            }
+            for item in &stack.items {
+                let protected = if item.protected() { " (protected)" } else { "" };
+                helps.push((None, format!("    {item:?}{protected}")));
+            }
+        }
+    }
+    helps
+}
+
+/// The helps of a data race report, showing the backtrace of the other access if it is known.
+fn data_race_helps(
+    ecx: &InterpCx<'_, '_, Evaluator<'_, '_>>,
+    other_access: &str,
+    other_backtrace: &[(String, SpanData, bool)],
+    history: &[(String, SpanData)],
+) -> Vec<(Option<SpanData>, String)> {
+    let mut helps = vec![
+        (None, format!("this indicates a bug in the program: it performed an invalid operation, and caused Undefined Behavior")),
+        (None, format!("see https://doc.rust-lang.org/nightly/reference/behavior-considered-undefined.html for further information")),
+    ];
+    // Like `prune_stacktrace`, only show the local frames unless a full backtrace was requested.
+    let has_local_frame = other_backtrace.iter().any(|(_, _, local)| *local);
+    let frames = other_backtrace.iter().filter(|(_, _, local)| {
+        *local || !has_local_frame || ecx.machine.backtrace_style == BacktraceStyle::Full
+    });
+    let source_map = ecx.tcx.sess.source_map();
+    for (idx, (instance, span, _)) in frames.enumerate() {
+        if idx == 0 {
+            helps.push((Some(*span), format!("the {other_access} happened here")));
+        }
+        let location = source_map.span_to_diagnostic_string(span.span());
+        helps.push((None, format!("inside `{instance}` at {location}")));
+    }
+    if !history.is_empty() {
+        helps.push((None, format!("the last accesses to this location were, oldest first:")));
+    }
+    for (access, span) in history {
+        let location = source_map.span_to_diagnostic_string(span.span());
+        helps.push((None, format!("{access} at {location}")));
+    }
+    helps
+}
+
+/// Explain a deadlock: if some threads wait for each other in a cycle, show the cycle, and
+/// otherwise show what all the blocked threads are waiting for.
+fn deadlock_helps(ecx: &InterpCx<'_, '_, Evaluator<'_, '_>>) -> Vec<(Option<SpanData>, String)> {
+    let threads = &ecx.machine.threads;
+    let source_map = ecx.tcx.sess.source_map();
+    let name = |thread: ThreadId| threads.describe_thread(thread);
+    // Describe what `thread` is waiting for. In a cycle, `next` is the next thread of the cycle,
+    // and only that holder is shown.
+    let describe = |thread: ThreadId, blocked_on: BlockedOn, next: Option<ThreadId>| {
+        let location = source_map.span_to_diagnostic_string(ecx.machine.thread_span(thread));
+        match blocked_on {
+            BlockedOn::Join(joined) =>
+                format!(
+                    "{} is waiting for {} to terminate at {location}",
+                    name(thread),
+                    name(joined)
+                ),
+            BlockedOn::Sync(what, holders) => {
+                let holders = holders
+                    .into_iter()
+                    .filter(|(holder, _)| next.map_or(true, |next| next == *holder))
+                    .map(|(holder, acquired_at)| match acquired_at {
+                        Some(span) =>
+                            format!(
+                                "{} (acquired at {})",
+                                name(holder),
+                                source_map.span_to_diagnostic_string(span)
+                            ),
+                        None => name(holder),
+                    })
+                    .collect::<Vec<_>>();
+                let mut msg = format!("{} is waiting for {what} at {location}", name(thread));
+                if !holders.is_empty() {
+                    msg.push_str(&format!(", which is held by {}", holders.join(" and ")));
+                }
+                msg
+            }
+        }
+    };
+
+    let mut helps = vec![];
+    if let Some(cycle) = threads.wait_cycle() {
+        helps.push((None, format!("these threads are waiting for each other:")));
+        for (idx, &thread) in cycle.iter().enumerate() {
+            let next = cycle[(idx + 1) % cycle.len()];
+            let blocked_on = threads.blocked_on(thread).unwrap();
+            helps.push((None, describe(thread, blocked_on, Some(next))));
+        }
+    } else {
+        for (thread, blocked_on) in threads.blocked_threads() {
+            helps.push((None, describe(thread, blocked_on, None)));
+        }
+    }
+    helps
+}
+
 /// Emit a custom diagnostic without going through the miri-engine machinery
 pub fn report_error<'tcx, 'mir>(
     ecx: &InterpCx<'mir, 'tcx, Evaluator<'mir, 'tcx>>,
@@ -162,7 +357,7 @@ pub fn report_error<'tcx, 'mir>(
                 Abort(_) => Some("abnormal termination"),
                 UnsupportedInIsolation(_) | Int2PtrWithStrictProvenance =>
                     Some("unsupported operation"),
-                StackedBorrowsUb { .. } => Some("Undefined Behavior"),
+                StackedBorrowsUb { .. } | DataRace { .. } => Some("Undefined Behavior"),
                 Deadlock => Some("deadlock"),
                 MultipleSymbolDefinitions { .. } | SymbolShimClashing { .. } => None,
             };
@@ -173,24 +368,32 @@ pub fn report_error<'tcx, 'mir>(
                         (None, format!("pass the flag `-Zmiri-disable-isolation` to disable isolation;")),
                         (None, format!("or pass `-Zmiri-isolation-error=warn` to configure Miri to return an error code from isolated operations (if supported for that operation) and continue with a warning")),
                     ],
-                StackedBorrowsUb { help, history, .. } => {
-                    let url = "https://github.com/rust-lang/unsafe-code-guidelines/blob/master/wip/stacked-borrows.md";
-                    msg.extend(help.clone());
-                    let mut helps = vec![
-                        (None, format!("this indicates a potential bug in the program: it performed an invalid operation, but the Stacked Borrows rules it violated are still experimental")),
-                        (None, format!("see {url} for further information")),
-                    ];
-                    if let Some(TagHistory {created, invalidated, protected}) = history.clone() {
-                        helps.push((Some(created.1), created.0));
-                        if let Some((msg, span)) = invalidated {
-                            helps.push((Some(span), msg));
-                        }
-                        if let Some((protector_msg, protector_span)) = protected {
-                            helps.push((Some(protector_span), protector_msg));
+                StackedBorrowsUb { msg: sb_msg, help, history, details } => {
+                    if let Some(path) = &ecx.machine.stacked_borrows_json {
+                        let json = sb_error_json(
+                            sb_msg,
+                            help.as_deref(),
+                            history.as_ref(),
+                            details,
+                            ecx.tcx.sess.source_map(),
+                        );
+                        match path {
+                            None => eprintln!("{json}"),
+                            Some(path) =>
+                                if let Err(err) = std::fs::write(path, format!("{json}\n")) {
+                                    ecx.tcx.sess.warn(format!(
+                                        "failed to write Stacked Borrows error to `{}`: {err}",
+                                        path.display()
+                                    ));
+                                },
                         }
                     }
-                    helps
+                    msg.extend(help.clone());
+                    stacked_borrows_helps(ecx, history, details)
                 }
+                DataRace { other_access, other_backtrace, history, .. } =>
+                    data_race_helps(ecx, other_access, other_backtrace, history),
+                Deadlock => deadlock_helps(ecx),
                 MultipleSymbolDefinitions { first, first_crate, second, second_crate, .. } =>
                     vec![
                         (Some(*first), format!("it's first defined here, in crate `{first_crate}`")),
@@ -337,6 +540,9 @@ fn report_msg<'mir, 'tcx>(
     }
 
     // Show note and help messages.
+    for span_data in notes.iter().chain(&helps).filter_map(|(span_data, _)| *span_data) {
+        load_source(sess.source_map(), span_data.span());
+    }
     for (span_data, note) in &notes {
         if let Some(span_data) = span_data {
             err.span_note(span_data.span(), note);
@@ -351,8 +557,13 @@ fn report_msg<'mir, 'tcx>(
             err.help(help);
         }
     }
-    if notes.len() + helps.len() > 0 {
-        // Add visual separator before backtrace.
+    // Add visual separator before backtrace. In multi-threaded programs, say which thread the
+    // backtrace belongs to.
+    let thread = ecx.get_active_thread();
+    if thread != MAIN_THREAD {
+        let name = ecx.machine.threads.describe_thread(thread);
+        err.note(&format!("BACKTRACE on {name}:"));
+    } else if notes.len() + helps.len() > 0 {
         err.note("BACKTRACE:");
     }
     // Add backtrace
@@ -369,6 +580,22 @@ fn report_msg<'mir, 'tcx>(
     err.emit();
 }
 
+/// Make sure the source of the file containing `span` is loaded, so that the span is rendered with a
+/// snippet instead of just a path. rustc only looks for the source of files from other crates at
+/// their local path, which is not always recorded in the crate metadata, e.g. for dependencies
+/// built by cargo; but the path that is recorded often still exists on this machine.
+fn load_source(source_map: &SourceMap, span: Span) {
+    let file = source_map.lookup_source_file(span.lo());
+    if source_map.ensure_source_file_source_present(file.clone()) {
+        return;
+    }
+    if let FileName::Real(name) = &file.name {
+        // This checks the hash of the source, so we cannot accidentally pick up a different file.
+        let path = name.local_path_if_available();
+        file.add_external_src(|| std::fs::read_to_string(path).ok());
+    }
+}
+
 thread_local! {
     static DIAGNOSTICS: RefCell<Vec<NonHaltingDiagnostic>> = RefCell::new(Vec::new());
 }
@@ -453,6 +680,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                     RejectedIsolatedOp(_) =>
                         ("operation rejected by isolation", DiagLevel::Warning),
                     Int2Ptr { .. } => ("integer-to-pointer cast", DiagLevel::Warning),
+                    StubbedForeignItem(_) =>
+                        ("stubbed out missing foreign function", DiagLevel::Warning),
+                    StackedBorrowsUb { .. } | DataRace { .. } =>
+                        ("Undefined Behavior (execution continues)", DiagLevel::Warning),
+                    LockOrderInversion { .. } => ("potential deadlock", DiagLevel::Warning),
+                    Livelock { .. } => ("possible livelock", DiagLevel::Warning),
                     CreatedPointerTag(..)
                     | PoppedPointerTag(..)
                     | CreatedCallId(..)
@@ -461,6 +694,10 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                     | ProgressReport { .. }
                     | WeakMemoryOutdatedLoad =>
                         ("tracking was triggered", DiagLevel::Note),
+                    InjectedFsFailure(..) => ("injected file system failure", DiagLevel::Note),
+                    FailedProcessCreation(_) =>
+                        ("process creation is not supported", DiagLevel::Note),
+                    Syslog(_) => ("syslog message", DiagLevel::Note),
                 };
 
                 let msg = match e {
@@ -498,6 +735,19 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                         format!("integer-to-pointer cast"),
                     WeakMemoryOutdatedLoad =>
                         format!("weak memory emulation: outdated value returned from load"),
+                    StubbedForeignItem(link_name) =>
+                        format!("can't call foreign function `{link_name}`, returning zero instead"),
+                    InjectedFsFailure(link_name, ref errno) =>
+                        format!("`{link_name}` was made to fail with `{errno}`"),
+                    FailedProcessCreation(link_name) =>
+                        format!("`{link_name}` was made to fail with `EAGAIN`"),
+                    Syslog(ref message) => message.clone(),
+                    StackedBorrowsUb { ref msg, .. } | DataRace { ref msg, .. } =>
+                        msg.clone(),
+                    LockOrderInversion { ref thread, ref other_thread, .. } =>
+                        format!("lock-order inversion: thread `{thread}` is locking a mutex while holding another one, but thread `{other_thread}` acquired them in the opposite order"),
+                    Livelock { ref thread, steps } =>
+                        format!("thread `{thread}` has executed {steps} basic blocks without synchronizing with other threads"),
                 };
 
                 let notes = match e {
@@ -521,6 +771,38 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                             (None, format!("You can then pass the `-Zmiri-strict-provenance` flag to Miri, to ensure you are not relying on `from_exposed_addr` semantics.")),
                             (None, format!("Alternatively, the `-Zmiri-permissive-provenance` flag disables this warning.")),
                         ],
+                    StackedBorrowsUb { ref help, ref history, ref details, .. } => {
+                        let mut helps: Vec<_> =
+                            help.iter().map(|help| (None, help.clone())).collect();
+                        helps.extend(stacked_borrows_helps(this, history, details));
+                        helps.push((None, format!("due to `-Zmiri-sb-report-only`, execution continues as if this operation was allowed; afterwards, all existing pointers may be used for the affected memory")));
+                        helps
+                    }
+                    DataRace { ref other_access, ref other_backtrace, ref history, .. } => {
+                        let mut helps =
+                            data_race_helps(this, other_access, other_backtrace, history);
+                        helps.push((None, format!("due to `-Zmiri-race-report-only`, execution continues as if this access did not race; later races may be consequences of this one")));
+                        helps
+                    }
+                    LockOrderInversion {
+                        ref other_thread,
+                        held_at,
+                        other_first,
+                        other_second,
+                        ..
+                    } =>
+                        vec![
+                            (Some(held_at), format!("the mutex that is being held was acquired here")),
+                            (Some(other_first), format!("thread `{other_thread}` acquired the mutex that is being locked here")),
+                            (Some(other_second), format!("and then acquired the mutex that is being held here")),
+                            (None, format!("if two threads acquire these mutexes concurrently in the two orders, they can deadlock")),
+                        ],
+                    Livelock { .. } =>
+                        vec![
+                            (None, format!("the thread keeps loading atomics while other threads are waiting to run; if it is spinning until another thread changes one of them, that might never happen")),
+                            (None, format!("use `std::thread::yield_now` or `std::hint::spin_loop` in spin loops to let other threads run")),
+                            (None, format!("this check can be configured with `-Zmiri-livelock-threshold`")),
+                        ],
                     _ => vec![],
                 };
 
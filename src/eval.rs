@@ -2,9 +2,13 @@
 
 use std::ffi::{OsStr, OsString};
 use std::iter;
+use std::mem;
+use std::net::Ipv4Addr;
 use std::panic::{self, AssertUnwindSafe};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 
 use log::info;
 
@@ -70,6 +74,24 @@ pub enum BacktraceStyle {
     Off,
 }
 
+/// The error codes that `-Zmiri-fs-fail-errno` accepts. These exist on all Unix targets.
+pub const FS_FAIL_ERRNOS: &[&str] = &[
+    "EACCES", "EAGAIN", "EBUSY", "EDQUOT", "EEXIST", "EFBIG", "EINTR", "EIO", "EMFILE", "ENFILE",
+    "ENOENT", "ENOMEM", "ENOSPC", "EPERM", "EROFS",
+];
+
+/// The resources whose limits `getrlimit` and `setrlimit` report, by the name of their `RLIMIT_*`
+/// constant, with their default soft and hard limit. `u64::MAX` means unlimited.
+pub const RLIMITS: &[(&str, u64, u64)] = &[
+    ("RLIMIT_AS", u64::MAX, u64::MAX),
+    ("RLIMIT_CORE", 0, u64::MAX),
+    ("RLIMIT_CPU", u64::MAX, u64::MAX),
+    ("RLIMIT_DATA", u64::MAX, u64::MAX),
+    ("RLIMIT_FSIZE", u64::MAX, u64::MAX),
+    ("RLIMIT_NOFILE", 1024, 4096),
+    ("RLIMIT_STACK", 8 * 1024 * 1024, u64::MAX),
+];
+
 /// Configuration needed to spawn a Miri instance.
 #[derive(Clone)]
 pub struct MiriConfig {
@@ -88,16 +110,24 @@ pub struct MiriConfig {
     pub isolated_op: IsolatedOp,
     /// Determines if memory leaks should be ignored.
     pub ignore_leaks: bool,
+    /// Determines if file descriptors and directory streams that are still open at the end of
+    /// the program should be reported.
+    pub check_fd_leaks: bool,
     /// Environment variables that should always be isolated from the host.
     pub excluded_env_vars: Vec<String>,
     /// Environment variables that should always be forwarded from the host.
     pub forwarded_env_vars: Vec<String>,
+    /// Determines if `setenv` and `unsetenv` also change the environment of the host. The host
+    /// environment is restored when the program ends.
+    pub env_write_through: bool,
     /// Command-line arguments passed to the interpreted program.
     pub args: Vec<String>,
     /// The seed to use when non-determinism or randomness are required (e.g. ptr-to-int cast, `getrandom()`).
     pub seed: Option<u64>,
     /// The stacked borrows pointer ids to report about
     pub tracked_pointer_tags: FxHashSet<SbTag>,
+    /// Report about all stacked borrows pointer ids created at these places.
+    pub tracked_tag_sites: Vec<TagCreationSite>,
     /// The stacked borrows call IDs to report about
     pub tracked_call_ids: FxHashSet<CallId>,
     /// The allocation ids to report about.
@@ -108,6 +138,17 @@ pub struct MiriConfig {
     pub weak_memory_emulation: bool,
     /// Track when an outdated (weak memory) load happens.
     pub track_outdated_loads: bool,
+    /// Record backtraces of memory accesses to show both sides of a data race.
+    pub data_race_backtraces: bool,
+    /// How many of the last accesses to each location to show when a data race is reported.
+    pub data_race_history: Option<usize>,
+    /// Report data races as warnings instead of halting execution.
+    pub race_report_only: bool,
+    /// Warn when two mutexes are acquired in opposite orders.
+    pub lock_order_check: bool,
+    /// If `Some`, a Graphviz graph of the happens-before relation between the synchronization
+    /// events of all threads is written to this file at the end of the run.
+    pub export_hb: Option<PathBuf>,
     /// Rate of spurious failures for compare_exchange_weak atomic operations,
     /// between 0.0 and 1.0, defaulting to 0.8 (80% chance of failure).
     pub cmpxchg_weak_failure_rate: f64,
@@ -116,6 +157,8 @@ pub struct MiriConfig {
     pub measureme_out: Option<String>,
     /// Panic when unsupported functionality is encountered.
     pub panic_on_unsupported: bool,
+    /// Make calls to unsupported foreign functions return zero, with a warning.
+    pub extern_stub_missing: bool,
     /// Which style to use for printing backtraces.
     pub backtrace_style: BacktraceStyle,
     /// Which provenance to use for int2ptr casts
@@ -123,8 +166,62 @@ pub struct MiriConfig {
     /// Whether to ignore any output by the program. This is helpful when debugging miri
     /// as its messages don't get intermingled with the program messages.
     pub mute_stdout_stderr: bool,
+    /// If `Some`, the input that the interpreted program reads from stdin, instead of reading
+    /// the host's stdin. This also works with isolation.
+    pub stdin: Option<Vec<u8>>,
+    /// If `Some`, whether the standard streams are terminals, instead of asking the host.
+    pub isatty: Option<bool>,
+    /// If `Some`, the window size of terminals as columns and rows, instead of asking the host.
+    pub terminal_size: Option<(u16, u16)>,
+    /// If `Some`, the N-th file system call (counting from 1) fails with `fs_fail_errno`.
+    pub fs_fail_nth: Option<u64>,
+    /// The probability of each file system call failing with `fs_fail_errno`, between 0.0 and
+    /// 1.0, defaulting to 0.0.
+    pub fs_fail_rate: f64,
+    /// The name of the error code that injected file system failures return, defaulting to `EIO`.
+    pub fs_fail_errno: String,
+    /// Soft and hard limits that replace the defaults in `RLIMITS`.
+    pub rlimits: Vec<(&'static str, u64, u64)>,
+    /// Whether opening more file descriptors than the soft `RLIMIT_NOFILE` limit allows fails.
+    pub enforce_rlimit_nofile: bool,
+    /// Whether `fork` and `posix_spawn` fail with `EAGAIN` rather than being reported as
+    /// unsupported.
+    pub fork_fails: bool,
+    /// Whether `socket` can create TCP sockets that are connected to each other through the
+    /// loopback address.
+    pub loopback_network: bool,
+    /// Host names that `getaddrinfo` resolves, in addition to `localhost`.
+    pub hosts: Vec<(String, Ipv4Addr)>,
+    /// The user ID of the process under isolation.
+    pub uid: u32,
+    /// The group ID of the process under isolation.
+    pub gid: u32,
+    /// The number of CPUs that the interpreted program sees.
+    pub num_cpus: u32,
+    /// The offset of the local time zone from UTC under isolation, in seconds east of UTC.
+    pub tz_offset: i64,
+    /// Use the virtual clock even if isolation is disabled.
+    pub virtual_clock: bool,
+    /// How many nanoseconds the virtual clock advances per basic block.
+    pub clock_tick_per_block: u64,
+    /// How many nanoseconds the virtual clock advances whenever the program reads it.
+    pub clock_tick_per_read: u64,
     /// The probability of the active thread being preempted at the end of each basic block.
     pub preemption_rate: f64,
+    /// How the scheduler picks the next thread to run.
+    pub scheduler_policy: SchedulerPolicy,
+    /// Run the destructors of pthread TLS keys in a random order, based on the seed.
+    pub random_tls_dtor_order: bool,
+    /// Report a possible livelock when a thread executes this many basic blocks without
+    /// synchronization progress. `0` disables the check.
+    pub livelock_threshold: u64,
+    /// Explore the thread schedules of the program with dynamic partial-order reduction.
+    pub dpor: bool,
+    /// The maximum number of executions of the schedule exploration.
+    pub dpor_max_executions: u64,
+    /// If `Some`, the thread that is picked at each scheduling point, to replay a schedule
+    /// found by the schedule exploration.
+    pub replay_schedule: Option<Vec<u32>>,
     /// Report the current instruction being executed every N basic blocks.
     pub report_progress: Option<u32>,
     /// Whether Stacked Borrows retagging should recurse into fields of datatypes.
@@ -132,8 +229,39 @@ pub struct MiriConfig {
     /// The location of a shared object file to load when calling external functions
     /// FIXME! consider allowing users to specify paths to multiple SO files, or to a directory
     pub external_so_file: Option<PathBuf>,
+    /// The path of the type that represents C's `long double` in calls into the external shared
+    /// object file, e.g. `my_crate::ffi::LongDouble`.
+    pub external_so_long_double: Option<String>,
+    /// Abort if a call into the external shared object file takes longer than this.
+    pub external_call_timeout: Option<Duration>,
+    /// If `Some`, the foreign function calls are counted here, across all the runs of
+    /// `-Zmiri-many-seeds` and `-Zmiri-dpor`. The driver prints the summary in the end.
+    pub external_call_summary: Option<Arc<Mutex<shims::ffi_support::ExternCallSummary>>>,
     /// Run a garbage collector for SbTags every N basic blocks.
     pub gc_interval: u32,
+    /// Print statistics about the SbTag GC at the end of the run.
+    pub tag_gc_stats: bool,
+    /// Keep only this many recent events of each kind in the Stacked Borrows history of an
+    /// allocation.
+    pub stacked_borrows_history_limit: Option<usize>,
+    /// Maintain an index of the position of every tag in every borrow stack.
+    pub sb_tag_index: bool,
+    /// If `Some`, Stacked Borrows errors are additionally written as JSON, to the given file or to
+    /// stderr.
+    pub stacked_borrows_json: Option<Option<PathBuf>>,
+    /// Show the entire borrow stack of the affected location in Stacked Borrows errors.
+    pub verbose_stacked_borrows: bool,
+    /// If `Some`, a Graphviz graph of the Stacked Borrows history of all allocations is written
+    /// to this file at the end of the run.
+    pub sb_trace_out: Option<PathBuf>,
+    /// Report Stacked Borrows errors as warnings and continue execution.
+    pub sb_report_only: bool,
+    /// Let all raw pointer retags grant `SharedReadWrite`, also for `*const`.
+    pub sb_permissive_raw_ptrs: bool,
+    /// Print statistics about Stacked Borrows at the end of the run.
+    pub sb_stats: bool,
+    /// Do not perform Stacked Borrows retags in code from these crates.
+    pub retag_disabled_crates: Vec<String>,
 }
 
 impl Default for MiriConfig {
@@ -146,27 +274,73 @@ impl Default for MiriConfig {
             check_abi: true,
             isolated_op: IsolatedOp::Reject(RejectOpWith::Abort),
             ignore_leaks: false,
+            check_fd_leaks: false,
             excluded_env_vars: vec![],
             forwarded_env_vars: vec![],
+            env_write_through: false,
             args: vec![],
             seed: None,
             tracked_pointer_tags: FxHashSet::default(),
+            tracked_tag_sites: Vec::new(),
             tracked_call_ids: FxHashSet::default(),
             tracked_alloc_ids: FxHashSet::default(),
             data_race_detector: true,
             weak_memory_emulation: true,
             track_outdated_loads: false,
+            data_race_backtraces: false,
+            data_race_history: None,
+            race_report_only: false,
+            lock_order_check: false,
+            export_hb: None,
             cmpxchg_weak_failure_rate: 0.8, // 80%
             measureme_out: None,
             panic_on_unsupported: false,
+            extern_stub_missing: false,
             backtrace_style: BacktraceStyle::Short,
             provenance_mode: ProvenanceMode::Default,
             mute_stdout_stderr: false,
+            stdin: None,
+            isatty: None,
+            terminal_size: None,
+            fs_fail_nth: None,
+            fs_fail_rate: 0.0,
+            fs_fail_errno: "EIO".to_owned(),
+            rlimits: Vec::new(),
+            enforce_rlimit_nofile: false,
+            fork_fails: false,
+            loopback_network: false,
+            hosts: Vec::new(),
+            uid: 1000,
+            gid: 1000,
+            num_cpus: 1,
+            tz_offset: 0,
+            virtual_clock: false,
+            clock_tick_per_block: 10,
+            clock_tick_per_read: 0,
             preemption_rate: 0.01, // 1%
+            scheduler_policy: SchedulerPolicy::RoundRobin,
+            livelock_threshold: 0,
+            random_tls_dtor_order: false,
+            dpor: false,
+            dpor_max_executions: 1000,
+            replay_schedule: None,
             report_progress: None,
             retag_fields: false,
             external_so_file: None,
+            external_so_long_double: None,
+            external_call_timeout: None,
+            external_call_summary: None,
             gc_interval: 10_000,
+            tag_gc_stats: false,
+            stacked_borrows_history_limit: None,
+            sb_tag_index: false,
+            stacked_borrows_json: None,
+            verbose_stacked_borrows: false,
+            sb_trace_out: None,
+            sb_report_only: false,
+            sb_permissive_raw_ptrs: false,
+            sb_stats: false,
+            retag_disabled_crates: vec![],
         }
     }
 }
@@ -331,6 +505,8 @@ pub fn create_ecx<'mir, 'tcx: 'mir>(
 /// Evaluates the entry function specified by `entry_id`.
 /// Returns `Some(return_code)` if program executed completed.
 /// Returns `None` if an evaluation error occurred.
+/// With `-Zmiri-dpor`, the program is executed once for every schedule that is explored,
+/// and the result of the first failing execution is returned.
 #[allow(clippy::needless_lifetimes)]
 pub fn eval_entry<'tcx>(
     tcx: TyCtxt<'tcx>,
@@ -338,16 +514,53 @@ pub fn eval_entry<'tcx>(
     entry_type: EntryFnType,
     config: MiriConfig,
 ) -> Option<i64> {
-    // Copy setting before we move `config`.
+    let backend: Box<dyn SchedulingBackend<'tcx>> = if config.dpor {
+        Box::new(Schedule::new(config.dpor_max_executions))
+    } else if let Some(choices) = &config.replay_schedule {
+        Box::new(Schedule::replay(choices))
+    } else {
+        Box::new(PolicyScheduler::new(config.scheduler_policy))
+    };
+    eval_entry_with_backend(tcx, entry_id, entry_type, config, backend)
+}
+
+/// Like `eval_entry`, but the thread interleavings are explored by `backend`: the program is
+/// executed as long as the backend asks for another execution, and the result of the last
+/// execution is returned.
+pub fn eval_entry_with_backend<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    entry_id: DefId,
+    entry_type: EntryFnType,
+    config: MiriConfig,
+    mut backend: Box<dyn SchedulingBackend<'tcx>>,
+) -> Option<i64> {
+    loop {
+        let res = eval_entry_once(tcx, entry_id, entry_type, &config, &mut backend);
+        if !backend.execution_end(tcx.sess, res != Some(0)) {
+            return res;
+        }
+    }
+}
+
+/// Evaluates the entry function once, scheduling threads with `backend`. The backend is handed
+/// back after the execution, so that it can prepare the next one.
+fn eval_entry_once<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    entry_id: DefId,
+    entry_type: EntryFnType,
+    config: &MiriConfig,
+    backend: &mut Box<dyn SchedulingBackend<'tcx>>,
+) -> Option<i64> {
     let ignore_leaks = config.ignore_leaks;
 
-    let (mut ecx, ret_place) = match create_ecx(tcx, entry_id, entry_type, &config) {
+    let (mut ecx, ret_place) = match create_ecx(tcx, entry_id, entry_type, config) {
         Ok(v) => v,
         Err(err) => {
             err.print_backtrace();
             panic!("Miri initialization error: {}", err.kind())
         }
     };
+    mem::swap(ecx.machine.scheduler.get_mut(), backend);
 
     // Perform the main execution.
     let res: thread::Result<InterpResult<'_, i64>> = panic::catch_unwind(AssertUnwindSafe(|| {
@@ -359,11 +572,6 @@ pub fn eval_entry<'tcx>(
                     assert!(ecx.step()?, "a terminated thread was scheduled for execution");
                 }
                 SchedulingAction::ExecuteTimeoutCallback => {
-                    assert!(
-                        ecx.machine.communicate(),
-                        "scheduler callbacks require disabled isolation, but the code \
-                        that created the callback did not check it"
-                    );
                     ecx.run_timeout_callback()?;
                 }
                 SchedulingAction::ExecuteDtors => {
@@ -385,6 +593,10 @@ pub fn eval_entry<'tcx>(
         ecx.handle_ice();
         panic::resume_unwind(panic_payload)
     });
+    mem::swap(ecx.machine.scheduler.get_mut(), backend);
+
+    // Undo the changes to the host environment, no matter how the program ended.
+    ecx.machine.env_vars.restore_host();
 
     // Machine cleanup. Only do this if all threads have terminated; threads that are still running
     // might cause Stacked Borrows errors (https://github.com/rust-lang/miri/issues/2396).
@@ -396,6 +608,26 @@ pub fn eval_entry<'tcx>(
         EnvVars::cleanup(&mut ecx).expect("error during env var cleanup");
     }
 
+    if let Some(stacked_borrows) = &ecx.machine.stacked_borrows {
+        stacked_borrows.borrow().print_stats(ecx.tcx.sess.source_map());
+        stacked_borrows.borrow().print_repeated_errors(ecx.tcx.sess.source_map());
+    }
+    if let Some(data_race) = &ecx.machine.data_race {
+        data_race.print_race_report(ecx.tcx.sess.source_map());
+        if let Some(out) = &config.export_hb {
+            if let Err(err) = data_race.write_hb_graph(out, &ecx.machine.threads) {
+                ecx.tcx.sess.warn(format!(
+                    "failed to write happens-before graph to `{}`: {err}",
+                    out.display()
+                ));
+            }
+        }
+    }
+    if let Some(stats) = &ecx.machine.tag_gc_stats {
+        stats.print();
+    }
+    ecx.write_sb_trace();
+
     // Process the result.
     match res {
         Ok(return_code) => {
@@ -419,6 +651,23 @@ pub fn eval_entry<'tcx>(
                     return None;
                 }
             }
+            if config.check_fd_leaks {
+                // Check for file descriptor leaks.
+                let fds = ecx.machine.file_handler.open_fds();
+                let dirs = ecx.machine.dir_handler.open_streams();
+                if !fds.is_empty() || !dirs.is_empty() {
+                    let mut err =
+                        tcx.sess.struct_err("the evaluated program leaked file descriptors");
+                    for (fd, span) in fds {
+                        err.span_note(span, &format!("file descriptor {fd} was opened here"));
+                    }
+                    for span in dirs {
+                        err.span_note(span, "directory stream was opened here");
+                    }
+                    err.emit();
+                    return None;
+                }
+            }
             Some(return_code)
         }
         Err(e) => report_error(&ecx, e),
@@ -1,5 +1,6 @@
 pub mod convert;
 
+use std::iter;
 use std::mem;
 use std::num::NonZeroUsize;
 use std::time::Duration;
@@ -13,7 +14,10 @@ use rustc_middle::ty::{
     layout::{LayoutOf, TyAndLayout},
     List, TyCtxt,
 };
-use rustc_span::{def_id::CrateNum, sym, Span, Symbol};
+use rustc_span::{
+    def_id::{CrateNum, LOCAL_CRATE},
+    sym, Span, Symbol,
+};
 use rustc_target::abi::{Align, FieldsShape, Size, Variants};
 use rustc_target::spec::abi::Abi;
 
@@ -592,6 +596,10 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 match err_kind {
                     NotFound => "ERROR_FILE_NOT_FOUND",
                     PermissionDenied => "ERROR_ACCESS_DENIED",
+                    AlreadyExists => "ERROR_FILE_EXISTS",
+                    InvalidInput => "ERROR_INVALID_PARAMETER",
+                    BrokenPipe => "ERROR_BROKEN_PIPE",
+                    TimedOut => "ERROR_TIMEOUT",
                     _ =>
                         throw_unsup_format!(
                             "io error {:?} cannot be translated into a raw os error",
@@ -887,6 +895,16 @@ impl<'mir, 'tcx> Evaluator<'mir, 'tcx> {
     pub fn current_span(&self, tcx: TyCtxt<'tcx>) -> CurrentSpan<'_, 'mir, 'tcx> {
         CurrentSpan { current_frame_idx: None, machine: self, tcx }
     }
+
+    /// Like `CurrentSpan::get`, but for the stack of any thread. This does not search the stack,
+    /// so it is cheap enough to be called on every synchronization operation.
+    pub fn thread_span(&self, thread: ThreadId) -> Span {
+        self.threads
+            .thread_stack(thread)
+            .get(self.threads.local_frame_index(thread))
+            .map(Frame::current_span)
+            .unwrap_or(rustc_span::DUMMY_SP)
+    }
 }
 
 /// A `CurrentSpan` should be created infrequently (ideally once) per interpreter step. It does
@@ -935,24 +953,33 @@ impl<'a, 'mir: 'a, 'tcx: 'a + 'mir> CurrentSpan<'a, 'mir, 'tcx> {
     // compiled/executed, part of the Cargo workspace, and is also not #[track_caller].
     #[inline(never)]
     fn compute_current_frame_index(tcx: TyCtxt<'_>, machine: &Evaluator<'_, '_>) -> usize {
-        machine
-            .threads
-            .active_thread_stack()
+        Self::local_frame_index(tcx, machine, machine.threads.active_thread_stack())
+    }
+
+    fn local_frame_index(
+        tcx: TyCtxt<'_>,
+        machine: &Evaluator<'_, '_>,
+        stack: &[Frame<'_, '_, Provenance, FrameData<'_>>],
+    ) -> usize {
+        stack
             .iter()
             .enumerate()
             .rev()
-            .find_map(|(idx, frame)| {
-                let def_id = frame.instance.def_id();
-                if (def_id.is_local() || machine.local_crates.contains(&def_id.krate))
-                    && !frame.instance.def.requires_caller_location(tcx)
-                {
-                    Some(idx)
-                } else {
-                    None
-                }
-            })
+            .find_map(|(idx, frame)| Self::is_local_frame(tcx, machine, frame).then_some(idx))
             .unwrap_or(0)
     }
+
+    /// Whether `frame` is part of the crate being compiled/executed or of the Cargo workspace,
+    /// and is not #[track_caller].
+    pub fn is_local_frame(
+        tcx: TyCtxt<'_>,
+        machine: &Evaluator<'_, '_>,
+        frame: &Frame<'_, '_, Provenance, FrameData<'_>>,
+    ) -> bool {
+        let def_id = frame.instance.def_id();
+        (def_id.is_local() || machine.local_crates.contains(&def_id.krate))
+            && !frame.instance.def.requires_caller_location(tcx)
+    }
 }
 
 /// Check that the number of args is what we expect.
@@ -994,8 +1021,48 @@ pub fn get_local_crates(tcx: TyCtxt<'_>) -> Vec<CrateNum> {
     local_crates
 }
 
+/// Convert the crate names passed to `-Zmiri-disable-retag-for-crate` into `CrateNum`s. Names that
+/// do not refer to any crate of the program are reported with a warning.
+pub fn get_retag_disabled_crates(tcx: TyCtxt<'_>, names: &[String]) -> Vec<CrateNum> {
+    let mut crates = Vec::new();
+    for name in names {
+        // Cargo package names may contain `-`, but crate names use `_` instead.
+        let name = name.replace('-', "_");
+        let len = crates.len();
+        crates.extend(
+            iter::once(LOCAL_CRATE)
+                .chain(tcx.crates(()).iter().copied())
+                .filter(|&krate| tcx.crate_name(krate).as_str() == name),
+        );
+        if crates.len() == len {
+            tcx.sess.warn(format!(
+                "-Zmiri-disable-retag-for-crate: the program does not use a crate called `{name}`"
+            ));
+        }
+    }
+    crates
+}
+
 /// Helper function used inside the shims of foreign functions to check that
 /// `target_os` is a supported UNIX OS.
 pub fn target_os_is_unix(target_os: &str) -> bool {
     matches!(target_os, "linux" | "macos" | "freebsd" | "android")
 }
+
+/// Escape `s` for a quoted string in a Graphviz file. Control characters are shown escaped, so
+/// that they cannot change the layout of the graph.
+pub fn escape_dot(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() =>
+                escaped.push_str(&c.escape_default().to_string().replace('\\', "\\\\")),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
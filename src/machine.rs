@@ -4,7 +4,8 @@
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::fmt;
-use std::time::Instant;
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
 
 use rand::rngs::StdRng;
 use rand::SeedableRng;
@@ -28,7 +29,13 @@ use rustc_target::spec::abi::Abi;
 
 use crate::{
     concurrency::{data_race, weak_memory},
-    shims::unix::FileHandler,
+    shims::{
+        ffi_support::EvalContextExt as _,
+        unix::{
+            DispatchEvalContextExt as _, FileHandler, Mapping, ResourceLimits,
+            SignalEvalContextExt as _,
+        },
+    },
     *,
 };
 
@@ -36,7 +43,6 @@ use crate::{
 pub const PAGE_SIZE: u64 = 4 * 1024; // FIXME: adjust to target architecture
 pub const STACK_ADDR: u64 = 32 * PAGE_SIZE; // not really about the "stack", but where we start assigning integer addresses to allocations
 pub const STACK_SIZE: u64 = 16 * PAGE_SIZE; // whatever
-pub const NUM_CPUS: u64 = 1;
 
 /// Extra data stored with each stack frame
 pub struct FrameData<'tcx> {
@@ -48,6 +54,19 @@ pub struct FrameData<'tcx> {
     /// we stop unwinding, use the `CatchUnwindData` to handle catching.
     pub catch_unwind: Option<CatchUnwindData<'tcx>>,
 
+    /// If this is Some(), then this is the frame of the initialization function called by
+    /// `dispatch_once_f`. When this frame returns, the predicate is marked as done.
+    pub dispatch_once: Option<shims::unix::DispatchOnceData<'tcx>>,
+
+    /// If this is Some(), then this is the frame of a signal handler. When this frame returns,
+    /// the signal mask of the thread is restored.
+    pub signal_handler: Option<shims::unix::SignalHandlerData>,
+
+    /// If this is Some(), then this is the frame of an `atexit` handler that runs because the
+    /// program called `exit` with this code. When this frame returns, the next handler runs, or
+    /// the program stops.
+    pub atexit_exit_code: Option<i64>,
+
     /// If `measureme` profiling is enabled, holds timing information
     /// for the start of this frame. When we finish executing this frame,
     /// we use this to register a completed event with `measureme`.
@@ -57,10 +76,20 @@ pub struct FrameData<'tcx> {
 impl<'tcx> std::fmt::Debug for FrameData<'tcx> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // Omitting `timing`, it does not support `Debug`.
-        let FrameData { stacked_borrows, catch_unwind, timing: _ } = self;
+        let FrameData {
+            stacked_borrows,
+            catch_unwind,
+            dispatch_once,
+            signal_handler,
+            atexit_exit_code,
+            timing: _,
+        } = self;
         f.debug_struct("FrameData")
             .field("stacked_borrows", stacked_borrows)
             .field("catch_unwind", catch_unwind)
+            .field("dispatch_once", dispatch_once)
+            .field("signal_handler", signal_handler)
+            .field("atexit_exit_code", atexit_exit_code)
             .finish()
     }
 }
@@ -89,6 +118,9 @@ pub enum MiriMemoryKind {
     /// Memory for thread-local statics.
     /// This memory may leak.
     Tls,
+    /// `mmap` memory.
+    /// This memory may leak.
+    Mmap,
 }
 
 impl From<MiriMemoryKind> for MemoryKind<MiriMemoryKind> {
@@ -104,7 +136,7 @@ impl MayLeak for MiriMemoryKind {
         use self::MiriMemoryKind::*;
         match self {
             Rust | C | WinHeap | Runtime => false,
-            Machine | Global | ExternStatic | Tls => true,
+            Machine | Global | ExternStatic | Tls | Mmap => true,
         }
     }
 }
@@ -121,6 +153,7 @@ impl fmt::Display for MiriMemoryKind {
             Global => write!(f, "global (static or const)"),
             ExternStatic => write!(f, "extern static"),
             Tls => write!(f, "thread-local static"),
+            Mmap => write!(f, "mmap'd memory"),
         }
     }
 }
@@ -251,6 +284,9 @@ pub struct AllocExtra {
     /// Weak memory emulation via the use of store buffers,
     ///  this is only added if it is enabled.
     pub weak_memory: Option<weak_memory::AllocExtra>,
+    /// Which pages of `mmap` memory are still mapped and how they are protected, since `munmap`
+    /// and `mprotect` can change parts of a mapping. This is only added for `mmap` memory.
+    pub mapping: Option<Mapping>,
 }
 
 /// Precomputed layouts of primitive types
@@ -263,6 +299,7 @@ pub struct PrimitiveLayouts<'tcx> {
     pub u8: TyAndLayout<'tcx>,
     pub u16: TyAndLayout<'tcx>,
     pub u32: TyAndLayout<'tcx>,
+    pub u64: TyAndLayout<'tcx>,
     pub usize: TyAndLayout<'tcx>,
     pub bool: TyAndLayout<'tcx>,
     pub mut_raw_ptr: TyAndLayout<'tcx>,   // *mut ()
@@ -283,12 +320,24 @@ impl<'mir, 'tcx: 'mir> PrimitiveLayouts<'tcx> {
             u8: layout_cx.layout_of(tcx.types.u8)?,
             u16: layout_cx.layout_of(tcx.types.u16)?,
             u32: layout_cx.layout_of(tcx.types.u32)?,
+            u64: layout_cx.layout_of(tcx.types.u64)?,
             usize: layout_cx.layout_of(tcx.types.usize)?,
             bool: layout_cx.layout_of(tcx.types.bool)?,
             mut_raw_ptr: layout_cx.layout_of(mut_raw_ptr)?,
             const_raw_ptr: layout_cx.layout_of(const_raw_ptr)?,
         })
     }
+
+    /// The layout of the unsigned integer type of the given size, if there is one.
+    pub fn uint(&self, size: Size) -> Option<TyAndLayout<'tcx>> {
+        match size.bits() {
+            8 => Some(self.u8),
+            16 => Some(self.u16),
+            32 => Some(self.u32),
+            64 => Some(self.u64),
+            _ => None,
+        }
+    }
 }
 
 /// The machine itself.
@@ -324,15 +373,57 @@ pub struct Evaluator<'mir, 'tcx> {
 
     /// The table of file descriptors.
     pub(crate) file_handler: shims::unix::FileHandler,
+    /// Whether the standard streams are terminals, as set by `-Zmiri-isatty`.
+    pub(crate) isatty: Option<bool>,
+    /// The window size of terminals, as set by `-Zmiri-terminal-size`.
+    pub(crate) terminal_size: Option<(u16, u16)>,
     /// The table of directory descriptors.
     pub(crate) dir_handler: shims::unix::DirHandler,
-
-    /// The "time anchor" for this machine's monotone clock (for `Instant` simulation).
-    pub(crate) time_anchor: Instant,
+    /// The resource limits of `getrlimit` and `setrlimit`.
+    pub(crate) rlimits: shims::unix::ResourceLimits,
+    /// Whether `fork` and `posix_spawn` fail with `EAGAIN`, as set by `-Zmiri-fork-fails`.
+    pub(crate) fork_fails: bool,
+    /// Whether `socket` supports `AF_INET`, as set by `-Zmiri-loopback-network`.
+    pub(crate) loopback_network: bool,
+    /// The host names that `getaddrinfo` resolves, as set by `-Zmiri-host`.
+    pub(crate) hosts: Vec<(String, Ipv4Addr)>,
+    /// The user and group ID of the process under isolation, as set by `-Zmiri-uid` and
+    /// `-Zmiri-gid`.
+    pub(crate) uid: u32,
+    pub(crate) gid: u32,
+    /// The offset of the local time zone from UTC under isolation, as set by `-Zmiri-tz-offset`.
+    pub(crate) tz_offset: i64,
+    /// The time zone abbreviations that `tm_zone` points to, allocated once for each name.
+    pub(crate) tm_zones: FxHashMap<String, Pointer<Option<Provenance>>>,
+
+    /// The number of CPUs that the interpreted program sees, as set by `-Zmiri-num-cpus`.
+    pub(crate) num_cpus: u32,
+    /// The state of `openlog` and the messages logged with `syslog`.
+    pub(crate) syslog: shims::unix::Syslog,
+    /// The threads that are blocked in `epoll_wait`.
+    pub(crate) epoll_waiters: Vec<shims::unix::EpollWaiter<'tcx>>,
+    /// The threads that are blocked in an operation on a file descriptor.
+    pub(crate) io_waiters: Vec<shims::unix::BlockedIo<'tcx>>,
+    /// The threads that are blocked in `WaitForSingleObject` or `WaitForMultipleObjects`.
+    pub(crate) object_waiters: Vec<shims::windows::ObjectWaiter<'tcx>>,
+    /// The objects created by the libdispatch shims on macOS.
+    pub(crate) dispatch: shims::unix::DispatchState,
+    /// The signal handlers and signal masks of the program.
+    pub(crate) signals: shims::unix::SignalState,
+    /// The memory handed out by the locale functions, and the locales set with `uselocale`.
+    pub(crate) locale: shims::unix::LocaleState,
+    /// The handlers registered with `atexit` and `__cxa_atexit`, in the order of registration.
+    pub(crate) atexit_handlers: Vec<shims::atexit::AtExitHandler<'tcx>>,
+
+    /// This machine's monotone clock.
+    pub(crate) clock: Clock,
 
     /// The set of threads.
     pub(crate) threads: ThreadManager<'mir, 'tcx>,
 
+    /// Decides which thread runs at every scheduling point.
+    pub(crate) scheduler: RefCell<Box<dyn SchedulingBackend<'tcx>>>,
+
     /// Precomputed `TyLayout`s for primitive data types that are commonly used inside Miri.
     pub(crate) layouts: PrimitiveLayouts<'tcx>,
 
@@ -341,7 +432,7 @@ pub struct Evaluator<'mir, 'tcx> {
 
     /// The `measureme` profiler used to record timing information about
     /// the emulated program.
-    profiler: Option<measureme::Profiler>,
+    pub(crate) profiler: Option<measureme::Profiler>,
     /// Used with `profiler` to cache the `StringId`s for event names
     /// uesd with `measureme`.
     string_cache: FxHashMap<String, measureme::StringId>,
@@ -355,12 +446,21 @@ pub struct Evaluator<'mir, 'tcx> {
     /// instead (default behavior)
     pub(crate) panic_on_unsupported: bool,
 
+    /// Whether calls to foreign functions that are neither provided by a shim nor by
+    /// `external_so_lib` should return zero instead of raising an error.
+    pub(crate) extern_stub_missing: bool,
+    /// The foreign functions that have been stubbed out so far, so that we warn only once per function.
+    pub(crate) stubbed_foreign_fns: FxHashSet<Symbol>,
+
     /// Equivalent setting as RUST_BACKTRACE on encountering an error.
     pub(crate) backtrace_style: BacktraceStyle,
 
     /// Crates which are considered local for the purposes of error reporting.
     pub(crate) local_crates: Vec<CrateNum>,
 
+    /// Crates whose code does not perform Stacked Borrows retags.
+    pub(crate) retag_disabled_crates: Vec<CrateNum>,
+
     /// Mapping extern static names to their base pointer.
     extern_statics: FxHashMap<Symbol, Pointer<Provenance>>,
 
@@ -387,6 +487,16 @@ pub struct Evaluator<'mir, 'tcx> {
     /// The probability of the active thread being preempted at the end of each basic block.
     pub(crate) preemption_rate: f64,
 
+    /// Run the destructors of pthread TLS keys in a random order.
+    pub(crate) random_tls_dtor_order: bool,
+
+    /// Report a possible livelock when a thread executes this many basic blocks without
+    /// synchronization progress. `0` disables the check.
+    pub(crate) livelock_threshold: u64,
+
+    /// Whether to warn when two mutexes are acquired in opposite orders.
+    pub(crate) lock_order_check: bool,
+
     /// If `Some`, we will report the current stack every N basic blocks.
     pub(crate) report_progress: Option<u32>,
     // The total number of blocks that have been executed.
@@ -394,11 +504,33 @@ pub struct Evaluator<'mir, 'tcx> {
 
     /// Handle of the optional shared object file for external functions.
     pub external_so_lib: Option<(libloading::Library, std::path::PathBuf)>,
+    /// Functions already looked up in `external_so_lib`, including the ones it does not define.
+    pub(crate) external_fn_cache: FxHashMap<Symbol, Option<libffi::low::CodePtr>>,
+    /// The path of the type that represents C's `long double` in calls into `external_so_lib`.
+    pub(crate) external_so_long_double: Option<String>,
+    /// The `static mut`s defined by `external_so_lib` that the program declares.
+    pub(crate) lib_statics: Vec<shims::ffi_support::LibStatic>,
+    /// Aborts the process if a call into `external_so_lib` takes longer than the configured
+    /// timeout.
+    pub(crate) external_call_watchdog: Option<shims::ffi_support::CallWatchdog>,
+    /// If `Some`, statistics about foreign function calls, shared by all the runs of the program.
+    pub(crate) external_call_summary: Option<Arc<Mutex<shims::ffi_support::ExternCallSummary>>>,
+
+    /// If `Some`, Stacked Borrows errors are additionally written as JSON, to the given file or to
+    /// stderr.
+    pub(crate) stacked_borrows_json: Option<Option<std::path::PathBuf>>,
+    /// Whether Stacked Borrows errors show the entire borrow stack of the affected location.
+    pub(crate) verbose_stacked_borrows: bool,
+    /// If `Some`, the Stacked Borrows histories of all allocations are collected here, to be
+    /// written out at the end of the run.
+    pub(crate) sb_trace: Option<stacked_borrows::diagnostics::SbTrace>,
 
     /// Run a garbage collector for SbTags every N basic blocks.
     pub(crate) gc_interval: u32,
     /// The number of blocks that passed since the last SbTag GC pass.
     pub(crate) since_gc: u32,
+    /// If `Some`, statistics about the SbTag GC that are printed at the end of the run.
+    pub(crate) tag_gc_stats: Option<crate::tag_gc::TagGcStats>,
 }
 
 impl<'mir, 'tcx> Evaluator<'mir, 'tcx> {
@@ -414,8 +546,14 @@ impl<'mir, 'tcx> Evaluator<'mir, 'tcx> {
         let stacked_borrows = config.stacked_borrows.then(|| {
             RefCell::new(stacked_borrows::GlobalStateInner::new(
                 config.tracked_pointer_tags.clone(),
+                config.tracked_tag_sites.clone(),
                 config.tracked_call_ids.clone(),
                 config.retag_fields,
+                config.stacked_borrows_history_limit,
+                config.sb_tag_index,
+                config.sb_report_only,
+                config.sb_permissive_raw_ptrs,
+                config.sb_stats,
             ))
         });
         let data_race = config.data_race_detector.then(|| data_race::GlobalState::new(config));
@@ -432,18 +570,48 @@ impl<'mir, 'tcx> Evaluator<'mir, 'tcx> {
             isolated_op: config.isolated_op,
             validate: config.validate,
             enforce_abi: config.check_abi,
-            file_handler: FileHandler::new(config.mute_stdout_stderr),
+            file_handler: FileHandler::new(config),
+            isatty: config.isatty,
+            terminal_size: config.terminal_size,
             dir_handler: Default::default(),
-            time_anchor: Instant::now(),
+            rlimits: ResourceLimits::new(config),
+            fork_fails: config.fork_fails,
+            loopback_network: config.loopback_network,
+            hosts: config.hosts.clone(),
+            uid: config.uid,
+            gid: config.gid,
+            tz_offset: config.tz_offset,
+            tm_zones: FxHashMap::default(),
+            num_cpus: config.num_cpus,
+            syslog: Default::default(),
+            epoll_waiters: Vec::new(),
+            io_waiters: Vec::new(),
+            object_waiters: Vec::new(),
+            dispatch: Default::default(),
+            signals: Default::default(),
+            locale: Default::default(),
+            atexit_handlers: Vec::new(),
+            clock: Clock::new(
+                config.isolated_op == IsolatedOp::Allow && !config.virtual_clock,
+                config.clock_tick_per_block,
+                config.clock_tick_per_read,
+            ),
             layouts,
-            threads: ThreadManager::default(),
+            threads: ThreadManager::new(),
+            scheduler: RefCell::new(Box::new(PolicyScheduler::new(config.scheduler_policy))),
             static_roots: Vec::new(),
             profiler,
             string_cache: Default::default(),
             exported_symbols_cache: FxHashMap::default(),
             panic_on_unsupported: config.panic_on_unsupported,
+            extern_stub_missing: config.extern_stub_missing,
+            stubbed_foreign_fns: FxHashSet::default(),
             backtrace_style: config.backtrace_style,
             local_crates,
+            retag_disabled_crates: helpers::get_retag_disabled_crates(
+                layout_cx.tcx,
+                &config.retag_disabled_crates,
+            ),
             extern_statics: FxHashMap::default(),
             rng: RefCell::new(rng),
             tracked_alloc_ids: config.tracked_alloc_ids.clone(),
@@ -452,16 +620,31 @@ impl<'mir, 'tcx> Evaluator<'mir, 'tcx> {
             mute_stdout_stderr: config.mute_stdout_stderr,
             weak_memory: config.weak_memory_emulation,
             preemption_rate: config.preemption_rate,
+            livelock_threshold: config.livelock_threshold,
+            random_tls_dtor_order: config.random_tls_dtor_order,
+            lock_order_check: config.lock_order_check,
             report_progress: config.report_progress,
             basic_block_count: 0,
             external_so_lib: config.external_so_file.as_ref().map(|lib_file_path| {
+                let sess = layout_cx.tcx.sess;
                 // Check if host target == the session target.
                 if env!("TARGET") != target_triple {
-                    panic!(
+                    sess.fatal(format!(
                         "calling external C functions in linked .so file requires host and target to be the same: host={}, target={}",
                         env!("TARGET"),
                         target_triple,
-                    );
+                    ));
+                }
+                // Check that the library was actually built for this target.
+                if let Err(err) =
+                    shims::elf::check_so_file_target(lib_file_path, &sess.target)
+                {
+                    sess.fatal(format!(
+                        "extern shared object file `{}` does not match the target `{}`: {}",
+                        lib_file_path.display(),
+                        target_triple,
+                        err,
+                    ));
                 }
                 // Note: it is the user's responsibility to provide a correct SO file.
                 // WATCH OUT: If an invalid/incorrect SO file is specified, this can cause
@@ -474,8 +657,23 @@ impl<'mir, 'tcx> Evaluator<'mir, 'tcx> {
                     lib_file_path.clone(),
                 )
             }),
+            external_fn_cache: FxHashMap::default(),
+            external_so_long_double: config.external_so_long_double.clone(),
+            lib_statics: Vec::new(),
+            external_call_watchdog: config
+                .external_call_timeout
+                .map(shims::ffi_support::CallWatchdog::new),
+            external_call_summary: config.external_call_summary.clone(),
+            stacked_borrows_json: config.stacked_borrows_json.clone(),
+            verbose_stacked_borrows: config.verbose_stacked_borrows,
+            sb_trace: config
+                .sb_trace_out
+                .clone()
+                .filter(|_| config.stacked_borrows)
+                .map(stacked_borrows::diagnostics::SbTrace::new),
             gc_interval: config.gc_interval,
             since_gc: 0,
+            tag_gc_stats: config.tag_gc_stats.then(Default::default),
         }
     }
 
@@ -485,11 +683,14 @@ impl<'mir, 'tcx> Evaluator<'mir, 'tcx> {
     ) -> InterpResult<'tcx> {
         EnvVars::init(this, config)?;
         Evaluator::init_extern_statics(this)?;
+        if this.machine.external_so_lib.is_some() {
+            this.init_lib_extern_statics()?;
+        }
         ThreadManager::init(this);
         Ok(())
     }
 
-    fn add_extern_static(
+    pub(crate) fn add_extern_static(
         this: &mut MiriEvalContext<'mir, 'tcx>,
         name: &str,
         ptr: Pointer<Option<Provenance>>,
@@ -499,6 +700,10 @@ impl<'mir, 'tcx> Evaluator<'mir, 'tcx> {
         this.machine.extern_statics.try_insert(Symbol::intern(name), ptr).unwrap();
     }
 
+    pub(crate) fn has_extern_static(&self, link_name: Symbol) -> bool {
+        self.extern_statics.contains_key(&link_name)
+    }
+
     fn alloc_extern_static(
         this: &mut MiriEvalContext<'mir, 'tcx>,
         name: &str,
@@ -574,6 +779,15 @@ impl<'mir, 'tcx> Evaluator<'mir, 'tcx> {
         let def_id = frame.instance.def_id();
         def_id.is_local() || self.local_crates.contains(&def_id.krate)
     }
+
+    /// Check whether retags should be performed in the given stack frame, i.e. whether its code
+    /// does not come from a crate listed in `-Zmiri-disable-retag-for-crate`.
+    pub(crate) fn retags_enabled(
+        &self,
+        frame: &Frame<'mir, 'tcx, Provenance, FrameData<'tcx>>,
+    ) -> bool {
+        !self.retag_disabled_crates.contains(&frame.instance.def_id().krate)
+    }
 }
 
 /// A rustc InterpCx for Miri.
@@ -783,12 +997,14 @@ impl<'mir, 'tcx> Machine<'mir, 'tcx> for Evaluator<'mir, 'tcx> {
             )
         });
         let buffer_alloc = ecx.machine.weak_memory.then(weak_memory::AllocExtra::new_allocation);
+        let mapping = (kind == MiriMemoryKind::Mmap.into()).then(|| Mapping::new(alloc.size()));
         let alloc: Allocation<Provenance, Self::AllocExtra> = alloc.adjust_from_tcx(
             &ecx.tcx,
             AllocExtra {
                 stacked_borrows: stacks.map(RefCell::new),
                 data_race: race_alloc,
                 weak_memory: buffer_alloc,
+                mapping,
             },
             |ptr| ecx.global_base_pointer(ptr),
         )?;
@@ -873,6 +1089,9 @@ impl<'mir, 'tcx> Machine<'mir, 'tcx> for Evaluator<'mir, 'tcx> {
         (alloc_id, prov_extra): (AllocId, Self::ProvenanceExtra),
         range: AllocRange,
     ) -> InterpResult<'tcx> {
+        if let Some(mapping) = &alloc_extra.mapping {
+            mapping.read(alloc_id, range)?;
+        }
         if let Some(data_race) = &alloc_extra.data_race {
             data_race.read(
                 alloc_id,
@@ -905,6 +1124,9 @@ impl<'mir, 'tcx> Machine<'mir, 'tcx> for Evaluator<'mir, 'tcx> {
         (alloc_id, prov_extra): (AllocId, Self::ProvenanceExtra),
         range: AllocRange,
     ) -> InterpResult<'tcx> {
+        if let Some(mapping) = &alloc_extra.mapping {
+            mapping.write(alloc_id, range)?;
+        }
         if let Some(data_race) = &mut alloc_extra.data_race {
             data_race.write(
                 alloc_id,
@@ -956,10 +1178,12 @@ impl<'mir, 'tcx> Machine<'mir, 'tcx> for Evaluator<'mir, 'tcx> {
                 machine.stacked_borrows.as_ref().unwrap(),
                 machine.current_span(tcx),
                 &machine.threads,
-            )
-        } else {
-            Ok(())
+            )?;
+            if let Some(sb_trace) = &mut machine.sb_trace {
+                sb_trace.add_allocation(stacked_borrows.get_mut(), tcx.sess.source_map());
+            }
         }
+        Ok(())
     }
 
     #[inline(always)]
@@ -968,7 +1192,11 @@ impl<'mir, 'tcx> Machine<'mir, 'tcx> for Evaluator<'mir, 'tcx> {
         kind: mir::RetagKind,
         place: &PlaceTy<'tcx, Provenance>,
     ) -> InterpResult<'tcx> {
-        if ecx.machine.stacked_borrows.is_some() { ecx.retag(kind, place) } else { Ok(()) }
+        if ecx.machine.stacked_borrows.is_some() && ecx.machine.retags_enabled(ecx.frame()) {
+            ecx.retag(kind, place)
+        } else {
+            Ok(())
+        }
     }
 
     #[inline(always)]
@@ -996,6 +1224,9 @@ impl<'mir, 'tcx> Machine<'mir, 'tcx> for Evaluator<'mir, 'tcx> {
         let extra = FrameData {
             stacked_borrows: stacked_borrows.map(|sb| sb.borrow_mut().new_frame()),
             catch_unwind: None,
+            dispatch_once: None,
+            signal_handler: None,
+            atexit_exit_code: None,
             timing,
         };
         Ok(frame.with_extra(extra))
@@ -1016,6 +1247,10 @@ impl<'mir, 'tcx> Machine<'mir, 'tcx> for Evaluator<'mir, 'tcx> {
     fn before_terminator(ecx: &mut InterpCx<'mir, 'tcx, Self>) -> InterpResult<'tcx> {
         ecx.machine.basic_block_count += 1u64; // a u64 that is only incremented by 1 will "never" overflow
         ecx.machine.since_gc += 1;
+        // Make sure that the virtual clock advances while the program is running.
+        ecx.machine.clock.tick();
+        // Look for threads that spin without letting other threads make progress.
+        ecx.check_livelock();
         // Possibly report our progress.
         if let Some(report_progress) = ecx.machine.report_progress {
             if ecx.machine.basic_block_count % u64::from(report_progress) == 0 {
@@ -1041,7 +1276,14 @@ impl<'mir, 'tcx> Machine<'mir, 'tcx> for Evaluator<'mir, 'tcx> {
 
     #[inline(always)]
     fn after_stack_push(ecx: &mut InterpCx<'mir, 'tcx, Self>) -> InterpResult<'tcx> {
-        if ecx.machine.stacked_borrows.is_some() { ecx.retag_return_place() } else { Ok(()) }
+        if CurrentSpan::is_local_frame(*ecx.tcx, &ecx.machine, ecx.frame()) {
+            ecx.machine.threads.push_local_frame();
+        }
+        if ecx.machine.stacked_borrows.is_some() && ecx.machine.retags_enabled(ecx.frame()) {
+            ecx.retag_return_place()
+        } else {
+            Ok(())
+        }
     }
 
     #[inline(always)]
@@ -1050,11 +1292,30 @@ impl<'mir, 'tcx> Machine<'mir, 'tcx> for Evaluator<'mir, 'tcx> {
         mut frame: Frame<'mir, 'tcx, Provenance, FrameData<'tcx>>,
         unwinding: bool,
     ) -> InterpResult<'tcx, StackPopJump> {
+        ecx.machine.threads.pop_local_frames();
         let timing = frame.extra.timing.take();
         if let Some(stacked_borrows) = &ecx.machine.stacked_borrows {
             stacked_borrows.borrow_mut().end_call(&frame.extra);
         }
-        let res = ecx.handle_stack_pop_unwind(frame.extra, unwinding);
+        if let Some(dispatch_once) = frame.extra.dispatch_once.take() {
+            // The initialization function cannot unwind, so we only get here when it returns.
+            assert!(!unwinding);
+            ecx.dispatch_once_done(dispatch_once)?;
+        }
+        let res = if let Some(signal_handler) = frame.extra.signal_handler.take() {
+            // Signal handlers cannot unwind either. We continue in the caller ourselves, since
+            // this might call the handler of another signal.
+            assert!(!unwinding);
+            ecx.signal_handler_done(signal_handler)?;
+            Ok(StackPopJump::NoJump)
+        } else if let Some(code) = frame.extra.atexit_exit_code.take() {
+            // The handler does not return to anywhere; `exit` goes on with the next one.
+            assert!(!unwinding);
+            ecx.exit(code)?;
+            Ok(StackPopJump::NoJump)
+        } else {
+            ecx.handle_stack_pop_unwind(frame.extra, unwinding)
+        };
         if let Some(profiler) = ecx.machine.profiler.as_ref() {
             profiler.finish_recording_interval_event(timing.unwrap());
         }
@@ -0,0 +1,93 @@
+//! Emulation of `atexit` and `__cxa_atexit`. The registered handlers run in reverse order when the
+//! program calls `exit`, and when the main thread returns from `main`, after its TLS destructors.
+//! Either way, they run before Miri checks for leaks.
+
+use log::trace;
+
+use rustc_middle::ty;
+use rustc_target::spec::abi::Abi;
+
+use crate::concurrency::thread::MAIN_THREAD;
+use crate::*;
+
+/// A function registered with `atexit`, or with `__cxa_atexit` together with its argument.
+#[derive(Debug)]
+pub struct AtExitHandler<'tcx> {
+    instance: ty::Instance<'tcx>,
+    arg: Option<Scalar<Provenance>>,
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Call the handler that was registered last, if there is one. Returns whether there was one.
+    fn call_next_atexit_handler(&mut self, cleanup: StackPopCleanup) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_mut();
+        let Some(handler) = this.machine.atexit_handlers.pop() else {
+            return Ok(false);
+        };
+        trace!("Running atexit handler {:?}", handler);
+        let args: Vec<Immediate<Provenance>> = handler.arg.into_iter().map(Into::into).collect();
+        this.call_function(handler.instance, Abi::C { unwind: false }, &args, None, cleanup)?;
+        Ok(true)
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    fn atexit(&mut self, func_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let func = this.read_pointer(func_op)?;
+        let instance = this.get_ptr_fn(func)?.as_instance()?;
+        this.machine.atexit_handlers.push(AtExitHandler { instance, arg: None });
+        Ok(0)
+    }
+
+    /// The DSO handle is ignored, since Miri never unloads a shared object and hence never calls
+    /// `__cxa_finalize` for one.
+    fn cxa_atexit(
+        &mut self,
+        func_op: &OpTy<'tcx, Provenance>,
+        arg_op: &OpTy<'tcx, Provenance>,
+        dso_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let func = this.read_pointer(func_op)?;
+        let arg = this.read_scalar(arg_op)?;
+        let _dso = this.read_pointer(dso_op)?;
+        let instance = this.get_ptr_fn(func)?.as_instance()?;
+        this.machine.atexit_handlers.push(AtExitHandler { instance, arg: Some(arg) });
+        Ok(0)
+    }
+
+    /// Schedule the next `atexit` handler to run on the main thread, once it has returned from
+    /// `main` and run its TLS destructors. Returns `true` if a handler was scheduled.
+    fn schedule_next_atexit_handler(&mut self) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_mut();
+        let active_thread = this.get_active_thread();
+        assert_eq!(active_thread, MAIN_THREAD);
+
+        if this.call_next_atexit_handler(StackPopCleanup::Root { cleanup: true })? {
+            // Run the handler to completion and come back here, like for TLS destructors.
+            this.enable_thread(active_thread);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Implements `exit`: the `atexit` handlers run one after the other on the calling thread,
+    /// and once they are all done, the program stops with `code`.
+    fn exit(&mut self, code: i64) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        // The frame of the handler is marked, so that this is called again when it returns. The
+        // handler never returns to the caller of `exit`.
+        let cleanup = StackPopCleanup::Goto { ret: None, unwind: StackPopUnwind::Skip };
+        if this.call_next_atexit_handler(cleanup)? {
+            this.frame_mut().extra.atexit_exit_code = Some(code);
+            return Ok(());
+        }
+        throw_machine_stop!(TerminationInfo::Exit(code));
+    }
+}
@@ -1,10 +1,59 @@
 use crate::*;
 use rustc_ast::ast::Mutability;
+use rustc_middle::mir::{self, SourceScope, OUTERMOST_SOURCE_SCOPE};
 use rustc_middle::ty::layout::LayoutOf as _;
 use rustc_middle::ty::{self, Instance};
-use rustc_span::{BytePos, Loc, Symbol};
+use rustc_span::{BytePos, Loc, Span, Symbol};
 use rustc_target::{abi::Size, spec::abi::Abi};
 
+/// The scope of `body` at which the function that `scope` belongs to was inlined, if it was.
+fn inlined_root(body: &mir::Body<'_>, scope: SourceScope) -> Option<SourceScope> {
+    let data = &body.source_scopes[scope];
+    if data.inlined.is_some() { Some(scope) } else { data.inlined_parent_scope }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// The locations in the current call stack, innermost first, given by the function of the
+    /// stack frame, a source scope of its body and a position. If `inlined` is set, each function
+    /// that was inlined into a frame gets its own location, whose scope belongs to the inlined
+    /// function. Otherwise, the scope is always the outermost one.
+    fn backtrace_locations(&self, inlined: bool) -> Vec<(Instance<'tcx>, SourceScope, BytePos)> {
+        let this = self.eval_context_ref();
+        let tcx = this.tcx;
+
+        let mut locations = Vec::new();
+        for frame in this.active_thread_stack().iter().rev() {
+            // Match the behavior of runtime backtrace spans
+            // by using a non-macro span in our backtrace. See `FunctionCx::debug_loc`.
+            let pos = |span: Span| {
+                if span.from_expansion() && !tcx.sess.opts.unstable_opts.debug_macros {
+                    rustc_span::hygiene::walk_chain(span, frame.body.span.ctxt()).lo()
+                } else {
+                    span.lo()
+                }
+            };
+            let (mut scope, mut span) = match frame.current_source_info() {
+                Some(source_info) => (source_info.scope, source_info.span),
+                None => (OUTERMOST_SOURCE_SCOPE, frame.current_span()),
+            };
+            if inlined {
+                // Report where we are in the inlined function, then continue at its call site.
+                while let Some(root) = inlined_root(&frame.body, scope) {
+                    locations.push((frame.instance, scope, pos(span)));
+                    let root = &frame.body.source_scopes[root];
+                    span = root.inlined.unwrap().1;
+                    scope = root.parent_scope.unwrap();
+                }
+            } else {
+                scope = OUTERMOST_SOURCE_SCOPE;
+            }
+            locations.push((frame.instance, scope, pos(span)));
+        }
+        locations
+    }
+}
+
 impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
 pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
     fn handle_miri_backtrace_size(
@@ -18,11 +67,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let [flags] = this.check_shim(abi, Abi::Rust, link_name, args)?;
 
         let flags = this.read_scalar(flags)?.to_u64()?;
-        if flags != 0 {
-            throw_unsup_format!("unknown `miri_backtrace_size` flags {}", flags);
-        }
-
-        let frame_count = this.active_thread_stack().len();
+        let frame_count = match flags {
+            0 => this.active_thread_stack().len(),
+            // Count the inlined functions as well.
+            1 => this.backtrace_locations(true).len(),
+            _ => throw_unsup_format!("unknown `miri_backtrace_size` flags {}", flags),
+        };
 
         this.write_scalar(Scalar::from_machine_usize(frame_count.try_into().unwrap(), this), dest)
     }
@@ -43,28 +93,28 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             throw_ub_format!("expected at least 1 argument")
         };
 
-        let mut data = Vec::new();
-        for frame in this.active_thread_stack().iter().rev() {
-            let mut span = frame.current_span();
-            // Match the behavior of runtime backtrace spans
-            // by using a non-macro span in our backtrace. See `FunctionCx::debug_loc`.
-            if span.from_expansion() && !tcx.sess.opts.unstable_opts.debug_macros {
-                span = rustc_span::hygiene::walk_chain(span, frame.body.span.ctxt())
-            }
-            data.push((frame.instance, span.lo()));
+        let inlined = flags == 2;
+        if inlined && this.pointer_size().bytes() < 8 {
+            throw_unsup_format!(
+                "`miri_get_backtrace` can only report inlined frames on 64-bit targets"
+            );
         }
+        let data = this.backtrace_locations(inlined);
 
         let ptrs: Vec<_> = data
             .into_iter()
-            .map(|(instance, pos)| {
+            .map(|(instance, scope, pos)| {
                 // We represent a frame pointer by using the `span.lo` value
                 // as an offset into the function's allocation. This gives us an
                 // opaque pointer that we can return to user code, and allows us
                 // to reconstruct the needed frame information in `handle_miri_resolve_frame`.
                 // Note that we never actually read or write anything from/to this pointer -
                 // all of the data is represented by the pointer value itself.
+                // The source scope goes into the upper 32 bits of the offset. It is only
+                // non-zero for inlined frames, which only exist on 64-bit targets.
+                let offset = u64::from(scope.as_u32()).checked_shl(32).unwrap() | u64::from(pos.0);
                 let fn_ptr = this.create_fn_alloc_ptr(FnVal::Instance(instance));
-                fn_ptr.wrapping_offset(Size::from_bytes(pos.0), this)
+                fn_ptr.wrapping_offset(Size::from_bytes(offset), this)
             })
             .collect();
 
@@ -94,7 +144,8 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 )?;
             }
             // storage for pointers is allocated by the caller
-            1 => {
+            // (with flags 2, the inlined functions get their own frames)
+            1 | 2 => {
                 let [_flags, buf] = this.check_shim(abi, Abi::Rust, link_name, args)?;
 
                 let buf_place = this.deref_operand(buf)?;
@@ -115,10 +166,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(())
     }
 
+    /// Returns the function of the frame, the location in it, the name of the function, the name
+    /// of the file, and whether the function was inlined into the next frame of the backtrace.
     fn resolve_frame_pointer(
         &mut self,
         ptr: &OpTy<'tcx, Provenance>,
-    ) -> InterpResult<'tcx, (Instance<'tcx>, Loc, String, String)> {
+    ) -> InterpResult<'tcx, (Instance<'tcx>, Loc, String, String, bool)> {
         let this = self.eval_context_mut();
 
         let ptr = this.read_pointer(ptr)?;
@@ -134,13 +187,43 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             throw_ub_format!("expected static function pointer, found {:?}", ptr);
         };
 
-        let lo =
-            this.tcx.sess.source_map().lookup_char_pos(BytePos(offset.bytes().try_into().unwrap()));
+        // The lower 32 bits of the offset are the position, the upper bits are the source scope.
+        let offset = offset.bytes();
+        let pos = BytePos(u32::try_from(offset & u64::from(u32::MAX)).unwrap());
+        let scope = offset.checked_shr(32).unwrap();
+
+        // Only frames of inlined functions have a scope other than the outermost one.
+        let (fn_instance, inlined) = if scope == 0 {
+            (fn_instance, false)
+        } else {
+            let body = this.load_mir(fn_instance.def, None)?;
+            let scope = usize::try_from(scope)
+                .ok()
+                .filter(|&scope| scope < body.source_scopes.len())
+                .map(SourceScope::from_usize);
+            let Some(scope) = scope else {
+                throw_ub_format!("expected frame pointer, found {:?}", ptr);
+            };
+            match inlined_root(body, scope) {
+                Some(root) => {
+                    let callee = body.source_scopes[root].inlined.unwrap().0;
+                    let callee = fn_instance.subst_mir_and_normalize_erasing_regions(
+                        *this.tcx,
+                        ty::ParamEnv::reveal_all(),
+                        callee,
+                    );
+                    (callee, true)
+                }
+                None => (fn_instance, false),
+            }
+        };
+
+        let lo = this.tcx.sess.source_map().lookup_char_pos(pos);
 
         let name = fn_instance.to_string();
         let filename = lo.file.name.prefer_remapped().to_string();
 
-        Ok((fn_instance, lo, name, filename))
+        Ok((fn_instance, lo, name, filename, inlined))
     }
 
     fn handle_miri_resolve_frame(
@@ -155,7 +238,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         let flags = this.read_scalar(flags)?.to_u64()?;
 
-        let (fn_instance, lo, name, filename) = this.resolve_frame_pointer(ptr)?;
+        let (fn_instance, lo, name, filename, inlined) = this.resolve_frame_pointer(ptr)?;
 
         // Reconstruct the original function pointer,
         // which we pass to user code.
@@ -163,11 +246,11 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         let num_fields = dest.layout.fields.count();
 
-        if !(4..=5).contains(&num_fields) {
-            // Always mention 5 fields, since the 4-field struct
+        if !(4..=6).contains(&num_fields) {
+            // Never mention 4 fields, since the 4-field struct
             // is deprecated and slated for removal.
             throw_ub_format!(
-                "bad declaration of miri_resolve_frame - should return a struct with 5 fields"
+                "bad declaration of miri_resolve_frame - should return a struct with 5 or 6 fields"
             );
         }
 
@@ -221,9 +304,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         // Support a 4-field struct for now - this is deprecated
         // and slated for removal.
-        if num_fields == 5 {
+        if num_fields >= 5 {
             this.write_pointer(fn_ptr, &this.mplace_field(&dest, 4)?.into())?;
         }
+        if num_fields == 6 {
+            this.write_int(u8::from(inlined), &this.mplace_field(&dest, 5)?.into())?;
+        }
 
         Ok(())
     }
@@ -244,7 +330,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             throw_unsup_format!("unknown `miri_resolve_frame_names` flags {}", flags);
         }
 
-        let (_, _, name, filename) = this.resolve_frame_pointer(ptr)?;
+        let (_, _, name, filename, _) = this.resolve_frame_pointer(ptr)?;
 
         this.write_bytes_ptr(this.read_pointer(name_ptr)?, name.bytes())?;
         this.write_bytes_ptr(this.read_pointer(filename_ptr)?, filename.bytes())?;
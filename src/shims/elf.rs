@@ -0,0 +1,450 @@
+//! Reading the parts of ELF files that Miri needs for calling into a shared object file: the
+//! header, to check that the file fits the target, and the symbol version tables, to look up
+//! specific versions of a symbol.
+
+use std::path::Path;
+
+use rustc_target::abi::Endian;
+use rustc_target::spec::Target;
+
+/// Split a link name of the form `name@version` or `name@@version` into its parts. The name itself
+/// may contain `@`, so only the last `@` counts, and only if a valid version name follows it.
+pub fn split_symbol_version(link_name: &str) -> Option<(&str, &str)> {
+    let (name, version) = link_name.rsplit_once('@')?;
+    let name = name.strip_suffix('@').unwrap_or(name);
+    let is_version_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    let is_version = !version.is_empty() && version.chars().all(is_version_char);
+    (is_version && !name.is_empty()).then_some((name, version))
+}
+
+/// Look up a specific version of a symbol in the already loaded library at `lib_path`.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+pub fn dlvsym_from_lib(
+    lib_path: &Path,
+    name: &str,
+    version: &str,
+) -> Option<*mut std::ffi::c_void> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let lib_path = CString::new(lib_path.as_os_str().as_bytes()).ok()?;
+    let name = CString::new(name).ok()?;
+    let version = CString::new(version).ok()?;
+    unsafe {
+        // The library is already loaded, so this just gives us another handle to it.
+        let handle = libc::dlopen(lib_path.as_ptr(), libc::RTLD_LAZY | libc::RTLD_NOLOAD);
+        if handle.is_null() {
+            return None;
+        }
+        let addr = libc::dlvsym(handle, name.as_ptr(), version.as_ptr());
+        libc::dlclose(handle);
+        Some(addr)
+    }
+}
+
+/// Symbol versioning is a GNU extension, so there are no versioned symbols to be found.
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+pub fn dlvsym_from_lib(
+    _lib_path: &Path,
+    _name: &str,
+    _version: &str,
+) -> Option<*mut std::ffi::c_void> {
+    None
+}
+
+/// Basic facts about a shared object file, as recorded in its ELF header.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ElfInfo {
+    /// 32 or 64.
+    pub pointer_width: u32,
+    pub endian: Endian,
+    /// The `e_machine` field, identifying the architecture.
+    pub machine: u16,
+}
+
+/// Parse the start of an ELF header. Returns `None` if `header` is not the start of an ELF file.
+pub fn parse_elf_header(header: &[u8]) -> Option<ElfInfo> {
+    if header.len() < 20 || header[..4] != *b"\x7fELF" {
+        return None;
+    }
+    let pointer_width = match header[4] {
+        1 => 32,
+        2 => 64,
+        _ => return None,
+    };
+    let endian = match header[5] {
+        1 => Endian::Little,
+        2 => Endian::Big,
+        _ => return None,
+    };
+    let machine_bytes = [header[18], header[19]];
+    let machine = match endian {
+        Endian::Little => u16::from_le_bytes(machine_bytes),
+        Endian::Big => u16::from_be_bytes(machine_bytes),
+    };
+    Some(ElfInfo { pointer_width, endian, machine })
+}
+
+const SHT_DYNSYM: u32 = 11;
+const SHT_GNU_VERDEF: u32 = 0x6ffffffd;
+const SHT_GNU_VERSYM: u32 = 0x6fffffff;
+
+/// Reads values out of an ELF file, taking its class and byte order into account.
+/// All reads are bounds-checked, returning `None` for malformed files.
+struct ElfReader<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    endian: Endian,
+}
+
+/// The parts of an ELF section header we care about.
+struct ElfSection {
+    kind: u32,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+}
+
+impl<'a> ElfReader<'a> {
+    fn bytes<const N: usize>(&self, offset: u64) -> Option<[u8; N]> {
+        let start = usize::try_from(offset).ok()?;
+        self.data.get(start..start.checked_add(N)?)?.try_into().ok()
+    }
+
+    fn u16(&self, offset: u64) -> Option<u16> {
+        let bytes = self.bytes(offset)?;
+        Some(match self.endian {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, offset: u64) -> Option<u32> {
+        let bytes = self.bytes(offset)?;
+        Some(match self.endian {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads a word-sized value, i.e., 4 bytes for 32-bit files and 8 bytes for 64-bit files.
+    fn word(&self, offset: u64) -> Option<u64> {
+        if !self.is_64 {
+            return self.u32(offset).map(u64::from);
+        }
+        let bytes = self.bytes(offset)?;
+        Some(match self.endian {
+            Endian::Little => u64::from_le_bytes(bytes),
+            Endian::Big => u64::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads the NUL-terminated string at `offset`.
+    fn str(&self, offset: u64) -> Option<&'a str> {
+        let bytes = self.data.get(usize::try_from(offset).ok()?..)?;
+        let len = bytes.iter().position(|&b| b == 0)?;
+        std::str::from_utf8(&bytes[..len]).ok()
+    }
+
+    fn sections(&self) -> Option<Vec<ElfSection>> {
+        // Offsets of `e_shoff`, `e_shentsize`, and `e_shnum` in the file header, and of
+        // `sh_offset`, `sh_size`, and `sh_link` in a section header.
+        let (shoff, shentsize, shnum, sh_offset, sh_size, sh_link) = if self.is_64 {
+            (0x28, 0x3a, 0x3c, 24, 32, 40)
+        } else {
+            (0x20, 0x2e, 0x30, 16, 20, 24)
+        };
+        let table = self.word(shoff)?;
+        let entry_size = u64::from(self.u16(shentsize)?);
+        (0..u64::from(self.u16(shnum)?))
+            .map(|i| {
+                let header = table.checked_add(i.checked_mul(entry_size)?)?;
+                let link = self.u32(header.checked_add(sh_link)?)?;
+                Some(ElfSection {
+                    kind: self.u32(header.checked_add(4)?)?,
+                    offset: self.word(header.checked_add(sh_offset)?)?,
+                    size: self.word(header.checked_add(sh_size)?)?,
+                    link,
+                    // `sh_info` directly follows `sh_link`.
+                    info: self.u32(header.checked_add(sh_link)?.checked_add(4)?)?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Returns the versions of the dynamic symbol `name` that are defined in the ELF file `data`.
+/// Returns `None` if this is not an ELF file, or it does not use symbol versioning.
+pub fn elf_symbol_versions(data: &[u8], name: &str) -> Option<Vec<String>> {
+    let info = parse_elf_header(data)?;
+    let elf = ElfReader { data, is_64: info.pointer_width == 64, endian: info.endian };
+    let sections = elf.sections()?;
+    let find_section = |kind| sections.iter().find(|s| s.kind == kind);
+    let dynsym = find_section(SHT_DYNSYM)?;
+    let versym = find_section(SHT_GNU_VERSYM)?;
+    let verdef = find_section(SHT_GNU_VERDEF)?;
+    let dynstr = sections.get(usize::try_from(dynsym.link).ok()?)?;
+    let verstr = sections.get(usize::try_from(verdef.link).ok()?)?;
+
+    // Collect the version index and name of each version definition.
+    let mut version_names = Vec::new();
+    let mut verdef_offset = verdef.offset;
+    for _ in 0..verdef.info {
+        let index = elf.u16(verdef_offset.checked_add(4)?)?;
+        let aux = elf.u32(verdef_offset.checked_add(12)?)?;
+        let next = elf.u32(verdef_offset.checked_add(16)?)?;
+        let name_offset = elf.u32(verdef_offset.checked_add(u64::from(aux))?)?;
+        version_names.push((index, elf.str(verstr.offset.checked_add(u64::from(name_offset))?)?));
+        if next == 0 {
+            break;
+        }
+        verdef_offset = verdef_offset.checked_add(u64::from(next))?;
+    }
+
+    // Find the definitions of `name` in the dynamic symbol table.
+    let (sym_size, sym_shndx) = if elf.is_64 { (24, 6) } else { (16, 14) };
+    let mut versions = Vec::new();
+    for i in 0..dynsym.size.checked_div(sym_size)? {
+        let sym = dynsym.offset.checked_add(i.checked_mul(sym_size)?)?;
+        let sym_name = elf.u32(sym)?;
+        if elf.u16(sym.checked_add(sym_shndx)?)? == 0 {
+            // An undefined symbol.
+            continue;
+        }
+        if elf.str(dynstr.offset.checked_add(u64::from(sym_name))?)? != name {
+            continue;
+        }
+        // The top bit marks hidden versions, which can still be requested explicitly.
+        let version = elf.u16(versym.offset.checked_add(i.checked_mul(2)?)?)? & 0x7fff;
+        if let Some((_, version_name)) = version_names.iter().find(|(index, _)| *index == version)
+        {
+            versions.push(version_name.to_string());
+        }
+    }
+    Some(versions)
+}
+
+/// The ELF `e_machine` value for a target architecture, if we know it.
+fn elf_machine_of_arch(arch: &str) -> Option<u16> {
+    Some(match arch {
+        "x86" => 3,
+        "mips" | "mips64" => 8,
+        "powerpc" => 20,
+        "powerpc64" => 21,
+        "s390x" => 22,
+        "arm" => 40,
+        "sparc64" => 43,
+        "x86_64" => 62,
+        "aarch64" => 183,
+        "riscv32" | "riscv64" => 243,
+        _ => return None,
+    })
+}
+
+/// Check that a shared object file with the given ELF header information can be used when
+/// interpreting a program for a target with the given architecture, pointer width and endianness.
+fn check_elf_info(
+    info: &ElfInfo,
+    arch: &str,
+    pointer_width: u32,
+    endian: Endian,
+) -> Result<(), String> {
+    if info.pointer_width != pointer_width {
+        return Err(format!(
+            "it is a {}-bit library, but the target has {}-bit pointers",
+            info.pointer_width, pointer_width
+        ));
+    }
+    if info.endian != endian {
+        return Err(format!(
+            "it is a {} library, but the target is {}",
+            info.endian.as_str(),
+            endian.as_str()
+        ));
+    }
+    if let Some(machine) = elf_machine_of_arch(arch) {
+        if info.machine != machine {
+            return Err(format!(
+                "it is built for ELF machine type {}, but the target architecture `{}` has machine type {}",
+                info.machine, arch, machine
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the shared object file at `path` was built for `target`, so that we do not pass
+/// values of the wrong width or byte order to it. Files that are not in ELF format cannot be
+/// checked and are accepted.
+pub fn check_so_file_target(path: &Path, target: &Target) -> Result<(), String> {
+    use std::io::Read;
+    let mut header = [0; 20];
+    let mut file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+    if file.read_exact(&mut header).is_err() {
+        // Too short to be an ELF file.
+        return Ok(());
+    }
+    match parse_elf_header(&header) {
+        Some(info) => check_elf_info(&info, &target.arch, target.pointer_width, target.endian),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::integer_arithmetic)] // small offsets into test data
+mod tests {
+    use super::*;
+
+    fn elf_header(class: u8, data: u8, machine: u16) -> Vec<u8> {
+        let mut header = b"\x7fELF".to_vec();
+        header.extend([class, data]);
+        header.resize(18, 0);
+        if data == 2 {
+            header.extend(machine.to_be_bytes());
+        } else {
+            header.extend(machine.to_le_bytes());
+        }
+        header
+    }
+
+    #[test]
+    fn parse_header() {
+        assert_eq!(
+            parse_elf_header(&elf_header(2, 1, 62)),
+            Some(ElfInfo { pointer_width: 64, endian: Endian::Little, machine: 62 })
+        );
+        assert_eq!(
+            parse_elf_header(&elf_header(1, 2, 20)),
+            Some(ElfInfo { pointer_width: 32, endian: Endian::Big, machine: 20 })
+        );
+        assert_eq!(parse_elf_header(b"#!/bin/sh\necho not an ELF file"), None);
+        assert_eq!(parse_elf_header(b"\x7fELF"), None);
+    }
+
+    #[test]
+    fn check_header_against_target() {
+        let x86_64 = parse_elf_header(&elf_header(2, 1, 62)).unwrap();
+        assert_eq!(check_elf_info(&x86_64, "x86_64", 64, Endian::Little), Ok(()));
+        assert!(check_elf_info(&x86_64, "x86", 32, Endian::Little).is_err());
+        assert!(check_elf_info(&x86_64, "aarch64", 64, Endian::Little).is_err());
+        assert!(check_elf_info(&x86_64, "x86_64", 64, Endian::Big).is_err());
+        // Unknown architectures are only checked for width and endianness.
+        assert_eq!(check_elf_info(&x86_64, "some_new_arch", 64, Endian::Little), Ok(()));
+    }
+
+    #[test]
+    fn symbol_versions() {
+        assert_eq!(split_symbol_version("foo"), None);
+        assert_eq!(split_symbol_version("foo@V1"), Some(("foo", "V1")));
+        assert_eq!(split_symbol_version("foo@@V_2.0"), Some(("foo", "V_2.0")));
+        // Only the last `@` separates the version, if it is followed by a valid version name.
+        assert_eq!(split_symbol_version("foo@bar@V1"), Some(("foo@bar", "V1")));
+        assert_eq!(split_symbol_version("foo@bar@@V1"), Some(("foo@bar", "V1")));
+        assert_eq!(split_symbol_version("foo@"), None);
+        assert_eq!(split_symbol_version("@V1"), None);
+        assert_eq!(split_symbol_version("foo@<bar>"), None);
+    }
+
+    /// A small 64-bit little-endian ELF file whose only dynamic symbol `foo` is defined in the
+    /// versions `V1` and (hidden) `V2`.
+    fn versioned_elf_file() -> Vec<u8> {
+        fn put(data: &mut Vec<u8>, offset: usize, bytes: &[u8]) {
+            if data.len() < offset + bytes.len() {
+                data.resize(offset + bytes.len(), 0);
+            }
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        let mut data = elf_header(2, 1, 62);
+        // `e_shoff`, `e_shentsize`, `e_shnum`
+        put(&mut data, 0x28, &0xd8u64.to_le_bytes());
+        put(&mut data, 0x3a, &64u16.to_le_bytes());
+        put(&mut data, 0x3c, &5u16.to_le_bytes());
+        // .dynstr
+        put(&mut data, 0x40, b"\0foo\0V1\0V2\0");
+        // .dynsym: the null symbol, and `foo` twice, defined in section 1
+        for sym in [0x68, 0x80] {
+            put(&mut data, sym, &1u32.to_le_bytes());
+            put(&mut data, sym + 6, &1u16.to_le_bytes());
+        }
+        // .gnu.version
+        for (i, version) in [0u16, 2, 0x8003].into_iter().enumerate() {
+            put(&mut data, 0x98 + 2 * i, &version.to_le_bytes());
+        }
+        // .gnu.version_d: two definitions, each followed by its auxiliary entry
+        for (verdef, index, name, next) in [(0xa0, 2u16, 5u32, 28u32), (0xbc, 3, 8, 0)] {
+            put(&mut data, verdef + 4, &index.to_le_bytes());
+            put(&mut data, verdef + 12, &20u32.to_le_bytes());
+            put(&mut data, verdef + 16, &next.to_le_bytes());
+            put(&mut data, verdef + 20, &name.to_le_bytes());
+        }
+        // The section headers: `sh_type`, `sh_offset`, `sh_size`, `sh_link`, `sh_info`.
+        let sections = [
+            (0, 0, 0, 0, 0),
+            (SHT_DYNSYM, 0x50, 72, 2, 0),
+            (3, 0x40, 11, 0, 0),
+            (SHT_GNU_VERSYM, 0x98, 6, 0, 0),
+            (SHT_GNU_VERDEF, 0xa0, 56, 2, 2),
+        ];
+        for (i, (kind, offset, size, link, info)) in sections.into_iter().enumerate() {
+            let header = 0xd8 + 64 * i;
+            put(&mut data, header + 4, &kind.to_le_bytes());
+            put(&mut data, header + 24, &u64::to_le_bytes(offset));
+            put(&mut data, header + 32, &u64::to_le_bytes(size));
+            put(&mut data, header + 40, &u32::to_le_bytes(link));
+            put(&mut data, header + 44, &u32::to_le_bytes(info));
+        }
+        data
+    }
+
+    #[test]
+    fn versions_of_symbol() {
+        let data = versioned_elf_file();
+        assert_eq!(elf_symbol_versions(&data, "foo"), Some(vec!["V1".to_owned(), "V2".to_owned()]));
+        assert_eq!(elf_symbol_versions(&data, "bar"), Some(vec![]));
+    }
+
+    #[test]
+    fn truncated_file() {
+        let data = versioned_elf_file();
+        // The section headers are at the end, so every prefix misses some of them.
+        for len in 0..data.len() {
+            assert_eq!(elf_symbol_versions(&data[..len], "foo"), None, "length {len}");
+        }
+        for len in 0..20 {
+            assert_eq!(parse_elf_header(&data[..len]), None, "length {len}");
+        }
+    }
+
+    #[test]
+    fn garbage_file() {
+        let data = versioned_elf_file();
+        let patched = |offset: usize, bytes: &[u8]| {
+            let mut data = data.clone();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            elf_symbol_versions(&data, "foo")
+        };
+        // The section header table starts past the end of the address space.
+        assert_eq!(patched(0x28, &u64::MAX.to_le_bytes()), None);
+        // The symbol table links to a section that does not exist.
+        assert_eq!(patched(0xd8 + 64 + 40, &7u32.to_le_bytes()), None);
+        // The symbol table extends past the end of the file.
+        assert_eq!(patched(0xd8 + 64 + 32, &u64::MAX.to_le_bytes()), None);
+        // A version definition whose `vd_next` points past the end of the file.
+        assert_eq!(patched(0xa0 + 16, &u32::MAX.to_le_bytes()), None);
+        // A symbol name past the end of the file.
+        assert_eq!(patched(0x68, &u32::MAX.to_le_bytes()), None);
+
+        // Random bytes after a valid start of a header must not make the parser panic.
+        let mut state = 0x2545f4914f6cdd1du64;
+        for _ in 0..100 {
+            let mut data = elf_header(2, 1, 62);
+            data.extend((0..4096).map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            }));
+            let _ = elf_symbol_versions(&data, "foo");
+        }
+    }
+}
@@ -26,6 +26,20 @@ fn windows_check_buffer_size((success, len): (bool, u64)) -> u32 {
     }
 }
 
+/// The process ID and the parent process ID that the program sees under isolation.
+const ISOLATED_PID: u32 = 1000;
+const ISOLATED_PPID: u32 = 1;
+
+#[cfg(unix)]
+fn host_parent_id() -> Option<u32> {
+    Some(std::os::unix::process::parent_id())
+}
+
+#[cfg(not(unix))]
+fn host_parent_id() -> Option<u32> {
+    None
+}
+
 #[derive(Default)]
 pub struct EnvVars<'tcx> {
     /// Stores pointers to the environment variables. These variables must be stored as
@@ -34,6 +48,19 @@ pub struct EnvVars<'tcx> {
 
     /// Place where the `environ` static is stored. Lazily initialized, but then never changes.
     pub(crate) environ: Option<MPlaceTy<'tcx, Provenance>>,
+
+    /// Whether changes to the variables are also made to the environment of the host.
+    write_through: bool,
+
+    /// The values that the host variables changed by the program had before, so that they can
+    /// be restored at the end. `None` means the variable did not exist.
+    host_originals: FxHashMap<OsString, Option<OsString>>,
+
+    /// The snapshots taken with `miri_env_snapshot`, as names and values.
+    snapshots: Vec<Vec<(OsString, OsString)>>,
+
+    /// The environment blocks returned by `GetEnvironmentStringsW` that were not freed yet.
+    env_blocks: Vec<Pointer<Option<Provenance>>>,
 }
 
 impl<'tcx> EnvVars<'tcx> {
@@ -70,9 +97,33 @@ impl<'tcx> EnvVars<'tcx> {
                 }
             }
         }
+        ecx.machine.env_vars.write_through = config.env_write_through && ecx.machine.communicate();
         ecx.update_environ()
     }
 
+    /// Makes the same change to the environment of the host, if write-through is enabled.
+    /// `None` removes the variable.
+    fn write_to_host(&mut self, name: &OsStr, value: Option<&OsStr>) {
+        if !self.write_through {
+            return;
+        }
+        self.host_originals.entry(name.to_owned()).or_insert_with(|| env::var_os(name));
+        match value {
+            Some(value) => env::set_var(name, value),
+            None => env::remove_var(name),
+        }
+    }
+
+    /// Undoes all the changes that the program made to the environment of the host.
+    pub(crate) fn restore_host(&mut self) {
+        for (name, value) in self.host_originals.drain() {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+    }
+
     pub(crate) fn cleanup<'mir>(
         ecx: &mut InterpCx<'mir, 'tcx, Evaluator<'mir, 'tcx>>,
     ) -> InterpResult<'tcx> {
@@ -111,6 +162,35 @@ fn alloc_env_var_as_wide_str<'mir, 'tcx>(
     ecx.alloc_os_str_as_wide_str(name_osstring.as_os_str(), MiriMemoryKind::Runtime.into())
 }
 
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Reads the value of the variable `name`, which is stored at `var_ptr` as `"{name}={value}"`.
+    fn read_env_var_value(
+        &self,
+        name: &OsStr,
+        var_ptr: Pointer<Option<Provenance>>,
+    ) -> InterpResult<'tcx, OsString> {
+        let this = self.eval_context_ref();
+        // The offset is used to strip the "{name}=" part of the string.
+        let name_offset = u64::try_from(name.len()).unwrap().checked_add(1).unwrap();
+        if this.tcx.sess.target.os == "windows" {
+            let name_offset_bytes = name_offset.checked_mul(2).unwrap();
+            let value_ptr = var_ptr.offset(Size::from_bytes(name_offset_bytes), this)?;
+            this.read_os_str_from_wide_str(value_ptr)
+        } else {
+            let value_ptr = var_ptr.offset(Size::from_bytes(name_offset), this)?;
+            Ok(this.read_os_str_from_c_str(value_ptr)?.to_owned())
+        }
+    }
+
+    /// The name under which the variable `name` is stored on Windows, where the names of
+    /// variables are not case-sensitive.
+    fn windows_env_var_name(&self, name: &OsStr) -> Option<OsString> {
+        let this = self.eval_context_ref();
+        this.machine.env_vars.map.keys().find(|key| key.eq_ignore_ascii_case(name)).cloned()
+    }
+}
+
 impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
 pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
     fn getenv(
@@ -148,6 +228,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         let name_ptr = this.read_pointer(name_op)?;
         let name = this.read_os_str_from_wide_str(name_ptr)?;
+        let name = this.windows_env_var_name(&name).unwrap_or(name);
         Ok(match this.machine.env_vars.map.get(&name) {
             Some(var_ptr) => {
                 // The offset is used to strip the "{name}=" part of the string.
@@ -178,16 +259,24 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         // Info on layout of environment blocks in Windows:
         // https://docs.microsoft.com/en-us/windows/win32/procthread/environment-variables
+        // Like on Windows, the variables are sorted by their names, ignoring case.
+        let mut vars: Vec<_> = this.machine.env_vars.map.iter().collect();
+        vars.sort_by_cached_key(|(name, _)| name.to_string_lossy().to_uppercase());
         let mut env_vars = std::ffi::OsString::new();
-        for &item in this.machine.env_vars.map.values() {
+        for (_, &item) in vars {
             let env_var = this.read_os_str_from_wide_str(item)?;
             env_vars.push(env_var);
             env_vars.push("\0");
         }
+        // An empty block still ends with two null terminators.
+        if env_vars.is_empty() {
+            env_vars.push("\0");
+        }
         // Allocate environment block & Store environment variables to environment block.
         // Final null terminator(block terminator) is added by `alloc_os_str_to_wide_str`.
         let envblock_ptr =
             this.alloc_os_str_as_wide_str(&env_vars, MiriMemoryKind::Runtime.into())?;
+        this.machine.env_vars.env_blocks.push(envblock_ptr);
         // If the function succeeds, the return value is a pointer to the environment block of the current process.
         Ok(envblock_ptr)
     }
@@ -201,9 +290,17 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         this.assert_target_os("windows", "FreeEnvironmentStringsW");
 
         let env_block_ptr = this.read_pointer(env_block_op)?;
-        let result = this.deallocate_ptr(env_block_ptr, None, MiriMemoryKind::Runtime.into());
+        let env_blocks = &mut this.machine.env_vars.env_blocks;
+        let Some(idx) = env_blocks.iter().position(|block| block.addr() == env_block_ptr.addr())
+        else {
+            throw_ub_format!(
+                "`FreeEnvironmentStringsW` called on memory not from `GetEnvironmentStringsW`"
+            );
+        };
+        env_blocks.swap_remove(idx);
+        this.deallocate_ptr(env_block_ptr, None, MiriMemoryKind::Runtime.into())?;
         // If the function succeeds, the return value is nonzero.
-        Ok(i32::from(result.is_ok()))
+        Ok(1)
     }
 
     fn setenv(
@@ -227,6 +324,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         }
         if let Some((name, value)) = new {
             let var_ptr = alloc_env_var_as_c_str(&name, &value, this)?;
+            this.machine.env_vars.write_to_host(&name, Some(&value));
             if let Some(var) = this.machine.env_vars.map.insert(name, var_ptr) {
                 this.deallocate_ptr(var, None, MiriMemoryKind::Runtime.into())?;
             }
@@ -258,26 +356,30 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         }
 
         let name = this.read_os_str_from_wide_str(name_ptr)?;
-        if name.is_empty() {
-            throw_unsup_format!("environment variable name is an empty string");
-        } else if name.to_string_lossy().contains('=') {
-            throw_unsup_format!("environment variable name contains '='");
-        } else if this.ptr_is_null(value_ptr)? {
+        if name.is_empty() || name.to_string_lossy().contains('=') {
+            let invalid_parameter = this.eval_windows("c", "ERROR_INVALID_PARAMETER")?;
+            this.set_last_error(invalid_parameter)?;
+            return Ok(0);
+        }
+        // The variable replaces the one whose name only differs in case, if there is one.
+        let old_var = match this.windows_env_var_name(&name) {
+            Some(old_name) => this.machine.env_vars.map.remove(&old_name),
+            None => None,
+        };
+        if let Some(var) = old_var {
+            this.deallocate_ptr(var, None, MiriMemoryKind::Runtime.into())?;
+        }
+        if this.ptr_is_null(value_ptr)? {
             // Delete environment variable `{name}`
-            if let Some(var) = this.machine.env_vars.map.remove(&name) {
-                this.deallocate_ptr(var, None, MiriMemoryKind::Runtime.into())?;
-                this.update_environ()?;
-            }
-            Ok(1) // return non-zero on success
+            this.machine.env_vars.write_to_host(&name, None);
         } else {
             let value = this.read_os_str_from_wide_str(value_ptr)?;
             let var_ptr = alloc_env_var_as_wide_str(&name, &value, this)?;
-            if let Some(var) = this.machine.env_vars.map.insert(name, var_ptr) {
-                this.deallocate_ptr(var, None, MiriMemoryKind::Runtime.into())?;
-            }
-            this.update_environ()?;
-            Ok(1) // return non-zero on success
+            this.machine.env_vars.write_to_host(&name, Some(&value));
+            this.machine.env_vars.map.insert(name, var_ptr);
         }
+        this.update_environ()?;
+        Ok(1) // return non-zero on success
     }
 
     fn unsetenv(&mut self, name_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
@@ -289,6 +391,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         if !this.ptr_is_null(name_ptr)? {
             let name = this.read_os_str_from_c_str(name_ptr)?.to_owned();
             if !name.is_empty() && !name.to_string_lossy().contains('=') {
+                this.machine.env_vars.write_to_host(&name, None);
                 success = Some(this.machine.env_vars.map.remove(&name));
             }
         }
@@ -451,17 +554,90 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(())
     }
 
+    /// Takes a snapshot of the environment variables, and returns its index.
+    fn miri_env_snapshot(&mut self) -> InterpResult<'tcx, u64> {
+        let this = self.eval_context_mut();
+
+        let mut vars = Vec::new();
+        for (name, &var_ptr) in &this.machine.env_vars.map {
+            vars.push((name.clone(), this.read_env_var_value(name, var_ptr)?));
+        }
+        let snapshots = &mut this.machine.env_vars.snapshots;
+        snapshots.push(vars);
+        Ok(u64::try_from(snapshots.len().checked_sub(1).unwrap()).unwrap())
+    }
+
+    /// Replaces the environment variables with those of the snapshot with the given index.
+    fn miri_env_restore(&mut self, snapshot: u64) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let Some(vars) = usize::try_from(snapshot)
+            .ok()
+            .and_then(|idx| this.machine.env_vars.snapshots.get(idx))
+            .cloned()
+        else {
+            throw_unsup_format!("`miri_env_restore` called with invalid snapshot {snapshot}");
+        };
+
+        let old_vars = mem::take(&mut this.machine.env_vars.map);
+        for (name, var_ptr) in old_vars {
+            if !vars.iter().any(|(snapshot_name, _)| *snapshot_name == name) {
+                this.machine.env_vars.write_to_host(&name, None);
+            }
+            this.deallocate_ptr(var_ptr, None, MiriMemoryKind::Runtime.into())?;
+        }
+        let windows = this.tcx.sess.target.os == "windows";
+        for (name, value) in vars {
+            let var_ptr = if windows {
+                alloc_env_var_as_wide_str(&name, &value, this)?
+            } else {
+                alloc_env_var_as_c_str(&name, &value, this)?
+            };
+            this.machine.env_vars.write_to_host(&name, Some(&value));
+            this.machine.env_vars.map.insert(name, var_ptr);
+        }
+        this.update_environ()
+    }
+
+    /// The ID of the process. Under isolation, it is always the same made-up ID.
+    fn get_pid(&self) -> u32 {
+        let this = self.eval_context_ref();
+        if this.machine.communicate() { std::process::id() } else { ISOLATED_PID }
+    }
+
     fn getpid(&mut self) -> InterpResult<'tcx, i32> {
         let this = self.eval_context_mut();
         this.assert_target_os_is_unix("getpid");
 
-        this.check_no_isolation("`getpid`")?;
-
         // The reason we need to do this wacky of a conversion is because
         // `libc::getpid` returns an i32, however, `std::process::id()` return an u32.
         // So we un-do the conversion that stdlib does and turn it back into an i32.
         #[allow(clippy::cast_possible_wrap)]
-        Ok(std::process::id() as i32)
+        Ok(this.get_pid() as i32)
+    }
+
+    /// Hosts that are not a Unix have no parent process ID, so there the made-up one is used even
+    /// without isolation.
+    fn getppid(&mut self) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+        this.assert_target_os_is_unix("getppid");
+
+        let ppid = host_parent_id().filter(|_| this.machine.communicate()).unwrap_or(ISOLATED_PPID);
+        #[allow(clippy::cast_possible_wrap)]
+        Ok(ppid as i32)
+    }
+
+    /// Like on Linux, the main thread has the ID of the process, and the other threads count up
+    /// from there. Once the program has seen a thread ID, diagnostics show them as well.
+    fn gettid(&mut self) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+        this.assert_target_os("linux", "gettid");
+
+        let pid = this.get_pid();
+        let thread = this.get_active_thread();
+        let tid = this.machine.threads.os_thread_id(thread, pid);
+        #[allow(clippy::cast_possible_wrap)]
+        Ok(tid as i32)
     }
 
     #[allow(non_snake_case)]
@@ -469,8 +645,6 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_mut();
         this.assert_target_os("windows", "GetCurrentProcessId");
 
-        this.check_no_isolation("`GetCurrentProcessId`")?;
-
-        Ok(std::process::id())
+        Ok(this.get_pid())
     }
 }
@@ -1,10 +1,29 @@
-use libffi::{high::call as ffi, low::CodePtr};
+use libffi::{
+    low::CodePtr,
+    middle::{Arg, Cif, Type},
+    raw::{ffi_arg, ffi_sarg},
+};
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use rustc_middle::ty::{self as ty, IntTy, Ty, UintTy};
-use rustc_span::Symbol;
-use rustc_target::abi::HasDataLayout;
+use rustc_apfloat::{
+    ieee::{Double, Quad, X87DoubleExtended},
+    Float, FloatConvert,
+};
+use rustc_ast::ast::Mutability;
+use rustc_data_structures::fx::FxHashMap;
+use rustc_middle::ty::{
+    self as ty,
+    layout::{LayoutOf, TyAndLayout},
+    IntTy, Ty, UintTy,
+};
+use rustc_span::{def_id::LOCAL_CRATE, Symbol};
+use rustc_target::abi::{self, Endian, FieldsShape, HasDataLayout, Size, Variants};
 
+use crate::shims::elf::{dlvsym_from_lib, elf_symbol_versions, split_symbol_version};
 use crate::*;
 
 impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
@@ -65,14 +84,364 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         );
     }
 
+    /// If `layout` is a `#[repr(transparent)]` wrapper, or a `#[repr(C)]` struct with a single
+    /// field, returns the index of the field that determines its ABI.
+    fn ffi_newtype_inner_field(&self, layout: TyAndLayout<'tcx>) -> Option<usize> {
+        let this = self.eval_context_ref();
+        let ty::Adt(adt_def, _) = layout.ty.kind() else { return None };
+        if !adt_def.is_struct() {
+            return None;
+        }
+        if adt_def.repr().transparent() {
+            // The only field that is not a ZST.
+            (0..layout.fields.count()).find(|&idx| !layout.field(this, idx).is_zst())
+        } else if adt_def.repr().c() && layout.fields.count() == 1 {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// Peel off newtype wrappers (see `ffi_newtype_inner_field`) from an argument, since they
+    /// are passed to C just like the value they wrap.
+    /// The `long double` type (see `is_c_long_double`) is not peeled; it has a special meaning.
+    fn peel_ffi_newtype_arg(
+        &self,
+        arg: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, OpTy<'tcx, Provenance>> {
+        let this = self.eval_context_ref();
+        let mut arg = arg.clone();
+        while !this.is_c_long_double(arg.layout.ty)? {
+            let Some(idx) = this.ffi_newtype_inner_field(arg.layout) else { break };
+            arg = this.operand_field(&arg, idx)?;
+        }
+        Ok(arg)
+    }
+
+    /// Peel off `#[repr(transparent)]` wrappers from the return place, since C returns them just
+    /// like the value they wrap. `#[repr(C)]` structs are returned as structs, even with a single
+    /// field: on some targets (e.g., i686 Linux), all structs are returned through a hidden
+    /// pointer.
+    /// The `long double` type (see `is_c_long_double`) is not peeled; it has a special meaning.
+    fn peel_ffi_newtype_dest(
+        &mut self,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, PlaceTy<'tcx, Provenance>> {
+        let this = self.eval_context_mut();
+        let mut dest = dest.clone();
+        while !this.is_c_long_double(dest.layout.ty)? {
+            let ty::Adt(adt_def, _) = dest.layout.ty.kind() else { break };
+            if !adt_def.repr().transparent() {
+                break;
+            }
+            let Some(idx) = this.ffi_newtype_inner_field(dest.layout) else { break };
+            dest = this.place_field(&dest, idx)?;
+        }
+        Ok(dest)
+    }
+
+    /// Returns whether `ty` is a `#[repr(C)]` struct.
+    fn is_repr_c_struct(&self, ty: Ty<'tcx>) -> bool {
+        matches!(ty.kind(), ty::Adt(adt_def, _) if adt_def.is_struct() && adt_def.repr().c())
+    }
+
+    /// Collect the leaves of `layout` (shifted by `offset`) in memory order, together with their
+    /// offsets. Leaves are primitives, as well as enums and unions: we cannot know which variant
+    /// is active without reading memory, and C is free to write any of them, so those are treated
+    /// as opaque blobs.
+    fn collect_leaves(
+        &self,
+        layout: TyAndLayout<'tcx>,
+        offset: Size,
+        leaves: &mut Vec<(Size, TyAndLayout<'tcx>)>,
+    ) {
+        let this = self.eval_context_ref();
+        if layout.is_zst() {
+            return;
+        }
+        match (&layout.fields, &layout.variants) {
+            (_, Variants::Multiple { .. })
+            | (FieldsShape::Union(..), _)
+            | (FieldsShape::Primitive, _) => leaves.push((offset, layout)),
+            (FieldsShape::Array { stride, count }, _) => {
+                let elem = layout.field(this, 0);
+                for idx in 0..*count {
+                    this.collect_leaves(elem, offset + *stride * idx, leaves);
+                }
+            }
+            (FieldsShape::Arbitrary { .. }, _) => {
+                let mut fields = (0..layout.fields.count())
+                    .map(|idx| (layout.fields.offset(idx), layout.field(this, idx)))
+                    .collect::<Vec<_>>();
+                fields.sort_by_key(|(field_offset, _)| *field_offset);
+                for (field_offset, field) in fields {
+                    this.collect_leaves(field, offset + field_offset, leaves);
+                }
+            }
+        }
+    }
+
+    /// Collect the byte ranges of `layout` that hold actual field data, i.e., everything except
+    /// padding. Adjacent ranges are merged.
+    fn collect_data_ranges(&self, layout: TyAndLayout<'tcx>) -> Vec<AllocRange> {
+        let mut leaves = Vec::new();
+        self.collect_leaves(layout, Size::ZERO, &mut leaves);
+        let mut ranges: Vec<AllocRange> = Vec::new();
+        for (offset, leaf) in leaves {
+            if let Some(last) = ranges.last_mut() {
+                if last.end() == offset {
+                    last.size += leaf.size;
+                    continue;
+                }
+            }
+            ranges.push(alloc_range(offset, leaf.size));
+        }
+        ranges
+    }
+
+    /// Copy the field data in `bytes` (a host copy of a struct) to `ptr` in Miri memory.
+    /// Only the bytes of actual fields are written (and hence marked as initialized); padding in
+    /// Miri memory is left untouched.
+    fn write_host_struct_bytes(
+        &mut self,
+        ptr: Pointer<Option<Provenance>>,
+        data_ranges: &[AllocRange],
+        bytes: &[u8],
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        for range in data_ranges {
+            let bytes = &bytes[range.start.bytes_usize()..range.end().bytes_usize()];
+            this.write_bytes_ptr(ptr.offset(range.start, this)?, bytes.iter().copied())?;
+        }
+        Ok(())
+    }
+
+    /// Describe the `#[repr(C)]` struct `layout` to libffi, so that libffi can apply the C ABI
+    /// for it.
+    fn ffi_type_of_layout(&self, layout: TyAndLayout<'tcx>) -> InterpResult<'tcx, Type> {
+        let this = self.eval_context_ref();
+        let mut leaves = Vec::new();
+        this.collect_leaves(layout, Size::ZERO, &mut leaves);
+        let mut fields = Vec::with_capacity(leaves.len());
+        for (_offset, leaf) in leaves {
+            let abi::Abi::Scalar(scalar) = leaf.abi else {
+                throw_unsup_format!(
+                    "unsupported field type in struct passed to external C function: {:?}",
+                    leaf.ty
+                );
+            };
+            fields.push(match scalar.primitive() {
+                abi::Primitive::Int(abi::Integer::I8, _) => Type::u8(),
+                abi::Primitive::Int(abi::Integer::I16, _) => Type::u16(),
+                abi::Primitive::Int(abi::Integer::I32, _) => Type::u32(),
+                abi::Primitive::Int(abi::Integer::I64, _) => Type::u64(),
+                abi::Primitive::F32 => Type::f32(),
+                abi::Primitive::F64 => Type::f64(),
+                abi::Primitive::Pointer => Type::pointer(),
+                _ =>
+                    throw_unsup_format!(
+                        "unsupported field type in struct passed to external C function: {:?}",
+                        leaf.ty
+                    ),
+            });
+        }
+        // `repr(C)` lays out fields just like C does, so the leaves in memory order describe
+        // the same layout. (Nested structs only matter for the ABI in their alignment, which
+        // the leaves already imply.)
+        Ok(Type::structure(fields))
+    }
+
+    /// Call an external C function that returns a `#[repr(C)]` struct, and store the struct to
+    /// `dest`.
+    fn call_external_c_and_store_struct(
+        &mut self,
+        link_name: Symbol,
+        dest: &PlaceTy<'tcx, Provenance>,
+        ptr: CodePtr,
+        libffi_args: &[CArg],
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let layout = dest.layout;
+        if layout.align.abi.bytes() > HostBuffer::MAX_ALIGN {
+            throw_unsup_format!(
+                "struct returned from external C function is aligned to more than {} bytes: {:?}",
+                HostBuffer::MAX_ALIGN,
+                layout.ty
+            );
+        }
+        // libffi knows how the target's C ABI returns a struct of this type, be it in registers
+        // or in memory provided by the caller.
+        let ret_ty = this.ffi_type_of_layout(layout)?;
+        let mut buf = HostBuffer::new(layout.size);
+        // Unsafe because of the call to external C code.
+        let res = unsafe {
+            this.call_external(link_name, || ffi_call_into(ptr, libffi_args, ret_ty, &mut buf))
+        };
+        if res.is_err() {
+            // The call did not return, and might still write to `buf`.
+            std::mem::forget(buf);
+        }
+        res?;
+
+        let dest = this.force_allocation(dest)?;
+        let data_ranges = this.collect_data_ranges(layout);
+        this.write_host_struct_bytes(dest.ptr, &data_ranges, buf.bytes())
+    }
+
+    /// Copy the `#[repr(C)]` struct that the raw pointer `ptr` (of type `ptr_ty`) points to into
+    /// host memory, so that C code can read and fill it in.
+    /// Returns `None` for null pointers, which are passed to C unchanged.
+    fn struct_ptr_to_host(
+        &mut self,
+        ptr: Pointer<Option<Provenance>>,
+        ptr_ty: Ty<'tcx>,
+    ) -> InterpResult<'tcx, Option<HostStruct>> {
+        let this = self.eval_context_mut();
+        let ty::RawPtr(ty::TypeAndMut { ty: pointee, mutbl }) = ptr_ty.kind() else {
+            bug!("struct_ptr_to_host called on non-pointer type {:?}", ptr_ty)
+        };
+        assert!(this.is_repr_c_struct(*pointee));
+        if this.ptr_is_null(ptr)? {
+            return Ok(None);
+        }
+        let layout = this.layout_of(*pointee)?;
+        if layout.align.abi.bytes() > HostBuffer::MAX_ALIGN {
+            throw_unsup_format!(
+                "pointee of pointer argument to external C function is aligned to more than {} bytes: {:?}",
+                HostBuffer::MAX_ALIGN,
+                ptr_ty
+            );
+        }
+        if *mutbl == Mutability::Mut {
+            // The C code could change the pointers, and they would lose their provenance when
+            // they are copied back.
+            let mut leaves = Vec::new();
+            this.collect_leaves(layout, Size::ZERO, &mut leaves);
+            let is_ptr = |leaf: &TyAndLayout<'tcx>| {
+                matches!(leaf.abi, abi::Abi::Scalar(s) if s.primitive() == abi::Primitive::Pointer)
+            };
+            if let Some((_, leaf)) = leaves.iter().find(|(_, leaf)| is_ptr(leaf)) {
+                throw_unsup_format!(
+                    "unsupported pointer field in struct passed by mutable pointer to external C function: {:?}",
+                    leaf.ty
+                );
+            }
+        }
+        let data_ranges = this.collect_data_ranges(layout);
+        let mut buf = HostBuffer::new(layout.size);
+
+        this.check_ptr_access_align(
+            ptr,
+            layout.size,
+            layout.align.abi,
+            CheckInAllocMsg::MemoryAccessTest,
+        )?;
+        // Only initialized field data is copied; uninitialized bytes and padding are zero on
+        // the host side. The field data is read like any other access, so that the aliasing and
+        // data race models know that the struct is being read.
+        for range in &data_ranges {
+            let (alloc_id, offset, _) = this.ptr_get_alloc_id(ptr)?;
+            let start = offset + range.start;
+            let end = offset + range.end();
+            let alloc = this.get_alloc_raw(alloc_id)?;
+            if alloc.init_mask().is_range_initialized(start, end).is_err() {
+                continue;
+            }
+            let bytes =
+                this.read_bytes_ptr_strip_provenance(ptr.offset(range.start, this)?, range.size)?;
+            buf.bytes_mut()[range.start.bytes_usize()..range.end().bytes_usize()]
+                .copy_from_slice(bytes);
+        }
+        Ok(Some(HostStruct { ptr, data_ranges, mutbl: *mutbl, buf }))
+    }
+
+    /// After the C call, copy back the field data of a struct that was passed by mutable
+    /// pointer.
+    fn write_back_host_struct(&mut self, host: &HostStruct) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        if host.mutbl == Mutability::Not {
+            return Ok(());
+        }
+        this.write_host_struct_bytes(host.ptr, &host.data_ranges, host.buf.bytes())
+    }
+
+    /// The representation of C's `long double` on the target.
+    fn long_double_repr(&self) -> InterpResult<'tcx, LongDoubleRepr> {
+        let target = &self.eval_context_ref().tcx.sess.target;
+        Ok(match target.arch.as_ref() {
+            "x86" | "x86_64" if !target.is_like_msvc => LongDoubleRepr::X87,
+            "aarch64" if !target.is_like_osx && !target.is_like_windows => LongDoubleRepr::Quad,
+            "riscv64" | "s390x" => LongDoubleRepr::Quad,
+            // IBM's double-double format cannot be converted exactly.
+            "powerpc" | "powerpc64" if target.env != "musl" =>
+                throw_unsup_format!(
+                    "passing `long double` to external C functions is not supported on {}",
+                    target.arch
+                ),
+            _ => LongDoubleRepr::Double,
+        })
+    }
+
+    /// Rust has no `long double` type, so it is represented by a struct with a single `f64`
+    /// field, whose path is given with `-Zmiri-extern-so-long-double`. Returns whether `ty` is
+    /// that struct.
+    fn is_c_long_double(&self, ty: Ty<'tcx>) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_ref();
+        let Some(long_double) = &this.machine.external_so_long_double else { return Ok(false) };
+        let ty::Adt(adt_def, _) = ty.kind() else { return Ok(false) };
+        let did = adt_def.did();
+        let path = format!(
+            "{}{}",
+            this.tcx.crate_name(did.krate),
+            this.tcx.def_path(did).to_string_no_crate_verbose()
+        );
+        if path != *long_double {
+            return Ok(false);
+        }
+        let layout = this.layout_of(ty)?;
+        if !adt_def.is_struct()
+            || layout.fields.count() != 1
+            || layout.field(this, 0).ty != this.tcx.types.f64
+        {
+            throw_unsup_format!(
+                "the `-Zmiri-extern-so-long-double` type must be a struct with a single `f64` field: {:?}",
+                ty
+            );
+        }
+        Ok(true)
+    }
+
+    /// Run `call`, which calls the external C function `link_name`. With
+    /// `-Zmiri-extern-call-timeout`, it runs on the watchdog thread, and an error is returned if it
+    /// does not return in time.
+    ///
+    /// SAFETY: `call` calls external C code. If it does not return in time, all data it refers to
+    /// must be leaked, since the external code might still access it.
+    unsafe fn call_external<R: Send>(
+        &mut self,
+        link_name: Symbol,
+        call: impl FnOnce() -> R,
+    ) -> InterpResult<'tcx, R> {
+        let this = self.eval_context_mut();
+        let Some(watchdog) = &mut this.machine.external_call_watchdog else { return Ok(call()) };
+        match watchdog.call(call) {
+            Some(ret) => Ok(ret),
+            None =>
+                throw_machine_stop!(TerminationInfo::Abort(format!(
+                    "external function `{}` did not return within {:?}",
+                    link_name, watchdog.timeout
+                ))),
+        }
+    }
+
     /// Call external C function and
     /// store output, depending on return type in the function signature.
-    fn call_external_c_and_store_return<'a>(
+    fn call_external_c_and_store_return(
         &mut self,
         link_name: Symbol,
         dest: &PlaceTy<'tcx, Provenance>,
         ptr: CodePtr,
-        libffi_args: Vec<libffi::high::Arg<'a>>,
+        libffi_args: &[CArg],
     ) -> InterpResult<'tcx, ()> {
         let this = self.eval_context_mut();
 
@@ -83,93 +452,276 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             // If the return type of a function is a primitive integer type,
             // then call the function (`ptr`) with arguments `libffi_args`, store the return value as the specified
             // primitive integer type, and then write this value out to the miri memory as an integer.
+            // Integers narrower than a register are returned by libffi widened to `ffi_arg`, so
+            // that is the type we read them as.
             match dest.layout.ty.kind() {
                 // ints
                 ty::Int(IntTy::I8) => {
-                    let x = ffi::call::<i8>(ptr, libffi_args.as_slice());
-                    this.write_int(x, dest)?;
+                    let x = this.call_external(link_name, || {
+                        ffi_call::<ffi_sarg>(ptr, libffi_args, Type::i8())
+                    })?;
+                    this.write_int(ffi_return_int::<_, i8>(x, link_name)?, dest)?;
                     return Ok(());
                 }
                 ty::Int(IntTy::I16) => {
-                    let x = ffi::call::<i16>(ptr, libffi_args.as_slice());
-                    this.write_int(x, dest)?;
+                    let x = this.call_external(link_name, || {
+                        ffi_call::<ffi_sarg>(ptr, libffi_args, Type::i16())
+                    })?;
+                    this.write_int(ffi_return_int::<_, i16>(x, link_name)?, dest)?;
                     return Ok(());
                 }
                 ty::Int(IntTy::I32) => {
-                    let x = ffi::call::<i32>(ptr, libffi_args.as_slice());
-                    this.write_int(x, dest)?;
+                    let x = this.call_external(link_name, || {
+                        ffi_call::<ffi_sarg>(ptr, libffi_args, Type::i32())
+                    })?;
+                    this.write_int(ffi_return_int::<_, i32>(x, link_name)?, dest)?;
                     return Ok(());
                 }
                 ty::Int(IntTy::I64) => {
-                    let x = ffi::call::<i64>(ptr, libffi_args.as_slice());
+                    let x = this.call_external(link_name, || {
+                        ffi_call::<i64>(ptr, libffi_args, Type::i64())
+                    })?;
                     this.write_int(x, dest)?;
                     return Ok(());
                 }
                 ty::Int(IntTy::Isize) => {
-                    let x = ffi::call::<isize>(ptr, libffi_args.as_slice());
+                    let x = this.call_external(link_name, || {
+                        ffi_call::<isize>(ptr, libffi_args, Type::isize())
+                    })?;
                     // `isize` doesn't `impl Into<i128>`, so convert manually.
                     // Convert to `i64` since this covers both 32- and 64-bit machines.
-                    this.write_int(i64::try_from(x).unwrap(), dest)?;
+                    this.write_int(ffi_return_int::<_, i64>(x, link_name)?, dest)?;
                     return Ok(());
                 }
                 // uints
                 ty::Uint(UintTy::U8) => {
-                    let x = ffi::call::<u8>(ptr, libffi_args.as_slice());
-                    this.write_int(x, dest)?;
+                    let x = this.call_external(link_name, || {
+                        ffi_call::<ffi_arg>(ptr, libffi_args, Type::u8())
+                    })?;
+                    this.write_int(ffi_return_int::<_, u8>(x, link_name)?, dest)?;
                     return Ok(());
                 }
                 ty::Uint(UintTy::U16) => {
-                    let x = ffi::call::<u16>(ptr, libffi_args.as_slice());
-                    this.write_int(x, dest)?;
+                    let x = this.call_external(link_name, || {
+                        ffi_call::<ffi_arg>(ptr, libffi_args, Type::u16())
+                    })?;
+                    this.write_int(ffi_return_int::<_, u16>(x, link_name)?, dest)?;
                     return Ok(());
                 }
                 ty::Uint(UintTy::U32) => {
-                    let x = ffi::call::<u32>(ptr, libffi_args.as_slice());
-                    this.write_int(x, dest)?;
+                    let x = this.call_external(link_name, || {
+                        ffi_call::<ffi_arg>(ptr, libffi_args, Type::u32())
+                    })?;
+                    this.write_int(ffi_return_int::<_, u32>(x, link_name)?, dest)?;
                     return Ok(());
                 }
                 ty::Uint(UintTy::U64) => {
-                    let x = ffi::call::<u64>(ptr, libffi_args.as_slice());
+                    let x = this.call_external(link_name, || {
+                        ffi_call::<u64>(ptr, libffi_args, Type::u64())
+                    })?;
                     this.write_int(x, dest)?;
                     return Ok(());
                 }
                 ty::Uint(UintTy::Usize) => {
-                    let x = ffi::call::<usize>(ptr, libffi_args.as_slice());
+                    let x = this.call_external(link_name, || {
+                        ffi_call::<usize>(ptr, libffi_args, Type::usize())
+                    })?;
                     // `usize` doesn't `impl Into<i128>`, so convert manually.
                     // Convert to `u64` since this covers both 32- and 64-bit machines.
-                    this.write_int(u64::try_from(x).unwrap(), dest)?;
+                    this.write_int(ffi_return_int::<_, u64>(x, link_name)?, dest)?;
                     return Ok(());
                 }
                 // Functions with no declared return type (i.e., the default return)
                 // have the output_type `Tuple([])`.
                 ty::Tuple(t_list) =>
                     if t_list.len() == 0 {
-                        ffi::call::<()>(ptr, libffi_args.as_slice());
+                        this.call_external(link_name, || {
+                            ffi_call::<()>(ptr, libffi_args, Type::void())
+                        })?;
                         return Ok(());
                     },
                 _ => {}
             }
+            if this.is_repr_c_struct(dest.layout.ty) && !this.is_c_long_double(dest.layout.ty)? {
+                this.call_external_c_and_store_struct(link_name, dest, ptr, libffi_args)?;
+                return Ok(());
+            }
+            if this.is_c_long_double(dest.layout.ty)? {
+                let x = this.call_external(link_name, || {
+                    ffi_call::<LongDouble>(ptr, libffi_args, Type::longdouble())
+                })?;
+                let x = x.to_f64(this.long_double_repr()?, this.data_layout().endian);
+                let field = this.place_field(dest, 0)?;
+                this.write_scalar(Scalar::from_f64(x), &field)?;
+                return Ok(());
+            }
             // FIXME ellen! deal with all the other return types
             throw_unsup_format!("unsupported return type to external C function: {:?}", link_name);
         }
     }
 
+    /// Make the `extern` statics declared anywhere in the program that are defined by the shared
+    /// object file available to the interpreted program. Each of them is copied into a Miri
+    /// allocation; statics that are not `mut` are made read-only, while `static mut`s are kept
+    /// in sync with the library around every call into it (see `sync_lib_statics_to_host`).
+    fn init_lib_extern_statics(&mut self) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let tcx = this.tcx.tcx;
+        let mut statics = Vec::new();
+        for krate in std::iter::once(LOCAL_CRATE).chain(tcx.crates(()).iter().copied()) {
+            for module in tcx.foreign_modules(krate).values() {
+                statics.extend(
+                    module.foreign_items.iter().copied().filter(|&def_id| tcx.is_static(def_id)),
+                );
+            }
+        }
+        // Sort by name so that allocations happen in a deterministic order.
+        let mut statics: Vec<_> =
+            statics.into_iter().map(|def_id| (this.item_link_name(def_id), def_id)).collect();
+        statics.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+        statics.dedup_by_key(|(name, _)| *name);
+
+        for (link_name, def_id) in statics {
+            if this.machine.has_extern_static(link_name) {
+                // Miri's own shims take precedence.
+                continue;
+            }
+            let Some(host_addr) = this.get_symbol_addr_explicitly_from_lib(link_name) else {
+                continue;
+            };
+            let layout = this.layout_of(tcx.type_of(def_id))?;
+            if layout.is_unsized() {
+                // This includes extern types.
+                throw_unsup_format!(
+                    "extern static `{}` defined by the extern shared object file has an unsized type: {:?}",
+                    link_name,
+                    layout.ty
+                );
+            }
+            let mutbl = tcx.static_mutability(def_id).unwrap();
+            let place = this.allocate(layout, MiriMemoryKind::ExternStatic.into())?;
+            let lib_static = LibStatic { ptr: place.ptr, host_addr, size: layout.size };
+            if mutbl == Mutability::Mut {
+                let (_lib, lib_path) = this.machine.external_so_lib.as_ref().unwrap();
+                // SAFETY: the library defines a static of this name, and it is the user's
+                // responsibility that it has the declared type.
+                unsafe { lib_static.reset_to_initial_value(lib_path, link_name.as_str()) };
+            }
+            this.copy_lib_static_from_host(&lib_static)?;
+            if mutbl == Mutability::Not {
+                let alloc_id = this.ptr_get_alloc_id(place.ptr)?.0;
+                this.alloc_mark_immutable(alloc_id)?;
+            } else {
+                this.machine.lib_statics.push(lib_static);
+            }
+            Evaluator::add_extern_static(this, link_name.as_str(), place.ptr);
+        }
+        Ok(())
+    }
+
+    /// Copy the value of `lib_static` from the library to Miri memory. The program does not
+    /// perform this access, so it bypasses the aliasing and data race models.
+    fn copy_lib_static_from_host(&mut self, lib_static: &LibStatic) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let tcx = *this.tcx;
+        let (alloc_id, offset, _) = this.ptr_get_alloc_id(lib_static.ptr)?;
+        let (alloc, _machine) = this.get_alloc_raw_mut(alloc_id)?;
+        let bytes = alloc
+            .get_bytes_mut(&tcx, alloc_range(offset, lib_static.size))
+            .map_err(|e| e.to_interp_error(alloc_id))?;
+        // SAFETY: see `init_lib_extern_statics`.
+        bytes.copy_from_slice(unsafe { lib_static.host_bytes() });
+        Ok(())
+    }
+
+    /// Before calling into the shared object file, copy the current values of its mutable
+    /// statics from Miri memory to the library. The program does not perform these reads, so
+    /// they bypass the aliasing and data race models.
+    fn sync_lib_statics_to_host(&mut self) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        for lib_static in &this.machine.lib_statics {
+            let (alloc_id, offset, _) = this.ptr_get_alloc_id(lib_static.ptr)?;
+            let alloc = this.get_alloc_raw(alloc_id)?;
+            let bytes = alloc.inspect_with_uninit_and_ptr_outside_interpreter(
+                offset.bytes_usize()..(offset + lib_static.size).bytes_usize(),
+            );
+            // SAFETY: see `init_lib_extern_statics`.
+            unsafe { lib_static.host_bytes_mut() }.copy_from_slice(bytes);
+        }
+        Ok(())
+    }
+
+    /// After calling into the shared object file, copy whatever it wrote into its mutable
+    /// statics back to Miri memory.
+    fn sync_lib_statics_from_host(&mut self) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let lib_statics = std::mem::take(&mut this.machine.lib_statics);
+        for lib_static in &lib_statics {
+            this.copy_lib_static_from_host(lib_static)?;
+        }
+        this.machine.lib_statics = lib_statics;
+        Ok(())
+    }
+
     /// Get the pointer to the function of the specified name in the shared object file,
     /// if it exists. The function must be in the shared object file specified: we do *not*
     /// return pointers to functions in dependencies of the library.  
-    fn get_func_ptr_explicitly_from_lib(&mut self, link_name: Symbol) -> Option<CodePtr> {
+    /// Lookups are cached, since `dlsym` and `dladdr` are too slow to be done on every call.
+    fn get_func_ptr_explicitly_from_lib(
+        &mut self,
+        link_name: Symbol,
+    ) -> InterpResult<'tcx, Option<CodePtr>> {
         let this = self.eval_context_mut();
-        // Try getting the function from the shared library.
+        if let Some(&code_ptr) = this.machine.external_fn_cache.get(&link_name) {
+            return Ok(code_ptr);
+        }
+        let code_ptr =
+            this.get_symbol_addr_explicitly_from_lib(link_name).map(|addr| CodePtr(addr.cast()));
+        if code_ptr.is_none() {
+            if let Some((name, version)) = split_symbol_version(link_name.as_str()) {
+                // The user explicitly asked for a symbol from this library, so falling back to
+                // the shims would be surprising.
+                let (_lib, lib_path) = this.machine.external_so_lib.as_ref().unwrap();
+                let versions = std::fs::read(lib_path)
+                    .ok()
+                    .and_then(|data| elf_symbol_versions(&data, name))
+                    .unwrap_or_default();
+                throw_unsup_format!(
+                    "the extern shared object file does not define version `{}` of `{}`; available versions: {}",
+                    version,
+                    name,
+                    if versions.is_empty() { "none".to_owned() } else { versions.join(", ") },
+                );
+            }
+        }
+        this.machine.external_fn_cache.insert(link_name, code_ptr);
+        Ok(code_ptr)
+    }
+
+    /// Get the address of a symbol (function or static) defined in the shared object file
+    /// itself, as opposed to one of its dependencies. `link_name` can request a specific
+    /// version of the symbol with `name@version`.
+    fn get_symbol_addr_explicitly_from_lib(
+        &self,
+        link_name: Symbol,
+    ) -> Option<*mut std::ffi::c_void> {
+        let this = self.eval_context_ref();
+        // Try getting the symbol from the shared library.
         // On windows `_lib_path` will be unused, hence the name starting with `_`.
         let (lib, _lib_path) = this.machine.external_so_lib.as_ref().unwrap();
-        let func: libloading::Symbol<'_, unsafe extern "C" fn()> = unsafe {
-            match lib.get(link_name.as_str().as_bytes()) {
-                Ok(x) => x,
-                Err(_) => {
-                    return None;
-                }
-            }
+        let addr = match split_symbol_version(link_name.as_str()) {
+            Some((name, version)) => dlvsym_from_lib(_lib_path, name, version)?,
+            None =>
+                unsafe {
+                    let sym: libloading::Symbol<'_, *mut std::ffi::c_void> =
+                        lib.get(link_name.as_str().as_bytes()).ok()?;
+                    *sym.deref()
+                },
         };
+        // Undefined weak symbols resolve to null; treat them as not provided by the library.
+        if addr.is_null() {
+            return None;
+        }
 
         // FIXME: this is a hack!
         // The `libloading` crate will automatically load system libraries like `libc`.
@@ -187,7 +739,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let mut info = std::mem::MaybeUninit::<libc::Dl_info>::uninit();
         #[cfg(unix)]
         unsafe {
-            if libc::dladdr(*func.deref() as *const _, info.as_mut_ptr()) != 0 {
+            if libc::dladdr(addr, info.as_mut_ptr()) != 0 {
                 if std::ffi::CStr::from_ptr(info.assume_init().dli_fname).to_str().unwrap()
                     != _lib_path.to_str().unwrap()
                 {
@@ -195,8 +747,8 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 }
             }
         }
-        // Return a pointer to the function.
-        Some(CodePtr(*func.deref() as *mut _))
+        // Return the address of the symbol.
+        Some(addr)
     }
 
     /// Call specified external C function, with supplied arguments.
@@ -211,7 +763,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         args: &[OpTy<'tcx, Provenance>],
     ) -> InterpResult<'tcx, bool> {
         // Get the pointer to the function in the shared object file if it exists.
-        let code_ptr = match self.get_func_ptr_explicitly_from_lib(link_name) {
+        let code_ptr = match self.get_func_ptr_explicitly_from_lib(link_name)? {
             Some(ptr) => ptr,
             None => {
                 // Shared object file does not export this function -- try the shims next.
@@ -223,7 +775,37 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         // Get the function arguments, and convert them to `libffi`-compatible form.
         let mut libffi_args = Vec::<CArg>::with_capacity(args.len());
+        // Host copies of structs passed by pointer. These must stay alive until the call is done.
+        let mut host_structs = Vec::new();
         for cur_arg in args.iter() {
+            let cur_arg = &this.peel_ffi_newtype_arg(cur_arg)?;
+            if let ty::RawPtr(ty::TypeAndMut { ty: pointee, .. }) = cur_arg.layout.ty.kind() {
+                let ptr = this.read_pointer(cur_arg)?;
+                if !this.is_repr_c_struct(*pointee) {
+                    // Other pointers are passed as they are. C cannot access Miri memory through
+                    // them, but it can compare them or hand them back to us.
+                    let addr = ptr.addr().bytes_usize() as *mut std::ffi::c_void;
+                    libffi_args.push(CArg::RawPtr(addr));
+                    continue;
+                }
+                match this.struct_ptr_to_host(ptr, cur_arg.layout.ty)? {
+                    Some(mut host) => {
+                        libffi_args.push(CArg::RawPtr(host.buf.as_mut_ptr()));
+                        host_structs.push(host);
+                    }
+                    None => libffi_args.push(CArg::RawPtr(std::ptr::null_mut())),
+                }
+                continue;
+            }
+            if this.is_c_long_double(cur_arg.layout.ty)? {
+                let x = this.read_scalar(&this.operand_field(cur_arg, 0)?)?.to_f64()?;
+                libffi_args.push(CArg::LongDouble(LongDouble::from_f64(
+                    x,
+                    this.long_double_repr()?,
+                    this.data_layout().endian,
+                )));
+                continue;
+            }
             libffi_args.push(Self::scalar_to_carg(
                 this.read_scalar(cur_arg)?,
                 cur_arg.layout.ty,
@@ -231,23 +813,259 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             )?);
         }
 
-        // Convert them to `libffi::high::Arg` type.
-        let libffi_args = libffi_args
-            .iter()
-            .map(|cur_arg| cur_arg.arg_downcast())
-            .collect::<Vec<libffi::high::Arg<'_>>>();
-
         // Call the function and store output, depending on return type in the function signature.
-        self.call_external_c_and_store_return(link_name, dest, code_ptr, libffi_args)?;
+        let inner_dest = this.peel_ffi_newtype_dest(dest)?;
+        this.sync_lib_statics_to_host()?;
+        let start = Instant::now();
+        let res =
+            this.call_external_c_and_store_return(link_name, &inner_dest, code_ptr, &libffi_args);
+        let elapsed = start.elapsed();
+        if this.machine.external_call_watchdog.as_ref().map_or(false, CallWatchdog::timed_out) {
+            // The call did not return, and might still access its arguments.
+            std::mem::forget(libffi_args);
+            std::mem::forget(host_structs);
+        }
+        res?;
+        if let Some(summary) = &this.machine.external_call_summary {
+            summary.lock().unwrap().record_native_call(link_name, elapsed);
+        }
+        this.sync_lib_statics_from_host()?;
+        // Writing to the wrapped field bypassed the validity invariant of the wrapper
+        // (e.g., `NonZeroU32`), and returned structs are written as plain bytes, so check that now.
+        if (inner_dest.layout.ty != dest.layout.ty || this.is_repr_c_struct(dest.layout.ty))
+            && this.machine.validate
+        {
+            let dest_op = this.place_to_op(dest)?;
+            this.validate_operand(&dest_op)?;
+        }
+
+        // Copy back whatever the C code wrote into structs passed by pointer.
+        for host in &host_structs {
+            this.write_back_host_struct(host)?;
+        }
         Ok(true)
     }
 }
 
+/// Statistics about the foreign functions called by the program, for `-Zmiri-extern-call-summary`.
+#[derive(Default)]
+pub struct ExternCallSummary {
+    /// Number of calls and total time spent in native code, per function of the shared
+    /// object file.
+    native_calls: FxHashMap<Symbol, (u64, Duration)>,
+    /// Number of calls per foreign function that is supported neither by a shim nor by the
+    /// shared object file.
+    unsupported_calls: FxHashMap<Symbol, u64>,
+}
+
+impl ExternCallSummary {
+    pub fn record_native_call(&mut self, link_name: Symbol, time: Duration) {
+        let (count, total) = self.native_calls.entry(link_name).or_default();
+        *count = count.saturating_add(1);
+        *total = total.saturating_add(time);
+    }
+
+    pub fn record_unsupported(&mut self, link_name: Symbol) {
+        let count = self.unsupported_calls.entry(link_name).or_default();
+        *count = count.saturating_add(1);
+    }
+
+    pub fn print(&self) {
+        fn sorted<V>(map: &FxHashMap<Symbol, V>) -> Vec<(&str, &V)> {
+            let mut entries: Vec<_> = map.iter().map(|(name, v)| (name.as_str(), v)).collect();
+            entries.sort_by_key(|&(name, _)| name);
+            entries
+        }
+        eprintln!("external function call summary:");
+        if self.native_calls.is_empty() {
+            eprintln!("  no calls into the extern shared object file");
+        }
+        for (name, (count, time)) in sorted(&self.native_calls) {
+            eprintln!("  {name}: {count} call(s), {time:?} in native code");
+        }
+        if !self.unsupported_calls.is_empty() {
+            eprintln!("unsupported foreign functions:");
+            for (name, count) in sorted(&self.unsupported_calls) {
+                eprintln!("  {name}: {count} call(s)");
+            }
+        }
+    }
+}
+
+/// The values that the `static mut`s of shared object files had when Miri first saw them, keyed by
+/// the path of the file and the name of the static. The library is not necessarily unloaded
+/// between the runs of `-Zmiri-many-seeds` and `-Zmiri-dpor`, so each run restores these values.
+static LIB_STATIC_INITIAL_VALUES: Mutex<Vec<(PathBuf, String, Vec<u8>)>> = Mutex::new(Vec::new());
+
+/// A `static mut` defined by the shared object file, mirrored in Miri memory.
+pub struct LibStatic {
+    /// The Miri allocation holding the static.
+    ptr: Pointer<Option<Provenance>>,
+    /// Where the library keeps the static.
+    host_addr: *mut std::ffi::c_void,
+    size: Size,
+}
+
+impl LibStatic {
+    /// SAFETY: `host_addr` must point to `size` bytes that are not being mutated.
+    unsafe fn host_bytes(&self) -> &[u8] {
+        std::slice::from_raw_parts(self.host_addr.cast(), self.size.bytes_usize())
+    }
+
+    /// SAFETY: `host_addr` must point to `size` writable bytes.
+    #[allow(clippy::mut_from_ref)] // the bytes are owned by the library, not by `self`
+    unsafe fn host_bytes_mut(&self) -> &mut [u8] {
+        std::slice::from_raw_parts_mut(self.host_addr.cast(), self.size.bytes_usize())
+    }
+
+    /// Restore the value the static `name` of the shared object file `lib_path` had when it was
+    /// first seen in this process, or remember it if this is the first time.
+    ///
+    /// SAFETY: see `host_bytes_mut`.
+    unsafe fn reset_to_initial_value(&self, lib_path: &Path, name: &str) {
+        let mut initial_values = LIB_STATIC_INITIAL_VALUES.lock().unwrap();
+        match initial_values.iter().find(|(path, n, _)| path == lib_path && n == name) {
+            Some((_, _, bytes)) => self.host_bytes_mut().copy_from_slice(bytes),
+            None => {
+                let bytes = self.host_bytes().to_vec();
+                initial_values.push((lib_path.to_owned(), name.to_owned(), bytes));
+            }
+        }
+    }
+}
+
+/// Runs the calls into the shared object file on a separate host thread, so that a call that does
+/// not return before the timeout expires can be reported as an error. We cannot interrupt a native
+/// function that does not return, so the thread is then abandoned, together with all data that
+/// the call might still access.
+///
+/// One thread is started for the whole run and used for every call, so that the library sees the
+/// same thread-local state in all calls.
+pub struct CallWatchdog {
+    timeout: Duration,
+    /// Sends the calls to the thread. Dropping this stops the thread.
+    calls: Option<mpsc::Sender<ExternalCall>>,
+    thread: Option<thread::JoinHandle<()>>,
+    /// Whether a call did not return in time, i.e., whether the thread is stuck in it.
+    timed_out: bool,
+}
+
+/// A call into the shared object file, to be run on the watchdog thread.
+struct ExternalCall(Box<dyn FnOnce()>);
+
+// SAFETY: the host data a call refers to is not used by the interpreter until the call returned,
+// and leaked if it does not (see `CallWatchdog::call`).
+unsafe impl Send for ExternalCall {}
+
+impl CallWatchdog {
+    pub fn new(timeout: Duration) -> Self {
+        let (calls, calls_rx) = mpsc::channel::<ExternalCall>();
+        let thread = thread::spawn(move || {
+            for ExternalCall(call) in calls_rx {
+                call();
+            }
+        });
+        CallWatchdog { timeout, calls: Some(calls), thread: Some(thread), timed_out: false }
+    }
+
+    /// Run `call` on the watchdog thread, and wait for it to return. Returns `None` if it did not
+    /// return before the timeout expired.
+    ///
+    /// SAFETY: if this returns `None`, all data `call` refers to must be leaked, since the call
+    /// might still access it.
+    unsafe fn call<R: Send>(&mut self, call: impl FnOnce() -> R) -> Option<R> {
+        let (ret, ret_rx) = mpsc::channel();
+        let call: Box<dyn FnOnce() + '_> = Box::new(move || {
+            // Nobody waits for the result anymore if the call timed out.
+            let _ = ret.send(call());
+        });
+        // SAFETY: we wait until the call returned, and otherwise, the caller leaks the data it
+        // refers to.
+        let call = std::mem::transmute::<_, Box<dyn FnOnce() + 'static>>(call);
+        self.calls.as_ref().unwrap().send(ExternalCall(call)).unwrap();
+        let ret = if Instant::now().checked_add(self.timeout).is_some() {
+            ret_rx.recv_timeout(self.timeout).ok()
+        } else {
+            // The deadline cannot even be represented, so the timeout never expires.
+            ret_rx.recv().ok()
+        };
+        self.timed_out = ret.is_none();
+        ret
+    }
+
+    /// Whether a call did not return in time.
+    fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+}
+
+impl Drop for CallWatchdog {
+    fn drop(&mut self) {
+        drop(self.calls.take());
+        // The thread cannot be joined while it is stuck in a call.
+        if !self.timed_out {
+            if let Some(thread) = self.thread.take() {
+                thread.join().unwrap();
+            }
+        }
+    }
+}
+
+/// Host-side copy of a `#[repr(C)]` struct that is passed to an external C function by pointer.
+pub struct HostStruct {
+    /// Where the struct lives in Miri memory.
+    ptr: Pointer<Option<Provenance>>,
+    /// The parts of the struct that hold field data, i.e., everything except padding.
+    data_ranges: Vec<AllocRange>,
+    /// Whether the C code may write to the struct.
+    mutbl: Mutability,
+    /// The host copy of the struct.
+    buf: HostBuffer,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, align(16))]
+struct HostChunk([u8; 16]);
+
+/// Zero-initialized host memory for a C value, stored in chunks so that it is suitably aligned.
+/// There are always at least 16 bytes, so that libffi can store full registers into it.
+struct HostBuffer {
+    chunks: Vec<HostChunk>,
+    /// The size of the value (which might be less than the size of the buffer).
+    len: usize,
+}
+
+impl HostBuffer {
+    /// The maximal alignment a host buffer can guarantee.
+    const MAX_ALIGN: u64 = std::mem::align_of::<HostChunk>() as u64;
+
+    fn new(size: Size) -> Self {
+        let chunk_size = std::mem::size_of::<HostChunk>();
+        #[allow(clippy::integer_arithmetic)] // `chunk_size` is 16, and the size of a layout fits in `isize`
+        let num_chunks = (size.bytes_usize() + chunk_size - 1) / chunk_size;
+        HostBuffer { chunks: vec![HostChunk([0; 16]); num_chunks.max(1)], len: size.bytes_usize() }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: `HostChunk` is just a byte array, and `chunks` covers at least `len` bytes.
+        unsafe { std::slice::from_raw_parts(self.chunks.as_ptr().cast::<u8>(), self.len) }
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: `HostChunk` is just a byte array, and `chunks` covers at least `len` bytes.
+        unsafe { std::slice::from_raw_parts_mut(self.chunks.as_mut_ptr().cast::<u8>(), self.len) }
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut std::ffi::c_void {
+        self.chunks.as_mut_ptr().cast()
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Enum of supported arguments to external C functions.
-// We introduce this enum instead of just calling `ffi::arg` and storing a list
-// of `libffi::high::Arg` directly, because the `libffi::high::Arg` just wraps a reference
-// to the value it represents: https://docs.rs/libffi/latest/libffi/high/call/struct.Arg.html
+// We introduce this enum instead of just calling `Arg::new` and storing a list
+// of `libffi::middle::Arg` directly, because the `libffi::middle::Arg` just wraps a pointer
+// to the value it represents: https://docs.rs/libffi/latest/libffi/middle/struct.Arg.html
 // and we need to store a copy of the value, and pass a reference to this copy to C instead.
 pub enum CArg {
     /// 8-bit signed integer.
@@ -270,22 +1088,153 @@ pub enum CArg {
     UInt64(u64),
     /// usize.
     USize(usize),
+    /// Raw pointer into host memory.
+    RawPtr(*mut std::ffi::c_void),
+    /// C `long double`, in the target's representation.
+    LongDouble(LongDouble),
 }
 
-impl<'a> CArg {
-    /// Convert a `CArg` to a `libffi` argument type.
-    fn arg_downcast(&'a self) -> libffi::high::Arg<'a> {
+impl CArg {
+    /// Convert a `CArg` to a `libffi` argument.
+    fn arg_downcast(&self) -> Arg {
         match self {
-            CArg::Int8(i) => ffi::arg(i),
-            CArg::Int16(i) => ffi::arg(i),
-            CArg::Int32(i) => ffi::arg(i),
-            CArg::Int64(i) => ffi::arg(i),
-            CArg::ISize(i) => ffi::arg(i),
-            CArg::UInt8(i) => ffi::arg(i),
-            CArg::UInt16(i) => ffi::arg(i),
-            CArg::UInt32(i) => ffi::arg(i),
-            CArg::UInt64(i) => ffi::arg(i),
-            CArg::USize(i) => ffi::arg(i),
+            CArg::Int8(i) => Arg::new(i),
+            CArg::Int16(i) => Arg::new(i),
+            CArg::Int32(i) => Arg::new(i),
+            CArg::Int64(i) => Arg::new(i),
+            CArg::ISize(i) => Arg::new(i),
+            CArg::UInt8(i) => Arg::new(i),
+            CArg::UInt16(i) => Arg::new(i),
+            CArg::UInt32(i) => Arg::new(i),
+            CArg::UInt64(i) => Arg::new(i),
+            CArg::USize(i) => Arg::new(i),
+            CArg::RawPtr(p) => Arg::new(p),
+            CArg::LongDouble(f) => Arg::new(f),
+        }
+    }
+
+    /// The `libffi` type of a `CArg`.
+    fn ffi_type(&self) -> Type {
+        match self {
+            CArg::Int8(_) => Type::i8(),
+            CArg::Int16(_) => Type::i16(),
+            CArg::Int32(_) => Type::i32(),
+            CArg::Int64(_) => Type::i64(),
+            CArg::ISize(_) => Type::isize(),
+            CArg::UInt8(_) => Type::u8(),
+            CArg::UInt16(_) => Type::u16(),
+            CArg::UInt32(_) => Type::u32(),
+            CArg::UInt64(_) => Type::u64(),
+            CArg::USize(_) => Type::usize(),
+            CArg::RawPtr(_) => Type::pointer(),
+            CArg::LongDouble(_) => Type::longdouble(),
+        }
+    }
+}
+
+/// Call the C function at `ptr` with the given arguments. `ret_ty` is the `libffi` type of the
+/// return value, which is read back as an `R`.
+///
+/// Safety: this calls arbitrary C code, and `R` must be large enough for whatever `libffi`
+/// writes for `ret_ty`.
+unsafe fn ffi_call<R>(ptr: CodePtr, args: &[CArg], ret_ty: Type) -> R {
+    let cif = Cif::new(args.iter().map(CArg::ffi_type), ret_ty);
+    let args = args.iter().map(CArg::arg_downcast).collect::<Vec<Arg>>();
+    cif.call::<R>(ptr, &args)
+}
+
+/// Convert the return value of the external C function `link_name`, as read from `libffi`, to its
+/// declared type.
+fn ffi_return_int<'tcx, T, R: TryFrom<T>>(x: T, link_name: Symbol) -> InterpResult<'tcx, R> {
+    R::try_from(x).map_err(|_| {
+        err_unsup_format!(
+            "return value of external C function `{}` does not fit into its return type",
+            link_name
+        )
+        .into()
+    })
+}
+
+/// Like `ffi_call`, but libffi writes the return value directly into `ret`. `ret` must be large
+/// enough for whatever `libffi` writes for `ret_ty`.
+unsafe fn ffi_call_into(ptr: CodePtr, args: &[CArg], ret_ty: Type, ret: &mut HostBuffer) {
+    let cif = Cif::new(args.iter().map(CArg::ffi_type), ret_ty);
+    let mut args = args.iter().map(CArg::arg_downcast).collect::<Vec<Arg>>();
+    libffi::raw::ffi_call(
+        cif.as_raw_ptr(),
+        Some(*ptr.as_safe_fun()),
+        ret.as_mut_ptr(),
+        args.as_mut_ptr().cast(),
+    );
+}
+
+/// The representation of C's `long double` on the target.
+#[derive(Debug, Clone, Copy)]
+pub enum LongDoubleRepr {
+    /// x87 80-bit extended precision (padded to 16 bytes in memory).
+    X87,
+    /// IEEE 754 binary128.
+    Quad,
+    /// Same as `double`.
+    Double,
+}
+
+/// The bytes of a C `long double`, in the target's representation.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(16))]
+pub struct LongDouble([u8; 16]);
+
+impl LongDouble {
+    /// Widen an `f64` to a `long double`. Every `double` is exactly representable as a
+    /// `long double`, so this never rounds.
+    fn from_f64(x: Double, repr: LongDoubleRepr, endian: Endian) -> Self {
+        let mut loses_info = false;
+        let (bits, size) = match repr {
+            LongDoubleRepr::X87 => {
+                let x: X87DoubleExtended = x.convert(&mut loses_info).value;
+                (x.to_bits(), 10)
+            }
+            LongDoubleRepr::Quad => {
+                let x: Quad = x.convert(&mut loses_info).value;
+                (x.to_bits(), 16)
+            }
+            LongDoubleRepr::Double => (x.to_bits(), 8),
+        };
+        assert!(!loses_info, "widening `double` to `long double` must be exact");
+        let mut bytes = [0; 16];
+        match endian {
+            Endian::Little => bytes[..size].copy_from_slice(&bits.to_le_bytes()[..size]),
+            #[allow(clippy::integer_arithmetic)] // size <= 16
+            Endian::Big => bytes[..size].copy_from_slice(&bits.to_be_bytes()[16 - size..]),
+        }
+        LongDouble(bytes)
+    }
+
+    /// Narrow a `long double` to an `f64`, rounding to nearest (ties to even).
+    fn to_f64(self, repr: LongDoubleRepr, endian: Endian) -> Double {
+        let size = match repr {
+            LongDoubleRepr::X87 => 10,
+            LongDoubleRepr::Quad => 16,
+            LongDoubleRepr::Double => 8,
+        };
+        let mut buf = [0; 16];
+        let bits = match endian {
+            Endian::Little => {
+                buf[..size].copy_from_slice(&self.0[..size]);
+                u128::from_le_bytes(buf)
+            }
+            #[allow(clippy::integer_arithmetic)] // size <= 16
+            Endian::Big => {
+                buf[16 - size..].copy_from_slice(&self.0[..size]);
+                u128::from_be_bytes(buf)
+            }
+        };
+        let mut loses_info = false;
+        match repr {
+            LongDoubleRepr::X87 =>
+                X87DoubleExtended::from_bits(bits).convert(&mut loses_info).value,
+            LongDoubleRepr::Quad => Quad::from_bits(bits).convert(&mut loses_info).value,
+            LongDoubleRepr::Double => Double::from_bits(bits),
         }
     }
 }
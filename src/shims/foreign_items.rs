@@ -24,6 +24,7 @@ use rustc_target::{
 use super::backtrace::EvalContextExt as _;
 use crate::helpers::{convert::Truncate, target_os_is_unix};
 use crate::shims::ffi_support::EvalContextExt as _;
+use crate::shims::unix::{DispatchEvalContextExt as _, SignalEvalContextExt as _};
 use crate::*;
 
 /// Returned by `emulate_foreign_item_by_name`.
@@ -287,8 +288,21 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                         let [code] = this.check_shim(abi, exp_abi, link_name, args)?;
                         // it's really u32 for ExitProcess, but we have to put it into the `Exit` variant anyway
                         let code = this.read_scalar(code)?.to_i32()?;
+                        if link_name.as_str() == "exit" {
+                            // This runs the `atexit` handlers first.
+                            this.exit(code.into())?;
+                            return Ok(None);
+                        }
                         throw_machine_stop!(TerminationInfo::Exit(code.into()));
                     }
+                    "ExitThread" => {
+                        let [code] =
+                            this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                        // The exit code is ignored, like the return value of the start routine.
+                        this.read_scalar(code)?.to_u32()?;
+                        this.request_thread_exit()?;
+                        return Ok(None);
+                    }
                     "abort" => {
                         let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
                         throw_machine_stop!(TerminationInfo::Abort(
@@ -299,6 +313,9 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                         if let Some(body) = this.lookup_exported_symbol(link_name)? {
                             return Ok(Some(body));
                         }
+                        if let Some(summary) = &this.machine.external_call_summary {
+                            summary.lock().unwrap().record_unsupported(link_name);
+                        }
                         this.handle_unsupported(format!(
                             "can't call (diverging) foreign function: {}",
                             link_name
@@ -309,8 +326,28 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             Some(p) => p,
         };
 
-        // Second: functions that return immediately.
-        match this.emulate_foreign_item_by_name(link_name, abi, args, dest)? {
+        // Second: functions that call back into the program, and hence need to know where to
+        // continue afterwards.
+        if this.tcx.sess.target.os == "macos" && link_name.as_str() == "dispatch_once_f" {
+            let [predicate, context, function] =
+                this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+            this.dispatch_once_f(predicate, context, function, ret)?;
+            return Ok(None);
+        }
+        if target_os_is_unix(this.tcx.sess.target.os.as_ref())
+            && this.emulate_signal_item(link_name, abi, args, dest, ret)?
+        {
+            return Ok(None);
+        }
+
+        // Third: functions that return immediately.
+        let result = this.emulate_foreign_item_by_name(link_name, abi, args, dest)?;
+        // Remember where the file descriptors opened by this function were opened.
+        if this.machine.file_handler.has_unlocated_fds() {
+            let span = this.machine.current_span(tcx).get();
+            this.machine.file_handler.locate_opened_fds(span);
+        }
+        match result {
             EmulateByNameResult::NeedsJumping => {
                 trace!("{:?}", this.dump_place(**dest));
                 this.go_to_block(ret);
@@ -322,6 +359,15 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                     return Ok(Some(body));
                 }
 
+                if let Some(summary) = &this.machine.external_call_summary {
+                    summary.lock().unwrap().record_unsupported(link_name);
+                }
+                if this.machine.extern_stub_missing {
+                    this.stub_missing_foreign_item(link_name, dest)?;
+                    this.go_to_block(ret);
+                    return Ok(None);
+                }
+
                 this.handle_unsupported(format!("can't call foreign function: {}", link_name))?;
                 return Ok(None);
             }
@@ -330,6 +376,28 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(None)
     }
 
+    /// Pretends that a foreign function that we cannot call returned zero (or NULL), and warns
+    /// about that the first time it happens for this function. Only functions returning integers,
+    /// raw pointers or nothing can be stubbed; zero is not a valid value of many other types.
+    fn stub_missing_foreign_item(
+        &mut self,
+        link_name: Symbol,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let ret_ty = dest.layout.ty;
+        if !(ret_ty.is_integral() || ret_ty.is_unsafe_ptr() || ret_ty.is_unit()) {
+            throw_unsup_format!(
+                "can't stub out foreign function `{link_name}` returning `{ret_ty}`: only integer, raw pointer and unit return types can be stubbed"
+            );
+        }
+        if this.machine.stubbed_foreign_fns.insert(link_name) {
+            register_diagnostic(NonHaltingDiagnostic::StubbedForeignItem(link_name));
+        }
+        let dest = this.force_allocation(dest)?;
+        this.write_bytes_ptr(dest.ptr, iter::repeat(0).take(dest.layout.size.bytes_usize()))
+    }
+
     /// Emulates calling the internal __rust_* allocator functions
     fn emulate_allocator(
         &mut self,
@@ -448,6 +516,98 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 this.handle_miri_resolve_frame_names(abi, link_name, args)?;
             }
 
+            // Prints the borrow stacks of some memory. See the README for details.
+            "miri_print_borrow_state" => {
+                let [ptr, size] = this.check_shim(abi, Abi::Rust, link_name, args)?;
+                let ptr = this.read_pointer(ptr)?;
+                let size = this.read_scalar(size)?.to_machine_usize(this)?;
+                this.print_borrow_state(ptr, Size::from_bytes(size))?;
+            }
+
+            // Changes the global preemption rate and returns the previous one. See the README for details.
+            "miri_set_preemption_rate" => {
+                let [rate] = this.check_shim(abi, Abi::Rust, link_name, args)?;
+                let rate = f64::from_bits(this.read_scalar(rate)?.to_u64()?);
+                if !(0.0..=1.0).contains(&rate) {
+                    throw_unsup_format!(
+                        "the preemption rate passed to `miri_set_preemption_rate` must be between 0 and 1, but it is {rate}"
+                    );
+                }
+                let old = std::mem::replace(&mut this.machine.preemption_rate, rate);
+                this.write_scalar(Scalar::from_u64(old.to_bits()), dest)?;
+            }
+            // Changes the preemption rate of the active thread and returns the previous one.
+            // See the README for details.
+            "miri_set_thread_preemption_rate" => {
+                let [rate] = this.check_shim(abi, Abi::Rust, link_name, args)?;
+                let rate = f64::from_bits(this.read_scalar(rate)?.to_u64()?);
+                let rate = if rate < 0.0 {
+                    None
+                } else if rate <= 1.0 {
+                    Some(rate)
+                } else {
+                    throw_unsup_format!(
+                        "the preemption rate passed to `miri_set_thread_preemption_rate` must be at most 1, but it is {rate}"
+                    );
+                };
+                let old = std::mem::replace(&mut this.active_thread_mut().preemption_rate, rate);
+                this.write_scalar(Scalar::from_u64(old.unwrap_or(-1.0).to_bits()), dest)?;
+            }
+            // Changes the priority of the active thread and returns the previous one.
+            // See the README for details.
+            "miri_set_thread_priority" => {
+                let [priority] = this.check_shim(abi, Abi::Rust, link_name, args)?;
+                let priority = this.read_scalar(priority)?.to_i32()?;
+                let old = std::mem::replace(&mut this.active_thread_mut().priority, priority);
+                this.write_scalar(Scalar::from_i32(old), dest)?;
+            }
+
+            // Writes the name of the active thread, as shown in diagnostics, to a buffer.
+            // See the README for details.
+            "miri_get_thread_name" => {
+                let [buf, len] = this.check_shim(abi, Abi::Rust, link_name, args)?;
+                let buf = this.read_pointer(buf)?;
+                let len = this.read_scalar(len)?.to_machine_usize(this)?;
+                let name = this.get_thread_name(this.get_active_thread()).to_owned();
+                let written = name.len().min(usize::try_from(len).unwrap());
+                this.write_bytes_ptr(buf, name[..written].iter().copied())?;
+                let name_len = name.len().try_into().unwrap();
+                this.write_scalar(Scalar::from_machine_usize(name_len, this), dest)?;
+            }
+            "miri_get_syslog" => {
+                let [buf, len] = this.check_shim(abi, Abi::Rust, link_name, args)?;
+                let buf = this.read_pointer(buf)?;
+                let len = this.read_scalar(len)?.to_machine_usize(this)?;
+                let log: Vec<u8> = this
+                    .machine
+                    .syslog
+                    .messages
+                    .iter()
+                    .flat_map(|message| message.bytes().chain(std::iter::once(b'\n')))
+                    .collect();
+                let written = log.len().min(usize::try_from(len).unwrap());
+                this.write_bytes_ptr(buf, log[..written].iter().copied())?;
+                let log_len = log.len().try_into().unwrap();
+                this.write_scalar(Scalar::from_machine_usize(log_len, this), dest)?;
+            }
+            "miri_env_snapshot" => {
+                let [] = this.check_shim(abi, Abi::Rust, link_name, args)?;
+                let snapshot = this.miri_env_snapshot()?;
+                this.write_scalar(Scalar::from_machine_usize(snapshot, this), dest)?;
+            }
+            "miri_env_restore" => {
+                let [snapshot] = this.check_shim(abi, Abi::Rust, link_name, args)?;
+                let snapshot = this.read_scalar(snapshot)?.to_machine_usize(this)?;
+                this.miri_env_restore(snapshot)?;
+            }
+
+            // Standard C exit handlers
+            "atexit" => {
+                let [func] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.atexit(func)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+
             // Standard C allocation
             "malloc" => {
                 let [size] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
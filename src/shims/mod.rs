@@ -1,12 +1,14 @@
 #![warn(clippy::integer_arithmetic)]
 
 mod backtrace;
+pub mod elf;
 pub mod ffi_support;
 pub mod foreign_items;
 pub mod intrinsics;
 pub mod unix;
 pub mod windows;
 
+pub mod atexit;
 pub mod dlsym;
 pub mod env;
 pub mod os_str;
@@ -1,4 +1,4 @@
-use std::time::{Duration, Instant, SystemTime};
+use std::time::{Duration, SystemTime};
 
 use crate::concurrency::thread::Time;
 use crate::*;
@@ -23,7 +23,6 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_mut();
 
         this.assert_target_os("linux", "clock_gettime");
-        this.check_no_isolation("`clock_gettime`")?;
 
         let clk_id = this.read_scalar(clk_id_op)?.to_i32()?;
 
@@ -35,14 +34,16 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             [this.eval_libc_i32("CLOCK_REALTIME")?, this.eval_libc_i32("CLOCK_REALTIME_COARSE")?];
         // The second kind is MONOTONIC clocks for which 0 is an arbitrary time point, but they are
         // never allowed to go backwards. We don't need to do any additonal monotonicity
-        // enforcement because std::time::Instant already guarantees that it is monotonic.
+        // enforcement because our `Clock` already guarantees that it is monotonic. Under
+        // isolation, they are backed by the virtual clock.
         let relative_clocks =
             [this.eval_libc_i32("CLOCK_MONOTONIC")?, this.eval_libc_i32("CLOCK_MONOTONIC_COARSE")?];
 
         let duration = if absolute_clocks.contains(&clk_id) {
+            this.check_no_isolation("`clock_gettime` with `REALTIME` clocks")?;
             system_time_to_duration(&SystemTime::now())?
         } else if relative_clocks.contains(&clk_id) {
-            Instant::now().duration_since(this.machine.time_anchor)
+            this.machine.clock.read().duration_since(this.machine.clock.anchor())
         } else {
             let einval = this.eval_libc("EINVAL")?;
             this.set_last_error(einval)?;
@@ -123,11 +124,10 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_mut();
 
         this.assert_target_os("windows", "QueryPerformanceCounter");
-        this.check_no_isolation("`QueryPerformanceCounter`")?;
 
         // QueryPerformanceCounter uses a hardware counter as its basis.
         // Miri will emulate a counter with a resolution of 1 nanosecond.
-        let duration = Instant::now().duration_since(this.machine.time_anchor);
+        let duration = this.machine.clock.read().duration_since(this.machine.clock.anchor());
         let qpc = i64::try_from(duration.as_nanos()).map_err(|_| {
             err_unsup_format!("programs running longer than 2^63 nanoseconds are not supported")
         })?;
@@ -146,7 +146,6 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_mut();
 
         this.assert_target_os("windows", "QueryPerformanceFrequency");
-        this.check_no_isolation("`QueryPerformanceFrequency`")?;
 
         // Retrieves the frequency of the hardware performance counter.
         // The frequency of the performance counter is fixed at system boot and
@@ -164,11 +163,10 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_ref();
 
         this.assert_target_os("macos", "mach_absolute_time");
-        this.check_no_isolation("`mach_absolute_time`")?;
 
         // This returns a u64, with time units determined dynamically by `mach_timebase_info`.
         // We return plain nanoseconds.
-        let duration = Instant::now().duration_since(this.machine.time_anchor);
+        let duration = this.machine.clock.read().duration_since(this.machine.clock.anchor());
         let res = u64::try_from(duration.as_nanos()).map_err(|_| {
             err_unsup_format!("programs running longer than 2^64 nanoseconds are not supported")
         })?;
@@ -182,7 +180,6 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_mut();
 
         this.assert_target_os("macos", "mach_timebase_info");
-        this.check_no_isolation("`mach_timebase_info`")?;
 
         let info = this.deref_operand(info_op)?;
 
@@ -202,7 +199,6 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_mut();
 
         this.assert_target_os_is_unix("nanosleep");
-        this.check_no_isolation("`nanosleep`")?;
 
         let duration = match this.read_timespec(&this.deref_operand(req_op)?)? {
             Some(duration) => duration,
@@ -213,15 +209,68 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             }
         };
         // If adding the duration overflows, let's just sleep for an hour. Waking up early is always acceptable.
-        let timeout_time = Instant::now()
-            .checked_add(duration)
-            .unwrap_or_else(|| Instant::now().checked_add(Duration::from_secs(3600)).unwrap());
-        let timeout_time = Time::Monotonic(timeout_time);
+        let timeout_time = this
+            .monotonic_deadline_after(duration)
+            .or_else(|| this.monotonic_deadline_after(Duration::from_secs(3600)));
 
         let active_thread = this.get_active_thread();
-        this.block_thread(active_thread);
+        this.block_thread_until(
+            active_thread,
+            timeout_time,
+            Box::new(move |ecx| {
+                ecx.unblock_thread(active_thread);
+                Ok(())
+            }),
+        );
+
+        Ok(0)
+    }
+
+    /// Returns the error number rather than setting `errno`. Relative sleeps on all clocks and
+    /// absolute sleeps on `CLOCK_MONOTONIC` use Miri's clock, so under isolation they just advance
+    /// the virtual time. Absolute `CLOCK_REALTIME` sleeps need the host's time.
+    fn clock_nanosleep(
+        &mut self,
+        clock_id_op: &OpTy<'tcx, Provenance>,
+        flags_op: &OpTy<'tcx, Provenance>,
+        req_op: &OpTy<'tcx, Provenance>,
+        _rem: &OpTy<'tcx, Provenance>, // Signal handlers are not supported, so rem will never be written to.
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        this.assert_target_os("linux", "clock_nanosleep");
+
+        let clock_id = this.read_scalar(clock_id_op)?.to_i32()?;
+        let flags = this.read_scalar(flags_op)?.to_i32()?;
 
-        this.register_timeout_callback(
+        let clock_realtime = this.eval_libc_i32("CLOCK_REALTIME")?;
+        let clock_monotonic = this.eval_libc_i32("CLOCK_MONOTONIC")?;
+        let timer_abstime = this.eval_libc_i32("TIMER_ABSTIME")?;
+        if clock_id != clock_realtime && clock_id != clock_monotonic {
+            return this.eval_libc_i32("EINVAL");
+        }
+        if flags & !timer_abstime != 0 {
+            return this.eval_libc_i32("EINVAL");
+        }
+        let Some(duration) = this.read_timespec(&this.deref_operand(req_op)?)? else {
+            return this.eval_libc_i32("EINVAL");
+        };
+
+        // A deadline that is too far in the future to be represented is never reached, so then we
+        // sleep for an hour like `nanosleep`. Waking up early is always acceptable.
+        let timeout_time = if flags & timer_abstime == 0 {
+            this.monotonic_deadline_after(duration)
+        } else if clock_id == clock_realtime {
+            this.check_no_isolation("`clock_nanosleep` with an absolute `CLOCK_REALTIME` time")?;
+            SystemTime::UNIX_EPOCH.checked_add(duration).map(Time::RealTime)
+        } else {
+            this.monotonic_deadline_since_anchor(duration)
+        };
+        let timeout_time =
+            timeout_time.or_else(|| this.monotonic_deadline_after(Duration::from_secs(3600)));
+
+        let active_thread = this.get_active_thread();
+        this.block_thread_until(
             active_thread,
             timeout_time,
             Box::new(move |ecx| {
@@ -238,17 +287,14 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_mut();
 
         this.assert_target_os("windows", "Sleep");
-        this.check_no_isolation("`Sleep`")?;
 
         let timeout_ms = this.read_scalar(timeout)?.to_u32()?;
 
         let duration = Duration::from_millis(timeout_ms.into());
-        let timeout_time = Time::Monotonic(Instant::now().checked_add(duration).unwrap());
+        let timeout_time = this.monotonic_deadline_after(duration);
 
         let active_thread = this.get_active_thread();
-        this.block_thread(active_thread);
-
-        this.register_timeout_callback(
+        this.block_thread_until(
             active_thread,
             timeout_time,
             Box::new(move |ecx| {
@@ -259,4 +305,22 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         Ok(())
     }
+
+    /// There are no asynchronous procedure calls or I/O completion routines, so even an alertable
+    /// sleep is never cut short, and 0 is returned.
+    #[allow(non_snake_case)]
+    fn SleepEx(
+        &mut self,
+        timeout: &OpTy<'tcx, Provenance>,
+        alertable: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, u32> {
+        let this = self.eval_context_mut();
+
+        this.assert_target_os("windows", "SleepEx");
+
+        let _alertable = this.read_scalar(alertable)?.to_i32()?;
+        this.Sleep(timeout)?;
+
+        Ok(0)
+    }
 }
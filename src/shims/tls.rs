@@ -5,12 +5,15 @@ use std::collections::hash_map::Entry as HashMapEntry;
 use std::collections::BTreeMap;
 
 use log::trace;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
 
 use rustc_data_structures::fx::FxHashMap;
 use rustc_middle::ty;
 use rustc_target::abi::{HasDataLayout, Size};
 use rustc_target::spec::abi::Abi;
 
+use crate::concurrency::thread::MAIN_THREAD;
 use crate::*;
 
 pub type TlsKey = u128;
@@ -25,10 +28,10 @@ pub struct TlsEntry<'tcx> {
 
 #[derive(Clone, Debug)]
 struct RunningDtorsState {
-    /// The last TlsKey used to retrieve a TLS destructor. `None` means that we
-    /// have not tried to retrieve a TLS destructor yet or that we already tried
-    /// all keys.
-    last_dtor_key: Option<TlsKey>,
+    /// The keys whose destructors have not been considered yet in the current
+    /// round, with the next one at the end. Empty means that we have not started
+    /// a round yet or that we already tried all keys.
+    remaining_dtor_keys: Vec<TlsKey>,
 }
 
 #[derive(Debug)]
@@ -157,8 +160,8 @@ impl<'tcx> TlsData<'tcx> {
         Ok(())
     }
 
-    /// Returns a dtor, its argument and its index, if one is supposed to run.
-    /// `key` is the last dtors that was run; we return the *next* one after that.
+    /// Returns a dtor and its argument, if one is supposed to run in the
+    /// current round.
     ///
     /// An optional destructor function may be associated with each key value.
     /// At thread exit, if a key value has a non-NULL destructor pointer,
@@ -178,30 +181,22 @@ impl<'tcx> TlsData<'tcx> {
     /// associated destructors exist, even though this might result in an infinite loop.
     fn fetch_tls_dtor(
         &mut self,
-        key: Option<TlsKey>,
         thread_id: ThreadId,
-    ) -> Option<(ty::Instance<'tcx>, Scalar<Provenance>, TlsKey)> {
-        use std::ops::Bound::*;
-
-        let thread_local = &mut self.keys;
-        let start = match key {
-            Some(key) => Excluded(key),
-            None => Unbounded,
-        };
+    ) -> Option<(ty::Instance<'tcx>, Scalar<Provenance>)> {
         // We interpret the documentaion above (taken from POSIX) as saying that we need to iterate
         // over all keys and run each destructor at least once before running any destructor a 2nd
-        // time. That's why we have `key` to indicate how far we got in the current iteration. If we
-        // return `None`, `schedule_next_pthread_tls_dtor` will re-try with `ket` set to `None` to
-        // start the next round.
-        // TODO: In the future, we might consider randomizing destructor order, but we still have to
-        // uphold this requirement.
-        for (&key, TlsEntry { data, dtor }) in thread_local.range_mut((start, Unbounded)) {
+        // time. That's why we keep the keys that are left in the current round. If we return
+        // `None`, `schedule_next_pthread_tls_dtor` will call `start_tls_dtor_round` and re-try.
+        let remaining = &mut self.dtors_running.get_mut(&thread_id).unwrap().remaining_dtor_keys;
+        while let Some(key) = remaining.pop() {
+            // The key might have been deleted by an earlier dtor.
+            let Some(TlsEntry { data, dtor }) = self.keys.get_mut(&key) else { continue };
             match data.entry(thread_id) {
                 BTreeEntry::Occupied(entry) => {
                     if let Some(dtor) = dtor {
                         // Set TLS data to NULL, and call dtor with old value.
                         let data_scalar = entry.remove();
-                        let ret = Some((*dtor, data_scalar, key));
+                        let ret = Some((*dtor, data_scalar));
                         return ret;
                     }
                 }
@@ -211,6 +206,23 @@ impl<'tcx> TlsData<'tcx> {
         None
     }
 
+    /// Start a new round of running the dtors of `thread_id`. The keys are visited in the order in
+    /// which they were created, or in a random order if `rng` is given.
+    fn start_tls_dtor_round(&mut self, thread_id: ThreadId, rng: Option<&mut StdRng>) {
+        let mut keys: Vec<TlsKey> = self
+            .keys
+            .iter()
+            .filter(|(_, entry)| entry.dtor.is_some())
+            .map(|(&key, _)| key)
+            .collect();
+        match rng {
+            Some(rng) => keys.shuffle(rng),
+            // The next key is taken from the end.
+            None => keys.reverse(),
+        }
+        self.dtors_running.get_mut(&thread_id).unwrap().remaining_dtor_keys = keys;
+    }
+
     /// Set that dtors are running for `thread`. It is guaranteed not to change
     /// the existing values stored in `dtors_running` for this thread. Returns
     /// `true` if dtors for `thread` are already running.
@@ -219,8 +231,8 @@ impl<'tcx> TlsData<'tcx> {
             HashMapEntry::Occupied(_) => true,
             HashMapEntry::Vacant(entry) => {
                 // We cannot just do `self.dtors_running.insert` because that
-                // would overwrite `last_dtor_key` with `None`.
-                entry.insert(RunningDtorsState { last_dtor_key: None });
+                // would reset `remaining_dtor_keys`.
+                entry.insert(RunningDtorsState { remaining_dtor_keys: Vec::new() });
                 false
             }
         }
@@ -313,16 +325,17 @@ trait EvalContextPrivExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let active_thread = this.get_active_thread();
 
         assert!(this.has_terminated(active_thread), "running TLS dtors for non-terminated thread");
-        // Fetch next dtor after `key`.
-        let last_key = this.machine.tls.dtors_running[&active_thread].last_dtor_key;
-        let dtor = match this.machine.tls.fetch_tls_dtor(last_key, active_thread) {
+        // Fetch the next dtor of the current round.
+        let dtor = match this.machine.tls.fetch_tls_dtor(active_thread) {
             dtor @ Some(_) => dtor,
-            // We ran each dtor once, start over from the beginning.
-            None => this.machine.tls.fetch_tls_dtor(None, active_thread),
+            // We ran each dtor once, start over.
+            None => {
+                let rng = this.machine.random_tls_dtor_order.then(|| this.machine.rng.get_mut());
+                this.machine.tls.start_tls_dtor_round(active_thread, rng);
+                this.machine.tls.fetch_tls_dtor(active_thread)
+            }
         };
-        if let Some((instance, ptr, key)) = dtor {
-            this.machine.tls.dtors_running.get_mut(&active_thread).unwrap().last_dtor_key =
-                Some(key);
+        if let Some((instance, ptr)) = dtor {
             trace!("Running TLS dtor {:?} on {:?} at {:?}", instance, ptr, active_thread);
             assert!(
                 !ptr.to_machine_usize(this).unwrap() != 0,
@@ -340,8 +353,6 @@ trait EvalContextPrivExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             this.enable_thread(active_thread);
             return Ok(true);
         }
-        this.machine.tls.dtors_running.get_mut(&active_thread).unwrap().last_dtor_key = None;
-
         Ok(false)
     }
 }
@@ -390,6 +401,11 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             // destructors list. Run it to completion and come back here.
             return Ok(());
         }
+        // Returning from `main` runs the `atexit` handlers like `exit` does, after the TLS
+        // destructors of the main thread.
+        if active_thread == MAIN_THREAD && this.schedule_next_atexit_handler()? {
+            return Ok(());
+        }
 
         // All dtors done!
         this.machine.tls.delete_all_thread_tls(active_thread);
@@ -0,0 +1,300 @@
+//! Emulation of `gmtime_r`, `localtime_r`, `mktime` and `timegm`, which convert between times and
+//! dates in the proleptic Gregorian calendar. Under isolation, the local time zone has the fixed
+//! offset set with `-Zmiri-tz-offset`; otherwise, it is the time zone of the host.
+
+use rustc_middle::mir::Mutability;
+
+use crate::*;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A broken-down time, as stored in a `struct tm`.
+struct Tm {
+    sec: i64,
+    min: i64,
+    hour: i64,
+    mday: i64,
+    /// The month, from 0 to 11.
+    mon: i64,
+    /// The year, counted from 1900.
+    year: i64,
+    /// The day of the week, from 0 (Sunday) to 6.
+    wday: i64,
+    /// The day of the year, from 0 to 365.
+    yday: i64,
+    isdst: bool,
+    /// The offset from UTC, in seconds east of UTC.
+    gmtoff: i64,
+    /// The abbreviation of the time zone.
+    zone: String,
+}
+
+/// The number of days between 1970-01-01 and the given date, with `month` from 1 to 12. `day`
+/// may be outside of the month, in which case the date is counted from the start of the month.
+#[allow(clippy::integer_arithmetic)] // callers make sure that the year is at most about 2^40
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // This counts in eras of 400 years which start in March, so that the leap day comes last.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date that is `days` days after 1970-01-01, as year, month from 1 to 12, and day.
+#[allow(clippy::integer_arithmetic)] // `days` is at most `i64::MAX / SECONDS_PER_DAY`
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Breaks `time` down in a time zone with the given offset. Returns `None` if the year does not
+/// fit in the `int` of `struct tm`.
+#[allow(clippy::integer_arithmetic)] // the divisors are not zero, and the rest cannot overflow
+fn broken_down(time: i64, gmtoff: i64, isdst: bool, zone: String) -> Option<Tm> {
+    let local = time.checked_add(gmtoff)?;
+    let days = local.div_euclid(SECONDS_PER_DAY);
+    let secs = local.rem_euclid(SECONDS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    let year = year.checked_sub(1900).filter(|year| i32::try_from(*year).is_ok())?;
+    Some(Tm {
+        sec: secs % 60,
+        min: secs / 60 % 60,
+        hour: secs / 3600,
+        mday: day,
+        mon: month - 1,
+        year,
+        // 1970-01-01 was a Thursday.
+        wday: (days + 4).rem_euclid(7),
+        yday: days - days_from_civil(year + 1900, 1, 1),
+        isdst,
+        gmtoff,
+        zone,
+    })
+}
+
+/// The number of seconds since the epoch of the given broken-down time in UTC. The fields may
+/// be out of their usual range.
+#[allow(clippy::integer_arithmetic)] // the fields are `int`s, so this cannot overflow
+fn seconds_from_fields(year: i64, mon: i64, mday: i64, hour: i64, min: i64, sec: i64) -> i64 {
+    let year = year + 1900 + mon.div_euclid(12);
+    let month = mon.rem_euclid(12) + 1;
+    days_from_civil(year, month, mday) * SECONDS_PER_DAY + hour * 3600 + min * 60 + sec
+}
+
+/// The abbreviation of a time zone with a fixed offset, like `+0530`.
+#[allow(clippy::integer_arithmetic)] // the offset is less than a day
+fn fixed_zone_name(offset: i64) -> String {
+    if offset == 0 {
+        return "UTC".to_owned();
+    }
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.abs() / 60;
+    format!("{sign}{:02}{:02}", minutes / 60, minutes % 60)
+}
+
+/// The offset from UTC of the local time zone of the host at `time`, whether daylight saving
+/// time is in effect, and the abbreviation of the time zone.
+#[cfg(unix)]
+#[allow(clippy::useless_conversion)] // `time_t` and `c_long` are not `i64` on all hosts
+fn host_zone(time: i64) -> Option<(i64, bool, String)> {
+    use std::ffi::CStr;
+    use std::mem::MaybeUninit;
+
+    let time = libc::time_t::try_from(time).ok()?;
+    let mut tm = MaybeUninit::<libc::tm>::uninit();
+    // SAFETY: `localtime_r` only writes to `tm`, and returns null if it did not fill it in.
+    let tm = unsafe {
+        if libc::localtime_r(&time, tm.as_mut_ptr()).is_null() {
+            return None;
+        }
+        tm.assume_init()
+    };
+    let zone = if tm.tm_zone.is_null() {
+        String::new()
+    } else {
+        // SAFETY: `tm_zone` points to a string in static storage of the C library.
+        unsafe { CStr::from_ptr(tm.tm_zone) }.to_string_lossy().into_owned()
+    };
+    Some((i64::from(tm.tm_gmtoff), tm.tm_isdst > 0, zone))
+}
+
+#[cfg(not(unix))]
+fn host_zone(_time: i64) -> Option<(i64, bool, String)> {
+    None
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// The offset from UTC of the local time zone at `time`, whether daylight saving time is in
+    /// effect, and the abbreviation of the time zone.
+    fn local_zone(&self, time: i64) -> (i64, bool, String) {
+        let this = self.eval_context_ref();
+        if this.machine.communicate() {
+            if let Some(zone) = host_zone(time) {
+                return zone;
+            }
+        }
+        let offset = this.machine.tz_offset;
+        (offset, false, fixed_zone_name(offset))
+    }
+
+    fn read_time_t(&self, timep_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_ref();
+        let timep = this.deref_operand(timep_op)?;
+        // `time_t` is a `long` on all Unix targets.
+        this.read_scalar(&timep.into())?.to_machine_isize(this)
+    }
+
+    /// Normalizes `tm` as a time in a time zone with the given offset, and returns the time as a
+    /// `time_t`. Sets the last error to `EOVERFLOW` if the time does not fit in a `time_t` or in
+    /// a `struct tm`.
+    fn write_normalized_tm(
+        &mut self,
+        time: i64,
+        zone: (i64, bool, String),
+        tm: &MPlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+        let (gmtoff, isdst, zone) = zone;
+        let fits = time >= this.machine_isize_min() && time <= this.machine_isize_max();
+        let Some(broken_down) = broken_down(time, gmtoff, isdst, zone).filter(|_| fits) else {
+            let eoverflow = this.eval_libc("EOVERFLOW")?;
+            this.set_last_error(eoverflow)?;
+            return Ok(Scalar::from_machine_isize(-1, this));
+        };
+        this.write_tm(broken_down, tm)?;
+        Ok(Scalar::from_machine_isize(time, this))
+    }
+
+    fn read_tm_field(
+        &self,
+        tm: &MPlaceTy<'tcx, Provenance>,
+        name: &str,
+    ) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_ref();
+        let field = this.mplace_field_named(tm, name)?;
+        Ok(this.read_scalar(&field.into())?.to_i32()?.into())
+    }
+
+    /// The time that `tm` stands for in UTC.
+    fn read_tm(&self, tm: &MPlaceTy<'tcx, Provenance>) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_ref();
+        Ok(seconds_from_fields(
+            this.read_tm_field(tm, "tm_year")?,
+            this.read_tm_field(tm, "tm_mon")?,
+            this.read_tm_field(tm, "tm_mday")?,
+            this.read_tm_field(tm, "tm_hour")?,
+            this.read_tm_field(tm, "tm_min")?,
+            this.read_tm_field(tm, "tm_sec")?,
+        ))
+    }
+
+    /// The abbreviation of the time zone is allocated once for each name and shared by all `tm`s
+    /// that use it, which is fine since it is immutable.
+    fn write_tm(&mut self, tm: Tm, dest: &MPlaceTy<'tcx, Provenance>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        this.write_int_fields_named(
+            &[
+                ("tm_sec", tm.sec.into()),
+                ("tm_min", tm.min.into()),
+                ("tm_hour", tm.hour.into()),
+                ("tm_mday", tm.mday.into()),
+                ("tm_mon", tm.mon.into()),
+                ("tm_year", tm.year.into()),
+                ("tm_wday", tm.wday.into()),
+                ("tm_yday", tm.yday.into()),
+                ("tm_isdst", i32::from(tm.isdst).into()),
+                ("tm_gmtoff", tm.gmtoff.into()),
+            ],
+            dest,
+        )?;
+        let zone = match this.machine.tm_zones.get(&tm.zone) {
+            Some(&zone) => zone,
+            None => {
+                let zone_str = format!("{}\0", tm.zone);
+                let zone = this
+                    .allocate_str(&zone_str, MiriMemoryKind::Machine.into(), Mutability::Not)
+                    .ptr;
+                this.machine.tm_zones.insert(tm.zone, zone);
+                zone
+            }
+        };
+        this.write_pointer(zone, &this.mplace_field_named(dest, "tm_zone")?.into())
+    }
+
+    /// Implements `gmtime_r` and `localtime_r`, which only differ in the time zone.
+    fn time_to_tm(
+        &mut self,
+        timep_op: &OpTy<'tcx, Provenance>,
+        result_op: &OpTy<'tcx, Provenance>,
+        local: bool,
+    ) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        let this = self.eval_context_mut();
+
+        let time = this.read_time_t(timep_op)?;
+        let result = this.deref_operand(result_op)?;
+
+        let (gmtoff, isdst, zone) =
+            if local { this.local_zone(time) } else { (0, false, "GMT".to_owned()) };
+        let Some(tm) = broken_down(time, gmtoff, isdst, zone) else {
+            let eoverflow = this.eval_libc("EOVERFLOW")?;
+            this.set_last_error(eoverflow)?;
+            return Ok(Pointer::null());
+        };
+        this.write_tm(tm, &result)?;
+        Ok(result.ptr)
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    fn gmtime_r(
+        &mut self,
+        timep_op: &OpTy<'tcx, Provenance>,
+        result_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        self.eval_context_mut().time_to_tm(timep_op, result_op, false)
+    }
+
+    fn localtime_r(
+        &mut self,
+        timep_op: &OpTy<'tcx, Provenance>,
+        result_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        self.eval_context_mut().time_to_tm(timep_op, result_op, true)
+    }
+
+    /// The fields of `tm` are normalized, and the other fields are filled in. `tm_isdst` is not
+    /// looked at: the offset of the time zone is the one in effect at the resulting time.
+    fn mktime(&mut self, tm_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        let tm = this.deref_operand(tm_op)?;
+        let local = this.read_tm(&tm)?;
+        // The offset depends on the time, so we need a guess for the time first. Starting out
+        // with the offset at `local` as if it were UTC is good enough to get the right offset for
+        // the second guess, unless `local` is too close to a change of the offset.
+        let (guess_offset, ..) = this.local_zone(local);
+        let zone = this.local_zone(local.saturating_sub(guess_offset));
+        this.write_normalized_tm(local.saturating_sub(zone.0), zone, &tm)
+    }
+
+    /// Like `mktime`, in UTC.
+    fn timegm(&mut self, tm_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        let tm = this.deref_operand(tm_op)?;
+        let time = this.read_tm(&tm)?;
+        this.write_normalized_tm(time, (0, false, "GMT".to_owned()), &tm)
+    }
+}
@@ -9,9 +9,20 @@ use rustc_target::spec::abi::Abi;
 
 use crate::*;
 use shims::foreign_items::EmulateByNameResult;
+use shims::unix::calendar::EvalContextExt as _;
 use shims::unix::fs::EvalContextExt as _;
+use shims::unix::locale::EvalContextExt as _;
+use shims::unix::mem::EvalContextExt as _;
+use shims::unix::netdb::EvalContextExt as _;
+use shims::unix::pipe::EvalContextExt as _;
+use shims::unix::process::EvalContextExt as _;
+use shims::unix::resource::EvalContextExt as _;
+use shims::unix::signal::EvalContextExt as _;
+use shims::unix::socket::EvalContextExt as _;
 use shims::unix::sync::EvalContextExt as _;
+use shims::unix::syslog::EvalContextExt as _;
 use shims::unix::thread::EvalContextExt as _;
+use shims::unix::user::EvalContextExt as _;
 
 impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
 pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
@@ -24,6 +35,11 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     ) -> InterpResult<'tcx, EmulateByNameResult<'mir, 'tcx>> {
         let this = self.eval_context_mut();
 
+        if this.inject_fs_failure(link_name, args)? {
+            this.write_int(-1, dest)?;
+            return Ok(EmulateByNameResult::NeedsJumping);
+        }
+
         // See `fn emulate_foreign_item_by_name` in `shims/foreign_items.rs` for the general pattern.
         #[rustfmt::skip]
         match link_name.as_str() {
@@ -59,7 +75,18 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             "open" | "open64" => {
                 // `open` is variadic, the third argument is only present when the second argument has O_CREAT (or on linux O_TMPFILE, but miri doesn't support that) set
                 this.check_abi_and_shim_symbol_clash(abi, Abi::C { unwind: false }, link_name)?;
-                let result = this.open(args)?;
+                let result = this.open(None, args)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "openat" | "openat64" => {
+                // Like `open`, `openat` is variadic, with `dirfd` in front of the `open` arguments.
+                this.check_abi_and_shim_symbol_clash(abi, Abi::C { unwind: false }, link_name)?;
+                let Some((dirfd, args)) = args.split_first() else {
+                    throw_ub_format!(
+                        "incorrect number of arguments for `openat`: got 0, expected at least 3"
+                    );
+                };
+                let result = this.open(Some(dirfd), args)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
             "close" => {
@@ -74,13 +101,35 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let result = this.fcntl(args)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
+            "ioctl" => {
+                // `ioctl` is variadic. The argument count is checked based on the request in
+                // `this.ioctl()`.
+                this.check_abi_and_shim_symbol_clash(abi, Abi::C { unwind: false }, link_name)?;
+                let result = this.ioctl(args)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "flock" => {
+                let [fd, operation] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let fd = this.read_scalar(fd)?.to_i32()?;
+                let operation = this.read_scalar(operation)?.to_i32()?;
+                this.flock(fd, operation, dest)?;
+            }
+            "dup" => {
+                let [old_fd] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.dup(old_fd)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "dup2" => {
+                let [old_fd, new_fd] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.dup3(old_fd, new_fd, None)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
             "read" => {
                 let [fd, buf, count] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
                 let fd = this.read_scalar(fd)?.to_i32()?;
                 let buf = this.read_pointer(buf)?;
                 let count = this.read_scalar(count)?.to_machine_usize(this)?;
-                let result = this.read(fd, buf, count)?;
-                this.write_scalar(Scalar::from_machine_isize(result, this), dest)?;
+                this.read(fd, buf, count, dest)?;
             }
             "write" => {
                 let [fd, buf, n] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
@@ -88,28 +137,200 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let buf = this.read_pointer(buf)?;
                 let count = this.read_scalar(n)?.to_machine_usize(this)?;
                 trace!("Called write({:?}, {:?}, {:?})", fd, buf, count);
-                let result = this.write(fd, buf, count)?;
-                // Now, `result` is the value we return back to the program.
+                this.write(fd, buf, count, dest)?;
+            }
+            "pread" | "pread64" => {
+                let [fd, buf, count, offset] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pread(fd, buf, count, offset)?;
+                this.write_scalar(Scalar::from_machine_isize(result, this), dest)?;
+            }
+            "pwrite" | "pwrite64" => {
+                let [fd, buf, count, offset] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pwrite(fd, buf, count, offset)?;
                 this.write_scalar(Scalar::from_machine_isize(result, this), dest)?;
             }
+            "preadv" | "preadv64" => {
+                let [fd, iov, iovcnt, offset] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.preadv(fd, iov, iovcnt, offset)?;
+                this.write_scalar(Scalar::from_machine_isize(result, this), dest)?;
+            }
+            "pwritev" | "pwritev64" => {
+                let [fd, iov, iovcnt, offset] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pwritev(fd, iov, iovcnt, offset)?;
+                this.write_scalar(Scalar::from_machine_isize(result, this), dest)?;
+            }
+            "pipe" => {
+                let [pipefd] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pipe2(pipefd, None)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "socketpair" => {
+                let [domain, type_, protocol, sv] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.socketpair(domain, type_, protocol, sv)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "socket" => {
+                let [domain, type_, protocol] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.socket(domain, type_, protocol)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "bind" => {
+                let [fd, addr, len] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.bind(fd, addr, len)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "listen" => {
+                let [fd, backlog] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.listen(fd, backlog)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "connect" => {
+                let [fd, addr, len] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.connect(fd, addr, len)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "accept" => {
+                let [fd, addr, addrlen] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let fd = this.read_scalar(fd)?.to_i32()?;
+                let addr = this.read_pointer(addr)?;
+                let addrlen = this.read_pointer(addrlen)?;
+                this.accept4(fd, addr, addrlen, 0, dest)?;
+            }
+            "getsockname" => {
+                let [fd, addr, addrlen] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.getsockname(fd, addr, addrlen)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "getpeername" => {
+                let [fd, addr, addrlen] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.getpeername(fd, addr, addrlen)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "setsockopt" => {
+                let [fd, level, option_name, option_value, option_len] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.setsockopt(fd, level, option_name, option_value, option_len)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "shutdown" => {
+                let [fd, how] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.shutdown(fd, how)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "getaddrinfo" => {
+                let [node, service, hints, res] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.getaddrinfo(node, service, hints, res)?;
+                this.write_scalar(result, dest)?;
+            }
+            "freeaddrinfo" => {
+                let [res] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.freeaddrinfo(res)?;
+            }
+            "gai_strerror" => {
+                let [errcode] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.gai_strerror(errcode)?;
+                this.write_pointer(result, dest)?;
+            }
+            "send" => {
+                let [fd, buf, len, flags] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.send(fd, buf, len, flags, dest)?;
+            }
+            "recv" => {
+                let [fd, buf, len, flags] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.recv(fd, buf, len, flags, dest)?;
+            }
             "unlink" => {
                 let [path] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
                 let result = this.unlink(path)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
+            "unlinkat" => {
+                let [dirfd, path, flags] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.unlinkat(dirfd, path, flags)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
             "symlink" => {
                 let [target, linkpath] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
-                let result = this.symlink(target, linkpath)?;
+                let result = this.symlinkat(target, None, linkpath)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "symlinkat" => {
+                let [target, newdirfd, linkpath] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.symlinkat(target, Some(newdirfd), linkpath)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
             "rename" => {
                 let [oldpath, newpath] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
-                let result = this.rename(oldpath, newpath)?;
+                let result = this.renameat(None, oldpath, None, newpath)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "renameat" => {
+                let [olddirfd, oldpath, newdirfd, newpath] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.renameat(Some(olddirfd), oldpath, Some(newdirfd), newpath)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
             "mkdir" => {
                 let [path, mode] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
-                let result = this.mkdir(path, mode)?;
+                let result = this.mkdirat(None, path, mode)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "mkdirat" => {
+                let [dirfd, path, mode] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.mkdirat(Some(dirfd), path, mode)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "chmod" => {
+                let [path, mode] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.fchmodat(None, path, mode, None)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "fchmod" => {
+                let [fd, mode] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.fchmod(fd, mode)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "fchmodat" => {
+                let [dirfd, path, mode, flags] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.fchmodat(Some(dirfd), path, mode, Some(flags))?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "access" => {
+                let [path, mode] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.faccessat(None, path, mode, None)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "faccessat" => {
+                let [dirfd, path, mode, flags] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.faccessat(Some(dirfd), path, mode, Some(flags))?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "chown" => {
+                let [path, owner, group] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.chown(path, owner, group, /* follow_symlinks */ true)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "lchown" => {
+                let [path, owner, group] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.chown(path, owner, group, /* follow_symlinks */ false)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "fchown" => {
+                let [fd, owner, group] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.fchown(fd, owner, group)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "umask" => {
+                let [mask] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.umask(mask)?;
+                this.write_scalar(result, dest)?;
+            }
+            "utimensat" => {
+                let [dirfd, path, times, flags] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.utimensat(dirfd, Some(path), times, Some(flags))?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "futimens" => {
+                let [fd, times] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.utimensat(fd, None, times, None)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
             "rmdir" => {
@@ -170,9 +391,19 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             }
             "mkstemp" => {
                 let [template] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
-                let result = this.mkstemp(template)?;
+                let result = this.mkostemp(template, None)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "mkostemp" => {
+                let [template, flags] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.mkostemp(template, Some(flags))?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
+            "mkdtemp" => {
+                let [template] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.mkdtemp(template)?;
+                this.write_scalar(result, dest)?;
+            }
 
             // Time related shims
             "gettimeofday" => {
@@ -180,6 +411,30 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let result = this.gettimeofday(tv, tz)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
+            "gmtime_r" => {
+                let [timep, result] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.gmtime_r(timep, result)?;
+                this.write_pointer(result, dest)?;
+            }
+            "localtime_r" => {
+                let [timep, result] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.localtime_r(timep, result)?;
+                this.write_pointer(result, dest)?;
+            }
+            "mktime" => {
+                let [tm] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.mktime(tm)?;
+                this.write_scalar(result, dest)?;
+            }
+            "timegm" => {
+                let [tm] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.timegm(tm)?;
+                this.write_scalar(result, dest)?;
+            }
+            "tzset" => {
+                let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                // The time zone is only looked at when it is used, so there is nothing to do.
+            }
 
             // Allocation
             "posix_memalign" => {
@@ -206,6 +461,32 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                     this.write_null(dest)?;
                 }
             }
+            "mmap" => {
+                let [addr, length, prot, flags, fd, offset] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.mmap(addr, length, prot, flags, fd, offset)?;
+                this.write_scalar(result, dest)?;
+            }
+            "munmap" => {
+                let [addr, length] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.munmap(addr, length)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "mprotect" => {
+                let [addr, length, prot] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.mprotect(addr, length, prot)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "madvise" => {
+                let [addr, length, advice] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.madvise(addr, length, advice)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "posix_madvise" => {
+                let [addr, length, advice] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.posix_madvise(addr, length, advice)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
 
             // Dynamic symbol loading
             "dlsym" => {
@@ -230,8 +511,8 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 // At least the names seem to all also exist on macOS.
                 let sysconfs: &[(&str, fn(&MiriEvalContext<'_, '_>) -> Scalar<Provenance>)] = &[
                     ("_SC_PAGESIZE", |this| Scalar::from_int(PAGE_SIZE, this.pointer_size())),
-                    ("_SC_NPROCESSORS_CONF", |this| Scalar::from_int(NUM_CPUS, this.pointer_size())),
-                    ("_SC_NPROCESSORS_ONLN", |this| Scalar::from_int(NUM_CPUS, this.pointer_size())),
+                    ("_SC_NPROCESSORS_CONF", |this| Scalar::from_int(this.machine.num_cpus, this.pointer_size())),
+                    ("_SC_NPROCESSORS_ONLN", |this| Scalar::from_int(this.machine.num_cpus, this.pointer_size())),
                     // 512 seems to be a reasonable default. The value is not critical, in
                     // the sense that getpwuid_r takes and checks the buffer length.
                     ("_SC_GETPW_R_SIZE_MAX", |this| Scalar::from_int(512, this.pointer_size()))
@@ -251,6 +532,17 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 }
             }
 
+            "getrlimit" => {
+                let [resource, rlim] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.getrlimit(resource, rlim)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "setrlimit" => {
+                let [resource, rlim] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.setrlimit(resource, rlim)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+
             // Thread-local storage
             "pthread_key_create" => {
                 let [key, dtor] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
@@ -347,6 +639,31 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let result = this.pthread_mutex_destroy(mutex)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
+            "pthread_rwlockattr_init" => {
+                let [attr] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_rwlockattr_init(attr)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "pthread_rwlockattr_setpshared" => {
+                let [attr, pshared] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_rwlockattr_setpshared(attr, pshared)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "pthread_rwlockattr_getpshared" => {
+                let [attr, pshared] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_rwlockattr_getpshared(attr, pshared)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "pthread_rwlockattr_destroy" => {
+                let [attr] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_rwlockattr_destroy(attr)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "pthread_rwlock_init" => {
+                let [rwlock, attr] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_rwlock_init(rwlock, attr)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
             "pthread_rwlock_rdlock" => {
                 let [rwlock] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
                 let result = this.pthread_rwlock_rdlock(rwlock)?;
@@ -357,6 +674,10 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let result = this.pthread_rwlock_tryrdlock(rwlock)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
+            "pthread_rwlock_timedrdlock" => {
+                let [rwlock, abstime] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.pthread_rwlock_timedrdlock(rwlock, abstime, dest)?;
+            }
             "pthread_rwlock_wrlock" => {
                 let [rwlock] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
                 let result = this.pthread_rwlock_wrlock(rwlock)?;
@@ -367,6 +688,10 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let result = this.pthread_rwlock_trywrlock(rwlock)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
+            "pthread_rwlock_timedwrlock" => {
+                let [rwlock, abstime] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.pthread_rwlock_timedwrlock(rwlock, abstime, dest)?;
+            }
             "pthread_rwlock_unlock" => {
                 let [rwlock] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
                 let result = this.pthread_rwlock_unlock(rwlock)?;
@@ -433,6 +758,11 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let result = this.pthread_detach(thread)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
+            "pthread_cancel" => {
+                let [thread] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_cancel(thread)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
             "pthread_self" => {
                 let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
                 let res = this.pthread_self()?;
@@ -455,6 +785,43 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let result = this.isatty(fd)?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
+            "fork" | "vfork" => {
+                let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.fork(link_name)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "posix_spawn" | "posix_spawnp" => {
+                let args = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.posix_spawn(link_name, args)?;
+                this.write_scalar(result, dest)?;
+            }
+            "__cxa_atexit" => {
+                let [func, arg, dso] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.cxa_atexit(func, arg, dso)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+
+            // System logger
+            "openlog" => {
+                let [ident, option, facility] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.openlog(ident, option, facility)?;
+            }
+            "syslog" => {
+                // `syslog` is variadic, the arguments after the format string are only checked
+                // against the conversions in it.
+                this.check_abi_and_shim_symbol_clash(abi, Abi::C { unwind: false }, link_name)?;
+                this.syslog(args)?;
+            }
+            "setlogmask" => {
+                let [mask] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.setlogmask(mask)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "closelog" => {
+                let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.closelog();
+            }
             "pthread_atfork" => {
                 let [prepare, parent, child] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
                 this.read_pointer(prepare)?;
@@ -483,6 +850,104 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let result = this.getpid()?;
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
+            "getppid" => {
+                let [] = this.check_shim(abi, Abi::C { unwind: false}, link_name, args)?;
+                let result = this.getppid()?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "getuid" => {
+                let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.write_int(this.getuid(), dest)?;
+            }
+            "geteuid" => {
+                let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.write_int(this.geteuid(), dest)?;
+            }
+            "getgid" => {
+                let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.write_int(this.getgid(), dest)?;
+            }
+            "getegid" => {
+                let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.write_int(this.getegid(), dest)?;
+            }
+            "getpwuid_r" => {
+                let [uid, pwd, buf, buflen, result] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.getpwuid_r(uid, pwd, buf, buflen, result)?;
+                this.write_scalar(result, dest)?;
+            }
+
+            // Locales
+            "setlocale" => {
+                let [category, locale] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.setlocale(category, locale)?;
+                this.write_pointer(result, dest)?;
+            }
+            "localeconv" => {
+                let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.localeconv()?;
+                this.write_pointer(result, dest)?;
+            }
+            "newlocale" => {
+                let [mask, locale, base] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.newlocale(mask, locale, base)?;
+                this.write_pointer(result, dest)?;
+            }
+            "duplocale" => {
+                let [locale] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.duplocale(locale)?;
+                this.write_pointer(result, dest)?;
+            }
+            "freelocale" => {
+                let [locale] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.freelocale(locale)?;
+                // On macOS, `freelocale` returns an `int`.
+                if this.tcx.sess.target.os == "macos" {
+                    this.write_scalar(Scalar::from_i32(0), dest)?;
+                }
+            }
+            "uselocale" => {
+                let [locale] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.uselocale(locale)?;
+                this.write_scalar(result, dest)?;
+            }
+
+            // Signal handling. The functions that might deliver a signal are handled in
+            // `shims/unix/signal.rs`, since they call back into the program.
+            "sigaction" => {
+                let [sig, act, oldact] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.sigaction(sig, act, oldact)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "signal" => {
+                let [sig, handler] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.signal(sig, handler)?;
+                this.write_scalar(result, dest)?;
+            }
+            "sigpending" => {
+                let [set] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.sigpending(set)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "sigemptyset" => {
+                let [set] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.sigemptyset(set)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "sigfillset" => {
+                let [set] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.sigfillset(set)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "sigaddset" | "sigdelset" | "sigismember" => {
+                let [set, sig] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.sigset_member(link_name.as_str(), set, sig)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
 
             // Incomplete shims that we "stub out" just to get pre-main initialization code to work.
             // These shims are enabled only when the caller is in the standard library.
@@ -532,61 +997,11 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 this.write_null(dest)?;
             }
 
-            | "signal"
-            | "sigaltstack"
+            "sigaltstack"
             if this.frame_in_std() => {
                 let [_, _] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
                 this.write_null(dest)?;
             }
-            | "sigaction"
-            | "mprotect"
-            if this.frame_in_std() => {
-                let [_, _, _] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
-                this.write_null(dest)?;
-            }
-
-            "getuid"
-            if this.frame_in_std() => {
-                let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
-                // FOr now, just pretend we always have this fixed UID.
-                this.write_int(super::UID, dest)?;
-            }
-
-            "getpwuid_r" if this.frame_in_std() => {
-                let [uid, pwd, buf, buflen, result] =
-                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
-                this.check_no_isolation("`getpwuid_r`")?;
-
-                let uid = this.read_scalar(uid)?.to_u32()?;
-                let pwd = this.deref_operand(pwd)?;
-                let buf = this.read_pointer(buf)?;
-                let buflen = this.read_scalar(buflen)?.to_machine_usize(this)?;
-                let result = this.deref_operand(result)?;
-
-                // Must be for "us".
-                if uid != crate::shims::unix::UID {
-                    throw_unsup_format!("`getpwuid_r` on other users is not supported");
-                }
-
-                // Reset all fields to `uninit` to make sure nobody reads them.
-                // (This is a std-only shim so we are okay with such hacks.)
-                this.write_uninit(&pwd.into())?;
-
-                // We only set the home_dir field.
-                #[allow(deprecated)]
-                let home_dir = std::env::home_dir().unwrap();
-                let (written, _) = this.write_path_to_c_str(&home_dir, buf, buflen)?;
-                let pw_dir = this.mplace_field_named(&pwd, "pw_dir")?;
-                this.write_pointer(buf, &pw_dir.into())?;
-
-                if written {
-                    this.write_pointer(pwd.ptr, &result.into())?;
-                    this.write_null(dest)?;
-                } else {
-                    this.write_null(&result.into())?;
-                    this.write_scalar(this.eval_libc("ERANGE")?, dest)?;
-                }
-            }
 
             // Platform-specific shims
             _ => {
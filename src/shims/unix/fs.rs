@@ -1,37 +1,148 @@
 use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::fs::{
-    read_dir, remove_dir, remove_file, rename, DirBuilder, File, FileType, OpenOptions, ReadDir,
+    metadata, read_dir, remove_dir, remove_file, rename, set_permissions, DirBuilder, File,
+    FileType, OpenOptions, Permissions, ReadDir,
 };
 use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::iter::{self, Peekable};
 use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
 use std::time::SystemTime;
 
 use log::trace;
+use rand::Rng;
 
 use rustc_data_structures::fx::FxHashMap;
 use rustc_middle::ty::{self, layout::LayoutOf};
+use rustc_span::{Span, Symbol};
 use rustc_target::abi::{Align, Size};
 
+use crate::concurrency::data_race;
+use crate::concurrency::thread::Time;
 use crate::shims::os_str::bytes_to_os_str;
 use crate::*;
 use shims::os_str::os_str_to_bytes;
 use shims::time::system_time_to_duration;
+use shims::unix::linux::epoll::{Epoll, EpollReadiness, EvalContextExt as _};
+use shims::unix::linux::timerfd::Timer;
+use shims::unix::resource::EvalContextExt as _;
+use shims::unix::socket::{EvalContextExt as _, Socket, SocketAddresses};
 
 #[derive(Debug)]
-struct FileHandle {
+pub(super) struct FileHandle {
     file: File,
+    readable: bool,
     writable: bool,
+    /// Shared between duplicates of the file descriptor. It identifies the open file description,
+    /// which is what `flock` locks belong to.
+    description: Rc<()>,
+    /// The `O_APPEND` flag of the file description. Miri implements it itself, so that it can be
+    /// changed with `fcntl`.
+    is_append: Rc<Cell<bool>>,
+    /// The `O_NONBLOCK` flag of the file description, which is also set on the host file.
+    is_nonblock: Rc<Cell<bool>>,
+    /// Set if this is a directory.
+    directory: Option<Rc<RefCell<OpenDirectory>>>,
 }
 
-trait FileDescriptor: std::fmt::Debug {
+/// A directory that was opened with `open`. Like the offset of a regular file, the state of
+/// reading it is shared between duplicates of the file descriptor.
+#[derive(Debug)]
+pub(super) struct OpenDirectory {
+    /// The absolute path of the directory, which the `*at` functions resolve relative paths
+    /// against.
+    path: PathBuf,
+    /// The entries not yet returned by `getdents64`.
+    read_dir: Peekable<ReadDir>,
+    /// The number of entries returned so far.
+    position: u64,
+}
+
+/// What a file description was opened for, which `fcntl(F_GETFL)` reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum AccessMode {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+pub(super) trait FileDescriptor: std::fmt::Debug {
     fn name(&self) -> &'static str;
 
     fn as_file_handle<'tcx>(&self) -> InterpResult<'tcx, &FileHandle> {
         throw_unsup_format!("{} cannot be used as FileHandle", self.name());
     }
 
+    fn as_epoll(&self) -> Option<&Epoll> {
+        None
+    }
+
+    fn as_socket(&self) -> Option<&Socket> {
+        None
+    }
+
+    fn as_timer(&self) -> Option<&Timer> {
+        None
+    }
+
+    fn as_directory(&self) -> Option<&Rc<RefCell<OpenDirectory>>> {
+        None
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        AccessMode::ReadWrite
+    }
+
+    /// Whether this is one of the standard streams, which `-Zmiri-isatty` applies to. They cannot
+    /// be closed, but `dup2` can replace them.
+    fn is_std_stream(&self) -> bool {
+        false
+    }
+
+    /// Whether this is a file or directory of the file system, which `-Zmiri-fs-fail-nth` applies
+    /// to.
+    fn is_fs_object(&self) -> bool {
+        false
+    }
+
+    /// The `O_NONBLOCK` flag of the file description, or `None` if it cannot be set. Like all file
+    /// status flags, it is shared between duplicates of the file descriptor.
+    fn nonblock_flag(&self) -> Option<&Cell<bool>> {
+        None
+    }
+
+    /// Changes `O_NONBLOCK` on the host, for file descriptions that are backed by a host file.
+    /// Called after the flag returned by `nonblock_flag` was changed.
+    fn set_host_nonblock(&self, _nonblock: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The `O_APPEND` flag of the file description, or `None` if it cannot be set.
+    fn append_flag(&self) -> Option<&Cell<bool>> {
+        None
+    }
+
+    /// Whether an operation that cannot make progress, which is reported as
+    /// `io::ErrorKind::WouldBlock`, blocks the thread until the file description is readable
+    /// instead of failing. Only file descriptions that Miri models itself can block.
+    fn is_blocking(&self) -> bool {
+        self.nonblock_flag().map_or(false, |nonblock| !nonblock.get())
+    }
+
+    /// The readiness of this file description for `epoll`, or `None` if it cannot be watched.
+    fn epoll_readiness(&self) -> Option<EpollReadiness> {
+        None
+    }
+
+    /// The point in time at which the file description becomes ready by itself, like a timerfd
+    /// that expires, or `None` if it only becomes ready through another operation.
+    fn ready_at(&self) -> Option<Instant> {
+        None
+    }
+
     fn read<'tcx>(
         &mut self,
         _communicate_allowed: bool,
@@ -48,6 +159,15 @@ trait FileDescriptor: std::fmt::Debug {
         throw_unsup_format!("cannot write to {}", self.name());
     }
 
+    /// For file descriptions that pass data between the threads of the program: called after a
+    /// successful `write` to release the clock of the writing thread to the data. The thread
+    /// that reads the data acquires it in `acquire_clock`, so that writing data happens-before
+    /// reading it.
+    fn release_clock(&self, _data_race: &data_race::GlobalState, _thread: ThreadId) {}
+
+    /// Called after a successful `read`, see `release_clock`.
+    fn acquire_clock(&self, _data_race: &data_race::GlobalState, _thread: ThreadId) {}
+
     fn seek<'tcx>(
         &mut self,
         _communicate_allowed: bool,
@@ -56,6 +176,26 @@ trait FileDescriptor: std::fmt::Debug {
         throw_unsup_format!("cannot seek on {}", self.name());
     }
 
+    /// Read from `offset` on, without moving the file offset.
+    fn pread<'tcx>(
+        &self,
+        _communicate_allowed: bool,
+        _bytes: &mut [u8],
+        _offset: u64,
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        throw_unsup_format!("cannot read at an offset from {}", self.name());
+    }
+
+    /// Write from `offset` on, without moving the file offset.
+    fn pwrite<'tcx>(
+        &self,
+        _communicate_allowed: bool,
+        _bytes: &[u8],
+        _offset: u64,
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        throw_unsup_format!("cannot write at an offset to {}", self.name());
+    }
+
     fn close<'tcx>(
         self: Box<Self>,
         _communicate_allowed: bool,
@@ -80,6 +220,57 @@ impl FileDescriptor for FileHandle {
         Ok(self)
     }
 
+    fn as_directory(&self) -> Option<&Rc<RefCell<OpenDirectory>>> {
+        self.directory.as_ref()
+    }
+
+    fn is_fs_object(&self) -> bool {
+        true
+    }
+
+    fn nonblock_flag(&self) -> Option<&Cell<bool>> {
+        Some(&self.is_nonblock)
+    }
+
+    fn set_host_nonblock(&self, nonblock: bool) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            let fd = self.file.as_raw_fd();
+            // SAFETY: `fcntl` with these commands has no memory safety preconditions.
+            let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+            if flags < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let flags = if nonblock { flags | libc::O_NONBLOCK } else { flags & !libc::O_NONBLOCK };
+            if unsafe { libc::fcntl(fd, libc::F_SETFL, flags) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        // Other hosts do not have non-blocking files, but then regular files never block anyway.
+        #[cfg(not(unix))]
+        let _ = nonblock;
+        Ok(())
+    }
+
+    fn append_flag(&self) -> Option<&Cell<bool>> {
+        Some(&self.is_append)
+    }
+
+    /// Reading a host file that would block blocks on the host, unless `O_NONBLOCK` is set,
+    /// which the host then reports.
+    fn is_blocking(&self) -> bool {
+        false
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        match (self.readable, self.writable) {
+            (true, false) => AccessMode::ReadOnly,
+            (false, true) => AccessMode::WriteOnly,
+            _ => AccessMode::ReadWrite,
+        }
+    }
+
     fn read<'tcx>(
         &mut self,
         communicate_allowed: bool,
@@ -95,6 +286,13 @@ impl FileDescriptor for FileHandle {
         bytes: &[u8],
     ) -> InterpResult<'tcx, io::Result<usize>> {
         assert!(communicate_allowed, "isolation should have prevented even opening a file");
+        // Unlike with `O_APPEND` on the host, other processes could write to the file in between,
+        // but the interpreted program cannot.
+        if self.is_append.get() {
+            if let Err(e) = (&mut &self.file).seek(SeekFrom::End(0)) {
+                return Ok(Err(e));
+            }
+        }
         Ok((&mut &self.file).write(bytes))
     }
 
@@ -107,6 +305,32 @@ impl FileDescriptor for FileHandle {
         Ok(self.file.seek(offset))
     }
 
+    fn pread<'tcx>(
+        &self,
+        communicate_allowed: bool,
+        bytes: &mut [u8],
+        offset: u64,
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        assert!(communicate_allowed, "isolation should have prevented even opening a file");
+        self.read_at(bytes, offset)
+    }
+
+    fn pwrite<'tcx>(
+        &self,
+        communicate_allowed: bool,
+        bytes: &[u8],
+        offset: u64,
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        assert!(communicate_allowed, "isolation should have prevented even opening a file");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileExt;
+            Ok(self.file.write_at(bytes, offset))
+        }
+        #[cfg(not(unix))]
+        throw_unsup_format!("`pwrite` is only supported on Unix hosts")
+    }
+
     fn close<'tcx>(
         self: Box<Self>,
         communicate_allowed: bool,
@@ -135,7 +359,15 @@ impl FileDescriptor for FileHandle {
 
     fn dup(&mut self) -> io::Result<Box<dyn FileDescriptor>> {
         let duplicated = self.file.try_clone()?;
-        Ok(Box::new(FileHandle { file: duplicated, writable: self.writable }))
+        Ok(Box::new(FileHandle {
+            file: duplicated,
+            readable: self.readable,
+            writable: self.writable,
+            description: Rc::clone(&self.description),
+            is_append: Rc::clone(&self.is_append),
+            is_nonblock: Rc::clone(&self.is_nonblock),
+            directory: self.directory.clone(),
+        }))
     }
 
     #[cfg(unix)]
@@ -150,6 +382,14 @@ impl FileDescriptor for io::Stdin {
         "stdin"
     }
 
+    fn is_std_stream(&self) -> bool {
+        true
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        AccessMode::ReadOnly
+    }
+
     fn read<'tcx>(
         &mut self,
         communicate_allowed: bool,
@@ -177,6 +417,14 @@ impl FileDescriptor for io::Stdout {
         "stdout"
     }
 
+    fn is_std_stream(&self) -> bool {
+        true
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        AccessMode::WriteOnly
+    }
+
     fn write<'tcx>(
         &self,
         _communicate_allowed: bool,
@@ -209,6 +457,14 @@ impl FileDescriptor for io::Stderr {
         "stderr"
     }
 
+    fn is_std_stream(&self) -> bool {
+        true
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        AccessMode::WriteOnly
+    }
+
     fn write<'tcx>(
         &self,
         _communicate_allowed: bool,
@@ -237,6 +493,14 @@ impl FileDescriptor for DummyOutput {
         "stderr and stdout"
     }
 
+    fn is_std_stream(&self) -> bool {
+        true
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        AccessMode::WriteOnly
+    }
+
     fn write<'tcx>(
         &self,
         _communicate_allowed: bool,
@@ -251,30 +515,108 @@ impl FileDescriptor for DummyOutput {
     }
 }
 
+/// The stdin of the interpreted program when its input is given by `-Zmiri-stdin-file` or
+/// `MIRI_STDIN`.
+#[derive(Debug)]
+struct ScriptedStdin {
+    /// Shared between duplicates of the file descriptor, like the offset of a file.
+    input: Rc<RefCell<io::Cursor<Vec<u8>>>>,
+}
+
+impl FileDescriptor for ScriptedStdin {
+    fn name(&self) -> &'static str {
+        "stdin"
+    }
+
+    fn is_std_stream(&self) -> bool {
+        true
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        AccessMode::ReadOnly
+    }
+
+    fn read<'tcx>(
+        &mut self,
+        _communicate_allowed: bool,
+        bytes: &mut [u8],
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        // The input is fixed, so reading it is fine with isolation.
+        Ok(self.input.borrow_mut().read(bytes))
+    }
+
+    fn dup(&mut self) -> io::Result<Box<dyn FileDescriptor>> {
+        Ok(Box::new(ScriptedStdin { input: Rc::clone(&self.input) }))
+    }
+}
+
 #[derive(Debug)]
 pub struct FileHandler {
-    handles: BTreeMap<i32, Box<dyn FileDescriptor>>,
+    pub(super) handles: BTreeMap<i32, Box<dyn FileDescriptor>>,
+    /// The addresses that sockets are bound to.
+    pub(super) socket_addresses: SocketAddresses,
+    /// The `flock` locks, by the host device and inode number of the locked file.
+    file_locks: FxHashMap<(u64, u64), FileLock>,
+    /// The file mode creation mask of the process, as set by `umask`.
+    umask: u32,
+    /// The number of file system calls so far, for `-Zmiri-fs-fail-nth`.
+    fs_calls: u64,
+    fs_fail_nth: Option<u64>,
+    fs_fail_rate: f64,
+    fs_fail_errno: String,
+    /// The file descriptors opened by the foreign call that is being emulated. Where they were
+    /// opened is only recorded once the call returns, see `locate_opened_fds`.
+    unlocated_fds: Vec<i32>,
+    /// Where each file descriptor was opened, for `-Zmiri-check-fd-leaks`.
+    opened_at: FxHashMap<i32, Span>,
 }
 
 impl FileHandler {
-    pub(crate) fn new(mute_stdout_stderr: bool) -> FileHandler {
+    pub(crate) fn new(config: &MiriConfig) -> FileHandler {
         let mut handles: BTreeMap<_, Box<dyn FileDescriptor>> = BTreeMap::new();
-        handles.insert(0i32, Box::new(io::stdin()));
-        if mute_stdout_stderr {
+        match &config.stdin {
+            Some(input) => {
+                let input = Rc::new(RefCell::new(io::Cursor::new(input.clone())));
+                handles.insert(0i32, Box::new(ScriptedStdin { input }));
+            }
+            None => {
+                handles.insert(0i32, Box::new(io::stdin()));
+            }
+        }
+        if config.mute_stdout_stderr {
             handles.insert(1i32, Box::new(DummyOutput));
             handles.insert(2i32, Box::new(DummyOutput));
         } else {
             handles.insert(1i32, Box::new(io::stdout()));
             handles.insert(2i32, Box::new(io::stderr()));
         }
-        FileHandler { handles }
+        FileHandler {
+            handles,
+            socket_addresses: SocketAddresses::default(),
+            file_locks: FxHashMap::default(),
+            umask: 0o022,
+            fs_calls: 0,
+            fs_fail_nth: config.fs_fail_nth,
+            fs_fail_rate: config.fs_fail_rate,
+            fs_fail_errno: config.fs_fail_errno.clone(),
+            unlocated_fds: Vec::new(),
+            opened_at: FxHashMap::default(),
+        }
     }
 
-    fn insert_fd(&mut self, file_handle: Box<dyn FileDescriptor>) -> i32 {
+    pub(super) fn insert_fd(&mut self, file_handle: Box<dyn FileDescriptor>) -> i32 {
         self.insert_fd_with_min_fd(file_handle, 0)
     }
 
     fn insert_fd_with_min_fd(&mut self, file_handle: Box<dyn FileDescriptor>, min_fd: i32) -> i32 {
+        let new_fd = self.lowest_free_fd(min_fd);
+        self.handles.try_insert(new_fd, file_handle).unwrap();
+        self.unlocated_fds.push(new_fd);
+        new_fd
+    }
+
+    /// The file descriptor that `insert_fd_with_min_fd` would use.
+    pub(super) fn lowest_free_fd(&self, min_fd: i32) -> i32 {
         // Find the lowest unused FD, starting from min_fd. If the first such unused FD is in
         // between used FDs, the find_map combinator will return it. If the first such unused FD
         // is after all other used FDs, the find_map combinator will return None, and we will use
@@ -290,20 +632,219 @@ impl FileHandler {
                     None
                 }
             });
-        let new_fd = candidate_new_fd.unwrap_or_else(|| {
+        candidate_new_fd.unwrap_or_else(|| {
             // find_map ran out of BTreeMap entries before finding a free fd, use one plus the
-            // maximum fd in the map
+            // maximum fd in the map, as long as that is not below min_fd
             self.handles
-                .last_key_value()
+                .range(min_fd..)
+                .next_back()
                 .map(|(fd, _)| fd.checked_add(1).unwrap())
                 .unwrap_or(min_fd)
-        });
+        })
+    }
 
-        self.handles.try_insert(new_fd, file_handle).unwrap();
-        new_fd
+    /// The number of open file descriptors below `limit`.
+    pub(super) fn count_fds_below(&self, limit: u64) -> u64 {
+        let limit = i32::try_from(limit).unwrap_or(i32::MAX);
+        u64::try_from(self.handles.range(..limit).count()).unwrap()
+    }
+
+    pub(crate) fn has_unlocated_fds(&self) -> bool {
+        !self.unlocated_fds.is_empty()
+    }
+
+    /// Records that the file descriptors opened by the current foreign call were opened at `span`.
+    pub(crate) fn locate_opened_fds(&mut self, span: Span) {
+        for fd in self.unlocated_fds.drain(..) {
+            self.opened_at.insert(fd, span);
+        }
+    }
+
+    /// The file descriptors that are still open, in order, with where they were opened. The
+    /// standard streams are only included if the program reopened them.
+    pub(crate) fn open_fds(&self) -> Vec<(i32, Span)> {
+        self.handles.keys().filter_map(|fd| Some((*fd, *self.opened_at.get(fd)?))).collect()
+    }
+
+    /// Adds a host file that was opened by the Windows file APIs, which share this table with
+    /// the Unix ones. Returns its file descriptor.
+    pub(crate) fn insert_host_file(
+        &mut self,
+        file: File,
+        readable: bool,
+        writable: bool,
+        is_append: bool,
+    ) -> i32 {
+        self.insert_fd(Box::new(FileHandle {
+            file,
+            readable,
+            writable,
+            description: Rc::new(()),
+            is_append: Rc::new(Cell::new(is_append)),
+            is_nonblock: Rc::new(Cell::new(false)),
+            directory: None,
+        }))
+    }
+
+    /// Reads from a file descriptor for the Windows file APIs, which never block. Returns `None`
+    /// if there is no such file descriptor.
+    pub(crate) fn read_fd<'tcx>(
+        &mut self,
+        fd: i32,
+        communicate_allowed: bool,
+        bytes: &mut [u8],
+    ) -> InterpResult<'tcx, Option<io::Result<usize>>> {
+        let Some(file_descriptor) = self.handles.get_mut(&fd) else {
+            return Ok(None);
+        };
+        file_descriptor.read(communicate_allowed, bytes).map(Some)
+    }
+
+    /// Writes to a file descriptor for the Windows file APIs, like `read_fd`.
+    pub(crate) fn write_fd<'tcx>(
+        &self,
+        fd: i32,
+        communicate_allowed: bool,
+        bytes: &[u8],
+    ) -> InterpResult<'tcx, Option<io::Result<usize>>> {
+        let Some(file_descriptor) = self.handles.get(&fd) else {
+            return Ok(None);
+        };
+        file_descriptor.write(communicate_allowed, bytes).map(Some)
+    }
+
+    /// Remove `fd` from the interests of all epoll instances, after it was closed.
+    fn remove_epoll_interests(&self, fd: i32) {
+        for file_descriptor in self.handles.values() {
+            if let Some(epoll) = file_descriptor.as_epoll() {
+                epoll.remove_interest(fd);
+            }
+        }
+    }
+
+    /// Closes a file descriptor for the Windows file APIs, like `read_fd`.
+    pub(crate) fn close_fd<'tcx>(
+        &mut self,
+        fd: i32,
+        communicate_allowed: bool,
+    ) -> InterpResult<'tcx, Option<io::Result<i32>>> {
+        let Some(file_descriptor) = self.handles.remove(&fd) else {
+            return Ok(None);
+        };
+        file_descriptor.close(communicate_allowed).map(Some)
+    }
+
+    /// The metadata of the host file behind a file descriptor, for the Windows file APIs, like
+    /// `read_fd`.
+    pub(crate) fn host_metadata<'tcx>(
+        &self,
+        fd: i32,
+    ) -> InterpResult<'tcx, Option<io::Result<std::fs::Metadata>>> {
+        let Some(file_descriptor) = self.handles.get(&fd) else {
+            return Ok(None);
+        };
+        Ok(Some(file_descriptor.as_file_handle()?.file.metadata()))
+    }
+}
+
+/// The `flock` lock on a file. All open file descriptions that hold it hold it in the same mode.
+#[derive(Debug, Default)]
+struct FileLock {
+    /// The `FileHandle::description`s of the holders. A holder that was closed no longer holds
+    /// the lock.
+    holders: Vec<Weak<()>>,
+    exclusive: bool,
+}
+
+impl FileLock {
+    /// Whether someone other than `owner` holds the lock in a way that prevents `owner` from
+    /// taking it.
+    fn conflicts(&self, owner: &Weak<()>, exclusive: bool) -> bool {
+        (exclusive || self.exclusive)
+            && self.holders.iter().any(|holder| holder.strong_count() > 0 && !holder.ptr_eq(owner))
+    }
+
+    /// Take the lock, or convert the lock `owner` already holds to the new mode. Returns `false`
+    /// if the lock is contended.
+    fn try_lock(&mut self, owner: &Weak<()>, exclusive: bool) -> bool {
+        self.holders.retain(|holder| holder.strong_count() > 0);
+        if self.conflicts(owner, exclusive) {
+            return false;
+        }
+        if !self.holders.iter().any(|holder| holder.ptr_eq(owner)) {
+            self.holders.push(owner.clone());
+        }
+        self.exclusive = exclusive;
+        true
+    }
+
+    fn unlock(&mut self, owner: &Weak<()>) {
+        self.holders.retain(|holder| holder.strong_count() > 0 && !holder.ptr_eq(owner));
+    }
+}
+
+impl FileHandle {
+    /// The host device and inode number, which tell whether two file descriptions refer to the
+    /// same file.
+    fn file_id<'tcx>(&self) -> InterpResult<'tcx, io::Result<(u64, u64)>> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            Ok(self.file.metadata().map(|metadata| (metadata.dev(), metadata.ino())))
+        }
+        #[cfg(not(unix))]
+        throw_unsup_format!("`flock` is only supported on Unix hosts")
+    }
+
+    /// Read into `bytes` from `offset` on, without moving the file offset. Returns how many bytes
+    /// were read, which is less than requested only at the end of the file.
+    pub(super) fn read_at<'tcx>(
+        &self,
+        bytes: &mut [u8],
+        offset: u64,
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileExt;
+            let mut read = 0;
+            while read < bytes.len() {
+                let position = offset.checked_add(u64::try_from(read).unwrap()).unwrap();
+                match self.file.read_at(&mut bytes[read..], position) {
+                    Ok(0) => break,
+                    Ok(n) => read = read.checked_add(n).unwrap(),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Ok(Err(e)),
+                }
+            }
+            Ok(Ok(read))
+        }
+        #[cfg(not(unix))]
+        throw_unsup_format!("reading at an offset is only supported on Unix hosts")
     }
 }
 
+/// An operation on a file descriptor that could not make progress. It is retried once the file
+/// description becomes readable, or writable for `Write`. `Flock` is retried once the lock is no
+/// longer contended.
+#[derive(Debug)]
+pub enum BlockedIoOp {
+    Read { buf: Pointer<Option<Provenance>>, count: u64 },
+    Write { buf: Pointer<Option<Provenance>>, count: u64 },
+    Accept { addr: Pointer<Option<Provenance>>, addrlen: Pointer<Option<Provenance>>, flags: i32 },
+    Flock { operation: i32, file: (u64, u64), owner: Weak<()>, exclusive: bool },
+}
+
+/// A thread that is blocked in an operation on a file descriptor, like a `read` from a socket
+/// without data.
+#[derive(Debug)]
+pub struct BlockedIo<'tcx> {
+    thread: ThreadId,
+    fd: i32,
+    op: BlockedIoOp,
+    /// The return place of the operation.
+    dest: PlaceTy<'tcx, Provenance>,
+}
+
 impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
 trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
     fn macos_stat_write_buf(
@@ -349,64 +890,438 @@ trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, '
         Ok(0)
     }
 
-    /// Function used when a handle is not found inside `FileHandler`. It returns `Ok(-1)`and sets
-    /// the last OS error to `libc::EBADF` (invalid file descriptor). This function uses
-    /// `T: From<i32>` instead of `i32` directly because some fs functions return different integer
-    /// types (like `read`, that returns an `i64`).
-    fn handle_not_found<T: From<i32>>(&mut self) -> InterpResult<'tcx, T> {
+    /// Read into `buffers`, in order, from `offset` on. Files never block, so neither does this.
+    fn pread_buffers(
+        &mut self,
+        fd: i32,
+        buffers: &[(Pointer<Option<Provenance>>, u64)],
+        offset: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_mut();
+
+        // Isolation check is done via `FileDescriptor` trait.
+
+        let offset = this.read_scalar(offset)?.to_int(offset.layout.size)?;
+        let Ok(offset) = u64::try_from(offset) else {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        };
+        for &(buf, len) in buffers {
+            this.check_ptr_access_align(
+                buf,
+                Size::from_bytes(len),
+                Align::ONE,
+                CheckInAllocMsg::MemoryAccessTest,
+            )?;
+        }
+        // We cap the number of read bytes like `read` does.
+        let count = buffers
+            .iter()
+            .map(|&(_, len)| len)
+            .sum::<u64>()
+            .min(u64::try_from(this.machine_isize_max()).unwrap())
+            .min(u64::try_from(isize::MAX).unwrap());
+        let communicate = this.machine.communicate();
+
+        let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) else {
+            return this.handle_not_found();
+        };
+        let mut bytes = vec![0; usize::try_from(count).unwrap()];
+        match file_descriptor.pread(communicate, &mut bytes, offset)? {
+            Ok(read_bytes) => {
+                // Scatter the bytes over the buffers, in order.
+                let mut rest = &bytes[..read_bytes];
+                for &(buf, len) in buffers {
+                    if rest.is_empty() {
+                        break;
+                    }
+                    let (chunk, tail) =
+                        rest.split_at(usize::try_from(len).unwrap().min(rest.len()));
+                    this.write_bytes_ptr(buf, chunk.iter().copied())?;
+                    rest = tail;
+                }
+                Ok(i64::try_from(read_bytes).unwrap())
+            }
+            Err(e) => {
+                this.set_last_error_from_io_error(e.kind())?;
+                Ok(-1)
+            }
+        }
+    }
+
+    /// Write the contents of `buffers`, in order, from `offset` on.
+    fn pwrite_buffers(
+        &mut self,
+        fd: i32,
+        buffers: &[(Pointer<Option<Provenance>>, u64)],
+        offset: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_mut();
+
+        // Isolation check is done via `FileDescriptor` trait.
+
+        let offset = this.read_scalar(offset)?.to_int(offset.layout.size)?;
+        let Ok(offset) = u64::try_from(offset) else {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        };
+        // Gather the bytes of all buffers.
+        let mut bytes = Vec::new();
+        for &(buf, len) in buffers {
+            bytes.extend_from_slice(
+                this.read_bytes_ptr_strip_provenance(buf, Size::from_bytes(len))?,
+            );
+        }
+        let communicate = this.machine.communicate();
+
+        let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) else {
+            return this.handle_not_found();
+        };
+        match file_descriptor.pwrite(communicate, &bytes, offset)? {
+            Ok(written_bytes) => Ok(i64::try_from(written_bytes).unwrap()),
+            Err(e) => {
+                this.set_last_error_from_io_error(e.kind())?;
+                Ok(-1)
+            }
+        }
+    }
+
+    /// Read the buffers of an `iovec` array with `iovcnt` entries. Returns `None` and sets the last
+    /// error to `EINVAL` if there are too many of them, or if they are too large in total.
+    fn read_iovecs(
+        &mut self,
+        iov: &OpTy<'tcx, Provenance>,
+        iovcnt: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Option<Vec<(Pointer<Option<Provenance>>, u64)>>> {
+        let this = self.eval_context_mut();
+
+        let iov = this.read_pointer(iov)?;
+        let iovcnt = this.read_scalar(iovcnt)?.to_i32()?;
+
+        // This is `IOV_MAX` on Linux and macOS.
+        let Some(iovcnt) = u64::try_from(iovcnt).ok().filter(|&iovcnt| iovcnt <= 1024) else {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(None);
+        };
+        let iovec_layout = this.libc_ty_layout("iovec")?;
+        let mut buffers = Vec::new();
+        let mut total: u64 = 0;
+        for i in 0..iovcnt {
+            let iovec = MPlaceTy::from_aligned_ptr(
+                iov.offset(iovec_layout.size * i, this)?,
+                iovec_layout,
+            );
+            let base = this.mplace_field_named(&iovec, "iov_base")?;
+            let base = this.read_pointer(&base.into())?;
+            let len = this.mplace_field_named(&iovec, "iov_len")?;
+            let len = this.read_scalar(&len.into())?.to_machine_usize(this)?;
+            total = total.saturating_add(len);
+            buffers.push((base, len));
+        }
+        // The total size has to fit into the return value.
+        if total > u64::try_from(this.machine_isize_max()).unwrap() {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(None);
+        }
+        Ok(Some(buffers))
+    }
+
+    /// Function used when a handle is not found inside `FileHandler`. It returns `Ok(-1)`and sets
+    /// the last OS error to `libc::EBADF` (invalid file descriptor). This function uses
+    /// `T: From<i32>` instead of `i32` directly because some fs functions return different integer
+    /// types (like `read`, that returns an `i64`).
+    fn handle_not_found<T: From<i32>>(&mut self) -> InterpResult<'tcx, T> {
+        let this = self.eval_context_mut();
+        let ebadf = this.eval_libc("EBADF")?;
+        this.set_last_error(ebadf)?;
+        Ok((-1).into())
+    }
+
+    fn file_type_to_d_type(
+        &mut self,
+        file_type: std::io::Result<FileType>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+        match file_type {
+            Ok(file_type) => {
+                if file_type.is_dir() {
+                    Ok(this.eval_libc("DT_DIR")?.to_u8()?.into())
+                } else if file_type.is_file() {
+                    Ok(this.eval_libc("DT_REG")?.to_u8()?.into())
+                } else if file_type.is_symlink() {
+                    Ok(this.eval_libc("DT_LNK")?.to_u8()?.into())
+                } else {
+                    // Certain file types are only supported when the host is a Unix system.
+                    // (i.e. devices and sockets) If it is, check those cases, if not, fall back to
+                    // DT_UNKNOWN sooner.
+
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::fs::FileTypeExt;
+                        if file_type.is_block_device() {
+                            Ok(this.eval_libc("DT_BLK")?.to_u8()?.into())
+                        } else if file_type.is_char_device() {
+                            Ok(this.eval_libc("DT_CHR")?.to_u8()?.into())
+                        } else if file_type.is_fifo() {
+                            Ok(this.eval_libc("DT_FIFO")?.to_u8()?.into())
+                        } else if file_type.is_socket() {
+                            Ok(this.eval_libc("DT_SOCK")?.to_u8()?.into())
+                        } else {
+                            Ok(this.eval_libc("DT_UNKNOWN")?.to_u8()?.into())
+                        }
+                    }
+                    #[cfg(not(unix))]
+                    Ok(this.eval_libc("DT_UNKNOWN")?.to_u8()?.into())
+                }
+            }
+            Err(e) =>
+                match e.raw_os_error() {
+                    Some(error) => Ok(error),
+                    None =>
+                        throw_unsup_format!(
+                            "the error {} couldn't be converted to a return value",
+                            e
+                        ),
+                },
+        }
+    }
+
+    /// Replaces the `XXXXXX` suffix of the template by random characters, and writes the result
+    /// back to the template, until `create` does not fail with `AlreadyExists` for it. Returns
+    /// `None` if this fails, in which case the last error is set.
+    fn create_unique_temp<T>(
+        &mut self,
+        name: &str,
+        template_op: &OpTy<'tcx, Provenance>,
+        mut create: impl FnMut(&Path) -> io::Result<T>,
+    ) -> InterpResult<'tcx, Option<T>> {
+        use rand::seq::SliceRandom;
+
+        // POSIX defines the template string.
+        const TEMPFILE_TEMPLATE_STR: &str = "XXXXXX";
+
+        let this = self.eval_context_mut();
+
+        // POSIX defines the maximum number of attempts before failure.
+        //
+        // `mkstemp()` relies on `tmpnam()` which in turn relies on `TMP_MAX`.
+        // POSIX says this about `TMP_MAX`:
+        // * Minimum number of unique filenames generated by `tmpnam()`.
+        // * Maximum number of times an application can call `tmpnam()` reliably.
+        //   * The value of `TMP_MAX` is at least 25.
+        //   * On XSI-conformant systems, the value of `TMP_MAX` is at least 10000.
+        // See <https://pubs.opengroup.org/onlinepubs/9699919799/basedefs/stdio.h.html>.
+        let max_attempts = this.eval_libc("TMP_MAX")?.to_u32()?;
+
+        // Get the raw bytes from the template -- as a byte slice, this is a string in the target
+        // (and the target is unix, so a byte slice is the right representation).
+        let template_ptr = this.read_pointer(template_op)?;
+        let mut template = this.eval_context_ref().read_c_str(template_ptr)?.to_owned();
+        let template_bytes = template.as_mut_slice();
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            this.reject_in_isolation(&format!("`{}`", name), reject_with)?;
+            let eacc = this.eval_libc("EACCES")?;
+            this.set_last_error(eacc)?;
+            return Ok(None);
+        }
+
+        // Get the bytes of the suffix we expect in _target_ encoding.
+        let suffix_bytes = TEMPFILE_TEMPLATE_STR.as_bytes();
+
+        // At this point we have one `&[u8]` that represents the template and one `&[u8]`
+        // that represents the expected suffix.
+
+        // Now we figure out the index of the slice we expect to contain the suffix.
+        let start_pos = template_bytes.len().saturating_sub(suffix_bytes.len());
+        let end_pos = template_bytes.len();
+        let last_six_char_bytes = &template_bytes[start_pos..end_pos];
+
+        // If we don't find the suffix, it is an error.
+        if last_six_char_bytes != suffix_bytes {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(None);
+        }
+
+        // At this point we know we have 6 ASCII 'X' characters as a suffix.
+
+        // From <https://github.com/lattera/glibc/blob/895ef79e04a953cac1493863bcae29ad85657ee1/sysdeps/posix/tempname.c#L175>
+        const SUBSTITUTIONS: &[char; 62] = &[
+            'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q',
+            'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H',
+            'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y',
+            'Z', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+        ];
+
+        // If the generated file already exists, we will try again `max_attempts` many times.
+        for _ in 0..max_attempts {
+            let rng = this.machine.rng.get_mut();
+
+            // Generate a random unique suffix.
+            let unique_suffix = SUBSTITUTIONS.choose_multiple(rng, 6).collect::<String>();
+
+            // Replace the template string with the random string.
+            template_bytes[start_pos..end_pos].copy_from_slice(unique_suffix.as_bytes());
+
+            // Write the modified template back to the passed in pointer to maintain POSIX semantics.
+            this.write_bytes_ptr(template_ptr, template_bytes.iter().copied())?;
+
+            // To actually create the file, turn this into a host OsString.
+            let p = bytes_to_os_str(template_bytes)?.to_os_string();
+
+            let possibly_unique = std::env::temp_dir().join::<PathBuf>(p.into());
+
+            match create(&possibly_unique) {
+                Ok(created) => return Ok(Some(created)),
+                Err(e) =>
+                    match e.kind() {
+                        // If the random file already exists, keep trying.
+                        ErrorKind::AlreadyExists => continue,
+                        // Any other errors are returned to the caller.
+                        _ => {
+                            // "On error, -1 is returned, and errno is set to
+                            // indicate the error"
+                            this.set_last_error_from_io_error(e.kind())?;
+                            return Ok(None);
+                        }
+                    },
+            }
+        }
+
+        // We ran out of attempts to create the file, return an error.
+        let eexist = this.eval_libc("EEXIST")?;
+        this.set_last_error(eexist)?;
+        Ok(None)
+    }
+
+    /// Sets the last error after a failed permission check. Missing permissions are reported as
+    /// `EACCES`, not as `EPERM` like other permission errors.
+    fn set_access_error(&mut self, kind: ErrorKind) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        if kind == ErrorKind::PermissionDenied {
+            let eacces = this.eval_libc("EACCES")?;
+            this.set_last_error(eacces)
+        } else {
+            this.set_last_error_from_io_error(kind)
+        }
+    }
+
+    /// Reads the access and modification times passed to `utimensat` or `futimens`. Returns
+    /// `None` if one of them is invalid.
+    fn read_timestamp_updates(
+        &mut self,
+        times_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Option<[TimestampUpdate; 2]>> {
+        let this = self.eval_context_mut();
+
+        let times_ptr = this.read_pointer(times_op)?;
+        // A null pointer sets both timestamps to the current time.
+        if this.ptr_is_null(times_ptr)? {
+            return Ok(Some([TimestampUpdate::Now; 2]));
+        }
+
+        let utime_now = this.eval_libc("UTIME_NOW")?.to_machine_isize(this)?;
+        let utime_omit = this.eval_libc("UTIME_OMIT")?.to_machine_isize(this)?;
+        let timespec_layout = this.libc_ty_layout("timespec")?;
+        let mut updates = [TimestampUpdate::Omit; 2];
+        for (i, update) in updates.iter_mut().enumerate() {
+            let ptr = times_ptr.offset(timespec_layout.size * u64::try_from(i).unwrap(), this)?;
+            let timespec = MPlaceTy::from_aligned_ptr(ptr, timespec_layout);
+            let sec = this.mplace_field_named(&timespec, "tv_sec")?;
+            let sec = this.read_scalar(&sec.into())?.to_machine_isize(this)?;
+            let nsec = this.mplace_field_named(&timespec, "tv_nsec")?;
+            let nsec = this.read_scalar(&nsec.into())?.to_machine_isize(this)?;
+            *update = if nsec == utime_now {
+                TimestampUpdate::Now
+            } else if nsec == utime_omit {
+                TimestampUpdate::Omit
+            } else if (0..1_000_000_000).contains(&nsec) {
+                TimestampUpdate::Set { sec, nsec }
+            } else {
+                return Ok(None);
+            };
+        }
+        Ok(Some(updates))
+    }
+
+    /// Resolves `path` relative to the directory `dirfd` refers to, like the `*at` functions do.
+    /// Absolute paths and `AT_FDCWD` leave the path as it is. Returns `None` if `dirfd` is not a
+    /// directory, in which case the last error is set.
+    fn resolve_at_path(&mut self, dirfd: i32, path: &Path) -> InterpResult<'tcx, Option<PathBuf>> {
         let this = self.eval_context_mut();
-        let ebadf = this.eval_libc("EBADF")?;
-        this.set_last_error(ebadf)?;
-        Ok((-1).into())
+
+        if path.is_absolute() || dirfd == this.eval_libc_i32("AT_FDCWD")? {
+            return Ok(Some(path.to_owned()));
+        }
+        let Some(file_descriptor) = this.machine.file_handler.handles.get(&dirfd) else {
+            this.handle_not_found::<i32>()?;
+            return Ok(None);
+        };
+        match file_descriptor.as_directory() {
+            Some(directory) => Ok(Some(directory.borrow().path.join(path))),
+            None => {
+                let enotdir = this.eval_libc("ENOTDIR")?;
+                this.set_last_error(enotdir)?;
+                Ok(None)
+            }
+        }
     }
 
-    fn file_type_to_d_type(
-        &mut self,
-        file_type: std::io::Result<FileType>,
-    ) -> InterpResult<'tcx, i32> {
+    /// Whether `fd` refers to a terminal. For the standard streams, `-Zmiri-isatty` decides that,
+    /// otherwise the host is asked if isolation is disabled. Returns `None` if `fd` is not open,
+    /// in which case the last error is set.
+    fn is_terminal(&mut self, fd: i32) -> InterpResult<'tcx, Option<bool>> {
         let this = self.eval_context_mut();
-        match file_type {
-            Ok(file_type) => {
-                if file_type.is_dir() {
-                    Ok(this.eval_libc("DT_DIR")?.to_u8()?.into())
-                } else if file_type.is_file() {
-                    Ok(this.eval_libc("DT_REG")?.to_u8()?.into())
-                } else if file_type.is_symlink() {
-                    Ok(this.eval_libc("DT_LNK")?.to_u8()?.into())
-                } else {
-                    // Certain file types are only supported when the host is a Unix system.
-                    // (i.e. devices and sockets) If it is, check those cases, if not, fall back to
-                    // DT_UNKNOWN sooner.
 
-                    #[cfg(unix)]
-                    {
-                        use std::os::unix::fs::FileTypeExt;
-                        if file_type.is_block_device() {
-                            Ok(this.eval_libc("DT_BLK")?.to_u8()?.into())
-                        } else if file_type.is_char_device() {
-                            Ok(this.eval_libc("DT_CHR")?.to_u8()?.into())
-                        } else if file_type.is_fifo() {
-                            Ok(this.eval_libc("DT_FIFO")?.to_u8()?.into())
-                        } else if file_type.is_socket() {
-                            Ok(this.eval_libc("DT_SOCK")?.to_u8()?.into())
-                        } else {
-                            Ok(this.eval_libc("DT_UNKNOWN")?.to_u8()?.into())
-                        }
-                    }
-                    #[cfg(not(unix))]
-                    Ok(this.eval_libc("DT_UNKNOWN")?.to_u8()?.into())
+        let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) else {
+            this.handle_not_found::<i32>()?;
+            return Ok(None);
+        };
+        if let Some(isatty) = this.machine.isatty.filter(|_| file_descriptor.is_std_stream()) {
+            return Ok(Some(isatty));
+        }
+        #[cfg(unix)]
+        if matches!(this.machine.isolated_op, IsolatedOp::Allow) {
+            if let Some(host_fd) = file_descriptor.as_unix_host_fd() {
+                // SAFETY: isatty has no preconditions
+                return Ok(Some(unsafe { libc::isatty(host_fd) } == 1));
+            }
+        }
+        // With isolation, and for the file descriptions that Miri models itself, there are no
+        // terminals.
+        Ok(Some(false))
+    }
+
+    /// The window size of the terminal `fd` refers to, as columns and rows. Without
+    /// `-Zmiri-terminal-size`, the host is asked if isolation is disabled, and the size defaults
+    /// to 80x24 otherwise.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn terminal_size(&self, fd: i32) -> (u16, u16) {
+        let this = self.eval_context_ref();
+
+        if let Some(size) = this.machine.terminal_size {
+            return size;
+        }
+        #[cfg(unix)]
+        if matches!(this.machine.isolated_op, IsolatedOp::Allow) {
+            let host_fd =
+                this.machine.file_handler.handles.get(&fd).and_then(|fd| fd.as_unix_host_fd());
+            if let Some(host_fd) = host_fd {
+                let mut winsize =
+                    libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+                // SAFETY: `TIOCGWINSZ` writes to the `winsize` that it is passed.
+                if unsafe { libc::ioctl(host_fd, libc::TIOCGWINSZ, &mut winsize) } == 0 {
+                    return (winsize.ws_col, winsize.ws_row);
                 }
             }
-            Err(e) =>
-                match e.raw_os_error() {
-                    Some(error) => Ok(error),
-                    None =>
-                        throw_unsup_format!(
-                            "the error {} couldn't be converted to a return value",
-                            e
-                        ),
-                },
         }
+        (80, 24)
     }
 }
 
@@ -417,12 +1332,14 @@ pub struct OpenDir {
     read_dir: ReadDir,
     /// The most recent entry returned by readdir()
     entry: Pointer<Option<Provenance>>,
+    /// Where the directory was opened, for `-Zmiri-check-fd-leaks`.
+    opened_at: Span,
 }
 
 impl OpenDir {
-    fn new(read_dir: ReadDir) -> Self {
+    fn new(read_dir: ReadDir, opened_at: Span) -> Self {
         // We rely on `free` being a NOP on null pointers.
-        Self { read_dir, entry: Pointer::null() }
+        Self { read_dir, entry: Pointer::null(), opened_at }
     }
 }
 
@@ -444,12 +1361,20 @@ pub struct DirHandler {
 
 impl DirHandler {
     #[allow(clippy::integer_arithmetic)]
-    fn insert_new(&mut self, read_dir: ReadDir) -> u64 {
+    fn insert_new(&mut self, read_dir: ReadDir, opened_at: Span) -> u64 {
         let id = self.next_id;
         self.next_id += 1;
-        self.streams.try_insert(id, OpenDir::new(read_dir)).unwrap();
+        self.streams.try_insert(id, OpenDir::new(read_dir, opened_at)).unwrap();
         id
     }
+
+    /// Where the directory streams that are still open were opened, in the order they were
+    /// opened in.
+    pub(crate) fn open_streams(&self) -> Vec<Span> {
+        let mut streams: Vec<_> = self.streams.iter().collect();
+        streams.sort_by_key(|(id, _)| **id);
+        streams.into_iter().map(|(_, open_dir)| open_dir.opened_at).collect()
+    }
 }
 
 impl Default for DirHandler {
@@ -478,18 +1403,112 @@ fn maybe_sync_file(
     }
 }
 
+/// Apply the permission bits of `mode` to `permissions`. Hosts other than Unix can only make
+/// files read-only, which they are if no write permission bit is set.
+fn permissions_with_mode(mut permissions: Permissions, mode: u32) -> Permissions {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(mode);
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(mode & 0o222 == 0);
+    permissions
+}
+
+/// How `utimensat` and `futimens` should change one of the timestamps of a file.
+#[derive(Clone, Copy, Debug)]
+enum TimestampUpdate {
+    /// `UTIME_NOW`: set it to the current time.
+    Now,
+    /// `UTIME_OMIT`: leave it as it is.
+    Omit,
+    Set { sec: i64, nsec: i64 },
+}
+
 impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
 pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
-    fn open(&mut self, args: &[OpTy<'tcx, Provenance>]) -> InterpResult<'tcx, i32> {
+    /// Decides whether the call to the file system function `link_name` fails, for
+    /// `-Zmiri-fs-fail-nth` and `-Zmiri-fs-fail-rate`. If so, the last error is set to
+    /// `-Zmiri-fs-fail-errno`, and the function should return -1 without doing anything.
+    fn inject_fs_failure(
+        &mut self,
+        link_name: Symbol,
+        args: &[OpTy<'tcx, Provenance>],
+    ) -> InterpResult<'tcx, bool> {
+        /// The functions that take a path.
+        const PATH_FUNCTIONS: &[&str] = &[
+            "open", "open64", "openat", "openat64", "stat", "stat64", "lstat", "lstat64",
+            "unlink", "unlinkat", "rename", "renameat", "mkdir", "mkdirat", "rmdir", "symlink",
+            "symlinkat", "readlink", "chmod", "fchmodat", "chown", "lchown", "access", "faccessat",
+            "utimensat", "mkstemp", "mkostemp",
+        ];
+        /// The functions that take a file descriptor as their first argument. Calls on file
+        /// descriptors that are not files or directories, like the standard streams, are not
+        /// counted.
+        const FD_FUNCTIONS: &[&str] = &[
+            "read", "write", "pread", "pwrite", "pread64", "pwrite64", "readv", "writev", "preadv",
+            "pwritev", "close", "lseek", "lseek64", "fstat", "fstat64", "fsync", "fdatasync",
+            "ftruncate", "ftruncate64", "fchmod", "fchown", "futimens", "flock", "sync_file_range",
+        ];
+
+        let this = self.eval_context_mut();
+        let fh = &this.machine.file_handler;
+        if fh.fs_fail_nth.is_none() && fh.fs_fail_rate == 0.0 {
+            return Ok(false);
+        }
+        // macOS has variants like `stat$INODE64`.
+        let name = link_name.as_str();
+        let name = name.split_once('$').map_or(name, |(name, _suffix)| name);
+        let counted = if PATH_FUNCTIONS.contains(&name) {
+            true
+        } else if FD_FUNCTIONS.contains(&name) && !args.is_empty() {
+            let fd = this.read_scalar(&args[0])?.to_i32()?;
+            fh.handles.get(&fd).map_or(false, |fd| fd.is_fs_object())
+        } else {
+            false
+        };
+        if !counted {
+            return Ok(false);
+        }
+
+        let fh = &mut this.machine.file_handler;
+        fh.fs_calls = fh.fs_calls.checked_add(1).unwrap();
+        let fail = fh.fs_fail_nth == Some(fh.fs_calls)
+            || (fh.fs_fail_rate > 0.0 && this.machine.rng.get_mut().gen_bool(fh.fs_fail_rate));
+        if !fail {
+            return Ok(false);
+        }
+        let errno_name = this.machine.file_handler.fs_fail_errno.clone();
+        let errno = this.eval_libc(&errno_name)?;
+        this.set_last_error(errno)?;
+        register_diagnostic(NonHaltingDiagnostic::InjectedFsFailure(link_name, errno_name));
+        Ok(true)
+    }
+
+    /// `open` is `openat` with `AT_FDCWD`. For `openat`, `args` are the arguments after `dirfd`.
+    fn open(
+        &mut self,
+        dirfd_op: Option<&OpTy<'tcx, Provenance>>,
+        args: &[OpTy<'tcx, Provenance>],
+    ) -> InterpResult<'tcx, i32> {
+        let name = if dirfd_op.is_some() { "openat" } else { "open" };
         if args.len() < 2 {
+            let extra = usize::from(dirfd_op.is_some());
             throw_ub_format!(
-                "incorrect number of arguments for `open`: got {}, expected at least 2",
-                args.len()
+                "incorrect number of arguments for `{}`: got {}, expected at least {}",
+                name,
+                args.len().checked_add(extra).unwrap(),
+                2usize.checked_add(extra).unwrap(),
             );
         }
 
         let this = self.eval_context_mut();
 
+        let dirfd = match dirfd_op {
+            Some(dirfd_op) => this.read_scalar(dirfd_op)?.to_i32()?,
+            None => this.eval_libc_i32("AT_FDCWD")?,
+        };
         let path = this.read_pointer(&args[0])?;
         let flag = this.read_scalar(&args[1])?.to_i32()?;
 
@@ -504,6 +1523,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         if (o_rdonly | o_wronly | o_rdwr) & !0b11 != 0 {
             throw_unsup_format!("access mode flags on this target are unsupported");
         }
+        let mut readable = true;
         let mut writable = true;
 
         // Now we check the access mode
@@ -513,6 +1533,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             writable = false;
             options.read(true);
         } else if access_mode == o_wronly {
+            readable = false;
             options.write(true);
         } else if access_mode == o_rdwr {
             options.read(true).write(true);
@@ -524,11 +1545,22 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         // options.
         let mut mirror = access_mode;
 
+        // `O_APPEND` is not passed on to the host, since `fcntl` can clear it again.
         let o_append = this.eval_libc_i32("O_APPEND")?;
-        if flag & o_append != 0 {
-            options.append(true);
+        let is_append = flag & o_append != 0;
+        if is_append {
             mirror |= o_append;
         }
+        let o_nonblock = this.eval_libc_i32("O_NONBLOCK")?;
+        let is_nonblock = flag & o_nonblock != 0;
+        if is_nonblock {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.custom_flags(libc::O_NONBLOCK);
+            }
+            mirror |= o_nonblock;
+        }
         let o_trunc = this.eval_libc_i32("O_TRUNC")?;
         if flag & o_trunc != 0 {
             options.truncate(true);
@@ -548,6 +1580,14 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 );
             };
 
+            // The permission bits of the new file are the mode without the bits in the umask.
+            // (The host process' umask still applies on top of that.)
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(mode & !this.machine.file_handler.umask);
+            }
+            #[cfg(not(unix))]
             if mode != 0o666 {
                 throw_unsup_format!("non-default mode 0o{:o} is not supported", mode);
             }
@@ -568,24 +1608,52 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             // (Technically we do not support *not* setting this flag, but we ignore that.)
             mirror |= o_cloexec;
         }
+        let o_directory = this.eval_libc_i32("O_DIRECTORY")?;
+        if flag & o_directory != 0 {
+            mirror |= o_directory;
+        }
         // If `flag` is not equal to `mirror`, there is an unsupported option enabled in `flag`,
         // then we throw an error.
         if flag != mirror {
             throw_unsup_format!("unsupported flags {:#x}", flag & !mirror);
         }
 
-        let path = this.read_path_from_c_str(path)?;
+        let path = this.read_path_from_c_str(path)?.into_owned();
 
         // Reject if isolation is enabled.
         if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
-            this.reject_in_isolation("`open`", reject_with)?;
+            this.reject_in_isolation(&format!("`{}`", name), reject_with)?;
             this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
             return Ok(-1);
         }
 
-        let fd = options.open(&path).map(|file| {
+        let Some(path) = this.resolve_at_path(dirfd, &path)? else {
+            return Ok(-1);
+        };
+        if !this.check_fd_limit(1)? {
+            return Ok(-1);
+        }
+        let fd = options.open(&path).and_then(|file| {
+            // Directories get their entries read on the host, so `getdents64` can return them.
+            let directory = if file.metadata()?.is_dir() {
+                let path = std::env::current_dir()?.join(&path);
+                let read_dir = read_dir(&path)?.peekable();
+                Some(Rc::new(RefCell::new(OpenDirectory { path, read_dir, position: 0 })))
+            } else if flag & o_directory != 0 {
+                return Err(ErrorKind::NotADirectory.into());
+            } else {
+                None
+            };
             let fh = &mut this.machine.file_handler;
-            fh.insert_fd(Box::new(FileHandle { file, writable }))
+            Ok(fh.insert_fd(Box::new(FileHandle {
+                file,
+                readable,
+                writable,
+                description: Rc::new(()),
+                is_append: Rc::new(Cell::new(is_append)),
+                is_nonblock: Rc::new(Cell::new(is_nonblock)),
+                directory,
+            })))
         });
 
         this.try_unwrap_io_result(fd)
@@ -603,6 +1671,122 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let fd = this.read_scalar(&args[0])?.to_i32()?;
         let cmd = this.read_scalar(&args[1])?.to_i32()?;
 
+        // These commands only use the state that Miri keeps itself, so they also work with
+        // isolation.
+        if cmd == this.eval_libc_i32("F_GETFL")? {
+            let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) else {
+                return this.handle_not_found();
+            };
+            let access_mode = match file_descriptor.access_mode() {
+                AccessMode::ReadOnly => "O_RDONLY",
+                AccessMode::WriteOnly => "O_WRONLY",
+                AccessMode::ReadWrite => "O_RDWR",
+            };
+            let mut flags = this.eval_libc_i32(access_mode)?;
+            if file_descriptor.nonblock_flag().map_or(false, Cell::get) {
+                flags |= this.eval_libc_i32("O_NONBLOCK")?;
+            }
+            if file_descriptor.append_flag().map_or(false, Cell::get) {
+                flags |= this.eval_libc_i32("O_APPEND")?;
+            }
+            return Ok(flags);
+        } else if cmd == this.eval_libc_i32("F_SETFL")? {
+            if args.len() < 3 {
+                throw_ub_format!(
+                    "incorrect number of arguments for fcntl with cmd=`F_SETFL`: got {}, expected at least 3",
+                    args.len()
+                );
+            }
+            let flags = this.read_scalar(&args[2])?.to_i32()?;
+            // The access mode and the file creation flags are ignored.
+            let ignored = this.eval_libc_i32("O_ACCMODE")?
+                | this.eval_libc_i32("O_CREAT")?
+                | this.eval_libc_i32("O_EXCL")?
+                | this.eval_libc_i32("O_NOCTTY")?
+                | this.eval_libc_i32("O_TRUNC")?;
+            let o_nonblock = this.eval_libc_i32("O_NONBLOCK")?;
+            let o_append = this.eval_libc_i32("O_APPEND")?;
+            if flags & !(ignored | o_nonblock | o_append) != 0 {
+                throw_unsup_format!("unsupported flags {:#x} for `fcntl(F_SETFL)`", flags);
+            }
+            let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) else {
+                return this.handle_not_found();
+            };
+            match file_descriptor.append_flag() {
+                Some(append) => append.set(flags & o_append != 0),
+                None if flags & o_append != 0 =>
+                    throw_unsup_format!("`O_APPEND` cannot be set on {}", file_descriptor.name()),
+                None => {}
+            }
+            let result = match file_descriptor.nonblock_flag() {
+                Some(nonblock) => {
+                    nonblock.set(flags & o_nonblock != 0);
+                    file_descriptor.set_host_nonblock(flags & o_nonblock != 0)
+                }
+                None if flags & o_nonblock != 0 =>
+                    throw_unsup_format!("`O_NONBLOCK` cannot be set on {}", file_descriptor.name()),
+                None => Ok(()),
+            };
+            return this.try_unwrap_io_result(result.map(|()| 0));
+        } else if cmd == this.eval_libc_i32("F_SETFD")? {
+            if args.len() < 3 {
+                throw_ub_format!(
+                    "incorrect number of arguments for fcntl with cmd=`F_SETFD`: got {}, expected at least 3",
+                    args.len()
+                );
+            }
+            let flags = this.read_scalar(&args[2])?.to_i32()?;
+            // `exec` is not supported, so we can ignore `FD_CLOEXEC`.
+            if flags & !this.eval_libc_i32("FD_CLOEXEC")? != 0 {
+                throw_unsup_format!("unsupported flags {:#x} for `fcntl(F_SETFD)`", flags);
+            }
+            if !this.machine.file_handler.handles.contains_key(&fd) {
+                return this.handle_not_found();
+            }
+            return Ok(0);
+        } else if cmd == this.eval_libc_i32("F_SETLK")?
+            || cmd == this.eval_libc_i32("F_SETLKW")?
+            || cmd == this.eval_libc_i32("F_GETLK")?
+        {
+            // Record locks belong to the process. Since the interpreted program is the only process
+            // using them, locks never conflict and `F_SETLKW` never has to wait. So we only check
+            // that the lock could be taken.
+            if args.len() < 3 {
+                throw_ub_format!(
+                    "incorrect number of arguments for fcntl with cmd=`F_SETLK`/`F_SETLKW`/`F_GETLK`: got {}, expected at least 3",
+                    args.len()
+                );
+            }
+            let lock = this.deref_operand(&args[2])?;
+            let l_type = this.mplace_field_named(&lock, "l_type")?;
+            let lock_type = this.read_scalar(&l_type.into())?.to_i16()?.into();
+            let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) else {
+                return this.handle_not_found();
+            };
+            let access_mode = file_descriptor.access_mode();
+            let is_getlk = cmd == this.eval_libc_i32("F_GETLK")?;
+            // Read locks need a readable file description, write locks a writable one.
+            let forbidden_access = if lock_type == this.eval_libc_i32("F_RDLCK")? {
+                Some(AccessMode::WriteOnly)
+            } else if lock_type == this.eval_libc_i32("F_WRLCK")? {
+                Some(AccessMode::ReadOnly)
+            } else if lock_type == this.eval_libc_i32("F_UNLCK")? && !is_getlk {
+                None
+            } else {
+                let einval = this.eval_libc("EINVAL")?;
+                this.set_last_error(einval)?;
+                return Ok(-1);
+            };
+            if is_getlk {
+                // No other process holds a lock.
+                let f_unlck = this.eval_libc_i32("F_UNLCK")?;
+                this.write_int(f_unlck, &l_type.into())?;
+            } else if forbidden_access == Some(access_mode) {
+                return this.handle_not_found();
+            }
+            return Ok(0);
+        }
+
         // Reject if isolation is enabled.
         if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
             this.reject_in_isolation("`fcntl`", reject_with)?;
@@ -636,6 +1820,16 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             }
             let start = this.read_scalar(&args[2])?.to_i32()?;
 
+            if this.machine.file_handler.handles.contains_key(&fd) {
+                if start < 0 {
+                    let einval = this.eval_libc("EINVAL")?;
+                    this.set_last_error(einval)?;
+                    return Ok(-1);
+                }
+                if !this.check_fd_limit_from(start)? {
+                    return Ok(-1);
+                }
+            }
             let fh = &mut this.machine.file_handler;
 
             match fh.handles.get_mut(&fd) {
@@ -654,7 +1848,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         } else if this.tcx.sess.target.os == "macos" && cmd == this.eval_libc_i32("F_FULLFSYNC")? {
             if let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) {
                 // FIXME: Support fullfsync for all FDs
-                let FileHandle { file, writable } = file_descriptor.as_file_handle()?;
+                let FileHandle { file, writable, .. } = file_descriptor.as_file_handle()?;
                 let io_result = maybe_sync_file(file, *writable, File::sync_all);
                 this.try_unwrap_io_result(io_result)
             } else {
@@ -665,6 +1859,82 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         }
     }
 
+    /// The new file descriptor refers to the same open file description as `old_fd`, so they share
+    /// the file offset and the file status flags.
+    fn dup(&mut self, old_fd_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let old_fd = this.read_scalar(old_fd_op)?.to_i32()?;
+
+        if this.machine.file_handler.handles.contains_key(&old_fd) && !this.check_fd_limit(1)? {
+            return Ok(-1);
+        }
+        let fh = &mut this.machine.file_handler;
+        match fh.handles.get_mut(&old_fd) {
+            Some(file_descriptor) =>
+                match file_descriptor.dup() {
+                    Ok(dup_fd) => Ok(fh.insert_fd(dup_fd)),
+                    Err(e) => {
+                        this.set_last_error_from_io_error(e.kind())?;
+                        Ok(-1)
+                    }
+                },
+            None => this.handle_not_found(),
+        }
+    }
+
+    /// `dup2` is `dup3` with `flags` set to `None`, which allows `old_fd` and `new_fd` to be equal.
+    fn dup3(
+        &mut self,
+        old_fd_op: &OpTy<'tcx, Provenance>,
+        new_fd_op: &OpTy<'tcx, Provenance>,
+        flags_op: Option<&OpTy<'tcx, Provenance>>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let old_fd = this.read_scalar(old_fd_op)?.to_i32()?;
+        let new_fd = this.read_scalar(new_fd_op)?.to_i32()?;
+        let flags = match flags_op {
+            Some(flags) => Some(this.read_scalar(flags)?.to_i32()?),
+            None => None,
+        };
+
+        if !this.machine.file_handler.handles.contains_key(&old_fd) || new_fd < 0 {
+            return this.handle_not_found();
+        }
+        if let Some(flags) = flags {
+            // `exec` is not supported, so we can ignore `O_CLOEXEC`.
+            if flags & !this.eval_libc_i32("O_CLOEXEC")? != 0 || old_fd == new_fd {
+                let einval = this.eval_libc("EINVAL")?;
+                this.set_last_error(einval)?;
+                return Ok(-1);
+            }
+        }
+        if old_fd == new_fd {
+            return Ok(new_fd);
+        }
+
+        let dup_fd = match this.machine.file_handler.handles.get_mut(&old_fd).unwrap().dup() {
+            Ok(dup_fd) => dup_fd,
+            Err(e) => {
+                this.set_last_error_from_io_error(e.kind())?;
+                return Ok(-1);
+            }
+        };
+        // If `new_fd` was open, it is closed silently. The host's standard streams stay open, we
+        // just stop using them.
+        this.machine.file_handler.unlocated_fds.push(new_fd);
+        if let Some(file_descriptor) = this.machine.file_handler.handles.insert(new_fd, dup_fd) {
+            this.machine.file_handler.remove_epoll_interests(new_fd);
+            if !file_descriptor.is_std_stream() {
+                let _ = file_descriptor.close(this.machine.communicate())?;
+                this.wake_blocked_io()?;
+            }
+        }
+
+        Ok(new_fd)
+    }
+
     fn close(&mut self, fd_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, Scalar<Provenance>> {
         let this = self.eval_context_mut();
 
@@ -672,7 +1942,11 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         Ok(Scalar::from_i32(
             if let Some(file_descriptor) = this.machine.file_handler.handles.remove(&fd) {
+                this.machine.file_handler.remove_epoll_interests(fd);
                 let result = file_descriptor.close(this.machine.communicate())?;
+                // Closing one end of a socket pair makes the other end readable, and closing the
+                // last file descriptor of an open file description releases its `flock` lock.
+                this.wake_blocked_io()?;
                 this.try_unwrap_io_result(result)?
             } else {
                 this.handle_not_found()?
@@ -685,7 +1959,8 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         fd: i32,
         buf: Pointer<Option<Provenance>>,
         count: u64,
-    ) -> InterpResult<'tcx, i64> {
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
 
         // Isolation check is done via `FileDescriptor` trait.
@@ -706,6 +1981,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             .min(u64::try_from(this.machine_isize_max()).unwrap())
             .min(u64::try_from(isize::MAX).unwrap());
         let communicate = this.machine.communicate();
+        let thread = this.get_active_thread();
 
         if let Some(file_descriptor) = this.machine.file_handler.handles.get_mut(&fd) {
             trace!("read: FD mapped to {:?}", file_descriptor);
@@ -717,21 +1993,33 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             // so this cannot fail.
             let result =
                 file_descriptor.read(communicate, &mut bytes)?.map(|c| i64::try_from(c).unwrap());
+            if let (Ok(_), Some(data_race)) = (&result, &this.machine.data_race) {
+                file_descriptor.acquire_clock(data_race, thread);
+            }
+            let is_blocking = file_descriptor.is_blocking();
 
-            match result {
+            let result = match result {
                 Ok(read_bytes) => {
                     // If reading to `bytes` did not fail, we write those bytes to the buffer.
                     this.write_bytes_ptr(buf, bytes)?;
-                    Ok(read_bytes)
+                    this.wake_blocked_io()?;
+                    read_bytes
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock && is_blocking => {
+                    // We write the result once there is something to read.
+                    this.block_on_fd(fd, BlockedIoOp::Read { buf, count }, dest);
+                    return Ok(());
                 }
                 Err(e) => {
                     this.set_last_error_from_io_error(e.kind())?;
-                    Ok(-1)
+                    -1
                 }
-            }
+            };
+            this.write_scalar(Scalar::from_machine_isize(result, this), dest)
         } else {
             trace!("read: FD not found");
-            this.handle_not_found()
+            let result = this.handle_not_found()?;
+            this.write_scalar(Scalar::from_machine_isize(result, this), dest)
         }
     }
 
@@ -740,7 +2028,8 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         fd: i32,
         buf: Pointer<Option<Provenance>>,
         count: u64,
-    ) -> InterpResult<'tcx, i64> {
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
 
         // Isolation check is done via `FileDescriptor` trait.
@@ -753,20 +2042,226 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             CheckInAllocMsg::MemoryAccessTest,
         )?;
 
-        // We cap the number of written bytes to the largest value that we are able to fit in both the
-        // host's and target's `isize`. This saves us from having to handle overflows later.
-        let count = count
-            .min(u64::try_from(this.machine_isize_max()).unwrap())
-            .min(u64::try_from(isize::MAX).unwrap());
-        let communicate = this.machine.communicate();
+        // We cap the number of written bytes to the largest value that we are able to fit in both the
+        // host's and target's `isize`. This saves us from having to handle overflows later.
+        let count = count
+            .min(u64::try_from(this.machine_isize_max()).unwrap())
+            .min(u64::try_from(isize::MAX).unwrap());
+        let communicate = this.machine.communicate();
+        let thread = this.get_active_thread();
+
+        if let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) {
+            let bytes = this.read_bytes_ptr_strip_provenance(buf, Size::from_bytes(count))?;
+            let result =
+                file_descriptor.write(communicate, bytes)?.map(|c| i64::try_from(c).unwrap());
+            if let (Ok(_), Some(data_race)) = (&result, &this.machine.data_race) {
+                file_descriptor.release_clock(data_race, thread);
+            }
+            let is_blocking = file_descriptor.is_blocking();
+
+            let result = match result {
+                Ok(written_bytes) => {
+                    this.wake_blocked_io()?;
+                    written_bytes
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock && is_blocking => {
+                    // We write the result once there is room for the data.
+                    this.block_on_fd(fd, BlockedIoOp::Write { buf, count }, dest);
+                    return Ok(());
+                }
+                Err(e) => {
+                    this.set_last_error_from_io_error(e.kind())?;
+                    -1
+                }
+            };
+            this.write_scalar(Scalar::from_machine_isize(result, this), dest)
+        } else {
+            let result = this.handle_not_found()?;
+            this.write_scalar(Scalar::from_machine_isize(result, this), dest)
+        }
+    }
+
+    fn pread(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        buf: &OpTy<'tcx, Provenance>,
+        count: &OpTy<'tcx, Provenance>,
+        offset: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_mut();
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let buf = this.read_pointer(buf)?;
+        let count = this.read_scalar(count)?.to_machine_usize(this)?;
+        this.pread_buffers(fd, &[(buf, count)], offset)
+    }
+
+    fn pwrite(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        buf: &OpTy<'tcx, Provenance>,
+        count: &OpTy<'tcx, Provenance>,
+        offset: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_mut();
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let buf = this.read_pointer(buf)?;
+        let count = this.read_scalar(count)?.to_machine_usize(this)?;
+        this.pwrite_buffers(fd, &[(buf, count)], offset)
+    }
+
+    fn preadv(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        iov: &OpTy<'tcx, Provenance>,
+        iovcnt: &OpTy<'tcx, Provenance>,
+        offset: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_mut();
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let Some(buffers) = this.read_iovecs(iov, iovcnt)? else {
+            return Ok(-1);
+        };
+        this.pread_buffers(fd, &buffers, offset)
+    }
+
+    fn pwritev(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        iov: &OpTy<'tcx, Provenance>,
+        iovcnt: &OpTy<'tcx, Provenance>,
+        offset: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_mut();
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let Some(buffers) = this.read_iovecs(iov, iovcnt)? else {
+            return Ok(-1);
+        };
+        this.pwrite_buffers(fd, &buffers, offset)
+    }
+
+    /// Block the active thread in `op` on `fd` until the file description becomes readable (or
+    /// writable, for writes), when the operation is retried.
+    fn block_on_fd(&mut self, fd: i32, op: BlockedIoOp, dest: &PlaceTy<'tcx, Provenance>) {
+        let this = self.eval_context_mut();
+        let thread = this.get_active_thread();
+        this.block_thread(thread);
+        let handles = &this.machine.file_handler.handles;
+        if let Some(deadline) = handles.get(&fd).and_then(|fd| fd.ready_at()) {
+            this.register_io_deadline(deadline);
+        }
+        this.machine.io_waiters.push(BlockedIo { thread, fd, op, dest: dest.clone() });
+    }
+
+    /// Wake up the threads that are blocked in an operation on a file descriptor or in
+    /// `epoll_wait`, if they can make progress now. This has to be called whenever the readiness of
+    /// a file description might have changed.
+    fn wake_blocked_io(&mut self) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        this.epoll_wake_waiters()?;
+        for waiter in std::mem::take(&mut this.machine.io_waiters) {
+            let fh = &this.machine.file_handler;
+            let ready = if let BlockedIoOp::Flock { file, owner, exclusive, .. } = &waiter.op {
+                !fh.file_locks.get(file).map_or(false, |lock| lock.conflicts(owner, *exclusive))
+            } else {
+                // If the file descriptor was closed, retrying the operation reports that.
+                fh.handles.get(&waiter.fd).map_or(true, |fd| {
+                    fd.epoll_readiness().map_or(true, |readiness| {
+                        if matches!(waiter.op, BlockedIoOp::Write { .. }) {
+                            readiness.writable
+                        } else {
+                            readiness.readable
+                        }
+                    })
+                })
+            };
+            if !ready {
+                this.machine.io_waiters.push(waiter);
+                continue;
+            }
+            // The operation is retried by a callback that runs on the waiting thread as soon as
+            // the active thread yields, since the return place lives in that thread's stack. If it
+            // still cannot make progress, it blocks again.
+            let BlockedIo { thread, fd, op, dest } = waiter;
+            this.register_timeout_callback(
+                thread,
+                Time::Monotonic(this.machine.clock.now()),
+                Box::new(move |this| {
+                    this.unblock_thread(thread);
+                    match op {
+                        BlockedIoOp::Read { buf, count } => this.read(fd, buf, count, &dest),
+                        BlockedIoOp::Write { buf, count } => this.write(fd, buf, count, &dest),
+                        BlockedIoOp::Accept { addr, addrlen, flags } =>
+                            this.accept4(fd, addr, addrlen, flags, &dest),
+                        BlockedIoOp::Flock { operation, .. } => this.flock(fd, operation, &dest),
+                    }
+                }),
+            );
+        }
+        Ok(())
+    }
+
+    /// Locks belong to the open file description, so they are shared with duplicates of `fd` but
+    /// separate opens of the same file contend for them, even in the same thread.
+    fn flock(
+        &mut self,
+        fd: i32,
+        operation: i32,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let lock_sh = this.eval_libc_i32("LOCK_SH")?;
+        let lock_ex = this.eval_libc_i32("LOCK_EX")?;
+        let lock_un = this.eval_libc_i32("LOCK_UN")?;
+        let lock_nb = this.eval_libc_i32("LOCK_NB")?;
 
-        if let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) {
-            let bytes = this.read_bytes_ptr_strip_provenance(buf, Size::from_bytes(count))?;
-            let result =
-                file_descriptor.write(communicate, bytes)?.map(|c| i64::try_from(c).unwrap());
-            this.try_unwrap_io_result(result)
+        let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) else {
+            let result = this.handle_not_found()?;
+            return this.write_scalar(Scalar::from_i32(result), dest);
+        };
+        let handle = file_descriptor.as_file_handle()?;
+        let owner = Rc::downgrade(&handle.description);
+        let file = match handle.file_id()? {
+            Ok(file) => file,
+            Err(e) => {
+                this.set_last_error_from_io_error(e.kind())?;
+                return this.write_scalar(Scalar::from_i32(-1), dest);
+            }
+        };
+
+        let exclusive = match operation & !lock_nb {
+            op if op == lock_sh => false,
+            op if op == lock_ex => true,
+            op if op == lock_un => {
+                if let Some(lock) = this.machine.file_handler.file_locks.get_mut(&file) {
+                    lock.unlock(&owner);
+                }
+                this.wake_blocked_io()?;
+                return this.write_scalar(Scalar::from_i32(0), dest);
+            }
+            _ => {
+                let einval = this.eval_libc("EINVAL")?;
+                this.set_last_error(einval)?;
+                return this.write_scalar(Scalar::from_i32(-1), dest);
+            }
+        };
+        let lock = this.machine.file_handler.file_locks.entry(file).or_default();
+        let was_exclusive = lock.exclusive;
+        if lock.try_lock(&owner, exclusive) {
+            if was_exclusive && !exclusive {
+                // Downgrading the lock lets other threads take a shared lock.
+                this.wake_blocked_io()?;
+            }
+            this.write_scalar(Scalar::from_i32(0), dest)
+        } else if operation & lock_nb != 0 {
+            let ewouldblock = this.eval_libc("EWOULDBLOCK")?;
+            this.set_last_error(ewouldblock)?;
+            this.write_scalar(Scalar::from_i32(-1), dest)
         } else {
-            this.handle_not_found()
+            // We write the result once the lock is released.
+            this.block_on_fd(fd, BlockedIoOp::Flock { operation, file, owner, exclusive }, dest);
+            Ok(())
         }
     }
 
@@ -825,9 +2320,45 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         this.try_unwrap_io_result(result)
     }
 
-    fn symlink(
+    fn unlinkat(
+        &mut self,
+        dirfd_op: &OpTy<'tcx, Provenance>,
+        path_op: &OpTy<'tcx, Provenance>,
+        flags_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let dirfd = this.read_scalar(dirfd_op)?.to_i32()?;
+        let path = this.read_path_from_c_str(this.read_pointer(path_op)?)?.into_owned();
+        let flags = this.read_scalar(flags_op)?.to_i32()?;
+
+        let at_removedir = this.eval_libc_i32("AT_REMOVEDIR")?;
+        if flags & !at_removedir != 0 {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            this.reject_in_isolation("`unlinkat`", reject_with)?;
+            this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
+            return Ok(-1);
+        }
+
+        let Some(path) = this.resolve_at_path(dirfd, &path)? else {
+            return Ok(-1);
+        };
+        // With `AT_REMOVEDIR`, this behaves like `rmdir`, otherwise like `unlink`.
+        let result = if flags & at_removedir != 0 { remove_dir(path) } else { remove_file(path) };
+        this.try_unwrap_io_result(result.map(|_| 0))
+    }
+
+    /// `symlink` is `symlinkat` with `AT_FDCWD`.
+    fn symlinkat(
         &mut self,
         target_op: &OpTy<'tcx, Provenance>,
+        newdirfd_op: Option<&OpTy<'tcx, Provenance>>,
         linkpath_op: &OpTy<'tcx, Provenance>,
     ) -> InterpResult<'tcx, i32> {
         #[cfg(unix)]
@@ -842,16 +2373,25 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         }
 
         let this = self.eval_context_mut();
-        let target = this.read_path_from_c_str(this.read_pointer(target_op)?)?;
-        let linkpath = this.read_path_from_c_str(this.read_pointer(linkpath_op)?)?;
+        let target = this.read_path_from_c_str(this.read_pointer(target_op)?)?.into_owned();
+        let newdirfd = match newdirfd_op {
+            Some(newdirfd_op) => this.read_scalar(newdirfd_op)?.to_i32()?,
+            None => this.eval_libc_i32("AT_FDCWD")?,
+        };
+        let linkpath = this.read_path_from_c_str(this.read_pointer(linkpath_op)?)?.into_owned();
 
         // Reject if isolation is enabled.
         if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
-            this.reject_in_isolation("`symlink`", reject_with)?;
+            let name = if newdirfd_op.is_some() { "`symlinkat`" } else { "`symlink`" };
+            this.reject_in_isolation(name, reject_with)?;
             this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
             return Ok(-1);
         }
 
+        // Only the location of the link is relative to `newdirfd`; the target is stored as is.
+        let Some(linkpath) = this.resolve_at_path(newdirfd, &linkpath)? else {
+            return Ok(-1);
+        };
         let result = create_link(&target, &linkpath).map(|_| 0);
         this.try_unwrap_io_result(result)
     }
@@ -980,37 +2520,16 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let path = this.read_path_from_c_str(pathname_ptr)?.into_owned();
         // See <https://github.com/rust-lang/rust/pull/79196> for a discussion of argument sizes.
         let empty_path_flag = flags & this.eval_libc("AT_EMPTY_PATH")?.to_i32()? != 0;
-        // We only support:
-        // * interpreting `path` as an absolute directory,
-        // * interpreting `path` as a path relative to `dirfd` when the latter is `AT_FDCWD`, or
-        // * interpreting `dirfd` as any file descriptor when `path` is empty and AT_EMPTY_PATH is
-        // set.
-        // Other behaviors cannot be tested from `libstd` and thus are not implemented. If you
-        // found this error, please open an issue reporting it.
-        if !(path.is_absolute()
-            || dirfd == this.eval_libc_i32("AT_FDCWD")?
-            || (path.as_os_str().is_empty() && empty_path_flag))
-        {
-            throw_unsup_format!(
-                "using statx is only supported with absolute paths, relative paths with the file \
-                descriptor `AT_FDCWD`, and empty paths with the `AT_EMPTY_PATH` flag set and any \
-                file descriptor"
-            )
-        }
-
         // Reject if isolation is enabled.
         if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
             this.reject_in_isolation("`statx`", reject_with)?;
-            let ecode = if path.is_absolute() || dirfd == this.eval_libc_i32("AT_FDCWD")? {
-                // since `path` is provided, either absolute or
-                // relative to CWD, `EACCES` is the most relevant.
-                this.eval_libc("EACCES")?
-            } else {
-                // `dirfd` is set to target file, and `path` is empty
-                // (or we would have hit the `throw_unsup_format`
-                // above). `EACCES` would violate the spec.
-                assert!(empty_path_flag);
+            let ecode = if path.as_os_str().is_empty() && empty_path_flag {
+                // `dirfd` is set to target file, and `path` is empty.
+                // `EACCES` would violate the spec.
                 this.eval_libc("EBADF")?
+            } else {
+                // since `path` is provided, `EACCES` is the most relevant.
+                this.eval_libc("EACCES")?
             };
             this.set_last_error(ecode)?;
             return Ok(-1);
@@ -1032,6 +2551,10 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let metadata = if path.as_os_str().is_empty() && empty_path_flag {
             FileMetadata::from_fd(this, dirfd)?
         } else {
+            // Otherwise `path` is resolved relative to `dirfd`, like for the other `*at` functions.
+            let Some(path) = this.resolve_at_path(dirfd, &path)? else {
+                return Ok(-1);
+            };
             FileMetadata::from_path(this, &path, follow_symlink)?
         };
         let metadata = match metadata {
@@ -1132,74 +2655,508 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(0)
     }
 
-    fn rename(
+    /// `rename` is `renameat` with `AT_FDCWD` for both directories.
+    fn renameat(
+        &mut self,
+        olddirfd_op: Option<&OpTy<'tcx, Provenance>>,
+        oldpath_op: &OpTy<'tcx, Provenance>,
+        newdirfd_op: Option<&OpTy<'tcx, Provenance>>,
+        newpath_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let at_fdcwd = this.eval_libc_i32("AT_FDCWD")?;
+        let olddirfd = match olddirfd_op {
+            Some(olddirfd_op) => this.read_scalar(olddirfd_op)?.to_i32()?,
+            None => at_fdcwd,
+        };
+        let newdirfd = match newdirfd_op {
+            Some(newdirfd_op) => this.read_scalar(newdirfd_op)?.to_i32()?,
+            None => at_fdcwd,
+        };
+
+        let oldpath_ptr = this.read_pointer(oldpath_op)?;
+        let newpath_ptr = this.read_pointer(newpath_op)?;
+
+        if this.ptr_is_null(oldpath_ptr)? || this.ptr_is_null(newpath_ptr)? {
+            let efault = this.eval_libc("EFAULT")?;
+            this.set_last_error(efault)?;
+            return Ok(-1);
+        }
+
+        let oldpath = this.read_path_from_c_str(oldpath_ptr)?.into_owned();
+        let newpath = this.read_path_from_c_str(newpath_ptr)?.into_owned();
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            let name = if olddirfd_op.is_some() { "`renameat`" } else { "`rename`" };
+            this.reject_in_isolation(name, reject_with)?;
+            this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
+            return Ok(-1);
+        }
+
+        let Some(oldpath) = this.resolve_at_path(olddirfd, &oldpath)? else {
+            return Ok(-1);
+        };
+        let Some(newpath) = this.resolve_at_path(newdirfd, &newpath)? else {
+            return Ok(-1);
+        };
+        let result = rename(oldpath, newpath).map(|_| 0);
+
+        this.try_unwrap_io_result(result)
+    }
+
+    /// `mkdir` is `mkdirat` with `AT_FDCWD`.
+    fn mkdirat(
+        &mut self,
+        dirfd_op: Option<&OpTy<'tcx, Provenance>>,
+        path_op: &OpTy<'tcx, Provenance>,
+        mode_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let dirfd = match dirfd_op {
+            Some(dirfd_op) => this.read_scalar(dirfd_op)?.to_i32()?,
+            None => this.eval_libc_i32("AT_FDCWD")?,
+        };
+
+        #[cfg_attr(not(unix), allow(unused_variables))]
+        let mode = if this.tcx.sess.target.os == "macos" {
+            u32::from(this.read_scalar(mode_op)?.to_u16()?)
+        } else {
+            this.read_scalar(mode_op)?.to_u32()?
+        };
+
+        let path = this.read_path_from_c_str(this.read_pointer(path_op)?)?.into_owned();
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            let name = if dirfd_op.is_some() { "`mkdirat`" } else { "`mkdir`" };
+            this.reject_in_isolation(name, reject_with)?;
+            this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
+            return Ok(-1);
+        }
+
+        let Some(path) = this.resolve_at_path(dirfd, &path)? else {
+            return Ok(-1);
+        };
+
+        #[cfg_attr(not(unix), allow(unused_mut))]
+        let mut builder = DirBuilder::new();
+
+        // If the host supports it, forward on the mode of the directory
+        // (i.e. permission bits and the sticky bit), with the bits in the umask cleared.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(mode & !this.machine.file_handler.umask);
+        }
+
+        let result = builder.create(path).map(|_| 0i32);
+
+        this.try_unwrap_io_result(result)
+    }
+
+    /// `chmod` is `fchmodat` with `AT_FDCWD` and no flags.
+    fn fchmodat(
+        &mut self,
+        dirfd_op: Option<&OpTy<'tcx, Provenance>>,
+        path_op: &OpTy<'tcx, Provenance>,
+        mode_op: &OpTy<'tcx, Provenance>,
+        flags_op: Option<&OpTy<'tcx, Provenance>>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let dirfd = match dirfd_op {
+            Some(dirfd_op) => this.read_scalar(dirfd_op)?.to_i32()?,
+            None => this.eval_libc_i32("AT_FDCWD")?,
+        };
+        let path = this.read_path_from_c_str(this.read_pointer(path_op)?)?.into_owned();
+        let mode = if this.tcx.sess.target.os == "macos" {
+            u32::from(this.read_scalar(mode_op)?.to_u16()?)
+        } else {
+            this.read_scalar(mode_op)?.to_u32()?
+        };
+        let flags = match flags_op {
+            Some(flags_op) => this.read_scalar(flags_op)?.to_i32()?,
+            None => 0,
+        };
+
+        // Changing the permissions of a symbolic link itself is not supported by Linux.
+        if flags != 0 {
+            throw_unsup_format!("unsupported flags {:#x} for `fchmodat`", flags);
+        }
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            let name = if dirfd_op.is_some() { "`fchmodat`" } else { "`chmod`" };
+            this.reject_in_isolation(name, reject_with)?;
+            this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
+            return Ok(-1);
+        }
+
+        let Some(path) = this.resolve_at_path(dirfd, &path)? else {
+            return Ok(-1);
+        };
+        let result = metadata(&path).and_then(|metadata| {
+            set_permissions(&path, permissions_with_mode(metadata.permissions(), mode))
+        });
+        this.try_unwrap_io_result(result.map(|_| 0i32))
+    }
+
+    /// `access` is `faccessat` with `AT_FDCWD` and no flags.
+    fn faccessat(
+        &mut self,
+        dirfd_op: Option<&OpTy<'tcx, Provenance>>,
+        path_op: &OpTy<'tcx, Provenance>,
+        mode_op: &OpTy<'tcx, Provenance>,
+        flags_op: Option<&OpTy<'tcx, Provenance>>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let dirfd = match dirfd_op {
+            Some(dirfd_op) => this.read_scalar(dirfd_op)?.to_i32()?,
+            None => this.eval_libc_i32("AT_FDCWD")?,
+        };
+        let path = this.read_path_from_c_str(this.read_pointer(path_op)?)?.into_owned();
+        let mode = this.read_scalar(mode_op)?.to_i32()?;
+        let flags = match flags_op {
+            Some(flags_op) => this.read_scalar(flags_op)?.to_i32()?,
+            None => 0,
+        };
+        let name = if dirfd_op.is_some() { "`faccessat`" } else { "`access`" };
+
+        let r_ok = this.eval_libc_i32("R_OK")?;
+        let w_ok = this.eval_libc_i32("W_OK")?;
+        let x_ok = this.eval_libc_i32("X_OK")?;
+        // `F_OK` is 0, checking for existence only.
+        let at_eaccess = this.eval_libc_i32("AT_EACCESS")?;
+        let at_symlink_nofollow = this.eval_libc_i32("AT_SYMLINK_NOFOLLOW")?;
+        if mode & !(r_ok | w_ok | x_ok) != 0 || flags & !(at_eaccess | at_symlink_nofollow) != 0 {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            this.reject_in_isolation(name, reject_with)?;
+            let eacc = this.eval_libc("EACCES")?;
+            this.set_last_error(eacc)?;
+            return Ok(-1);
+        }
+
+        let Some(path) = this.resolve_at_path(dirfd, &path)? else {
+            return Ok(-1);
+        };
+
+        #[cfg(unix)]
+        {
+            use std::ffi::CString;
+            use std::os::unix::ffi::OsStrExt;
+
+            let mut host_mode = libc::F_OK;
+            for (target, host) in [(r_ok, libc::R_OK), (w_ok, libc::W_OK), (x_ok, libc::X_OK)] {
+                if mode & target != 0 {
+                    host_mode |= host;
+                }
+            }
+            // The emulated process runs with the real IDs of the Miri process as its effective
+            // IDs, so `AT_EACCESS` makes no difference to the host either.
+            let mut host_flags = 0;
+            if flags & at_eaccess != 0 {
+                host_flags |= libc::AT_EACCESS;
+            }
+            if flags & at_symlink_nofollow != 0 {
+                host_flags |= libc::AT_SYMLINK_NOFOLLOW;
+            }
+
+            let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+            // SAFETY: `path` is a valid nul-terminated string.
+            let result =
+                unsafe { libc::faccessat(libc::AT_FDCWD, path.as_ptr(), host_mode, host_flags) };
+            if result != 0 {
+                this.set_access_error(io::Error::last_os_error().kind())?;
+            }
+            Ok(result)
+        }
+        #[cfg(not(unix))]
+        {
+            // Without Unix permissions, all we can tell is whether the file exists and is
+            // read-only.
+            if mode & x_ok != 0 {
+                throw_unsup_format!(
+                    "checking for execute permission with {} is only supported on unix hosts",
+                    name
+                );
+            }
+            let result = if flags & at_symlink_nofollow != 0 {
+                std::fs::symlink_metadata(&path)
+            } else {
+                metadata(&path)
+            };
+            let result = result.and_then(|metadata| {
+                if mode & w_ok != 0 && metadata.permissions().readonly() {
+                    Err(ErrorKind::PermissionDenied.into())
+                } else {
+                    Ok(())
+                }
+            });
+            match result {
+                Ok(()) => Ok(0),
+                Err(e) => {
+                    this.set_access_error(e.kind())?;
+                    Ok(-1)
+                }
+            }
+        }
+    }
+
+    fn fchmod(
+        &mut self,
+        fd_op: &OpTy<'tcx, Provenance>,
+        mode_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd_op)?.to_i32()?;
+        let mode = if this.tcx.sess.target.os == "macos" {
+            u32::from(this.read_scalar(mode_op)?.to_u16()?)
+        } else {
+            this.read_scalar(mode_op)?.to_u32()?
+        };
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            this.reject_in_isolation("`fchmod`", reject_with)?;
+            // Set error code as "EBADF" (bad fd)
+            return this.handle_not_found();
+        }
+
+        if let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) {
+            // FIXME: Support fchmod for all FDs
+            let FileHandle { file, .. } = file_descriptor.as_file_handle()?;
+            let result = file.metadata().and_then(|metadata| {
+                file.set_permissions(permissions_with_mode(metadata.permissions(), mode))
+            });
+            this.try_unwrap_io_result(result.map(|_| 0i32))
+        } else {
+            this.handle_not_found()
+        }
+    }
+
+    /// Implements `chown` and `lchown`. The latter does not follow a symbolic link at the end of
+    /// the path.
+    #[cfg_attr(not(unix), allow(unused))]
+    fn chown(
+        &mut self,
+        path_op: &OpTy<'tcx, Provenance>,
+        owner_op: &OpTy<'tcx, Provenance>,
+        group_op: &OpTy<'tcx, Provenance>,
+        follow_symlinks: bool,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let path = this.read_path_from_c_str(this.read_pointer(path_op)?)?;
+        // `uid_t` and `gid_t` are 32 bits wide on all supported targets; `-1` leaves the ID as is.
+        let owner = this.read_scalar(owner_op)?.to_u32()?;
+        let group = this.read_scalar(group_op)?.to_u32()?;
+        let name = if follow_symlinks { "`chown`" } else { "`lchown`" };
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            this.reject_in_isolation(name, reject_with)?;
+            this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
+            return Ok(-1);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::ffi::CString;
+            use std::os::unix::ffi::OsStrExt;
+
+            let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+            // SAFETY: `path` is a valid nul-terminated string.
+            let result = unsafe {
+                if follow_symlinks {
+                    libc::chown(path.as_ptr(), owner, group)
+                } else {
+                    libc::lchown(path.as_ptr(), owner, group)
+                }
+            };
+            if result != 0 {
+                this.set_last_error_from_io_error(io::Error::last_os_error().kind())?;
+            }
+            Ok(result)
+        }
+        #[cfg(not(unix))]
+        throw_unsup_format!("{} is only supported on unix hosts", name)
+    }
+
+    #[cfg_attr(not(unix), allow(unused))]
+    fn fchown(
         &mut self,
-        oldpath_op: &OpTy<'tcx, Provenance>,
-        newpath_op: &OpTy<'tcx, Provenance>,
+        fd_op: &OpTy<'tcx, Provenance>,
+        owner_op: &OpTy<'tcx, Provenance>,
+        group_op: &OpTy<'tcx, Provenance>,
     ) -> InterpResult<'tcx, i32> {
         let this = self.eval_context_mut();
 
-        let oldpath_ptr = this.read_pointer(oldpath_op)?;
-        let newpath_ptr = this.read_pointer(newpath_op)?;
-
-        if this.ptr_is_null(oldpath_ptr)? || this.ptr_is_null(newpath_ptr)? {
-            let efault = this.eval_libc("EFAULT")?;
-            this.set_last_error(efault)?;
-            return Ok(-1);
-        }
-
-        let oldpath = this.read_path_from_c_str(oldpath_ptr)?;
-        let newpath = this.read_path_from_c_str(newpath_ptr)?;
+        let fd = this.read_scalar(fd_op)?.to_i32()?;
+        let owner = this.read_scalar(owner_op)?.to_u32()?;
+        let group = this.read_scalar(group_op)?.to_u32()?;
 
         // Reject if isolation is enabled.
         if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
-            this.reject_in_isolation("`rename`", reject_with)?;
-            this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
-            return Ok(-1);
+            this.reject_in_isolation("`fchown`", reject_with)?;
+            // Set error code as "EBADF" (bad fd)
+            return this.handle_not_found();
         }
 
-        let result = rename(oldpath, newpath).map(|_| 0);
+        let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) else {
+            return this.handle_not_found();
+        };
+        #[cfg(unix)]
+        {
+            let Some(host_fd) = file_descriptor.as_unix_host_fd() else {
+                throw_unsup_format!("`fchown` is not supported on {}", file_descriptor.name());
+            };
+            // SAFETY: `fchown` has no memory safety preconditions.
+            let result = unsafe { libc::fchown(host_fd, owner, group) };
+            if result != 0 {
+                this.set_last_error_from_io_error(io::Error::last_os_error().kind())?;
+            }
+            Ok(result)
+        }
+        #[cfg(not(unix))]
+        throw_unsup_format!("`fchown` is only supported on unix hosts")
+    }
 
-        this.try_unwrap_io_result(result)
+    /// Sets the file mode creation mask and returns the previous one. This never fails, and works
+    /// with isolation enabled since the mask is part of the emulated process.
+    fn umask(
+        &mut self,
+        mask_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        // `mode_t` is `u16` on macOS and `u32` elsewhere.
+        let size = mask_op.layout.size;
+        let mask = u32::try_from(this.read_scalar(mask_op)?.to_bits(size)?).unwrap();
+        let old_mask = std::mem::replace(&mut this.machine.file_handler.umask, mask & 0o777);
+        Ok(Scalar::from_uint(old_mask, size))
     }
 
-    fn mkdir(
+    /// `futimens` is `utimensat` on the file descriptor itself, which is what a null path means
+    /// on Linux.
+    #[cfg_attr(not(unix), allow(unused))]
+    fn utimensat(
         &mut self,
-        path_op: &OpTy<'tcx, Provenance>,
-        mode_op: &OpTy<'tcx, Provenance>,
+        dirfd_op: &OpTy<'tcx, Provenance>,
+        path_op: Option<&OpTy<'tcx, Provenance>>,
+        times_op: &OpTy<'tcx, Provenance>,
+        flags_op: Option<&OpTy<'tcx, Provenance>>,
     ) -> InterpResult<'tcx, i32> {
         let this = self.eval_context_mut();
 
-        #[cfg_attr(not(unix), allow(unused_variables))]
-        let mode = if this.tcx.sess.target.os == "macos" {
-            u32::from(this.read_scalar(mode_op)?.to_u16()?)
-        } else {
-            this.read_scalar(mode_op)?.to_u32()?
+        let dirfd = this.read_scalar(dirfd_op)?.to_i32()?;
+        let path = match path_op {
+            Some(path_op) => {
+                let path_ptr = this.read_pointer(path_op)?;
+                if this.ptr_is_null(path_ptr)? {
+                    None
+                } else {
+                    Some(this.read_path_from_c_str(path_ptr)?.into_owned())
+                }
+            }
+            None => None,
         };
+        let flags = match flags_op {
+            Some(flags_op) => this.read_scalar(flags_op)?.to_i32()?,
+            None => 0,
+        };
+        let name = if path_op.is_some() { "`utimensat`" } else { "`futimens`" };
 
-        let path = this.read_path_from_c_str(this.read_pointer(path_op)?)?;
+        let at_symlink_nofollow = this.eval_libc_i32("AT_SYMLINK_NOFOLLOW")?;
+        if flags & !at_symlink_nofollow != 0 {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+        let Some(times) = this.read_timestamp_updates(times_op)? else {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        };
 
         // Reject if isolation is enabled.
         if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
-            this.reject_in_isolation("`mkdir`", reject_with)?;
-            this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
-            return Ok(-1);
+            this.reject_in_isolation(name, reject_with)?;
+            if path.is_some() {
+                this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
+                return Ok(-1);
+            }
+            // Set error code as "EBADF" (bad fd)
+            return this.handle_not_found();
         }
 
-        #[cfg_attr(not(unix), allow(unused_mut))]
-        let mut builder = DirBuilder::new();
-
-        // If the host supports it, forward on the mode of the directory
-        // (i.e. permission bits and the sticky bit)
         #[cfg(unix)]
         {
-            use std::os::unix::fs::DirBuilderExt;
-            builder.mode(mode);
-        }
+            use std::ffi::CString;
+            use std::os::unix::ffi::OsStrExt;
 
-        let result = builder.create(path).map(|_| 0i32);
+            // A relative path is resolved against the directory on the host, and `AT_FDCWD`
+            // refers to the working directory of the host process, which is ours as well.
+            let host_fd = if path.is_some() && dirfd == this.eval_libc_i32("AT_FDCWD")? {
+                libc::AT_FDCWD
+            } else {
+                match this.machine.file_handler.handles.get(&dirfd) {
+                    Some(file_descriptor) =>
+                        match file_descriptor.as_unix_host_fd() {
+                            Some(host_fd) => host_fd,
+                            None =>
+                                throw_unsup_format!(
+                                    "{} is not supported on {}",
+                                    name,
+                                    file_descriptor.name()
+                                ),
+                        },
+                    None => return this.handle_not_found(),
+                }
+            };
 
-        this.try_unwrap_io_result(result)
+            let times = times.map(|update| {
+                // SAFETY: `timespec` is plain old data.
+                let mut timespec: libc::timespec = unsafe { std::mem::zeroed() };
+                match update {
+                    TimestampUpdate::Now => timespec.tv_nsec = libc::UTIME_NOW,
+                    TimestampUpdate::Omit => timespec.tv_nsec = libc::UTIME_OMIT,
+                    TimestampUpdate::Set { sec, nsec } => {
+                        timespec.tv_sec = sec.try_into().unwrap();
+                        timespec.tv_nsec = nsec.try_into().unwrap();
+                    }
+                }
+                timespec
+            });
+            // SAFETY: `path` is a valid nul-terminated string, and `times` holds two timestamps.
+            let result = match path {
+                Some(path) => {
+                    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+                    let mut host_flags = 0;
+                    if flags & at_symlink_nofollow != 0 {
+                        host_flags |= libc::AT_SYMLINK_NOFOLLOW;
+                    }
+                    unsafe { libc::utimensat(host_fd, path.as_ptr(), times.as_ptr(), host_flags) }
+                }
+                None => unsafe { libc::futimens(host_fd, times.as_ptr()) },
+            };
+            if result != 0 {
+                this.set_last_error_from_io_error(io::Error::last_os_error().kind())?;
+            }
+            Ok(result)
+        }
+        #[cfg(not(unix))]
+        throw_unsup_format!("{} is only supported on unix hosts", name)
     }
 
     fn rmdir(&mut self, path_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
@@ -1239,7 +3196,8 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         match result {
             Ok(dir_iter) => {
-                let id = this.machine.dir_handler.insert_new(dir_iter);
+                let span = this.machine.current_span(*this.tcx).get();
+                let id = this.machine.dir_handler.insert_new(dir_iter, span);
 
                 // The libc API for opendir says that this method returns a pointer to an opaque
                 // structure, but we are returning an ID number. Thus, pass it as a scalar of
@@ -1343,6 +3301,114 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(Scalar::from_maybe_pointer(entry, this))
     }
 
+    fn linux_getdents64(
+        &mut self,
+        fd_op: &OpTy<'tcx, Provenance>,
+        dirp_op: &OpTy<'tcx, Provenance>,
+        count_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_mut();
+
+        this.assert_target_os("linux", "getdents64");
+
+        let fd = this.read_scalar(fd_op)?.to_i32()?;
+        let dirp = this.read_pointer(dirp_op)?;
+        let count = this.read_scalar(count_op)?.to_machine_usize(this)?;
+
+        let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) else {
+            return this.handle_not_found();
+        };
+        let Some(directory) = file_descriptor.as_directory().cloned() else {
+            let enotdir = this.eval_libc("ENOTDIR")?;
+            this.set_last_error(enotdir)?;
+            return Ok(-1);
+        };
+
+        // The records have the layout of `dirent64`, but only take up as much space as the name
+        // needs, rounded up to a multiple of 8 bytes:
+        // pub struct linux_dirent64 {
+        //     pub d_ino: ino64_t,
+        //     pub d_off: off64_t,
+        //     pub d_reclen: c_ushort,
+        //     pub d_type: c_uchar,
+        //     pub d_name: [c_char],
+        // }
+        let dirent64_layout = this.libc_ty_layout("dirent64")?;
+        let d_name_offset = dirent64_layout.fields.offset(4 /* d_name */);
+        let record_align = Align::from_bytes(8).unwrap();
+
+        // Take as many entries as fit into the buffer.
+        let mut entries = Vec::new();
+        let mut size = Size::ZERO;
+        let mut directory = directory.borrow_mut();
+        loop {
+            let name = match directory.read_dir.peek() {
+                None => break,
+                // Not a Path as there are no separators!
+                Some(Ok(dir_entry)) => dir_entry.file_name(),
+                // Report the error once the entries before it have been returned.
+                Some(Err(_)) if !entries.is_empty() => break,
+                Some(Err(_)) => {
+                    let e = directory.read_dir.next().unwrap().unwrap_err();
+                    this.set_last_error_from_io_error(e.kind())?;
+                    return Ok(-1);
+                }
+            };
+            // The name is followed by a NUL terminator.
+            let name_len = u64::try_from(os_str_to_bytes(&name)?.len()).unwrap();
+            let reclen = d_name_offset
+                .checked_add(Size::from_bytes(name_len.checked_add(1).unwrap()), this)
+                .unwrap()
+                .align_to(record_align);
+            let end = size.checked_add(reclen, this).unwrap();
+            if end.bytes() > count {
+                if entries.is_empty() {
+                    // Not even the first entry fits.
+                    let einval = this.eval_libc("EINVAL")?;
+                    this.set_last_error(einval)?;
+                    return Ok(-1);
+                }
+                break;
+            }
+
+            let dir_entry = directory.read_dir.next().unwrap().unwrap();
+            // If the host is a Unix system, fill in the inode number with its real value.
+            // If not, use 0 as a fallback value.
+            #[cfg(unix)]
+            let ino = std::os::unix::fs::DirEntryExt::ino(&dir_entry);
+            #[cfg(not(unix))]
+            let ino = 0u64;
+            directory.position = directory.position.checked_add(1).unwrap();
+            entries.push((name, ino, dir_entry.file_type(), directory.position, size, reclen));
+            size = end;
+        }
+        drop(directory);
+
+        for (name, ino, file_type, position, offset, reclen) in entries {
+            let file_type = this.file_type_to_d_type(file_type)?;
+            let entry = dirp.offset(offset, this)?;
+            this.write_int_fields_named(
+                &[
+                    ("d_ino", ino.into()),
+                    // The offset of the next entry, which is all `lseek` would need.
+                    ("d_off", position.into()),
+                    ("d_reclen", reclen.bytes().into()),
+                    ("d_type", file_type.into()),
+                ],
+                &MPlaceTy::from_aligned_ptr(entry, dirent64_layout),
+            )?;
+
+            // Write the name, its NUL terminator, and zeros up to the next record.
+            let name_bytes = os_str_to_bytes(&name)?;
+            let bytes = name_bytes.iter().copied().chain(iter::repeat(0));
+            let len = (reclen - d_name_offset).bytes().try_into().unwrap();
+            let name_ptr = entry.offset(d_name_offset, this)?;
+            this.write_bytes_ptr(name_ptr, bytes.take(len))?;
+        }
+
+        Ok(size.bytes().try_into().unwrap())
+    }
+
     fn macos_readdir_r(
         &mut self,
         dirp_op: &OpTy<'tcx, Provenance>,
@@ -1484,7 +3550,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(Scalar::from_i32(
             if let Some(file_descriptor) = this.machine.file_handler.handles.get_mut(&fd) {
                 // FIXME: Support ftruncate64 for all FDs
-                let FileHandle { file, writable } = file_descriptor.as_file_handle()?;
+                let FileHandle { file, writable, .. } = file_descriptor.as_file_handle()?;
                 if *writable {
                     if let Ok(length) = length.try_into() {
                         let result = file.set_len(length);
@@ -1525,7 +3591,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         if let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) {
             // FIXME: Support fsync for all FDs
-            let FileHandle { file, writable } = file_descriptor.as_file_handle()?;
+            let FileHandle { file, writable, .. } = file_descriptor.as_file_handle()?;
             let io_result = maybe_sync_file(file, *writable, File::sync_all);
             this.try_unwrap_io_result(io_result)
         } else {
@@ -1547,7 +3613,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         if let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) {
             // FIXME: Support fdatasync for all FDs
-            let FileHandle { file, writable } = file_descriptor.as_file_handle()?;
+            let FileHandle { file, writable, .. } = file_descriptor.as_file_handle()?;
             let io_result = maybe_sync_file(file, *writable, File::sync_data);
             this.try_unwrap_io_result(io_result)
         } else {
@@ -1592,7 +3658,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         if let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) {
             // FIXME: Support sync_data_range for all FDs
-            let FileHandle { file, writable } = file_descriptor.as_file_handle()?;
+            let FileHandle { file, writable, .. } = file_descriptor.as_file_handle()?;
             let io_result = maybe_sync_file(file, *writable, File::sync_data);
             Ok(Scalar::from_i32(this.try_unwrap_io_result(io_result)?))
         } else {
@@ -1645,36 +3711,68 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         }
     }
 
-    #[cfg_attr(not(unix), allow(unused))]
     fn isatty(&mut self, miri_fd: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
         let this = self.eval_context_mut();
-        #[cfg(unix)]
-        if matches!(this.machine.isolated_op, IsolatedOp::Allow) {
-            let miri_fd = this.read_scalar(miri_fd)?.to_i32()?;
-            if let Some(host_fd) =
-                this.machine.file_handler.handles.get(&miri_fd).and_then(|fd| fd.as_unix_host_fd())
-            {
-                // "returns 1 if fd is an open file descriptor referring to a terminal;
-                // otherwise 0 is returned, and errno is set to indicate the error"
-                // SAFETY: isatty has no preconditions
-                let is_tty = unsafe { libc::isatty(host_fd) };
-                if is_tty == 0 {
-                    let errno = std::io::Error::last_os_error()
-                        .raw_os_error()
-                        .map(Scalar::from_i32)
-                        .unwrap();
-                    this.set_last_error(errno)?;
+        let miri_fd = this.read_scalar(miri_fd)?.to_i32()?;
+
+        // "returns 1 if fd is an open file descriptor referring to a terminal;
+        // otherwise 0 is returned, and errno is set to indicate the error"
+        match this.is_terminal(miri_fd)? {
+            Some(true) => Ok(1),
+            Some(false) => {
+                let enotty = this.eval_libc("ENOTTY")?;
+                this.set_last_error(enotty)?;
+                Ok(0)
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn ioctl(&mut self, args: &[OpTy<'tcx, Provenance>]) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        if args.len() < 2 {
+            throw_ub_format!(
+                "incorrect number of arguments for `ioctl`: got {}, expected at least 2",
+                args.len()
+            );
+        }
+        let fd = this.read_scalar(&args[0])?.to_i32()?;
+        // The type of the request differs between the C libraries, so we accept any integer.
+        let request = this.read_scalar(&args[1])?.to_bits(args[1].layout.size)?;
+
+        if request == u128::from(this.eval_libc("TIOCGWINSZ")?.to_machine_usize(this)?) {
+            if args.len() < 3 {
+                throw_ub_format!(
+                    "incorrect number of arguments for `ioctl` with `TIOCGWINSZ`: got {}, expected at least 3",
+                    args.len()
+                );
+            }
+            let winsize_ptr = this.read_pointer(&args[2])?;
+            match this.is_terminal(fd)? {
+                Some(true) => {}
+                Some(false) => {
+                    let enotty = this.eval_libc("ENOTTY")?;
+                    this.set_last_error(enotty)?;
+                    return Ok(-1);
                 }
-                return Ok(is_tty);
+                None => return Ok(-1),
             }
+            let (cols, rows) = this.terminal_size(fd);
+            let winsize = MPlaceTy::from_aligned_ptr(winsize_ptr, this.libc_ty_layout("winsize")?);
+            this.write_int_fields_named(
+                &[
+                    ("ws_row", rows.into()),
+                    ("ws_col", cols.into()),
+                    ("ws_xpixel", 0),
+                    ("ws_ypixel", 0),
+                ],
+                &winsize,
+            )?;
+            Ok(0)
+        } else {
+            throw_unsup_format!("unsupported `ioctl` request {:#x}", request);
         }
-        // We are attemping to use a Unix interface on a non-Unix platform, or we are on a Unix
-        // platform and the passed file descriptor is not open, or isolation is enabled
-        // FIXME: It should be possible to emulate this at least on Windows by using
-        // GetConsoleMode.
-        let enotty = this.eval_libc("ENOTTY")?;
-        this.set_last_error(enotty)?;
-        Ok(0)
     }
 
     fn realpath(
@@ -1696,9 +3794,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             return Ok(Scalar::from_machine_usize(0, this));
         }
 
+        // This resolves symbolic links through the host.
         let result = std::fs::canonicalize(pathname);
         match result {
             Ok(resolved) => {
+                #[cfg(windows)]
+                let resolved = strip_verbatim_prefix(resolved);
                 let path_max = this
                     .eval_libc_i32("PATH_MAX")?
                     .try_into()
@@ -1713,6 +3814,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                     // the resolved pathname, and returns a pointer to this buffer.  The
                     // caller should deallocate this buffer using free(3)."
                     // <https://man7.org/linux/man-pages/man3/realpath.3.html>
+                    // The buffer still has to fit the path and the null terminator.
+                    if u64::try_from(resolved.as_os_str().len()).unwrap() >= path_max {
+                        let enametoolong = this.eval_libc("ENAMETOOLONG")?;
+                        this.set_last_error(enametoolong)?;
+                        return Ok(Scalar::from_machine_usize(0, this));
+                    }
                     this.alloc_path_as_c_str(&resolved, MiriMemoryKind::C.into())?
                 } else {
                     let (wrote_path, _) =
@@ -1737,73 +3844,42 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             }
         }
     }
-    fn mkstemp(&mut self, template_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
-        use rand::seq::SliceRandom;
-
-        // POSIX defines the template string.
-        const TEMPFILE_TEMPLATE_STR: &str = "XXXXXX";
 
+    /// `mkstemp` is `mkostemp` without additional flags.
+    fn mkostemp(
+        &mut self,
+        template_op: &OpTy<'tcx, Provenance>,
+        flags_op: Option<&OpTy<'tcx, Provenance>>,
+    ) -> InterpResult<'tcx, i32> {
         let this = self.eval_context_mut();
-        this.assert_target_os_is_unix("mkstemp");
-
-        // POSIX defines the maximum number of attempts before failure.
-        //
-        // `mkstemp()` relies on `tmpnam()` which in turn relies on `TMP_MAX`.
-        // POSIX says this about `TMP_MAX`:
-        // * Minimum number of unique filenames generated by `tmpnam()`.
-        // * Maximum number of times an application can call `tmpnam()` reliably.
-        //   * The value of `TMP_MAX` is at least 25.
-        //   * On XSI-conformant systems, the value of `TMP_MAX` is at least 10000.
-        // See <https://pubs.opengroup.org/onlinepubs/9699919799/basedefs/stdio.h.html>.
-        let max_attempts = this.eval_libc("TMP_MAX")?.to_u32()?;
-
-        // Get the raw bytes from the template -- as a byte slice, this is a string in the target
-        // (and the target is unix, so a byte slice is the right representation).
-        let template_ptr = this.read_pointer(template_op)?;
-        let mut template = this.eval_context_ref().read_c_str(template_ptr)?.to_owned();
-        let template_bytes = template.as_mut_slice();
-
-        // Reject if isolation is enabled.
-        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
-            this.reject_in_isolation("`mkstemp`", reject_with)?;
-            let eacc = this.eval_libc("EACCES")?;
-            this.set_last_error(eacc)?;
-            return Ok(-1);
-        }
-
-        // Get the bytes of the suffix we expect in _target_ encoding.
-        let suffix_bytes = TEMPFILE_TEMPLATE_STR.as_bytes();
-
-        // At this point we have one `&[u8]` that represents the template and one `&[u8]`
-        // that represents the expected suffix.
+        let name = if flags_op.is_some() { "mkostemp" } else { "mkstemp" };
+        this.assert_target_os_is_unix(name);
 
-        // Now we figure out the index of the slice we expect to contain the suffix.
-        let start_pos = template_bytes.len().saturating_sub(suffix_bytes.len());
-        let end_pos = template_bytes.len();
-        let last_six_char_bytes = &template_bytes[start_pos..end_pos];
-
-        // If we don't find the suffix, it is an error.
-        if last_six_char_bytes != suffix_bytes {
-            let einval = this.eval_libc("EINVAL")?;
-            this.set_last_error(einval)?;
-            return Ok(-1);
-        }
-
-        // At this point we know we have 6 ASCII 'X' characters as a suffix.
-
-        // From <https://github.com/lattera/glibc/blob/895ef79e04a953cac1493863bcae29ad85657ee1/sysdeps/posix/tempname.c#L175>
-        const SUBSTITUTIONS: &[char; 62] = &[
-            'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q',
-            'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H',
-            'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y',
-            'Z', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
-        ];
+        let flags = match flags_op {
+            Some(flags_op) => this.read_scalar(flags_op)?.to_i32()?,
+            None => 0,
+        };
 
         // The file is opened with specific options, which Rust does not expose in a portable way.
         // So we use specific APIs depending on the host OS.
         let mut fopts = OpenOptions::new();
         fopts.read(true).write(true).create_new(true);
 
+        let mut mirror = 0;
+        let o_append = this.eval_libc_i32("O_APPEND")?;
+        let is_append = flags & o_append != 0;
+        if is_append {
+            mirror |= o_append;
+        }
+        let o_cloexec = this.eval_libc_i32("O_CLOEXEC")?;
+        if flags & o_cloexec != 0 {
+            // We do not need to do anything for this flag because `std` already sets it.
+            mirror |= o_cloexec;
+        }
+        if flags != mirror {
+            throw_unsup_format!("unsupported flags {:#x} for `{}`", flags & !mirror, name);
+        }
+
         #[cfg(unix)]
         {
             use std::os::unix::fs::OpenOptionsExt;
@@ -1818,54 +3894,67 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             fopts.share_mode(0);
         }
 
-        // If the generated file already exists, we will try again `max_attempts` many times.
-        for _ in 0..max_attempts {
-            let rng = this.machine.rng.get_mut();
-
-            // Generate a random unique suffix.
-            let unique_suffix = SUBSTITUTIONS.choose_multiple(rng, 6).collect::<String>();
-
-            // Replace the template string with the random string.
-            template_bytes[start_pos..end_pos].copy_from_slice(unique_suffix.as_bytes());
-
-            // Write the modified template back to the passed in pointer to maintain POSIX semantics.
-            this.write_bytes_ptr(template_ptr, template_bytes.iter().copied())?;
-
-            // To actually open the file, turn this into a host OsString.
-            let p = bytes_to_os_str(template_bytes)?.to_os_string();
-
-            let possibly_unique = std::env::temp_dir().join::<PathBuf>(p.into());
-
-            let file = fopts.open(&possibly_unique);
+        if !this.check_fd_limit(1)? {
+            return Ok(-1);
+        }
+        let Some(file) = this.create_unique_temp(name, template_op, |path| fopts.open(path))? else {
+            return Ok(-1);
+        };
+        let fh = &mut this.machine.file_handler;
+        let fd = fh.insert_fd(Box::new(FileHandle {
+            file,
+            readable: true,
+            writable: true,
+            description: Rc::new(()),
+            is_append: Rc::new(Cell::new(is_append)),
+            is_nonblock: Rc::new(Cell::new(false)),
+            directory: None,
+        }));
+        Ok(fd)
+    }
+
+    fn mkdtemp(
+        &mut self,
+        template_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+        this.assert_target_os_is_unix("mkdtemp");
 
-            match file {
-                Ok(f) => {
-                    let fh = &mut this.machine.file_handler;
-                    let fd = fh.insert_fd(Box::new(FileHandle { file: f, writable: true }));
-                    return Ok(fd);
-                }
-                Err(e) =>
-                    match e.kind() {
-                        // If the random file already exists, keep trying.
-                        ErrorKind::AlreadyExists => continue,
-                        // Any other errors are returned to the caller.
-                        _ => {
-                            // "On error, -1 is returned, and errno is set to
-                            // indicate the error"
-                            this.set_last_error_from_io_error(e.kind())?;
-                            return Ok(-1);
-                        }
-                    },
-            }
+        #[cfg_attr(not(unix), allow(unused_mut))]
+        let mut builder = DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
         }
 
-        // We ran out of attempts to create the file, return an error.
-        let eexist = this.eval_libc("EEXIST")?;
-        this.set_last_error(eexist)?;
-        Ok(-1)
+        match this.create_unique_temp("mkdtemp", template_op, |path| builder.create(path))? {
+            Some(()) => Ok(this.read_scalar(template_op)?),
+            None => Ok(Scalar::null_ptr(this)),
+        }
     }
 }
 
+/// On Windows hosts, `canonicalize` returns verbatim paths such as `\\?\C:\foo`. Turn them back
+/// into the regular form, which is what a Unix target expects to see after separator conversion.
+#[cfg(windows)]
+fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+    use std::path::{Component, Prefix};
+
+    let mut components = path.components();
+    let prefix = match components.next() {
+        Some(Component::Prefix(prefix)) =>
+            match prefix.kind() {
+                Prefix::VerbatimDisk(disk) => format!("{}:", disk as char),
+                Prefix::VerbatimUNC(server, share) =>
+                    format!(r"\\{}\{}", server.to_string_lossy(), share.to_string_lossy()),
+                _ => return path,
+            },
+        _ => return path,
+    };
+    Path::new(&prefix).join(components.as_path())
+}
+
 /// Extracts the number of seconds and nanoseconds elapsed between `time` and the unix epoch when
 /// `time` is Ok. Returns `None` if `time` is an error. Fails if `time` happens before the unix
 /// epoch.
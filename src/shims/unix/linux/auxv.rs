@@ -0,0 +1,105 @@
+//! Emulation of `getauxval`. There is no real auxiliary vector; the values are made up to match
+//! the rest of the machine. The hardware capabilities are derived from the target features, so
+//! they can be configured with `-C target-feature`, just like `cfg!(target_feature)`.
+
+use rustc_span::Symbol;
+
+use crate::*;
+use shims::unix::user::EvalContextExt as _;
+
+/// The bits of `AT_HWCAP` and `AT_HWCAP2` on AArch64 that stand for the given target features.
+const AARCH64_HWCAP: &[(&str, u64)] = &[
+    ("neon", 1 << 0 | 1 << 1),
+    ("aes", 1 << 3 | 1 << 4),
+    ("sha2", 1 << 5 | 1 << 6),
+    ("crc", 1 << 7),
+    ("lse", 1 << 8),
+    ("fp16", 1 << 9 | 1 << 10),
+    ("rdm", 1 << 12),
+    ("jsconv", 1 << 13),
+    ("fcma", 1 << 14),
+    ("rcpc", 1 << 15),
+    ("dpb", 1 << 16),
+    ("sha3", 1 << 17 | 1 << 21),
+    ("sm4", 1 << 18 | 1 << 19),
+    ("dotprod", 1 << 20),
+    ("sve", 1 << 22),
+    ("fhm", 1 << 23),
+    ("dit", 1 << 24),
+    ("rcpc2", 1 << 26),
+    ("flagm", 1 << 27),
+    ("ssbs", 1 << 28),
+    ("sb", 1 << 29),
+    ("paca", 1 << 30),
+    ("pacg", 1 << 31),
+];
+const AARCH64_HWCAP2: &[(&str, u64)] = &[
+    ("dpb2", 1 << 0),
+    ("sve2", 1 << 1),
+    ("sve2-aes", 1 << 2 | 1 << 3),
+    ("sve2-bitperm", 1 << 4),
+    ("sve2-sha3", 1 << 5),
+    ("sve2-sm4", 1 << 6),
+    ("frintts", 1 << 8),
+    ("i8mm", 1 << 13),
+    ("bf16", 1 << 14),
+    ("rand", 1 << 16),
+    ("bti", 1 << 17),
+    ("mte", 1 << 18),
+];
+
+/// On x86, `AT_HWCAP` is the EDX register of `cpuid` leaf 1.
+const X86_HWCAP: &[(&str, u64)] = &[("fxsr", 1 << 24), ("sse", 1 << 25), ("sse2", 1 << 26)];
+
+/// The frequency of the clock of `times`, in ticks per second.
+const CLOCK_TICKS: u64 = 100;
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// The bits of the hardware capabilities for the target features that are enabled.
+    fn hwcap(&self, bits: &[(&str, u64)]) -> u64 {
+        let this = self.eval_context_ref();
+        let features = &this.tcx.sess.target_features;
+        bits.iter()
+            .filter(|(feature, _)| features.contains(&Symbol::intern(feature)))
+            .fold(0, |hwcap, (_, bits)| hwcap | bits)
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Entries that are not supported, including those that point into memory, are missing:
+    /// for them, `ENOENT` is set and 0 is returned.
+    fn getauxval(&mut self, type_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, u64> {
+        let this = self.eval_context_mut();
+        this.assert_target_os("linux", "getauxval");
+
+        let type_ = this.read_scalar(type_op)?.to_machine_usize(this)?;
+
+        let (hwcap, hwcap2) = match this.tcx.sess.target.arch.as_ref() {
+            "aarch64" => (this.hwcap(AARCH64_HWCAP), this.hwcap(AARCH64_HWCAP2)),
+            "x86" | "x86_64" => (this.hwcap(X86_HWCAP), 0),
+            _ => (0, 0),
+        };
+        let entries = [
+            ("AT_PAGESZ", PAGE_SIZE),
+            ("AT_HWCAP", hwcap),
+            ("AT_HWCAP2", hwcap2),
+            ("AT_CLKTCK", CLOCK_TICKS),
+            ("AT_UID", this.getuid().into()),
+            ("AT_EUID", this.geteuid().into()),
+            ("AT_GID", this.getgid().into()),
+            ("AT_EGID", this.getegid().into()),
+            ("AT_SECURE", 0),
+        ];
+        for (name, value) in entries {
+            if this.eval_libc(name)?.to_machine_usize(this)? == type_ {
+                return Ok(value);
+            }
+        }
+
+        let enoent = this.eval_libc("ENOENT")?;
+        this.set_last_error(enoent)?;
+        Ok(0)
+    }
+}
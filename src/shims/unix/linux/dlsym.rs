@@ -11,6 +11,8 @@ impl Dlsym {
     pub fn from_str<'tcx>(name: &str) -> InterpResult<'tcx, Option<Dlsym>> {
         Ok(match name {
             "__pthread_get_minstack" => None,
+            // std only uses this to decide whether to call `res_init` after `getaddrinfo` failed.
+            "gnu_get_libc_version" => None,
             "getrandom" => None, // std falls back to syscall(SYS_getrandom, ...) when this is NULL.
             "statx" => None,     // std falls back to syscall(SYS_statx, ...) when this is NULL.
             _ => throw_unsup_format!("unsupported Linux dlsym: {}", name),
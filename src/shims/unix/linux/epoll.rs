@@ -0,0 +1,360 @@
+//! Emulation of `epoll`. Only the file descriptors that Miri models itself (eventfds, timerfds,
+//! signalfds, sockets and pipes) can be watched; regular files and the standard streams cannot.
+//! Interests are keyed by file descriptor, and are removed when their file descriptor is closed,
+//! even if a duplicate keeps the open file description alive (Linux keeps watching it then).
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::concurrency::thread::Time;
+use crate::*;
+use shims::unix::fs::FileDescriptor;
+use shims::unix::resource::EvalContextExt as _;
+
+/// The readiness of a file description, as seen by `epoll`.
+#[derive(Clone, Copy, Debug)]
+pub struct EpollReadiness {
+    pub readable: bool,
+    pub writable: bool,
+    /// Changes whenever the state of the file description changes. Edge-triggered interests report
+    /// an event again once this has changed.
+    pub generation: u64,
+}
+
+/// The registration of a file descriptor with an epoll instance.
+#[derive(Debug)]
+struct EpollInterest {
+    /// The events that were asked for, as given in `epoll_event::events`.
+    events: u32,
+    /// The user data that is returned with the events.
+    data: u64,
+    /// The generation of the file description when an event was last reported, for `EPOLLET`.
+    reported_generation: Option<u64>,
+    /// Whether an event was already reported for an `EPOLLONESHOT` interest.
+    disabled: bool,
+}
+
+/// An epoll instance, created by `epoll_create1`.
+#[derive(Debug, Default)]
+pub struct Epoll {
+    /// The watched file descriptors. This is shared between duplicates of the file descriptor.
+    interests: Rc<RefCell<BTreeMap<i32, EpollInterest>>>,
+}
+
+impl Epoll {
+    /// Stop watching `fd`, because it was closed. Otherwise a file descriptor that is opened later
+    /// and gets the same number would be watched in its place.
+    pub fn remove_interest(&self, fd: i32) {
+        self.interests.borrow_mut().remove(&fd);
+    }
+}
+
+impl FileDescriptor for Epoll {
+    fn name(&self) -> &'static str {
+        "epoll"
+    }
+
+    fn as_epoll(&self) -> Option<&Epoll> {
+        Some(self)
+    }
+
+    fn dup(&mut self) -> io::Result<Box<dyn FileDescriptor>> {
+        Ok(Box::new(Epoll { interests: Rc::clone(&self.interests) }))
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+    ) -> InterpResult<'tcx, io::Result<i32>> {
+        Ok(Ok(0))
+    }
+}
+
+/// A thread that is blocked in `epoll_wait`.
+#[derive(Debug)]
+pub struct EpollWaiter<'tcx> {
+    thread: ThreadId,
+    epfd: i32,
+    /// The first element of the `events` buffer.
+    events: MPlaceTy<'tcx, Provenance>,
+    maxevents: usize,
+    /// The return place of the `epoll_wait` call.
+    dest: PlaceTy<'tcx, Provenance>,
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Collect at most `maxevents` events of the epoll instance `epfd`. This marks them as
+    /// reported, so edge-triggered and one-shot interests do not report them again.
+    fn epoll_ready_events(
+        &mut self,
+        epfd: i32,
+        maxevents: usize,
+    ) -> InterpResult<'tcx, Vec<(u32, u64)>> {
+        let this = self.eval_context_mut();
+
+        // The flags are `c_int`s in libc, but `epoll_event::events` is a `u32`.
+        let epollin = this.eval_libc("EPOLLIN")?.to_u32()?;
+        let epollout = this.eval_libc("EPOLLOUT")?.to_u32()?;
+        let epollet = this.eval_libc("EPOLLET")?.to_u32()?;
+        let epolloneshot = this.eval_libc("EPOLLONESHOT")?.to_u32()?;
+
+        let handles = &this.machine.file_handler.handles;
+        let Some(epoll) = handles.get(&epfd).and_then(|fd| fd.as_epoll()) else {
+            // The epoll instance was closed while a thread was waiting on it.
+            return Ok(Vec::new());
+        };
+        let mut ready = Vec::new();
+        for (fd, interest) in epoll.interests.borrow_mut().iter_mut() {
+            if ready.len() == maxevents {
+                break;
+            }
+            if interest.disabled {
+                continue;
+            }
+            let Some(readiness) = handles.get(fd).and_then(|fd| fd.epoll_readiness()) else {
+                continue;
+            };
+            let mut events = 0;
+            if readiness.readable {
+                events |= interest.events & epollin;
+            }
+            if readiness.writable {
+                events |= interest.events & epollout;
+            }
+            if events == 0 {
+                continue;
+            }
+            if interest.events & epollet != 0 {
+                if interest.reported_generation == Some(readiness.generation) {
+                    continue;
+                }
+                interest.reported_generation = Some(readiness.generation);
+            }
+            if interest.events & epolloneshot != 0 {
+                interest.disabled = true;
+            }
+            ready.push((events, interest.data));
+        }
+        Ok(ready)
+    }
+
+    /// Write `ready` to the buffer starting at `events`.
+    fn epoll_write_events(
+        &mut self,
+        events: &MPlaceTy<'tcx, Provenance>,
+        ready: &[(u32, u64)],
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        for (i, &(event, data)) in ready.iter().enumerate() {
+            let offset = events.layout.size * u64::try_from(i).unwrap();
+            let place = events.offset(offset, events.layout, this)?;
+            this.write_int_fields_named(&[("events", event.into()), ("u64", data.into())], &place)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    fn epoll_create1(&mut self, flags: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let flags = this.read_scalar(flags)?.to_i32()?;
+
+        // `exec` is not supported, so we can ignore `EPOLL_CLOEXEC`.
+        if flags & !this.eval_libc_i32("EPOLL_CLOEXEC")? != 0 {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+        if !this.check_fd_limit(1)? {
+            return Ok(-1);
+        }
+
+        Ok(this.machine.file_handler.insert_fd(Box::new(Epoll::default())))
+    }
+
+    fn epoll_ctl(
+        &mut self,
+        epfd: &OpTy<'tcx, Provenance>,
+        op: &OpTy<'tcx, Provenance>,
+        fd: &OpTy<'tcx, Provenance>,
+        event: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let epfd = this.read_scalar(epfd)?.to_i32()?;
+        let op = this.read_scalar(op)?.to_i32()?;
+        let fd = this.read_scalar(fd)?.to_i32()?;
+
+        let epoll_ctl_add = this.eval_libc_i32("EPOLL_CTL_ADD")?;
+        let epoll_ctl_mod = this.eval_libc_i32("EPOLL_CTL_MOD")?;
+        let epoll_ctl_del = this.eval_libc_i32("EPOLL_CTL_DEL")?;
+
+        let handles = &this.machine.file_handler.handles;
+        let interests = match (handles.get(&epfd), handles.get(&fd)) {
+            (Some(epoll), Some(target)) =>
+                match epoll.as_epoll() {
+                    Some(epoll) if epfd != fd =>
+                        if target.epoll_readiness().is_some() {
+                            Ok(Rc::clone(&epoll.interests))
+                        } else {
+                            // Linux returns `EPERM` for files that do not support `epoll`.
+                            Err("EPERM")
+                        },
+                    _ => Err("EINVAL"),
+                },
+            _ => Err("EBADF"),
+        };
+        let interests = match interests {
+            Ok(interests) => interests,
+            Err(error) => {
+                let error = this.eval_libc(error)?;
+                this.set_last_error(error)?;
+                return Ok(-1);
+            }
+        };
+
+        let error = if op == epoll_ctl_add || op == epoll_ctl_mod {
+            let event = this.deref_operand(event)?;
+            let events = this.read_scalar(&this.mplace_field_named(&event, "events")?.into())?;
+            let data = this.read_scalar(&this.mplace_field_named(&event, "u64")?.into())?;
+            let interest = EpollInterest {
+                events: events.to_u32()?,
+                data: data.to_u64()?,
+                reported_generation: None,
+                disabled: false,
+            };
+            let mut interests = interests.borrow_mut();
+            match (interests.contains_key(&fd), op == epoll_ctl_add) {
+                (true, true) => Some("EEXIST"),
+                (false, false) => Some("ENOENT"),
+                _ => {
+                    interests.insert(fd, interest);
+                    None
+                }
+            }
+        } else if op == epoll_ctl_del {
+            interests.borrow_mut().remove(&fd).is_none().then_some("ENOENT")
+        } else {
+            Some("EINVAL")
+        };
+        if let Some(error) = error {
+            let error = this.eval_libc(error)?;
+            this.set_last_error(error)?;
+            return Ok(-1);
+        }
+
+        // A thread might be waiting for the file descriptor that was just added.
+        this.epoll_wake_waiters()?;
+        Ok(0)
+    }
+
+    fn epoll_wait(
+        &mut self,
+        epfd: &OpTy<'tcx, Provenance>,
+        events: &OpTy<'tcx, Provenance>,
+        maxevents: &OpTy<'tcx, Provenance>,
+        timeout: &OpTy<'tcx, Provenance>,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let epfd = this.read_scalar(epfd)?.to_i32()?;
+        let maxevents = this.read_scalar(maxevents)?.to_i32()?;
+        let timeout = this.read_scalar(timeout)?.to_i32()?;
+
+        let is_epoll =
+            this.machine.file_handler.handles.get(&epfd).map(|fd| fd.as_epoll().is_some());
+        let error = match (is_epoll, usize::try_from(maxevents)) {
+            (None, _) => Some("EBADF"),
+            (Some(false), _) | (_, Err(_) | Ok(0)) => Some("EINVAL"),
+            _ => None,
+        };
+        if let Some(error) = error {
+            let error = this.eval_libc(error)?;
+            this.set_last_error(error)?;
+            this.write_scalar(Scalar::from_i32(-1), dest)?;
+            return Ok(());
+        }
+        let maxevents = usize::try_from(maxevents).unwrap();
+        let events = this.deref_operand(events)?;
+
+        let ready = this.epoll_ready_events(epfd, maxevents)?;
+        if !ready.is_empty() || timeout == 0 {
+            this.epoll_write_events(&events, &ready)?;
+            this.write_scalar(Scalar::from_i32(ready.len().try_into().unwrap()), dest)?;
+            return Ok(());
+        }
+
+        // Nothing is ready yet, so we block the thread until an event arrives or the timeout
+        // expires. A negative timeout means waiting forever. Watched file descriptions that become
+        // ready by themselves wake up the thread when they do.
+        let handles = &this.machine.file_handler.handles;
+        let interests = handles.get(&epfd).and_then(|fd| fd.as_epoll()).unwrap().interests.borrow();
+        let deadlines: Vec<_> =
+            interests.keys().filter_map(|fd| handles.get(fd)?.ready_at()).collect();
+        drop(interests);
+        for deadline in deadlines {
+            this.register_io_deadline(deadline);
+        }
+        let timeout_time = match u64::try_from(timeout) {
+            Ok(timeout) => this.monotonic_deadline_after(Duration::from_millis(timeout)),
+            Err(_) => None,
+        };
+        let thread = this.get_active_thread();
+        let timeout_dest = dest.clone();
+        this.block_thread_until(
+            thread,
+            timeout_time,
+            Box::new(move |this| {
+                this.unblock_thread(thread);
+                this.machine.epoll_waiters.retain(|waiter| waiter.thread != thread);
+                this.write_scalar(Scalar::from_i32(0), &timeout_dest)?;
+                Ok(())
+            }),
+        );
+        this.machine.epoll_waiters.push(EpollWaiter {
+            thread,
+            epfd,
+            events,
+            maxevents,
+            dest: dest.clone(),
+        });
+        Ok(())
+    }
+
+    /// Wake up the threads blocked in `epoll_wait` whose epoll instance has events now. This has to
+    /// be called whenever the readiness of a file description might have changed.
+    fn epoll_wake_waiters(&mut self) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        for waiter in std::mem::take(&mut this.machine.epoll_waiters) {
+            let ready = this.epoll_ready_events(waiter.epfd, waiter.maxevents)?;
+            if ready.is_empty() {
+                this.machine.epoll_waiters.push(waiter);
+                continue;
+            }
+            // The results are written by a callback that runs on the waiting thread as soon as
+            // the active thread yields, since the return place lives in that thread's stack.
+            let EpollWaiter { thread, events, dest, .. } = waiter;
+            this.unregister_timeout_callback_if_exists(thread);
+            this.register_timeout_callback(
+                thread,
+                Time::Monotonic(this.machine.clock.now()),
+                Box::new(move |this| {
+                    this.unblock_thread(thread);
+                    this.epoll_write_events(&events, &ready)?;
+                    this.write_scalar(Scalar::from_i32(ready.len().try_into().unwrap()), &dest)?;
+                    Ok(())
+                }),
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+//! Emulation of `eventfd`.
+
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::rc::Rc;
+
+use rustc_target::abi::Endian;
+
+use crate::concurrency::data_race;
+use crate::concurrency::vector_clock::VClock;
+use crate::*;
+use shims::unix::fs::FileDescriptor;
+use shims::unix::linux::epoll::EpollReadiness;
+use shims::unix::resource::EvalContextExt as _;
+
+#[derive(Debug)]
+struct EventState {
+    counter: u64,
+    generation: u64,
+    is_semaphore: bool,
+    /// The clocks of all threads that increased the counter, so that increasing it
+    /// happens-before reading it.
+    clock: VClock,
+}
+
+/// An eventfd, created by `eventfd`.
+#[derive(Debug)]
+struct Event {
+    /// The state of the eventfd. This is shared between duplicates of the file descriptor.
+    state: Rc<RefCell<EventState>>,
+    /// Shared between duplicates of the file descriptor as well.
+    is_nonblock: Rc<Cell<bool>>,
+    /// The endianness of the target, which the counter is read and written in.
+    endian: Endian,
+}
+
+impl FileDescriptor for Event {
+    fn name(&self) -> &'static str {
+        "event"
+    }
+
+    fn nonblock_flag(&self) -> Option<&Cell<bool>> {
+        Some(&self.is_nonblock)
+    }
+
+    fn read<'tcx>(
+        &mut self,
+        _communicate_allowed: bool,
+        bytes: &mut [u8],
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        let Some(bytes) = bytes.get_mut(..8) else {
+            return Ok(Err(io::ErrorKind::InvalidInput.into()));
+        };
+        let mut state = self.state.borrow_mut();
+        if state.counter == 0 {
+            // Unless the eventfd is non-blocking, the thread waits for the counter to be increased.
+            return Ok(Err(io::ErrorKind::WouldBlock.into()));
+        }
+        let value = if state.is_semaphore { 1 } else { state.counter };
+        state.counter = state.counter.checked_sub(value).unwrap();
+        state.generation = state.generation.wrapping_add(1);
+        bytes.copy_from_slice(&match self.endian {
+            Endian::Little => value.to_le_bytes(),
+            Endian::Big => value.to_be_bytes(),
+        });
+        Ok(Ok(8))
+    }
+
+    fn write<'tcx>(
+        &self,
+        _communicate_allowed: bool,
+        bytes: &[u8],
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        let Some(bytes) = bytes.get(..8) else {
+            return Ok(Err(io::ErrorKind::InvalidInput.into()));
+        };
+        let bytes = bytes.try_into().unwrap();
+        let value = match self.endian {
+            Endian::Little => u64::from_le_bytes(bytes),
+            Endian::Big => u64::from_be_bytes(bytes),
+        };
+        if value == u64::MAX {
+            return Ok(Err(io::ErrorKind::InvalidInput.into()));
+        }
+        let mut state = self.state.borrow_mut();
+        // The counter can hold at most `u64::MAX - 1`.
+        let Some(counter) = state.counter.checked_add(value).filter(|&c| c != u64::MAX) else {
+            // Unless the eventfd is non-blocking, the thread waits for the counter to be read.
+            return Ok(Err(io::ErrorKind::WouldBlock.into()));
+        };
+        state.counter = counter;
+        state.generation = state.generation.wrapping_add(1);
+        Ok(Ok(8))
+    }
+
+    fn release_clock(&self, data_race: &data_race::GlobalState, thread: ThreadId) {
+        data_race.validate_lock_release_shared(&mut self.state.borrow_mut().clock, thread);
+    }
+
+    fn acquire_clock(&self, data_race: &data_race::GlobalState, thread: ThreadId) {
+        data_race.validate_lock_acquire(&self.state.borrow().clock, thread);
+    }
+
+    fn epoll_readiness(&self) -> Option<EpollReadiness> {
+        let state = self.state.borrow();
+        Some(EpollReadiness {
+            readable: state.counter > 0,
+            // Writing is possible as long as the counter can be increased by at least 1.
+            writable: state.counter.checked_add(1).map_or(false, |c| c != u64::MAX),
+            generation: state.generation,
+        })
+    }
+
+    fn dup(&mut self) -> io::Result<Box<dyn FileDescriptor>> {
+        Ok(Box::new(Event {
+            state: Rc::clone(&self.state),
+            is_nonblock: Rc::clone(&self.is_nonblock),
+            endian: self.endian,
+        }))
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+    ) -> InterpResult<'tcx, io::Result<i32>> {
+        Ok(Ok(0))
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    fn eventfd(
+        &mut self,
+        initval: &OpTy<'tcx, Provenance>,
+        flags: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let initval = this.read_scalar(initval)?.to_u32()?;
+        let flags = this.read_scalar(flags)?.to_i32()?;
+
+        let efd_cloexec = this.eval_libc_i32("EFD_CLOEXEC")?;
+        let efd_nonblock = this.eval_libc_i32("EFD_NONBLOCK")?;
+        let efd_semaphore = this.eval_libc_i32("EFD_SEMAPHORE")?;
+
+        if flags & !(efd_cloexec | efd_nonblock | efd_semaphore) != 0 {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+        if !this.check_fd_limit(1)? {
+            return Ok(-1);
+        }
+
+        let state = EventState {
+            counter: initval.into(),
+            generation: 0,
+            is_semaphore: flags & efd_semaphore != 0,
+            clock: VClock::default(),
+        };
+        let event = Event {
+            state: Rc::new(RefCell::new(state)),
+            is_nonblock: Rc::new(Cell::new(flags & efd_nonblock != 0)),
+            endian: this.tcx.data_layout.endian,
+        };
+        Ok(this.machine.file_handler.insert_fd(Box::new(event)))
+    }
+}
@@ -1,10 +1,18 @@
 use rustc_span::Symbol;
+use rustc_target::abi::Size;
 use rustc_target::spec::abi::Abi;
 
 use crate::*;
 use shims::foreign_items::EmulateByNameResult;
 use shims::unix::fs::EvalContextExt as _;
+use shims::unix::linux::auxv::EvalContextExt as _;
+use shims::unix::linux::epoll::EvalContextExt as _;
+use shims::unix::linux::eventfd::EvalContextExt as _;
 use shims::unix::linux::sync::futex;
+use shims::unix::linux::timerfd::EvalContextExt as _;
+use shims::unix::pipe::EvalContextExt as _;
+use shims::unix::resource::EvalContextExt as _;
+use shims::unix::socket::EvalContextExt as _;
 use shims::unix::sync::EvalContextExt as _;
 use shims::unix::thread::EvalContextExt as _;
 
@@ -42,6 +50,75 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let result = this.sync_file_range(fd, offset, nbytes, flags)?;
                 this.write_scalar(result, dest)?;
             }
+            "dup3" => {
+                let [old_fd, new_fd, flags] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.dup3(old_fd, new_fd, Some(flags))?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "pipe2" => {
+                let [pipefd, flags] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.pipe2(pipefd, Some(flags))?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "accept4" => {
+                let [fd, addr, addrlen, flags] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let fd = this.read_scalar(fd)?.to_i32()?;
+                let addr = this.read_pointer(addr)?;
+                let addrlen = this.read_pointer(addrlen)?;
+                let flags = this.read_scalar(flags)?.to_i32()?;
+                this.accept4(fd, addr, addrlen, flags, dest)?;
+            }
+
+            // epoll, eventfd, timerfd, signalfd
+            "epoll_create1" => {
+                let [flags] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.epoll_create1(flags)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "epoll_ctl" => {
+                let [epfd, op, fd, event] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.epoll_ctl(epfd, op, fd, event)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "epoll_wait" => {
+                let [epfd, events, maxevents, timeout] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.epoll_wait(epfd, events, maxevents, timeout, dest)?;
+            }
+            "eventfd" => {
+                let [initval, flags] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.eventfd(initval, flags)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "timerfd_create" => {
+                let [clockid, flags] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.timerfd_create(clockid, flags)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "timerfd_settime" => {
+                let [fd, flags, new_value, old_value] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.timerfd_settime(fd, flags, new_value, old_value)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "timerfd_gettime" => {
+                let [fd, curr_value] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.timerfd_gettime(fd, curr_value)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "signalfd" => {
+                let [fd, mask, flags] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.signalfd(fd, mask, flags)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
 
             // Time related shims
             "clock_gettime" => {
@@ -51,6 +128,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let result = this.clock_gettime(clk_id, tp)?;
                 this.write_scalar(result, dest)?;
             }
+            "clock_nanosleep" => {
+                let [clock_id, flags, req, rem] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.clock_nanosleep(clock_id, flags, req, rem)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
 
             // Threading
             "pthread_condattr_setclock" => {
@@ -72,6 +155,43 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                     this.pthread_setname_np(this.read_scalar(thread)?, this.read_scalar(name)?)?;
                 this.write_scalar(res, dest)?;
             }
+            "getauxval" => {
+                let [type_] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.getauxval(type_)?;
+                this.write_scalar(Scalar::from_machine_usize(result, this), dest)?;
+            }
+            "gettid" => {
+                let [] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.gettid()?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "prctl" => {
+                // We do not use `check_shim` here because `prctl` is variadic. The argument
+                // count is checked in `this.prctl()`.
+                this.check_abi_and_shim_symbol_clash(abi, Abi::C { unwind: false }, link_name)?;
+                let result = this.prctl(args)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+
+            // Resource limits
+            "getrlimit64" => {
+                let [resource, rlim] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.getrlimit(resource, rlim)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "setrlimit64" => {
+                let [resource, rlim] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.setrlimit(resource, rlim)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "prlimit64" => {
+                let [pid, resource, new_limit, old_limit] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.prlimit64(pid, resource, new_limit, old_limit)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
 
             // Dynamically invoked syscalls
             "syscall" => {
@@ -89,6 +209,15 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
                 let sys_futex = this.eval_libc("SYS_futex")?.to_machine_usize(this)?;
 
+                let sys_pread64 = this.eval_libc("SYS_pread64")?.to_machine_usize(this)?;
+                let sys_pwrite64 = this.eval_libc("SYS_pwrite64")?.to_machine_usize(this)?;
+                let sys_preadv = this.eval_libc("SYS_preadv")?.to_machine_usize(this)?;
+                let sys_pwritev = this.eval_libc("SYS_pwritev")?.to_machine_usize(this)?;
+
+                let sys_getdents64 = this.eval_libc("SYS_getdents64")?.to_machine_usize(this)?;
+
+                let sys_gettid = this.eval_libc("SYS_gettid")?.to_machine_usize(this)?;
+
                 if args.is_empty() {
                     throw_ub_format!(
                         "incorrect number of arguments for syscall: got 0, expected at least 1"
@@ -125,6 +254,61 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                     id if id == sys_futex => {
                         futex(this, &args[1..], dest)?;
                     }
+                    // Positioned I/O. We only support 64-bit targets, where the offset is passed
+                    // as a single argument.
+                    id if id == sys_pread64 || id == sys_pwrite64 => {
+                        // The first argument is the syscall id, so skip over it.
+                        let [_, fd, buf, count, offset, ..] = args else {
+                            throw_ub_format!(
+                                "incorrect number of arguments for `pread64`/`pwrite64` syscall: got {}, expected at least 5",
+                                args.len()
+                            );
+                        };
+                        if this.pointer_size().bits() != 64 {
+                            throw_unsup_format!("the `pread64` and `pwrite64` syscalls are only supported on 64-bit targets");
+                        }
+                        let result = if id == sys_pread64 {
+                            this.pread(fd, buf, count, offset)?
+                        } else {
+                            this.pwrite(fd, buf, count, offset)?
+                        };
+                        this.write_scalar(Scalar::from_machine_isize(result, this), dest)?;
+                    }
+                    id if id == sys_preadv || id == sys_pwritev => {
+                        // The first argument is the syscall id, so skip over it.
+                        let [_, fd, iov, iovcnt, offset, ..] = args else {
+                            throw_ub_format!(
+                                "incorrect number of arguments for `preadv`/`pwritev` syscall: got {}, expected at least 5",
+                                args.len()
+                            );
+                        };
+                        if this.pointer_size().bits() != 64 {
+                            throw_unsup_format!("the `preadv` and `pwritev` syscalls are only supported on 64-bit targets");
+                        }
+                        let result = if id == sys_preadv {
+                            this.preadv(fd, iov, iovcnt, offset)?
+                        } else {
+                            this.pwritev(fd, iov, iovcnt, offset)?
+                        };
+                        this.write_scalar(Scalar::from_machine_isize(result, this), dest)?;
+                    }
+                    // `getdents64` has no libc wrapper in older glibc versions.
+                    id if id == sys_getdents64 => {
+                        // The first argument is the syscall id, so skip over it.
+                        let [_, fd, dirp, count, ..] = args else {
+                            throw_ub_format!(
+                                "incorrect number of arguments for `getdents64` syscall: got {}, expected at least 4",
+                                args.len()
+                            );
+                        };
+                        let result = this.linux_getdents64(fd, dirp, count)?;
+                        this.write_scalar(Scalar::from_machine_isize(result, this), dest)?;
+                    }
+                    // `gettid` has no libc wrapper before glibc 2.30.
+                    id if id == sys_gettid => {
+                        let result = this.gettid()?;
+                        this.write_scalar(Scalar::from_machine_isize(result.into(), this), dest)?;
+                    }
                     id => {
                         this.handle_unsupported(format!("can't execute syscall with ID {}", id))?;
                         return Ok(EmulateByNameResult::AlreadyJumped);
@@ -141,13 +325,47 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             "sched_getaffinity" => {
                 let [pid, cpusetsize, mask] =
                     this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
-                this.read_scalar(pid)?.to_i32()?;
-                this.read_scalar(cpusetsize)?.to_machine_usize(this)?;
-                this.deref_operand(mask)?;
-                // FIXME: we just return an error; `num_cpus` then falls back to `sysconf`.
-                let einval = this.eval_libc("EINVAL")?;
-                this.set_last_error(einval)?;
-                this.write_scalar(Scalar::from_i32(-1), dest)?;
+                let pid = this.read_scalar(pid)?.to_i32()?;
+                let cpusetsize = this.read_scalar(cpusetsize)?.to_machine_usize(this)?;
+                let mask = this.read_pointer(mask)?;
+                if pid != 0 {
+                    throw_unsup_format!("`sched_getaffinity` is only supported for the calling thread");
+                }
+
+                // Every thread may run on all CPUs. `cpu_set_t` is an array of `unsigned long`s
+                // with a bit for each CPU, and it has to have room for all of them.
+                let word_size = this.pointer_size();
+                let word_bits = word_size.bits();
+                let num_cpus = u64::from(this.machine.num_cpus);
+                #[allow(clippy::integer_arithmetic)] // `word_bits` is not zero
+                let min_size = (num_cpus + word_bits - 1) / word_bits * word_size.bytes();
+                #[allow(clippy::integer_arithmetic)] // `word_size` is not zero
+                let whole_words = cpusetsize % word_size.bytes() == 0;
+                if cpusetsize < min_size || !whole_words {
+                    let einval = this.eval_libc("EINVAL")?;
+                    this.set_last_error(einval)?;
+                    this.write_scalar(Scalar::from_i32(-1), dest)?;
+                } else {
+                    let len = usize::try_from(cpusetsize).unwrap();
+                    this.write_bytes_ptr(mask, std::iter::repeat(0u8).take(len))?;
+                    let mut remaining = num_cpus;
+                    let mut offset = Size::ZERO;
+                    while remaining > 0 {
+                        let bits = remaining.min(word_bits);
+                        let word = 1u128.checked_shl(u32::try_from(bits).unwrap()).unwrap();
+                        let place = MPlaceTy::from_aligned_ptr(
+                            mask.offset(offset, this)?,
+                            this.machine.layouts.usize,
+                        );
+                        this.write_scalar(
+                            Scalar::from_uint(word.wrapping_sub(1), word_size),
+                            &place.into(),
+                        )?;
+                        remaining = remaining.saturating_sub(bits);
+                        offset += word_size;
+                    }
+                    this.write_scalar(Scalar::from_i32(0), dest)?;
+                }
             }
 
             // Incomplete shims that we "stub out" just to get pre-main initialization code to work.
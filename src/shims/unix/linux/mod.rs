@@ -1,3 +1,7 @@
+pub mod auxv;
 pub mod dlsym;
+pub mod epoll;
+pub mod eventfd;
 pub mod foreign_items;
 pub mod sync;
+pub mod timerfd;
@@ -1,7 +1,7 @@
 use crate::concurrency::thread::Time;
 use crate::*;
 use rustc_target::abi::{Align, Size};
-use std::time::{Instant, SystemTime};
+use std::time::SystemTime;
 
 /// Implementation of the SYS_futex syscall.
 /// `args` is the arguments *after* the syscall number.
@@ -42,6 +42,8 @@ pub fn futex<'tcx>(
     let futex_wake = this.eval_libc_i32("FUTEX_WAKE")?;
     let futex_wake_bitset = this.eval_libc_i32("FUTEX_WAKE_BITSET")?;
     let futex_realtime = this.eval_libc_i32("FUTEX_CLOCK_REALTIME")?;
+    let futex_requeue = this.eval_libc_i32("FUTEX_REQUEUE")?;
+    let futex_cmp_requeue = this.eval_libc_i32("FUTEX_CMP_REQUEUE")?;
 
     // FUTEX_PRIVATE enables an optimization that stops it from working across processes.
     // Miri doesn't support that anyway, so we ignore that flag.
@@ -89,9 +91,11 @@ pub fn futex<'tcx>(
             let timeout_time = if this.ptr_is_null(timeout.ptr)? {
                 None
             } else {
-                this.check_no_isolation(
-                    "`futex` syscall with `op=FUTEX_WAIT` and non-null timeout",
-                )?;
+                if op & futex_realtime != 0 {
+                    this.check_no_isolation(
+                        "`futex` syscall with `op=FUTEX_WAIT` and `FUTEX_CLOCK_REALTIME`",
+                    )?;
+                }
                 let duration = match this.read_timespec(&timeout)? {
                     Some(duration) => duration,
                     None => {
@@ -101,21 +105,23 @@ pub fn futex<'tcx>(
                         return Ok(());
                     }
                 };
-                Some(if wait_bitset {
+                // A deadline that is too far in the future to be represented is never reached, so
+                // then we wait without a timeout.
+                if wait_bitset {
                     // FUTEX_WAIT_BITSET uses an absolute timestamp.
                     if op & futex_realtime != 0 {
-                        Time::RealTime(SystemTime::UNIX_EPOCH.checked_add(duration).unwrap())
+                        SystemTime::UNIX_EPOCH.checked_add(duration).map(Time::RealTime)
                     } else {
-                        Time::Monotonic(this.machine.time_anchor.checked_add(duration).unwrap())
+                        this.monotonic_deadline_since_anchor(duration)
                     }
                 } else {
                     // FUTEX_WAIT uses a relative timestamp.
                     if op & futex_realtime != 0 {
-                        Time::RealTime(SystemTime::now().checked_add(duration).unwrap())
+                        SystemTime::now().checked_add(duration).map(Time::RealTime)
                     } else {
-                        Time::Monotonic(Instant::now().checked_add(duration).unwrap())
+                        this.monotonic_deadline_after(duration)
                     }
-                })
+                }
             };
             // Check the pointer for alignment and validity.
             // The API requires `addr` to be a 4-byte aligned pointer, and will
@@ -181,28 +187,25 @@ pub fn futex<'tcx>(
                 )?
                 .to_i32()?;
             if val == futex_val {
-                // The value still matches, so we block the thread make it wait for FUTEX_WAKE.
-                this.block_thread(thread);
+                // The value still matches, so we block the thread make it wait for FUTEX_WAKE, or
+                // until the timeout if one was specified. The timeout callback overrides the
+                // return value.
+                let timeout_dest = dest.clone();
+                this.block_thread_until(
+                    thread,
+                    timeout_time,
+                    Box::new(move |this| {
+                        this.unblock_thread(thread);
+                        this.futex_remove_waiter(thread);
+                        let etimedout = this.eval_libc("ETIMEDOUT")?;
+                        this.set_last_error(etimedout)?;
+                        this.write_scalar(Scalar::from_machine_isize(-1, this), &timeout_dest)?;
+                        Ok(())
+                    }),
+                );
                 this.futex_wait(addr_usize, thread, bitset);
                 // Succesfully waking up from FUTEX_WAIT always returns zero.
                 this.write_scalar(Scalar::from_machine_isize(0, this), dest)?;
-                // Register a timeout callback if a timeout was specified.
-                // This callback will override the return value when the timeout triggers.
-                if let Some(timeout_time) = timeout_time {
-                    let dest = dest.clone();
-                    this.register_timeout_callback(
-                        thread,
-                        timeout_time,
-                        Box::new(move |this| {
-                            this.unblock_thread(thread);
-                            this.futex_remove_waiter(addr_usize, thread);
-                            let etimedout = this.eval_libc("ETIMEDOUT")?;
-                            this.set_last_error(etimedout)?;
-                            this.write_scalar(Scalar::from_machine_isize(-1, this), &dest)?;
-                            Ok(())
-                        }),
-                    );
-                }
             } else {
                 // The futex value doesn't match the expected value, so we return failure
                 // right away without sleeping: -1 and errno set to EAGAIN.
@@ -241,17 +244,62 @@ pub fn futex<'tcx>(
             // will see the latest value on addr which could be changed by our caller
             // before doing the syscall.
             this.atomic_fence(AtomicFenceOrd::SeqCst)?;
-            let mut n = 0;
-            #[allow(clippy::integer_arithmetic)]
-            for _ in 0..val {
-                if let Some(thread) = this.futex_wake(addr_usize, bitset) {
-                    this.unblock_thread(thread);
-                    this.unregister_timeout_callback_if_exists(thread);
-                    n += 1;
-                } else {
-                    break;
+            let n = futex_wake_threads(this, addr_usize, bitset, val);
+            this.write_scalar(Scalar::from_machine_isize(n, this), dest)?;
+        }
+        // FUTEX_REQUEUE: (int *addr, int op = FUTEX_REQUEUE, int val, int val2, int *addr2)
+        // Wakes at most `val` threads waiting on the futex at `addr`, and moves at most `val2` of
+        // the remaining waiters to the futex at `addr2`. `val2` is passed in place of the timeout.
+        // Returns the amount of threads woken up.
+        // FUTEX_CMP_REQUEUE: (int *addr, int op = FUTEX_CMP_REQUEUE, int val, int val2, int *addr2, int val3)
+        // Same as FUTEX_REQUEUE, but first checks that *addr still equals `val3`, and fails with
+        // EAGAIN otherwise. Returns the amount of threads woken up or requeued.
+        op if op == futex_requeue || op == futex_cmp_requeue => {
+            let cmp = op == futex_cmp_requeue;
+            let min_args = if cmp { 6 } else { 5 };
+            if args.len() < min_args {
+                throw_ub_format!(
+                    "incorrect number of arguments for `futex` syscall with `op={}`: got {}, expected at least {}",
+                    if cmp { "FUTEX_CMP_REQUEUE" } else { "FUTEX_REQUEUE" },
+                    args.len(),
+                    min_args,
+                );
+            }
+            let val2 = this.read_scalar(&args[3])?.to_machine_isize(this)?;
+            let addr2 = this.read_scalar(&args[4])?.to_machine_usize(this)?;
+            if val < 0 || val2 < 0 {
+                let einval = this.eval_libc("EINVAL")?;
+                this.set_last_error(einval)?;
+                this.write_scalar(Scalar::from_machine_isize(-1, this), dest)?;
+                return Ok(());
+            }
+            // Like for FUTEX_WAKE, this fence makes sure that we see the latest value on addr,
+            // and that the threads we wake up see everything our caller did before the syscall.
+            this.atomic_fence(AtomicFenceOrd::SeqCst)?;
+            if cmp {
+                let val3 = this.read_scalar(&args[5])?.to_i32()?;
+                let futex_val = this
+                    .read_scalar_at_offset_atomic(
+                        &addr.into(),
+                        0,
+                        this.machine.layouts.i32,
+                        AtomicReadOrd::Relaxed,
+                    )?
+                    .to_i32()?;
+                if futex_val != val3 {
+                    let eagain = this.eval_libc("EAGAIN")?;
+                    this.set_last_error(eagain)?;
+                    this.write_scalar(Scalar::from_machine_isize(-1, this), dest)?;
+                    return Ok(());
                 }
             }
+            let woken = futex_wake_threads(this, addr_usize, u32::MAX, val);
+            let requeued = this.futex_requeue(addr_usize, addr2, val2.try_into().unwrap());
+            let n = if cmp {
+                woken.checked_add(i64::try_from(requeued).unwrap()).unwrap()
+            } else {
+                woken
+            };
             this.write_scalar(Scalar::from_machine_isize(n, this), dest)?;
         }
         op => throw_unsup_format!("Miri does not support `futex` syscall with op={}", op),
@@ -259,3 +307,25 @@ pub fn futex<'tcx>(
 
     Ok(())
 }
+
+/// Wake up at most `count` threads waiting on the futex at `addr` whose bitset matches `bitset`.
+/// Returns the amount of threads woken up.
+fn futex_wake_threads<'tcx>(
+    this: &mut MiriEvalContext<'_, 'tcx>,
+    addr: u64,
+    bitset: u32,
+    count: i32,
+) -> i64 {
+    let mut n = 0;
+    #[allow(clippy::integer_arithmetic)]
+    for _ in 0..count {
+        if let Some(thread) = this.futex_wake(addr, bitset) {
+            this.unblock_thread(thread);
+            this.unregister_timeout_callback_if_exists(thread);
+            n += 1;
+        } else {
+            break;
+        }
+    }
+    n
+}
@@ -0,0 +1,395 @@
+//! Emulation of `timerfd` and `signalfd`. Timers run on Miri's clock, so under isolation they
+//! expire in virtual time. No signals are ever delivered, so a signalfd is never readable.
+
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use rustc_target::abi::Endian;
+
+use crate::*;
+use shims::time::system_time_to_duration;
+use shims::unix::fs::FileDescriptor;
+use shims::unix::linux::epoll::EpollReadiness;
+use shims::unix::resource::EvalContextExt as _;
+
+#[derive(Debug)]
+struct TimerState {
+    /// The next expiration, or `None` if the timer is disarmed.
+    deadline: Option<Instant>,
+    /// The period of the timer. Zero for a timer that only expires once.
+    interval: Duration,
+    generation: u64,
+}
+
+impl TimerState {
+    /// The number of expirations that have not been read yet.
+    fn expirations(&self, now: Instant) -> u64 {
+        match self.deadline {
+            Some(deadline) if deadline <= now =>
+                if self.interval.is_zero() {
+                    1
+                } else {
+                    let elapsed = now.duration_since(deadline).as_nanos();
+                    let periods = elapsed.checked_div(self.interval.as_nanos()).unwrap();
+                    u64::try_from(periods).unwrap_or(u64::MAX).saturating_add(1)
+                },
+            _ => 0,
+        }
+    }
+
+    /// The expiration after the next `count` ones, or `None` if there is none.
+    fn deadline_after(&self, count: u64) -> Option<Instant> {
+        let deadline = self.deadline?;
+        if count == 0 {
+            return Some(deadline);
+        }
+        if self.interval.is_zero() {
+            return None;
+        }
+        let nanos = self.interval.as_nanos().saturating_mul(count.into());
+        deadline.checked_add(Duration::from_nanos(u64::try_from(nanos).ok()?))
+    }
+}
+
+/// A timer, created by `timerfd_create`.
+#[derive(Debug)]
+pub struct Timer {
+    /// The state of the timer. This is shared between duplicates of the file descriptor.
+    state: Rc<RefCell<TimerState>>,
+    /// Shared between duplicates of the file descriptor as well.
+    is_nonblock: Rc<Cell<bool>>,
+    /// The clock of the machine, which the timer expires on.
+    clock: Clock,
+    /// Whether the timer was created for `CLOCK_REALTIME`, which absolute times are given in.
+    is_realtime: bool,
+    /// The endianness of the target, which the number of expirations is read in.
+    endian: Endian,
+}
+
+impl FileDescriptor for Timer {
+    fn name(&self) -> &'static str {
+        "timerfd"
+    }
+
+    fn as_timer(&self) -> Option<&Timer> {
+        Some(self)
+    }
+
+    fn nonblock_flag(&self) -> Option<&Cell<bool>> {
+        Some(&self.is_nonblock)
+    }
+
+    fn ready_at(&self) -> Option<Instant> {
+        self.state.borrow().deadline
+    }
+
+    fn read<'tcx>(
+        &mut self,
+        _communicate_allowed: bool,
+        bytes: &mut [u8],
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        let Some(bytes) = bytes.get_mut(..8) else {
+            return Ok(Err(io::ErrorKind::InvalidInput.into()));
+        };
+        let mut state = self.state.borrow_mut();
+        let count = state.expirations(self.clock.now());
+        if count == 0 {
+            // Unless the timerfd is non-blocking, the thread waits for the timer to expire.
+            return Ok(Err(io::ErrorKind::WouldBlock.into()));
+        }
+        state.deadline = state.deadline_after(count);
+        state.generation = state.generation.wrapping_add(1);
+        bytes.copy_from_slice(&match self.endian {
+            Endian::Little => count.to_le_bytes(),
+            Endian::Big => count.to_be_bytes(),
+        });
+        Ok(Ok(8))
+    }
+
+    fn epoll_readiness(&self) -> Option<EpollReadiness> {
+        let state = self.state.borrow();
+        let count = state.expirations(self.clock.now());
+        Some(EpollReadiness {
+            readable: count > 0,
+            writable: false,
+            // Every expiration is a new event for edge-triggered interests.
+            generation: state.generation.wrapping_add(count),
+        })
+    }
+
+    fn dup(&mut self) -> io::Result<Box<dyn FileDescriptor>> {
+        Ok(Box::new(Timer {
+            state: Rc::clone(&self.state),
+            is_nonblock: Rc::clone(&self.is_nonblock),
+            clock: self.clock.clone(),
+            is_realtime: self.is_realtime,
+            endian: self.endian,
+        }))
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+    ) -> InterpResult<'tcx, io::Result<i32>> {
+        Ok(Ok(0))
+    }
+}
+
+/// A signalfd, created by `signalfd`. Since Miri never delivers signals, nothing can be read from
+/// it.
+#[derive(Debug)]
+struct SignalFd {
+    /// Shared between duplicates of the file descriptor.
+    is_nonblock: Rc<Cell<bool>>,
+}
+
+impl FileDescriptor for SignalFd {
+    fn name(&self) -> &'static str {
+        "signalfd"
+    }
+
+    fn nonblock_flag(&self) -> Option<&Cell<bool>> {
+        Some(&self.is_nonblock)
+    }
+
+    fn read<'tcx>(
+        &mut self,
+        _communicate_allowed: bool,
+        bytes: &mut [u8],
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        // Reads have to have room for at least one `signalfd_siginfo`.
+        if bytes.len() < 128 {
+            return Ok(Err(io::ErrorKind::InvalidInput.into()));
+        }
+        Ok(Err(io::ErrorKind::WouldBlock.into()))
+    }
+
+    fn epoll_readiness(&self) -> Option<EpollReadiness> {
+        Some(EpollReadiness { readable: false, writable: false, generation: 0 })
+    }
+
+    fn dup(&mut self) -> io::Result<Box<dyn FileDescriptor>> {
+        Ok(Box::new(SignalFd { is_nonblock: Rc::clone(&self.is_nonblock) }))
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+    ) -> InterpResult<'tcx, io::Result<i32>> {
+        Ok(Ok(0))
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// The state of the timer that `fd` refers to, and whether it is a `CLOCK_REALTIME` timer. Sets
+    /// the last error and returns `None` if `fd` is not a timer.
+    fn timer_state(
+        &mut self,
+        fd: i32,
+    ) -> InterpResult<'tcx, Option<(Rc<RefCell<TimerState>>, bool)>> {
+        let this = self.eval_context_mut();
+
+        let error = match this.machine.file_handler.handles.get(&fd) {
+            Some(fd) =>
+                match fd.as_timer() {
+                    Some(timer) => return Ok(Some((Rc::clone(&timer.state), timer.is_realtime))),
+                    None => "EINVAL",
+                },
+            None => "EBADF",
+        };
+        let error = this.eval_libc(error)?;
+        this.set_last_error(error)?;
+        Ok(None)
+    }
+
+    /// Writes the time until the next expiration and the interval of the timer to the `itimerspec`
+    /// at `place`.
+    fn write_itimerspec(
+        &mut self,
+        state: &TimerState,
+        place: &MPlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let now = this.machine.clock.now();
+        let remaining = state
+            .deadline_after(state.expirations(now))
+            .map_or(Duration::ZERO, |deadline| deadline.duration_since(now));
+        for (field, duration) in [("it_value", remaining), ("it_interval", state.interval)] {
+            let field = this.mplace_field_named(place, field)?;
+            this.write_int_fields(
+                &[duration.as_secs().into(), duration.subsec_nanos().into()],
+                &field,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// `CLOCK_MONOTONIC`, `CLOCK_BOOTTIME` and `CLOCK_REALTIME` timers all run on Miri's clock.
+    /// They only differ in how absolute expiration times are given.
+    fn timerfd_create(
+        &mut self,
+        clockid: &OpTy<'tcx, Provenance>,
+        flags: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let clockid = this.read_scalar(clockid)?.to_i32()?;
+        let flags = this.read_scalar(flags)?.to_i32()?;
+
+        let tfd_cloexec = this.eval_libc_i32("TFD_CLOEXEC")?;
+        let tfd_nonblock = this.eval_libc_i32("TFD_NONBLOCK")?;
+        let clock_realtime = this.eval_libc_i32("CLOCK_REALTIME")?;
+        let clocks = [
+            clock_realtime,
+            this.eval_libc_i32("CLOCK_MONOTONIC")?,
+            this.eval_libc_i32("CLOCK_BOOTTIME")?,
+        ];
+
+        if flags & !(tfd_cloexec | tfd_nonblock) != 0 || !clocks.contains(&clockid) {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+        if !this.check_fd_limit(1)? {
+            return Ok(-1);
+        }
+
+        let state = TimerState { deadline: None, interval: Duration::ZERO, generation: 0 };
+        let timer = Timer {
+            state: Rc::new(RefCell::new(state)),
+            is_nonblock: Rc::new(Cell::new(flags & tfd_nonblock != 0)),
+            clock: this.machine.clock.clone(),
+            is_realtime: clockid == clock_realtime,
+            endian: this.tcx.data_layout.endian,
+        };
+        Ok(this.machine.file_handler.insert_fd(Box::new(timer)))
+    }
+
+    /// Arms or disarms the timer. Absolute `CLOCK_REALTIME` times need the host's time, so they are
+    /// not supported under isolation.
+    fn timerfd_settime(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        flags: &OpTy<'tcx, Provenance>,
+        new_value: &OpTy<'tcx, Provenance>,
+        old_value: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let flags = this.read_scalar(flags)?.to_i32()?;
+        let new_value = this.deref_operand(new_value)?;
+        let old_value_ptr = this.read_pointer(old_value)?;
+
+        let Some((state, is_realtime)) = this.timer_state(fd)? else {
+            return Ok(-1);
+        };
+        let tfd_timer_abstime = this.eval_libc_i32("TFD_TIMER_ABSTIME")?;
+        let value = this.mplace_field_named(&new_value, "it_value")?;
+        let value = this.read_timespec(&value)?;
+        let interval = this.mplace_field_named(&new_value, "it_interval")?;
+        let interval = this.read_timespec(&interval)?;
+        let (Some(value), Some(interval)) = (value, interval) else {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        };
+        if flags & !tfd_timer_abstime != 0 {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+
+        if !this.ptr_is_null(old_value_ptr)? {
+            let old_value = this.deref_operand(old_value)?;
+            this.write_itimerspec(&state.borrow(), &old_value)?;
+        }
+
+        // A deadline that is too far in the future to be represented is never reached.
+        let now = this.machine.clock.now();
+        let deadline = if value.is_zero() {
+            None
+        } else if flags & tfd_timer_abstime == 0 {
+            now.checked_add(value)
+        } else if is_realtime {
+            this.check_no_isolation("`timerfd_settime` with an absolute `CLOCK_REALTIME` time")?;
+            let since_epoch = system_time_to_duration(&SystemTime::now())?;
+            now.checked_add(value.saturating_sub(since_epoch))
+        } else {
+            this.machine.clock.anchor().checked_add(value)
+        };
+        let mut state = state.borrow_mut();
+        state.deadline = deadline;
+        state.interval = interval;
+        state.generation = state.generation.wrapping_add(1);
+        if let Some(deadline) = deadline {
+            this.register_io_deadline(deadline);
+        }
+        Ok(0)
+    }
+
+    fn timerfd_gettime(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        curr_value: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let curr_value = this.deref_operand(curr_value)?;
+
+        let Some((state, _)) = this.timer_state(fd)? else {
+            return Ok(-1);
+        };
+        this.write_itimerspec(&state.borrow(), &curr_value)?;
+        Ok(0)
+    }
+
+    /// Creates a signalfd if `fd` is -1. Otherwise, `fd` has to be a signalfd already, and it is
+    /// returned unchanged: the mask makes no difference, since no signals are delivered anyway.
+    fn signalfd(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        mask: &OpTy<'tcx, Provenance>,
+        flags: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        this.read_pointer(mask)?;
+        let flags = this.read_scalar(flags)?.to_i32()?;
+
+        let sfd_cloexec = this.eval_libc_i32("SFD_CLOEXEC")?;
+        let sfd_nonblock = this.eval_libc_i32("SFD_NONBLOCK")?;
+
+        let error = if flags & !(sfd_cloexec | sfd_nonblock) != 0 {
+            Some("EINVAL")
+        } else if fd == -1 {
+            None
+        } else {
+            match this.machine.file_handler.handles.get(&fd) {
+                Some(handle) if handle.name() == "signalfd" => return Ok(fd),
+                Some(_) => Some("EINVAL"),
+                None => Some("EBADF"),
+            }
+        };
+        if let Some(error) = error {
+            let error = this.eval_libc(error)?;
+            this.set_last_error(error)?;
+            return Ok(-1);
+        }
+        if !this.check_fd_limit(1)? {
+            return Ok(-1);
+        }
+
+        let signalfd = SignalFd { is_nonblock: Rc::new(Cell::new(flags & sfd_nonblock != 0)) };
+        Ok(this.machine.file_handler.insert_fd(Box::new(signalfd)))
+    }
+}
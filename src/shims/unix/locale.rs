@@ -0,0 +1,215 @@
+//! Emulation of `setlocale`, `localeconv` and the `newlocale` family. The only locale that exists
+//! is "C" (also known as "POSIX"), and it is used for every category. `""`, which selects the
+//! locale from the environment, also gives the "C" locale.
+
+use rustc_data_structures::fx::FxHashMap;
+use rustc_middle::mir::Mutability;
+
+use crate::*;
+
+/// The locale names that are accepted. They all stand for the "C" locale.
+const C_LOCALE_NAMES: &[&[u8]] = &[b"", b"C", b"POSIX"];
+
+/// The categories that exist on all Unix targets. glibc has some more.
+const CATEGORIES: &[&str] =
+    &["LC_ALL", "LC_COLLATE", "LC_CTYPE", "LC_MESSAGES", "LC_MONETARY", "LC_NUMERIC", "LC_TIME"];
+const GLIBC_CATEGORIES: &[&str] = &[
+    "LC_PAPER",
+    "LC_NAME",
+    "LC_ADDRESS",
+    "LC_TELEPHONE",
+    "LC_MEASUREMENT",
+    "LC_IDENTIFICATION",
+];
+
+/// The value of `CHAR_MAX`, which the `lconv` of the "C" locale uses for the values that are not
+/// available. It is the same whether `char` is signed or not, since the C locale stays in ASCII.
+const CHAR_MAX: i128 = 127;
+
+/// The memory that is handed out for the locale, allocated on first use. Since there is only one
+/// locale, `newlocale` and `duplocale` always give out the same handle, and `freelocale` does
+/// nothing.
+#[derive(Debug, Default)]
+pub struct LocaleState {
+    /// The name returned by `setlocale`.
+    name: Option<Pointer<Option<Provenance>>>,
+    /// The handle returned by `newlocale` and `duplocale`.
+    handle: Option<Pointer<Option<Provenance>>>,
+    /// The `struct lconv` returned by `localeconv`.
+    lconv: Option<Pointer<Option<Provenance>>>,
+    /// The threads that have set their locale with `uselocale`. The other threads use the global
+    /// locale.
+    thread_locales: FxHashMap<ThreadId, Pointer<Option<Provenance>>>,
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Reads a locale name, and returns whether it stands for the "C" locale.
+    fn is_c_locale(&self, name: Pointer<Option<Provenance>>) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_ref();
+        let name = this.read_c_str(name)?;
+        Ok(C_LOCALE_NAMES.contains(&name))
+    }
+
+    fn is_valid_category(&self, category: i32) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_ref();
+        let glibc_categories: &[&str] =
+            if this.tcx.sess.target.os == "linux" { GLIBC_CATEGORIES } else { &[] };
+        for &name in CATEGORIES.iter().chain(glibc_categories) {
+            if this.eval_libc_i32(name)? == category {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// `LC_GLOBAL_LOCALE` is `(locale_t)-1` on all Unix targets.
+    fn global_locale(&self) -> Scalar<Provenance> {
+        let this = self.eval_context_ref();
+        Scalar::from_machine_isize(-1, this)
+    }
+
+    fn is_global_locale(&self, locale: Pointer<Option<Provenance>>) -> bool {
+        let this = self.eval_context_ref();
+        locale.addr().bytes() == this.machine_usize_max()
+    }
+
+    fn locale_handle(&mut self) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        let this = self.eval_context_mut();
+        if let Some(handle) = this.machine.locale.handle {
+            return Ok(handle);
+        }
+        // The program cannot look inside a `locale_t`, so it does not matter what it points to.
+        let layout = this.machine.layouts.usize;
+        let handle = this.allocate(layout, MiriMemoryKind::Machine.into())?.ptr;
+        this.machine.locale.handle = Some(handle);
+        Ok(handle)
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Returns "C" for every category, and null if another locale is requested.
+    fn setlocale(
+        &mut self,
+        category_op: &OpTy<'tcx, Provenance>,
+        locale_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        let this = self.eval_context_mut();
+
+        let category = this.read_scalar(category_op)?.to_i32()?;
+        let locale = this.read_pointer(locale_op)?;
+
+        if !this.is_valid_category(category)? {
+            return Ok(Pointer::null());
+        }
+        // A null locale only asks for the name of the current one.
+        if !this.ptr_is_null(locale)? && !this.is_c_locale(locale)? {
+            return Ok(Pointer::null());
+        }
+        if let Some(name) = this.machine.locale.name {
+            return Ok(name);
+        }
+        let name = this.allocate_str("C\0", MiriMemoryKind::Machine.into(), Mutability::Not).ptr;
+        this.machine.locale.name = Some(name);
+        Ok(name)
+    }
+
+    /// Returns the `struct lconv` of the "C" locale: the decimal point is ".", all other strings
+    /// are empty, and all other values are `CHAR_MAX`. The same struct is returned every time.
+    fn localeconv(&mut self) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        let this = self.eval_context_mut();
+
+        if let Some(lconv) = this.machine.locale.lconv {
+            return Ok(lconv);
+        }
+        let lconv_layout = this.libc_ty_layout("lconv")?;
+        let lconv = this.allocate(lconv_layout, MiriMemoryKind::Machine.into())?;
+        let empty = this.allocate_str("\0", MiriMemoryKind::Machine.into(), Mutability::Not).ptr;
+        for idx in 0..lconv_layout.fields.count() {
+            let field = this.mplace_field(&lconv, idx)?;
+            if field.layout.ty.is_unsafe_ptr() {
+                this.write_pointer(empty, &field.into())?;
+            } else {
+                this.write_int(CHAR_MAX, &field.into())?;
+            }
+        }
+        let dot = this.allocate_str(".\0", MiriMemoryKind::Machine.into(), Mutability::Not).ptr;
+        let decimal_point = this.mplace_field_named(&lconv, "decimal_point")?;
+        this.write_pointer(dot, &decimal_point.into())?;
+
+        this.machine.locale.lconv = Some(lconv.ptr);
+        Ok(lconv.ptr)
+    }
+
+    /// Since all categories use the same locale, the category mask is not looked at. The handle
+    /// that is returned is the same as `base`, if that is not null.
+    fn newlocale(
+        &mut self,
+        mask_op: &OpTy<'tcx, Provenance>,
+        locale_op: &OpTy<'tcx, Provenance>,
+        base_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        let this = self.eval_context_mut();
+
+        let _mask = this.read_scalar(mask_op)?.to_i32()?;
+        let locale = this.read_pointer(locale_op)?;
+        let _base = this.read_pointer(base_op)?;
+
+        if this.ptr_is_null(locale)? {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(Pointer::null());
+        }
+        if !this.is_c_locale(locale)? {
+            let enoent = this.eval_libc("ENOENT")?;
+            this.set_last_error(enoent)?;
+            return Ok(Pointer::null());
+        }
+        this.locale_handle()
+    }
+
+    fn duplocale(
+        &mut self,
+        locale_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        let this = self.eval_context_mut();
+
+        let _locale = this.read_pointer(locale_op)?;
+        this.locale_handle()
+    }
+
+    /// The handle stays valid, since it is the same for all locales.
+    fn freelocale(&mut self, locale_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let _locale = this.read_pointer(locale_op)?;
+        Ok(())
+    }
+
+    /// Sets the locale of the calling thread, and returns the one it used before. A null locale
+    /// only returns the current one.
+    fn uselocale(
+        &mut self,
+        locale_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        let locale = this.read_pointer(locale_op)?;
+        let thread = this.get_active_thread();
+
+        let old_locale = this.machine.locale.thread_locales.get(&thread).copied();
+        if this.is_global_locale(locale) {
+            this.machine.locale.thread_locales.remove(&thread);
+        } else if !this.ptr_is_null(locale)? {
+            if Some(locale.addr()) != this.machine.locale.handle.map(|handle| handle.addr()) {
+                throw_ub_format!("`uselocale` called with a locale not created by `newlocale`");
+            }
+            this.machine.locale.thread_locales.insert(thread, locale);
+        }
+        Ok(match old_locale {
+            Some(old_locale) => Scalar::from_maybe_pointer(old_locale, this),
+            None => this.global_locale(),
+        })
+    }
+}
@@ -0,0 +1,271 @@
+//! Emulation of the parts of libdispatch that do not need a dispatch queue: `dispatch_once_f` and
+//! dispatch semaphores.
+
+use std::time::Duration;
+
+use rustc_data_structures::fx::FxHashMap;
+use rustc_middle::mir;
+use rustc_target::spec::abi::Abi;
+
+use crate::*;
+
+/// The value of a `dispatch_once_t` once the initialization has completed, `~0l`.
+const DISPATCH_ONCE_DONE: i64 = -1;
+/// The value of a `dispatch_once_t` while the initialization is running. Any value other than 0
+/// and `DISPATCH_ONCE_DONE` means that; libdispatch stores a list of waiters there.
+const DISPATCH_ONCE_RUNNING: i64 = 1;
+
+const DISPATCH_TIME_NOW: u64 = 0;
+const DISPATCH_TIME_FOREVER: u64 = !0;
+
+/// What `dispatch_semaphore_wait` returns on a timeout.
+const KERN_OPERATION_TIMED_OUT: i64 = 49;
+
+/// A dispatch object created by Miri. Only semaphores are supported.
+#[derive(Debug)]
+struct DispatchObject {
+    semaphore: SemaphoreId,
+    /// Adjusted by `dispatch_retain` and `dispatch_release`.
+    refcount: u64,
+}
+
+/// The dispatch objects, keyed by their address.
+#[derive(Debug, Default)]
+pub struct DispatchState {
+    objects: FxHashMap<u64, DispatchObject>,
+}
+
+/// Marks the frame of the initialization function called by `dispatch_once_f`. When this frame is
+/// popped, the predicate is set to done and the threads waiting for it are woken up.
+#[derive(Debug)]
+pub struct DispatchOnceData<'tcx> {
+    predicate: MPlaceTy<'tcx, Provenance>,
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    fn dispatch_once_f(
+        &mut self,
+        predicate_op: &OpTy<'tcx, Provenance>,
+        context_op: &OpTy<'tcx, Provenance>,
+        function_op: &OpTy<'tcx, Provenance>,
+        ret: mir::BasicBlock,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let predicate =
+            this.deref_operand_and_offset(predicate_op, 0, this.machine.layouts.isize)?;
+        let context = this.read_immediate(context_op)?;
+        let function = this.read_pointer(function_op)?;
+
+        let state =
+            this.read_scalar_atomic(&predicate, AtomicReadOrd::Acquire)?.to_machine_isize(this)?;
+        if state == DISPATCH_ONCE_DONE {
+            this.go_to_block(ret);
+        } else if state == 0 {
+            // We are the first, so we run the initialization. Everyone else has to wait until it
+            // is done.
+            this.write_scalar_atomic(
+                Scalar::from_machine_isize(DISPATCH_ONCE_RUNNING, this),
+                &predicate,
+                AtomicWriteOrd::Relaxed,
+            )?;
+            let instance = this.get_ptr_fn(function)?.as_instance()?;
+            this.call_function(
+                instance,
+                Abi::C { unwind: false },
+                &[*context],
+                None,
+                StackPopCleanup::Goto { ret: Some(ret), unwind: StackPopUnwind::Skip },
+            )?;
+            this.frame_mut().extra.dispatch_once = Some(DispatchOnceData { predicate });
+        } else {
+            // Another thread is running the initialization, wait for it to finish. We continue
+            // after the call once we are woken up.
+            let thread = this.get_active_thread();
+            let addr = predicate.ptr.addr().bytes();
+            this.futex_wait(addr, thread, u32::MAX);
+            this.block_thread(thread);
+            this.go_to_block(ret);
+        }
+
+        Ok(())
+    }
+
+    /// Called when the initialization function of `dispatch_once_f` returns.
+    fn dispatch_once_done(&mut self, data: DispatchOnceData<'tcx>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        this.write_scalar_atomic(
+            Scalar::from_machine_isize(DISPATCH_ONCE_DONE, this),
+            &data.predicate,
+            AtomicWriteOrd::Release,
+        )?;
+        let addr = data.predicate.ptr.addr().bytes();
+        while let Some(thread) = this.futex_wake(addr, u32::MAX) {
+            this.unblock_thread(thread);
+        }
+
+        Ok(())
+    }
+
+    fn dispatch_time(
+        &mut self,
+        when_op: &OpTy<'tcx, Provenance>,
+        delta_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, u64> {
+        let this = self.eval_context_mut();
+
+        let when = this.read_scalar(when_op)?.to_u64()?;
+        let delta = this.read_scalar(delta_op)?.to_i64()?;
+
+        if when == DISPATCH_TIME_FOREVER {
+            return Ok(DISPATCH_TIME_FOREVER);
+        }
+        // Times are in the units of `mach_absolute_time`, so they are nanoseconds for us.
+        let base = if when == DISPATCH_TIME_NOW {
+            this.mach_absolute_time()?.to_u64()?
+        } else if i64::try_from(when).is_err() {
+            throw_unsup_format!("`dispatch_time` with a wall clock time is not supported");
+        } else {
+            when
+        };
+        let time = match u64::try_from(delta) {
+            Ok(delta) => base.saturating_add(delta),
+            Err(_) => base.saturating_sub(delta.unsigned_abs()),
+        };
+        // Values with the highest bit set would be wall clock times.
+        Ok(if i64::try_from(time).is_err() { DISPATCH_TIME_FOREVER } else { time })
+    }
+
+    fn dispatch_semaphore_create(
+        &mut self,
+        value_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        let this = self.eval_context_mut();
+
+        let value = this.read_scalar(value_op)?.to_machine_isize(this)?;
+        let Ok(value) = u64::try_from(value) else {
+            // Negative values are not allowed.
+            return Ok(Pointer::null());
+        };
+
+        let semaphore = this.semaphore_create(value);
+        // The object is opaque, we only need its address.
+        let object = this.allocate(this.machine.layouts.usize, MiriMemoryKind::Machine.into())?;
+        this.machine
+            .dispatch
+            .objects
+            .try_insert(object.ptr.addr().bytes(), DispatchObject { semaphore, refcount: 1 })
+            .unwrap();
+
+        Ok(object.ptr)
+    }
+
+    fn dispatch_semaphore_wait(
+        &mut self,
+        semaphore_op: &OpTy<'tcx, Provenance>,
+        timeout_op: &OpTy<'tcx, Provenance>,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let id = dispatch_semaphore_id(this, semaphore_op, "dispatch_semaphore_wait")?;
+        let timeout = this.read_scalar(timeout_op)?.to_u64()?;
+        let thread = this.get_active_thread();
+
+        if this.semaphore_try_acquire(id, thread) {
+            this.write_null(dest)?;
+            return Ok(());
+        }
+        if timeout == DISPATCH_TIME_NOW {
+            this.write_scalar(Scalar::from_machine_isize(KERN_OPERATION_TIMED_OUT, this), dest)?;
+            return Ok(());
+        }
+
+        let timeout_time = if timeout == DISPATCH_TIME_FOREVER {
+            None
+        } else {
+            this.monotonic_deadline_since_anchor(Duration::from_nanos(timeout))
+        };
+        this.semaphore_enqueue_and_block(id, thread);
+        // We return success for now and override it in the timeout callback.
+        this.write_null(dest)?;
+        if let Some(timeout_time) = timeout_time {
+            let dest = dest.clone();
+            this.register_timeout_callback(
+                thread,
+                timeout_time,
+                Box::new(move |this| {
+                    this.unblock_thread(thread);
+                    this.semaphore_remove_waiter(id, thread);
+                    this.write_scalar(
+                        Scalar::from_machine_isize(KERN_OPERATION_TIMED_OUT, this),
+                        &dest,
+                    )?;
+                    Ok(())
+                }),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn dispatch_semaphore_signal(
+        &mut self,
+        semaphore_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i64> {
+        let this = self.eval_context_mut();
+
+        let id = dispatch_semaphore_id(this, semaphore_op, "dispatch_semaphore_signal")?;
+        // Returns non-zero if a thread was woken up.
+        Ok(if this.semaphore_release(id).is_some() { 1 } else { 0 })
+    }
+
+    fn dispatch_retain(&mut self, object_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let ptr = this.read_pointer(object_op)?;
+        let Some(object) = this.machine.dispatch.objects.get_mut(&ptr.addr().bytes()) else {
+            throw_ub_format!("`dispatch_retain` called on something that is not a dispatch object");
+        };
+        object.refcount = object.refcount.checked_add(1).unwrap();
+
+        Ok(())
+    }
+
+    fn dispatch_release(&mut self, object_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let ptr = this.read_pointer(object_op)?;
+        let addr = ptr.addr().bytes();
+        let Some(object) = this.machine.dispatch.objects.get_mut(&addr) else {
+            throw_ub_format!(
+                "`dispatch_release` called on something that is not a dispatch object"
+            );
+        };
+        object.refcount = object.refcount.checked_sub(1).unwrap();
+        if object.refcount == 0 {
+            let semaphore = object.semaphore;
+            if this.semaphore_is_awaited(semaphore) {
+                throw_ub_format!("releasing a dispatch semaphore that threads are waiting on");
+            }
+            this.machine.dispatch.objects.remove(&addr);
+            this.deallocate_ptr(ptr, None, MiriMemoryKind::Machine.into())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn dispatch_semaphore_id<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    semaphore_op: &OpTy<'tcx, Provenance>,
+    name: &str,
+) -> InterpResult<'tcx, SemaphoreId> {
+    let ptr = ecx.read_pointer(semaphore_op)?;
+    match ecx.machine.dispatch.objects.get(&ptr.addr().bytes()) {
+        Some(object) => Ok(object.semaphore),
+        None => throw_ub_format!("`{name}` called on something that is not a dispatch semaphore"),
+    }
+}
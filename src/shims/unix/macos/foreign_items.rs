@@ -5,6 +5,7 @@ use crate::*;
 use shims::foreign_items::EmulateByNameResult;
 use shims::unix::fs::EvalContextExt as _;
 use shims::unix::thread::EvalContextExt as _;
+use shims::unix::DispatchEvalContextExt as _;
 
 impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
 pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
@@ -171,6 +172,62 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let stack_size = Scalar::from_uint(STACK_SIZE, this.pointer_size());
                 this.write_scalar(stack_size, dest)?;
             }
+            "sysctlbyname" => {
+                let [name, oldp, oldlenp, newp, newlen] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let name = this.read_c_str(this.read_pointer(name)?)?.to_owned();
+                let oldp = this.read_pointer(oldp)?;
+                let oldlen_ptr = this.read_pointer(oldlenp)?;
+                let newp = this.read_pointer(newp)?;
+                this.read_scalar(newlen)?.to_machine_usize(this)?;
+
+                // Only the CPU counts are supported. They are all `int`s.
+                let cpu_counts: &[&[u8]] = &[
+                    b"hw.ncpu",
+                    b"hw.activecpu",
+                    b"hw.logicalcpu",
+                    b"hw.logicalcpu_max",
+                    b"hw.physicalcpu",
+                    b"hw.physicalcpu_max",
+                ];
+                if !cpu_counts.contains(&&name[..]) {
+                    throw_unsup_format!(
+                        "unsupported `sysctlbyname` name `{}`",
+                        String::from_utf8_lossy(&name)
+                    );
+                }
+                let int_size = this.machine.layouts.i32.size;
+                let error = if !this.ptr_is_null(newp)? {
+                    Some("EPERM")
+                } else if this.ptr_is_null(oldlen_ptr)? {
+                    (!this.ptr_is_null(oldp)?).then_some("EINVAL")
+                } else {
+                    let oldlen_place = this.deref_operand(oldlenp)?;
+                    let oldlen = this.read_scalar(&oldlen_place.into())?.to_machine_usize(this)?;
+                    let has_buffer = !this.ptr_is_null(oldp)?;
+                    if has_buffer && oldlen < int_size.bytes() {
+                        Some("ENOMEM")
+                    } else {
+                        if has_buffer {
+                            let num_cpus = i32::try_from(this.machine.num_cpus).unwrap_or(i32::MAX);
+                            let i32_layout = this.machine.layouts.i32;
+                            let old_place = MPlaceTy::from_aligned_ptr(oldp, i32_layout);
+                            this.write_scalar(Scalar::from_i32(num_cpus), &old_place.into())?;
+                        }
+                        // Without a buffer, only the size of the value is reported.
+                        let size = Scalar::from_machine_usize(int_size.bytes(), this);
+                        this.write_scalar(size, &oldlen_place.into())?;
+                        None
+                    }
+                };
+                if let Some(error) = error {
+                    let error = this.eval_libc(error)?;
+                    this.set_last_error(error)?;
+                    this.write_scalar(Scalar::from_i32(-1), dest)?;
+                } else {
+                    this.write_scalar(Scalar::from_i32(0), dest)?;
+                }
+            }
 
             // Threading
             "pthread_setname_np" => {
@@ -179,14 +236,37 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 this.pthread_setname_np(thread, this.read_scalar(name)?)?;
             }
 
-            // Incomplete shims that we "stub out" just to get pre-main initialization code to work.
-            // These shims are enabled only when the caller is in the standard library.
-            "mmap" if this.frame_in_std() => {
-                // This is a horrible hack, but since the guard page mechanism calls mmap and expects a particular return value, we just give it that value.
-                let [addr, _, _, _, _, _] =
+            // libdispatch
+            // `dispatch_once_f` is handled in `shims/foreign_items.rs` since it calls back into
+            // the program.
+            "dispatch_time" => {
+                let [when, delta] =
                     this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
-                let addr = this.read_scalar(addr)?;
-                this.write_scalar(addr, dest)?;
+                let result = this.dispatch_time(when, delta)?;
+                this.write_scalar(Scalar::from_u64(result), dest)?;
+            }
+            "dispatch_semaphore_create" => {
+                let [value] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.dispatch_semaphore_create(value)?;
+                this.write_pointer(result, dest)?;
+            }
+            "dispatch_semaphore_wait" => {
+                let [semaphore, timeout] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.dispatch_semaphore_wait(semaphore, timeout, dest)?;
+            }
+            "dispatch_semaphore_signal" => {
+                let [semaphore] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                let result = this.dispatch_semaphore_signal(semaphore)?;
+                this.write_scalar(Scalar::from_machine_isize(result, this), dest)?;
+            }
+            "dispatch_retain" => {
+                let [object] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.dispatch_retain(object)?;
+            }
+            "dispatch_release" => {
+                let [object] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.dispatch_release(object)?;
             }
 
             _ => return Ok(EmulateByNameResult::NotSupported),
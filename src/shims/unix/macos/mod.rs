@@ -1,2 +1,3 @@
+pub mod dispatch;
 pub mod dlsym;
 pub mod foreign_items;
@@ -0,0 +1,466 @@
+//! Emulation of `mmap`, `munmap`, `mprotect` and `madvise`. Every mapping is its own allocation,
+//! so pointers cannot be moved from one mapping into another, even if they happen to be adjacent.
+
+use rustc_target::abi::{Align, Size};
+
+use crate::*;
+use shims::unix::fs::AccessMode;
+
+/// How a page of `mmap` memory may be accessed. Code is never executed from interpreted memory, so
+/// `PROT_EXEC` is not tracked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Protection {
+    read: bool,
+    write: bool,
+}
+
+/// The state of the pages of an `mmap` allocation. `None` means the page was unmapped.
+#[derive(Clone, Debug)]
+pub struct Mapping {
+    pages: RangeMap<Option<Protection>>,
+    /// Whether this maps a file rather than anonymous memory.
+    is_file: bool,
+}
+
+impl Mapping {
+    pub fn new(size: Size) -> Self {
+        Mapping {
+            pages: RangeMap::new(size, Some(Protection { read: true, write: true })),
+            is_file: false,
+        }
+    }
+
+    /// Reading memory that is not readable raises `SIGSEGV`. We report it like accessing
+    /// deallocated memory.
+    pub fn read<'tcx>(&self, alloc_id: AllocId, range: AllocRange) -> InterpResult<'tcx> {
+        self.check_access(alloc_id, range, "read", |protection| protection.read)
+    }
+
+    pub fn write<'tcx>(&self, alloc_id: AllocId, range: AllocRange) -> InterpResult<'tcx> {
+        self.check_access(alloc_id, range, "write", |protection| protection.write)
+    }
+
+    fn check_access<'tcx>(
+        &self,
+        alloc_id: AllocId,
+        range: AllocRange,
+        access: &str,
+        allowed: impl Fn(Protection) -> bool,
+    ) -> InterpResult<'tcx> {
+        for (offset, protection) in self.pages.iter(range.start, range.size) {
+            let offset = offset.max(range.start).bytes();
+            match protection {
+                None =>
+                    throw_ub_format!(
+                        "{} access to {:?}+{:#x}, which was unmapped by `munmap`",
+                        access,
+                        alloc_id,
+                        offset
+                    ),
+                Some(protection) if !allowed(*protection) =>
+                    throw_ub_format!(
+                        "{} access to {:?}+{:#x}, which is protected against it by `mprotect`",
+                        access,
+                        alloc_id,
+                        offset
+                    ),
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Set the last error to `error` and return `MAP_FAILED`.
+    fn map_failed(&mut self, error: &str) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+        let error = this.eval_libc(error)?;
+        this.set_last_error(error)?;
+        Ok(Scalar::from_machine_isize(-1, this))
+    }
+
+    /// The allocation, offset and length in whole pages of the range starting at `addr` that
+    /// `munmap` or `mprotect` are applied to. Returns `None` and sets the last error to `EINVAL`
+    /// if the range does not start at a page or is empty.
+    fn page_range(
+        &mut self,
+        addr: Pointer<Option<Provenance>>,
+        length: u64,
+        name: &str,
+    ) -> InterpResult<'tcx, Option<(AllocId, Size, Size)>> {
+        let this = self.eval_context_mut();
+
+        let Some(length) = round_to_pages(length).filter(|&length| length > 0) else {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(None);
+        };
+        if !is_page_aligned(addr.addr().bytes()) {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(None);
+        }
+        let Ok((alloc_id, offset, _)) = this.ptr_try_get_alloc_id(addr) else {
+            throw_unsup_format!("`{}` is only supported on memory allocated by `mmap`", name);
+        };
+        let (size, ..) = this.get_alloc_info(alloc_id);
+        if offset.bytes().checked_add(length).map_or(true, |end| end > size.bytes()) {
+            throw_unsup_format!("`{}` is only supported on ranges within a single mapping", name);
+        }
+        Ok(Some((alloc_id, offset, Size::from_bytes(length))))
+    }
+
+    /// The allocation, offset and length in whole pages of the range starting at `addr` that
+    /// `madvise` or `posix_madvise` are applied to, or `None` if it is empty. Returns the name of
+    /// the error if the range does not start at a page or is not mapped.
+    fn advice_range(
+        &mut self,
+        addr: Pointer<Option<Provenance>>,
+        length: u64,
+        name: &str,
+    ) -> InterpResult<'tcx, Result<Option<(AllocId, Size, Size)>, &'static str>> {
+        let this = self.eval_context_mut();
+
+        if !is_page_aligned(addr.addr().bytes()) {
+            return Ok(Err("EINVAL"));
+        }
+        let Some(length) = round_to_pages(length) else {
+            return Ok(Err("EINVAL"));
+        };
+        if length == 0 {
+            return Ok(Ok(None));
+        }
+        let is_mmap = match this.ptr_try_get_alloc_id(addr) {
+            Ok((alloc_id, ..)) => this.get_alloc_extra(alloc_id)?.mapping.is_some(),
+            Err(_) => false,
+        };
+        if !is_mmap {
+            throw_unsup_format!("`{}` is only supported on memory allocated by `mmap`", name);
+        }
+        let (alloc_id, offset, _) = this.ptr_get_alloc_id(addr)?;
+        let (size, ..) = this.get_alloc_info(alloc_id);
+        if offset.bytes().checked_add(length).map_or(true, |end| end > size.bytes()) {
+            throw_unsup_format!("`{}` is only supported on ranges within a single mapping", name);
+        }
+        let length = Size::from_bytes(length);
+        let mapping = this.get_alloc_extra(alloc_id)?.mapping.as_ref().unwrap();
+        if mapping.pages.iter(offset, length).any(|(_, page)| page.is_none()) {
+            return Ok(Err("ENOMEM"));
+        }
+        Ok(Ok(Some((alloc_id, offset, length))))
+    }
+
+    fn protection(&self, prot: i32, name: &str) -> InterpResult<'tcx, Protection> {
+        let this = self.eval_context_ref();
+        let prot_read = this.eval_libc_i32("PROT_READ")?;
+        let prot_write = this.eval_libc_i32("PROT_WRITE")?;
+        let prot_exec = this.eval_libc_i32("PROT_EXEC")?;
+        if prot & !(prot_read | prot_write | prot_exec) != 0 {
+            throw_unsup_format!("unsupported protection {:#x} for `{}`", prot, name);
+        }
+        Ok(Protection { read: prot & prot_read != 0, write: prot & prot_write != 0 })
+    }
+}
+
+fn is_page_aligned(addr: u64) -> bool {
+    #[allow(clippy::integer_arithmetic)] // `PAGE_SIZE` is not zero
+    let rem = addr % PAGE_SIZE;
+    rem == 0
+}
+
+/// Round `length` up to a multiple of the page size.
+#[allow(clippy::integer_arithmetic)] // `PAGE_SIZE` is not zero
+fn round_to_pages(length: u64) -> Option<u64> {
+    Some(length.checked_add(PAGE_SIZE - 1)? / PAGE_SIZE * PAGE_SIZE)
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Anonymous mappings can be private or shared, which is the same for a single process. File
+    /// mappings have to be private, and get a copy of the file's contents.
+    fn mmap(
+        &mut self,
+        addr: &OpTy<'tcx, Provenance>,
+        length: &OpTy<'tcx, Provenance>,
+        prot: &OpTy<'tcx, Provenance>,
+        flags: &OpTy<'tcx, Provenance>,
+        fd: &OpTy<'tcx, Provenance>,
+        offset: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        let addr = this.read_pointer(addr)?;
+        let length = this.read_scalar(length)?.to_machine_usize(this)?;
+        let prot = this.read_scalar(prot)?.to_i32()?;
+        let flags = this.read_scalar(flags)?.to_i32()?;
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let offset = this.read_scalar(offset)?.to_int(offset.layout.size)?;
+
+        let map_private = this.eval_libc_i32("MAP_PRIVATE")?;
+        let map_shared = this.eval_libc_i32("MAP_SHARED")?;
+        let map_anon = this.eval_libc_i32("MAP_ANON")?;
+        let map_fixed = this.eval_libc_i32("MAP_FIXED")?;
+
+        if flags & map_fixed != 0 {
+            if this.frame_in_std() {
+                // The guard page mechanism of libstd maps a page at a fixed address and expects to
+                // get that address back. We do not have guard pages, so we just give it that.
+                return Ok(Scalar::from_maybe_pointer(addr, this));
+            }
+            throw_unsup_format!("`MAP_FIXED` is not supported for `mmap`");
+        }
+        let mut supported_flags = map_private | map_shared | map_anon;
+        if this.tcx.sess.target.os == "linux" {
+            // We never reserve memory up front anyway.
+            supported_flags |= this.eval_libc_i32("MAP_NORESERVE")?;
+        }
+        if flags & !supported_flags != 0 {
+            throw_unsup_format!("unsupported flags {:#x} for `mmap`", flags);
+        }
+        let protection = this.protection(prot, "mmap")?;
+
+        // Exactly one of `MAP_PRIVATE` and `MAP_SHARED` has to be set.
+        if (flags & map_private != 0) == (flags & map_shared != 0) || length == 0 {
+            return this.map_failed("EINVAL");
+        }
+        let Some(length) = round_to_pages(length)
+            .filter(|&length| length <= u64::try_from(this.machine_isize_max()).unwrap())
+        else {
+            return this.map_failed("ENOMEM");
+        };
+
+        let is_file = flags & map_anon == 0;
+        let mut file_offset = 0;
+        if is_file {
+            if flags & map_shared != 0 {
+                throw_unsup_format!("shared file mappings are not supported by `mmap`");
+            }
+            let Ok(offset) = u64::try_from(offset) else {
+                return this.map_failed("EINVAL");
+            };
+            if !is_page_aligned(offset) {
+                return this.map_failed("EINVAL");
+            }
+            let Some(file_descriptor) = this.machine.file_handler.handles.get(&fd) else {
+                return this.map_failed("EBADF");
+            };
+            if file_descriptor.access_mode() == AccessMode::WriteOnly {
+                return this.map_failed("EACCES");
+            }
+            file_offset = offset;
+        }
+
+        let ptr = match this.allocate_ptr(
+            Size::from_bytes(length),
+            Align::from_bytes(PAGE_SIZE).unwrap(),
+            MiriMemoryKind::Mmap.into(),
+        ) {
+            Ok(ptr) => ptr,
+            Err(e) if matches!(e.kind(), InterpError::ResourceExhaustion(_)) =>
+                return this.map_failed("ENOMEM"),
+            Err(e) => return Err(e),
+        };
+        // New allocations are zeroed, so marking the memory as initialized is enough for the
+        // anonymous parts. The program cannot have accessed the memory yet, so this uses raw
+        // accesses.
+        let (alloc_id, ..) = this.ptr_get_alloc_id(ptr.into())?;
+        let tcx = *this.tcx;
+        let (alloc, machine) = this.get_alloc_raw_mut(alloc_id)?;
+        let bytes = alloc
+            .get_bytes_mut(&tcx, alloc_range(Size::ZERO, Size::from_bytes(length)))
+            .map_err(|e| e.to_interp_error(alloc_id))?;
+        if is_file {
+            // The part of the last page after the end of the file is zeroed. Accessing pages after
+            // that would raise `SIGBUS`, we just zero them as well.
+            let file_descriptor = machine.file_handler.handles.get(&fd).unwrap();
+            if let Err(e) = file_descriptor.as_file_handle()?.read_at(bytes, file_offset)? {
+                this.deallocate_ptr(ptr.into(), None, MiriMemoryKind::Mmap.into())?;
+                this.set_last_error_from_io_error(e.kind())?;
+                return Ok(Scalar::from_machine_isize(-1, this));
+            }
+        }
+        // Only protect the memory after initializing it.
+        let (alloc_extra, _) = this.get_alloc_extra_mut(alloc_id)?;
+        let mapping = alloc_extra.mapping.as_mut().unwrap();
+        mapping.pages.iter_mut_all().for_each(|page| {
+            *page = Some(protection);
+        });
+        mapping.is_file = is_file;
+        Ok(Scalar::from_pointer(ptr, this))
+    }
+
+    /// Unmapping the middle of a mapping leaves the parts before and after it mapped, and the
+    /// allocation is freed once none of it is mapped anymore.
+    fn munmap(
+        &mut self,
+        addr: &OpTy<'tcx, Provenance>,
+        length: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let addr = this.read_pointer(addr)?;
+        let length = this.read_scalar(length)?.to_machine_usize(this)?;
+
+        let Some((alloc_id, offset, length)) = this.page_range(addr, length, "munmap")? else {
+            return Ok(-1);
+        };
+        let (size, align, _) = this.get_alloc_info(alloc_id);
+        let (alloc_extra, _) = this.get_alloc_extra_mut(alloc_id)?;
+        let Some(mapping) = &mut alloc_extra.mapping else {
+            throw_ub_format!("`munmap` on {:?}, which was not allocated by `mmap`", alloc_id);
+        };
+
+        for (_, page) in mapping.pages.iter_mut(offset, length) {
+            *page = None;
+        }
+        if mapping.pages.iter(Size::ZERO, size).all(|(_, page)| page.is_none()) {
+            let (provenance, addr) = addr.into_parts();
+            let base = Pointer::new(provenance, addr - offset);
+            this.deallocate_ptr(base, Some((size, align)), MiriMemoryKind::Mmap.into())?;
+        }
+        Ok(0)
+    }
+
+    fn mprotect(
+        &mut self,
+        addr: &OpTy<'tcx, Provenance>,
+        length: &OpTy<'tcx, Provenance>,
+        prot: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let addr = this.read_pointer(addr)?;
+        let length = this.read_scalar(length)?.to_machine_usize(this)?;
+        let prot = this.read_scalar(prot)?.to_i32()?;
+        let protection = this.protection(prot, "mprotect")?;
+
+        let is_mmap = match this.ptr_try_get_alloc_id(addr) {
+            Ok((alloc_id, ..)) => this.get_alloc_extra(alloc_id)?.mapping.is_some(),
+            Err(_) => false,
+        };
+        if !is_mmap {
+            if this.frame_in_std() {
+                // libstd protects the guard page of the main thread's stack, which is not
+                // allocated by `mmap`. We do not have guard pages, so we just pretend it worked.
+                return Ok(0);
+            }
+            throw_unsup_format!("`mprotect` is only supported on memory allocated by `mmap`");
+        }
+        let Some((alloc_id, offset, length)) = this.page_range(addr, length, "mprotect")? else {
+            return Ok(-1);
+        };
+        let (alloc_extra, _) = this.get_alloc_extra_mut(alloc_id)?;
+        let pages = &mut alloc_extra.mapping.as_mut().unwrap().pages;
+        if pages.iter(offset, length).any(|(_, page)| page.is_none()) {
+            let enomem = this.eval_libc("ENOMEM")?;
+            this.set_last_error(enomem)?;
+            return Ok(-1);
+        }
+        for (_, page) in pages.iter_mut(offset, length) {
+            *page = Some(protection);
+        }
+        Ok(0)
+    }
+
+    /// Advice about the access pattern makes no difference. `MADV_DONTNEED` and `MADV_FREE`
+    /// discard the contents of anonymous pages, which read as zeros afterwards. Even though
+    /// `MADV_FREE` only discards them once the memory is needed elsewhere, that is always assumed
+    /// to happen right away, to catch code that relies on the contents.
+    fn madvise(
+        &mut self,
+        addr: &OpTy<'tcx, Provenance>,
+        length: &OpTy<'tcx, Provenance>,
+        advice: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let addr = this.read_pointer(addr)?;
+        let length = this.read_scalar(length)?.to_machine_usize(this)?;
+        let advice = this.read_scalar(advice)?.to_i32()?;
+
+        let mut hints = vec![
+            this.eval_libc_i32("MADV_NORMAL")?,
+            this.eval_libc_i32("MADV_RANDOM")?,
+            this.eval_libc_i32("MADV_SEQUENTIAL")?,
+            this.eval_libc_i32("MADV_WILLNEED")?,
+        ];
+        let mut discarding = vec![this.eval_libc_i32("MADV_DONTNEED")?];
+        let os = this.tcx.sess.target.os.as_ref();
+        if matches!(os, "linux" | "macos" | "freebsd") {
+            discarding.push(this.eval_libc_i32("MADV_FREE")?);
+        }
+        if os == "linux" {
+            for name in ["MADV_HUGEPAGE", "MADV_NOHUGEPAGE", "MADV_DONTDUMP", "MADV_DODUMP"] {
+                hints.push(this.eval_libc_i32(name)?);
+            }
+        }
+
+        let range = if hints.contains(&advice) || discarding.contains(&advice) {
+            this.advice_range(addr, length, "madvise")?
+        } else {
+            Err("EINVAL")
+        };
+        let (alloc_id, offset, length) = match range {
+            Ok(Some(range)) => range,
+            Ok(None) => return Ok(0),
+            Err(error) => {
+                let error = this.eval_libc(error)?;
+                this.set_last_error(error)?;
+                return Ok(-1);
+            }
+        };
+        if !discarding.contains(&advice) {
+            return Ok(0);
+        }
+
+        if this.get_alloc_extra(alloc_id)?.mapping.as_ref().unwrap().is_file {
+            throw_unsup_format!("`madvise` cannot discard the pages of a file mapping");
+        }
+        // The kernel replaces the pages, so this is not a write by the program: it ignores the
+        // protection of the pages, does not race with other threads, and references to the pages
+        // lose their permissions like after a fresh `mmap`.
+        let tcx = *this.tcx;
+        let range = alloc_range(offset, length);
+        let (alloc, machine) = this.get_alloc_raw_mut(alloc_id)?;
+        alloc.get_bytes_mut(&tcx, range).map_err(|e| e.to_interp_error(alloc_id))?.fill(0);
+        if let Some(stacked_borrows) = &alloc.extra.stacked_borrows {
+            let state = machine.stacked_borrows.as_ref().unwrap();
+            stacked_borrows.borrow_mut().reset_range(alloc_id, range, state);
+        }
+        Ok(0)
+    }
+
+    /// Like `madvise`, but returns the error instead of setting `errno`. Like in glibc,
+    /// `POSIX_MADV_DONTNEED` does not discard the contents of the pages.
+    fn posix_madvise(
+        &mut self,
+        addr: &OpTy<'tcx, Provenance>,
+        length: &OpTy<'tcx, Provenance>,
+        advice: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let addr = this.read_pointer(addr)?;
+        let length = this.read_scalar(length)?.to_machine_usize(this)?;
+        let advice = this.read_scalar(advice)?.to_i32()?;
+
+        let mut known = false;
+        for name in [
+            "POSIX_MADV_NORMAL",
+            "POSIX_MADV_RANDOM",
+            "POSIX_MADV_SEQUENTIAL",
+            "POSIX_MADV_WILLNEED",
+            "POSIX_MADV_DONTNEED",
+        ] {
+            known |= this.eval_libc_i32(name)? == advice;
+        }
+        let range =
+            if known { this.advice_range(addr, length, "posix_madvise")? } else { Err("EINVAL") };
+        match range {
+            Ok(_) => Ok(0),
+            Err(error) => this.eval_libc_i32(error),
+        }
+    }
+}
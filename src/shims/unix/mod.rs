@@ -1,16 +1,34 @@
 pub mod dlsym;
 pub mod foreign_items;
 
+mod calendar;
 mod fs;
+mod locale;
+mod mem;
+mod netdb;
+mod pipe;
+mod process;
+mod resource;
+mod signal;
+mod socket;
 mod sync;
+mod syslog;
 mod thread;
+mod user;
 
 mod android;
 mod freebsd;
 mod linux;
 mod macos;
 
-pub use fs::{DirHandler, FileHandler};
+pub use fs::{BlockedIo, DirHandler, EvalContextExt as FsEvalContextExt, FileHandler};
+pub use linux::epoll::EpollWaiter;
+pub use locale::LocaleState;
+pub use mem::Mapping;
+pub use resource::ResourceLimits;
+pub use signal::{EvalContextExt as SignalEvalContextExt, SignalHandlerData, SignalState};
+pub use syslog::Syslog;
+pub use macos::dispatch::{
+    DispatchOnceData, DispatchState, EvalContextExt as DispatchEvalContextExt,
+};
 
-// Make up some constants.
-const UID: u32 = 1000;
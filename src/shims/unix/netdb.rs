@@ -0,0 +1,229 @@
+//! Emulation of `getaddrinfo`. Names are never looked up on the host, so the results are the same
+//! on every run: numeric IPv4 addresses resolve to themselves, `localhost` resolves to
+//! `127.0.0.1`, and other names only resolve if they were given with `-Zmiri-host`. Only IPv4
+//! addresses are returned, and services can only be given as port numbers.
+
+use std::ffi::OsStr;
+use std::net::Ipv4Addr;
+use std::str;
+
+use rustc_ast::Mutability;
+
+use crate::*;
+
+/// The messages of `gai_strerror`, like glibc has them.
+const GAI_ERRORS: &[(&str, &str)] = &[
+    ("EAI_AGAIN", "Temporary failure in name resolution"),
+    ("EAI_BADFLAGS", "Bad value for ai_flags"),
+    ("EAI_FAIL", "Non-recoverable failure in name resolution"),
+    ("EAI_FAMILY", "ai_family not supported"),
+    ("EAI_MEMORY", "Memory allocation failure"),
+    ("EAI_NONAME", "Name or service not known"),
+    ("EAI_SERVICE", "Servname not supported for ai_socktype"),
+    ("EAI_SOCKTYPE", "ai_socktype not supported"),
+    ("EAI_SYSTEM", "System error"),
+];
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// The address of the host `name`, or `None` if it is unknown. Unless `numeric_only` is set,
+    /// `localhost` and the names given with `-Zmiri-host` are known as well.
+    fn resolve_host(&self, name: &[u8], numeric_only: bool) -> Option<Ipv4Addr> {
+        let this = self.eval_context_ref();
+        let name = str::from_utf8(name).ok()?;
+        if let Ok(ip) = name.parse() {
+            return Some(ip);
+        }
+        if numeric_only {
+            return None;
+        }
+        if name.eq_ignore_ascii_case("localhost") {
+            return Some(Ipv4Addr::LOCALHOST);
+        }
+        let hosts = &this.machine.hosts;
+        let (_, ip) = hosts.iter().find(|(host, _)| host.eq_ignore_ascii_case(name))?;
+        Some(*ip)
+    }
+
+    /// Allocate an `addrinfo` structure and the `sockaddr_in` it points to, with the given fields.
+    fn alloc_addrinfo(
+        &mut self,
+        flags: i32,
+        (socktype, protocol): (i32, i32),
+        (ip, port): (Ipv4Addr, u16),
+        canonname: Pointer<Option<Provenance>>,
+        next: Pointer<Option<Provenance>>,
+    ) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        let this = self.eval_context_mut();
+
+        let af_inet = this.eval_libc_i32("AF_INET")?;
+
+        // Both structures are zeroed, so that their padding and the fields that only some targets
+        // have, like `sin_len`, are initialized.
+        let sockaddr_in = this.libc_ty_layout("sockaddr_in")?;
+        let addr = this.malloc(sockaddr_in.size.bytes(), /*zero_init:*/ true, MiriMemoryKind::C)?;
+        let addr_place = MPlaceTy::from_aligned_ptr(addr, sockaddr_in);
+        let family = this.mplace_field_named(&addr_place, "sin_family")?;
+        this.write_int(af_inet, &family.into())?;
+        // Both the address and the port are in network byte order.
+        let sin_port = this.mplace_field_named(&addr_place, "sin_port")?;
+        this.write_bytes_ptr(sin_port.ptr, port.to_be_bytes())?;
+        let sin_addr = this.mplace_field_named(&addr_place, "sin_addr")?;
+        this.write_bytes_ptr(sin_addr.ptr, ip.octets())?;
+
+        let addrinfo = this.libc_ty_layout("addrinfo")?;
+        let info = this.malloc(addrinfo.size.bytes(), /*zero_init:*/ true, MiriMemoryKind::C)?;
+        let info_place = MPlaceTy::from_aligned_ptr(info, addrinfo);
+        this.write_int_fields_named(
+            &[
+                ("ai_flags", flags.into()),
+                ("ai_family", af_inet.into()),
+                ("ai_socktype", socktype.into()),
+                ("ai_protocol", protocol.into()),
+                ("ai_addrlen", sockaddr_in.size.bytes().into()),
+            ],
+            &info_place,
+        )?;
+        for (field, ptr) in [("ai_addr", addr), ("ai_canonname", canonname), ("ai_next", next)] {
+            let field = this.mplace_field_named(&info_place, field)?;
+            this.write_pointer(ptr, &field.into())?;
+        }
+        Ok(info)
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Returns 0 or one of the `EAI_*` error codes. Without a socket type in `hints`, there is an
+    /// entry for TCP and one for UDP, like glibc returns them.
+    fn getaddrinfo(
+        &mut self,
+        node_op: &OpTy<'tcx, Provenance>,
+        service_op: &OpTy<'tcx, Provenance>,
+        hints_op: &OpTy<'tcx, Provenance>,
+        res_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        let node = this.read_pointer(node_op)?;
+        let service = this.read_pointer(service_op)?;
+        let hints = this.read_pointer(hints_op)?;
+        let res = this.deref_operand(res_op)?;
+
+        let (flags, family, socktype, protocol) = if this.ptr_is_null(hints)? {
+            (0, this.eval_libc_i32("AF_UNSPEC")?, 0, 0)
+        } else {
+            let hints = MPlaceTy::from_aligned_ptr(hints, this.libc_ty_layout("addrinfo")?);
+            let read_field = |name| -> InterpResult<'tcx, i32> {
+                let field = this.mplace_field_named(&hints, name)?;
+                this.read_scalar(&field.into())?.to_i32()
+            };
+            (
+                read_field("ai_flags")?,
+                read_field("ai_family")?,
+                read_field("ai_socktype")?,
+                read_field("ai_protocol")?,
+            )
+        };
+        // The other flags only make a difference for IPv6 addresses or depending on the network
+        // interfaces of the host, so they are ignored.
+        let ai_passive = this.eval_libc_i32("AI_PASSIVE")?;
+        let ai_canonname = this.eval_libc_i32("AI_CANONNAME")?;
+        let ai_numerichost = this.eval_libc_i32("AI_NUMERICHOST")?;
+
+        if family != this.eval_libc_i32("AF_UNSPEC")? && family != this.eval_libc_i32("AF_INET")? {
+            return this.eval_libc("EAI_FAMILY");
+        }
+        let sock_stream = (this.eval_libc_i32("SOCK_STREAM")?, this.eval_libc_i32("IPPROTO_TCP")?);
+        let sock_dgram = (this.eval_libc_i32("SOCK_DGRAM")?, this.eval_libc_i32("IPPROTO_UDP")?);
+        let socktypes: Vec<_> = [sock_stream, sock_dgram]
+            .into_iter()
+            .filter(|&(type_, proto)| {
+                (socktype == 0 || socktype == type_) && (protocol == 0 || protocol == proto)
+            })
+            .collect();
+        if socktypes.is_empty() {
+            return this.eval_libc("EAI_SOCKTYPE");
+        }
+
+        if this.ptr_is_null(node)? && this.ptr_is_null(service)? {
+            return this.eval_libc("EAI_NONAME");
+        }
+        let port = if this.ptr_is_null(service)? {
+            0
+        } else {
+            let service = this.read_c_str(service)?;
+            match str::from_utf8(service).ok().and_then(|service| service.parse().ok()) {
+                Some(port) => port,
+                None => return this.eval_libc("EAI_SERVICE"),
+            }
+        };
+        let (ip, canonname) = if this.ptr_is_null(node)? {
+            // Without a node, the address is the one to bind to or the one to connect to.
+            let ip =
+                if flags & ai_passive != 0 { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+            (ip, None)
+        } else {
+            let name = this.read_c_str(node)?;
+            match this.resolve_host(name, flags & ai_numerichost != 0) {
+                Some(ip) => (ip, Some(String::from_utf8_lossy(name).into_owned())),
+                None => return this.eval_libc("EAI_NONAME"),
+            }
+        };
+
+        // The canonical name is only returned in the first entry.
+        let canonname = match canonname {
+            Some(name) if flags & ai_canonname != 0 =>
+                this.alloc_os_str_as_c_str(OsStr::new(&name), MiriMemoryKind::C.into())?,
+            _ => Pointer::null(),
+        };
+        let mut list = Pointer::null();
+        for (i, &socktype) in socktypes.iter().enumerate().rev() {
+            let canonname = if i == 0 { canonname } else { Pointer::null() };
+            list = this.alloc_addrinfo(flags, socktype, (ip, port), canonname, list)?;
+        }
+        this.write_pointer(list, &res.into())?;
+
+        Ok(Scalar::from_i32(0))
+    }
+
+    fn freeaddrinfo(&mut self, res_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let addrinfo = this.libc_ty_layout("addrinfo")?;
+        let mut info = this.read_pointer(res_op)?;
+        while !this.ptr_is_null(info)? {
+            let info_place = MPlaceTy::from_aligned_ptr(info, addrinfo);
+            let read_field = |name| -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+                let field = this.mplace_field_named(&info_place, name)?;
+                this.read_pointer(&field.into())
+            };
+            let (addr, canonname, next) =
+                (read_field("ai_addr")?, read_field("ai_canonname")?, read_field("ai_next")?);
+            this.free(addr, MiriMemoryKind::C)?;
+            this.free(canonname, MiriMemoryKind::C)?;
+            this.free(info, MiriMemoryKind::C)?;
+            info = next;
+        }
+        Ok(())
+    }
+
+    /// The messages are allocated on every call and never freed, which is fine since they are
+    /// immutable.
+    fn gai_strerror(
+        &mut self,
+        errcode_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Pointer<Option<Provenance>>> {
+        let this = self.eval_context_mut();
+
+        let errcode = this.read_scalar(errcode_op)?.to_i32()?;
+        let mut message = "Unknown error";
+        for &(name, error_message) in GAI_ERRORS {
+            if this.eval_libc_i32(name)? == errcode {
+                message = error_message;
+            }
+        }
+        let message = format!("{message}\0");
+        Ok(this.allocate_str(&message, MiriMemoryKind::Machine.into(), Mutability::Not).ptr)
+    }
+}
@@ -0,0 +1,208 @@
+//! Emulation of `pipe` and `pipe2`. The pipes are in-memory buffers that never leave the
+//! interpreter.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::io;
+use std::rc::Rc;
+
+use crate::concurrency::data_race;
+use crate::concurrency::vector_clock::VClock;
+use crate::*;
+use shims::unix::fs::{AccessMode, FileDescriptor};
+use shims::unix::linux::epoll::EpollReadiness;
+use shims::unix::resource::EvalContextExt as _;
+
+/// The number of bytes a pipe can hold, which is the default on Linux.
+const PIPE_CAPACITY: usize = 65536;
+
+/// Writes of at most this many bytes are atomic: they are never interleaved with other writes,
+/// so they wait until there is room for all of the data. This is `PIPE_BUF` on Linux.
+const PIPE_BUF: usize = 4096;
+
+/// The bytes in a pipe.
+#[derive(Debug, Default)]
+struct PipeBuffer {
+    data: VecDeque<u8>,
+    /// Whether all file descriptors of the write end were closed.
+    writer_closed: bool,
+    /// Whether all file descriptors of the read end were closed.
+    reader_closed: bool,
+    /// Incremented on every change, see [`EpollReadiness::generation`].
+    generation: u64,
+    /// The clocks of all threads that wrote to the pipe, so that writing data happens-before
+    /// reading it.
+    clock: VClock,
+}
+
+/// One end of a pipe, created by `pipe` or `pipe2`.
+#[derive(Debug)]
+struct Pipe {
+    buffer: Rc<RefCell<PipeBuffer>>,
+    /// Whether this is the write end.
+    is_writer: bool,
+    /// Shared between duplicates of the file descriptor, which also tells when the last one is
+    /// closed.
+    is_nonblock: Rc<Cell<bool>>,
+}
+
+impl FileDescriptor for Pipe {
+    fn name(&self) -> &'static str {
+        "pipe"
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        if self.is_writer { AccessMode::WriteOnly } else { AccessMode::ReadOnly }
+    }
+
+    fn nonblock_flag(&self) -> Option<&Cell<bool>> {
+        Some(&self.is_nonblock)
+    }
+
+    fn read<'tcx>(
+        &mut self,
+        _communicate_allowed: bool,
+        bytes: &mut [u8],
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        if self.is_writer {
+            // Reading from the write end fails with `EBADF`.
+            throw_unsup_format!("reading from the write end of a pipe is not supported");
+        }
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.data.is_empty() && !bytes.is_empty() {
+            if buffer.writer_closed {
+                // End of file.
+                return Ok(Ok(0));
+            }
+            // Unless the pipe is non-blocking, the thread waits for data to arrive.
+            return Ok(Err(io::ErrorKind::WouldBlock.into()));
+        }
+        let len = bytes.len().min(buffer.data.len());
+        for (byte, data) in bytes.iter_mut().zip(buffer.data.drain(..len)) {
+            *byte = data;
+        }
+        buffer.generation = buffer.generation.wrapping_add(1);
+        Ok(Ok(len))
+    }
+
+    fn write<'tcx>(
+        &self,
+        _communicate_allowed: bool,
+        bytes: &[u8],
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        if !self.is_writer {
+            throw_unsup_format!("writing to the read end of a pipe is not supported");
+        }
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.reader_closed {
+            return Ok(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        let free = PIPE_CAPACITY.checked_sub(buffer.data.len()).unwrap();
+        if free == 0 || (bytes.len() <= PIPE_BUF && bytes.len() > free) {
+            // Unless the pipe is non-blocking, the thread waits for room in the pipe. Larger
+            // writes write as much as fits, and return how much that was.
+            return Ok(Err(io::ErrorKind::WouldBlock.into()));
+        }
+        let len = bytes.len().min(free);
+        buffer.data.extend(&bytes[..len]);
+        buffer.generation = buffer.generation.wrapping_add(1);
+        Ok(Ok(len))
+    }
+
+    fn release_clock(&self, data_race: &data_race::GlobalState, thread: ThreadId) {
+        data_race.validate_lock_release_shared(&mut self.buffer.borrow_mut().clock, thread);
+    }
+
+    fn acquire_clock(&self, data_race: &data_race::GlobalState, thread: ThreadId) {
+        data_race.validate_lock_acquire(&self.buffer.borrow().clock, thread);
+    }
+
+    fn epoll_readiness(&self) -> Option<EpollReadiness> {
+        let buffer = self.buffer.borrow();
+        Some(if self.is_writer {
+            EpollReadiness {
+                readable: false,
+                writable: buffer.data.len() < PIPE_CAPACITY || buffer.reader_closed,
+                generation: buffer.generation,
+            }
+        } else {
+            EpollReadiness {
+                readable: !buffer.data.is_empty() || buffer.writer_closed,
+                writable: false,
+                generation: buffer.generation,
+            }
+        })
+    }
+
+    fn dup(&mut self) -> io::Result<Box<dyn FileDescriptor>> {
+        Ok(Box::new(Pipe {
+            buffer: Rc::clone(&self.buffer),
+            is_writer: self.is_writer,
+            is_nonblock: Rc::clone(&self.is_nonblock),
+        }))
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+    ) -> InterpResult<'tcx, io::Result<i32>> {
+        if Rc::strong_count(&self.is_nonblock) == 1 {
+            let mut buffer = self.buffer.borrow_mut();
+            if self.is_writer {
+                buffer.writer_closed = true;
+            } else {
+                buffer.reader_closed = true;
+            }
+            buffer.generation = buffer.generation.wrapping_add(1);
+        }
+        Ok(Ok(0))
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// `pipe` is `pipe2` with `flags` set to `None`.
+    fn pipe2(
+        &mut self,
+        pipefd: &OpTy<'tcx, Provenance>,
+        flags: Option<&OpTy<'tcx, Provenance>>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let pipefd = this.deref_operand(pipefd)?;
+        let mut flags = match flags {
+            Some(flags) => this.read_scalar(flags)?.to_i32()?,
+            None => 0,
+        };
+
+        let o_nonblock = this.eval_libc_i32("O_NONBLOCK")?;
+        let is_nonblock = flags & o_nonblock != 0;
+        // `exec` is not supported, so we can ignore `O_CLOEXEC`.
+        flags &= !(o_nonblock | this.eval_libc_i32("O_CLOEXEC")?);
+        if flags != 0 {
+            throw_unsup_format!("unsupported flags {:#x} for `pipe2`", flags);
+        }
+
+        if !this.check_fd_limit(2)? {
+            return Ok(-1);
+        }
+        let buffer = Rc::new(RefCell::new(PipeBuffer::default()));
+        let fh = &mut this.machine.file_handler;
+        let reader = fh.insert_fd(Box::new(Pipe {
+            buffer: Rc::clone(&buffer),
+            is_writer: false,
+            is_nonblock: Rc::new(Cell::new(is_nonblock)),
+        }));
+        let writer = fh.insert_fd(Box::new(Pipe {
+            buffer,
+            is_writer: true,
+            is_nonblock: Rc::new(Cell::new(is_nonblock)),
+        }));
+
+        this.write_scalar(Scalar::from_i32(reader), &pipefd.into())?;
+        let pipefd1 = pipefd.offset(pipefd.layout.size, pipefd.layout, this)?;
+        this.write_scalar(Scalar::from_i32(writer), &pipefd1.into())?;
+
+        Ok(0)
+    }
+}
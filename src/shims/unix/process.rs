@@ -0,0 +1,58 @@
+//! Miri only interprets a single process, so `fork` and `posix_spawn` cannot create another one.
+//! By default, calling them is reported as unsupported. With `-Zmiri-fork-fails`, they fail with
+//! `EAGAIN` instead, as if the process limit had been reached, so that programs that can do
+//! without the new process keep running.
+
+use rustc_span::Symbol;
+
+use crate::*;
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Reports that `link_name` cannot create a process, or returns `EAGAIN` with
+    /// `-Zmiri-fork-fails`.
+    fn refuse_process_creation(
+        &mut self,
+        link_name: Symbol,
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        if !this.machine.fork_fails {
+            throw_unsup_format!(
+                "`{link_name}` is not supported: Miri interprets a single process and cannot start \
+                another one; pass `-Zmiri-fork-fails` to make `{link_name}` fail with `EAGAIN` instead"
+            );
+        }
+        register_diagnostic(NonHaltingDiagnostic::FailedProcessCreation(link_name));
+        this.eval_libc("EAGAIN")
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// `fork` and `vfork`.
+    fn fork(&mut self, link_name: Symbol) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let eagain = this.refuse_process_creation(link_name)?;
+        this.set_last_error(eagain)?;
+        Ok(-1)
+    }
+
+    /// `posix_spawn` and `posix_spawnp`, which return the error code instead of setting the last
+    /// error. All of their arguments are pointers: `pid`, `path`, `file_actions`, `attrp`, `argv`
+    /// and `envp`.
+    fn posix_spawn(
+        &mut self,
+        link_name: Symbol,
+        args: &[OpTy<'tcx, Provenance>; 6],
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        for arg in args {
+            this.read_pointer(arg)?;
+        }
+
+        this.refuse_process_creation(link_name)
+    }
+}
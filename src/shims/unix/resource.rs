@@ -0,0 +1,239 @@
+//! Emulation of `getrlimit`, `setrlimit` and `prlimit64`. The limits are synthetic: they start
+//! out as the defaults in `RLIMITS` or the values set with `-Zmiri-rlimit`, and only change when
+//! the program changes them. Only `RLIMIT_NOFILE` is ever enforced, and only with
+//! `-Zmiri-enforce-rlimit-nofile`.
+
+use rustc_data_structures::fx::FxHashMap;
+
+use crate::*;
+
+#[derive(Debug)]
+pub struct ResourceLimits {
+    /// The soft and hard limit of each resource, by the name of its `RLIMIT_*` constant.
+    /// `u64::MAX` means unlimited.
+    limits: FxHashMap<&'static str, (u64, u64)>,
+    /// Whether opening a file descriptor fails with `EMFILE` when that would exceed the soft
+    /// `RLIMIT_NOFILE` limit.
+    enforce_nofile: bool,
+}
+
+impl ResourceLimits {
+    pub(crate) fn new(config: &MiriConfig) -> Self {
+        let mut limits: FxHashMap<_, _> =
+            RLIMITS.iter().map(|&(resource, soft, hard)| (resource, (soft, hard))).collect();
+        for &(resource, soft, hard) in &config.rlimits {
+            limits.insert(resource, (soft, hard));
+        }
+        ResourceLimits { limits, enforce_nofile: config.enforce_rlimit_nofile }
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// The name of the `RLIMIT_*` constant with the value `resource`. Returns `None` and sets the
+    /// last error to `EINVAL` if `resource` is negative.
+    fn resource_name(
+        &mut self,
+        resource: i32,
+        name: &str,
+    ) -> InterpResult<'tcx, Option<&'static str>> {
+        let this = self.eval_context_mut();
+
+        for &(resource_name, ..) in RLIMITS {
+            if this.eval_libc_i32(resource_name)? == resource {
+                return Ok(Some(resource_name));
+            }
+        }
+        if resource < 0 {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(None);
+        }
+        throw_unsup_format!("resource {} is not supported by `{}`", resource, name);
+    }
+
+    /// The value that means unlimited in the `rlimit` or `rlimit64` structure `rlim`. On 32-bit
+    /// Linux, only `rlimit64` has 64-bit fields and uses `RLIM64_INFINITY`.
+    fn rlim_infinity(&self, rlim: &MPlaceTy<'tcx, Provenance>) -> InterpResult<'tcx, u64> {
+        let this = self.eval_context_ref();
+        let size = this.mplace_field_named(rlim, "rlim_cur")?.layout.size;
+        let mut infinity = this.eval_libc("RLIM_INFINITY")?;
+        if infinity.size() != size {
+            infinity = this.eval_libc("RLIM64_INFINITY")?;
+        }
+        Ok(u64::try_from(infinity.to_bits(size)?).unwrap())
+    }
+
+    fn read_rlimit(&self, rlim: &MPlaceTy<'tcx, Provenance>) -> InterpResult<'tcx, (u64, u64)> {
+        let this = self.eval_context_ref();
+        let infinity = this.rlim_infinity(rlim)?;
+        let read_field = |name| -> InterpResult<'tcx, u64> {
+            let field = this.mplace_field_named(rlim, name)?;
+            let value = this.read_scalar(&field.into())?.to_bits(field.layout.size)?;
+            let value = u64::try_from(value).unwrap();
+            Ok(if value == infinity { u64::MAX } else { value })
+        };
+        Ok((read_field("rlim_cur")?, read_field("rlim_max")?))
+    }
+
+    /// Limits that do not fit into the fields of `rlim` are written as unlimited, like glibc does.
+    fn write_rlimit(
+        &mut self,
+        (soft, hard): (u64, u64),
+        rlim: &MPlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let infinity = this.rlim_infinity(rlim)?;
+        this.write_int_fields_named(
+            &[("rlim_cur", soft.min(infinity).into()), ("rlim_max", hard.min(infinity).into())],
+            rlim,
+        )
+    }
+
+    fn set_rlimit(
+        &mut self,
+        resource: &'static str,
+        (soft, hard): (u64, u64),
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let (_, old_hard) = this.machine.rlimits.limits[resource];
+        if soft > hard {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+        if hard > old_hard {
+            let eperm = this.eval_libc("EPERM")?;
+            this.set_last_error(eperm)?;
+            return Ok(-1);
+        }
+        this.machine.rlimits.limits.insert(resource, (soft, hard));
+        Ok(0)
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// `getrlimit` and `getrlimit64`. `rlim_op` points to an `rlimit` or `rlimit64` structure.
+    fn getrlimit(
+        &mut self,
+        resource_op: &OpTy<'tcx, Provenance>,
+        rlim_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let resource = this.read_scalar(resource_op)?.to_i32()?;
+        let rlim = this.deref_operand(rlim_op)?;
+
+        let Some(resource) = this.resource_name(resource, "getrlimit")? else {
+            return Ok(-1);
+        };
+        let limit = this.machine.rlimits.limits[resource];
+        this.write_rlimit(limit, &rlim)?;
+        Ok(0)
+    }
+
+    /// `setrlimit` and `setrlimit64`. The soft limit cannot exceed the hard limit, and the hard
+    /// limit can only be lowered, since the program is not privileged.
+    fn setrlimit(
+        &mut self,
+        resource_op: &OpTy<'tcx, Provenance>,
+        rlim_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let resource = this.read_scalar(resource_op)?.to_i32()?;
+        let rlim = this.deref_operand(rlim_op)?;
+
+        let Some(resource) = this.resource_name(resource, "setrlimit")? else {
+            return Ok(-1);
+        };
+        let limit = this.read_rlimit(&rlim)?;
+        this.set_rlimit(resource, limit)
+    }
+
+    /// `prlimit64` is `getrlimit` followed by `setrlimit`, either of which is skipped if its
+    /// pointer is null. Only the limits of the current process can be queried and set.
+    fn prlimit64(
+        &mut self,
+        pid_op: &OpTy<'tcx, Provenance>,
+        resource_op: &OpTy<'tcx, Provenance>,
+        new_limit_op: &OpTy<'tcx, Provenance>,
+        old_limit_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let pid = this.read_scalar(pid_op)?.to_i32()?;
+        let resource = this.read_scalar(resource_op)?.to_i32()?;
+        let new_limit = this.read_pointer(new_limit_op)?;
+        let old_limit = this.read_pointer(old_limit_op)?;
+
+        if pid != 0 && pid != this.getpid()? {
+            let esrch = this.eval_libc("ESRCH")?;
+            this.set_last_error(esrch)?;
+            return Ok(-1);
+        }
+        let Some(resource) = this.resource_name(resource, "prlimit64")? else {
+            return Ok(-1);
+        };
+        let rlimit64 = this.libc_ty_layout("rlimit64")?;
+        let new_limit = if this.ptr_is_null(new_limit)? {
+            None
+        } else {
+            Some(this.read_rlimit(&MPlaceTy::from_aligned_ptr(new_limit, rlimit64))?)
+        };
+        if !this.ptr_is_null(old_limit)? {
+            let limit = this.machine.rlimits.limits[resource];
+            this.write_rlimit(limit, &MPlaceTy::from_aligned_ptr(old_limit, rlimit64))?;
+        }
+        match new_limit {
+            Some(new_limit) => this.set_rlimit(resource, new_limit),
+            None => Ok(0),
+        }
+    }
+
+    /// Whether `count` more file descriptors can be opened without exceeding the soft
+    /// `RLIMIT_NOFILE` limit. If not, the last error is set to `EMFILE`. Since new file
+    /// descriptors are always the lowest ones available, this is the case if there are enough
+    /// free ones below the limit.
+    fn check_fd_limit(&mut self, count: u64) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_mut();
+
+        if !this.machine.rlimits.enforce_nofile {
+            return Ok(true);
+        }
+        let (limit, _) = this.machine.rlimits.limits["RLIMIT_NOFILE"];
+        let open = this.machine.file_handler.count_fds_below(limit);
+        if open.saturating_add(count) > limit {
+            let emfile = this.eval_libc("EMFILE")?;
+            this.set_last_error(emfile)?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Like `check_fd_limit` for a single file descriptor that has to be at least `min_fd`, as
+    /// for `F_DUPFD`. If `min_fd` itself is not below the limit, the last error is set to
+    /// `EINVAL` instead.
+    fn check_fd_limit_from(&mut self, min_fd: i32) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_mut();
+
+        if !this.machine.rlimits.enforce_nofile {
+            return Ok(true);
+        }
+        let (limit, _) = this.machine.rlimits.limits["RLIMIT_NOFILE"];
+        if u64::try_from(min_fd).unwrap() >= limit {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(false);
+        }
+        let new_fd = this.machine.file_handler.lowest_free_fd(min_fd);
+        if u64::try_from(new_fd).unwrap() >= limit {
+            let emfile = this.eval_libc("EMFILE")?;
+            this.set_last_error(emfile)?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,587 @@
+//! Emulation of signal handling. The program can only send signals to itself, with `raise` or
+//! `pthread_kill` on the calling thread, and the handler registered with `sigaction` or `signal`
+//! is then called right away, as if the signal had interrupted the program at that point. Signals
+//! that are blocked with `sigprocmask` or `pthread_sigmask` stay pending until they are unblocked.
+
+use std::iter;
+
+use rustc_data_structures::fx::FxHashMap;
+use rustc_middle::mir;
+use rustc_span::Symbol;
+use rustc_target::abi::Size;
+use rustc_target::spec::abi::Abi;
+
+use crate::*;
+
+/// The handlers `SIG_DFL` and `SIG_IGN`, which are the same on all Unix targets.
+const SIG_DFL: u64 = 0;
+const SIG_IGN: u64 = 1;
+
+/// The signals that are ignored by default, instead of terminating the program.
+const IGNORED_BY_DEFAULT: &[&str] = &["SIGCHLD", "SIGURG", "SIGWINCH", "SIGCONT"];
+/// The signals that stop the program by default.
+const STOP_BY_DEFAULT: &[&str] = &["SIGSTOP", "SIGTSTP", "SIGTTIN", "SIGTTOU"];
+
+/// What happens when a signal is delivered, as set by `sigaction`.
+#[derive(Debug, Clone, Copy)]
+struct SigAction {
+    /// `SIG_DFL`, `SIG_IGN`, or the handler function.
+    handler: Pointer<Option<Provenance>>,
+    /// The signals that are blocked while the handler runs.
+    mask: u128,
+    flags: i32,
+}
+
+impl Default for SigAction {
+    fn default() -> Self {
+        SigAction { handler: Pointer::null(), mask: 0, flags: 0 }
+    }
+}
+
+/// The signal dispositions of the process and the signal masks of its threads. Signal sets are
+/// kept as bit masks in which bit `n - 1` stands for signal `n`.
+#[derive(Debug, Default)]
+pub struct SignalState {
+    /// The actions set with `sigaction` or `signal`. The other signals have their default action.
+    actions: FxHashMap<i32, SigAction>,
+    /// The signals that each thread blocks.
+    masks: FxHashMap<ThreadId, u128>,
+    /// The signals that were sent to each thread while it blocked them.
+    pending: FxHashMap<ThreadId, u128>,
+}
+
+impl SignalState {
+    fn action(&self, sig: i32) -> SigAction {
+        self.actions.get(&sig).copied().unwrap_or_default()
+    }
+
+    fn mask(&self, thread: ThreadId) -> u128 {
+        self.masks.get(&thread).copied().unwrap_or(0)
+    }
+
+    /// New threads start out blocking the same signals as the thread that created them.
+    pub fn thread_created(&mut self, parent: ThreadId, child: ThreadId) {
+        let mask = self.mask(parent);
+        self.masks.insert(child, mask);
+    }
+}
+
+/// Marks the frame of a signal handler. When this frame returns, the signal mask of the thread is
+/// restored and the program continues at `ret`.
+#[derive(Debug)]
+pub struct SignalHandlerData {
+    ret: mir::BasicBlock,
+    /// The signal mask of the thread before the handler was called.
+    mask: u128,
+    /// The `siginfo_t` passed to an `SA_SIGINFO` handler, which is freed afterwards.
+    siginfo: Option<Pointer<Option<Provenance>>>,
+}
+
+/// The bit of `sig` in a signal set. `sig` must be a valid signal number.
+fn signal_bit(sig: i32) -> u128 {
+    let shift = u32::try_from(sig).ok().and_then(|sig| sig.checked_sub(1)).unwrap();
+    1u128.checked_shl(shift).unwrap()
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Whether `sig` is a signal number of the target, including the real-time signals.
+    fn is_valid_signal(&self, sig: i32) -> bool {
+        let this = self.eval_context_ref();
+        let max = match &*this.tcx.sess.target.os {
+            "macos" => 31,
+            "freebsd" => 128,
+            _ => 64,
+        };
+        (1..=max).contains(&sig)
+    }
+
+    fn is_signal_in(&self, sig: i32, names: &[&str]) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_ref();
+        for name in names {
+            if this.eval_libc_i32(name)? == sig {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The words of the `sigset_t` at `set` that hold the signals Miri supports. On Linux, a
+    /// `sigset_t` is an array of `unsigned long`; elsewhere, it is made of 32-bit words.
+    fn sigset_words(
+        &self,
+        set: &MPlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Vec<MPlaceTy<'tcx, Provenance>>> {
+        let this = self.eval_context_ref();
+        let word = match &*this.tcx.sess.target.os {
+            "linux" | "android" => this.machine.layouts.usize,
+            _ => this.machine.layouts.u32,
+        };
+        let mut words = Vec::new();
+        let mut offset = Size::ZERO;
+        while offset + word.size <= set.layout.size && (offset + word.size).bits() <= 128 {
+            words.push(set.offset(offset, word, this)?);
+            offset += word.size;
+        }
+        Ok(words)
+    }
+
+    fn read_sigset(&self, set: &MPlaceTy<'tcx, Provenance>) -> InterpResult<'tcx, u128> {
+        let this = self.eval_context_ref();
+        let mut signals = 0u128;
+        let mut shift = 0u32;
+        for word in this.sigset_words(set)? {
+            let bits = this.read_scalar(&word.into())?.to_bits(word.layout.size)?;
+            signals |= bits.checked_shl(shift).unwrap();
+            shift = shift.checked_add(u32::try_from(word.layout.size.bits()).unwrap()).unwrap();
+        }
+        Ok(signals)
+    }
+
+    /// Write `signals` to the `sigset_t` at `set`. The signals that Miri does not support are
+    /// cleared.
+    fn write_sigset(
+        &mut self,
+        signals: u128,
+        set: &MPlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let size = usize::try_from(set.layout.size.bytes()).unwrap();
+        this.write_bytes_ptr(set.ptr, iter::repeat(0u8).take(size))?;
+        let mut shift = 0u32;
+        for word in this.sigset_words(set)? {
+            let bits = word.layout.size.truncate(signals.checked_shr(shift).unwrap());
+            this.write_scalar(Scalar::from_uint(bits, word.layout.size), &word.into())?;
+            shift = shift.checked_add(u32::try_from(word.layout.size.bits()).unwrap()).unwrap();
+        }
+        Ok(())
+    }
+
+    /// The `sigset_t` that `set_op` points to, or `None` if it is null.
+    fn deref_sigset(
+        &self,
+        set_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Option<MPlaceTy<'tcx, Provenance>>> {
+        let this = self.eval_context_ref();
+        if this.ptr_is_null(this.read_pointer(set_op)?)? {
+            return Ok(None);
+        }
+        Ok(Some(this.deref_operand(set_op)?))
+    }
+
+    /// Whether `sig` is discarded when it is sent, because it is ignored.
+    fn is_signal_ignored(&self, sig: i32) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_ref();
+        Ok(match this.machine.signals.action(sig).handler.addr().bytes() {
+            SIG_IGN => true,
+            SIG_DFL => this.is_signal_in(sig, IGNORED_BY_DEFAULT)?,
+            _ => false,
+        })
+    }
+
+    /// Send `sig` to the active thread. It is delivered by `deliver_pending_signals`, once the
+    /// thread does not block it.
+    fn send_signal(&mut self, sig: i32) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        if !this.is_signal_ignored(sig)? {
+            let thread = this.get_active_thread();
+            *this.machine.signals.pending.entry(thread).or_default() |= signal_bit(sig);
+        }
+        Ok(())
+    }
+
+    /// Call the handler of `sig`, which then returns to `ret`.
+    fn call_signal_handler(
+        &mut self,
+        sig: i32,
+        action: SigAction,
+        ret: mir::BasicBlock,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        // While the handler runs, the signals in its mask are blocked, and so is the signal
+        // itself unless `SA_NODEFER` is set.
+        let thread = this.get_active_thread();
+        let mask = this.machine.signals.mask(thread);
+        let mut handler_mask = mask | action.mask;
+        if action.flags & this.eval_libc_i32("SA_NODEFER")? == 0 {
+            handler_mask |= signal_bit(sig);
+        }
+        this.machine.signals.masks.insert(thread, handler_mask);
+        if action.flags & this.eval_libc_i32("SA_RESETHAND")? != 0 {
+            this.machine.signals.actions.remove(&sig);
+        }
+
+        // Handlers are usually given as integers, since that is the type of the field in
+        // `sigaction`, so they are cast back to function pointers here.
+        let handler = match action.handler.into_parts() {
+            (None, addr) => Pointer::new(Some(Provenance::Wildcard), addr),
+            _ => action.handler,
+        };
+        let instance = this.get_ptr_fn(handler)?.as_instance()?;
+
+        let mut args = vec![Scalar::from_i32(sig).into()];
+        let siginfo = if action.flags & this.eval_libc_i32("SA_SIGINFO")? != 0 {
+            let layout = this.libc_ty_layout("siginfo_t")?;
+            let siginfo = this.allocate(layout, MiriMemoryKind::Machine.into())?;
+            let size = usize::try_from(layout.size.bytes()).unwrap();
+            this.write_bytes_ptr(siginfo.ptr, iter::repeat(0u8).take(size))?;
+            this.write_int_fields_named(&[("si_signo", sig.into())], &siginfo)?;
+            // There is no context to resume, so the third argument is null.
+            args.push(Scalar::from_maybe_pointer(siginfo.ptr, this).into());
+            args.push(Scalar::null_ptr(this).into());
+            Some(siginfo.ptr)
+        } else {
+            None
+        };
+
+        this.call_function(
+            instance,
+            Abi::C { unwind: false },
+            &args,
+            None,
+            StackPopCleanup::Goto { ret: Some(ret), unwind: StackPopUnwind::Skip },
+        )?;
+        this.frame_mut().extra.signal_handler = Some(SignalHandlerData { ret, mask, siginfo });
+        Ok(())
+    }
+
+    fn raise(&mut self, sig_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let sig = this.read_scalar(sig_op)?.to_i32()?;
+        if !this.is_valid_signal(sig) {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+        this.send_signal(sig)?;
+        Ok(0)
+    }
+
+    /// Only the calling thread can be sent a signal other than 0, which only checks whether the
+    /// thread still exists.
+    fn pthread_kill(
+        &mut self,
+        thread_op: &OpTy<'tcx, Provenance>,
+        sig_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let thread = this.read_scalar(thread_op)?.to_machine_usize(this)?;
+        let sig = this.read_scalar(sig_op)?.to_i32()?;
+
+        if sig != 0 && !this.is_valid_signal(sig) {
+            return this.eval_libc_i32("EINVAL");
+        }
+        let exists = thread < this.get_total_thread_count()
+            && !this.has_terminated(thread.try_into().expect("thread ID should fit in u32"));
+        if !exists {
+            return this.eval_libc_i32("ESRCH");
+        }
+        if sig == 0 {
+            return Ok(0);
+        }
+        if thread != u64::from(this.get_active_thread().to_u32()) {
+            throw_unsup_format!(
+                "Miri only supports sending signals to the calling thread with `pthread_kill`"
+            );
+        }
+        this.send_signal(sig)?;
+        Ok(0)
+    }
+
+    /// Returns 0 or an error number, like `pthread_sigmask`.
+    fn pthread_sigmask(
+        &mut self,
+        how_op: &OpTy<'tcx, Provenance>,
+        set_op: &OpTy<'tcx, Provenance>,
+        oldset_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let how = this.read_scalar(how_op)?.to_i32()?;
+        let set = this.deref_sigset(set_op)?;
+        let oldset = this.deref_sigset(oldset_op)?;
+
+        let thread = this.get_active_thread();
+        let mask = this.machine.signals.mask(thread);
+        let new_mask = match set {
+            None => mask,
+            Some(set) => {
+                let set = this.read_sigset(&set)?;
+                if how == this.eval_libc_i32("SIG_BLOCK")? {
+                    mask | set
+                } else if how == this.eval_libc_i32("SIG_UNBLOCK")? {
+                    mask & !set
+                } else if how == this.eval_libc_i32("SIG_SETMASK")? {
+                    set
+                } else {
+                    return this.eval_libc_i32("EINVAL");
+                }
+            }
+        };
+        if let Some(oldset) = oldset {
+            this.write_sigset(mask, &oldset)?;
+        }
+        // `SIGKILL` and `SIGSTOP` cannot be blocked, which is silently enforced.
+        let sigkill = signal_bit(this.eval_libc_i32("SIGKILL")?);
+        let sigstop = signal_bit(this.eval_libc_i32("SIGSTOP")?);
+        this.machine.signals.masks.insert(thread, new_mask & !(sigkill | sigstop));
+        Ok(0)
+    }
+
+    fn sigprocmask(
+        &mut self,
+        how_op: &OpTy<'tcx, Provenance>,
+        set_op: &OpTy<'tcx, Provenance>,
+        oldset_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let result = this.pthread_sigmask(how_op, set_op, oldset_op)?;
+        if result != 0 {
+            this.set_last_error(Scalar::from_i32(result))?;
+            return Ok(-1);
+        }
+        Ok(0)
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Emulates the functions that might deliver a signal, and hence need to know where to
+    /// continue afterwards. Returns whether `link_name` is one of them.
+    fn emulate_signal_item(
+        &mut self,
+        link_name: Symbol,
+        abi: Abi,
+        args: &[OpTy<'tcx, Provenance>],
+        dest: &PlaceTy<'tcx, Provenance>,
+        ret: mir::BasicBlock,
+    ) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_mut();
+
+        let result = match link_name.as_str() {
+            "raise" => {
+                let [sig] = this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.raise(sig)?
+            }
+            "pthread_kill" => {
+                let [thread, sig] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.pthread_kill(thread, sig)?
+            }
+            "sigprocmask" => {
+                let [how, set, oldset] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.sigprocmask(how, set, oldset)?
+            }
+            "pthread_sigmask" => {
+                let [how, set, oldset] =
+                    this.check_shim(abi, Abi::C { unwind: false }, link_name, args)?;
+                this.pthread_sigmask(how, set, oldset)?
+            }
+            _ => return Ok(false),
+        };
+        this.write_scalar(Scalar::from_i32(result), dest)?;
+        this.deliver_pending_signals(ret)?;
+        Ok(true)
+    }
+
+    /// Deliver the signals that are pending for the active thread and that it no longer blocks,
+    /// lowest first like Linux does, and then continue at `ret`. If a handler is called, the
+    /// remaining signals are delivered once it returns.
+    fn deliver_pending_signals(&mut self, ret: mir::BasicBlock) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let thread = this.get_active_thread();
+        loop {
+            let mask = this.machine.signals.mask(thread);
+            let pending = this.machine.signals.pending.entry(thread).or_default();
+            let deliverable = *pending & !mask;
+            if deliverable == 0 {
+                this.go_to_block(ret);
+                return Ok(());
+            }
+            let sig = i32::try_from(deliverable.trailing_zeros()).unwrap().checked_add(1).unwrap();
+            *pending &= !signal_bit(sig);
+
+            let action = this.machine.signals.action(sig);
+            match action.handler.addr().bytes() {
+                SIG_IGN => {}
+                SIG_DFL => {
+                    if this.is_signal_in(sig, IGNORED_BY_DEFAULT)? {
+                        continue;
+                    }
+                    if this.is_signal_in(sig, STOP_BY_DEFAULT)? {
+                        throw_unsup_format!(
+                            "stopping the program with signal {sig} is not supported"
+                        );
+                    }
+                    throw_machine_stop!(TerminationInfo::Abort(format!(
+                        "the program was terminated by signal {sig}"
+                    )));
+                }
+                _ => {
+                    this.call_signal_handler(sig, action, ret)?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Called when a signal handler returns.
+    fn signal_handler_done(&mut self, data: SignalHandlerData) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let thread = this.get_active_thread();
+        this.machine.signals.masks.insert(thread, data.mask);
+        if let Some(siginfo) = data.siginfo {
+            this.deallocate_ptr(siginfo, None, MiriMemoryKind::Machine.into())?;
+        }
+        // Restoring the mask might have unblocked some pending signals.
+        this.deliver_pending_signals(data.ret)
+    }
+
+    fn sigaction(
+        &mut self,
+        sig_op: &OpTy<'tcx, Provenance>,
+        act_op: &OpTy<'tcx, Provenance>,
+        oldact_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let sig = this.read_scalar(sig_op)?.to_i32()?;
+        let act = this.read_pointer(act_op)?;
+        let oldact = this.read_pointer(oldact_op)?;
+
+        let sigkill = this.eval_libc_i32("SIGKILL")?;
+        let sigstop = this.eval_libc_i32("SIGSTOP")?;
+        if !this.is_valid_signal(sig)
+            || (!this.ptr_is_null(act)? && (sig == sigkill || sig == sigstop))
+        {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+
+        let sigaction = this.libc_ty_layout("sigaction")?;
+        // The new action is read before the old one is written, since they might overlap.
+        let new_action = if this.ptr_is_null(act)? {
+            None
+        } else {
+            let act = MPlaceTy::from_aligned_ptr(act, sigaction);
+            let handler = this.mplace_field_named(&act, "sa_sigaction")?;
+            let mask = this.mplace_field_named(&act, "sa_mask")?;
+            let flags = this.mplace_field_named(&act, "sa_flags")?;
+            Some(SigAction {
+                handler: this.read_pointer(&handler.into())?,
+                mask: this.read_sigset(&mask)?,
+                flags: this.read_scalar(&flags.into())?.to_i32()?,
+            })
+        };
+
+        if !this.ptr_is_null(oldact)? {
+            let action = this.machine.signals.action(sig);
+            let oldact = MPlaceTy::from_aligned_ptr(oldact, sigaction);
+            let size = usize::try_from(sigaction.size.bytes()).unwrap();
+            this.write_bytes_ptr(oldact.ptr, iter::repeat(0u8).take(size))?;
+            let handler = this.mplace_field_named(&oldact, "sa_sigaction")?;
+            this.write_pointer(action.handler, &handler.into())?;
+            let mask = this.mplace_field_named(&oldact, "sa_mask")?;
+            this.write_sigset(action.mask, &mask)?;
+            let flags = this.mplace_field_named(&oldact, "sa_flags")?;
+            this.write_int(action.flags, &flags.into())?;
+        }
+
+        if let Some(action) = new_action {
+            this.machine.signals.actions.insert(sig, action);
+        }
+        Ok(0)
+    }
+
+    /// Like glibc, `signal` keeps the handler installed and restarts interrupted functions.
+    fn signal(
+        &mut self,
+        sig_op: &OpTy<'tcx, Provenance>,
+        handler_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        let sig = this.read_scalar(sig_op)?.to_i32()?;
+        let handler = this.read_pointer(handler_op)?;
+
+        let sigkill = this.eval_libc_i32("SIGKILL")?;
+        let sigstop = this.eval_libc_i32("SIGSTOP")?;
+        if !this.is_valid_signal(sig) || sig == sigkill || sig == sigstop {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            // `SIG_ERR` is -1.
+            return Ok(Scalar::from_machine_isize(-1, this));
+        }
+
+        let action = SigAction { handler, mask: 0, flags: this.eval_libc_i32("SA_RESTART")? };
+        let old_action = this.machine.signals.actions.insert(sig, action).unwrap_or_default();
+        Ok(Scalar::from_maybe_pointer(old_action.handler, this))
+    }
+
+    fn sigpending(&mut self, set_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let set = this.deref_operand(set_op)?;
+        let thread = this.get_active_thread();
+        let pending = this.machine.signals.pending.get(&thread).copied().unwrap_or(0);
+        this.write_sigset(pending, &set)?;
+        Ok(0)
+    }
+
+    fn sigemptyset(&mut self, set_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let set = this.deref_operand(set_op)?;
+        this.write_sigset(0, &set)?;
+        Ok(0)
+    }
+
+    fn sigfillset(&mut self, set_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let set = this.deref_operand(set_op)?;
+        let mut signals = 0;
+        for sig in 1..=128 {
+            if this.is_valid_signal(sig) {
+                signals |= signal_bit(sig);
+            }
+        }
+        this.write_sigset(signals, &set)?;
+        Ok(0)
+    }
+
+    /// Implements `sigaddset`, `sigdelset` and `sigismember`, depending on `link_name`.
+    fn sigset_member(
+        &mut self,
+        link_name: &str,
+        set_op: &OpTy<'tcx, Provenance>,
+        sig_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let set = this.deref_operand(set_op)?;
+        let sig = this.read_scalar(sig_op)?.to_i32()?;
+        if !this.is_valid_signal(sig) {
+            let einval = this.eval_libc("EINVAL")?;
+            this.set_last_error(einval)?;
+            return Ok(-1);
+        }
+
+        let signals = this.read_sigset(&set)?;
+        match link_name {
+            "sigaddset" => this.write_sigset(signals | signal_bit(sig), &set)?,
+            "sigdelset" => this.write_sigset(signals & !signal_bit(sig), &set)?,
+            "sigismember" => return Ok(i32::from(signals & signal_bit(sig) != 0)),
+            _ => unreachable!(),
+        }
+        Ok(0)
+    }
+}
@@ -0,0 +1,962 @@
+//! Emulation of unix domain stream sockets, created by `socketpair` or by `socket` with `AF_UNIX`.
+//! The sockets are in-memory byte streams that never leave the interpreter. A socket created by
+//! `socket` can be bound to an address and listen on it, or connect to a listening socket. These
+//! addresses only exist inside the interpreter; binding to a path does not create a file.
+//!
+//! With `-Zmiri-loopback-network`, `socket` also creates TCP sockets with `AF_INET`. They work the
+//! same way, on a virtual network that only has the loopback address `127.0.0.1`, so a client and
+//! a server in the same program can talk to each other without touching the host network.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::io;
+use std::iter;
+use std::rc::{Rc, Weak};
+
+use rustc_data_structures::fx::FxHashMap;
+use rustc_target::abi::Size;
+
+use crate::concurrency::data_race;
+use crate::concurrency::vector_clock::VClock;
+use crate::*;
+use shims::unix::fs::{BlockedIoOp, EvalContextExt as _, FileDescriptor};
+use shims::unix::linux::epoll::EpollReadiness;
+use shims::unix::resource::EvalContextExt as _;
+
+/// The first port that is handed out to sockets that are bound to port 0 or that connect without
+/// being bound. The ports up to `u16::MAX` are used in turn, like the ephemeral port range of the
+/// IANA.
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+/// The address family of a socket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Domain {
+    /// `AF_UNIX`.
+    Unix,
+    /// `AF_INET`, which is only available with `-Zmiri-loopback-network`.
+    Inet,
+}
+
+/// The address that a socket is bound to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum SocketAddress {
+    /// A unix domain socket address, stored like `sockaddr_un::sun_path`, so abstract addresses
+    /// start with a null byte.
+    Unix(Vec<u8>),
+    /// A port on the loopback address `127.0.0.1`.
+    Inet(u16),
+}
+
+/// The bytes sent in one direction of a connection.
+#[derive(Debug, Default)]
+struct SocketBuffer {
+    data: VecDeque<u8>,
+    /// Whether all file descriptors of the sending end were closed.
+    writer_closed: bool,
+    /// Whether all file descriptors of the receiving end were closed.
+    reader_closed: bool,
+    /// Incremented on every change, see [`EpollReadiness::generation`].
+    generation: u64,
+    /// The clocks of all threads that sent data, so that sending data happens-before receiving
+    /// it.
+    clock: VClock,
+}
+
+/// One end of a connection.
+#[derive(Debug)]
+struct Stream {
+    /// The bytes sent to this end.
+    readbuf: Rc<RefCell<SocketBuffer>>,
+    /// The bytes sent to the other end.
+    writebuf: Rc<RefCell<SocketBuffer>>,
+    /// The address of this end, or `None` if it is an unnamed unix domain socket.
+    local: Option<SocketAddress>,
+    /// The address of the other end, or `None` if it is an unnamed unix domain socket.
+    peer: Option<SocketAddress>,
+}
+
+impl Stream {
+    /// The two ends of a new connection, the first one with the address `addr0` and the second
+    /// one with `addr1`.
+    fn pair(addr0: Option<SocketAddress>, addr1: Option<SocketAddress>) -> (Stream, Stream) {
+        let buf0 = Rc::new(RefCell::new(SocketBuffer::default()));
+        let buf1 = Rc::new(RefCell::new(SocketBuffer::default()));
+        (
+            Stream {
+                readbuf: Rc::clone(&buf0),
+                writebuf: Rc::clone(&buf1),
+                local: addr0.clone(),
+                peer: addr1.clone(),
+            },
+            Stream { readbuf: buf1, writebuf: buf0, local: addr1, peer: addr0 },
+        )
+    }
+
+    /// Close this end of the connection, which the other end sees as end of file.
+    fn close(&self) {
+        self.shutdown(true, true);
+    }
+
+    /// Stop receiving or sending data on this end of the connection, like `shutdown` does.
+    fn shutdown(&self, read: bool, write: bool) {
+        if read {
+            let mut readbuf = self.readbuf.borrow_mut();
+            readbuf.reader_closed = true;
+            readbuf.generation = readbuf.generation.wrapping_add(1);
+        }
+        if write {
+            let mut writebuf = self.writebuf.borrow_mut();
+            writebuf.writer_closed = true;
+            writebuf.generation = writebuf.generation.wrapping_add(1);
+        }
+    }
+}
+
+/// A socket that was bound to an address with `bind`.
+#[derive(Debug)]
+struct Listener {
+    address: SocketAddress,
+    /// Whether `listen` was called, so that other sockets can connect to the address.
+    listening: bool,
+    /// Our ends of the connections that were made with `connect`, but not accepted yet.
+    pending: VecDeque<Stream>,
+    /// Incremented on every change, see [`EpollReadiness::generation`].
+    generation: u64,
+}
+
+impl Listener {
+    fn new(address: SocketAddress) -> Self {
+        Listener { address, listening: false, pending: VecDeque::new(), generation: 0 }
+    }
+}
+
+#[derive(Debug)]
+enum SocketState {
+    /// Created by `socket`, and neither bound nor connected yet.
+    Unbound,
+    /// Bound to an address by `bind`.
+    Bound(Rc<RefCell<Listener>>),
+    /// Created by `socketpair` or `accept`, or connected by `connect`.
+    Connected(Stream),
+}
+
+/// The addresses that sockets are bound to. An address is free again once all file descriptors
+/// of the socket that was bound to it are closed.
+#[derive(Debug)]
+pub struct SocketAddresses {
+    bound: FxHashMap<SocketAddress, Weak<RefCell<Listener>>>,
+    /// The next ephemeral port to try.
+    next_port: u16,
+}
+
+impl Default for SocketAddresses {
+    fn default() -> Self {
+        SocketAddresses { bound: FxHashMap::default(), next_port: FIRST_EPHEMERAL_PORT }
+    }
+}
+
+impl SocketAddresses {
+    fn is_bound(&self, address: &SocketAddress) -> bool {
+        self.bound.get(address).and_then(Weak::upgrade).is_some()
+    }
+
+    /// The next ephemeral port that no socket is bound to, or `None` if they are all in use.
+    fn ephemeral_port(&mut self) -> Option<u16> {
+        for _ in FIRST_EPHEMERAL_PORT..=u16::MAX {
+            let port = self.next_port;
+            self.next_port = port.checked_add(1).unwrap_or(FIRST_EPHEMERAL_PORT);
+            if !self.is_bound(&SocketAddress::Inet(port)) {
+                return Some(port);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug)]
+pub struct Socket {
+    domain: Domain,
+    /// Shared between duplicates of the file descriptor, to tell when the last one is closed.
+    state: Rc<RefCell<SocketState>>,
+    /// Shared between duplicates of the file descriptor as well.
+    is_nonblock: Rc<Cell<bool>>,
+}
+
+impl Socket {
+    fn new(domain: Domain, state: SocketState, is_nonblock: bool) -> Box<Socket> {
+        Box::new(Socket {
+            domain,
+            state: Rc::new(RefCell::new(state)),
+            is_nonblock: Rc::new(Cell::new(is_nonblock)),
+        })
+    }
+}
+
+impl FileDescriptor for Socket {
+    fn name(&self) -> &'static str {
+        "socket"
+    }
+
+    fn as_socket(&self) -> Option<&Socket> {
+        Some(self)
+    }
+
+    fn nonblock_flag(&self) -> Option<&Cell<bool>> {
+        Some(&self.is_nonblock)
+    }
+
+    fn read<'tcx>(
+        &mut self,
+        _communicate_allowed: bool,
+        bytes: &mut [u8],
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        let state = self.state.borrow();
+        let SocketState::Connected(stream) = &*state else {
+            return Ok(Err(io::ErrorKind::NotConnected.into()));
+        };
+        let mut readbuf = stream.readbuf.borrow_mut();
+        if readbuf.data.is_empty() && !bytes.is_empty() {
+            if readbuf.writer_closed || readbuf.reader_closed {
+                // End of file.
+                return Ok(Ok(0));
+            }
+            // Unless the socket is non-blocking, the thread waits for data to arrive.
+            return Ok(Err(io::ErrorKind::WouldBlock.into()));
+        }
+        let len = bytes.len().min(readbuf.data.len());
+        for (byte, data) in bytes.iter_mut().zip(readbuf.data.drain(..len)) {
+            *byte = data;
+        }
+        readbuf.generation = readbuf.generation.wrapping_add(1);
+        Ok(Ok(len))
+    }
+
+    fn write<'tcx>(
+        &self,
+        _communicate_allowed: bool,
+        bytes: &[u8],
+    ) -> InterpResult<'tcx, io::Result<usize>> {
+        let state = self.state.borrow();
+        let SocketState::Connected(stream) = &*state else {
+            return Ok(Err(io::ErrorKind::NotConnected.into()));
+        };
+        let mut writebuf = stream.writebuf.borrow_mut();
+        if writebuf.reader_closed || writebuf.writer_closed {
+            return Ok(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        writebuf.data.extend(bytes);
+        writebuf.generation = writebuf.generation.wrapping_add(1);
+        Ok(Ok(bytes.len()))
+    }
+
+    fn release_clock(&self, data_race: &data_race::GlobalState, thread: ThreadId) {
+        if let SocketState::Connected(stream) = &*self.state.borrow() {
+            data_race.validate_lock_release_shared(&mut stream.writebuf.borrow_mut().clock, thread);
+        }
+    }
+
+    fn acquire_clock(&self, data_race: &data_race::GlobalState, thread: ThreadId) {
+        if let SocketState::Connected(stream) = &*self.state.borrow() {
+            data_race.validate_lock_acquire(&stream.readbuf.borrow().clock, thread);
+        }
+    }
+
+    fn epoll_readiness(&self) -> Option<EpollReadiness> {
+        Some(match &*self.state.borrow() {
+            SocketState::Unbound =>
+                EpollReadiness { readable: false, writable: false, generation: 0 },
+            SocketState::Bound(listener) => {
+                // A listening socket is readable when there is a connection to accept.
+                let listener = listener.borrow();
+                EpollReadiness {
+                    readable: !listener.pending.is_empty(),
+                    writable: false,
+                    generation: listener.generation,
+                }
+            }
+            SocketState::Connected(stream) => {
+                let readbuf = stream.readbuf.borrow();
+                let writebuf = stream.writebuf.borrow();
+                EpollReadiness {
+                    readable: !readbuf.data.is_empty() || readbuf.writer_closed,
+                    // We do not limit the size of the buffers, so writing never blocks.
+                    writable: true,
+                    generation: readbuf.generation.wrapping_add(writebuf.generation),
+                }
+            }
+        })
+    }
+
+    fn dup(&mut self) -> io::Result<Box<dyn FileDescriptor>> {
+        Ok(Box::new(Socket {
+            domain: self.domain,
+            state: Rc::clone(&self.state),
+            is_nonblock: Rc::clone(&self.is_nonblock),
+        }))
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+    ) -> InterpResult<'tcx, io::Result<i32>> {
+        if Rc::strong_count(&self.state) == 1 {
+            match &*self.state.borrow() {
+                SocketState::Unbound => {}
+                SocketState::Bound(listener) => {
+                    // The connections that were not accepted are closed with the listener.
+                    for stream in listener.borrow_mut().pending.drain(..) {
+                        stream.close();
+                    }
+                }
+                SocketState::Connected(stream) => stream.close(),
+            }
+        }
+        Ok(Ok(0))
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Set the last error to the given libc error and return -1.
+    fn socket_error<T: From<i32>>(&mut self, error: &str) -> InterpResult<'tcx, T> {
+        let this = self.eval_context_mut();
+        let error = this.eval_libc(error)?;
+        this.set_last_error(error)?;
+        Ok((-1).into())
+    }
+
+    /// The state of the socket `fd`, whether it is non-blocking and its address family, or the
+    /// libc error to report if `fd` is not a socket.
+    fn socket_state(
+        &self,
+        fd: i32,
+    ) -> Result<(Rc<RefCell<SocketState>>, bool, Domain), &'static str> {
+        let this = self.eval_context_ref();
+        match this.machine.file_handler.handles.get(&fd) {
+            None => Err("EBADF"),
+            Some(file_descriptor) => match file_descriptor.as_socket() {
+                Some(socket) =>
+                    Ok((Rc::clone(&socket.state), socket.is_nonblock.get(), socket.domain)),
+                None => Err("ENOTSOCK"),
+            },
+        }
+    }
+
+    /// The address family `domain` of a new socket. `AF_INET` is only supported with
+    /// `-Zmiri-loopback-network`.
+    fn socket_domain(&self, domain: i32, name: &str) -> InterpResult<'tcx, Domain> {
+        let this = self.eval_context_ref();
+        if domain == this.eval_libc_i32("AF_UNIX")? {
+            Ok(Domain::Unix)
+        } else if domain == this.eval_libc_i32("AF_INET")? && this.machine.loopback_network {
+            Ok(Domain::Inet)
+        } else {
+            throw_unsup_format!(
+                "`{}` is only supported with domain `AF_UNIX`, or `AF_INET` with `-Zmiri-loopback-network`",
+                name
+            );
+        }
+    }
+
+    /// Split the `SOCK_NONBLOCK` and `SOCK_CLOEXEC` flags off a socket type, and return whether the
+    /// socket is non-blocking.
+    fn socket_type_flags(&self, type_: i32) -> InterpResult<'tcx, (i32, bool)> {
+        let this = self.eval_context_ref();
+        // `exec` is not supported, so we can ignore `SOCK_CLOEXEC`. macOS has neither of the flags.
+        if this.tcx.sess.target.os == "macos" {
+            return Ok((type_, false));
+        }
+        let sock_nonblock = this.eval_libc_i32("SOCK_NONBLOCK")?;
+        let sock_cloexec = this.eval_libc_i32("SOCK_CLOEXEC")?;
+        Ok((type_ & !(sock_nonblock | sock_cloexec), type_ & sock_nonblock != 0))
+    }
+
+    /// The offset of `sun_path` in `sockaddr_un`, which is where the address of a unix domain
+    /// socket starts.
+    fn sun_path_offset(&self) -> InterpResult<'tcx, Size> {
+        let this = self.eval_context_ref();
+        let sockaddr_un = this.libc_ty_layout("sockaddr_un")?;
+        let adt = sockaddr_un.ty.ty_adt_def().unwrap();
+        let idx = adt
+            .non_enum_variant()
+            .fields
+            .iter()
+            .position(|field| field.name.as_str() == "sun_path")
+            .unwrap();
+        Ok(sockaddr_un.fields.offset(idx))
+    }
+
+    /// Read the `sockaddr_un` or `sockaddr_in` given to `bind` or `connect`. Returns the address
+    /// as it is stored in `SocketAddresses`, or the libc error to report if it is not valid. The
+    /// unspecified address `0.0.0.0` is treated like the loopback address.
+    fn read_socket_address(
+        &self,
+        domain: Domain,
+        addr_op: &OpTy<'tcx, Provenance>,
+        len_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Result<SocketAddress, &'static str>> {
+        let this = self.eval_context_ref();
+
+        let addr_ptr = this.read_pointer(addr_op)?;
+        let len = Size::from_bytes(this.read_scalar(len_op)?.to_u32()?);
+
+        if domain == Domain::Inet {
+            let sockaddr_in = this.libc_ty_layout("sockaddr_in")?;
+            if len < sockaddr_in.size {
+                return Ok(Err("EINVAL"));
+            }
+            let addr = MPlaceTy::from_aligned_ptr(addr_ptr, sockaddr_in);
+            let family = this.mplace_field_named(&addr, "sin_family")?;
+            let family = this.read_scalar(&family.into())?.to_uint(family.layout.size)?;
+            if i128::try_from(family).unwrap() != i128::from(this.eval_libc_i32("AF_INET")?) {
+                return Ok(Err("EAFNOSUPPORT"));
+            }
+            // Both the address and the port are in network byte order.
+            let ip = this.mplace_field_named(&addr, "sin_addr")?;
+            let ip = this.read_bytes_ptr_strip_provenance(ip.ptr, ip.layout.size)?;
+            if ip != [127, 0, 0, 1] && ip != [0, 0, 0, 0] {
+                throw_unsup_format!(
+                    "the emulated network only has the loopback address 127.0.0.1, not {}.{}.{}.{}",
+                    ip[0],
+                    ip[1],
+                    ip[2],
+                    ip[3]
+                );
+            }
+            let port = this.mplace_field_named(&addr, "sin_port")?;
+            let port = this.read_bytes_ptr_strip_provenance(port.ptr, port.layout.size)?;
+            return Ok(Ok(SocketAddress::Inet(u16::from_be_bytes(port.try_into().unwrap()))));
+        }
+
+        let sockaddr_un = this.libc_ty_layout("sockaddr_un")?;
+        let path_offset = this.sun_path_offset()?;
+        if len < path_offset || len > sockaddr_un.size {
+            return Ok(Err("EINVAL"));
+        }
+
+        let addr = MPlaceTy::from_aligned_ptr(addr_ptr, sockaddr_un);
+        let family = this.mplace_field_named(&addr, "sun_family")?;
+        let family = this.read_scalar(&family.into())?.to_uint(family.layout.size)?;
+        if i128::try_from(family).unwrap() != i128::from(this.eval_libc_i32("AF_UNIX")?) {
+            return Ok(Err("EAFNOSUPPORT"));
+        }
+
+        let path = this.mplace_field_named(&addr, "sun_path")?;
+        let path = this.read_bytes_ptr_strip_provenance(path.ptr, len - path_offset)?;
+        Ok(match path.first() {
+            None => throw_unsup_format!("unix domain sockets without an address are not supported"),
+            // Abstract addresses are all of the given bytes.
+            Some(0) => Ok(SocketAddress::Unix(path.to_owned())),
+            // Paths end at the first null byte.
+            Some(_) => {
+                let path = path.iter().copied().take_while(|&byte| byte != 0).collect();
+                Ok(SocketAddress::Unix(path))
+            }
+        })
+    }
+
+    /// Write `address` to the `sockaddr` that `addr` points to, like `accept`, `getsockname` and
+    /// `getpeername` do, and its length to `addrlen`. Of an unnamed unix domain socket, only the
+    /// address family is written, and a TCP socket without an address has the address
+    /// `0.0.0.0:0`. The address is not written if it does not fit into `*addrlen` bytes.
+    fn write_socket_address(
+        &mut self,
+        domain: Domain,
+        address: Option<&SocketAddress>,
+        addr: Pointer<Option<Provenance>>,
+        addrlen: Pointer<Option<Provenance>>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let addrlen = MPlaceTy::from_aligned_ptr(addrlen, this.libc_ty_layout("socklen_t")?);
+        let available = Size::from_bytes(this.read_scalar(&addrlen.into())?.to_u32()?);
+        let len = match domain {
+            Domain::Unix => {
+                let path = match address {
+                    Some(SocketAddress::Unix(path)) => &path[..],
+                    _ => &[],
+                };
+                // Paths are null-terminated, abstract addresses are not.
+                let terminator = usize::from(matches!(path.first(), Some(&byte) if byte != 0));
+                let len = this.sun_path_offset()?
+                    + Size::from_bytes(path.len())
+                    + Size::from_bytes(terminator);
+                if len <= available {
+                    let af_unix = this.eval_libc_i32("AF_UNIX")?;
+                    let sockaddr_un = this.libc_ty_layout("sockaddr_un")?;
+                    let addr = MPlaceTy::from_aligned_ptr(addr, sockaddr_un);
+                    let family = this.mplace_field_named(&addr, "sun_family")?;
+                    this.write_int(af_unix, &family.into())?;
+                    let sun_path = this.mplace_field_named(&addr, "sun_path")?;
+                    let bytes = path.iter().copied().chain(iter::repeat(0).take(terminator));
+                    this.write_bytes_ptr(sun_path.ptr, bytes)?;
+                }
+                len
+            }
+            Domain::Inet => {
+                let sockaddr_in = this.libc_ty_layout("sockaddr_in")?;
+                if sockaddr_in.size <= available {
+                    let (ip, port) = match address {
+                        Some(&SocketAddress::Inet(port)) => ([127, 0, 0, 1], port),
+                        _ => ([0; 4], 0),
+                    };
+                    let af_inet = this.eval_libc_i32("AF_INET")?;
+                    let addr = MPlaceTy::from_aligned_ptr(addr, sockaddr_in);
+                    // This also zeroes the padding, and `sin_len` on the targets that have it.
+                    let zeros = iter::repeat(0).take(sockaddr_in.size.bytes_usize());
+                    this.write_bytes_ptr(addr.ptr, zeros)?;
+                    let family = this.mplace_field_named(&addr, "sin_family")?;
+                    this.write_int(af_inet, &family.into())?;
+                    let sin_port = this.mplace_field_named(&addr, "sin_port")?;
+                    this.write_bytes_ptr(sin_port.ptr, port.to_be_bytes())?;
+                    let sin_addr = this.mplace_field_named(&addr, "sin_addr")?;
+                    this.write_bytes_ptr(sin_addr.ptr, ip)?;
+                }
+                sockaddr_in.size
+            }
+        };
+        this.write_int(len.bytes(), &addrlen.into())
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    fn socketpair(
+        &mut self,
+        domain: &OpTy<'tcx, Provenance>,
+        type_: &OpTy<'tcx, Provenance>,
+        protocol: &OpTy<'tcx, Provenance>,
+        sv: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let domain = this.read_scalar(domain)?.to_i32()?;
+        let type_ = this.read_scalar(type_)?.to_i32()?;
+        let protocol = this.read_scalar(protocol)?.to_i32()?;
+        let sv = this.deref_operand(sv)?;
+
+        let (type_, is_nonblock) = this.socket_type_flags(type_)?;
+        if domain != this.eval_libc_i32("AF_UNIX")?
+            || type_ != this.eval_libc_i32("SOCK_STREAM")?
+            || protocol != 0
+        {
+            throw_unsup_format!(
+                "`socketpair` is only supported with domain `AF_UNIX`, type `SOCK_STREAM` and protocol 0"
+            );
+        }
+
+        if !this.check_fd_limit(2)? {
+            return Ok(-1);
+        }
+        let (stream0, stream1) = Stream::pair(None, None);
+        let fh = &mut this.machine.file_handler;
+        let fd0 =
+            fh.insert_fd(Socket::new(Domain::Unix, SocketState::Connected(stream0), is_nonblock));
+        let fd1 =
+            fh.insert_fd(Socket::new(Domain::Unix, SocketState::Connected(stream1), is_nonblock));
+
+        this.write_scalar(Scalar::from_i32(fd0), &sv.into())?;
+        let sv1 = sv.offset(sv.layout.size, sv.layout, this)?;
+        this.write_scalar(Scalar::from_i32(fd1), &sv1.into())?;
+
+        Ok(0)
+    }
+
+    fn socket(
+        &mut self,
+        domain: &OpTy<'tcx, Provenance>,
+        type_: &OpTy<'tcx, Provenance>,
+        protocol: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let domain = this.read_scalar(domain)?.to_i32()?;
+        let type_ = this.read_scalar(type_)?.to_i32()?;
+        let protocol = this.read_scalar(protocol)?.to_i32()?;
+
+        let domain = this.socket_domain(domain, "socket")?;
+        let (type_, is_nonblock) = this.socket_type_flags(type_)?;
+        // TCP is the default protocol of stream sockets with `AF_INET`.
+        let tcp = domain == Domain::Inet && protocol == this.eval_libc_i32("IPPROTO_TCP")?;
+        if type_ != this.eval_libc_i32("SOCK_STREAM")? || (protocol != 0 && !tcp) {
+            throw_unsup_format!(
+                "`socket` is only supported with type `SOCK_STREAM` and the default protocol"
+            );
+        }
+        if !this.check_fd_limit(1)? {
+            return Ok(-1);
+        }
+
+        let socket = Socket::new(domain, SocketState::Unbound, is_nonblock);
+        Ok(this.machine.file_handler.insert_fd(socket))
+    }
+
+    /// Binding a TCP socket to port 0 binds it to the next free ephemeral port.
+    fn bind(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        addr: &OpTy<'tcx, Provenance>,
+        len: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let (state, _, domain) = match this.socket_state(fd) {
+            Ok(socket) => socket,
+            Err(error) => return this.socket_error(error),
+        };
+        let address = match this.read_socket_address(domain, addr, len)? {
+            Ok(address) => address,
+            Err(error) => return this.socket_error(error),
+        };
+
+        let mut state = state.borrow_mut();
+        if !matches!(*state, SocketState::Unbound) {
+            return this.socket_error("EINVAL");
+        }
+        let addresses = &mut this.machine.file_handler.socket_addresses;
+        let address = match address {
+            SocketAddress::Inet(0) =>
+                match addresses.ephemeral_port() {
+                    Some(port) => SocketAddress::Inet(port),
+                    None => return this.socket_error("EADDRINUSE"),
+                },
+            address => address,
+        };
+        if addresses.is_bound(&address) {
+            return this.socket_error("EADDRINUSE");
+        }
+        let listener = Rc::new(RefCell::new(Listener::new(address.clone())));
+        addresses.bound.insert(address, Rc::downgrade(&listener));
+        *state = SocketState::Bound(listener);
+
+        Ok(0)
+    }
+
+    fn listen(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        backlog: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        // We do not limit the number of pending connections.
+        let _backlog = this.read_scalar(backlog)?.to_i32()?;
+        let (state, ..) = match this.socket_state(fd) {
+            Ok(socket) => socket,
+            Err(error) => return this.socket_error(error),
+        };
+
+        let state = state.borrow();
+        match &*state {
+            SocketState::Unbound =>
+                throw_unsup_format!("`listen` on a socket that is not bound is not supported"),
+            SocketState::Bound(listener) => listener.borrow_mut().listening = true,
+            SocketState::Connected(_) => return this.socket_error("EINVAL"),
+        }
+
+        Ok(0)
+    }
+
+    /// A TCP socket that is not bound gets the next free ephemeral port.
+    fn connect(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        addr: &OpTy<'tcx, Provenance>,
+        len: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let (state, _, domain) = match this.socket_state(fd) {
+            Ok(socket) => socket,
+            Err(error) => return this.socket_error(error),
+        };
+        let address = match this.read_socket_address(domain, addr, len)? {
+            Ok(address) => address,
+            Err(error) => return this.socket_error(error),
+        };
+
+        let mut state = state.borrow_mut();
+        let local = match &*state {
+            SocketState::Connected(_) => return this.socket_error("EISCONN"),
+            SocketState::Bound(listener) if listener.borrow().listening =>
+                return this.socket_error("EINVAL"),
+            SocketState::Bound(listener) => Some(listener.borrow().address.clone()),
+            SocketState::Unbound => None,
+        };
+        let addresses = &mut this.machine.file_handler.socket_addresses;
+        let Some(listener) = addresses.bound.get(&address).and_then(Weak::upgrade) else {
+            // Paths that nothing is bound to do not exist.
+            return this.socket_error(match &address {
+                SocketAddress::Unix(path) if path[0] != 0 => "ENOENT",
+                _ => "ECONNREFUSED",
+            });
+        };
+        let mut listener = listener.borrow_mut();
+        if !listener.listening {
+            return this.socket_error("ECONNREFUSED");
+        }
+        let local = match local {
+            None if domain == Domain::Inet =>
+                match addresses.ephemeral_port() {
+                    Some(port) => Some(SocketAddress::Inet(port)),
+                    None => return this.socket_error("EADDRNOTAVAIL"),
+                },
+            local => local,
+        };
+
+        // The connection is established right away, even before it is accepted.
+        let (ours, theirs) = Stream::pair(local, Some(address));
+        listener.pending.push_back(theirs);
+        listener.generation = listener.generation.wrapping_add(1);
+        *state = SocketState::Connected(ours);
+        drop(listener);
+        drop(state);
+        // A thread might be waiting in `accept` for the connection.
+        this.wake_blocked_io()?;
+
+        Ok(0)
+    }
+
+    fn accept4(
+        &mut self,
+        fd: i32,
+        addr: Pointer<Option<Provenance>>,
+        addrlen: Pointer<Option<Provenance>>,
+        flags: i32,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let (flags, is_nonblock) = this.socket_type_flags(flags)?;
+        if flags != 0 {
+            throw_unsup_format!("unsupported flags {:#x} for `accept4`", flags);
+        }
+        let (state, listener_is_nonblock, domain) = match this.socket_state(fd) {
+            Ok(socket) => socket,
+            Err(error) => {
+                let result = this.socket_error(error)?;
+                return this.write_scalar(Scalar::from_i32(result), dest);
+            }
+        };
+        if !this.check_fd_limit(1)? {
+            return this.write_scalar(Scalar::from_i32(-1), dest);
+        }
+
+        let stream = match &*state.borrow() {
+            SocketState::Bound(listener) if listener.borrow().listening => {
+                let mut listener = listener.borrow_mut();
+                listener.generation = listener.generation.wrapping_add(1);
+                listener.pending.pop_front()
+            }
+            _ => {
+                let result = this.socket_error("EINVAL")?;
+                return this.write_scalar(Scalar::from_i32(result), dest);
+            }
+        };
+        let Some(stream) = stream else {
+            if listener_is_nonblock {
+                this.set_last_error_from_io_error(io::ErrorKind::WouldBlock)?;
+                return this.write_scalar(Scalar::from_i32(-1), dest);
+            }
+            // We write the result once there is a connection to accept.
+            this.block_on_fd(fd, BlockedIoOp::Accept { addr, addrlen, flags }, dest);
+            return Ok(());
+        };
+
+        if !this.ptr_is_null(addr)? {
+            this.write_socket_address(domain, stream.peer.as_ref(), addr, addrlen)?;
+        }
+
+        let fd = this
+            .machine
+            .file_handler
+            .insert_fd(Socket::new(domain, SocketState::Connected(stream), is_nonblock));
+        this.write_scalar(Scalar::from_i32(fd), dest)
+    }
+
+    fn getsockname(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        addr: &OpTy<'tcx, Provenance>,
+        addrlen: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let addr = this.read_pointer(addr)?;
+        let addrlen = this.read_pointer(addrlen)?;
+        let (state, _, domain) = match this.socket_state(fd) {
+            Ok(socket) => socket,
+            Err(error) => return this.socket_error(error),
+        };
+
+        let address = match &*state.borrow() {
+            SocketState::Unbound => None,
+            SocketState::Bound(listener) => Some(listener.borrow().address.clone()),
+            SocketState::Connected(stream) => stream.local.clone(),
+        };
+        this.write_socket_address(domain, address.as_ref(), addr, addrlen)?;
+
+        Ok(0)
+    }
+
+    fn getpeername(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        addr: &OpTy<'tcx, Provenance>,
+        addrlen: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let addr = this.read_pointer(addr)?;
+        let addrlen = this.read_pointer(addrlen)?;
+        let (state, _, domain) = match this.socket_state(fd) {
+            Ok(socket) => socket,
+            Err(error) => return this.socket_error(error),
+        };
+
+        let address = match &*state.borrow() {
+            SocketState::Connected(stream) => stream.peer.clone(),
+            _ => return this.socket_error("ENOTCONN"),
+        };
+        this.write_socket_address(domain, address.as_ref(), addr, addrlen)?;
+
+        Ok(0)
+    }
+
+    /// Only options that make no difference for the emulated sockets can be set.
+    fn setsockopt(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        level: &OpTy<'tcx, Provenance>,
+        option_name: &OpTy<'tcx, Provenance>,
+        option_value: &OpTy<'tcx, Provenance>,
+        option_len: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let level = this.read_scalar(level)?.to_i32()?;
+        let option_name = this.read_scalar(option_name)?.to_i32()?;
+        let _option_value = this.read_pointer(option_value)?;
+        let _option_len = this.read_scalar(option_len)?.to_u32()?;
+        if let Err(error) = this.socket_state(fd) {
+            return this.socket_error(error);
+        }
+
+        // Addresses can always be reused once the socket bound to them is closed, there is no
+        // buffering that `TCP_NODELAY` could turn off, and we never raise `SIGPIPE`.
+        let ignored = if level == this.eval_libc_i32("SOL_SOCKET")? {
+            option_name == this.eval_libc_i32("SO_REUSEADDR")?
+                || (this.tcx.sess.target.os == "macos"
+                    && option_name == this.eval_libc_i32("SO_NOSIGPIPE")?)
+        } else {
+            level == this.eval_libc_i32("IPPROTO_TCP")?
+                && option_name == this.eval_libc_i32("TCP_NODELAY")?
+        };
+        if !ignored {
+            throw_unsup_format!(
+                "`setsockopt` with level {} and option {} is not supported",
+                level,
+                option_name
+            );
+        }
+
+        Ok(0)
+    }
+
+    fn shutdown(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        how: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let how = this.read_scalar(how)?.to_i32()?;
+        let (state, ..) = match this.socket_state(fd) {
+            Ok(socket) => socket,
+            Err(error) => return this.socket_error(error),
+        };
+
+        let (read, write) = if how == this.eval_libc_i32("SHUT_RD")? {
+            (true, false)
+        } else if how == this.eval_libc_i32("SHUT_WR")? {
+            (false, true)
+        } else if how == this.eval_libc_i32("SHUT_RDWR")? {
+            (true, true)
+        } else {
+            return this.socket_error("EINVAL");
+        };
+        let state = state.borrow();
+        let SocketState::Connected(stream) = &*state else {
+            return this.socket_error("ENOTCONN");
+        };
+        stream.shutdown(read, write);
+        drop(state);
+        // A thread might be waiting in `recv` for the end of file.
+        this.wake_blocked_io()?;
+
+        Ok(0)
+    }
+
+    fn send(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        buf: &OpTy<'tcx, Provenance>,
+        len: &OpTy<'tcx, Provenance>,
+        flags: &OpTy<'tcx, Provenance>,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let buf = this.read_pointer(buf)?;
+        let len = this.read_scalar(len)?.to_machine_usize(this)?;
+        let mut flags = this.read_scalar(flags)?.to_i32()?;
+
+        // We never raise `SIGPIPE`, so `MSG_NOSIGNAL` can be ignored. macOS does not have it.
+        if this.tcx.sess.target.os != "macos" {
+            flags &= !this.eval_libc_i32("MSG_NOSIGNAL")?;
+        }
+        if flags != 0 {
+            throw_unsup_format!("unsupported flags {:#x} for `send`", flags);
+        }
+        if let Err(error) = this.socket_state(fd) {
+            let result = this.socket_error(error)?;
+            return this.write_scalar(Scalar::from_machine_isize(result, this), dest);
+        }
+
+        this.write(fd, buf, len, dest)
+    }
+
+    fn recv(
+        &mut self,
+        fd: &OpTy<'tcx, Provenance>,
+        buf: &OpTy<'tcx, Provenance>,
+        len: &OpTy<'tcx, Provenance>,
+        flags: &OpTy<'tcx, Provenance>,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let buf = this.read_pointer(buf)?;
+        let len = this.read_scalar(len)?.to_machine_usize(this)?;
+        let flags = this.read_scalar(flags)?.to_i32()?;
+
+        if flags != 0 {
+            throw_unsup_format!("unsupported flags {:#x} for `recv`", flags);
+        }
+        if let Err(error) = this.socket_state(fd) {
+            let result = this.socket_error(error)?;
+            return this.write_scalar(Scalar::from_machine_isize(result, this), dest);
+        }
+
+        this.read(fd, buf, len, dest)
+    }
+}
@@ -149,6 +149,20 @@ fn rwlock_get_id<'mir, 'tcx: 'mir>(
     ecx.read_scalar_at_offset_atomic(rwlock_op, 4, ecx.machine.layouts.u32, AtomicReadOrd::Relaxed)
 }
 
+fn rwlock_set_id<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    rwlock_op: &OpTy<'tcx, Provenance>,
+    id: impl Into<Scalar<Provenance>>,
+) -> InterpResult<'tcx, ()> {
+    ecx.write_scalar_at_offset_atomic(
+        rwlock_op,
+        4,
+        id,
+        layout_of_maybe_uninit(ecx.tcx, ecx.tcx.types.u32),
+        AtomicWriteOrd::Relaxed,
+    )
+}
+
 fn rwlock_get_or_create_id<'mir, 'tcx: 'mir>(
     ecx: &mut MiriEvalContext<'mir, 'tcx>,
     rwlock_op: &OpTy<'tcx, Provenance>,
@@ -176,6 +190,69 @@ fn rwlock_get_or_create_id<'mir, 'tcx: 'mir>(
     })
 }
 
+// pthread_rwlockattr_t
+
+// Our chosen memory layout for emulation (does not have to match the platform layout!):
+// store an i32 in the first four bytes equal to the corresponding libc process-shared constant
+// (`pthread_rwlockattr_t` is at least 8 bytes on all supported platforms).
+
+fn rwlockattr_get_pshared<'mir, 'tcx: 'mir>(
+    ecx: &MiriEvalContext<'mir, 'tcx>,
+    attr_op: &OpTy<'tcx, Provenance>,
+) -> InterpResult<'tcx, Scalar<Provenance>> {
+    ecx.read_scalar_at_offset(attr_op, 0, ecx.machine.layouts.i32)
+}
+
+fn rwlockattr_set_pshared<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    attr_op: &OpTy<'tcx, Provenance>,
+    pshared: impl Into<Scalar<Provenance>>,
+) -> InterpResult<'tcx, ()> {
+    ecx.write_scalar_at_offset(
+        attr_op,
+        0,
+        pshared,
+        layout_of_maybe_uninit(ecx.tcx, ecx.machine.layouts.i32.ty),
+    )
+}
+
+/// Convert the absolute `CLOCK_REALTIME` timeout of a timed lock operation into a `Time`.
+/// Returns `None` if the timeout is invalid, and `Some(None)` if it is too far in the future to
+/// be represented, in which case the operation never times out.
+fn lock_timeout_time<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    abstime_op: &OpTy<'tcx, Provenance>,
+) -> InterpResult<'tcx, Option<Option<Time>>> {
+    let duration = ecx.read_timespec(&ecx.deref_operand(abstime_op)?)?;
+    Ok(duration.map(|duration| SystemTime::UNIX_EPOCH.checked_add(duration).map(Time::RealTime)))
+}
+
+/// Make the thread that is waiting for the rwlock give up at `timeout_time`. If the thread gets
+/// the lock before that, the callback is removed when the thread is taken out of the queue.
+fn register_rwlock_timeout<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    id: RwLockId,
+    thread: ThreadId,
+    timeout_time: Time,
+    dest: &PlaceTy<'tcx, Provenance>,
+) {
+    let dest = dest.clone();
+    ecx.register_timeout_callback(
+        thread,
+        timeout_time,
+        Box::new(move |ecx| {
+            ecx.rwlock_remove_waiter(id, thread);
+            ecx.unblock_thread(thread);
+
+            // Set the return value: we timed out.
+            let etimedout = ecx.eval_libc("ETIMEDOUT")?;
+            ecx.write_scalar(etimedout, &dest)?;
+
+            Ok(())
+        }),
+    );
+}
+
 // pthread_condattr_t
 
 // Our chosen memory layout for emulation (does not have to match the platform layout!):
@@ -437,6 +514,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let kind = mutex_get_kind(this, mutex_op)?;
         let id = mutex_get_or_create_id(this, mutex_op)?;
         let active_thread = this.get_active_thread();
+        this.mutex_check_lock_order(id);
 
         if this.mutex_is_locked(id) {
             let owner_thread = this.mutex_get_owner(id);
@@ -562,6 +640,85 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(0)
     }
 
+    fn pthread_rwlockattr_init(
+        &mut self,
+        attr_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let default_pshared = this.eval_libc("PTHREAD_PROCESS_PRIVATE")?;
+        rwlockattr_set_pshared(this, attr_op, default_pshared)?;
+
+        Ok(0)
+    }
+
+    fn pthread_rwlockattr_setpshared(
+        &mut self,
+        attr_op: &OpTy<'tcx, Provenance>,
+        pshared_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        // There is only one process, so both values behave the same.
+        let pshared = this.read_scalar(pshared_op)?;
+        if pshared == this.eval_libc("PTHREAD_PROCESS_PRIVATE")?
+            || pshared == this.eval_libc("PTHREAD_PROCESS_SHARED")?
+        {
+            rwlockattr_set_pshared(this, attr_op, pshared)?;
+        } else {
+            return this.eval_libc_i32("EINVAL");
+        }
+
+        Ok(0)
+    }
+
+    fn pthread_rwlockattr_getpshared(
+        &mut self,
+        attr_op: &OpTy<'tcx, Provenance>,
+        pshared_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let pshared = rwlockattr_get_pshared(this, attr_op)?;
+        this.write_scalar(pshared, &this.deref_operand(pshared_op)?.into())?;
+
+        Ok(0)
+    }
+
+    fn pthread_rwlockattr_destroy(
+        &mut self,
+        attr_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        // Destroying an uninit pthread_rwlockattr is UB, so check to make sure it's not uninit.
+        rwlockattr_get_pshared(this, attr_op)?;
+
+        // This might lead to false positives, see comment in pthread_mutexattr_destroy
+        this.write_uninit(&this.deref_operand(attr_op)?.into())?;
+
+        Ok(0)
+    }
+
+    fn pthread_rwlock_init(
+        &mut self,
+        rwlock_op: &OpTy<'tcx, Provenance>,
+        attr_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let attr = this.read_pointer(attr_op)?;
+        if !this.ptr_is_null(attr)? {
+            // Using an uninit pthread_rwlockattr is UB. We do not need the value itself.
+            rwlockattr_get_pshared(this, attr_op)?;
+        }
+
+        // Write 0 to use the same code path as the static initializers.
+        rwlock_set_id(this, rwlock_op, Scalar::from_i32(0))?;
+
+        Ok(0)
+    }
+
     fn pthread_rwlock_rdlock(
         &mut self,
         rwlock_op: &OpTy<'tcx, Provenance>,
@@ -597,6 +754,41 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         }
     }
 
+    fn pthread_rwlock_timedrdlock(
+        &mut self,
+        rwlock_op: &OpTy<'tcx, Provenance>,
+        abstime_op: &OpTy<'tcx, Provenance>,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        this.check_no_isolation("`pthread_rwlock_timedrdlock`")?;
+
+        let id = rwlock_get_or_create_id(this, rwlock_op)?;
+        let active_thread = this.get_active_thread();
+
+        if !this.rwlock_is_write_locked(id) {
+            this.rwlock_reader_lock(id, active_thread);
+            this.write_scalar(Scalar::from_i32(0), dest)?;
+            return Ok(());
+        }
+        // The timeout is only checked if the lock cannot be acquired immediately.
+        let Some(timeout_time) = lock_timeout_time(this, abstime_op)? else {
+            let einval = this.eval_libc("EINVAL")?;
+            this.write_scalar(einval, dest)?;
+            return Ok(());
+        };
+
+        this.rwlock_enqueue_and_block_reader(id, active_thread);
+        // We return success for now and override it in the timeout callback.
+        this.write_scalar(Scalar::from_i32(0), dest)?;
+        if let Some(timeout_time) = timeout_time {
+            register_rwlock_timeout(this, id, active_thread, timeout_time, dest);
+        }
+
+        Ok(())
+    }
+
     fn pthread_rwlock_wrlock(
         &mut self,
         rwlock_op: &OpTy<'tcx, Provenance>,
@@ -627,6 +819,43 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(0)
     }
 
+    fn pthread_rwlock_timedwrlock(
+        &mut self,
+        rwlock_op: &OpTy<'tcx, Provenance>,
+        abstime_op: &OpTy<'tcx, Provenance>,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        this.check_no_isolation("`pthread_rwlock_timedwrlock`")?;
+
+        let id = rwlock_get_or_create_id(this, rwlock_op)?;
+        let active_thread = this.get_active_thread();
+
+        if !this.rwlock_is_locked(id) {
+            this.rwlock_writer_lock(id, active_thread);
+            this.write_scalar(Scalar::from_i32(0), dest)?;
+            return Ok(());
+        }
+        // The timeout is only checked if the lock cannot be acquired immediately.
+        let Some(timeout_time) = lock_timeout_time(this, abstime_op)? else {
+            let einval = this.eval_libc("EINVAL")?;
+            this.write_scalar(einval, dest)?;
+            return Ok(());
+        };
+
+        // Like `pthread_rwlock_wrlock`, this deadlocks (until the timeout) if the lock is already
+        // held by this thread.
+        this.rwlock_enqueue_and_block_writer(id, active_thread);
+        // We return success for now and override it in the timeout callback.
+        this.write_scalar(Scalar::from_i32(0), dest)?;
+        if let Some(timeout_time) = timeout_time {
+            register_rwlock_timeout(this, id, active_thread, timeout_time, dest);
+        }
+
+        Ok(())
+    }
+
     fn pthread_rwlock_trywrlock(
         &mut self,
         rwlock_op: &OpTy<'tcx, Provenance>,
@@ -773,8 +1002,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     fn pthread_cond_signal(&mut self, cond_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
         let this = self.eval_context_mut();
         let id = cond_get_or_create_id(this, cond_op)?;
-        if let Some((thread, mutex)) = this.condvar_signal(id) {
-            post_cond_signal(this, thread, mutex)?;
+        if let Some((thread, lock)) = this.condvar_signal(id) {
+            if let CondvarLock::Mutex(mutex) = lock {
+                post_cond_signal(this, thread, mutex)?;
+            } else {
+                panic!("condvar should not have an rwlock on unix");
+            }
         }
 
         Ok(0)
@@ -787,8 +1020,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let this = self.eval_context_mut();
         let id = cond_get_or_create_id(this, cond_op)?;
 
-        while let Some((thread, mutex)) = this.condvar_signal(id) {
-            post_cond_signal(this, thread, mutex)?;
+        while let Some((thread, lock)) = this.condvar_signal(id) {
+            if let CondvarLock::Mutex(mutex) = lock {
+                post_cond_signal(this, thread, mutex)?;
+            } else {
+                panic!("condvar should not have an rwlock on unix");
+            }
         }
 
         Ok(0)
@@ -806,7 +1043,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         let active_thread = this.get_active_thread();
 
         release_cond_mutex_and_block(this, active_thread, mutex_id)?;
-        this.condvar_wait(id, active_thread, mutex_id);
+        this.condvar_wait(id, active_thread, CondvarLock::Mutex(mutex_id));
 
         Ok(0)
     }
@@ -820,8 +1057,6 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
     ) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
 
-        this.check_no_isolation("`pthread_cond_timedwait`")?;
-
         let id = cond_get_or_create_id(this, cond_op)?;
         let mutex_id = mutex_get_or_create_id(this, mutex_op)?;
         let active_thread = this.get_active_thread();
@@ -838,20 +1073,23 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         };
 
         let timeout_time = if clock_id == this.eval_libc_i32("CLOCK_REALTIME")? {
-            Time::RealTime(SystemTime::UNIX_EPOCH.checked_add(duration).unwrap())
+            this.check_no_isolation("`pthread_cond_timedwait` with `CLOCK_REALTIME`")?;
+            SystemTime::UNIX_EPOCH.checked_add(duration).map(Time::RealTime)
         } else if clock_id == this.eval_libc_i32("CLOCK_MONOTONIC")? {
-            Time::Monotonic(this.machine.time_anchor.checked_add(duration).unwrap())
+            this.monotonic_deadline_since_anchor(duration)
         } else {
             throw_unsup_format!("unsupported clock id: {}", clock_id);
         };
 
         release_cond_mutex_and_block(this, active_thread, mutex_id)?;
-        this.condvar_wait(id, active_thread, mutex_id);
+        this.condvar_wait(id, active_thread, CondvarLock::Mutex(mutex_id));
 
         // We return success for now and override it in the timeout callback.
         this.write_scalar(Scalar::from_i32(0), dest)?;
 
-        // Register the timeout callback.
+        // Register the timeout callback. A deadline that is too far in the future to be represented
+        // is never reached, so then we wait without a timeout.
+        let Some(timeout_time) = timeout_time else { return Ok(()) };
         let dest = dest.clone();
         this.register_timeout_callback(
             active_thread,
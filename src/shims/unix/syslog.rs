@@ -0,0 +1,283 @@
+//! Emulation of `openlog`, `syslog`, `setlogmask` and `closelog`. There is no system logger, so
+//! the messages are shown as notes and kept for `miri_get_syslog` instead.
+
+use std::iter::Peekable;
+
+use crate::*;
+
+#[derive(Debug)]
+pub struct Syslog {
+    /// The identifier set by `openlog`, which is put in front of every message.
+    ident: Option<Vec<u8>>,
+    /// The mask of the priorities that are logged, as set by `setlogmask`.
+    mask: i32,
+    /// The messages logged so far, for `miri_get_syslog`.
+    pub(crate) messages: Vec<String>,
+}
+
+impl Default for Syslog {
+    fn default() -> Self {
+        // All priorities are logged by default.
+        Syslog { ident: None, mask: 0xff, messages: Vec::new() }
+    }
+}
+
+/// The flags, field width and precision of a `printf` conversion.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    /// Pads `sign` and `digits` to the field width.
+    fn pad(&self, out: &mut Vec<u8>, sign: &str, digits: &[u8]) {
+        let len = sign.len().saturating_add(digits.len());
+        let padding = self.width.saturating_sub(len);
+        if self.left {
+            out.extend_from_slice(sign.as_bytes());
+            out.extend_from_slice(digits);
+            out.extend(std::iter::repeat(b' ').take(padding));
+        } else if self.zero {
+            out.extend_from_slice(sign.as_bytes());
+            out.extend(std::iter::repeat(b'0').take(padding));
+            out.extend_from_slice(digits);
+        } else {
+            out.extend(std::iter::repeat(b' ').take(padding));
+            out.extend_from_slice(sign.as_bytes());
+            out.extend_from_slice(digits);
+        }
+    }
+
+    /// Pads `digits` with leading zeros to the precision.
+    fn with_precision(&self, digits: String) -> Vec<u8> {
+        let zeros = self.precision.unwrap_or(0).saturating_sub(digits.len());
+        std::iter::repeat(b'0').take(zeros).chain(digits.into_bytes()).collect()
+    }
+}
+
+/// Parses the field width or precision at the start of `format`. Returns `None` if it is `*`,
+/// which means that it is passed as an argument.
+fn parse_number(format: &mut Peekable<impl Iterator<Item = u8>>) -> Option<usize> {
+    if format.next_if_eq(&b'*').is_some() {
+        return None;
+    }
+    let mut value = 0usize;
+    while let Some(digit) = format.next_if(u8::is_ascii_digit) {
+        value = value.saturating_mul(10).saturating_add(usize::from(digit - b'0'));
+    }
+    Some(value)
+}
+
+/// The sign to put in front of a number.
+fn sign(negative: bool, spec: &Spec) -> &'static str {
+    if negative {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Formats `format` like `printf` does with the variadic arguments `args`. Length modifiers
+    /// are accepted but not needed, since the arguments are read according to their type.
+    fn format_printf(
+        &mut self,
+        name: &str,
+        format: &[u8],
+        args: &[OpTy<'tcx, Provenance>],
+    ) -> InterpResult<'tcx, String> {
+        let this = self.eval_context_mut();
+
+        let mut out = Vec::new();
+        let mut args = args.iter();
+        let mut next_arg = || {
+            args.next().ok_or_else(|| {
+                err_ub_format!("not enough arguments for the format string of `{}`", name)
+            })
+        };
+        let mut format = format.iter().copied().peekable();
+        while let Some(byte) = format.next() {
+            if byte != b'%' {
+                out.push(byte);
+                continue;
+            }
+
+            let mut spec = Spec::default();
+            while let Some(flag) = format.next_if(|byte| b"-0+ #".contains(byte)) {
+                match flag {
+                    b'-' => spec.left = true,
+                    b'0' => spec.zero = true,
+                    b'+' => spec.plus = true,
+                    b' ' => spec.space = true,
+                    _ => spec.alternate = true,
+                }
+            }
+            // A negative width given as an argument means left alignment.
+            spec.width = match parse_number(&mut format) {
+                Some(width) => width,
+                None => {
+                    let width = this.read_scalar(next_arg()?)?.to_i32()?;
+                    spec.left |= width < 0;
+                    usize::try_from(width.unsigned_abs()).unwrap()
+                }
+            };
+            // A negative precision given as an argument is ignored.
+            if format.next_if_eq(&b'.').is_some() {
+                spec.precision = match parse_number(&mut format) {
+                    Some(precision) => Some(precision),
+                    None => usize::try_from(this.read_scalar(next_arg()?)?.to_i32()?).ok(),
+                };
+            }
+            while format.next_if(|byte| b"hlLqjzt".contains(byte)).is_some() {}
+
+            let Some(conversion) = format.next() else {
+                throw_ub_format!(
+                    "incomplete conversion at the end of the format string of `{}`",
+                    name
+                );
+            };
+            match conversion {
+                b'%' => out.push(b'%'),
+                b'd' | b'i' => {
+                    let arg = next_arg()?;
+                    let value = this.read_scalar(arg)?.to_int(arg.layout.size)?;
+                    let digits = spec.with_precision(value.unsigned_abs().to_string());
+                    spec.pad(&mut out, sign(value < 0, &spec), &digits);
+                }
+                b'u' | b'x' | b'X' | b'o' => {
+                    let arg = next_arg()?;
+                    let value = this.read_scalar(arg)?.to_uint(arg.layout.size)?;
+                    let (digits, prefix) = match conversion {
+                        b'u' => (value.to_string(), ""),
+                        b'x' => (format!("{value:x}"), "0x"),
+                        b'X' => (format!("{value:X}"), "0X"),
+                        _ => (format!("{value:o}"), "0"),
+                    };
+                    let prefix = if spec.alternate && value != 0 { prefix } else { "" };
+                    spec.pad(&mut out, prefix, &spec.with_precision(digits));
+                }
+                b'c' => {
+                    let arg = next_arg()?;
+                    let value = this.read_scalar(arg)?.to_int(arg.layout.size)?;
+                    spec.pad(&mut out, "", &[u8::try_from(value & 0xff).unwrap()]);
+                }
+                b's' => {
+                    let ptr = this.read_pointer(next_arg()?)?;
+                    let string =
+                        if this.ptr_is_null(ptr)? { &b"(null)"[..] } else { this.read_c_str(ptr)? };
+                    let len = string.len().min(spec.precision.unwrap_or(usize::MAX));
+                    spec.pad(&mut out, "", &string[..len]);
+                }
+                b'p' => {
+                    let ptr = this.read_pointer(next_arg()?)?;
+                    let digits = if this.ptr_is_null(ptr)? {
+                        "(nil)".to_owned()
+                    } else {
+                        format!("{:#x}", ptr.addr().bytes())
+                    };
+                    spec.pad(&mut out, "", digits.as_bytes());
+                }
+                b'f' | b'F' => {
+                    let value = f64::from_bits(this.read_scalar(next_arg()?)?.to_u64()?);
+                    let digits = format!("{:.*}", spec.precision.unwrap_or(6), value.abs());
+                    spec.pad(&mut out, sign(value.is_sign_negative(), &spec), digits.as_bytes());
+                }
+                b'm' => {
+                    // `%m` is the message of the current `errno` and takes no argument.
+                    let errnum = this.get_last_error()?;
+                    let message = match this.try_errnum_to_io_error(errnum)? {
+                        Some(kind) => std::io::Error::from(kind).to_string(),
+                        None => format!("Unknown error {}", errnum.to_i32()?),
+                    };
+                    spec.pad(&mut out, "", message.as_bytes());
+                }
+                _ =>
+                    throw_unsup_format!(
+                        "unsupported conversion `%{}` in the format string of `{}`",
+                        char::from(conversion),
+                        name
+                    ),
+            }
+        }
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// The identifier is copied, so it does not need to outlive the call. The options and the
+    /// facility make no difference.
+    fn openlog(
+        &mut self,
+        ident_op: &OpTy<'tcx, Provenance>,
+        option_op: &OpTy<'tcx, Provenance>,
+        facility_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let ident = this.read_pointer(ident_op)?;
+        this.read_scalar(option_op)?.to_i32()?;
+        this.read_scalar(facility_op)?.to_i32()?;
+
+        this.machine.syslog.ident =
+            if this.ptr_is_null(ident)? { None } else { Some(this.read_c_str(ident)?.to_owned()) };
+        Ok(())
+    }
+
+    fn closelog(&mut self) {
+        let this = self.eval_context_mut();
+        this.machine.syslog.ident = None;
+    }
+
+    /// Returns the previous mask. A mask of 0 leaves it unchanged.
+    fn setlogmask(&mut self, mask_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let mask = this.read_scalar(mask_op)?.to_i32()?;
+        let old = this.machine.syslog.mask;
+        if mask != 0 {
+            this.machine.syslog.mask = mask;
+        }
+        Ok(old)
+    }
+
+    /// `args` are the arguments of `syslog`: the priority, the format string and the values to
+    /// format.
+    fn syslog(&mut self, args: &[OpTy<'tcx, Provenance>]) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let [priority, format, args @ ..] = args else {
+            throw_ub_format!(
+                "incorrect number of arguments for `syslog`: got {}, expected at least 2",
+                args.len()
+            );
+        };
+        let priority = this.read_scalar(priority)?.to_i32()?;
+        let format = this.read_c_str(this.read_pointer(format)?)?.to_owned();
+
+        // The lowest three bits of the priority are the level, the rest is the facility.
+        let level = priority & 7;
+        if this.machine.syslog.mask & (1 << level) == 0 {
+            return Ok(());
+        }
+        let message = this.format_printf("syslog", &format, args)?;
+        let message = match &this.machine.syslog.ident {
+            Some(ident) => format!("{}: {message}", String::from_utf8_lossy(ident)),
+            None => message,
+        };
+        register_diagnostic(NonHaltingDiagnostic::Syslog(message.clone()));
+        this.machine.syslog.messages.push(message);
+        Ok(())
+    }
+}
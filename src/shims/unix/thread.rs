@@ -19,13 +19,15 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         let func_arg = this.read_immediate(arg)?;
 
-        this.start_thread(
+        let new_thread = this.start_thread(
             Some(thread_info_place),
             start_routine,
             Abi::C { unwind: false },
             func_arg,
             this.layout_of(this.tcx.types.usize)?,
         )?;
+        let parent = this.get_active_thread();
+        this.machine.signals.thread_created(parent, new_thread);
 
         Ok(0)
     }
@@ -67,6 +69,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(Scalar::from_machine_usize(thread_id.into(), this))
     }
 
+    fn pthread_cancel(&mut self, _thread: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        throw_unsup_format!(
+            "Miri does not support thread cancellation: `pthread_cancel` is not supported"
+        );
+    }
+
     fn pthread_setname_np(
         &mut self,
         thread: Scalar<Provenance>,
@@ -83,6 +91,37 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(Scalar::from_u32(0))
     }
 
+    fn prctl(&mut self, args: &[OpTy<'tcx, Provenance>]) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        // `prctl` is variadic. All options we support take one argument.
+        if args.len() < 2 {
+            throw_ub_format!(
+                "incorrect number of arguments for `prctl`: got {}, expected at least 2",
+                args.len()
+            );
+        }
+        let option = this.read_scalar(&args[0])?.to_i32()?;
+        let thread = this.get_active_thread();
+        // Thread names are at most 16 bytes long, including the null terminator.
+        if option == this.eval_libc_i32("PR_SET_NAME")? {
+            let name = this.read_pointer(&args[1])?;
+            let mut name = this.read_c_str(name)?.to_owned();
+            name.truncate(15);
+            this.set_thread_name(thread, name);
+            Ok(0)
+        } else if option == this.eval_libc_i32("PR_GET_NAME")? {
+            let buf = this.read_pointer(&args[1])?;
+            let mut name = this.get_thread_name(thread).to_owned();
+            name.truncate(15);
+            name.push(0);
+            this.write_bytes_ptr(buf, name)?;
+            Ok(0)
+        } else {
+            throw_unsup_format!("Miri does not support `prctl` with option {}", option);
+        }
+    }
+
     fn sched_yield(&mut self) -> InterpResult<'tcx, i32> {
         let this = self.eval_context_mut();
 
@@ -0,0 +1,205 @@
+//! Emulation of the user and group identity of the process. Under isolation, the process runs as
+//! a made-up user named `miri`, with the IDs set by `-Zmiri-uid` and `-Zmiri-gid`. Otherwise, the
+//! identity of the host process is used.
+
+use std::io;
+use std::iter;
+
+use rustc_target::abi::Size;
+
+use crate::*;
+
+/// The user name and home directory of the made-up user.
+const USER_NAME: &str = "miri";
+const HOME_DIR: &str = "/home/miri";
+
+/// The contents of a `passwd` entry.
+struct Passwd {
+    name: Vec<u8>,
+    passwd: Vec<u8>,
+    uid: u32,
+    gid: u32,
+    gecos: Vec<u8>,
+    dir: Vec<u8>,
+    shell: Vec<u8>,
+}
+
+#[cfg(unix)]
+fn host_ids() -> (u32, u32, u32, u32) {
+    // SAFETY: these functions cannot fail.
+    unsafe { (libc::getuid(), libc::geteuid(), libc::getgid(), libc::getegid()) }
+}
+
+#[cfg(not(unix))]
+fn host_ids() -> (u32, u32, u32, u32) {
+    unreachable!()
+}
+
+/// The `passwd` entry of the host user `uid`, or `None` if there is no such user.
+#[cfg(unix)]
+fn host_passwd(uid: u32) -> io::Result<Option<Passwd>> {
+    use std::ffi::CStr;
+    use std::mem::MaybeUninit;
+    use std::ptr;
+
+    let mut buf = vec![0u8; 1024];
+    loop {
+        let mut pwd = MaybeUninit::<libc::passwd>::uninit();
+        let mut result = ptr::null_mut();
+        // SAFETY: `buf` is valid for `buf.len()` bytes, and the entry is only read if it was
+        // found.
+        let error = unsafe {
+            let buf_ptr = buf.as_mut_ptr().cast();
+            libc::getpwuid_r(uid, pwd.as_mut_ptr(), buf_ptr, buf.len(), &mut result)
+        };
+        if error == libc::ERANGE {
+            buf.resize(buf.len().checked_mul(2).unwrap(), 0);
+            continue;
+        }
+        if error != 0 {
+            return Err(io::Error::from_raw_os_error(error));
+        }
+        if result.is_null() {
+            return Ok(None);
+        }
+        // SAFETY: `getpwuid_r` found the entry and initialized it.
+        let pwd = unsafe { pwd.assume_init() };
+        let string = |ptr: *const libc::c_char| {
+            if ptr.is_null() {
+                Vec::new()
+            } else {
+                // SAFETY: the strings of the entry are null-terminated and live in `buf`.
+                unsafe { CStr::from_ptr(ptr) }.to_bytes().to_vec()
+            }
+        };
+        return Ok(Some(Passwd {
+            name: string(pwd.pw_name),
+            passwd: string(pwd.pw_passwd),
+            uid: pwd.pw_uid,
+            gid: pwd.pw_gid,
+            gecos: string(pwd.pw_gecos),
+            dir: string(pwd.pw_dir),
+            shell: string(pwd.pw_shell),
+        }));
+    }
+}
+
+#[cfg(not(unix))]
+fn host_passwd(_uid: u32) -> io::Result<Option<Passwd>> {
+    unreachable!()
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// The real and effective user and group IDs of the process. Hosts that are not a Unix have
+    /// no such IDs, so there the made-up ones are used even without isolation.
+    fn ids(&self) -> (u32, u32, u32, u32) {
+        let this = self.eval_context_ref();
+        if this.machine.communicate() && cfg!(unix) {
+            host_ids()
+        } else {
+            let (uid, gid) = (this.machine.uid, this.machine.gid);
+            (uid, uid, gid, gid)
+        }
+    }
+
+    /// The `passwd` entry of the user `uid`, or `None` if there is no such user. Under isolation,
+    /// only the made-up user exists.
+    fn passwd(&self, uid: u32) -> io::Result<Option<Passwd>> {
+        let this = self.eval_context_ref();
+        if this.machine.communicate() && cfg!(unix) {
+            return host_passwd(uid);
+        }
+        if uid != this.machine.uid {
+            return Ok(None);
+        }
+        Ok(Some(Passwd {
+            name: USER_NAME.into(),
+            passwd: "x".into(),
+            uid,
+            gid: this.machine.gid,
+            gecos: USER_NAME.into(),
+            dir: HOME_DIR.into(),
+            shell: "/bin/sh".into(),
+        }))
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    fn getuid(&self) -> u32 {
+        self.eval_context_ref().ids().0
+    }
+
+    fn geteuid(&self) -> u32 {
+        self.eval_context_ref().ids().1
+    }
+
+    fn getgid(&self) -> u32 {
+        self.eval_context_ref().ids().2
+    }
+
+    fn getegid(&self) -> u32 {
+        self.eval_context_ref().ids().3
+    }
+
+    /// The strings of the entry are stored in `buf`. If they do not fit, `ERANGE` is returned.
+    fn getpwuid_r(
+        &mut self,
+        uid_op: &OpTy<'tcx, Provenance>,
+        pwd_op: &OpTy<'tcx, Provenance>,
+        buf_op: &OpTy<'tcx, Provenance>,
+        buflen_op: &OpTy<'tcx, Provenance>,
+        result_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        let uid = this.read_scalar(uid_op)?.to_u32()?;
+        let pwd = this.deref_operand(pwd_op)?;
+        let buf = this.read_pointer(buf_op)?;
+        let buflen = this.read_scalar(buflen_op)?.to_machine_usize(this)?;
+        let result = this.deref_operand(result_op)?;
+
+        this.write_null(&result.into())?;
+        let entry = match this.passwd(uid) {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return Ok(Scalar::from_i32(0)),
+            Err(e) => return this.io_error_to_errnum(e.kind()),
+        };
+
+        // Not all targets have `pw_gecos`.
+        let fields = &pwd.layout.ty.ty_adt_def().unwrap().non_enum_variant().fields;
+        let strings: Vec<_> = [
+            ("pw_name", &entry.name),
+            ("pw_passwd", &entry.passwd),
+            ("pw_gecos", &entry.gecos),
+            ("pw_dir", &entry.dir),
+            ("pw_shell", &entry.shell),
+        ]
+        .into_iter()
+        .filter(|(name, _)| fields.iter().any(|field| field.name.as_str() == *name))
+        .collect();
+        let needed: usize = strings.iter().map(|(_, string)| string.len().saturating_add(1)).sum();
+        if u64::try_from(needed).unwrap() > buflen {
+            return this.eval_libc("ERANGE");
+        }
+
+        // Fields that are not set, like the ones that only exist on some targets, are zero.
+        this.write_bytes_ptr(pwd.ptr, iter::repeat(0u8).take(pwd.layout.size.bytes_usize()))?;
+        let mut offset = Size::ZERO;
+        for (field, string) in &strings {
+            let ptr = buf.offset(offset, this)?;
+            this.write_bytes_ptr(ptr, string.iter().copied().chain(iter::once(0)))?;
+            let field = this.mplace_field_named(&pwd, field)?;
+            this.write_pointer(ptr, &field.into())?;
+            offset += Size::from_bytes(string.len().saturating_add(1));
+        }
+        this.write_int_fields_named(
+            &[("pw_uid", entry.uid.into()), ("pw_gid", entry.gid.into())],
+            &pwd,
+        )?;
+
+        this.write_pointer(pwd.ptr, &result.into())?;
+        Ok(Scalar::from_i32(0))
+    }
+}
@@ -5,13 +5,19 @@ use rustc_target::spec::abi::Abi;
 use log::trace;
 
 use crate::helpers::check_arg_count;
+use crate::shims::windows::fs::EvalContextExt as _;
 use crate::shims::windows::handle::{EvalContextExt as _, Handle, PseudoHandle};
+use crate::shims::windows::sync::EvalContextExt as _;
 use crate::*;
 
 #[derive(Debug, Copy, Clone)]
 pub enum Dlsym {
+    NtReadFile,
     NtWriteFile,
     SetThreadDescription,
+    WaitOnAddress,
+    WakeByAddressSingle,
+    WakeByAddressAll,
 }
 
 impl Dlsym {
@@ -20,8 +26,12 @@ impl Dlsym {
     pub fn from_str<'tcx>(name: &str) -> InterpResult<'tcx, Option<Dlsym>> {
         Ok(match name {
             "GetSystemTimePreciseAsFileTime" => None,
+            "NtReadFile" => Some(Dlsym::NtReadFile),
             "NtWriteFile" => Some(Dlsym::NtWriteFile),
             "SetThreadDescription" => Some(Dlsym::SetThreadDescription),
+            "WaitOnAddress" => Some(Dlsym::WaitOnAddress),
+            "WakeByAddressSingle" => Some(Dlsym::WakeByAddressSingle),
+            "WakeByAddressAll" => Some(Dlsym::WakeByAddressAll),
             _ => throw_unsup_format!("unsupported Windows dlsym: {}", name),
         })
     }
@@ -44,10 +54,61 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         this.check_abi(abi, Abi::System { unwind: false })?;
 
         match dlsym {
+            Dlsym::NtReadFile => {
+                if !this.frame_in_std() {
+                    throw_unsup_format!(
+                        "`NtReadFile` support is crude and just enough for files to work"
+                    );
+                }
+
+                let [
+                    handle,
+                    _event,
+                    _apc_routine,
+                    _apc_context,
+                    io_status_block,
+                    buf,
+                    n,
+                    byte_offset,
+                    _key,
+                ] = check_arg_count(args)?;
+                let handle = this.read_scalar(handle)?;
+                let buf = this.read_pointer(buf)?;
+                let n = this.read_scalar(n)?.to_u32()?;
+                let byte_offset = this.read_scalar(byte_offset)?.to_machine_usize(this)?; // is actually a pointer
+                let io_status_block = this.deref_operand(io_status_block)?;
+
+                if byte_offset != 0 {
+                    throw_unsup_format!(
+                        "`NtReadFile` `ByteOffset` paremeter is non-null, which is unsupported"
+                    );
+                }
+
+                let Some(fd) = this.handle_to_fd(handle)? else {
+                    this.invalid_handle("NtReadFile")?
+                };
+                let read = this.read_from_fd(fd, buf, n, "NtReadFile")?.ok();
+                if let Some(read) = read {
+                    let io_status_information =
+                        this.mplace_field_named(&io_status_block, "Information")?;
+                    this.write_scalar(
+                        Scalar::from_machine_usize(read.into(), this),
+                        &io_status_information.into(),
+                    )?;
+                }
+                // Reading nothing at the end of the file is STATUS_END_OF_FILE, and for errors we
+                // pick STATUS_IO_DEVICE_ERROR, like `NtWriteFile`.
+                let status = match read {
+                    Some(0) if n > 0 => 0xC0000011u32,
+                    Some(_) => 0,
+                    None => 0xC0000185u32,
+                };
+                this.write_scalar(Scalar::from_u32(status), dest)?;
+            }
             Dlsym::NtWriteFile => {
                 if !this.frame_in_std() {
                     throw_unsup_format!(
-                        "`NtWriteFile` support is crude and just enough for stdout to work"
+                        "`NtWriteFile` support is crude and just enough for stdout and files to work"
                     );
                 }
 
@@ -62,7 +123,8 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                     byte_offset,
                     _key,
                 ] = check_arg_count(args)?;
-                let handle = this.read_scalar(handle)?.to_machine_isize(this)?;
+                let handle_scalar = this.read_scalar(handle)?;
+                let handle = handle_scalar.to_machine_isize(this)?;
                 let buf = this.read_pointer(buf)?;
                 let n = this.read_scalar(n)?.to_u32()?;
                 let byte_offset = this.read_scalar(byte_offset)?.to_machine_usize(this)?; // is actually a pointer
@@ -89,9 +151,11 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                     };
                     // We write at most `n` bytes, which is a `u32`, so we cannot have written more than that.
                     res.ok().map(|n| u32::try_from(n).unwrap())
+                } else if let Some(fd) = this.handle_to_fd(handle_scalar)? {
+                    this.write_to_fd(fd, buf, n, "NtWriteFile")?.ok()
                 } else {
                     throw_unsup_format!(
-                        "on Windows, writing to anything except stdout/stderr is not supported"
+                        "on Windows, writing to anything except stdout/stderr and files is not supported"
                     )
                 };
                 // We have to put the result into io_status_block.
@@ -127,6 +191,21 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
                 this.write_null(dest)?;
             }
+            Dlsym::WaitOnAddress => {
+                let [ptr, compare, size, timeout] = check_arg_count(args)?;
+
+                this.WaitOnAddress(ptr, compare, size, timeout, dest)?;
+            }
+            Dlsym::WakeByAddressSingle => {
+                let [ptr] = check_arg_count(args)?;
+
+                this.WakeByAddressSingle(ptr)?;
+            }
+            Dlsym::WakeByAddressAll => {
+                let [ptr] = check_arg_count(args)?;
+
+                this.WakeByAddressAll(ptr)?;
+            }
         }
 
         trace!("{:?}", this.dump_place(**dest));
@@ -6,6 +6,7 @@ use rustc_target::spec::abi::Abi;
 
 use crate::*;
 use shims::foreign_items::EmulateByNameResult;
+use shims::windows::fs::EvalContextExt as _;
 use shims::windows::handle::{EvalContextExt as _, Handle, PseudoHandle};
 use shims::windows::sync::EvalContextExt as _;
 use shims::windows::thread::EvalContextExt as _;
@@ -69,6 +70,75 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 this.write_scalar(Scalar::from_i32(result), dest)?;
             }
 
+            // File related shims
+            "CreateFileW" => {
+                let [
+                    file_name,
+                    desired_access,
+                    share_mode,
+                    security_attributes,
+                    creation_disposition,
+                    flags_and_attributes,
+                    template_file,
+                ] = this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let result = this.CreateFileW(
+                    file_name,
+                    desired_access,
+                    share_mode,
+                    security_attributes,
+                    creation_disposition,
+                    flags_and_attributes,
+                    template_file,
+                )?;
+                this.write_scalar(result, dest)?;
+            }
+            "ReadFile" => {
+                let [handle, buffer, number_of_bytes_to_read, number_of_bytes_read, overlapped] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let result = this.ReadFile(
+                    handle,
+                    buffer,
+                    number_of_bytes_to_read,
+                    number_of_bytes_read,
+                    overlapped,
+                )?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "WriteFile" => {
+                let [
+                    handle,
+                    buffer,
+                    number_of_bytes_to_write,
+                    number_of_bytes_written,
+                    overlapped,
+                ] = this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let result = this.WriteFile(
+                    handle,
+                    buffer,
+                    number_of_bytes_to_write,
+                    number_of_bytes_written,
+                    overlapped,
+                )?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "GetFileInformationByHandle" => {
+                let [handle, file_information] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let result = this.GetFileInformationByHandle(handle, file_information)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "GetFileInformationByHandleEx" => {
+                let [handle, file_information_class, file_information, buffer_size] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let result = this.GetFileInformationByHandleEx(
+                    handle,
+                    file_information_class,
+                    file_information,
+                    buffer_size,
+                )?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+
             // Allocation
             "HeapAlloc" => {
                 let [handle, flags, size] =
@@ -161,9 +231,15 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                     Scalar::from_int(PAGE_SIZE, dword_layout.size),
                     &page_size.into(),
                 )?;
+                // Set the mask of active processors, which has a bit for each processor that fits.
+                let num_cpus = this.machine.num_cpus;
+                let bits = num_cpus.min(u32::try_from(usize_layout.size.bits()).unwrap());
+                let mask = 1u64.checked_shl(bits).map_or(u64::MAX, |bit| bit.wrapping_sub(1));
+                let active_mask = system_info.offset(field_offsets[5], usize_layout, &this.tcx)?;
+                this.write_scalar(Scalar::from_uint(mask, usize_layout.size), &active_mask.into())?;
                 // Set number of processors.
-                let num_cpus = system_info.offset(field_offsets[6], dword_layout, &this.tcx)?;
-                this.write_scalar(Scalar::from_int(NUM_CPUS, dword_layout.size), &num_cpus.into())?;
+                let num_cpus_place = system_info.offset(field_offsets[6], dword_layout, &this.tcx)?;
+                this.write_scalar(Scalar::from_u32(num_cpus), &num_cpus_place.into())?;
             }
 
             // Thread-local storage
@@ -230,6 +306,13 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
                 this.Sleep(timeout)?;
             }
+            "SleepEx" => {
+                let [timeout, alertable] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+
+                let result = this.SleepEx(timeout, alertable)?;
+                this.write_scalar(Scalar::from_u32(result), dest)?;
+            }
 
             // Synchronization primitives
             "AcquireSRWLockExclusive" => {
@@ -258,6 +341,64 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let ret = this.TryAcquireSRWLockShared(ptr)?;
                 this.write_scalar(Scalar::from_u8(ret), dest)?;
             }
+            "SleepConditionVariableSRW" => {
+                let [condvar, lock, timeout, flags] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                this.SleepConditionVariableSRW(condvar, lock, timeout, flags, dest)?;
+            }
+            "WakeConditionVariable" => {
+                let [condvar] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                this.WakeConditionVariable(condvar)?;
+            }
+            "WakeAllConditionVariable" => {
+                let [condvar] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                this.WakeAllConditionVariable(condvar)?;
+            }
+            "WaitOnAddress" => {
+                let [ptr, compare, size, timeout] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                this.WaitOnAddress(ptr, compare, size, timeout, dest)?;
+            }
+            "WakeByAddressSingle" => {
+                let [ptr] = this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                this.WakeByAddressSingle(ptr)?;
+            }
+            "WakeByAddressAll" => {
+                let [ptr] = this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                this.WakeByAddressAll(ptr)?;
+            }
+            "CreateEventW" => {
+                let [security, manual_reset, initial_state, name] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let event = this.CreateEventW(security, manual_reset, initial_state, name)?;
+                this.write_scalar(Handle::Event(event).to_scalar(this), dest)?;
+            }
+            "SetEvent" => {
+                let [handle] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let result = this.SetEvent(handle)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "ResetEvent" => {
+                let [handle] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let result = this.ResetEvent(handle)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
+            "CreateMutexW" => {
+                let [security, initial_owner, name] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let mutex = this.CreateMutexW(security, initial_owner, name)?;
+                this.write_scalar(Handle::Mutex(mutex).to_scalar(this), dest)?;
+            }
+            "ReleaseMutex" => {
+                let [handle] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let result = this.ReleaseMutex(handle)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
+            }
 
             // Dynamic symbol loading
             "GetProcAddress" => {
@@ -352,9 +493,9 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let [handle] =
                     this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
 
-                this.CloseHandle(handle)?;
+                let result = this.CloseHandle(handle)?;
 
-                this.write_scalar(Scalar::from_u32(1), dest)?;
+                this.write_scalar(Scalar::from_i32(result), dest)?;
             }
 
             // Threading
@@ -371,7 +512,17 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 let [handle, timeout] =
                     this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
 
-                let ret = this.WaitForSingleObject(handle, timeout)?;
+                this.WaitForSingleObject(handle, timeout, dest)?;
+            }
+            "WaitForMultipleObjects" => {
+                let [count, handles, wait_all, timeout] =
+                    this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+
+                this.WaitForMultipleObjects(count, handles, wait_all, timeout, dest)?;
+            }
+            "GetCurrentThreadId" => {
+                let [] = this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
+                let ret = this.GetCurrentThreadId()?;
                 this.write_scalar(Scalar::from_u32(ret), dest)?;
             }
             "GetCurrentThread" => {
@@ -419,7 +570,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 // Any non zero value works for the stdlib. This is just used for stack overflows anyway.
                 this.write_scalar(Scalar::from_u32(1), dest)?;
             }
-            "GetCurrentProcessId" if this.frame_in_std() => {
+            "GetCurrentProcessId" => {
                 let [] = this.check_shim(abi, Abi::System { unwind: false }, link_name, args)?;
                 let result = this.GetCurrentProcessId()?;
                 this.write_scalar(Scalar::from_u32(result), dest)?;
@@ -0,0 +1,576 @@
+//! Emulation of the Win32 file APIs. A file `HANDLE` refers to a file descriptor in the file table
+//! of the Unix shims, so the files are read and written just like the ones opened with `open`.
+
+use std::fs::{Metadata, OpenOptions};
+use std::io::{self, ErrorKind};
+use std::time::SystemTime;
+
+use rustc_middle::ty::layout::TyAndLayout;
+use rustc_target::abi::{Align, Size};
+
+use crate::*;
+use shims::windows::handle::{EvalContextExt as _, Handle};
+
+/// The access rights to the data of a file, which decide what the file is opened for. The other
+/// specific access rights, like the ones to the attributes, are not looked at.
+const FILE_READ_DATA: u32 = 0x1;
+const FILE_WRITE_DATA: u32 = 0x2;
+const FILE_APPEND_DATA: u32 = 0x4;
+const GENERIC_ALL: u32 = 0x10000000;
+
+/// The attributes of files that Miri reports. A file without other attributes is "normal".
+const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+/// The flags for the quality of service of named pipes, which do not matter for files.
+const SECURITY_SQOS_FLAGS: u32 = 0x001F0000;
+
+/// The values of `FILE_INFO_BY_HANDLE_CLASS` that `GetFileInformationByHandleEx` supports.
+const FILE_BASIC_INFO: u32 = 0;
+const FILE_STANDARD_INFO: u32 = 1;
+const FILE_ATTRIBUTE_TAG_INFO: u32 = 9;
+
+#[cfg(windows)]
+fn file_attributes(metadata: &Metadata) -> u32 {
+    use std::os::windows::fs::MetadataExt;
+    metadata.file_attributes()
+}
+
+#[cfg(not(windows))]
+fn file_attributes(metadata: &Metadata) -> u32 {
+    let mut attributes = 0;
+    if metadata.is_dir() {
+        attributes |= FILE_ATTRIBUTE_DIRECTORY;
+    }
+    if metadata.permissions().readonly() {
+        attributes |= FILE_ATTRIBUTE_READONLY;
+    }
+    if attributes == 0 { FILE_ATTRIBUTE_NORMAL } else { attributes }
+}
+
+/// The volume serial number, the file index and the number of links of a file. Unix hosts have
+/// the device and inode numbers instead of the first two; other hosts do not tell.
+#[cfg(unix)]
+fn file_ids(metadata: &Metadata) -> (u32, u64, u32) {
+    use std::os::unix::fs::MetadataExt;
+    // The serial number is only used to compare files, so dropping the upper bits is fine.
+    let serial = u32::try_from(metadata.dev() & 0xFFFF_FFFF).unwrap();
+    (serial, metadata.ino(), u32::try_from(metadata.nlink()).unwrap_or(u32::MAX))
+}
+
+#[cfg(not(unix))]
+fn file_ids(_metadata: &Metadata) -> (u32, u64, u32) {
+    (0, 0, 1)
+}
+
+/// The bytes that a file takes up on disk, which only Unix hosts tell.
+#[cfg(unix)]
+fn allocation_size(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // `st_blocks` counts units of 512 bytes.
+    metadata.blocks().saturating_mul(512)
+}
+
+#[cfg(not(unix))]
+fn allocation_size(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Splits a 64-bit value into the upper and the lower 32 bits, like Windows stores it in two
+/// `DWORD`s.
+fn split_u64(value: u64) -> (u32, u32) {
+    let high = u32::try_from(value.checked_shr(32).unwrap()).unwrap();
+    let low = u32::try_from(value & 0xFFFF_FFFF).unwrap();
+    (high, low)
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExtPrivate<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+trait EvalContextExtPrivate<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// `INVALID_HANDLE_VALUE`, which `CreateFileW` returns on failure.
+    fn invalid_handle_value(&self) -> Scalar<Provenance> {
+        let this = self.eval_context_ref();
+        Scalar::from_machine_isize(-1, this)
+    }
+
+    /// Writes the number of bytes that `ReadFile` or `WriteFile` transferred, if the pointer to it
+    /// is not null, and returns the `BOOL` that tells whether they succeeded.
+    fn finish_file_io(
+        &mut self,
+        result: io::Result<u32>,
+        transferred_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let (transferred, success) = match result {
+            Ok(transferred) => (transferred, 1),
+            Err(e) => {
+                this.set_last_error_from_io_error(e.kind())?;
+                (0, 0)
+            }
+        };
+        if !this.ptr_is_null(this.read_pointer(transferred_op)?)? {
+            let transferred_place = this.deref_operand(transferred_op)?;
+            this.write_scalar(Scalar::from_u32(transferred), &transferred_place.into())?;
+        }
+        Ok(success)
+    }
+
+    /// The host metadata of the file behind `handle`. Sets the last error and returns `None` if
+    /// it cannot be read.
+    fn handle_metadata(
+        &mut self,
+        handle: Scalar<Provenance>,
+        function_name: &str,
+    ) -> InterpResult<'tcx, Option<Metadata>> {
+        let this = self.eval_context_mut();
+
+        let Some(fd) = this.handle_to_fd(handle)? else {
+            this.invalid_handle(function_name)?
+        };
+        let Some(metadata) = this.machine.file_handler.host_metadata(fd)? else {
+            this.invalid_handle(function_name)?
+        };
+        match metadata {
+            Ok(metadata) => Ok(Some(metadata)),
+            Err(e) => {
+                this.set_last_error_from_io_error(e.kind())?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// The time as a `FILETIME`, which counts intervals of 100ns since 1601. Times that the host
+    /// does not know, or that do not fit, are 0.
+    #[allow(clippy::integer_arithmetic)] // the divisors are not zero
+    fn filetime(&self, time: io::Result<SystemTime>) -> InterpResult<'tcx, u64> {
+        let this = self.eval_context_ref();
+
+        let nanos_per_interval = u128::from(
+            this.eval_windows_u64("time", "NANOS_PER_SEC")?
+                / this.eval_windows_u64("time", "INTERVALS_PER_SEC")?,
+        );
+        let intervals_to_unix_epoch = this.eval_windows_u64("time", "INTERVALS_TO_UNIX_EPOCH")?;
+
+        let Ok(time) = time else {
+            return Ok(0);
+        };
+        let intervals = match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(after) =>
+                u64::try_from(after.as_nanos() / nanos_per_interval)
+                    .ok()
+                    .and_then(|intervals| intervals_to_unix_epoch.checked_add(intervals)),
+            Err(before) =>
+                u64::try_from(before.duration().as_nanos() / nanos_per_interval)
+                    .ok()
+                    .and_then(|intervals| intervals_to_unix_epoch.checked_sub(intervals)),
+        };
+        Ok(intervals.unwrap_or(0))
+    }
+
+    /// Writes the integers one after the other to `ptr`, each with the size of its layout. The
+    /// layouts have to be in an order that needs no padding.
+    fn write_struct_at(
+        &mut self,
+        ptr: Pointer<Option<Provenance>>,
+        align: Align,
+        fields: &[(TyAndLayout<'tcx>, i128)],
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let size = fields.iter().fold(Size::ZERO, |size, (layout, _)| size + layout.size);
+        this.check_ptr_access_align(ptr, size, align, CheckInAllocMsg::MemoryAccessTest)?;
+        let mut offset = Size::ZERO;
+        for &(layout, value) in fields {
+            let field = MPlaceTy::from_aligned_ptr(ptr.offset(offset, this)?, layout);
+            this.write_int(value, &field.into())?;
+            offset += layout.size;
+        }
+        Ok(())
+    }
+}
+
+impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
+#[allow(non_snake_case)]
+pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// The file descriptor that a `HANDLE` refers to, or `None` if it is not a file. The values
+    /// that `GetStdHandle` returns stand for the standard streams.
+    fn handle_to_fd(&self, handle: Scalar<Provenance>) -> InterpResult<'tcx, Option<i32>> {
+        let this = self.eval_context_ref();
+        // `STD_INPUT_HANDLE`, `STD_OUTPUT_HANDLE` and `STD_ERROR_HANDLE`.
+        match handle.to_machine_isize(this)? {
+            -10 => return Ok(Some(0)),
+            -11 => return Ok(Some(1)),
+            -12 => return Ok(Some(2)),
+            _ => {}
+        }
+        Ok(match Handle::from_scalar(handle, this)? {
+            Some(Handle::File(fd)) => Some(fd),
+            _ => None,
+        })
+    }
+
+    /// Reads up to `len` bytes from `fd` into `buf`, and returns how many were read.
+    fn read_from_fd(
+        &mut self,
+        fd: i32,
+        buf: Pointer<Option<Provenance>>,
+        len: u32,
+        function_name: &str,
+    ) -> InterpResult<'tcx, io::Result<u32>> {
+        let this = self.eval_context_mut();
+
+        // Check that the *entire* buffer is actually valid memory.
+        this.check_ptr_access_align(
+            buf,
+            Size::from_bytes(len),
+            Align::ONE,
+            CheckInAllocMsg::MemoryAccessTest,
+        )?;
+
+        let communicate = this.machine.communicate();
+        let mut bytes = vec![0; usize::try_from(len).unwrap()];
+        let Some(result) = this.machine.file_handler.read_fd(fd, communicate, &mut bytes)? else {
+            this.invalid_handle(function_name)?
+        };
+        let read = match result {
+            Ok(read) => read,
+            Err(e) => return Ok(Err(e)),
+        };
+        bytes.truncate(read);
+        this.write_bytes_ptr(buf, bytes)?;
+        // We read at most `len` bytes, which is a `u32`.
+        Ok(Ok(u32::try_from(read).unwrap()))
+    }
+
+    /// Writes the `len` bytes at `buf` to `fd`, and returns how many were written.
+    fn write_to_fd(
+        &mut self,
+        fd: i32,
+        buf: Pointer<Option<Provenance>>,
+        len: u32,
+        function_name: &str,
+    ) -> InterpResult<'tcx, io::Result<u32>> {
+        let this = self.eval_context_mut();
+
+        let communicate = this.machine.communicate();
+        let bytes = this.read_bytes_ptr_strip_provenance(buf, Size::from_bytes(len))?;
+        let Some(result) = this.machine.file_handler.write_fd(fd, communicate, bytes)? else {
+            this.invalid_handle(function_name)?
+        };
+        // We write at most `len` bytes, which is a `u32`.
+        Ok(result.map(|written| u32::try_from(written).unwrap()))
+    }
+
+    /// Closes a file for `CloseHandle`, and returns the `BOOL` that tells whether that worked.
+    fn close_file(&mut self, fd: i32) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let communicate = this.machine.communicate();
+        let Some(result) = this.machine.file_handler.close_fd(fd, communicate)? else {
+            this.invalid_handle("CloseHandle")?
+        };
+        match result {
+            Ok(_) => Ok(1),
+            Err(e) => {
+                this.set_last_error_from_io_error(e.kind())?;
+                Ok(0)
+            }
+        }
+    }
+
+    /// Opens a host file. The share mode is only passed on to Windows hosts: other hosts cannot
+    /// keep other processes from opening the file, and the program sees no difference between
+    /// handles that it opened itself.
+    fn CreateFileW(
+        &mut self,
+        file_name_op: &OpTy<'tcx, Provenance>,
+        desired_access_op: &OpTy<'tcx, Provenance>,
+        share_mode_op: &OpTy<'tcx, Provenance>,
+        security_attributes_op: &OpTy<'tcx, Provenance>,
+        creation_disposition_op: &OpTy<'tcx, Provenance>,
+        flags_and_attributes_op: &OpTy<'tcx, Provenance>,
+        template_file_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, Scalar<Provenance>> {
+        let this = self.eval_context_mut();
+
+        let file_name = this.read_pointer(file_name_op)?;
+        let desired_access = this.read_scalar(desired_access_op)?.to_u32()?;
+        let share_mode = this.read_scalar(share_mode_op)?.to_u32()?;
+        let security_attributes = this.read_pointer(security_attributes_op)?;
+        let creation_disposition = this.read_scalar(creation_disposition_op)?.to_u32()?;
+        let flags_and_attributes = this.read_scalar(flags_and_attributes_op)?.to_u32()?;
+        let template_file = this.read_scalar(template_file_op)?.to_machine_isize(this)?;
+
+        if !this.ptr_is_null(security_attributes)? {
+            throw_unsup_format!("`CreateFileW` with security attributes is not supported");
+        }
+        if template_file != 0 {
+            throw_unsup_format!("`CreateFileW` with a template file is not supported");
+        }
+
+        let mut options = OpenOptions::new();
+
+        let generic_read = this.eval_windows("c", "GENERIC_READ")?.to_u32()?;
+        let generic_write = this.eval_windows("c", "GENERIC_WRITE")?.to_u32()?;
+        let readable = desired_access & (generic_read | GENERIC_ALL | FILE_READ_DATA) != 0;
+        let writes_data = desired_access & (generic_write | GENERIC_ALL | FILE_WRITE_DATA) != 0;
+        // The right to append without the right to write is what makes all writes go to the end
+        // of the file, which is how `std` opens files for appending.
+        let is_append = !writes_data && desired_access & FILE_APPEND_DATA != 0;
+        let writable = writes_data || is_append;
+        // The host needs an access mode, even if the program only wants to look at the file.
+        options.read(readable || !writable).write(writable);
+
+        let file_share_read = this.eval_windows("c", "FILE_SHARE_READ")?.to_u32()?;
+        let file_share_write = this.eval_windows("c", "FILE_SHARE_WRITE")?.to_u32()?;
+        let file_share_delete = this.eval_windows("c", "FILE_SHARE_DELETE")?.to_u32()?;
+        let share_flags = file_share_read | file_share_write | file_share_delete;
+        if share_mode & !share_flags != 0 {
+            throw_unsup_format!("unsupported share mode {:#x}", share_mode & !share_flags);
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::fs::OpenOptionsExt;
+            options.share_mode(share_mode);
+        }
+
+        // Whether an existing file sets the last error to `ERROR_ALREADY_EXISTS`, even though the
+        // call succeeds.
+        let mut reports_existing = false;
+        if creation_disposition == this.eval_windows("c", "CREATE_NEW")?.to_u32()? {
+            options.create_new(true);
+        } else if creation_disposition == this.eval_windows("c", "CREATE_ALWAYS")?.to_u32()? {
+            options.create(true).truncate(true);
+            reports_existing = true;
+        } else if creation_disposition == this.eval_windows("c", "OPEN_EXISTING")?.to_u32()? {
+            // Nothing to do, this is how `OpenOptions` opens files by default.
+        } else if creation_disposition == this.eval_windows("c", "OPEN_ALWAYS")?.to_u32()? {
+            options.create(true);
+            reports_existing = true;
+        } else if creation_disposition == this.eval_windows("c", "TRUNCATE_EXISTING")?.to_u32()? {
+            options.truncate(true);
+        } else {
+            let invalid_parameter = this.eval_windows("c", "ERROR_INVALID_PARAMETER")?;
+            this.set_last_error(invalid_parameter)?;
+            return Ok(this.invalid_handle_value());
+        }
+
+        let backup_semantics = this.eval_windows("c", "FILE_FLAG_BACKUP_SEMANTICS")?.to_u32()?;
+        // Miri always follows symbolic links, so opening a reparse point opens its target.
+        let open_reparse_point = this.eval_windows("c", "FILE_FLAG_OPEN_REPARSE_POINT")?.to_u32()?;
+        let supported_flags =
+            FILE_ATTRIBUTE_NORMAL | backup_semantics | open_reparse_point | SECURITY_SQOS_FLAGS;
+        if flags_and_attributes & !supported_flags != 0 {
+            throw_unsup_format!(
+                "unsupported flags and attributes {:#x}",
+                flags_and_attributes & !supported_flags
+            );
+        }
+        let is_backup = flags_and_attributes & backup_semantics != 0;
+        #[cfg(windows)]
+        if is_backup {
+            use std::os::windows::fs::OpenOptionsExt;
+            options.custom_flags(backup_semantics);
+        }
+
+        let path = this.read_path_from_wide_str(file_name)?;
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            this.reject_in_isolation("`CreateFileW`", reject_with)?;
+            this.set_last_error_from_io_error(ErrorKind::PermissionDenied)?;
+            return Ok(this.invalid_handle_value());
+        }
+
+        let existed = reports_existing && path.exists();
+        let file = options.open(&path).and_then(|file| {
+            // Only backup semantics allow opening a directory.
+            if !is_backup && file.metadata()?.is_dir() {
+                return Err(ErrorKind::PermissionDenied.into());
+            }
+            Ok(file)
+        });
+        match file {
+            Ok(file) => {
+                let fh = &mut this.machine.file_handler;
+                let fd = fh.insert_host_file(file, readable, writable, is_append);
+                if existed {
+                    let already_exists = this.eval_windows("c", "ERROR_ALREADY_EXISTS")?;
+                    this.set_last_error(already_exists)?;
+                } else if reports_existing {
+                    this.set_last_error(Scalar::from_u32(0))?;
+                }
+                Ok(Handle::File(fd).to_scalar(this))
+            }
+            Err(e) => {
+                this.set_last_error_from_io_error(e.kind())?;
+                Ok(this.invalid_handle_value())
+            }
+        }
+    }
+
+    /// Fills in a `BY_HANDLE_FILE_INFORMATION`.
+    fn GetFileInformationByHandle(
+        &mut self,
+        handle_op: &OpTy<'tcx, Provenance>,
+        file_information_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let handle = this.read_scalar(handle_op)?;
+        let file_information = this.read_pointer(file_information_op)?;
+
+        let Some(metadata) = this.handle_metadata(handle, "GetFileInformationByHandle")? else {
+            return Ok(0);
+        };
+        let (created_high, created_low) = split_u64(this.filetime(metadata.created())?);
+        let (accessed_high, accessed_low) = split_u64(this.filetime(metadata.accessed())?);
+        let (modified_high, modified_low) = split_u64(this.filetime(metadata.modified())?);
+        let (size_high, size_low) = split_u64(metadata.len());
+        let (serial, index, links) = file_ids(&metadata);
+        let (index_high, index_low) = split_u64(index);
+
+        // All fields are `DWORD`s, and a `FILETIME` is two of them, the lower one first.
+        let dword = this.machine.layouts.u32;
+        this.write_struct_at(
+            file_information,
+            dword.align.abi,
+            &[
+                (dword, file_attributes(&metadata).into()),
+                (dword, created_low.into()),
+                (dword, created_high.into()),
+                (dword, accessed_low.into()),
+                (dword, accessed_high.into()),
+                (dword, modified_low.into()),
+                (dword, modified_high.into()),
+                (dword, serial.into()),
+                (dword, size_high.into()),
+                (dword, size_low.into()),
+                (dword, links.into()),
+                (dword, index_high.into()),
+                (dword, index_low.into()),
+            ],
+        )?;
+        Ok(1)
+    }
+
+    /// Supports `FileBasicInfo`, `FileStandardInfo` and `FileAttributeTagInfo`. There are no
+    /// reparse points, since Miri follows symbolic links, and no files that are about to be
+    /// deleted, since Miri does not delete files on close. The change time is the time of the
+    /// last write.
+    fn GetFileInformationByHandleEx(
+        &mut self,
+        handle_op: &OpTy<'tcx, Provenance>,
+        file_information_class_op: &OpTy<'tcx, Provenance>,
+        file_information_op: &OpTy<'tcx, Provenance>,
+        buffer_size_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let handle = this.read_scalar(handle_op)?;
+        let file_information_class = this.read_scalar(file_information_class_op)?.to_u32()?;
+        let file_information = this.read_pointer(file_information_op)?;
+        let buffer_size = this.read_scalar(buffer_size_op)?.to_u32()?;
+
+        let dword = this.machine.layouts.u32;
+        let large_integer = this.machine.layouts.u64;
+        let boolean = this.machine.layouts.u8;
+        let (required_size, align) = match file_information_class {
+            FILE_BASIC_INFO => (40, large_integer.align.abi),
+            FILE_STANDARD_INFO => (24, large_integer.align.abi),
+            FILE_ATTRIBUTE_TAG_INFO => (8, dword.align.abi),
+            _ =>
+                throw_unsup_format!(
+                    "`GetFileInformationByHandleEx` with information class {} is not supported",
+                    file_information_class
+                ),
+        };
+        if buffer_size < required_size {
+            let insufficient_buffer = this.eval_windows("c", "ERROR_INSUFFICIENT_BUFFER")?;
+            this.set_last_error(insufficient_buffer)?;
+            return Ok(0);
+        }
+
+        let Some(metadata) = this.handle_metadata(handle, "GetFileInformationByHandleEx")? else {
+            return Ok(0);
+        };
+        let attributes = file_attributes(&metadata);
+        let fields: Vec<(TyAndLayout<'tcx>, i128)> = match file_information_class {
+            FILE_BASIC_INFO => {
+                let modified = this.filetime(metadata.modified())?;
+                vec![
+                    (large_integer, this.filetime(metadata.created())?.into()),
+                    (large_integer, this.filetime(metadata.accessed())?.into()),
+                    (large_integer, modified.into()),
+                    (large_integer, modified.into()),
+                    (dword, attributes.into()),
+                ]
+            }
+            FILE_STANDARD_INFO => {
+                let (_, _, links) = file_ids(&metadata);
+                vec![
+                    (large_integer, allocation_size(&metadata).into()),
+                    (large_integer, metadata.len().into()),
+                    (dword, links.into()),
+                    (boolean, 0),
+                    (boolean, i128::from(metadata.is_dir())),
+                ]
+            }
+            FILE_ATTRIBUTE_TAG_INFO => vec![(dword, attributes.into()), (dword, 0)],
+            _ => unreachable!(),
+        };
+        this.write_struct_at(file_information, align, &fields)?;
+        Ok(1)
+    }
+
+    /// Reading at the end of a file succeeds with 0 bytes.
+    fn ReadFile(
+        &mut self,
+        handle_op: &OpTy<'tcx, Provenance>,
+        buffer_op: &OpTy<'tcx, Provenance>,
+        number_of_bytes_to_read_op: &OpTy<'tcx, Provenance>,
+        number_of_bytes_read_op: &OpTy<'tcx, Provenance>,
+        overlapped_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let handle = this.read_scalar(handle_op)?;
+        let buffer = this.read_pointer(buffer_op)?;
+        let number_of_bytes_to_read = this.read_scalar(number_of_bytes_to_read_op)?.to_u32()?;
+        let overlapped = this.read_pointer(overlapped_op)?;
+
+        if !this.ptr_is_null(overlapped)? {
+            throw_unsup_format!("`ReadFile` with overlapped I/O is not supported");
+        }
+        let Some(fd) = this.handle_to_fd(handle)? else {
+            this.invalid_handle("ReadFile")?
+        };
+        let result = this.read_from_fd(fd, buffer, number_of_bytes_to_read, "ReadFile")?;
+        this.finish_file_io(result, number_of_bytes_read_op)
+    }
+
+    fn WriteFile(
+        &mut self,
+        handle_op: &OpTy<'tcx, Provenance>,
+        buffer_op: &OpTy<'tcx, Provenance>,
+        number_of_bytes_to_write_op: &OpTy<'tcx, Provenance>,
+        number_of_bytes_written_op: &OpTy<'tcx, Provenance>,
+        overlapped_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let handle = this.read_scalar(handle_op)?;
+        let buffer = this.read_pointer(buffer_op)?;
+        let number_of_bytes_to_write = this.read_scalar(number_of_bytes_to_write_op)?.to_u32()?;
+        let overlapped = this.read_pointer(overlapped_op)?;
+
+        if !this.ptr_is_null(overlapped)? {
+            throw_unsup_format!("`WriteFile` with overlapped I/O is not supported");
+        }
+        let Some(fd) = this.handle_to_fd(handle)? else {
+            this.invalid_handle("WriteFile")?
+        };
+        let result = this.write_to_fd(fd, buffer, number_of_bytes_to_write, "WriteFile")?;
+        this.finish_file_io(result, number_of_bytes_written_op)
+    }
+}
@@ -2,6 +2,7 @@ use rustc_target::abi::HasDataLayout;
 use std::mem::variant_count;
 
 use crate::*;
+use shims::windows::fs::EvalContextExt as _;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum PseudoHandle {
@@ -14,6 +15,13 @@ pub enum Handle {
     Null,
     Pseudo(PseudoHandle),
     Thread(ThreadId),
+    /// A file opened with `CreateFileW`, which refers to a file descriptor of the file table that
+    /// is shared with the Unix shims.
+    File(i32),
+    /// An event created with `CreateEventW`.
+    Event(EventId),
+    /// A mutex created with `CreateMutexW`.
+    Mutex(MutexId),
 }
 
 impl PseudoHandle {
@@ -37,12 +45,18 @@ impl Handle {
     const NULL_DISCRIMINANT: u32 = 0;
     const PSEUDO_DISCRIMINANT: u32 = 1;
     const THREAD_DISCRIMINANT: u32 = 2;
+    const FILE_DISCRIMINANT: u32 = 3;
+    const EVENT_DISCRIMINANT: u32 = 4;
+    const MUTEX_DISCRIMINANT: u32 = 5;
 
     fn discriminant(self) -> u32 {
         match self {
             Self::Null => Self::NULL_DISCRIMINANT,
             Self::Pseudo(_) => Self::PSEUDO_DISCRIMINANT,
             Self::Thread(_) => Self::THREAD_DISCRIMINANT,
+            Self::File(_) => Self::FILE_DISCRIMINANT,
+            Self::Event(_) => Self::EVENT_DISCRIMINANT,
+            Self::Mutex(_) => Self::MUTEX_DISCRIMINANT,
         }
     }
 
@@ -51,6 +65,9 @@ impl Handle {
             Self::Null => 0,
             Self::Pseudo(pseudo_handle) => pseudo_handle.value(),
             Self::Thread(thread) => thread.to_u32(),
+            Self::File(fd) => u32::try_from(fd).unwrap(),
+            Self::Event(event) => event.to_u32(),
+            Self::Mutex(mutex) => mutex.to_u32(),
         }
     }
 
@@ -96,6 +113,10 @@ impl Handle {
             Self::NULL_DISCRIMINANT if data == 0 => Some(Self::Null),
             Self::PSEUDO_DISCRIMINANT => Some(Self::Pseudo(PseudoHandle::from_value(data)?)),
             Self::THREAD_DISCRIMINANT => Some(Self::Thread(data.into())),
+            Self::FILE_DISCRIMINANT => Some(Self::File(i32::try_from(data).ok()?)),
+            // 0 is not a valid id.
+            Self::EVENT_DISCRIMINANT if data != 0 => Some(Self::Event(EventId::from_u32(data))),
+            Self::MUTEX_DISCRIMINANT if data != 0 => Some(Self::Mutex(MutexId::from_u32(data))),
             _ => None,
         }
     }
@@ -155,7 +176,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         )))
     }
 
-    fn CloseHandle(&mut self, handle_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx> {
+    fn CloseHandle(&mut self, handle_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
         let this = self.eval_context_mut();
 
         let handle = this.read_scalar(handle_op)?;
@@ -163,9 +184,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         match Handle::from_scalar(handle, this)? {
             Some(Handle::Thread(thread)) =>
                 this.detach_thread(thread, /*allow_terminated_joined*/ true)?,
+            Some(Handle::File(fd)) => return this.close_file(fd),
+            // Like the other synchronization primitives, events and mutexes are never freed.
+            Some(Handle::Event(_) | Handle::Mutex(_)) => {}
             _ => this.invalid_handle("CloseHandle")?,
         }
 
-        Ok(())
+        Ok(1)
     }
 }
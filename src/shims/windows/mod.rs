@@ -1,6 +1,9 @@
 pub mod dlsym;
 pub mod foreign_items;
 
+mod fs;
 mod handle;
 mod sync;
 mod thread;
+
+pub use sync::{EvalContextExt as WindowsSyncEvalContextExt, ObjectWaiter};
@@ -1,4 +1,28 @@
+use std::time::Duration;
+
+use rustc_target::abi::Size;
+
+use crate::concurrency::thread::Time;
 use crate::*;
+use shims::windows::handle::{EvalContextExt as _, Handle, PseudoHandle};
+
+/// The most objects that `WaitForMultipleObjects` can wait for.
+const MAXIMUM_WAIT_OBJECTS: u32 = 64;
+/// The result of a wait that failed.
+const WAIT_FAILED: u32 = 0xFFFFFFFF;
+/// The error of `ReleaseMutex` when the calling thread does not own the mutex.
+const ERROR_NOT_OWNER: u32 = 288;
+
+/// A thread that is blocked in `WaitForSingleObject` or `WaitForMultipleObjects` until one or all
+/// of the objects are signaled.
+#[derive(Debug)]
+pub struct ObjectWaiter<'tcx> {
+    thread: ThreadId,
+    handles: Vec<Handle>,
+    wait_all: bool,
+    /// The return place of the wait call.
+    dest: PlaceTy<'tcx, Provenance>,
+}
 
 // Locks are pointer-sized pieces of data, initialized to 0.
 // We use the first 4 bytes to store the RwLockId.
@@ -30,6 +54,155 @@ fn srwlock_get_or_create_id<'mir, 'tcx: 'mir>(
     })
 }
 
+// Condition variables are pointer-sized pieces of data, initialized to 0.
+// We use the first 4 bytes to store the CondvarId.
+
+fn condvar_get_or_create_id<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    condvar_op: &OpTy<'tcx, Provenance>,
+) -> InterpResult<'tcx, CondvarId> {
+    let value_place = ecx.deref_operand_and_offset(condvar_op, 0, ecx.machine.layouts.u32)?;
+
+    ecx.condvar_get_or_create(|ecx, next_id| {
+        let (old, success) = ecx
+            .atomic_compare_exchange_scalar(
+                &value_place,
+                &ImmTy::from_uint(0u32, ecx.machine.layouts.u32),
+                next_id.to_u32_scalar(),
+                AtomicRwOrd::Relaxed,
+                AtomicReadOrd::Relaxed,
+                false,
+            )?
+            .to_scalar_pair();
+
+        Ok(if success.to_bool().expect("compare_exchange's second return value is a bool") {
+            // Caller of the closure needs to allocate next_id
+            None
+        } else {
+            Some(CondvarId::from_u32(old.to_u32().expect("layout is u32")))
+        })
+    })
+}
+
+/// Try to reacquire the SRWLock associated with the condition variable after we were signaled or
+/// timed out.
+fn reacquire_cond_srwlock<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    thread: ThreadId,
+    lock: RwLockId,
+    mode: RwLockMode,
+) {
+    ecx.unblock_thread(thread);
+    match mode {
+        RwLockMode::Read =>
+            if ecx.rwlock_is_write_locked(lock) {
+                ecx.rwlock_enqueue_and_block_reader(lock, thread);
+            } else {
+                ecx.rwlock_reader_lock(lock, thread);
+            },
+        RwLockMode::Write =>
+            if ecx.rwlock_is_locked(lock) {
+                ecx.rwlock_enqueue_and_block_writer(lock, thread);
+            } else {
+                ecx.rwlock_writer_lock(lock, thread);
+            },
+    }
+}
+
+/// After a thread waiting on a condition variable was signaled: reacquire the SRWLock and remove
+/// the timeout callback if any was registered.
+fn post_cond_signal<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    thread: ThreadId,
+    lock: CondvarLock,
+) {
+    let CondvarLock::RwLock { id, mode } = lock else {
+        panic!("mutexes should not exist on windows");
+    };
+    reacquire_cond_srwlock(ecx, thread, id, mode);
+    // Waiting for the lock is not included in the waiting time because we need to reacquire the
+    // lock always, even if we get a timeout.
+    ecx.unregister_timeout_callback_if_exists(thread);
+}
+
+/// Reads a handle that can be waited for. The pseudo handle of the current thread becomes the
+/// handle of the active thread.
+fn read_object_handle<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    handle: Scalar<Provenance>,
+    function_name: &str,
+) -> InterpResult<'tcx, Handle> {
+    Ok(match Handle::from_scalar(handle, ecx)? {
+        Some(handle @ (Handle::Thread(_) | Handle::Event(_) | Handle::Mutex(_))) => handle,
+        Some(Handle::Pseudo(PseudoHandle::CurrentThread)) =>
+            Handle::Thread(ecx.get_active_thread()),
+        _ => ecx.invalid_handle(function_name)?,
+    })
+}
+
+/// Whether the object is signaled for `thread`: a thread when it terminated, an event when it is
+/// set, and a mutex when no other thread owns it.
+fn object_is_signaled<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    handle: Handle,
+    thread: ThreadId,
+) -> bool {
+    match handle {
+        Handle::Thread(joined) => ecx.has_terminated(joined),
+        Handle::Event(id) => ecx.event_is_signaled(id),
+        Handle::Mutex(id) => !ecx.mutex_is_locked(id) || ecx.mutex_get_owner(id) == thread,
+        _ => bug!("waiting for {handle:?}"),
+    }
+}
+
+/// If the objects end the wait of `thread`, acquire them for it and return the index of the
+/// object that ended the wait. Acquiring resets auto-reset events and locks mutexes.
+fn objects_try_acquire<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    handles: &[Handle],
+    wait_all: bool,
+    thread: ThreadId,
+) -> Option<usize> {
+    let index = if wait_all {
+        if !handles.iter().all(|&handle| object_is_signaled(ecx, handle, thread)) {
+            return None;
+        }
+        0
+    } else {
+        handles.iter().position(|&handle| object_is_signaled(ecx, handle, thread))?
+    };
+    let acquired = if wait_all { handles } else { std::slice::from_ref(&handles[index]) };
+    for &handle in acquired {
+        match handle {
+            Handle::Event(id) => ecx.event_acquire(id, thread),
+            Handle::Mutex(id) => ecx.mutex_lock(id, thread),
+            _ => {}
+        }
+    }
+    Some(index)
+}
+
+/// Return from a wait that was ended by the object at `index`. This runs on the waiting thread,
+/// which joins the threads that it waited for, so that their termination happens-before the
+/// return.
+fn objects_finish_wait<'mir, 'tcx: 'mir>(
+    ecx: &mut MiriEvalContext<'mir, 'tcx>,
+    handles: &[Handle],
+    wait_all: bool,
+    index: usize,
+    dest: &PlaceTy<'tcx, Provenance>,
+) -> InterpResult<'tcx> {
+    let acquired = if wait_all { handles } else { std::slice::from_ref(&handles[index]) };
+    for &handle in acquired {
+        if let Handle::Thread(thread) = handle {
+            ecx.join_thread(thread)?;
+        }
+    }
+    let wait_object_0 = ecx.eval_windows("c", "WAIT_OBJECT_0")?.to_u32()?;
+    let result = wait_object_0.checked_add(u32::try_from(index).unwrap()).unwrap();
+    ecx.write_scalar(Scalar::from_u32(result), dest)
+}
+
 impl<'mir, 'tcx> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
 pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx> {
     #[allow(non_snake_case)]
@@ -135,4 +308,424 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
 
         Ok(())
     }
+
+    #[allow(non_snake_case)]
+    fn WaitOnAddress(
+        &mut self,
+        ptr_op: &OpTy<'tcx, Provenance>,
+        compare_op: &OpTy<'tcx, Provenance>,
+        size_op: &OpTy<'tcx, Provenance>,
+        timeout_op: &OpTy<'tcx, Provenance>,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let ptr = this.read_pointer(ptr_op)?;
+        let compare = this.read_pointer(compare_op)?;
+        let size = this.read_scalar(size_op)?.to_machine_usize(this)?;
+        let timeout_ms = this.read_scalar(timeout_op)?.to_u32()?;
+
+        let thread = this.get_active_thread();
+        let addr = ptr.addr().bytes();
+
+        let Some(layout) = this.machine.layouts.uint(Size::from_bytes(size)) else {
+            let invalid_param = this.eval_windows("c", "ERROR_INVALID_PARAMETER")?;
+            this.set_last_error(invalid_param)?;
+            this.write_scalar(Scalar::from_i32(0), dest)?;
+            return Ok(());
+        };
+
+        let timeout_time = if timeout_ms == this.eval_windows("c", "INFINITE")?.to_u32()? {
+            None
+        } else {
+            this.monotonic_deadline_after(Duration::from_millis(timeout_ms.into()))
+        };
+
+        // See the Linux futex implementation for why this fence exists: together with the fence
+        // in `WakeByAddress*`, it makes sure that we see the latest value at `ptr`.
+        this.atomic_fence(AtomicFenceOrd::SeqCst)?;
+
+        let futex_val = this
+            .read_scalar_atomic(&MPlaceTy::from_aligned_ptr(ptr, layout), AtomicReadOrd::Relaxed)?;
+        let compare_val = this.read_scalar(&MPlaceTy::from_aligned_ptr(compare, layout).into())?;
+
+        if futex_val == compare_val {
+            // The values are the same, so we have to block until we are woken up, or until the
+            // timeout. The timeout callback overrides the return value.
+            let timeout_dest = dest.clone();
+            this.block_thread_until(
+                thread,
+                timeout_time,
+                Box::new(move |this| {
+                    this.unblock_thread(thread);
+                    this.futex_remove_waiter(thread);
+                    let error_timeout = this.eval_windows("c", "ERROR_TIMEOUT")?;
+                    this.set_last_error(error_timeout)?;
+                    this.write_scalar(Scalar::from_i32(0), &timeout_dest)?;
+                    Ok(())
+                }),
+            );
+            this.futex_wait(addr, thread, u32::MAX);
+        }
+
+        // If the values differ, we return immediately. This also counts as success.
+        this.write_scalar(Scalar::from_i32(1), dest)?;
+
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    fn WakeByAddressSingle(&mut self, ptr_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let ptr = this.read_pointer(ptr_op)?;
+
+        // See the Linux futex implementation for why this fence exists.
+        this.atomic_fence(AtomicFenceOrd::SeqCst)?;
+
+        if let Some(thread) = this.futex_wake(ptr.addr().bytes(), u32::MAX) {
+            this.unblock_thread(thread);
+            this.unregister_timeout_callback_if_exists(thread);
+        }
+
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    fn WakeByAddressAll(&mut self, ptr_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let ptr = this.read_pointer(ptr_op)?;
+
+        // See the Linux futex implementation for why this fence exists.
+        this.atomic_fence(AtomicFenceOrd::SeqCst)?;
+
+        while let Some(thread) = this.futex_wake(ptr.addr().bytes(), u32::MAX) {
+            this.unblock_thread(thread);
+            this.unregister_timeout_callback_if_exists(thread);
+        }
+
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    fn SleepConditionVariableSRW(
+        &mut self,
+        condvar_op: &OpTy<'tcx, Provenance>,
+        lock_op: &OpTy<'tcx, Provenance>,
+        timeout_op: &OpTy<'tcx, Provenance>,
+        flags_op: &OpTy<'tcx, Provenance>,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let condvar_id = condvar_get_or_create_id(this, condvar_op)?;
+        let lock_id = srwlock_get_or_create_id(this, lock_op)?;
+        let timeout_ms = this.read_scalar(timeout_op)?.to_u32()?;
+        let flags = this.read_scalar(flags_op)?.to_u32()?;
+
+        let timeout_time = if timeout_ms == this.eval_windows("c", "INFINITE")?.to_u32()? {
+            None
+        } else {
+            this.monotonic_deadline_after(Duration::from_millis(timeout_ms.into()))
+        };
+
+        let shared_mode = 0x1; // CONDITION_VARIABLE_LOCKMODE_SHARED is not in std
+        let mode = if flags == 0 {
+            RwLockMode::Write
+        } else if flags == shared_mode {
+            RwLockMode::Read
+        } else {
+            throw_unsup_format!("unsupported `Flags` {flags} in `SleepConditionVariableSRW`");
+        };
+
+        let active_thread = this.get_active_thread();
+
+        let was_locked = match mode {
+            RwLockMode::Read => this.rwlock_reader_unlock(lock_id, active_thread),
+            RwLockMode::Write => this.rwlock_writer_unlock(lock_id, active_thread),
+        };
+        if !was_locked {
+            throw_ub_format!(
+                "calling SleepConditionVariableSRW with an SRWLock that is not locked by the current thread"
+            );
+        }
+
+        let timeout_dest = dest.clone();
+        this.block_thread_until(
+            active_thread,
+            timeout_time,
+            Box::new(move |this| {
+                // We are not waiting for the condition variable any more, wait for the lock
+                // instead.
+                reacquire_cond_srwlock(this, active_thread, lock_id, mode);
+                this.condvar_remove_waiter(condvar_id, active_thread);
+                let error_timeout = this.eval_windows("c", "ERROR_TIMEOUT")?;
+                this.set_last_error(error_timeout)?;
+                this.write_scalar(Scalar::from_i32(0), &timeout_dest)?;
+                Ok(())
+            }),
+        );
+        this.condvar_wait(condvar_id, active_thread, CondvarLock::RwLock { id: lock_id, mode });
+
+        // We return success for now and override it in the timeout callback.
+        this.write_scalar(Scalar::from_i32(1), dest)?;
+
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    fn WakeConditionVariable(&mut self, condvar_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let condvar_id = condvar_get_or_create_id(this, condvar_op)?;
+
+        if let Some((thread, lock)) = this.condvar_signal(condvar_id) {
+            post_cond_signal(this, thread, lock);
+        }
+
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    fn WakeAllConditionVariable(
+        &mut self,
+        condvar_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let condvar_id = condvar_get_or_create_id(this, condvar_op)?;
+
+        while let Some((thread, lock)) = this.condvar_signal(condvar_id) {
+            post_cond_signal(this, thread, lock);
+        }
+
+        Ok(())
+    }
+
+    /// Wait until one or, with `wait_all`, all of the objects are signaled, or until `timeout_ms`
+    /// milliseconds passed. The objects that end the wait are acquired, and `WAIT_OBJECT_0` plus
+    /// the index of the object that ended it is written to `dest`. On timeout, `WAIT_TIMEOUT` is
+    /// written instead.
+    fn wait_for_objects(
+        &mut self,
+        handles: Vec<Handle>,
+        wait_all: bool,
+        timeout_ms: u32,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let thread = this.get_active_thread();
+        if let Some(index) = objects_try_acquire(this, &handles, wait_all, thread) {
+            return objects_finish_wait(this, &handles, wait_all, index, dest);
+        }
+
+        let wait_timeout = this.eval_windows("c", "WAIT_TIMEOUT")?;
+        if timeout_ms == 0 {
+            this.write_scalar(wait_timeout, dest)?;
+            return Ok(());
+        }
+        let timeout_time = if timeout_ms == this.eval_windows("c", "INFINITE")?.to_u32()? {
+            None
+        } else {
+            this.monotonic_deadline_after(Duration::from_millis(timeout_ms.into()))
+        };
+
+        let timeout_dest = dest.clone();
+        this.block_thread_until(
+            thread,
+            timeout_time,
+            Box::new(move |this| {
+                this.unblock_thread(thread);
+                this.machine.object_waiters.retain(|waiter| waiter.thread != thread);
+                this.write_scalar(wait_timeout, &timeout_dest)?;
+                Ok(())
+            }),
+        );
+        this.machine.object_waiters.push(ObjectWaiter {
+            thread,
+            handles,
+            wait_all,
+            dest: dest.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Wake up the threads blocked in `WaitForSingleObject` or `WaitForMultipleObjects` whose
+    /// wait is over. This has to be called whenever an object might have become signaled.
+    fn wake_object_waiters(&mut self) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        for waiter in std::mem::take(&mut this.machine.object_waiters) {
+            let Some(index) =
+                objects_try_acquire(this, &waiter.handles, waiter.wait_all, waiter.thread)
+            else {
+                this.machine.object_waiters.push(waiter);
+                continue;
+            };
+            // The objects are acquired right away so that no other thread can take them, but the
+            // result is written by a callback that runs on the waiting thread as soon as the
+            // active thread yields, since the return place lives in that thread's stack.
+            let ObjectWaiter { thread, handles, wait_all, dest } = waiter;
+            this.unregister_timeout_callback_if_exists(thread);
+            this.register_timeout_callback(
+                thread,
+                Time::Monotonic(this.machine.clock.now()),
+                Box::new(move |this| {
+                    this.unblock_thread(thread);
+                    objects_finish_wait(this, &handles, wait_all, index, &dest)
+                }),
+            );
+        }
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    fn WaitForMultipleObjects(
+        &mut self,
+        count_op: &OpTy<'tcx, Provenance>,
+        handles_op: &OpTy<'tcx, Provenance>,
+        wait_all_op: &OpTy<'tcx, Provenance>,
+        timeout_op: &OpTy<'tcx, Provenance>,
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let count = this.read_scalar(count_op)?.to_u32()?;
+        let handles_ptr = this.read_pointer(handles_op)?;
+        let wait_all = this.read_scalar(wait_all_op)?.to_i32()? != 0;
+        let timeout_ms = this.read_scalar(timeout_op)?.to_u32()?;
+
+        if count == 0 || count > MAXIMUM_WAIT_OBJECTS {
+            let invalid_param = this.eval_windows("c", "ERROR_INVALID_PARAMETER")?;
+            this.set_last_error(invalid_param)?;
+            this.write_scalar(Scalar::from_u32(WAIT_FAILED), dest)?;
+            return Ok(());
+        }
+
+        let handle_layout = this.machine.layouts.isize;
+        let mut handles = Vec::new();
+        for idx in 0..u64::from(count) {
+            let offset = handle_layout.size.checked_mul(idx, this).unwrap();
+            let ptr = handles_ptr.offset(offset, this)?;
+            let handle = this.read_scalar(&MPlaceTy::from_aligned_ptr(ptr, handle_layout).into())?;
+            handles.push(read_object_handle(this, handle, "WaitForMultipleObjects")?);
+        }
+
+        // Like Windows, reject the same object twice when waiting for all of them.
+        let has_duplicates =
+            handles.iter().enumerate().any(|(idx, handle)| handles[..idx].contains(handle));
+        if wait_all && has_duplicates {
+            let invalid_param = this.eval_windows("c", "ERROR_INVALID_PARAMETER")?;
+            this.set_last_error(invalid_param)?;
+            this.write_scalar(Scalar::from_u32(WAIT_FAILED), dest)?;
+            return Ok(());
+        }
+
+        this.wait_for_objects(handles, wait_all, timeout_ms, dest)
+    }
+
+    /// Named events and security attributes are not supported.
+    #[allow(non_snake_case)]
+    fn CreateEventW(
+        &mut self,
+        security_op: &OpTy<'tcx, Provenance>,
+        manual_reset_op: &OpTy<'tcx, Provenance>,
+        initial_state_op: &OpTy<'tcx, Provenance>,
+        name_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, EventId> {
+        let this = self.eval_context_mut();
+
+        let security = this.read_pointer(security_op)?;
+        let manual_reset = this.read_scalar(manual_reset_op)?.to_i32()? != 0;
+        let initial_state = this.read_scalar(initial_state_op)?.to_i32()? != 0;
+        let name = this.read_pointer(name_op)?;
+
+        if !this.ptr_is_null(security)? {
+            throw_unsup_format!("non-null `lpEventAttributes` in `CreateEventW`");
+        }
+        if !this.ptr_is_null(name)? {
+            throw_unsup_format!("named events are not supported");
+        }
+
+        Ok(this.event_create(manual_reset, initial_state))
+    }
+
+    #[allow(non_snake_case)]
+    fn SetEvent(&mut self, handle_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let handle = this.read_scalar(handle_op)?;
+        let Some(Handle::Event(id)) = Handle::from_scalar(handle, this)? else {
+            this.invalid_handle("SetEvent")?
+        };
+
+        this.event_set(id);
+        this.wake_object_waiters()?;
+
+        Ok(1)
+    }
+
+    #[allow(non_snake_case)]
+    fn ResetEvent(&mut self, handle_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let handle = this.read_scalar(handle_op)?;
+        let Some(Handle::Event(id)) = Handle::from_scalar(handle, this)? else {
+            this.invalid_handle("ResetEvent")?
+        };
+
+        this.event_reset(id);
+
+        Ok(1)
+    }
+
+    /// Named mutexes and security attributes are not supported.
+    #[allow(non_snake_case)]
+    fn CreateMutexW(
+        &mut self,
+        security_op: &OpTy<'tcx, Provenance>,
+        initial_owner_op: &OpTy<'tcx, Provenance>,
+        name_op: &OpTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx, MutexId> {
+        let this = self.eval_context_mut();
+
+        let security = this.read_pointer(security_op)?;
+        let initial_owner = this.read_scalar(initial_owner_op)?.to_i32()? != 0;
+        let name = this.read_pointer(name_op)?;
+
+        if !this.ptr_is_null(security)? {
+            throw_unsup_format!("non-null `lpMutexAttributes` in `CreateMutexW`");
+        }
+        if !this.ptr_is_null(name)? {
+            throw_unsup_format!("named mutexes are not supported");
+        }
+
+        let id = this.mutex_create();
+        if initial_owner {
+            let active_thread = this.get_active_thread();
+            this.mutex_lock(id, active_thread);
+        }
+        Ok(id)
+    }
+
+    /// A mutex has to be released as often as it was acquired before another thread can have it.
+    #[allow(non_snake_case)]
+    fn ReleaseMutex(&mut self, handle_op: &OpTy<'tcx, Provenance>) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+
+        let handle = this.read_scalar(handle_op)?;
+        let Some(Handle::Mutex(id)) = Handle::from_scalar(handle, this)? else {
+            this.invalid_handle("ReleaseMutex")?
+        };
+
+        let active_thread = this.get_active_thread();
+        if this.mutex_unlock(id, active_thread).is_none() {
+            this.set_last_error(Scalar::from_u32(ERROR_NOT_OWNER))?;
+            return Ok(0);
+        }
+        this.wake_object_waiters()?;
+
+        Ok(1)
+    }
 }
@@ -1,8 +1,11 @@
+use std::time::Duration;
+
 use rustc_middle::ty::layout::LayoutOf;
 use rustc_target::spec::abi::Abi;
 
 use crate::*;
 use shims::windows::handle::{EvalContextExt as _, Handle, PseudoHandle};
+use shims::windows::sync::EvalContextExt as _;
 
 impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
 
@@ -59,7 +62,8 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         &mut self,
         handle_op: &OpTy<'tcx, Provenance>,
         timeout_op: &OpTy<'tcx, Provenance>,
-    ) -> InterpResult<'tcx, u32> {
+        dest: &PlaceTy<'tcx, Provenance>,
+    ) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
 
         let handle = this.read_scalar(handle_op)?;
@@ -70,15 +74,46 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
             // Unlike on posix, the outcome of joining the current thread is not documented.
             // On current Windows, it just deadlocks.
             Some(Handle::Pseudo(PseudoHandle::CurrentThread)) => this.get_active_thread(),
+            Some(handle @ (Handle::Event(_) | Handle::Mutex(_))) =>
+                return this.wait_for_objects(vec![handle], /*wait_all*/ false, timeout, dest),
             _ => this.invalid_handle("WaitForSingleObject")?,
         };
 
-        if timeout != this.eval_windows("c", "INFINITE")?.to_u32()? {
-            throw_unsup_format!("`WaitForSingleObject` with non-infinite timeout");
+        let timeout_time = if timeout == this.eval_windows("c", "INFINITE")?.to_u32()? {
+            None
+        } else {
+            this.monotonic_deadline_after(Duration::from_millis(timeout.into()))
+        };
+
+        let wait_object_0 = this.eval_windows("c", "WAIT_OBJECT_0")?;
+        let wait_timeout = this.eval_windows("c", "WAIT_TIMEOUT")?;
+
+        if let Some(timeout_time) = timeout_time {
+            if !this.has_terminated(thread) {
+                // If the thread does not terminate in time, we stop waiting and report the
+                // timeout. The callback is unregistered when the thread terminates first.
+                let active_thread = this.get_active_thread();
+                let dest = dest.clone();
+                this.register_timeout_callback(
+                    active_thread,
+                    timeout_time,
+                    Box::new(move |this| {
+                        this.cancel_join(active_thread);
+                        this.write_scalar(wait_timeout, &dest)?;
+                        Ok(())
+                    }),
+                );
+            }
         }
 
         this.join_thread(thread)?;
+        this.write_scalar(wait_object_0, dest)?;
+
+        Ok(())
+    }
 
-        Ok(0)
+    fn GetCurrentThreadId(&mut self) -> InterpResult<'tcx, u32> {
+        let this = self.eval_context_mut();
+        Ok(this.get_active_thread().to_u32())
     }
 }
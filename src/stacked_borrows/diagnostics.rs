@@ -1,11 +1,14 @@
+use serde_json::json;
 use smallvec::SmallVec;
 use std::fmt;
+use std::path::{Path, PathBuf};
 
+use rustc_data_structures::fx::FxHashMap;
 use rustc_middle::mir::interpret::{alloc_range, AllocId, AllocRange};
-use rustc_span::{Span, SpanData};
+use rustc_span::{source_map::SourceMap, Span, SpanData};
 use rustc_target::abi::Size;
 
-use crate::helpers::CurrentSpan;
+use crate::helpers::{escape_dot, CurrentSpan};
 use crate::stacked_borrows::{err_sb_ub, AccessKind, GlobalStateInner, Permission};
 use crate::*;
 
@@ -18,25 +21,52 @@ pub struct AllocHistory {
     creations: smallvec::SmallVec<[Creation; 1]>,
     invalidations: smallvec::SmallVec<[Invalidation; 1]>,
     protectors: smallvec::SmallVec<[Protection; 1]>,
+    /// The most recent exposure of each tag.
+    exposures: FxHashMap<SbTag, Exposure>,
+    /// The number of exposures so far, which orders them.
+    exposure_count: u64,
+    /// If `Some(n)`, only (at least) the `n` most recent events of each kind other than
+    /// exposures are kept.
+    limit: Option<usize>,
+    /// Whether any events were discarded because of `limit`.
+    truncated: bool,
 }
 
 #[derive(Clone, Debug)]
 struct Creation {
     retag: RetagOp,
     span: Span,
+    /// The range of the entire retag. This differs from `retag.range` if the retag grants
+    /// different permissions to different parts of the range, due to `UnsafeCell`.
+    full_range: AllocRange,
 }
 
 impl Creation {
     fn generate_diagnostic(&self) -> (String, SpanData) {
         let tag = self.retag.new_tag;
         if let Some(perm) = self.retag.permission {
-            (
-                format!(
-                    "{tag:?} was created by a {:?} retag at offsets {:?}",
-                    perm, self.retag.range,
-                ),
-                self.span.data(),
-            )
+            let range = self.retag.range;
+            let mut msg = format!("{tag:?} was created by a {perm:?} retag at offsets {range:?}");
+            if self.retag.freeze_sensitive {
+                let split = range.start != self.full_range.start
+                    || range.size != self.full_range.size;
+                // For shared references, the permission depends on whether the bytes are inside
+                // an `UnsafeCell`.
+                match perm {
+                    Permission::SharedReadWrite =>
+                        msg.push_str(", which are inside an `UnsafeCell`"),
+                    Permission::SharedReadOnly if split =>
+                        msg.push_str(", which are not inside an `UnsafeCell`"),
+                    _ => {}
+                }
+                if split {
+                    msg.push_str(&format!(
+                        " (this is part of a retag at offsets {:?})",
+                        self.full_range
+                    ));
+                }
+            }
+            (msg, self.span.data())
         } else {
             assert!(self.retag.range.size == Size::ZERO);
             (
@@ -94,6 +124,15 @@ impl fmt::Display for InvalidationCause {
 struct Protection {
     tag: SbTag,
     span: Span,
+    /// The function whose call installed the protector, and the span of that call.
+    call: Option<(String, Span)>,
+}
+
+#[derive(Clone, Debug)]
+struct Exposure {
+    span: Span,
+    /// The value of `AllocHistory::exposure_count` when the tag was exposed.
+    index: u64,
 }
 
 #[derive(Clone)]
@@ -101,6 +140,55 @@ pub struct TagHistory {
     pub created: (String, SpanData),
     pub invalidated: Option<(String, SpanData)>,
     pub protected: Option<(String, SpanData)>,
+    pub protector_call: Option<(String, SpanData)>,
+}
+
+/// What kind of operation a Stacked Borrows error occurred in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SbErrorKind {
+    Retag,
+    Access(AccessKind),
+    Dealloc,
+    /// An operation that would have removed a protected item.
+    Protector,
+}
+
+/// The location and borrow stack involved in a Stacked Borrows error, for machine-readable
+/// error output.
+#[derive(Clone, Debug)]
+pub struct SbErrorDetails {
+    pub kind: SbErrorKind,
+    pub alloc_id: AllocId,
+    /// The offset at which the error occurred.
+    pub offset: Size,
+    /// The range affected by the operation that failed, if it is a retag or an access.
+    pub range: Option<AllocRange>,
+    /// The tag used for the operation, if it is not a wildcard.
+    pub tag: Option<SbTag>,
+    /// The borrow stack at `offset`, if available.
+    pub stack: Option<StackSnapshot>,
+    /// Whether some of the history of this allocation was discarded, so the tag history in the
+    /// error might be incomplete.
+    pub history_truncated: bool,
+    /// For errors involving a wildcard pointer, the places where the tags that might have been
+    /// used were exposed, oldest first.
+    pub exposures: Vec<(String, SpanData)>,
+}
+
+/// A copy of a borrow stack, bottom item first.
+#[derive(Clone, Debug)]
+pub struct StackSnapshot {
+    pub items: Vec<Item>,
+    pub unknown_bottom: Option<SbTag>,
+}
+
+impl StackSnapshot {
+    fn new(stack: &Stack) -> Self {
+        StackSnapshot {
+            items: (0..stack.len()).map(|i| stack.get(i).unwrap()).collect(),
+            unknown_bottom: stack.unknown_bottom(),
+        }
+    }
 }
 
 pub struct DiagnosticCxBuilder<'span, 'ecx, 'mir, 'tcx> {
@@ -143,9 +231,16 @@ impl<'span, 'ecx, 'mir, 'tcx> DiagnosticCxBuilder<'span, 'ecx, 'mir, 'tcx> {
         new_tag: SbTag,
         orig_tag: ProvenanceExtra,
         range: AllocRange,
+        freeze_sensitive: bool,
     ) -> Self {
-        let operation =
-            Operation::Retag(RetagOp { cause, new_tag, orig_tag, range, permission: None });
+        let operation = Operation::Retag(RetagOp {
+            cause,
+            new_tag,
+            orig_tag,
+            range,
+            permission: None,
+            freeze_sensitive,
+        });
 
         DiagnosticCxBuilder { current_span, threads, operation }
     }
@@ -181,6 +276,11 @@ impl<'span, 'ecx, 'mir, 'tcx> DiagnosticCxBuilder<'span, 'ecx, 'mir, 'tcx> {
 }
 
 impl<'span, 'history, 'ecx, 'mir, 'tcx> DiagnosticCx<'span, 'history, 'ecx, 'mir, 'tcx> {
+    /// The span of the operation this context was built for.
+    pub fn current_span(&mut self) -> Span {
+        self.current_span.get()
+    }
+
     pub fn unbuild(self) -> DiagnosticCxBuilder<'span, 'ecx, 'mir, 'tcx> {
         DiagnosticCxBuilder {
             operation: self.operation,
@@ -204,9 +304,11 @@ struct RetagOp {
     orig_tag: ProvenanceExtra,
     range: AllocRange,
     permission: Option<Permission>,
+    /// Whether the permission depends on `UnsafeCell`, as for shared references.
+    freeze_sensitive: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RetagCause {
     Normal,
     FnReturn,
@@ -227,14 +329,47 @@ struct DeallocOp {
 }
 
 impl AllocHistory {
-    pub fn new(id: AllocId, item: Item, current_span: &mut CurrentSpan<'_, '_, '_>) -> Self {
+    pub fn new(
+        id: AllocId,
+        item: Item,
+        limit: Option<usize>,
+        current_span: &mut CurrentSpan<'_, '_, '_>,
+    ) -> Self {
         Self {
             id,
             base: (item, current_span.get()),
             creations: SmallVec::new(),
             invalidations: SmallVec::new(),
             protectors: SmallVec::new(),
+            exposures: FxHashMap::default(),
+            exposure_count: 0,
+            limit,
+            truncated: false,
+        }
+    }
+
+    /// Enforce `limit` on all event lists. To keep this cheap, we let each list grow to twice the
+    /// limit and then drop the oldest half at once.
+    fn enforce_limit(&mut self) {
+        fn truncate<A: smallvec::Array>(events: &mut SmallVec<A>, limit: usize) -> bool {
+            if events.len() < limit.saturating_mul(2) {
+                return false;
+            }
+            events.drain(..events.len() - limit);
+            true
         }
+        let Some(limit) = self.limit else { return };
+        let truncated = truncate(&mut self.creations, limit)
+            | truncate(&mut self.invalidations, limit)
+            | truncate(&mut self.protectors, limit);
+        self.truncated |= truncated;
+    }
+
+    /// Record that `tag` was exposed at `span`. Only the most recent exposure of each tag is
+    /// kept.
+    pub fn log_exposure(&mut self, tag: SbTag, span: Span) {
+        self.exposures.insert(tag, Exposure { span, index: self.exposure_count });
+        self.exposure_count += 1;
     }
 }
 
@@ -254,11 +389,14 @@ impl<'span, 'history, 'ecx, 'mir, 'tcx> DiagnosticCx<'span, 'history, 'ecx, 'mir
                 if previous != perm {
                     // 'Split up' the creation event.
                     let previous_range = last_creation.retag.range;
-                    last_creation.retag.range = alloc_range(previous_range.start, self.offset);
+                    last_creation.retag.range =
+                        alloc_range(previous_range.start, self.offset - previous_range.start);
                     let mut new_event = last_creation.clone();
-                    new_event.retag.range = alloc_range(self.offset, previous_range.end());
+                    new_event.retag.range =
+                        alloc_range(self.offset, previous_range.end() - self.offset);
                     new_event.retag.permission = Some(perm);
                     self.history.creations.push(new_event);
+                    self.history.enforce_limit();
                 },
         }
     }
@@ -267,10 +405,17 @@ impl<'span, 'history, 'ecx, 'mir, 'tcx> DiagnosticCx<'span, 'history, 'ecx, 'mir
         let Operation::Retag(op) = &self.operation else {
             unreachable!("log_creation must only be called during a retag")
         };
-        self.history.creations.push(Creation { retag: op.clone(), span: self.current_span.get() });
+        self.history.creations.push(Creation {
+            retag: op.clone(),
+            span: self.current_span.get(),
+            full_range: op.range,
+        });
+        self.history.enforce_limit();
     }
 
-    pub fn log_invalidation(&mut self, tag: SbTag) {
+    /// Record that `tag` was invalidated by the current operation, and return the span that is
+    /// blamed for it.
+    pub fn log_invalidation(&mut self, tag: SbTag) -> Span {
         let mut span = self.current_span.get();
         let (range, cause) = match &self.operation {
             Operation::Retag(RetagOp { cause, range, permission, .. }) => {
@@ -284,13 +429,27 @@ impl<'span, 'history, 'ecx, 'mir, 'tcx> DiagnosticCx<'span, 'history, 'ecx, 'mir
             _ => unreachable!("Tags can only be invalidated during a retag or access"),
         };
         self.history.invalidations.push(Invalidation { tag, range, span, cause });
+        self.history.enforce_limit();
+        span
     }
 
     pub fn log_protector(&mut self) {
         let Operation::Retag(op) = &self.operation else {
             unreachable!("Protectors can only be created during a retag")
         };
-        self.history.protectors.push(Protection { tag: op.new_tag, span: self.current_span.get() });
+        // Protectors are added by retags in the callee right after the call, so the caller is the
+        // second frame from the top.
+        let stack = self.threads.active_thread_stack();
+        let call = match stack {
+            [.., caller, callee] => Some((callee.instance.to_string(), caller.current_span())),
+            _ => None,
+        };
+        self.history.protectors.push(Protection {
+            tag: op.new_tag,
+            span: self.current_span.get(),
+            call,
+        });
+        self.history.enforce_limit();
     }
 
     pub fn get_logs_relevant_to(
@@ -348,16 +507,88 @@ impl<'span, 'history, 'ecx, 'mir, 'tcx> DiagnosticCx<'span, 'history, 'ecx, 'mir
             if event.tag == tag { Some(event.generate_diagnostic()) } else { None }
         });
 
-        let protected = protector_tag
-            .and_then(|protector| {
-                self.history.protectors.iter().find(|protection| protection.tag == protector)
+        let protection = protector_tag.and_then(|protector| {
+            self.history.protectors.iter().find(|protection| protection.tag == protector)
+        });
+        let protected = protection.map(|protection| {
+            let protected_tag = protection.tag;
+            (format!("{protected_tag:?} is this argument"), protection.span.data())
+        });
+        let protector_call = protection.and_then(|protection| {
+            let protected_tag = protection.tag;
+            let (function, span) = protection.call.as_ref()?;
+            Some((
+                format!("{protected_tag:?} is protected by this call to `{function}`"),
+                span.data(),
+            ))
+        });
+
+        Some(TagHistory { created, invalidated, protected, protector_call })
+    }
+
+    /// Explain where the tags that a wildcard pointer could have used at this location were
+    /// exposed. Only the most recent exposure of each tag is shown.
+    fn exposure_logs(&self, stack: &Stack) -> Vec<(String, SpanData)> {
+        /// Showing more than this many exposures is unlikely to help.
+        const MAX_EXPOSURES: usize = 3;
+
+        let covers_offset = |tag: SbTag| {
+            self.history.base.0.tag() == tag
+                || self.history.creations.iter().any(|event| {
+                    let range = event.retag.range;
+                    event.retag.new_tag == tag
+                        && self.offset >= range.start
+                        && self.offset < range.end()
+                })
+        };
+        let mut exposures: Vec<_> = self.history.exposures.iter().collect();
+        exposures.sort_by_key(|(_, exposure)| std::cmp::Reverse(exposure.index));
+        let mut logs: Vec<_> = exposures
+            .into_iter()
+            .filter_map(|(&tag, exposure)| {
+                let item =
+                    (0..stack.len()).map(|i| stack.get(i).unwrap()).find(|item| item.tag() == tag);
+                let msg = match item {
+                    Some(item) =>
+                        format!(
+                            "{tag:?} was exposed here, but it only has {:?} permission for this location",
+                            item.perm()
+                        ),
+                    // Tags that never existed at this offset are not relevant.
+                    None if !covers_offset(tag) => return None,
+                    None =>
+                        format!(
+                            "{tag:?} was exposed here, but it is no longer in the borrow stack for this location"
+                        ),
+                };
+                Some((msg, exposure.span.data()))
             })
-            .map(|protection| {
-                let protected_tag = protection.tag;
-                (format!("{protected_tag:?} is this argument"), protection.span.data())
-            });
+            .take(MAX_EXPOSURES)
+            .collect();
+        logs.reverse();
+        logs
+    }
 
-        Some(TagHistory { created, invalidated, protected })
+    fn error_details(&self, stack: Option<&Stack>) -> SbErrorDetails {
+        let (kind, tag, range) = match &self.operation {
+            Operation::Retag(op) => (SbErrorKind::Retag, op.orig_tag, Some(op.range)),
+            Operation::Access(op) => (SbErrorKind::Access(op.kind), op.tag, Some(op.range)),
+            Operation::Dealloc(op) => (SbErrorKind::Dealloc, op.tag, None),
+        };
+        let exposures = match (tag, stack) {
+            (ProvenanceExtra::Wildcard, Some(stack)) => self.exposure_logs(stack),
+            _ => Vec::new(),
+        };
+        SbErrorDetails {
+            kind,
+            alloc_id: self.history.id,
+            offset: self.offset,
+            range,
+            tag: tag.and_then(Some),
+            stack: stack.map(StackSnapshot::new),
+            history_truncated: self.history.truncated,
+            exposures,
+        }
     }
 
     /// Report a descriptive error when `new` could not be granted from `derived_from`.
@@ -377,6 +608,7 @@ impl<'span, 'history, 'ecx, 'mir, 'tcx> DiagnosticCx<'span, 'history, 'ecx, 'mir
             format!("{}{}", action, error_cause(stack, op.orig_tag)),
             Some(operation_summary(&op.cause.summary(), self.history.id, op.range)),
             op.orig_tag.and_then(|orig_tag| self.get_logs_relevant_to(orig_tag, None)),
+            self.error_details(Some(stack)),
         )
     }
 
@@ -397,6 +629,7 @@ impl<'span, 'history, 'ecx, 'mir, 'tcx> DiagnosticCx<'span, 'history, 'ecx, 'mir
             format!("{}{}", action, error_cause(stack, op.tag)),
             Some(operation_summary("an access", self.history.id, op.range)),
             op.tag.and_then(|tag| self.get_logs_relevant_to(tag, None)),
+            self.error_details(Some(stack)),
         )
     }
 
@@ -421,6 +654,7 @@ impl<'span, 'history, 'ecx, 'mir, 'tcx> DiagnosticCx<'span, 'history, 'ecx, 'mir
                     ),
                     None,
                     None,
+                    SbErrorDetails { kind: SbErrorKind::Protector, ..self.error_details(None) },
                 ),
             Operation::Retag(RetagOp { orig_tag: tag, .. })
             | Operation::Access(AccessOp { tag, .. }) =>
@@ -431,12 +665,13 @@ impl<'span, 'history, 'ecx, 'mir, 'tcx> DiagnosticCx<'span, 'history, 'ecx, 'mir
                     ),
                     None,
                     tag.and_then(|tag| self.get_logs_relevant_to(tag, Some(item.tag()))),
+                    SbErrorDetails { kind: SbErrorKind::Protector, ..self.error_details(None) },
                 ),
         }
     }
 
     #[inline(never)] // This is only called on fatal code paths
-    pub fn dealloc_error(&self) -> InterpError<'tcx> {
+    pub fn dealloc_error(&self, stack: &Stack) -> InterpError<'tcx> {
         let Operation::Dealloc(op) = &self.operation else {
             unreachable!("dealloc_error should only be called during a deallocation")
         };
@@ -447,6 +682,7 @@ impl<'span, 'history, 'ecx, 'mir, 'tcx> DiagnosticCx<'span, 'history, 'ecx, 'mir
             ),
             None,
             op.tag.and_then(|tag| self.get_logs_relevant_to(tag, None)),
+            self.error_details(Some(stack)),
         )
     }
 
@@ -505,3 +741,243 @@ impl RetagCause {
         .to_string()
     }
 }
+
+/// Render a Stacked Borrows error as a JSON object, for consumption by other tools. Tags and
+/// allocations are written the way the error messages show them.
+pub fn sb_error_json(
+    msg: &str,
+    help: Option<&str>,
+    history: Option<&TagHistory>,
+    details: &SbErrorDetails,
+    source_map: &SourceMap,
+) -> String {
+    let event = |(msg, span): &(String, SpanData)| {
+        let lo = source_map.lookup_char_pos(span.lo);
+        let hi = source_map.lookup_char_pos(span.hi);
+        json!({
+            "message": msg,
+            "file": lo.file.name.prefer_local().to_string(),
+            "line_start": lo.line,
+            "column_start": lo.col_display.saturating_add(1),
+            "line_end": hi.line,
+            "column_end": hi.col_display.saturating_add(1),
+        })
+    };
+    let tag = |tag: SbTag| format!("{tag:?}");
+    let stack = details.stack.as_ref().map(|stack| {
+        let items: Vec<_> = stack
+            .items
+            .iter()
+            .map(|item| {
+                json!({
+                    "tag": tag(item.tag()),
+                    "permission": format!("{:?}", item.perm()),
+                    "protected": item.protected(),
+                })
+            })
+            .collect();
+        json!({ "items": items, "unknown_bottom": stack.unknown_bottom.map(tag) })
+    });
+    json!({
+        "message": msg,
+        "help": help,
+        "alloc_id": format!("{:?}", details.alloc_id),
+        "offset": details.offset.bytes(),
+        "range": details.range.map(|range| {
+            json!({ "start": range.start.bytes(), "size": range.size.bytes() })
+        }),
+        "tag": details.tag.map(tag),
+        "created": history.map(|h| event(&h.created)),
+        "invalidated": history.and_then(|h| h.invalidated.as_ref()).map(event),
+        "protected": history.and_then(|h| h.protected.as_ref()).map(event),
+        "protector_call": history.and_then(|h| h.protector_call.as_ref()).map(event),
+        "exposures": details.exposures.iter().map(event).collect::<Vec<_>>(),
+        "stack": stack,
+        "history_truncated": details.history_truncated,
+    })
+    .to_string()
+}
+
+/// Collects the histories of allocations for `-Zmiri-sb-trace-out`, and writes them as a Graphviz
+/// graph at the end of the run.
+#[derive(Debug)]
+pub struct SbTrace {
+    out: PathBuf,
+    /// One `subgraph` per allocation.
+    clusters: String,
+}
+
+impl SbTrace {
+    pub fn new(out: PathBuf) -> Self {
+        SbTrace { out, clusters: String::new() }
+    }
+
+    pub fn out(&self) -> &Path {
+        &self.out
+    }
+
+    /// Add the history of an allocation to the trace. Allocations in which no tags were ever
+    /// created are skipped, to keep the graph readable.
+    pub fn add_allocation(&mut self, stacks: &Stacks, source_map: &SourceMap) {
+        self.add_history(&stacks.history, |span| source_map.span_to_diagnostic_string(span));
+    }
+
+    /// Render one `subgraph` for `history`, using `span_to_string` to describe source locations.
+    fn add_history(&mut self, history: &AllocHistory, span_to_string: impl Fn(Span) -> String) {
+        if history.creations.is_empty() {
+            return;
+        }
+        let span = |span: Span| escape_dot(&span_to_string(span));
+        let node = |tag: SbTag| format!("\"{:?}_{}\"", history.id, tag.0);
+
+        // The label lines of every tag, in order of creation.
+        let (base_item, base_span) = history.base;
+        let mut tags: Vec<(SbTag, Vec<String>)> = vec![(
+            base_item.tag(),
+            vec![
+                format!("{:?}: base tag ({:?})", base_item.tag(), base_item.perm()),
+                span(base_span),
+            ],
+        )];
+        let mut edges: Vec<(SbTag, SbTag)> = Vec::new();
+        for creation in &history.creations {
+            let retag = &creation.retag;
+            let line = match retag.permission {
+                Some(perm) =>
+                    format!("{:?}: {:?} retag at {:?}", retag.new_tag, perm, retag.range),
+                None => format!("{:?}: zero-size retag at {:?}", retag.new_tag, retag.range),
+            };
+            let lines = match tags.iter().position(|(tag, _)| *tag == retag.new_tag) {
+                Some(idx) => &mut tags[idx].1,
+                None => {
+                    tags.push((retag.new_tag, Vec::new()));
+                    &mut tags.last_mut().unwrap().1
+                }
+            };
+            lines.push(line);
+            lines.push(span(creation.span));
+            if let ProvenanceExtra::Concrete(orig_tag) = retag.orig_tag {
+                if !edges.contains(&(orig_tag, retag.new_tag)) {
+                    edges.push((orig_tag, retag.new_tag));
+                }
+            }
+        }
+        for protection in &history.protectors {
+            if let Some((_, lines)) = tags.iter_mut().find(|(tag, _)| *tag == protection.tag) {
+                lines.push(format!("protected at {}", span(protection.span)));
+            }
+        }
+
+        let out = &mut self.clusters;
+        out.push_str(&format!("  subgraph \"cluster_{:?}\" {{\n", history.id));
+        out.push_str(&format!("    label=\"{:?}\";\n", history.id));
+        for (tag, lines) in &tags {
+            out.push_str(&format!("    {} [label=\"{}\"];\n", node(*tag), lines.join("\\n")));
+        }
+        for (from, to) in &edges {
+            // The tag we derived from might have been dropped by the history limit.
+            if tags.iter().any(|(tag, _)| tag == from) {
+                out.push_str(&format!("    {} -> {};\n", node(*from), node(*to)));
+            }
+        }
+        for (i, invalidation) in history.invalidations.iter().enumerate() {
+            let inv_node = format!("\"{:?}_invalidation{}\"", history.id, i);
+            out.push_str(&format!(
+                "    {} [shape=box, color=red, label=\"invalidated at {:?} by a {}\\n{}\"];\n",
+                inv_node,
+                invalidation.range,
+                invalidation.cause,
+                span(invalidation.span),
+            ));
+            out.push_str(&format!(
+                "    {} -> {} [style=dashed, color=red];\n",
+                node(invalidation.tag),
+                inv_node
+            ));
+        }
+        out.push_str("  }\n");
+    }
+
+    pub fn write(&self) -> std::io::Result<()> {
+        std::fs::write(&self.out, format!("digraph stacked_borrows {{\n{}}}\n", self.clusters))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_span::DUMMY_SP;
+
+    #[test]
+    fn sb_trace_renders_dot() {
+        let id = AllocId(std::num::NonZeroU64::new(7).unwrap());
+        let tag = |i| SbTag::new(i).unwrap();
+        let retag = |new_tag, orig_tag, permission: Option<Permission>| RetagOp {
+            cause: RetagCause::Normal,
+            new_tag,
+            orig_tag,
+            range: alloc_range(
+                Size::ZERO,
+                Size::from_bytes(if permission.is_some() { 4 } else { 0 }),
+            ),
+            permission,
+            freeze_sensitive: false,
+        };
+        let creation = |retag: RetagOp| Creation {
+            retag,
+            span: DUMMY_SP,
+            full_range: alloc_range(Size::ZERO, Size::from_bytes(4)),
+        };
+        let history = AllocHistory {
+            id,
+            base: (Item::new(tag(1), Permission::Unique, false), DUMMY_SP),
+            creations: smallvec::smallvec![
+                creation(retag(
+                    tag(2),
+                    ProvenanceExtra::Concrete(tag(1)),
+                    Some(Permission::SharedReadWrite)
+                )),
+                // Derived from a tag whose creation is no longer in the history.
+                creation(retag(tag(4), ProvenanceExtra::Concrete(tag(3)), None)),
+            ],
+            invalidations: smallvec::smallvec![Invalidation {
+                tag: tag(2),
+                range: alloc_range(Size::ZERO, Size::from_bytes(4)),
+                span: DUMMY_SP,
+                cause: InvalidationCause::Access(AccessKind::Write),
+            }],
+            protectors: smallvec::smallvec![Protection { tag: tag(2), span: DUMMY_SP, call: None }],
+            exposures: FxHashMap::default(),
+            exposure_count: 0,
+            limit: None,
+            truncated: false,
+        };
+
+        let mut trace = SbTrace::new(PathBuf::new());
+        trace.add_history(&history, |_| r#"src\main.rs:1:1 "quoted""#.to_string());
+        let expected = r#"  subgraph "cluster_alloc7" {
+    label="alloc7";
+    "alloc7_1" [label="<1>: base tag (Unique)\nsrc\\main.rs:1:1 \"quoted\""];
+    "alloc7_2" [label="<2>: SharedReadWrite retag at [0x0..0x4]\nsrc\\main.rs:1:1 \"quoted\"\nprotected at src\\main.rs:1:1 \"quoted\""];
+    "alloc7_4" [label="<4>: zero-size retag at [0x0..0x0]\nsrc\\main.rs:1:1 \"quoted\""];
+    "alloc7_1" -> "alloc7_2";
+    "alloc7_invalidation0" [shape=box, color=red, label="invalidated at [0x0..0x4] by a write access\nsrc\\main.rs:1:1 \"quoted\""];
+    "alloc7_2" -> "alloc7_invalidation0" [style=dashed, color=red];
+  }
+"#;
+        assert_eq!(trace.clusters, expected);
+
+        // Control characters cannot end the label early or break the line.
+        let mut trace = SbTrace::new(PathBuf::new());
+        trace.add_history(&history, |_| "line\nbreak".to_string());
+        assert!(trace.clusters.contains(r"line\\nbreak"));
+        assert!(!trace.clusters.contains("line\nbreak"));
+
+        // Allocations without any retags are left out.
+        let mut empty = history.clone();
+        empty.creations.clear();
+        let mut trace = SbTrace::new(PathBuf::new());
+        trace.add_history(&empty, |_| String::new());
+        assert_eq!(trace.clusters, "");
+    }
+}
@@ -7,7 +7,7 @@ use std::cmp;
 use std::fmt;
 use std::num::NonZeroU64;
 
-use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use rustc_data_structures::fx::{FxHashMap, FxHashSet, FxIndexMap};
 use rustc_hir::Mutability;
 use rustc_middle::mir::RetagKind;
 use rustc_middle::ty::{
@@ -15,19 +15,24 @@ use rustc_middle::ty::{
     layout::{HasParamEnv, LayoutOf},
     Ty,
 };
-use rustc_span::DUMMY_SP;
+use rustc_span::{source_map::SourceMap, Span, SpanData, DUMMY_SP};
 use rustc_target::abi::Size;
 use smallvec::SmallVec;
 
 use crate::*;
 
 pub mod diagnostics;
-use diagnostics::{AllocHistory, DiagnosticCx, DiagnosticCxBuilder, RetagCause, TagHistory};
+use diagnostics::{
+    AllocHistory, DiagnosticCx, DiagnosticCxBuilder, RetagCause, SbErrorDetails, SbErrorKind,
+    TagHistory,
+};
 
 mod item;
 pub use item::{Item, Permission};
 mod stack;
 pub use stack::Stack;
+mod stats;
+use stats::SbStats;
 
 pub type CallId = NonZeroU64;
 
@@ -55,6 +60,28 @@ impl fmt::Debug for SbTag {
     }
 }
 
+/// A place in the source code where all newly created tags should be tracked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TagCreationSite {
+    /// Retags on the given line of a file whose path ends in the given string.
+    Line { file: String, line: usize },
+    /// Retags in the function with the given path, or a path ending in `::` and the given string.
+    Function(String),
+}
+
+impl std::str::FromStr for TagCreationSite {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((file, line)) = s.rsplit_once(':') {
+            if let Ok(line) = line.parse() {
+                return Ok(TagCreationSite::Line { file: file.to_owned(), line });
+            }
+        }
+        Ok(TagCreationSite::Function(s.to_owned()))
+    }
+}
+
 #[derive(Debug)]
 pub struct FrameExtra {
     /// The ID of the call this frame corresponds to.
@@ -103,10 +130,26 @@ pub struct GlobalStateInner {
     protected_tags: FxHashSet<SbTag>,
     /// The pointer ids to trace
     tracked_pointer_tags: FxHashSet<SbTag>,
+    /// Tags created at these places are added to `tracked_pointer_tags`.
+    tracked_tag_sites: Vec<TagCreationSite>,
     /// The call ids to trace
     tracked_call_ids: FxHashSet<CallId>,
     /// Whether to recurse into datatypes when searching for pointers to retag.
     retag_fields: bool,
+    /// The maximal number of events of each kind to keep in the history of an allocation.
+    history_limit: Option<usize>,
+    /// Whether every borrow stack maintains an index from tags to their position.
+    tag_index: bool,
+    /// Whether Stacked Borrows errors are reported as warnings, after which execution continues.
+    report_only: bool,
+    /// In report-only mode, the errors that were reported, keyed by the kind of error, the span
+    /// where the tag was created and the span of the failing operation. For each key, we only
+    /// show the first error, and count how often it occurred.
+    reported_errors: FxIndexMap<(SbErrorKind, Option<SpanData>, SpanData), (String, u64)>,
+    /// Whether all raw pointer retags grant `SharedReadWrite`.
+    permissive_raw_ptrs: bool,
+    /// If `Some`, statistics that are printed at the end of the run.
+    stats: Option<SbStats>,
 }
 
 /// We need interior mutable access to the global state.
@@ -157,8 +200,14 @@ impl fmt::Display for RefKind {
 impl GlobalStateInner {
     pub fn new(
         tracked_pointer_tags: FxHashSet<SbTag>,
+        tracked_tag_sites: Vec<TagCreationSite>,
         tracked_call_ids: FxHashSet<CallId>,
         retag_fields: bool,
+        history_limit: Option<usize>,
+        tag_index: bool,
+        report_only: bool,
+        permissive_raw_ptrs: bool,
+        stats: bool,
     ) -> Self {
         GlobalStateInner {
             next_ptr_tag: SbTag(NonZeroU64::new(1).unwrap()),
@@ -166,8 +215,15 @@ impl GlobalStateInner {
             next_call_id: NonZeroU64::new(1).unwrap(),
             protected_tags: FxHashSet::default(),
             tracked_pointer_tags,
+            tracked_tag_sites,
             tracked_call_ids,
             retag_fields,
+            history_limit,
+            tag_index,
+            report_only,
+            reported_errors: FxIndexMap::default(),
+            permissive_raw_ptrs,
+            stats: stats.then(SbStats::default),
         }
     }
 
@@ -199,6 +255,35 @@ impl GlobalStateInner {
         }
     }
 
+    /// The tags that are currently protected. Their items must not be garbage collected even if
+    /// no pointer with that tag is left, since popping them is still UB.
+    pub fn protected_tags(&self) -> impl Iterator<Item = SbTag> + '_ {
+        self.protected_tags.iter().copied()
+    }
+
+    pub fn print_stats(&self, source_map: &SourceMap) {
+        if let Some(stats) = &self.stats {
+            stats.print(source_map);
+        }
+    }
+
+    /// For `-Zmiri-sb-report-only`: list the errors that were not shown because they repeated an
+    /// earlier error.
+    pub fn print_repeated_errors(&self, source_map: &SourceMap) {
+        let mut repeated =
+            self.reported_errors.iter().filter(|(_key, (_msg, count))| *count > 1).peekable();
+        if repeated.peek().is_none() {
+            return;
+        }
+        eprintln!(
+            "Stacked Borrows errors that occurred more than once (only the first occurrence was shown):"
+        );
+        for ((_kind, _created, site), (msg, count)) in repeated {
+            let site = source_map.span_to_diagnostic_string(site.span());
+            eprintln!("  {site}: {count} times: {msg}");
+        }
+    }
+
     pub fn base_ptr_tag(&mut self, id: AllocId) -> SbTag {
         self.base_ptr_tags.get(&id).copied().unwrap_or_else(|| {
             let tag = self.new_ptr();
@@ -217,8 +302,40 @@ pub fn err_sb_ub<'tcx>(
     msg: String,
     help: Option<String>,
     history: Option<TagHistory>,
+    details: SbErrorDetails,
 ) -> InterpError<'tcx> {
-    err_machine_stop!(TerminationInfo::StackedBorrowsUb { msg, help, history })
+    err_machine_stop!(TerminationInfo::StackedBorrowsUb { msg, help, history, details })
+}
+
+/// For `-Zmiri-sb-report-only`: turn a Stacked Borrows error into a warning. All other errors are
+/// returned unchanged. `site` is the span of the failing operation.
+fn report_sb_ub<'tcx>(
+    err: InterpErrorInfo<'tcx>,
+    site: Span,
+    global: &mut GlobalStateInner,
+) -> InterpResult<'tcx> {
+    if let InterpError::MachineStop(info) = err.kind() {
+        if let Some(TerminationInfo::StackedBorrowsUb { msg, help, history, details }) =
+            info.downcast_ref::<TerminationInfo>()
+        {
+            // An error in a loop would otherwise be reported in every iteration, with fresh tags
+            // each time. Only show the first one, and count the rest.
+            let key = (details.kind, history.as_ref().map(|h| h.created.1), site.data());
+            let (_msg, count) =
+                global.reported_errors.entry(key).or_insert_with(|| (msg.clone(), 0));
+            *count += 1;
+            if *count == 1 {
+                register_diagnostic(NonHaltingDiagnostic::StackedBorrowsUb {
+                    msg: msg.clone(),
+                    help: help.clone(),
+                    history: history.clone(),
+                    details: details.clone(),
+                });
+            }
+            return Ok(());
+        }
+    }
+    Err(err)
 }
 
 // # Stacked Borrows Core Begin
@@ -353,7 +470,11 @@ impl<'tcx> Stack {
             };
             self.pop_items_after(first_incompatible_idx, |item| {
                 Stack::item_popped(&item, global, dcx)?;
-                dcx.log_invalidation(item.tag());
+                let span = dcx.log_invalidation(item.tag());
+                if let Some(stats) = &mut global.stats {
+                    stats.record_pop();
+                    stats.record_invalidation(item.tag(), span);
+                }
                 Ok(())
             })?;
         } else {
@@ -374,7 +495,11 @@ impl<'tcx> Stack {
             };
             self.disable_uniques_starting_at(first_incompatible_idx, |item| {
                 Stack::item_popped(&item, global, dcx)?;
-                dcx.log_invalidation(item.tag());
+                let span = dcx.log_invalidation(item.tag());
+                if let Some(stats) = &mut global.stats {
+                    stats.record_disable();
+                    stats.record_invalidation(item.tag(), span);
+                }
                 Ok(())
             })?;
         }
@@ -417,7 +542,7 @@ impl<'tcx> Stack {
     ) -> InterpResult<'tcx> {
         // Step 1: Make sure there is a granting item.
         self.find_granting(AccessKind::Write, tag, exposed_tags)
-            .map_err(|_| dcx.dealloc_error())?;
+            .map_err(|_| dcx.dealloc_error(self))?;
 
         // Step 2: Consider all items removed. This checks for protectors.
         for idx in (0..self.len()).rev() {
@@ -494,6 +619,9 @@ impl<'tcx> Stack {
         // Put the new item there.
         trace!("reborrow: adding item {:?}", new);
         self.insert(new_idx, new);
+        if let Some(stats) = &mut global.stats {
+            stats.record_push(self.len());
+        }
         Ok(())
     }
 }
@@ -501,15 +629,20 @@ impl<'tcx> Stack {
 
 /// Integration with the SbTag garbage collector
 impl Stacks {
-    pub fn remove_unreachable_tags(&mut self, live_tags: &FxHashSet<SbTag>) {
+    /// Returns the number of items that were removed.
+    pub fn remove_unreachable_tags(&mut self, live_tags: &FxHashSet<SbTag>) -> usize {
+        let mut removed = 0;
         if self.modified_since_last_gc {
             for stack in self.stacks.iter_mut_all() {
                 if stack.len() > 64 {
+                    let len = stack.len();
                     stack.retain(live_tags);
+                    removed += len - stack.len();
                 }
             }
             self.modified_since_last_gc = false;
         }
+        removed
     }
 }
 
@@ -522,26 +655,34 @@ impl<'tcx> Stacks {
         perm: Permission,
         tag: SbTag,
         id: AllocId,
+        history_limit: Option<usize>,
+        tag_index: bool,
         current_span: &mut CurrentSpan<'_, '_, '_>,
     ) -> Self {
         let item = Item::new(tag, perm, false);
-        let stack = Stack::new(item);
+        let stack = Stack::new(item, tag_index);
 
         Stacks {
             stacks: RangeMap::new(size, stack),
-            history: AllocHistory::new(id, item, current_span),
+            history: AllocHistory::new(id, item, history_limit, current_span),
             exposed_tags: FxHashSet::default(),
             modified_since_last_gc: false,
         }
     }
 
     /// Call `f` on every stack in the range.
+    ///
+    /// With `-Zmiri-sb-report-only`, a Stacked Borrows error in `f` is reported as a warning, and
+    /// the stack it occurred in is forgotten, so that every existing tag may be used at this
+    /// location afterwards. This avoids follow-up errors caused by the same bug.
     fn for_each(
         &mut self,
         range: AllocRange,
+        global: &mut GlobalStateInner,
         mut dcx_builder: DiagnosticCxBuilder<'_, '_, '_, 'tcx>,
         mut f: impl FnMut(
             &mut Stack,
+            &mut GlobalStateInner,
             &mut DiagnosticCx<'_, '_, '_, '_, 'tcx>,
             &mut FxHashSet<SbTag>,
         ) -> InterpResult<'tcx>,
@@ -549,7 +690,14 @@ impl<'tcx> Stacks {
         self.modified_since_last_gc = true;
         for (offset, stack) in self.stacks.iter_mut(range.start, range.size) {
             let mut dcx = dcx_builder.build(&mut self.history, offset);
-            f(stack, &mut dcx, &mut self.exposed_tags)?;
+            if let Err(err) = f(stack, global, &mut dcx, &mut self.exposed_tags) {
+                if !global.report_only {
+                    return Err(err);
+                }
+                report_sb_ub(err, dcx.current_span(), global)?;
+                trace!("forgetting stack at {:?} after reporting an error", offset);
+                stack.set_unknown_bottom(global.next_ptr_tag);
+            }
             dcx_builder = dcx.unbuild();
         }
         Ok(())
@@ -576,7 +724,27 @@ impl Stacks {
             // Everything else is shared by default.
             _ => (extra.base_ptr_tag(id), Permission::SharedReadWrite),
         };
-        Stacks::new(size, perm, base_tag, id, &mut current_span)
+        Stacks::new(
+            size,
+            perm,
+            base_tag,
+            id,
+            extra.history_limit,
+            extra.tag_index,
+            &mut current_span,
+        )
+    }
+
+    /// Forget all tags in `range` except for the base tag, as if the memory had just been
+    /// allocated. This is for memory whose contents are replaced behind the back of the program,
+    /// like the pages discarded by `madvise`.
+    pub fn reset_range(&mut self, id: AllocId, range: AllocRange, state: &GlobalState) {
+        let mut extra = state.borrow_mut();
+        let item = Item::new(extra.base_ptr_tag(id), Permission::SharedReadWrite, false);
+        for (_, stack) in self.stacks.iter_mut(range.start, range.size) {
+            *stack = Stack::new(item, extra.tag_index);
+        }
+        self.modified_since_last_gc = true;
     }
 
     #[inline(always)]
@@ -600,8 +768,11 @@ impl Stacks {
         );
         let dcx = DiagnosticCxBuilder::read(&mut current_span, threads, tag, range);
         let mut state = state.borrow_mut();
-        self.for_each(range, dcx, |stack, dcx, exposed_tags| {
-            stack.access(AccessKind::Read, tag, &mut state, dcx, exposed_tags)
+        if let Some(stats) = &mut state.stats {
+            stats.record_access(AccessKind::Read, alloc_id);
+        }
+        self.for_each(range, &mut state, dcx, |stack, global, dcx, exposed_tags| {
+            stack.access(AccessKind::Read, tag, global, dcx, exposed_tags)
         })
     }
 
@@ -623,8 +794,11 @@ impl Stacks {
         );
         let dcx = DiagnosticCxBuilder::write(&mut current_span, threads, tag, range);
         let mut state = state.borrow_mut();
-        self.for_each(range, dcx, |stack, dcx, exposed_tags| {
-            stack.access(AccessKind::Write, tag, &mut state, dcx, exposed_tags)
+        if let Some(stats) = &mut state.stats {
+            stats.record_access(AccessKind::Write, alloc_id);
+        }
+        self.for_each(range, &mut state, dcx, |stack, global, dcx, exposed_tags| {
+            stack.access(AccessKind::Write, tag, global, dcx, exposed_tags)
         })
     }
 
@@ -640,9 +814,9 @@ impl Stacks {
     ) -> InterpResult<'tcx> {
         trace!("deallocation with tag {:?}: {:?}, size {}", tag, alloc_id, range.size.bytes());
         let dcx = DiagnosticCxBuilder::dealloc(&mut current_span, threads, tag);
-        let state = state.borrow();
-        self.for_each(range, dcx, |stack, dcx, exposed_tags| {
-            stack.dealloc(tag, &state, dcx, exposed_tags)
+        let mut state = state.borrow_mut();
+        self.for_each(range, &mut state, dcx, |stack, global, dcx, exposed_tags| {
+            stack.dealloc(tag, global, dcx, exposed_tags)
         })?;
         Ok(())
     }
@@ -655,6 +829,32 @@ impl<'mir: 'ecx, 'tcx: 'mir, 'ecx> EvalContextPrivExt<'mir, 'tcx, 'ecx>
 {
 }
 trait EvalContextPrivExt<'mir: 'ecx, 'tcx: 'mir, 'ecx>: crate::MiriEvalContextExt<'mir, 'tcx> {
+    /// Whether tags created by the current retag should be tracked because of where the retag
+    /// happens, as requested by `-Zmiri-track-pointer-tags-at`.
+    fn is_tracked_tag_site(&self) -> bool {
+        let this = self.eval_context_ref();
+        let global = this.machine.stacked_borrows.as_ref().unwrap().borrow();
+        if global.tracked_tag_sites.is_empty() {
+            return false;
+        }
+        let span = this.machine.current_span(*this.tcx).get();
+        let loc = this.tcx.sess.source_map().lookup_char_pos(span.lo());
+        let file = loc.file.name.prefer_local().to_string();
+        let function = this.active_thread_stack().last().map(|frame| {
+            this.tcx.def_path_str(frame.instance.def_id())
+        });
+        global.tracked_tag_sites.iter().any(|site| {
+            match site {
+                TagCreationSite::Line { file: site_file, line } =>
+                    *line == loc.line && file.ends_with(site_file.as_str()),
+                TagCreationSite::Function(name) =>
+                    function.as_deref().map_or(false, |function| {
+                        function == name || function.ends_with(&format!("::{name}"))
+                    }),
+            }
+        })
+    }
+
     /// Returns the `AllocId` the reborrow was done in, if some actual borrow stack manipulation
     /// happened.
     fn reborrow(
@@ -667,11 +867,28 @@ trait EvalContextPrivExt<'mir: 'ecx, 'tcx: 'mir, 'ecx>: crate::MiriEvalContextEx
         protect: bool,
     ) -> InterpResult<'tcx, Option<AllocId>> {
         let this = self.eval_context_mut();
+        let global = this.machine.stacked_borrows.as_mut().unwrap().get_mut();
+        if let Some(stats) = &mut global.stats {
+            stats.record_retag(retag_cause);
+        }
+        // With `-Zmiri-sb-permissive-raw-ptrs`, `*const` is treated like `*mut`, so that all raw
+        // pointers get `SharedReadWrite` for the entire range.
+        let kind = match kind {
+            RefKind::Raw { mutable: false } if global.permissive_raw_ptrs =>
+                RefKind::Raw { mutable: true },
+            kind => kind,
+        };
+        // Shared references get different permissions inside and outside of `UnsafeCell`.
+        let freeze_sensitive = matches!(kind, RefKind::Shared | RefKind::Raw { mutable: false });
 
         // It is crucial that this gets called on all code paths, to ensure we track tag creation.
         let log_creation = |this: &MiriEvalContext<'mir, 'tcx>,
                             loc: Option<(AllocId, Size, ProvenanceExtra)>| // alloc_id, base_offset, orig_tag
          -> InterpResult<'tcx> {
+            if this.is_tracked_tag_site() {
+                let mut global = this.machine.stacked_borrows.as_ref().unwrap().borrow_mut();
+                global.tracked_pointer_tags.insert(new_tag);
+            }
             let global = this.machine.stacked_borrows.as_ref().unwrap().borrow();
             if global.tracked_pointer_tags.contains(&new_tag) {
                 register_diagnostic(NonHaltingDiagnostic::CreatedPointerTag(
@@ -708,6 +925,7 @@ trait EvalContextPrivExt<'mir: 'ecx, 'tcx: 'mir, 'ecx>: crate::MiriEvalContextEx
                         new_tag,
                         orig_tag,
                         alloc_range(base_offset, size),
+                        freeze_sensitive,
                     );
                     let mut dcx = dcx.build(&mut stacked_borrows.history, base_offset);
                     dcx.log_creation();
@@ -748,6 +966,9 @@ trait EvalContextPrivExt<'mir: 'ecx, 'tcx: 'mir, 'ecx>: crate::MiriEvalContextEx
 
         let (alloc_id, base_offset, orig_tag) = this.ptr_get_alloc_id(place.ptr)?;
         log_creation(this, Some((alloc_id, base_offset, orig_tag)))?;
+        if let Some(stats) = &mut this.machine.stacked_borrows.as_mut().unwrap().get_mut().stats {
+            stats.record_alloc_operation(alloc_id);
+        }
 
         // Ensure we bail out if the pointer goes out-of-bounds (see miri#1050).
         let (alloc_size, _) = this.get_live_alloc_size_and_align(alloc_id)?;
@@ -831,10 +1052,16 @@ trait EvalContextPrivExt<'mir: 'ecx, 'tcx: 'mir, 'ecx>: crate::MiriEvalContextEx
                         new_tag,
                         orig_tag,
                         alloc_range(base_offset, size),
+                        freeze_sensitive,
                     );
-                    stacked_borrows.for_each(range, dcx, |stack, dcx, exposed_tags| {
-                        stack.grant(orig_tag, item, &mut global, dcx, exposed_tags)
-                    })
+                    stacked_borrows.for_each(
+                        range,
+                        &mut global,
+                        dcx,
+                        |stack, global, dcx, exposed_tags| {
+                            stack.grant(orig_tag, item, global, dcx, exposed_tags)
+                        },
+                    )
                 })?;
                 return Ok(Some(alloc_id));
             }
@@ -862,9 +1089,10 @@ trait EvalContextPrivExt<'mir: 'ecx, 'tcx: 'mir, 'ecx>: crate::MiriEvalContextEx
             new_tag,
             orig_tag,
             alloc_range(base_offset, size),
+            freeze_sensitive,
         );
-        stacked_borrows.for_each(range, dcx, |stack, dcx, exposed_tags| {
-            stack.grant(orig_tag, item, &mut global, dcx, exposed_tags)
+        stacked_borrows.for_each(range, &mut global, dcx, |stack, global, dcx, exposed_tags| {
+            stack.grant(orig_tag, item, global, dcx, exposed_tags)
         })?;
 
         Ok(Some(alloc_id))
@@ -1061,9 +1289,97 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
         Ok(())
     }
 
+    /// Print the borrow stacks of the `size` bytes that `ptr` points to, for
+    /// `miri_print_borrow_state`. Adjacent bytes with the same stack are grouped together.
+    fn print_borrow_state(
+        &mut self,
+        ptr: Pointer<Option<Provenance>>,
+        size: Size,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        if this.machine.stacked_borrows.is_none() {
+            eprintln!("Stacked Borrows is disabled");
+            return Ok(());
+        }
+        let (alloc_id, offset, tag) = this.ptr_get_alloc_id(ptr)?;
+        let (alloc_size, _align, kind) = this.get_alloc_info(alloc_id);
+        if !matches!(kind, AllocKind::LiveData) {
+            eprintln!("{alloc_id:?} is not a live allocation and has no borrow stacks");
+            return Ok(());
+        }
+        let end = match offset.checked_add(size, this) {
+            Some(end) if end <= alloc_size => end,
+            _ =>
+                throw_unsup_format!(
+                    "miri_print_borrow_state: {alloc_id:?} has size {} but {} bytes at offset {} were requested",
+                    alloc_size.bytes(),
+                    size.bytes(),
+                    offset.bytes(),
+                ),
+        };
+        let alloc_extra = this.get_alloc_extra(alloc_id)?;
+        let stacks = alloc_extra.stacked_borrows.as_ref().unwrap().borrow();
+        eprintln!(
+            "borrow state of {alloc_id:?}{:?} for pointer with tag {tag:?}:",
+            alloc_range(offset, size)
+        );
+        let chunks: Vec<_> = stacks.stacks.iter(offset, size).collect();
+        // Merge neighboring chunks with the same stack.
+        let mut groups: Vec<(Size, &Stack)> = Vec::new();
+        for (start, stack) in chunks {
+            if groups.last().map_or(true, |&(_, last)| last != stack) {
+                groups.push((cmp::max(start, offset), stack));
+            }
+        }
+        for (i, &(start, stack)) in groups.iter().enumerate() {
+            let group_end = groups.get(i + 1).map_or(end, |&(next, _)| next);
+            let mut items: Vec<String> = Vec::new();
+            if let Some(bottom) = stack.unknown_bottom() {
+                items.push(format!("[unknown below {bottom:?}]"));
+            }
+            items.extend((0..stack.len()).map(|i| {
+                let item = stack.get(i).unwrap();
+                let protected = if item.protected() { " (protected)" } else { "" };
+                format!("{item:?}{protected}")
+            }));
+            eprintln!("  {:?}: {}", alloc_range(start, group_end - start), items.join(" "));
+        }
+        if !stacks.exposed_tags.is_empty() {
+            let mut exposed: Vec<_> = stacks.exposed_tags.iter().collect();
+            exposed.sort_by_key(|tag| tag.0);
+            eprintln!("  exposed tags: {exposed:?}");
+        }
+        Ok(())
+    }
+
+    /// Write the `-Zmiri-sb-trace-out` trace, including the allocations that are still live.
+    fn write_sb_trace(&mut self) {
+        let this = self.eval_context_mut();
+        let Some(mut trace) = this.machine.sb_trace.take() else { return };
+        let source_map = this.tcx.tcx.sess.source_map();
+        this.memory.alloc_map().iter(|it| {
+            let mut allocs: Vec<_> = it
+                .filter_map(|(id, (_kind, alloc))| {
+                    Some((*id, alloc.extra.stacked_borrows.as_ref()?))
+                })
+                .collect();
+            allocs.sort_by_key(|(id, _)| *id);
+            for (_id, stacks) in allocs {
+                trace.add_allocation(&stacks.borrow(), source_map);
+            }
+        });
+        if let Err(err) = trace.write() {
+            this.tcx.sess.warn(format!(
+                "failed to write Stacked Borrows trace to `{}`: {err}",
+                trace.out().display()
+            ));
+        }
+    }
+
     /// Mark the given tag as exposed. It was found on a pointer with the given AllocId.
     fn expose_tag(&mut self, alloc_id: AllocId, tag: SbTag) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
+        let span = this.machine.current_span(*this.tcx).get();
 
         // Function pointers and dead objects don't have an alloc_extra so we ignore them.
         // This is okay because accessing them is UB anyway, no need for any Stacked Borrows checks.
@@ -1076,7 +1392,9 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: crate::MiriEvalContextExt<'mir, 'tcx
                 // uncovers a non-supported `extern static`.
                 let alloc_extra = this.get_alloc_extra(alloc_id)?;
                 trace!("Stacked Borrows tag {tag:?} exposed in {alloc_id:?}");
-                alloc_extra.stacked_borrows.as_ref().unwrap().borrow_mut().exposed_tags.insert(tag);
+                let mut stacks = alloc_extra.stacked_borrows.as_ref().unwrap().borrow_mut();
+                stacks.exposed_tags.insert(tag);
+                stacks.history.log_exposure(tag, span);
             }
             AllocKind::Function | AllocKind::VTable | AllocKind::Dead => {
                 // No stacked borrows on these allocations.
@@ -1,7 +1,7 @@
 #[cfg(feature = "stack-cache")]
 use std::ops::Range;
 
-use rustc_data_structures::fx::FxHashSet;
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
 
 use crate::stacked_borrows::{AccessKind, Item, Permission, SbTag};
 use crate::ProvenanceExtra;
@@ -37,6 +37,9 @@ pub struct Stack {
     /// this scan by keeping track of the region of the borrow stack that may contain `Unique`s.
     #[cfg(feature = "stack-cache")]
     unique_range: Range<usize>,
+    /// With `-Zmiri-sb-tag-index`, this maps every tag in `borrows` to its position, so that
+    /// looking up a tag does not require a linear search even if it is not in the cache.
+    index: Option<FxHashMap<SbTag, usize>>,
 }
 
 impl Stack {
@@ -72,6 +75,11 @@ impl Stack {
         }
         self.borrows.truncate(write_idx);
 
+        if first_removed.is_some() {
+            // Everything above the first removed item has moved, so just rebuild the index.
+            self.rebuild_index();
+        }
+
         #[cfg(not(feature = "stack-cache"))]
         drop(first_removed); // This is only needed for the stack-cache
 
@@ -123,6 +131,27 @@ impl StackCache {
     }
 }
 
+impl Stack {
+    /// Recomputes the position of every tag in the index, if there is one.
+    fn rebuild_index(&mut self) {
+        if let Some(index) = &mut self.index {
+            index.clear();
+            index.extend(self.borrows.iter().enumerate().map(|(idx, item)| (item.tag(), idx)));
+        }
+    }
+
+    /// Panics if the index does not match the borrow stack.
+    #[cfg(debug_assertions)]
+    fn verify_index_consistency(&self) {
+        if let Some(index) = &self.index {
+            assert_eq!(index.len(), self.borrows.len());
+            for (idx, item) in self.borrows.iter().enumerate() {
+                assert_eq!(index.get(&item.tag()), Some(&idx));
+            }
+        }
+    }
+}
+
 impl PartialEq for Stack {
     fn eq(&self, other: &Self) -> bool {
         // All the semantics of Stack are in self.borrows, everything else is caching
@@ -182,6 +211,8 @@ impl<'tcx> Stack {
     ) -> Result<Option<usize>, ()> {
         #[cfg(all(feature = "stack-cache", debug_assertions))]
         self.verify_cache_consistency();
+        #[cfg(debug_assertions)]
+        self.verify_index_consistency();
 
         let ProvenanceExtra::Concrete(tag) = tag else {
             // Handle the wildcard case.
@@ -218,6 +249,12 @@ impl<'tcx> Stack {
     }
 
     fn find_granting_tagged(&mut self, access: AccessKind, tag: SbTag) -> Option<usize> {
+        // With an index, we know exactly where to look; no tag occurs in the stack more than once.
+        if let Some(index) = &self.index {
+            let stack_idx = *index.get(&tag)?;
+            return self.borrows[stack_idx].perm().grants(access).then_some(stack_idx);
+        }
+
         #[cfg(feature = "stack-cache")]
         if let Some(idx) = self.find_granting_cache(access, tag) {
             return Some(idx);
@@ -278,6 +315,19 @@ impl<'tcx> Stack {
     pub fn insert(&mut self, new_idx: usize, new: Item) {
         self.borrows.insert(new_idx, new);
 
+        if let Some(index) = &mut self.index {
+            // Like for the cache, an insert that is not a push moves every item above it.
+            // This is linear in the size of the stack, but so is the insert itself.
+            if new_idx != self.borrows.len() - 1 {
+                for idx in index.values_mut() {
+                    if *idx >= new_idx {
+                        *idx += 1;
+                    }
+                }
+            }
+            index.insert(new.tag(), new_idx);
+        }
+
         #[cfg(feature = "stack-cache")]
         self.insert_cache(new_idx, new);
     }
@@ -321,8 +371,9 @@ impl<'tcx> Stack {
         self.verify_cache_consistency();
     }
 
-    /// Construct a new `Stack` using the passed `Item` as the base tag.
-    pub fn new(item: Item) -> Self {
+    /// Construct a new `Stack` using the passed `Item` as the base tag. If `tag_index` is set,
+    /// the stack maintains an index of the position of every tag.
+    pub fn new(item: Item, tag_index: bool) -> Self {
         Stack {
             borrows: vec![item],
             unknown_bottom: None,
@@ -330,6 +381,7 @@ impl<'tcx> Stack {
             cache: StackCache { idx: [0; CACHE_LEN], items: [item; CACHE_LEN] },
             #[cfg(feature = "stack-cache")]
             unique_range: if item.perm() == Permission::Unique { 0..1 } else { 0..0 },
+            index: tag_index.then(|| [(item.tag(), 0)].into_iter().collect()),
         }
     }
 
@@ -352,6 +404,9 @@ impl<'tcx> Stack {
         // cache when it has been cleared and not yet refilled.
         self.borrows.clear();
         self.unknown_bottom = Some(tag);
+        if let Some(index) = &mut self.index {
+            index.clear();
+        }
         #[cfg(feature = "stack-cache")]
         {
             self.unique_range = 0..0;
@@ -415,6 +470,9 @@ impl<'tcx> Stack {
     ) -> crate::InterpResult<'tcx> {
         while self.borrows.len() > start {
             let item = self.borrows.pop().unwrap();
+            if let Some(index) = &mut self.index {
+                index.remove(&item.tag());
+            }
             visitor(item)?;
         }
 
@@ -0,0 +1,120 @@
+//! Statistics about the work done by Stacked Borrows, printed at the end of the run with
+//! `-Zmiri-sb-stats`.
+
+use rustc_data_structures::fx::FxHashMap;
+use rustc_span::{source_map::SourceMap, Span};
+
+use crate::stacked_borrows::{diagnostics::RetagCause, AccessKind, SbTag};
+use crate::*;
+
+/// How many of the allocations with the most Stacked Borrows operations to show.
+const HOTTEST_ALLOCS: usize = 10;
+/// How many of the places and tags with the most invalidations to show.
+const HOTTEST_INVALIDATIONS: usize = 10;
+
+#[derive(Debug, Default)]
+pub struct SbStats {
+    retags: FxHashMap<RetagCause, u64>,
+    reads: u64,
+    writes: u64,
+    items_pushed: u64,
+    items_popped: u64,
+    items_disabled: u64,
+    max_stack_depth: usize,
+    /// The number of retags and accesses per allocation.
+    alloc_operations: FxHashMap<AllocId, u64>,
+    /// The number of items invalidated by the operation at each span.
+    invalidation_sites: FxHashMap<Span, u64>,
+    /// The number of times each tag was invalidated, i.e. at how many locations.
+    invalidated_tags: FxHashMap<SbTag, u64>,
+}
+
+impl SbStats {
+    pub fn record_retag(&mut self, cause: RetagCause) {
+        *self.retags.entry(cause).or_default() += 1;
+    }
+
+    pub fn record_access(&mut self, kind: AccessKind, alloc_id: AllocId) {
+        match kind {
+            AccessKind::Read => self.reads += 1,
+            AccessKind::Write => self.writes += 1,
+        }
+        self.record_alloc_operation(alloc_id);
+    }
+
+    pub fn record_alloc_operation(&mut self, alloc_id: AllocId) {
+        *self.alloc_operations.entry(alloc_id).or_default() += 1;
+    }
+
+    /// Record that an item was pushed onto a stack, which now has `depth` items.
+    pub fn record_push(&mut self, depth: usize) {
+        self.items_pushed += 1;
+        self.max_stack_depth = self.max_stack_depth.max(depth);
+    }
+
+    pub fn record_pop(&mut self) {
+        self.items_popped += 1;
+    }
+
+    pub fn record_disable(&mut self) {
+        self.items_disabled += 1;
+    }
+
+    /// Record that `tag` was popped or disabled by the operation at `span`.
+    pub fn record_invalidation(&mut self, tag: SbTag, span: Span) {
+        *self.invalidation_sites.entry(span).or_default() += 1;
+        *self.invalidated_tags.entry(tag).or_default() += 1;
+    }
+
+    pub fn print(&self, source_map: &SourceMap) {
+        let retag_count = |cause| self.retags.get(&cause).copied().unwrap_or(0);
+        eprintln!("Stacked Borrows statistics:");
+        eprintln!(
+            "  retags: {} (Normal: {}, FnEntry: {}, FnReturn: {}, TwoPhase: {})",
+            self.retags.values().sum::<u64>(),
+            retag_count(RetagCause::Normal),
+            retag_count(RetagCause::FnEntry),
+            retag_count(RetagCause::FnReturn),
+            retag_count(RetagCause::TwoPhase),
+        );
+        eprintln!("  accesses checked: {} reads, {} writes", self.reads, self.writes);
+        eprintln!(
+            "  borrow stack items: {} pushed, {} popped, {} disabled",
+            self.items_pushed, self.items_popped, self.items_disabled
+        );
+        eprintln!("  maximal borrow stack depth: {}", self.max_stack_depth);
+        let mut allocs: Vec<_> = self.alloc_operations.iter().collect();
+        allocs.sort_by(|(id1, count1), (id2, count2)| count2.cmp(count1).then(id1.cmp(id2)));
+        if !allocs.is_empty() {
+            eprintln!("  allocations with the most retags and accesses:");
+        }
+        for (alloc_id, count) in allocs.into_iter().take(HOTTEST_ALLOCS) {
+            eprintln!("    {alloc_id:?}: {count}");
+        }
+        // Spans are not ordered, so break ties by their rendering to keep the output stable.
+        let mut sites: Vec<_> = self
+            .invalidation_sites
+            .iter()
+            .map(|(span, count)| (source_map.span_to_diagnostic_string(*span), *count))
+            .collect();
+        sites.sort_by(|(site1, count1), (site2, count2)| {
+            count2.cmp(count1).then_with(|| site1.cmp(site2))
+        });
+        if !sites.is_empty() {
+            eprintln!("  places that invalidated the most borrow stack items:");
+        }
+        for (site, count) in sites.into_iter().take(HOTTEST_INVALIDATIONS) {
+            eprintln!("    {site}: {count}");
+        }
+        let mut tags: Vec<_> = self.invalidated_tags.iter().collect();
+        tags.sort_by(|(tag1, count1), (tag2, count2)| {
+            count2.cmp(count1).then_with(|| tag1.0.cmp(&tag2.0))
+        });
+        if !tags.is_empty() {
+            eprintln!("  tags invalidated at the most locations:");
+        }
+        for (tag, count) in tags.into_iter().take(HOTTEST_INVALIDATIONS) {
+            eprintln!("    {tag:?}: {count}");
+        }
+    }
+}
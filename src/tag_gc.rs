@@ -1,6 +1,32 @@
+use std::time::{Duration, Instant};
+
 use crate::*;
 use rustc_data_structures::fx::FxHashSet;
 
+/// Statistics about the tag garbage collector, printed at the end of the run with
+/// `-Zmiri-tag-gc-stats`.
+#[derive(Debug, Default)]
+pub struct TagGcStats {
+    /// How often the garbage collector ran.
+    passes: u64,
+    /// The largest number of live tags found in a single pass.
+    max_live_tags: usize,
+    /// The total number of items that were removed from borrow stacks.
+    items_removed: u64,
+    /// The total time spent in the garbage collector.
+    time: Duration,
+}
+
+impl TagGcStats {
+    pub fn print(&self) {
+        eprintln!("tag garbage collector statistics:");
+        eprintln!("  passes: {}", self.passes);
+        eprintln!("  most live tags in a pass: {}", self.max_live_tags);
+        eprintln!("  borrow stack items removed: {}", self.items_removed);
+        eprintln!("  time spent: {:?}", self.time);
+    }
+}
+
 impl<'mir, 'tcx: 'mir> EvalContextExt<'mir, 'tcx> for crate::MiriEvalContext<'mir, 'tcx> {}
 pub trait EvalContextExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
     fn garbage_collect_tags(&mut self) -> InterpResult<'tcx> {
@@ -10,8 +36,12 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
             return Ok(());
         }
 
+        let start = Instant::now();
         let mut tags = FxHashSet::default();
 
+        // Protected items must stay even if there is no pointer with their tag any more.
+        tags.extend(this.machine.stacked_borrows.as_ref().unwrap().borrow().protected_tags());
+
         for thread in this.machine.threads.iter() {
             if let Some(Scalar::Ptr(
                 Pointer { provenance: Provenance::Concrete { sb, .. }, .. },
@@ -26,7 +56,16 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
         self.find_tags_in_memory(&mut tags);
         self.find_tags_in_locals(&mut tags)?;
 
-        self.remove_unreachable_tags(tags);
+        let live_tags = tags.len();
+        let items_removed = self.remove_unreachable_tags(tags);
+
+        let this = self.eval_context_mut();
+        if let Some(stats) = &mut this.machine.tag_gc_stats {
+            stats.passes += 1;
+            stats.max_live_tags = stats.max_live_tags.max(live_tags);
+            stats.items_removed += u64::try_from(items_removed).unwrap();
+            stats.time += start.elapsed();
+        }
 
         Ok(())
     }
@@ -100,11 +139,13 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
         Ok(())
     }
 
-    fn remove_unreachable_tags(&mut self, tags: FxHashSet<SbTag>) {
+    /// Returns the number of borrow stack items that were removed.
+    fn remove_unreachable_tags(&mut self, tags: FxHashSet<SbTag>) -> usize {
         let this = self.eval_context_mut();
         this.memory.alloc_map().iter(|it| {
+            let mut removed = 0;
             for (_id, (_kind, alloc)) in it {
-                alloc
+                removed += alloc
                     .extra
                     .stacked_borrows
                     .as_ref()
@@ -112,6 +153,7 @@ pub trait EvalContextExt<'mir, 'tcx: 'mir>: MiriEvalContextExt<'mir, 'tcx> {
                     .borrow_mut()
                     .remove_unreachable_tags(&tags);
             }
-        });
+            removed
+        })
     }
 }
@@ -0,0 +1,13 @@
+//@only-target-linux
+//@only-on-host
+//@compile-flags: -Zmiri-extern-call-timeout=100ms
+
+extern "C" {
+    fn never_return();
+}
+
+fn main() {
+    unsafe {
+        never_return(); //~ ERROR: external function `never_return` did not return within 100ms
+    }
+}
@@ -0,0 +1,15 @@
+//@only-target-linux
+//@only-on-host
+// Stacked Borrows detects that we are casting & to &mut and so it changes why we fail
+//@compile-flags: -Zmiri-disable-stacked-borrows
+
+extern "C" {
+    static lib_answer: i32;
+}
+
+fn main() {
+    unsafe {
+        let ptr = &lib_answer as *const i32 as *mut i32;
+        *ptr = 0; //~ ERROR: read-only
+    }
+}
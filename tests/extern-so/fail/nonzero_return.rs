@@ -0,0 +1,15 @@
+//@only-target-linux
+//@only-on-host
+
+use std::num::NonZeroU32;
+
+extern "C" {
+    #[link_name = "get_zero"]
+    fn get_zero_nonzero() -> NonZeroU32;
+}
+
+fn main() {
+    unsafe {
+        get_zero_nonzero(); //~ ERROR: encountered 0, but expected something greater or equal to 1
+    }
+}
@@ -0,0 +1,20 @@
+//@only-target-linux
+//@only-on-host
+
+#[repr(C)]
+#[allow(dead_code)]
+struct WithPtr {
+    a: i8,
+    p: *const u8,
+}
+
+extern "C" {
+    fn fill_pair(p: *mut WithPtr);
+}
+
+fn main() {
+    let mut s = WithPtr { a: 0, p: std::ptr::null() };
+    unsafe {
+        fill_pair(&mut s); //~ ERROR: unsupported pointer field
+    }
+}
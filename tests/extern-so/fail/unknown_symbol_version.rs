@@ -0,0 +1,13 @@
+//@only-target-linux
+//@only-on-host
+
+extern "C" {
+    #[link_name = "add_one_int@CODEABI_2.0"]
+    fn add_one_int_v2(x: i32) -> i32;
+}
+
+fn main() {
+    unsafe {
+        add_one_int_v2(1); //~ ERROR: does not define version `CODEABI_2.0` of `add_one_int`; available versions: CODEABI_1.0
+    }
+}
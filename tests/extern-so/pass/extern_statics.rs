@@ -0,0 +1,22 @@
+//@only-target-linux
+//@only-on-host
+
+extern "C" {
+    static lib_answer: i32;
+    static mut lib_counter: i32;
+    fn bump_lib_counter();
+}
+
+fn main() {
+    unsafe {
+        assert_eq!(lib_answer, 42);
+
+        // Changes made by C code are visible to Rust, and vice versa.
+        assert_eq!(lib_counter, 5);
+        bump_lib_counter();
+        assert_eq!(lib_counter, 6);
+        lib_counter = 10;
+        bump_lib_counter();
+        assert_eq!(lib_counter, 11);
+    }
+}
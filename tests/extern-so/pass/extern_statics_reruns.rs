@@ -0,0 +1,18 @@
+//@only-target-linux
+//@only-on-host
+//@compile-flags: -Zmiri-many-seeds=0..2
+
+extern "C" {
+    static mut lib_counter: i32;
+    fn bump_lib_counter();
+}
+
+// Every run starts with the initial value of the library's `static mut`s, even though the
+// library stays loaded.
+fn main() {
+    unsafe {
+        assert_eq!(lib_counter, 5);
+        bump_lib_counter();
+        println!("{}", lib_counter);
+    }
+}
@@ -0,0 +1,24 @@
+//@only-target-linux
+//@only-on-host
+//@compile-flags: -Zmiri-extern-so-long-double=long_double::LongDouble
+
+// Rust has no `long double`; Miri passes this as the target's `long double` to C.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LongDouble(f64);
+
+extern "C" {
+    fn add_one_long_double(x: LongDouble) -> LongDouble;
+}
+
+fn main() {
+    unsafe {
+        // test that `long double` survives the round trip through C
+        assert_eq!(add_one_long_double(LongDouble(1.5)).0, 2.5);
+
+        // test that the result is rounded to nearest (ties to even) when narrowing to `f64`:
+        // `2 + 2^-52` is exact in `long double`, but exactly between two `f64` values
+        let x = 1.0 + f64::EPSILON;
+        assert_eq!(add_one_long_double(LongDouble(x)).0, 2.0);
+    }
+}
@@ -0,0 +1,56 @@
+//@only-target-linux
+//@only-on-host
+
+use std::marker::PhantomData;
+use std::num::{NonZeroU32, Wrapping};
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Fd(i32);
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Tagged<T>(PhantomData<T>, i32);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct COneField {
+    x: i16,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OneInt {
+    x: i32,
+}
+
+extern "C" {
+    #[link_name = "add_one_int"]
+    fn add_one_fd(x: Fd) -> Fd;
+    #[link_name = "add_one_int"]
+    fn add_one_tagged(x: Tagged<u8>) -> Tagged<u8>;
+    #[link_name = "add_int16"]
+    fn add_int16_c(x: COneField) -> i16;
+    fn make_one_int(a: i32, b: i32) -> OneInt;
+    #[link_name = "get_unsigned_int"]
+    fn get_unsigned_int_wrapping() -> Wrapping<u32>;
+    #[link_name = "get_unsigned_int"]
+    fn get_unsigned_int_nonzero() -> NonZeroU32;
+}
+
+fn main() {
+    unsafe {
+        // test `#[repr(transparent)]` newtypes as arguments and return values
+        assert_eq!(add_one_fd(Fd(1)), Fd(3));
+        assert_eq!(add_one_tagged(Tagged(PhantomData, 1)), Tagged(PhantomData, 3));
+
+        // test single-field `#[repr(C)]` structs as arguments and return values; the latter are
+        // returned like structs, which must not mix up the arguments
+        assert_eq!(add_int16_c(COneField { x: -1 }), 2);
+        assert_eq!(make_one_int(5, 3), OneInt { x: 2 });
+
+        // test standard library wrappers
+        assert_eq!(get_unsigned_int_wrapping(), Wrapping((-10i32) as u32));
+        assert_eq!(get_unsigned_int_nonzero().get(), (-10i32) as u32);
+    }
+}
@@ -0,0 +1,36 @@
+//@only-target-linux
+//@only-on-host
+
+use std::mem::MaybeUninit;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pair {
+    a: i8,
+    b: i32,
+}
+
+extern "C" {
+    fn fill_pair(p: *mut Pair);
+    fn sum_pair(p: *const Pair) -> i32;
+    fn ptr_is_null(p: *const u8) -> i32;
+}
+
+fn main() {
+    unsafe {
+        // test C filling in an uninitialized struct through an out-parameter
+        let mut pair = MaybeUninit::<Pair>::uninit();
+        fill_pair(pair.as_mut_ptr());
+        let pair = pair.assume_init();
+        assert_eq!(pair, Pair { a: 1, b: 2 });
+
+        // test C reading a struct through a const pointer
+        let pair = Pair { a: 40, b: 2 };
+        assert_eq!(sum_pair(&pair), 42);
+
+        // test passing pointers to other types, which C cannot dereference
+        let x = 0u8;
+        assert_eq!(ptr_is_null(&x), 0);
+        assert_eq!(ptr_is_null(std::ptr::null()), 1);
+    }
+}
@@ -0,0 +1,45 @@
+//@only-target-linux
+//@only-on-host
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SmallInts {
+    a: i32,
+    b: i32,
+    c: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Mixed {
+    l: i64,
+    d: f64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Large {
+    a: i64,
+    b: i64,
+    c: i64,
+    d: u8,
+}
+
+extern "C" {
+    fn make_small_ints(x: i32) -> SmallInts;
+    fn make_mixed(l: i64) -> Mixed;
+    fn make_large(x: i64) -> Large;
+}
+
+fn main() {
+    unsafe {
+        // test a struct returned in integer registers
+        assert_eq!(make_small_ints(1), SmallInts { a: 1, b: 2, c: 3 });
+
+        // test a struct returned in an integer and a floating-point register
+        assert_eq!(make_mixed(5), Mixed { l: 5, d: 2.5 });
+
+        // test a struct returned through memory provided by the caller
+        assert_eq!(make_large(7), Large { a: 7, b: 14, c: 21, d: b'L' });
+    }
+}
@@ -0,0 +1,20 @@
+//@only-target-linux
+//@only-on-host
+
+extern "C" {
+    // Symbols with an explicit version.
+    #[link_name = "add_one_int@CODEABI_1.0"]
+    fn add_one_int_v1(x: i32) -> i32;
+    #[link_name = "lib_counter@@CODEABI_1.0"]
+    static mut lib_counter_v1: i32;
+    // A weak symbol.
+    fn weak_add_one(x: i32) -> i32;
+}
+
+fn main() {
+    unsafe {
+        assert_eq!(add_one_int_v1(1), 3);
+        assert_eq!(lib_counter_v1, 5);
+        assert_eq!(weak_add_one(1), 2);
+    }
+}
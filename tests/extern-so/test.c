@@ -1,3 +1,4 @@
+#include <stddef.h>
 #include <stdio.h>
 
 int add_one_int(int x) {
@@ -25,3 +26,91 @@ short add_int16(short x) {
 long add_short_to_long(short x, long y) {
   return x + y;
 }
+
+struct Pair {
+  char a;
+  // 3 bytes of padding here on all supported targets
+  int b;
+};
+
+void fill_pair(struct Pair *p) {
+  p->a = 1;
+  p->b = 2;
+}
+
+int sum_pair(const struct Pair *p) {
+  return p->a + p->b;
+}
+
+int ptr_is_null(const void *p) {
+  return p == NULL;
+}
+
+long double add_one_long_double(long double x) {
+  return x + 1.0L;
+}
+
+unsigned int get_zero() {
+  return 0;
+}
+
+// i686 Linux returns even this struct through a hidden pointer
+struct OneInt {
+  int x;
+};
+
+struct OneInt make_one_int(int a, int b) {
+  struct OneInt s = { a - b };
+  return s;
+}
+
+// returned in two integer registers on x86-64
+struct SmallInts {
+  int a;
+  int b;
+  int c;
+};
+
+struct SmallInts make_small_ints(int x) {
+  struct SmallInts s = { x, x + 1, x + 2 };
+  return s;
+}
+
+// returned in an integer and an SSE register on x86-64
+struct Mixed {
+  long l;
+  double d;
+};
+
+struct Mixed make_mixed(long l) {
+  struct Mixed m = { l, l * 0.5 };
+  return m;
+}
+
+// returned in memory, via a hidden pointer argument
+struct Large {
+  long a;
+  long b;
+  long c;
+  char d;
+};
+
+struct Large make_large(long x) {
+  struct Large s = { x, x * 2, x * 3, 'L' };
+  return s;
+}
+
+void never_return() {
+  for (;;) {}
+}
+
+const int lib_answer = 42;
+int lib_counter = 5;
+
+void bump_lib_counter() {
+  lib_counter += 1;
+}
+
+__attribute__((weak)) int weak_add_one(int x) {
+  return x + 1;
+}
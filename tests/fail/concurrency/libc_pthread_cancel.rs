@@ -0,0 +1,9 @@
+//@ignore-target-windows: No libc on Windows
+
+// Miri does not support thread cancellation.
+
+fn main() {
+    unsafe {
+        libc::pthread_cancel(libc::pthread_self()); //~ ERROR: unsupported operation: Miri does not support thread cancellation
+    }
+}
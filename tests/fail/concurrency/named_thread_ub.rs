@@ -0,0 +1,15 @@
+// Errors on other threads than the main thread say which thread they happened on.
+
+use std::thread;
+
+#[allow(deref_nullptr)]
+fn main() {
+    let handle = thread::Builder::new()
+        .name("worker".into())
+        .spawn(|| {
+            let x: i32 = unsafe { *std::ptr::null() }; //~ ERROR: null pointer is a dangling pointer
+            x
+        })
+        .unwrap();
+    handle.join().unwrap();
+}
@@ -0,0 +1,35 @@
+// We want to control preemption here.
+//@compile-flags: -Zmiri-preemption-rate=0 -Zmiri-data-race-history=2
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+
+#[derive(Copy, Clone)]
+struct EvilSend<T>(pub T);
+
+unsafe impl<T> Send for EvilSend<T> {}
+unsafe impl<T> Sync for EvilSend<T> {}
+
+pub fn main() {
+    let a: &'static AtomicU32 = Box::leak(Box::new(AtomicU32::new(0)));
+    let c = EvilSend(a as *const AtomicU32 as *mut u32);
+
+    let j1 = thread::Builder::new()
+        .name("first".into())
+        .spawn(move || {
+            a.store(1, Ordering::Relaxed);
+            a.fetch_add(1, Ordering::Relaxed);
+            a.store(3, Ordering::Release);
+        })
+        .unwrap();
+
+    let j2 = thread::Builder::new()
+        .name("second".into())
+        .spawn(move || unsafe {
+            *c.0 = 64; //~ ERROR: Data race detected between Write on thread `second` and Atomic Store on thread `first`
+        })
+        .unwrap();
+
+    j1.join().unwrap();
+    j2.join().unwrap();
+}
@@ -0,0 +1,30 @@
+//@only-target-linux: `FD_SET` is a function in the Linux part of libc
+//@compile-flags: -Zmiri-preemption-rate=0 -Zmiri-data-race-backtraces -Zmiri-backtrace=full
+
+// The other access of the race happens in libc, and the diagnostic shows the source of libc.
+
+#![feature(start)]
+
+use std::mem;
+use std::ptr;
+
+extern "C" fn thread_start(set: *mut libc::c_void) -> *mut libc::c_void {
+    unsafe { libc::FD_SET(1, set.cast()) };
+    ptr::null_mut()
+}
+
+#[start]
+fn start(_: isize, _: *const *const u8) -> isize {
+    unsafe {
+        let mut set: libc::fd_set = mem::zeroed();
+        let attr: libc::pthread_attr_t = mem::zeroed();
+        let mut thread: libc::pthread_t = mem::zeroed();
+        let arg = ptr::addr_of_mut!(set).cast();
+        assert_eq!(libc::pthread_create(&mut thread, &attr, thread_start, arg), 0);
+        // Let the thread run without synchronizing with it.
+        libc::sched_yield();
+        let _val = *ptr::addr_of!(set).cast::<libc::c_ulong>(); //~ ERROR: Data race detected between Read on thread `main` and Write on thread `<unnamed>`
+        assert_eq!(libc::pthread_join(thread, ptr::null_mut()), 0);
+    }
+    0
+}
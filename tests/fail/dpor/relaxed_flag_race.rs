@@ -0,0 +1,44 @@
+//@ignore-target-windows: No libc on Windows
+// Without weak memory emulation, the relaxed load sees the flag as soon as it is stored, so
+// whether the race happens only depends on the order in which the threads run.
+//@compile-flags: -Zmiri-dpor -Zmiri-disable-weak-memory-emulation
+
+#![feature(start)]
+
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static READY: AtomicBool = AtomicBool::new(false);
+static mut DATA: u32 = 0;
+
+extern "C" fn reader(_arg: *mut libc::c_void) -> *mut libc::c_void {
+    // The first execution runs the reader first, so the race is only found
+    // by the second one, which runs the writer before it.
+    if READY.load(Ordering::Relaxed) {
+        let _val = unsafe { DATA }; //~ ERROR: Data race detected between Read on thread `<unnamed>` and Write on thread `<unnamed>`
+    }
+    ptr::null_mut()
+}
+
+extern "C" fn writer(_arg: *mut libc::c_void) -> *mut libc::c_void {
+    unsafe { DATA = 1 };
+    READY.store(true, Ordering::Relaxed);
+    ptr::null_mut()
+}
+
+// Use `start` and pthreads directly so that the standard library does not add any atomic
+// operations, which would make the schedules much longer.
+#[start]
+fn start(_: isize, _: *const *const u8) -> isize {
+    unsafe {
+        let mut reader_thread: libc::pthread_t = mem::zeroed();
+        let mut writer_thread: libc::pthread_t = mem::zeroed();
+        let attr: libc::pthread_attr_t = mem::zeroed();
+        assert_eq!(libc::pthread_create(&mut reader_thread, &attr, reader, ptr::null_mut()), 0);
+        assert_eq!(libc::pthread_create(&mut writer_thread, &attr, writer, ptr::null_mut()), 0);
+        assert_eq!(libc::pthread_join(reader_thread, ptr::null_mut()), 0);
+        assert_eq!(libc::pthread_join(writer_thread, ptr::null_mut()), 0);
+    }
+    0
+}
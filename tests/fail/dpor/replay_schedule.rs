@@ -0,0 +1,41 @@
+//@ignore-target-windows: No libc on Windows
+// Replay the schedule that `relaxed_flag_race.rs` reports: the writer runs before the reader.
+//@compile-flags: -Zmiri-schedule=2,2,1,1 -Zmiri-disable-weak-memory-emulation
+
+#![feature(start)]
+
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static READY: AtomicBool = AtomicBool::new(false);
+static mut DATA: u32 = 0;
+
+extern "C" fn reader(_arg: *mut libc::c_void) -> *mut libc::c_void {
+    if READY.load(Ordering::Relaxed) {
+        let _val = unsafe { DATA }; //~ ERROR: Data race detected between Read on thread `<unnamed>` and Write on thread `<unnamed>`
+    }
+    ptr::null_mut()
+}
+
+extern "C" fn writer(_arg: *mut libc::c_void) -> *mut libc::c_void {
+    unsafe { DATA = 1 };
+    READY.store(true, Ordering::Relaxed);
+    ptr::null_mut()
+}
+
+// Use `start` and pthreads directly so that the standard library does not add any atomic
+// operations, which would make the schedules much longer.
+#[start]
+fn start(_: isize, _: *const *const u8) -> isize {
+    unsafe {
+        let mut reader_thread: libc::pthread_t = mem::zeroed();
+        let mut writer_thread: libc::pthread_t = mem::zeroed();
+        let attr: libc::pthread_attr_t = mem::zeroed();
+        assert_eq!(libc::pthread_create(&mut reader_thread, &attr, reader, ptr::null_mut()), 0);
+        assert_eq!(libc::pthread_create(&mut writer_thread, &attr, writer, ptr::null_mut()), 0);
+        assert_eq!(libc::pthread_join(reader_thread, ptr::null_mut()), 0);
+        assert_eq!(libc::pthread_join(writer_thread, ptr::null_mut()), 0);
+    }
+    0
+}
@@ -0,0 +1,11 @@
+//@compile-flags: -Zmiri-extern-stub-missing
+
+extern "C" {
+    fn missing_bool() -> bool;
+}
+
+fn main() {
+    unsafe {
+        missing_bool(); //~ ERROR: unsupported operation: can't stub out foreign function `missing_bool` returning `bool`
+    }
+}
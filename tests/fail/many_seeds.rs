@@ -0,0 +1,9 @@
+// The range is hexadecimal like `-Zmiri-seed`, so this only tries seed 0xff.
+//@compile-flags: -Zmiri-many-seeds=ff..100
+#![feature(core_intrinsics)]
+
+fn main() {
+    unsafe {
+        core::intrinsics::breakpoint() //~ ERROR: Trace/breakpoint trap
+    };
+}
@@ -0,0 +1,22 @@
+//@ignore-target-windows: No libc on Windows
+
+// Reading a guard page that was protected with `mprotect` would raise `SIGSEGV`.
+
+use std::ptr;
+
+fn main() {
+    unsafe {
+        let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            2 * page_size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_eq!(libc::mprotect(ptr, page_size, libc::PROT_NONE), 0);
+        let guard = ptr.cast::<u8>();
+        let _val = *guard; //~ ERROR: which is protected against it by `mprotect`
+    }
+}
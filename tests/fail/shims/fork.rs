@@ -0,0 +1,7 @@
+//@ignore-target-windows: No libc on Windows
+
+fn main() {
+    unsafe {
+        libc::fork(); //~ ERROR: Miri interprets a single process
+    }
+}
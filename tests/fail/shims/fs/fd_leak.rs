@@ -0,0 +1,14 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-disable-isolation -Zmiri-check-fd-leaks
+//@error-pattern: the evaluated program leaked file descriptors
+
+fn main() {
+    let mut fds = [0; 2];
+    unsafe {
+        assert_eq!(libc::pipe(fds.as_mut_ptr()), 0);
+        // Closed file descriptors are not reported.
+        assert_eq!(libc::close(fds[0]), 0);
+        let dir = libc::opendir(b".\0".as_ptr().cast());
+        assert!(!dir.is_null());
+    }
+}
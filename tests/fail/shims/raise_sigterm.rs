@@ -0,0 +1,8 @@
+//! Without a handler, `SIGTERM` terminates the program.
+//@ignore-target-windows: No libc on Windows
+
+fn main() {
+    unsafe {
+        libc::raise(libc::SIGTERM); //~ ERROR: terminated by signal 15
+    }
+}
@@ -0,0 +1,27 @@
+//@ignore-target-windows: No libc on Windows
+// We are making scheduler assumptions here.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::cell::UnsafeCell;
+use std::thread;
+
+struct Mutex(UnsafeCell<libc::pthread_mutex_t>);
+
+unsafe impl Sync for Mutex {}
+
+static A: Mutex = Mutex(UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER));
+static B: Mutex = Mutex(UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER));
+
+fn main() {
+    unsafe {
+        libc::pthread_mutex_lock(A.0.get());
+        let t = thread::spawn(|| {
+            libc::pthread_mutex_lock(B.0.get());
+            libc::pthread_mutex_lock(A.0.get());
+        });
+        // Let the other thread take `B` and block on `A`.
+        thread::yield_now();
+        libc::pthread_mutex_lock(B.0.get()); //~ ERROR: deadlock
+        t.join().unwrap();
+    }
+}
@@ -0,0 +1,13 @@
+//@compile-flags: -Zmiri-stacked-borrows-history-limit=1
+
+fn main() {
+    let mut x = 0u32;
+    let xraw = &mut x as *mut u32;
+    let xref = unsafe { &mut *xraw };
+    // These retags push the creation of `xref` out of the history.
+    for _ in 0..4 {
+        let _r = &*xref;
+    }
+    unsafe { *xraw = 1 };
+    *xref = 2; //~ ERROR: /write access .* tag does not exist in the borrow stack/
+}
@@ -0,0 +1,14 @@
+//@ignore-target-windows: The backslashes in file paths are escaped in JSON
+//@compile-flags: -Zmiri-stacked-borrows-json
+
+fn main() {
+    let mut x = 15;
+    let xraw = &mut x as *mut _;
+    let xref = unsafe { &mut *xraw };
+    callee(xraw);
+    let _val = *xref; //~ ERROR: /read access .* tag does not exist in the borrow stack/
+}
+
+fn callee(xraw: *mut i32) {
+    let _val = unsafe { *xraw };
+}
@@ -0,0 +1,10 @@
+//@ignore-32bit
+extern "Rust" {
+    fn miri_print_borrow_state(ptr: *const u8, size: usize);
+}
+
+fn main() {
+    let x = 0u32;
+    let xraw = &x as *const u32 as *const u8;
+    unsafe { miri_print_borrow_state(xraw.add(1), usize::MAX) }; //~ ERROR: were requested
+}
@@ -0,0 +1,32 @@
+//@ignore-target-windows: No libc on Windows
+// Three threads incrementing a counter have more than two schedules to explore.
+//@compile-flags: -Zmiri-dpor -Zmiri-dpor-max-executions=2
+
+#![feature(start)]
+
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn increment(_arg: *mut libc::c_void) -> *mut libc::c_void {
+    COUNTER.fetch_add(1, Ordering::Relaxed);
+    ptr::null_mut()
+}
+
+#[start]
+fn start(_: isize, _: *const *const u8) -> isize {
+    unsafe {
+        let attr: libc::pthread_attr_t = mem::zeroed();
+        let mut threads: [libc::pthread_t; 3] = mem::zeroed();
+        for thread in &mut threads {
+            assert_eq!(libc::pthread_create(thread, &attr, increment, ptr::null_mut()), 0);
+        }
+        for thread in threads {
+            assert_eq!(libc::pthread_join(thread, ptr::null_mut()), 0);
+        }
+    }
+    assert_eq!(COUNTER.load(Ordering::Relaxed), 3);
+    0
+}
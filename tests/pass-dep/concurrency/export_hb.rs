@@ -0,0 +1,30 @@
+//@only-target-linux: The thread name is set with the Linux version of `pthread_setname_np`
+//@compile-flags: -Zmiri-export-hb=/dev/stderr
+
+#![feature(start)]
+
+use std::mem;
+use std::ptr;
+
+static mut DATA: u32 = 0;
+
+extern "C" fn thread_start(_arg: *mut libc::c_void) -> *mut libc::c_void {
+    // The name is escaped in the graph.
+    let name = b"say \"hi\"\0";
+    assert_eq!(unsafe { libc::pthread_setname_np(libc::pthread_self(), name.as_ptr().cast()) }, 0);
+    unsafe { DATA = 1 };
+    ptr::null_mut()
+}
+
+#[start]
+fn start(_: isize, _: *const *const u8) -> isize {
+    unsafe {
+        let attr: libc::pthread_attr_t = mem::zeroed();
+        let mut thread: libc::pthread_t = mem::zeroed();
+        assert_eq!(libc::pthread_create(&mut thread, &attr, thread_start, ptr::null_mut()), 0);
+        assert_eq!(libc::pthread_join(thread, ptr::null_mut()), 0);
+        // The join makes the write of the thread visible.
+        assert_eq!(DATA, 1);
+    }
+    0
+}
@@ -0,0 +1,128 @@
+//@ignore-target-windows: File handling is not implemented yet
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::fs::{remove_file, File};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn tmp() -> PathBuf {
+    std::env::var("MIRI_TEMP")
+        .map(|tmp| {
+            // MIRI_TEMP is set outside of our emulated
+            // program, so it may have path separators that don't
+            // correspond to our target platform. We normalize them here
+            // before constructing a `PathBuf`
+            PathBuf::from(tmp.replace("\\", "/"))
+        })
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+fn flock(fd: i32, operation: i32) -> i32 {
+    unsafe { libc::flock(fd, operation) }
+}
+
+fn test_flock() {
+    let path = tmp().join("miri_test_libc_flock.txt");
+    remove_file(&path).ok();
+    let a = File::create(&path).unwrap();
+    let b = File::open(&path).unwrap();
+
+    // Separate opens of the same file contend for the lock, duplicates do not.
+    assert_eq!(flock(a.as_raw_fd(), libc::LOCK_EX), 0);
+    assert_eq!(flock(b.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB), -1);
+    assert_eq!(errno(), libc::EWOULDBLOCK);
+    let dup = unsafe { libc::dup(a.as_raw_fd()) };
+    assert_eq!(flock(dup, libc::LOCK_EX | libc::LOCK_NB), 0);
+
+    // Downgrading the lock lets others take a shared lock.
+    assert_eq!(flock(a.as_raw_fd(), libc::LOCK_SH), 0);
+    assert_eq!(flock(b.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB), 0);
+    assert_eq!(flock(a.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB), -1);
+    assert_eq!(errno(), libc::EWOULDBLOCK);
+    assert_eq!(flock(b.as_raw_fd(), libc::LOCK_UN), 0);
+    assert_eq!(flock(a.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB), 0);
+
+    // The lock is released once all file descriptors of the open file description are closed.
+    drop(a);
+    assert_eq!(flock(b.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB), -1);
+    assert_eq!(unsafe { libc::close(dup) }, 0);
+    assert_eq!(flock(b.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB), 0);
+
+    assert_eq!(flock(b.as_raw_fd(), 0), -1);
+    assert_eq!(errno(), libc::EINVAL);
+    assert_eq!(flock(-1, libc::LOCK_EX), -1);
+    assert_eq!(errno(), libc::EBADF);
+
+    drop(b);
+    remove_file(&path).unwrap();
+}
+
+fn test_flock_blocking() {
+    let path = tmp().join("miri_test_libc_flock_blocking.txt");
+    remove_file(&path).ok();
+    let a = File::create(&path).unwrap();
+    assert_eq!(flock(a.as_raw_fd(), libc::LOCK_EX), 0);
+
+    let unlocked = Arc::new(AtomicBool::new(false));
+    let waiter = thread::spawn({
+        let path = path.clone();
+        let unlocked = Arc::clone(&unlocked);
+        move || {
+            let b = File::open(&path).unwrap();
+            // This blocks until the main thread releases its lock.
+            assert_eq!(flock(b.as_raw_fd(), libc::LOCK_SH), 0);
+            assert!(unlocked.load(Ordering::Relaxed));
+        }
+    });
+
+    thread::yield_now();
+    unlocked.store(true, Ordering::Relaxed);
+    assert_eq!(flock(a.as_raw_fd(), libc::LOCK_UN), 0);
+    waiter.join().unwrap();
+
+    drop(a);
+    remove_file(&path).unwrap();
+}
+
+fn test_fcntl_locks() {
+    let path = tmp().join("miri_test_libc_fcntl_locks.txt");
+    remove_file(&path).ok();
+    File::create(&path).unwrap();
+    let file = File::open(&path).unwrap();
+    let fd = file.as_raw_fd();
+
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_RDLCK as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    assert_eq!(unsafe { libc::fcntl(fd, libc::F_SETLK, &lock) }, 0);
+    assert_eq!(unsafe { libc::fcntl(fd, libc::F_SETLKW, &lock) }, 0);
+
+    // No other process holds a lock on the file.
+    lock.l_type = libc::F_WRLCK as _;
+    assert_eq!(unsafe { libc::fcntl(fd, libc::F_GETLK, &mut lock) }, 0);
+    assert_eq!(lock.l_type, libc::F_UNLCK as _);
+
+    // A write lock needs a writable file.
+    lock.l_type = libc::F_WRLCK as _;
+    assert_eq!(unsafe { libc::fcntl(fd, libc::F_SETLK, &lock) }, -1);
+    assert_eq!(errno(), libc::EBADF);
+
+    lock.l_type = libc::F_UNLCK as _;
+    assert_eq!(unsafe { libc::fcntl(fd, libc::F_SETLK, &lock) }, 0);
+
+    drop(file);
+    remove_file(&path).unwrap();
+}
+
+fn main() {
+    test_flock();
+    test_flock_blocking();
+    test_fcntl_locks();
+}
@@ -0,0 +1,131 @@
+//@ignore-target-windows: No libc on Windows
+
+use std::thread;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn pipe() -> [i32; 2] {
+    let mut fds = [-1, -1];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    fds
+}
+
+fn read(fd: i32, buf: &mut [u8]) -> isize {
+    unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) }
+}
+
+fn write(fd: i32, data: &[u8]) -> isize {
+    unsafe { libc::write(fd, data.as_ptr().cast(), data.len()) }
+}
+
+fn test_eof() {
+    let [reader, writer] = pipe();
+    assert_eq!(write(writer, b"abc"), 3);
+    assert_eq!(unsafe { libc::close(writer) }, 0);
+    // The data is still there after the write end was closed, and then we reach the end of file.
+    let mut buf = [0; 8];
+    assert_eq!(read(reader, &mut buf), 3);
+    assert_eq!(&buf[..3], b"abc");
+    assert_eq!(read(reader, &mut buf), 0);
+    assert_eq!(unsafe { libc::close(reader) }, 0);
+
+    // Writing without a reader fails.
+    let [reader, writer] = pipe();
+    // A duplicate keeps the read end open.
+    let dup = unsafe { libc::fcntl(reader, libc::F_DUPFD, 0) };
+    assert_eq!(unsafe { libc::close(reader) }, 0);
+    assert_eq!(write(writer, b"abc"), 3);
+    assert_eq!(unsafe { libc::close(dup) }, 0);
+    assert_eq!(write(writer, b"abc"), -1);
+    assert_eq!(errno(), libc::EPIPE);
+    assert_eq!(unsafe { libc::close(writer) }, 0);
+}
+
+fn test_blocking() {
+    let [reader, writer] = pipe();
+
+    // The read blocks until the other thread has written something.
+    let writer_thread = thread::spawn(move || {
+        thread::yield_now();
+        assert_eq!(write(writer, b"ping"), 4);
+        // Fill up the pipe. Large writes might only be partially done, but at some point a write
+        // blocks until the main thread reads some data.
+        let data = vec![1; 65536];
+        let mut data = &data[..];
+        while !data.is_empty() {
+            let n = write(writer, data);
+            assert!(n > 0);
+            data = &data[n as usize..];
+        }
+        assert_eq!(write(writer, &[2; 16]), 16);
+        assert_eq!(unsafe { libc::close(writer) }, 0);
+    });
+    let mut buf = [0; 4];
+    assert_eq!(read(reader, &mut buf), 4);
+    assert_eq!(&buf, b"ping");
+
+    let mut total = 0;
+    let mut buf = vec![0; 1000];
+    loop {
+        match read(reader, &mut buf) {
+            0 => break,
+            n => total += n,
+        }
+    }
+    assert_eq!(total, 65536 + 16);
+    writer_thread.join().unwrap();
+    assert_eq!(unsafe { libc::close(reader) }, 0);
+}
+
+/// Writing to a pipe happens-before reading the data, so the reader can access data that was
+/// written before without a data race.
+fn test_handoff() {
+    static mut DATA: u64 = 0;
+
+    let [reader, writer] = pipe();
+    let writer_thread = thread::spawn(move || {
+        unsafe { DATA = 42 };
+        assert_eq!(write(writer, b"x"), 1);
+    });
+    let mut buf = [0; 1];
+    assert_eq!(read(reader, &mut buf), 1);
+    assert_eq!(unsafe { DATA }, 42);
+    writer_thread.join().unwrap();
+    assert_eq!(unsafe { libc::close(reader) }, 0);
+    assert_eq!(unsafe { libc::close(writer) }, 0);
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn test_nonblocking() {
+    let mut fds = [-1, -1];
+    assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) }, 0);
+    let [reader, writer] = fds;
+
+    let mut buf = [0; 8];
+    assert_eq!(read(reader, &mut buf), -1);
+    assert_eq!(errno(), libc::EAGAIN);
+
+    // A full pipe takes as much of a large write as fits.
+    assert_eq!(write(writer, &vec![0; 65000]), 65000);
+    assert_eq!(write(writer, &vec![0; 8192]), 536);
+    assert_eq!(write(writer, &[0]), -1);
+    assert_eq!(errno(), libc::EAGAIN);
+    // Small writes are atomic, so they do not fit into a pipe with less free space.
+    assert_eq!(read(reader, &mut buf), 8);
+    assert_eq!(write(writer, &[0; 16]), -1);
+    assert_eq!(errno(), libc::EAGAIN);
+    assert_eq!(write(writer, &[0; 8]), 8);
+
+    assert_eq!(unsafe { libc::close(reader) }, 0);
+    assert_eq!(unsafe { libc::close(writer) }, 0);
+}
+
+fn main() {
+    test_eof();
+    test_blocking();
+    test_handoff();
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    test_nonblocking();
+}
@@ -0,0 +1,158 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-loopback-network
+
+use std::mem;
+use std::ptr;
+use std::thread;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+/// A `sockaddr_in` with the given address and port.
+fn address(ip: [u8; 4], port: u16) -> libc::sockaddr_in {
+    let mut addr: libc::sockaddr_in = unsafe { mem::zeroed() };
+    addr.sin_family = libc::AF_INET as libc::sa_family_t;
+    addr.sin_port = port.to_be();
+    addr.sin_addr.s_addr = u32::from_ne_bytes(ip);
+    addr
+}
+
+fn socket() -> i32 {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    assert!(fd >= 0);
+    fd
+}
+
+fn bind(fd: i32, port: u16) -> i32 {
+    let addr = address([127, 0, 0, 1], port);
+    let len = mem::size_of_val(&addr) as libc::socklen_t;
+    unsafe { libc::bind(fd, ptr::addr_of!(addr).cast(), len) }
+}
+
+fn connect(fd: i32, port: u16) -> i32 {
+    let addr = address([127, 0, 0, 1], port);
+    let len = mem::size_of_val(&addr) as libc::socklen_t;
+    unsafe { libc::connect(fd, ptr::addr_of!(addr).cast(), len) }
+}
+
+/// The address that `getsockname` or `getpeername` reports for `fd`, as the IP and the port.
+fn name(
+    get: unsafe extern "C" fn(i32, *mut libc::sockaddr, *mut libc::socklen_t) -> i32,
+    fd: i32,
+) -> ([u8; 4], u16) {
+    let mut addr: libc::sockaddr_in = unsafe { mem::zeroed() };
+    let mut len = mem::size_of_val(&addr) as libc::socklen_t;
+    assert_eq!(unsafe { get(fd, ptr::addr_of_mut!(addr).cast(), &mut len) }, 0);
+    assert_eq!(len as usize, mem::size_of_val(&addr));
+    assert_eq!(i32::from(addr.sin_family), libc::AF_INET);
+    (addr.sin_addr.s_addr.to_ne_bytes(), u16::from_be(addr.sin_port))
+}
+
+fn send(fd: i32, data: &[u8]) -> isize {
+    unsafe { libc::send(fd, data.as_ptr().cast(), data.len(), 0) }
+}
+
+fn recv(fd: i32, buf: &mut [u8]) -> isize {
+    unsafe { libc::recv(fd, buf.as_mut_ptr().cast(), buf.len(), 0) }
+}
+
+fn test_libc() {
+    static mut DATA: u64 = 0;
+
+    let server = socket();
+    let one: i32 = 1;
+    let res = unsafe {
+        libc::setsockopt(
+            server,
+            libc::SOL_SOCKET,
+            libc::SO_REUSEADDR,
+            ptr::addr_of!(one).cast(),
+            mem::size_of_val(&one) as libc::socklen_t,
+        )
+    };
+    assert_eq!(res, 0);
+    // Port 0 picks the first ephemeral port.
+    assert_eq!(name(libc::getsockname, server), ([0; 4], 0));
+    assert_eq!(bind(server, 0), 0);
+    assert_eq!(name(libc::getsockname, server), ([127, 0, 0, 1], 49152));
+    let (_, port) = name(libc::getsockname, server);
+
+    // Nothing listens yet.
+    let client = socket();
+    assert_eq!(connect(client, port), -1);
+    assert_eq!(errno(), libc::ECONNREFUSED);
+    assert_eq!(unsafe { libc::listen(server, 128) }, 0);
+    let other = socket();
+    assert_eq!(bind(other, port), -1);
+    assert_eq!(errno(), libc::EADDRINUSE);
+    assert_eq!(unsafe { libc::close(other) }, 0);
+
+    // `accept` blocks until the client connects, and reports the port of the client.
+    let accepter = thread::spawn(move || {
+        let mut addr: libc::sockaddr_in = unsafe { mem::zeroed() };
+        let mut len = mem::size_of_val(&addr) as libc::socklen_t;
+        let fd = unsafe { libc::accept(server, ptr::addr_of_mut!(addr).cast(), &mut len) };
+        assert!(fd >= 0);
+        assert_eq!(addr.sin_addr.s_addr.to_ne_bytes(), [127, 0, 0, 1]);
+        assert_eq!(u16::from_be(addr.sin_port), 49153);
+        assert_eq!(name(libc::getsockname, fd), ([127, 0, 0, 1], 49152));
+        let mut buf = [0; 8];
+        assert_eq!(recv(fd, &mut buf), 5);
+        assert_eq!(&buf[..5], b"hello");
+        // Sending data happens-before receiving it, so this write does not race with the read
+        // after `recv` below.
+        unsafe { DATA = 42 };
+        assert_eq!(send(fd, b"world"), 5);
+        assert_eq!(unsafe { libc::close(fd) }, 0);
+    });
+    thread::yield_now();
+    assert_eq!(connect(client, port), 0);
+    assert_eq!(name(libc::getsockname, client), ([127, 0, 0, 1], 49153));
+    assert_eq!(name(libc::getpeername, client), ([127, 0, 0, 1], port));
+    assert_eq!(send(client, b"hello"), 5);
+    let mut buf = [0; 8];
+    assert_eq!(recv(client, &mut buf), 5);
+    assert_eq!(&buf[..5], b"world");
+    assert_eq!(unsafe { DATA }, 42);
+    accepter.join().unwrap();
+    assert_eq!(recv(client, &mut buf), 0);
+
+    assert_eq!(unsafe { libc::close(client) }, 0);
+    assert_eq!(unsafe { libc::close(server) }, 0);
+    // Now the port can be used again.
+    let server = socket();
+    assert_eq!(bind(server, port), 0);
+    assert_eq!(unsafe { libc::close(server) }, 0);
+}
+
+#[cfg(target_os = "linux")]
+fn test_std() {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut stream, peer) = listener.accept().unwrap();
+        assert!(peer.ip().is_loopback());
+        let mut buf = String::new();
+        stream.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "ping");
+        stream.write_all(b"pong").unwrap();
+    });
+    let mut stream = TcpStream::connect(addr).unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), addr);
+    stream.write_all(b"ping").unwrap();
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+    let mut buf = String::new();
+    stream.read_to_string(&mut buf).unwrap();
+    assert_eq!(buf, "pong");
+    server.join().unwrap();
+}
+
+fn main() {
+    test_libc();
+    #[cfg(target_os = "linux")]
+    test_std();
+}
@@ -0,0 +1,165 @@
+//@ignore-target-windows: No libc on Windows
+
+use std::mem;
+use std::ptr;
+use std::thread;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+/// A `sockaddr_un` with the given `sun_path`, and the length to pass along with it.
+fn address(path: &[u8]) -> (libc::sockaddr_un, libc::socklen_t) {
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    for (dst, &src) in addr.sun_path.iter_mut().zip(path) {
+        *dst = src as libc::c_char;
+    }
+    let path_offset = ptr::addr_of!(addr.sun_path) as usize - ptr::addr_of!(addr) as usize;
+    (addr, (path_offset + path.len()) as libc::socklen_t)
+}
+
+fn socket() -> i32 {
+    let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0) };
+    assert!(fd >= 0);
+    fd
+}
+
+fn bind(fd: i32, path: &[u8]) -> i32 {
+    let (addr, len) = address(path);
+    unsafe { libc::bind(fd, ptr::addr_of!(addr).cast(), len) }
+}
+
+fn connect(fd: i32, path: &[u8]) -> i32 {
+    let (addr, len) = address(path);
+    unsafe { libc::connect(fd, ptr::addr_of!(addr).cast(), len) }
+}
+
+fn send(fd: i32, data: &[u8]) -> isize {
+    unsafe { libc::send(fd, data.as_ptr().cast(), data.len(), 0) }
+}
+
+fn recv(fd: i32, buf: &mut [u8]) -> isize {
+    unsafe { libc::recv(fd, buf.as_mut_ptr().cast(), buf.len(), 0) }
+}
+
+fn test_socketpair() {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    let [a, b] = fds;
+
+    // The read blocks until the other thread has written something.
+    let writer = thread::spawn(move || {
+        thread::yield_now();
+        assert_eq!(send(b, b"ping"), 4);
+    });
+    let mut buf = [0; 8];
+    assert_eq!(recv(a, &mut buf), 4);
+    assert_eq!(&buf[..4], b"ping");
+    writer.join().unwrap();
+
+    // Once the other end is closed, reads return end of file and writes fail.
+    assert_eq!(unsafe { libc::close(b) }, 0);
+    assert_eq!(recv(a, &mut buf), 0);
+    assert_eq!(send(a, b"pong"), -1);
+    assert_eq!(errno(), libc::EPIPE);
+    assert_eq!(unsafe { libc::close(a) }, 0);
+}
+
+/// Sending data happens-before receiving it, so the receiver can access data that was written
+/// before without a data race.
+fn test_socketpair_handoff() {
+    static mut DATA: u64 = 0;
+
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    let [a, b] = fds;
+
+    let writer = thread::spawn(move || {
+        unsafe { DATA = 42 };
+        assert_eq!(send(b, b"x"), 1);
+    });
+    let mut buf = [0; 1];
+    assert_eq!(recv(a, &mut buf), 1);
+    assert_eq!(unsafe { DATA }, 42);
+    writer.join().unwrap();
+    assert_eq!(unsafe { libc::close(a) }, 0);
+    assert_eq!(unsafe { libc::close(b) }, 0);
+}
+
+fn test_connect(path: &[u8]) {
+    static mut DATA: u64 = 0;
+
+    let server = socket();
+    assert_eq!(bind(server, path), 0);
+    // Connecting before `listen` is refused.
+    let client = socket();
+    assert_eq!(connect(client, path), -1);
+    assert_eq!(errno(), libc::ECONNREFUSED);
+    assert_eq!(unsafe { libc::listen(server, 1) }, 0);
+    // The address is in use until the server is closed.
+    let other = socket();
+    assert_eq!(bind(other, path), -1);
+    assert_eq!(errno(), libc::EADDRINUSE);
+    assert_eq!(unsafe { libc::close(other) }, 0);
+
+    // `accept` blocks until the client connects.
+    let accepter = thread::spawn(move || {
+        let fd = unsafe { libc::accept(server, ptr::null_mut(), ptr::null_mut()) };
+        assert!(fd >= 0);
+        let mut buf = [0; 8];
+        assert_eq!(recv(fd, &mut buf), 5);
+        assert_eq!(&buf[..5], b"hello");
+        // Sending data happens-before receiving it, so this write does not race with the read
+        // after `recv` below.
+        unsafe { DATA = 42 };
+        assert_eq!(send(fd, b"world"), 5);
+        assert_eq!(unsafe { libc::close(fd) }, 0);
+    });
+    thread::yield_now();
+    assert_eq!(connect(client, path), 0);
+    assert_eq!(connect(client, path), -1);
+    assert_eq!(errno(), libc::EISCONN);
+    assert_eq!(send(client, b"hello"), 5);
+    let mut buf = [0; 8];
+    assert_eq!(recv(client, &mut buf), 5);
+    assert_eq!(&buf[..5], b"world");
+    assert_eq!(unsafe { DATA }, 42);
+    accepter.join().unwrap();
+    assert_eq!(recv(client, &mut buf), 0);
+
+    assert_eq!(unsafe { libc::close(client) }, 0);
+    assert_eq!(unsafe { libc::close(server) }, 0);
+    // Now the address can be used again.
+    let server = socket();
+    assert_eq!(bind(server, path), 0);
+    assert_eq!(unsafe { libc::close(server) }, 0);
+}
+
+fn test_errors() {
+    // Nothing is bound to this address.
+    let fd = socket();
+    assert_eq!(connect(fd, b"/miri/no-such-socket\0"), -1);
+    assert_eq!(errno(), libc::ENOENT);
+    // The socket is not connected.
+    let mut buf = [0; 8];
+    assert_eq!(recv(fd, &mut buf), -1);
+    assert_eq!(errno(), libc::ENOTCONN);
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+
+    // Only sockets can be bound.
+    assert_eq!(bind(1, b"/miri/stdout\0"), -1);
+    assert_eq!(errno(), libc::ENOTSOCK);
+}
+
+fn main() {
+    test_socketpair();
+    test_socketpair_handoff();
+    // The addresses only exist inside the interpreter, so this does not need a writable directory.
+    test_connect(b"/miri/socket\0");
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    test_connect(b"\0miri-abstract");
+    test_errors();
+}
@@ -0,0 +1,100 @@
+//@only-target-linux: macOS does not have the timed rwlock functions
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::thread;
+use std::time::{Duration, Instant};
+
+extern "C" {
+    fn pthread_rwlock_timedrdlock(
+        rwlock: *mut libc::pthread_rwlock_t,
+        abstime: *const libc::timespec,
+    ) -> libc::c_int;
+    fn pthread_rwlock_timedwrlock(
+        rwlock: *mut libc::pthread_rwlock_t,
+        abstime: *const libc::timespec,
+    ) -> libc::c_int;
+}
+
+struct RwLock(UnsafeCell<libc::pthread_rwlock_t>);
+
+unsafe impl Sync for RwLock {}
+
+/// The `CLOCK_REALTIME` time `secs` seconds from now.
+fn timeout_in(secs: i64) -> libc::timespec {
+    let mut now: MaybeUninit<libc::timespec> = MaybeUninit::uninit();
+    assert_eq!(unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, now.as_mut_ptr()) }, 0);
+    let now = unsafe { now.assume_init() };
+    libc::timespec { tv_sec: now.tv_sec + secs, tv_nsec: now.tv_nsec }
+}
+
+fn test_timeout() {
+    let rw = UnsafeCell::new(libc::PTHREAD_RWLOCK_INITIALIZER);
+    unsafe {
+        assert_eq!(libc::pthread_rwlock_wrlock(rw.get()), 0);
+        let timeout = timeout_in(1);
+        let start = Instant::now();
+        assert_eq!(pthread_rwlock_timedrdlock(rw.get(), &timeout), libc::ETIMEDOUT);
+        assert_eq!(pthread_rwlock_timedwrlock(rw.get(), &timeout_in(1)), libc::ETIMEDOUT);
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(2), "timed out too early: {elapsed:?}");
+        assert_eq!(libc::pthread_rwlock_unlock(rw.get()), 0);
+
+        // Read locks can be shared without waiting.
+        assert_eq!(pthread_rwlock_timedrdlock(rw.get(), &timeout_in(1)), 0);
+        assert_eq!(pthread_rwlock_timedrdlock(rw.get(), &timeout_in(1)), 0);
+        assert_eq!(pthread_rwlock_timedwrlock(rw.get(), &timeout_in(1)), libc::ETIMEDOUT);
+        assert_eq!(libc::pthread_rwlock_unlock(rw.get()), 0);
+        assert_eq!(libc::pthread_rwlock_unlock(rw.get()), 0);
+        assert_eq!(libc::pthread_rwlock_destroy(rw.get()), 0);
+    }
+}
+
+fn test_invalid_timeout() {
+    let rw = UnsafeCell::new(libc::PTHREAD_RWLOCK_INITIALIZER);
+    let invalid = libc::timespec { tv_sec: 0, tv_nsec: 1_000_000_000 };
+    unsafe {
+        // The timeout is not checked if the lock is available.
+        assert_eq!(pthread_rwlock_timedwrlock(rw.get(), &invalid), 0);
+        assert_eq!(pthread_rwlock_timedrdlock(rw.get(), &invalid), libc::EINVAL);
+        assert_eq!(libc::pthread_rwlock_unlock(rw.get()), 0);
+    }
+}
+
+fn test_acquired_before_timeout() {
+    static RW: RwLock = RwLock(UnsafeCell::new(libc::PTHREAD_RWLOCK_INITIALIZER));
+    unsafe {
+        assert_eq!(libc::pthread_rwlock_rdlock(RW.0.get()), 0);
+        let writer = thread::spawn(|| {
+            assert_eq!(pthread_rwlock_timedwrlock(RW.0.get(), &timeout_in(10)), 0);
+            assert_eq!(libc::pthread_rwlock_unlock(RW.0.get()), 0);
+        });
+        thread::yield_now();
+        assert_eq!(libc::pthread_rwlock_unlock(RW.0.get()), 0);
+        writer.join().unwrap();
+    }
+}
+
+/// A timeout too far in the future to be represented never expires.
+fn test_far_future_timeout() {
+    static RW: RwLock = RwLock(UnsafeCell::new(libc::PTHREAD_RWLOCK_INITIALIZER));
+    unsafe {
+        assert_eq!(libc::pthread_rwlock_wrlock(RW.0.get()), 0);
+        let reader = thread::spawn(|| {
+            let timeout = libc::timespec { tv_sec: libc::time_t::MAX, tv_nsec: 0 };
+            assert_eq!(pthread_rwlock_timedrdlock(RW.0.get(), &timeout), 0);
+            assert_eq!(libc::pthread_rwlock_unlock(RW.0.get()), 0);
+        });
+        thread::yield_now();
+        assert_eq!(libc::pthread_rwlock_unlock(RW.0.get()), 0);
+        reader.join().unwrap();
+    }
+}
+
+fn main() {
+    test_timeout();
+    test_invalid_timeout();
+    test_acquired_before_timeout();
+    test_far_future_timeout();
+}
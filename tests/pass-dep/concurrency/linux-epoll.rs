@@ -0,0 +1,206 @@
+//@only-target-linux
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn write_event(fd: i32, value: u64) -> isize {
+    unsafe { libc::write(fd, &value as *const u64 as *const libc::c_void, 8) }
+}
+
+fn read_event(fd: i32) -> Option<u64> {
+    let mut value = 0u64;
+    let res = unsafe { libc::read(fd, &mut value as *mut u64 as *mut libc::c_void, 8) };
+    if res == 8 { Some(value) } else { None }
+}
+
+fn wait(epfd: i32, timeout: i32) -> Vec<(u32, u64)> {
+    let mut events = [libc::epoll_event { events: 0, u64: 0 }; 8];
+    let n = unsafe { libc::epoll_wait(epfd, events.as_mut_ptr(), 8, timeout) };
+    assert!(n >= 0);
+    events[..n as usize].iter().map(|event| (event.events, event.u64)).collect()
+}
+
+fn ctl(epfd: i32, op: i32, fd: i32, events: i32, data: u64) -> i32 {
+    let mut event = libc::epoll_event { events: events as u32, u64: data };
+    unsafe { libc::epoll_ctl(epfd, op, fd, &mut event) }
+}
+
+fn test_eventfd() {
+    let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+    assert!(fd >= 0);
+
+    // Reading an empty eventfd would block.
+    assert_eq!(read_event(fd), None);
+    assert_eq!(errno(), libc::EAGAIN);
+
+    assert_eq!(write_event(fd, 1), 8);
+    assert_eq!(write_event(fd, 2), 8);
+    assert_eq!(read_event(fd), Some(3));
+    assert_eq!(read_event(fd), None);
+
+    // The counter cannot overflow.
+    assert_eq!(write_event(fd, u64::MAX - 1), 8);
+    assert_eq!(write_event(fd, 1), -1);
+    assert_eq!(errno(), libc::EAGAIN);
+    assert_eq!(write_event(fd, u64::MAX), -1);
+    assert_eq!(errno(), libc::EINVAL);
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+
+    // In semaphore mode, every read decrements the counter by one.
+    let fd = unsafe { libc::eventfd(2, libc::EFD_NONBLOCK | libc::EFD_SEMAPHORE) };
+    assert_eq!(read_event(fd), Some(1));
+    assert_eq!(read_event(fd), Some(1));
+    assert_eq!(read_event(fd), None);
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+}
+
+/// Writing to an eventfd happens-before reading from it, so the reader can access data that was
+/// written before without a data race.
+fn test_eventfd_handoff() {
+    static mut DATA: u64 = 0;
+
+    let fd = unsafe { libc::eventfd(0, 0) };
+    assert!(fd >= 0);
+    let writer = thread::spawn(move || {
+        unsafe { DATA = 42 };
+        assert_eq!(write_event(fd, 1), 8);
+    });
+    assert_eq!(read_event(fd), Some(1));
+    assert_eq!(unsafe { DATA }, 42);
+    writer.join().unwrap();
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+}
+
+fn test_epoll_level_and_edge_triggered() {
+    let epfd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+    assert!(epfd >= 0);
+    let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK) };
+
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_ADD, fd, libc::EPOLLIN, 7), 0);
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_ADD, fd, libc::EPOLLIN, 7), -1);
+    assert_eq!(errno(), libc::EEXIST);
+    assert!(wait(epfd, 0).is_empty());
+
+    // Level-triggered interests are reported as long as the eventfd is readable.
+    assert_eq!(write_event(fd, 1), 8);
+    assert_eq!(wait(epfd, 0), [(libc::EPOLLIN as u32, 7)]);
+    assert_eq!(wait(epfd, 0), [(libc::EPOLLIN as u32, 7)]);
+    assert_eq!(read_event(fd), Some(1));
+    assert!(wait(epfd, 0).is_empty());
+
+    // Edge-triggered interests are only reported again after a change.
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_MOD, fd, libc::EPOLLIN | libc::EPOLLET, 8), 0);
+    assert_eq!(write_event(fd, 1), 8);
+    assert_eq!(wait(epfd, 0), [(libc::EPOLLIN as u32, 8)]);
+    assert!(wait(epfd, 0).is_empty());
+    assert_eq!(write_event(fd, 1), 8);
+    assert_eq!(wait(epfd, 0), [(libc::EPOLLIN as u32, 8)]);
+
+    // One-shot interests are only reported once.
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_MOD, fd, libc::EPOLLOUT | libc::EPOLLONESHOT, 9), 0);
+    assert_eq!(wait(epfd, 0), [(libc::EPOLLOUT as u32, 9)]);
+    assert!(wait(epfd, 0).is_empty());
+
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_DEL, fd, 0, 0), 0);
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_DEL, fd, 0, 0), -1);
+    assert_eq!(errno(), libc::ENOENT);
+
+    // Files that Miri does not model cannot be watched.
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_ADD, libc::STDOUT_FILENO, libc::EPOLLOUT, 0), -1);
+    assert_eq!(errno(), libc::EPERM);
+
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+    assert_eq!(unsafe { libc::close(epfd) }, 0);
+}
+
+/// Closing a file descriptor removes it from the epoll instance, so a new file descriptor that
+/// reuses the number is not watched.
+fn test_epoll_closed_fd() {
+    let epfd = unsafe { libc::epoll_create1(0) };
+    let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK) };
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_ADD, fd, libc::EPOLLIN, 1), 0);
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+
+    let new_fd = unsafe { libc::eventfd(1, libc::EFD_NONBLOCK) };
+    assert_eq!(new_fd, fd);
+    assert!(wait(epfd, 0).is_empty());
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_ADD, new_fd, libc::EPOLLIN, 2), 0);
+    assert_eq!(wait(epfd, 0), [(libc::EPOLLIN as u32, 2)]);
+
+    assert_eq!(unsafe { libc::close(new_fd) }, 0);
+    assert_eq!(unsafe { libc::close(epfd) }, 0);
+}
+
+fn test_epoll_wait_blocks() {
+    let epfd = unsafe { libc::epoll_create1(0) };
+    let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK) };
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_ADD, fd, libc::EPOLLIN | libc::EPOLLET, 1), 0);
+
+    let t = thread::spawn(move || {
+        thread::yield_now();
+        assert_eq!(write_event(fd, 1), 8);
+    });
+    // Wait without a timeout until the other thread writes to the eventfd.
+    assert_eq!(wait(epfd, -1), [(libc::EPOLLIN as u32, 1)]);
+    t.join().unwrap();
+
+    // Nothing happens here, so the wait times out.
+    let start = Instant::now();
+    assert!(wait(epfd, 100).is_empty());
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+    assert_eq!(unsafe { libc::close(epfd) }, 0);
+}
+
+fn test_socketpair() {
+    let mut fds = [-1, -1];
+    let res = unsafe {
+        libc::socketpair(
+            libc::AF_UNIX,
+            libc::SOCK_STREAM | libc::SOCK_NONBLOCK,
+            0,
+            fds.as_mut_ptr(),
+        )
+    };
+    assert_eq!(res, 0);
+    let epfd = unsafe { libc::epoll_create1(0) };
+    assert_eq!(ctl(epfd, libc::EPOLL_CTL_ADD, fds[1], libc::EPOLLIN, 1), 0);
+    assert!(wait(epfd, 0).is_empty());
+
+    let data = *b"hello";
+    let res = unsafe { libc::write(fds[0], data.as_ptr() as *const libc::c_void, 5) };
+    assert_eq!(res, 5);
+    assert_eq!(wait(epfd, 0), [(libc::EPOLLIN as u32, 1)]);
+    let mut buf = [0u8; 8];
+    let res = unsafe { libc::read(fds[1], buf.as_mut_ptr() as *mut libc::c_void, 8) };
+    assert_eq!(res, 5);
+    assert_eq!(&buf[..5], b"hello");
+    assert!(wait(epfd, 0).is_empty());
+
+    // Closing one end makes the other one readable, and reading returns end of file.
+    assert_eq!(unsafe { libc::close(fds[0]) }, 0);
+    assert_eq!(wait(epfd, 0), [(libc::EPOLLIN as u32, 1)]);
+    let res = unsafe { libc::read(fds[1], buf.as_mut_ptr() as *mut libc::c_void, 8) };
+    assert_eq!(res, 0);
+    let res = unsafe { libc::write(fds[1], data.as_ptr() as *const libc::c_void, 5) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EPIPE);
+
+    assert_eq!(unsafe { libc::close(fds[1]) }, 0);
+    assert_eq!(unsafe { libc::close(epfd) }, 0);
+}
+
+fn main() {
+    test_eventfd();
+    test_eventfd_handoff();
+    test_epoll_level_and_edge_triggered();
+    test_epoll_closed_fd();
+    test_epoll_wait_blocks();
+    test_socketpair();
+}
@@ -219,6 +219,118 @@ fn wait_wake_bitset() {
     t.join().unwrap();
 }
 
+fn requeue() {
+    static mut FUTEX: i32 = 0;
+    static mut FUTEX2: i32 = 0;
+
+    let waiters: Vec<_> = (0..3)
+        .map(|_| {
+            thread::spawn(|| unsafe {
+                assert_eq!(
+                    libc::syscall(
+                        libc::SYS_futex,
+                        &FUTEX as *const i32,
+                        libc::FUTEX_WAIT,
+                        0,
+                        ptr::null::<libc::timespec>(),
+                    ),
+                    0,
+                );
+            })
+        })
+        .collect();
+    thread::sleep(Duration::from_millis(200));
+
+    unsafe {
+        // The value does not match, so nothing happens.
+        assert_eq!(
+            libc::syscall(
+                libc::SYS_futex,
+                &FUTEX as *const i32,
+                libc::FUTEX_CMP_REQUEUE,
+                1,
+                1usize,
+                &FUTEX2 as *const i32,
+                1,
+            ),
+            -1,
+        );
+        assert_eq!(*libc::__errno_location(), libc::EAGAIN);
+
+        // Wake up one thread and move another one to FUTEX2.
+        assert_eq!(
+            libc::syscall(
+                libc::SYS_futex,
+                &FUTEX as *const i32,
+                libc::FUTEX_CMP_REQUEUE,
+                1,
+                1usize,
+                &FUTEX2 as *const i32,
+                0,
+            ),
+            2, // Woken up or requeued two threads.
+        );
+        // Move the last thread without waking anyone.
+        assert_eq!(
+            libc::syscall(
+                libc::SYS_futex,
+                &FUTEX as *const i32,
+                libc::FUTEX_REQUEUE,
+                0,
+                10usize,
+                &FUTEX2 as *const i32,
+            ),
+            0, // Woken up no thread.
+        );
+        assert_eq!(libc::syscall(libc::SYS_futex, &FUTEX as *const i32, libc::FUTEX_WAKE, 10), 0);
+        assert_eq!(libc::syscall(libc::SYS_futex, &FUTEX2 as *const i32, libc::FUTEX_WAKE, 10), 2);
+    }
+
+    for waiter in waiters {
+        waiter.join().unwrap();
+    }
+}
+
+fn requeue_timeout() {
+    static mut FUTEX: i32 = 0;
+    static mut FUTEX2: i32 = 0;
+
+    let t = thread::spawn(|| unsafe {
+        assert_eq!(
+            libc::syscall(
+                libc::SYS_futex,
+                &FUTEX as *const i32,
+                libc::FUTEX_WAIT,
+                0,
+                &libc::timespec { tv_sec: 0, tv_nsec: 200_000_000 },
+            ),
+            -1,
+        );
+        assert_eq!(*libc::__errno_location(), libc::ETIMEDOUT);
+    });
+    thread::sleep(Duration::from_millis(100));
+
+    unsafe {
+        assert_eq!(
+            libc::syscall(
+                libc::SYS_futex,
+                &FUTEX as *const i32,
+                libc::FUTEX_REQUEUE,
+                0,
+                1usize,
+                &FUTEX2 as *const i32,
+            ),
+            0,
+        );
+    }
+    t.join().unwrap();
+
+    // The thread timed out while waiting on FUTEX2, so it is no longer waiting there.
+    unsafe {
+        assert_eq!(libc::syscall(libc::SYS_futex, &FUTEX2 as *const i32, libc::FUTEX_WAKE, 10), 0);
+    }
+}
+
 fn concurrent_wait_wake() {
     const FREE: i32 = 0;
     const HELD: i32 = 1;
@@ -287,5 +399,7 @@ fn main() {
     wait_absolute_timeout();
     wait_wake();
     wait_wake_bitset();
+    requeue();
+    requeue_timeout();
     concurrent_wait_wake();
 }
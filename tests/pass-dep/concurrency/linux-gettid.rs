@@ -0,0 +1,38 @@
+//@only-target-linux
+
+use std::thread;
+
+fn gettid() -> i32 {
+    unsafe { libc::gettid() }
+}
+
+fn main() {
+    // Under isolation, the process IDs are made up, but always the same.
+    let pid = unsafe { libc::getpid() };
+    assert_eq!(pid, 1000);
+    assert_eq!(std::process::id(), 1000);
+    assert_eq!(unsafe { libc::getppid() }, 1);
+
+    // The main thread has the ID of the process.
+    assert_eq!(gettid(), pid);
+    assert_eq!(unsafe { libc::syscall(libc::SYS_gettid) } as i32, pid);
+
+    // Other threads have their own IDs, which do not change.
+    let tids: Vec<i32> = (0..3)
+        .map(|_| {
+            thread::spawn(|| {
+                let tid = gettid();
+                thread::yield_now();
+                assert_eq!(gettid(), tid);
+                assert_eq!(unsafe { libc::getpid() }, 1000);
+                tid
+            })
+        })
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    for (i, tid) in tids.iter().enumerate() {
+        assert_ne!(*tid, pid);
+        assert!(!tids[..i].contains(tid));
+    }
+    assert_eq!(gettid(), pid);
+}
@@ -0,0 +1,162 @@
+//@only-target-linux
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn timespec(duration: Duration) -> libc::timespec {
+    libc::timespec { tv_sec: duration.as_secs() as _, tv_nsec: duration.subsec_nanos() as _ }
+}
+
+fn settime(fd: i32, value: Duration, interval: Duration) -> i32 {
+    let new_value = libc::itimerspec { it_value: timespec(value), it_interval: timespec(interval) };
+    unsafe { libc::timerfd_settime(fd, 0, &new_value, std::ptr::null_mut()) }
+}
+
+fn gettime(fd: i32) -> (Duration, Duration) {
+    let mut value: libc::itimerspec = unsafe { std::mem::zeroed() };
+    assert_eq!(unsafe { libc::timerfd_gettime(fd, &mut value) }, 0);
+    let duration = |ts: libc::timespec| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32);
+    (duration(value.it_value), duration(value.it_interval))
+}
+
+fn read_expirations(fd: i32) -> Option<u64> {
+    let mut value = 0u64;
+    let res = unsafe { libc::read(fd, &mut value as *mut u64 as *mut libc::c_void, 8) };
+    if res == 8 { Some(value) } else { None }
+}
+
+fn test_nonblocking() {
+    let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, libc::TFD_NONBLOCK) };
+    assert!(fd >= 0);
+
+    // A disarmed timer never expires.
+    assert_eq!(gettime(fd), (Duration::ZERO, Duration::ZERO));
+    assert_eq!(read_expirations(fd), None);
+    assert_eq!(errno(), libc::EAGAIN);
+
+    assert_eq!(settime(fd, Duration::from_secs(10), Duration::ZERO), 0);
+    let (remaining, interval) = gettime(fd);
+    assert!(remaining > Duration::ZERO && remaining <= Duration::from_secs(10));
+    assert_eq!(interval, Duration::ZERO);
+    assert_eq!(read_expirations(fd), None);
+    assert_eq!(errno(), libc::EAGAIN);
+
+    // Disarm the timer again.
+    assert_eq!(settime(fd, Duration::ZERO, Duration::ZERO), 0);
+    assert_eq!(gettime(fd), (Duration::ZERO, Duration::ZERO));
+
+    // An absolute time in the past expires right away.
+    let new_value = libc::itimerspec {
+        it_value: timespec(Duration::from_nanos(1)),
+        it_interval: timespec(Duration::ZERO),
+    };
+    let res = unsafe {
+        libc::timerfd_settime(fd, libc::TFD_TIMER_ABSTIME, &new_value, std::ptr::null_mut())
+    };
+    assert_eq!(res, 0);
+    assert_eq!(read_expirations(fd), Some(1));
+    assert_eq!(read_expirations(fd), None);
+
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+}
+
+fn test_blocking() {
+    let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, libc::TFD_CLOEXEC) };
+    assert!(fd >= 0);
+
+    // A read blocks until the timer expires.
+    let start = Instant::now();
+    assert_eq!(settime(fd, Duration::from_millis(50), Duration::ZERO), 0);
+    assert_eq!(read_expirations(fd), Some(1));
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    // A periodic timer counts the expirations since the last read.
+    assert_eq!(settime(fd, Duration::from_millis(10), Duration::from_millis(10)), 0);
+    assert_eq!(read_expirations(fd), Some(1));
+    thread::sleep(Duration::from_millis(35));
+    assert!(read_expirations(fd).unwrap() >= 3);
+    let (remaining, interval) = gettime(fd);
+    assert!(remaining <= Duration::from_millis(10));
+    assert_eq!(interval, Duration::from_millis(10));
+
+    // Another thread can wait for the timer as well.
+    let waiter = thread::spawn(move || read_expirations(fd));
+    assert!(waiter.join().unwrap().unwrap() >= 1);
+
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+}
+
+fn test_epoll() {
+    let epfd = unsafe { libc::epoll_create1(0) };
+    let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, libc::TFD_NONBLOCK) };
+    let mut event = libc::epoll_event { events: libc::EPOLLIN as u32, u64: 5 };
+    assert_eq!(unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_ADD, fd, &mut event) }, 0);
+
+    let mut events = [libc::epoll_event { events: 0, u64: 0 }; 4];
+    assert_eq!(unsafe { libc::epoll_wait(epfd, events.as_mut_ptr(), 4, 0) }, 0);
+
+    // `epoll_wait` is woken up when the timer expires.
+    assert_eq!(settime(fd, Duration::from_millis(20), Duration::ZERO), 0);
+    assert_eq!(unsafe { libc::epoll_wait(epfd, events.as_mut_ptr(), 4, -1) }, 1);
+    assert_eq!({ events[0].u64 }, 5);
+    assert_eq!(read_expirations(fd), Some(1));
+
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+    assert_eq!(unsafe { libc::close(epfd) }, 0);
+}
+
+fn test_errors() {
+    assert_eq!(unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, 0x1234) }, -1);
+    assert_eq!(errno(), libc::EINVAL);
+
+    let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, 0) };
+    let new_value = libc::itimerspec {
+        it_value: libc::timespec { tv_sec: 0, tv_nsec: 1_000_000_000 },
+        it_interval: timespec(Duration::ZERO),
+    };
+    assert_eq!(unsafe { libc::timerfd_settime(fd, 0, &new_value, std::ptr::null_mut()) }, -1);
+    assert_eq!(errno(), libc::EINVAL);
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+
+    // Only timers have a time.
+    let eventfd = unsafe { libc::eventfd(0, 0) };
+    assert_eq!(settime(eventfd, Duration::from_secs(1), Duration::ZERO), -1);
+    assert_eq!(errno(), libc::EINVAL);
+    assert_eq!(settime(-1, Duration::from_secs(1), Duration::ZERO), -1);
+    assert_eq!(errno(), libc::EBADF);
+    assert_eq!(unsafe { libc::close(eventfd) }, 0);
+}
+
+fn test_signalfd() {
+    let mask: libc::sigset_t = unsafe { std::mem::zeroed() };
+    let fd = unsafe { libc::signalfd(-1, &mask, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC) };
+    assert!(fd >= 0);
+
+    // No signal ever arrives.
+    let mut info: libc::signalfd_siginfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::signalfd_siginfo>();
+    let res = unsafe { libc::read(fd, &mut info as *mut _ as *mut libc::c_void, size) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EAGAIN);
+
+    // Changing the mask returns the same file descriptor.
+    assert_eq!(unsafe { libc::signalfd(fd, &mask, 0) }, fd);
+    let eventfd = unsafe { libc::eventfd(0, 0) };
+    assert_eq!(unsafe { libc::signalfd(eventfd, &mask, 0) }, -1);
+    assert_eq!(errno(), libc::EINVAL);
+
+    assert_eq!(unsafe { libc::close(eventfd) }, 0);
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+}
+
+fn main() {
+    test_nonblocking();
+    test_blocking();
+    test_epoll();
+    test_errors();
+    test_signalfd();
+}
@@ -0,0 +1,36 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-lock-order-check
+
+use std::cell::UnsafeCell;
+use std::thread;
+
+struct Mutex(UnsafeCell<libc::pthread_mutex_t>);
+
+unsafe impl Sync for Mutex {}
+
+static A: Mutex = Mutex(UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER));
+static B: Mutex = Mutex(UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER));
+
+fn main() {
+    unsafe {
+        libc::pthread_mutex_lock(A.0.get());
+        libc::pthread_mutex_lock(B.0.get());
+        libc::pthread_mutex_unlock(B.0.get());
+        libc::pthread_mutex_unlock(A.0.get());
+        // This does not deadlock since the threads do not run concurrently,
+        // but it would if they did.
+        thread::spawn(|| {
+            libc::pthread_mutex_lock(B.0.get());
+            libc::pthread_mutex_lock(A.0.get());
+            libc::pthread_mutex_unlock(A.0.get());
+            libc::pthread_mutex_unlock(B.0.get());
+        })
+        .join()
+        .unwrap();
+        // The same inversion is only reported once.
+        libc::pthread_mutex_lock(B.0.get());
+        libc::pthread_mutex_lock(A.0.get());
+        libc::pthread_mutex_unlock(A.0.get());
+        libc::pthread_mutex_unlock(B.0.get());
+    }
+}
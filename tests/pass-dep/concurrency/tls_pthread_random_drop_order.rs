@@ -0,0 +1,54 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-random-tls-dtor-order -Zmiri-seed=3
+//! Test that pthread_key destructors run in a random order, but every destructor still runs once
+//! per round.
+
+use std::thread;
+
+type Key = libc::pthread_key_t;
+
+const N: usize = 8;
+
+static mut KEYS: [Key; N] = [0; N];
+/// The value of each key, which says how often its destructor still has to re-set it.
+static mut ROUNDS: [u8; N] = [1; N];
+static mut RECORD: Vec<usize> = Vec::new();
+
+unsafe extern "C" fn dtor(ptr: *mut libc::c_void) {
+    let which_key = ROUNDS
+        .iter()
+        .position(|rounds| rounds as *const u8 == ptr as *const u8)
+        .expect("should find my value");
+    RECORD.push(which_key);
+    if ROUNDS[which_key] > 0 {
+        ROUNDS[which_key] -= 1;
+        assert_eq!(libc::pthread_setspecific(KEYS[which_key], ptr), 0);
+    }
+}
+
+fn main() {
+    unsafe {
+        for key in KEYS.iter_mut() {
+            assert_eq!(libc::pthread_key_create(key, Some(dtor)), 0);
+        }
+    }
+    thread::spawn(|| unsafe {
+        for (key, rounds) in KEYS.iter().zip(ROUNDS.iter_mut()) {
+            assert_eq!(libc::pthread_setspecific(*key, rounds as *mut u8 as *mut libc::c_void), 0);
+        }
+    })
+    .join()
+    .unwrap();
+
+    let record = unsafe { &RECORD };
+    assert_eq!(record.len(), 2 * N);
+    // Each round runs every destructor exactly once.
+    for round in record.chunks(N) {
+        let mut sorted = round.to_vec();
+        sorted.sort();
+        assert_eq!(sorted, (0..N).collect::<Vec<_>>());
+    }
+    // With 8 keys, the chance that the destructors happen to run in creation order in both rounds
+    // is tiny, and the seed is fixed.
+    assert_ne!(record[..], [(0..N).collect::<Vec<_>>(), (0..N).collect()].concat());
+}
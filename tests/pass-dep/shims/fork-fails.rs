@@ -0,0 +1,28 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-fork-fails
+
+use std::ptr;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn main() {
+    unsafe {
+        assert_eq!(libc::fork(), -1);
+        assert_eq!(errno(), libc::EAGAIN);
+
+        let mut pid = 0;
+        let argv = [b"true\0".as_ptr().cast::<libc::c_char>(), ptr::null()];
+        let envp = [ptr::null()];
+        let result = libc::posix_spawnp(
+            &mut pid,
+            argv[0],
+            ptr::null(),
+            ptr::null(),
+            argv.as_ptr() as *const *mut _,
+            envp.as_ptr() as *const *mut _,
+        );
+        assert_eq!(result, libc::EAGAIN);
+    }
+}
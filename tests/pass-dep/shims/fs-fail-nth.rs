@@ -0,0 +1,40 @@
+//@ignore-target-windows: File handling is not implemented yet
+//@compile-flags: -Zmiri-disable-isolation -Zmiri-fs-fail-nth=3 -Zmiri-fs-fail-errno=ENOSPC
+
+use std::ffi::CString;
+use std::fs::{read_to_string, remove_file};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+fn tmp() -> PathBuf {
+    std::env::var("MIRI_TEMP")
+        .map(|tmp| {
+            // MIRI_TEMP is set outside of our emulated
+            // program, so it may have path separators that don't
+            // correspond to our target platform. We normalize them here
+            // before constructing a `PathBuf`
+            PathBuf::from(tmp.replace("\\", "/"))
+        })
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+fn main() {
+    let path = tmp().join("miri_test_fs_fail_nth.txt");
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    // Only calls on files count, writes to stdout do not.
+    println!("starting");
+    unsafe {
+        // The first two calls succeed.
+        libc::unlink(c_path.as_ptr());
+        let fd = libc::open(c_path.as_ptr(), libc::O_WRONLY | libc::O_CREAT, 0o644);
+        assert!(fd > 0);
+        // The third one fails with the configured error.
+        assert_eq!(libc::write(fd, b"a".as_ptr().cast(), 1), -1);
+        assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ENOSPC));
+        // All others succeed again.
+        assert_eq!(libc::write(fd, b"b".as_ptr().cast(), 1), 1);
+        assert_eq!(libc::close(fd), 0);
+    }
+    assert_eq!(read_to_string(&path).unwrap(), "b");
+    remove_file(&path).unwrap();
+}
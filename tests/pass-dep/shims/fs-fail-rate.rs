@@ -0,0 +1,57 @@
+//@ignore-target-windows: File handling is not implemented yet
+//@compile-flags: -Zmiri-disable-isolation -Zmiri-fs-fail-rate=0.5 -Zmiri-seed=2a
+// Which calls fail depends on the seed, so only check that there were notes.
+//@normalize-stderr-test: "(note: injected file system failure\n(.+\n)+\n)*note: injected file system failure\n(.+\n)+" -> "NOTES"
+
+use std::ffi::CString;
+use std::io::Error;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+fn tmp() -> PathBuf {
+    std::env::var("MIRI_TEMP")
+        .map(|tmp| {
+            // MIRI_TEMP is set outside of our emulated
+            // program, so it may have path separators that don't
+            // correspond to our target platform. We normalize them here
+            // before constructing a `PathBuf`
+            PathBuf::from(tmp.replace("\\", "/"))
+        })
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+fn last_errno() -> i32 {
+    Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn main() {
+    let path = tmp().join("miri_test_fs_fail_rate.txt");
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    // Creating the file can fail as well, so retry until it worked.
+    while std::fs::write(&path, "").is_err() {}
+
+    let mut failures = 0;
+    for _ in 0..64 {
+        if unsafe { libc::access(c_path.as_ptr(), libc::F_OK) } == -1 {
+            assert_eq!(last_errno(), libc::EIO);
+            failures += 1;
+        }
+    }
+    // With a rate of one half, both outcomes occur.
+    assert!(failures > 0 && failures < 64, "{failures} of 64 calls failed");
+
+    // A call that fails does nothing, so `unlink` has to be retried until the file is gone.
+    while unsafe { libc::unlink(c_path.as_ptr()) } == -1 {
+        assert_eq!(last_errno(), libc::EIO);
+    }
+    loop {
+        assert_eq!(unsafe { libc::access(c_path.as_ptr(), libc::F_OK) }, -1);
+        match last_errno() {
+            libc::EIO => continue,
+            errno => {
+                assert_eq!(errno, libc::ENOENT);
+                break;
+            }
+        }
+    }
+}
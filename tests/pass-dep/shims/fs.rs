@@ -20,6 +20,12 @@ fn main() {
     test_metadata();
     test_file_set_len();
     test_file_sync();
+    test_set_permissions();
+    test_chown();
+    test_umask();
+    test_access();
+    test_utimensat();
+    test_at_functions();
     test_symlink();
     test_errors();
     test_rename();
@@ -262,6 +268,184 @@ fn test_file_sync() {
     remove_file(&path).unwrap();
 }
 
+fn test_set_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = prepare_with_content("miri_test_fs_set_permissions.txt", b"");
+
+    // Test changing the mode by path.
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o444)).unwrap();
+    assert_eq!(path.metadata().unwrap().permissions().mode() & 0o777, 0o444);
+
+    // Test changing the mode of an open file.
+    let file = File::open(&path).unwrap();
+    file.set_permissions(std::fs::Permissions::from_mode(0o640)).unwrap();
+    assert_eq!(file.metadata().unwrap().permissions().mode() & 0o777, 0o640);
+    drop(file);
+
+    remove_file(&path).unwrap();
+}
+
+fn test_chown() {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::AsRawFd;
+
+    let path = prepare_with_content("miri_test_fs_chown.txt", b"");
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+
+    // Passing `-1` for both IDs leaves the ownership unchanged, which needs no privileges.
+    let file = File::open(&path).unwrap();
+    unsafe {
+        assert_eq!(libc::chown(c_path.as_ptr(), u32::MAX, u32::MAX), 0);
+        assert_eq!(libc::lchown(c_path.as_ptr(), u32::MAX, u32::MAX), 0);
+        assert_eq!(libc::fchown(file.as_raw_fd(), u32::MAX, u32::MAX), 0);
+        assert_eq!(libc::fchown(-1, u32::MAX, u32::MAX), -1);
+        assert_eq!(Error::last_os_error().raw_os_error(), Some(libc::EBADF));
+    }
+    drop(file);
+
+    remove_file(&path).unwrap();
+    assert_eq!(unsafe { libc::chown(c_path.as_ptr(), u32::MAX, u32::MAX) }, -1);
+    assert_eq!(Error::last_os_error().kind(), ErrorKind::NotFound);
+}
+
+fn test_umask() {
+    use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+
+    let path = prepare("miri_test_fs_umask.txt");
+    let dir_path = prepare_dir("miri_test_fs_umask_dir");
+
+    // The new mask is returned when it is replaced again.
+    let old_mask = unsafe { libc::umask(0o077) };
+    assert_eq!(unsafe { libc::umask(0o027) }, 0o077);
+
+    // Created files and directories do not get the permission bits in the mask.
+    OpenOptions::new().write(true).create_new(true).mode(0o666).open(&path).unwrap();
+    assert_eq!(path.metadata().unwrap().permissions().mode() & 0o777, 0o640);
+    std::fs::DirBuilder::new().mode(0o777).create(&dir_path).unwrap();
+    assert_eq!(dir_path.metadata().unwrap().permissions().mode() & 0o777, 0o750);
+
+    unsafe { libc::umask(old_mask) };
+    remove_file(&path).unwrap();
+    remove_dir(&dir_path).unwrap();
+}
+
+fn test_access() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = prepare_with_content("miri_test_fs_access.txt", b"");
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o644)).unwrap();
+
+    unsafe {
+        assert_eq!(libc::access(c_path.as_ptr(), libc::F_OK), 0);
+        assert_eq!(libc::access(c_path.as_ptr(), libc::R_OK | libc::W_OK), 0);
+        // Not even root may execute a file without any execute permission bits.
+        assert_eq!(libc::access(c_path.as_ptr(), libc::X_OK), -1);
+        assert_eq!(Error::last_os_error().raw_os_error(), Some(libc::EACCES));
+        let flags = libc::AT_EACCESS;
+        assert_eq!(libc::faccessat(libc::AT_FDCWD, c_path.as_ptr(), libc::R_OK, flags), 0);
+        assert_eq!(libc::faccessat(libc::AT_FDCWD, c_path.as_ptr(), libc::R_OK, -1), -1);
+        assert_eq!(Error::last_os_error().kind(), ErrorKind::InvalidInput);
+    }
+
+    remove_file(&path).unwrap();
+    assert_eq!(unsafe { libc::access(c_path.as_ptr(), libc::F_OK) }, -1);
+    assert_eq!(Error::last_os_error().kind(), ErrorKind::NotFound);
+}
+
+fn test_utimensat() {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::AsRawFd;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn timespec(tv_sec: libc::time_t, tv_nsec: libc::c_long) -> libc::timespec {
+        let mut timespec: libc::timespec = unsafe { std::mem::zeroed() };
+        timespec.tv_sec = tv_sec;
+        timespec.tv_nsec = tv_nsec;
+        timespec
+    }
+
+    let path = prepare_with_content("miri_test_fs_utimensat.txt", b"");
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let file = File::open(&path).unwrap();
+
+    unsafe {
+        let times = [timespec(1_000_000_000, 5), timespec(0, libc::UTIME_OMIT)];
+        assert_eq!(libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), 0), 0);
+        let times = [timespec(0, libc::UTIME_OMIT), timespec(1_200_000_000, 0)];
+        assert_eq!(libc::futimens(file.as_raw_fd(), times.as_ptr()), 0);
+    }
+    let metadata = path.metadata().unwrap();
+    assert_eq!(metadata.accessed().unwrap(), UNIX_EPOCH + Duration::new(1_000_000_000, 5));
+    assert_eq!(metadata.modified().unwrap(), UNIX_EPOCH + Duration::from_secs(1_200_000_000));
+
+    unsafe {
+        // Without times, both are set to the current time.
+        assert_eq!(libc::futimens(file.as_raw_fd(), std::ptr::null()), 0);
+        let modified = path.metadata().unwrap().modified().unwrap();
+        assert!(modified > UNIX_EPOCH + Duration::from_secs(1_200_000_000));
+
+        let times = [timespec(0, 1_000_000_000), timespec(0, 0)];
+        assert_eq!(libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), 0), -1);
+        assert_eq!(Error::last_os_error().kind(), ErrorKind::InvalidInput);
+        assert_eq!(libc::futimens(-1, std::ptr::null()), -1);
+        assert_eq!(Error::last_os_error().raw_os_error(), Some(libc::EBADF));
+    }
+
+    drop(file);
+    remove_file(&path).unwrap();
+}
+
+fn test_at_functions() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir_path = prepare_dir("miri_test_fs_at_functions");
+    create_dir(&dir_path).unwrap();
+    let c_dir_path = CString::new(dir_path.as_os_str().as_bytes()).unwrap();
+    let dirfd = unsafe { libc::open(c_dir_path.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY) };
+    assert!(dirfd > 0);
+
+    unsafe {
+        // All relative paths are resolved relative to `dirfd`.
+        assert_eq!(libc::mkdirat(dirfd, b"sub\0".as_ptr().cast(), 0o755), 0);
+        assert!(dir_path.join("sub").is_dir());
+        let flags = libc::O_WRONLY | libc::O_CREAT;
+        let fd = libc::openat(dirfd, b"sub/file\0".as_ptr().cast(), flags, 0o644);
+        assert!(fd > 0);
+        assert_eq!(libc::write(fd, b"hello".as_ptr().cast(), 5), 5);
+        assert_eq!(libc::close(fd), 0);
+        let (old, new) = (b"sub/file\0".as_ptr().cast(), b"renamed\0".as_ptr().cast());
+        assert_eq!(libc::renameat(dirfd, old, dirfd, new), 0);
+        let (target, link) = (b"renamed\0".as_ptr().cast(), b"link\0".as_ptr().cast());
+        assert_eq!(libc::symlinkat(target, dirfd, link), 0);
+    }
+    // The target of the link is stored unchanged, and resolved relative to the link.
+    assert_eq!(read_link(dir_path.join("link")).unwrap(), Path::new("renamed"));
+    let mut contents = String::new();
+    File::open(dir_path.join("link")).unwrap().read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "hello");
+
+    unsafe {
+        assert_eq!(libc::unlinkat(dirfd, b"link\0".as_ptr().cast(), 0), 0);
+        assert_eq!(libc::unlinkat(dirfd, b"renamed\0".as_ptr().cast(), 0), 0);
+        // Directories can only be removed with `AT_REMOVEDIR`.
+        assert_eq!(libc::unlinkat(dirfd, b"sub\0".as_ptr().cast(), 0), -1);
+        assert_eq!(libc::unlinkat(dirfd, b"sub\0".as_ptr().cast(), libc::AT_REMOVEDIR), 0);
+        assert_eq!(libc::unlinkat(dirfd, b"sub\0".as_ptr().cast(), -1), -1);
+        assert_eq!(Error::last_os_error().kind(), ErrorKind::InvalidInput);
+        // Relative paths need `dirfd` to be an open directory.
+        assert_eq!(libc::mkdirat(-1, b"sub\0".as_ptr().cast(), 0o755), -1);
+        assert_eq!(Error::last_os_error().raw_os_error(), Some(libc::EBADF));
+        assert_eq!(libc::mkdirat(0, b"sub\0".as_ptr().cast(), 0o755), -1);
+        assert_eq!(Error::last_os_error().raw_os_error(), Some(libc::ENOTDIR));
+        assert_eq!(libc::close(dirfd), 0);
+    }
+    assert_eq!(read_dir(&dir_path).unwrap().count(), 0);
+
+    remove_dir(&dir_path).unwrap();
+}
+
 fn test_symlink() {
     let bytes = b"Hello, World!\n";
     let path = prepare_with_content("miri_test_fs_link_target.txt", bytes);
@@ -0,0 +1,30 @@
+//@ignore-target-windows: No libc on Windows
+
+fn write(message: &str) {
+    let res = unsafe { libc::write(1, message.as_ptr().cast(), message.len()) };
+    assert_eq!(res, message.len() as isize);
+}
+
+extern "C" fn first() {
+    write("first\n");
+}
+
+extern "C" fn second() {
+    write("second\n");
+    // Handlers registered while the handlers run are called as well.
+    unsafe { libc::atexit(third) };
+}
+
+extern "C" fn third() {
+    write("third\n");
+}
+
+fn main() {
+    unsafe {
+        assert_eq!(libc::atexit(first), 0);
+        assert_eq!(libc::atexit(second), 0);
+    }
+    write("main\n");
+    // `exit` runs the handlers, and none of the code after it.
+    std::process::exit(0);
+}
@@ -0,0 +1,45 @@
+//@ignore-target-windows: No libc on Windows
+
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+extern "C" {
+    fn __cxa_atexit(
+        func: extern "C" fn(*mut libc::c_void),
+        arg: *mut libc::c_void,
+        dso_handle: *mut libc::c_void,
+    ) -> libc::c_int;
+}
+
+/// Memory that is only freed by an `atexit` handler, which has to run before the leak check.
+static BUFFER: AtomicPtr<u8> = AtomicPtr::new(std::ptr::null_mut());
+
+fn write(message: &str) {
+    let res = unsafe { libc::write(1, message.as_ptr().cast(), message.len()) };
+    assert_eq!(res, message.len() as isize);
+}
+
+extern "C" fn first() {
+    write("first\n");
+}
+
+extern "C" fn second() {
+    write("second\n");
+    unsafe { libc::free(BUFFER.load(Ordering::Relaxed).cast()) };
+}
+
+extern "C" fn with_arg(arg: *mut libc::c_void) {
+    write(unsafe { *arg.cast::<&str>() });
+}
+
+static MESSAGE: &str = "with argument\n";
+
+fn main() {
+    BUFFER.store(unsafe { libc::malloc(16).cast() }, Ordering::Relaxed);
+    unsafe {
+        assert_eq!(libc::atexit(first), 0);
+        assert_eq!(libc::atexit(second), 0);
+        let arg = &MESSAGE as *const &str as *mut libc::c_void;
+        assert_eq!(__cxa_atexit(with_arg, arg, std::ptr::null_mut()), 0);
+    }
+    write("main\n");
+}
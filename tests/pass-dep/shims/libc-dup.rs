@@ -0,0 +1,111 @@
+//@ignore-target-windows: File handling is not implemented yet
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::fs::{remove_file, File};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn tmp() -> PathBuf {
+    std::env::var("MIRI_TEMP")
+        .map(|tmp| {
+            // MIRI_TEMP is set outside of our emulated
+            // program, so it may have path separators that don't
+            // correspond to our target platform. We normalize them here
+            // before constructing a `PathBuf`
+            PathBuf::from(tmp.replace("\\", "/"))
+        })
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+fn read(fd: i32, buf: &mut [u8]) -> isize {
+    unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) }
+}
+
+fn test_shared_offset() {
+    let path = tmp().join("miri_test_libc_dup.txt");
+    remove_file(&path).ok();
+    File::create(&path).unwrap().write_all(b"hello world").unwrap();
+
+    let file = File::open(&path).unwrap();
+    let fd = file.as_raw_fd();
+    let dup = unsafe { libc::dup(fd) };
+    assert!(dup > fd);
+
+    // Reading from one file descriptor moves the offset of the other one.
+    let mut buf = [0; 5];
+    assert_eq!(read(fd, &mut buf), 5);
+    assert_eq!(&buf, b"hello");
+    let mut buf = [0; 6];
+    assert_eq!(read(dup, &mut buf), 6);
+    assert_eq!(&buf, b" world");
+    assert_eq!(read(fd, &mut buf), 0);
+
+    // The duplicate stays open when the original is closed.
+    drop(file);
+    assert_eq!(read(dup, &mut buf), 0);
+    assert_eq!(unsafe { libc::close(dup) }, 0);
+
+    remove_file(&path).unwrap();
+}
+
+fn test_dup2() {
+    let mut fds = [-1, -1];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let [reader, writer] = fds;
+    let mut other = [-1, -1];
+    assert_eq!(unsafe { libc::pipe(other.as_mut_ptr()) }, 0);
+
+    // `dup2` closes the target file descriptor, so the other pipe has no reader anymore.
+    assert_eq!(unsafe { libc::dup2(reader, other[0]) }, other[0]);
+    assert_eq!(unsafe { libc::write(other[1], [0u8].as_ptr().cast(), 1) }, -1);
+    assert_eq!(errno(), libc::EPIPE);
+
+    assert_eq!(unsafe { libc::write(writer, b"ab".as_ptr().cast(), 2) }, 2);
+    let mut buf = [0; 1];
+    assert_eq!(read(other[0], &mut buf), 1);
+    assert_eq!(&buf, b"a");
+    assert_eq!(read(reader, &mut buf), 1);
+    assert_eq!(&buf, b"b");
+
+    // Duplicating onto itself does nothing, and the target has to be valid.
+    assert_eq!(unsafe { libc::dup2(reader, reader) }, reader);
+    assert_eq!(unsafe { libc::dup2(reader, -1) }, -1);
+    assert_eq!(errno(), libc::EBADF);
+    assert_eq!(unsafe { libc::dup2(-1, reader) }, -1);
+    assert_eq!(errno(), libc::EBADF);
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        assert_eq!(unsafe { libc::dup3(reader, reader, 0) }, -1);
+        assert_eq!(errno(), libc::EINVAL);
+        assert_eq!(unsafe { libc::dup3(reader, 100, libc::O_CLOEXEC) }, 100);
+        assert_eq!(unsafe { libc::close(100) }, 0);
+    }
+
+    for fd in [reader, writer, other[0], other[1]] {
+        assert_eq!(unsafe { libc::close(fd) }, 0);
+    }
+}
+
+/// This has to run last, since nothing is printed to stdout afterwards.
+fn test_redirect_stdout() {
+    let path = tmp().join("miri_test_libc_dup_stdout.txt");
+    remove_file(&path).ok();
+    let file = File::create(&path).unwrap();
+    assert_eq!(unsafe { libc::dup2(file.as_raw_fd(), libc::STDOUT_FILENO) }, libc::STDOUT_FILENO);
+    drop(file);
+    println!("redirected");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "redirected\n");
+    remove_file(&path).unwrap();
+}
+
+fn main() {
+    test_shared_offset();
+    test_dup2();
+    test_redirect_stdout();
+}
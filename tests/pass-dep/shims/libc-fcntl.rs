@@ -0,0 +1,106 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::ffi::CString;
+use std::fs::{read_to_string, remove_file, File};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn tmp() -> PathBuf {
+    std::env::var("MIRI_TEMP")
+        .map(|tmp| {
+            // MIRI_TEMP is set outside of our emulated
+            // program, so it may have path separators that don't
+            // correspond to our target platform. We normalize them here
+            // before constructing a `PathBuf`
+            PathBuf::from(tmp.replace("\\", "/"))
+        })
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+fn test_pipe() {
+    let mut fds = [-1, -1];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let [reader, writer] = fds;
+
+    unsafe {
+        assert_eq!(libc::fcntl(reader, libc::F_GETFL), libc::O_RDONLY);
+        assert_eq!(libc::fcntl(writer, libc::F_GETFL), libc::O_WRONLY);
+        assert_eq!(libc::fcntl(0, libc::F_GETFL), libc::O_RDONLY);
+
+        // Duplicates share the flags.
+        let dup = libc::fcntl(reader, libc::F_DUPFD_CLOEXEC, 10);
+        assert!(dup >= 10);
+        assert_eq!(libc::fcntl(dup, libc::F_SETFL, libc::O_RDONLY | libc::O_NONBLOCK), 0);
+        assert_eq!(libc::fcntl(reader, libc::F_GETFL), libc::O_RDONLY | libc::O_NONBLOCK);
+
+        // Now reading does not block.
+        let mut buf = [0u8; 4];
+        assert_eq!(libc::read(reader, buf.as_mut_ptr().cast(), 4), -1);
+        assert_eq!(errno(), libc::EAGAIN);
+
+        assert_eq!(libc::fcntl(reader, libc::F_SETFL, 0), 0);
+        assert_eq!(libc::fcntl(dup, libc::F_GETFL), libc::O_RDONLY);
+        assert_eq!(libc::fcntl(dup, libc::F_SETFD, libc::FD_CLOEXEC), 0);
+        assert_eq!(libc::fcntl(dup, libc::F_GETFD), libc::FD_CLOEXEC);
+        assert_eq!(libc::close(dup), 0);
+
+        assert_eq!(libc::fcntl(dup, libc::F_GETFL), -1);
+        assert_eq!(errno(), libc::EBADF);
+
+        assert_eq!(libc::close(reader), 0);
+        assert_eq!(libc::close(writer), 0);
+    }
+}
+
+fn test_file() {
+    let path = tmp().join("miri_test_libc_fcntl.txt");
+    remove_file(&path).ok();
+    File::create(&path).unwrap();
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let write = |fd, bytes: &[u8]| unsafe { libc::write(fd, bytes.as_ptr().cast(), bytes.len()) };
+    // Hosts may report more flags for files, like `O_LARGEFILE`.
+    let status_flags = |fd| unsafe {
+        libc::fcntl(fd, libc::F_GETFL) & (libc::O_ACCMODE | libc::O_APPEND | libc::O_NONBLOCK)
+    };
+
+    unsafe {
+        let flags = libc::O_WRONLY | libc::O_APPEND | libc::O_NONBLOCK;
+        let fd = libc::open(c_path.as_ptr(), flags);
+        assert!(fd > 0);
+        assert_eq!(status_flags(fd), flags);
+
+        // Every write goes to the end of the file, wherever the offset was.
+        assert_eq!(write(fd, b"hello"), 5);
+        assert_eq!(libc::lseek(fd, 0, libc::SEEK_SET), 0);
+        assert_eq!(write(fd, b" world"), 6);
+        assert_eq!(libc::lseek(fd, 0, libc::SEEK_CUR), 11);
+
+        // Without `O_APPEND`, writes go to the offset again.
+        assert_eq!(libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK), 0);
+        assert_eq!(status_flags(fd), libc::O_WRONLY | libc::O_NONBLOCK);
+        assert_eq!(libc::lseek(fd, 0, libc::SEEK_SET), 0);
+        assert_eq!(write(fd, b"H"), 1);
+
+        // Duplicates share the flags.
+        let dup = libc::dup(fd);
+        assert_eq!(libc::fcntl(dup, libc::F_SETFL, libc::O_APPEND), 0);
+        assert_eq!(status_flags(fd), libc::O_WRONLY | libc::O_APPEND);
+        assert_eq!(write(fd, b"!"), 1);
+
+        assert_eq!(libc::close(dup), 0);
+        assert_eq!(libc::close(fd), 0);
+    }
+    assert_eq!(read_to_string(&path).unwrap(), "Hello world!");
+
+    remove_file(&path).unwrap();
+}
+
+fn main() {
+    test_pipe();
+    test_file();
+}
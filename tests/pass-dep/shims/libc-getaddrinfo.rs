@@ -0,0 +1,88 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-host=example.com=93.184.216.34
+
+use std::ffi::CStr;
+use std::mem;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::ptr;
+
+/// The addresses and socket types that `getaddrinfo` returns, and the canonical name.
+fn getaddrinfo(
+    node: Option<&[u8]>,
+    service: Option<&[u8]>,
+    flags: i32,
+    socktype: i32,
+) -> Result<(Vec<([u8; 4], u16, i32)>, Option<String>), i32> {
+    let mut hints: libc::addrinfo = unsafe { mem::zeroed() };
+    hints.ai_flags = flags;
+    hints.ai_family = libc::AF_UNSPEC;
+    hints.ai_socktype = socktype;
+    let node = node.map_or(ptr::null(), |node| node.as_ptr().cast());
+    let service = service.map_or(ptr::null(), |service| service.as_ptr().cast());
+    let mut res = ptr::null_mut();
+    let error = unsafe { libc::getaddrinfo(node, service, &hints, &mut res) };
+    if error != 0 {
+        return Err(error);
+    }
+
+    let mut entries = Vec::new();
+    let canonname = unsafe { (*res).ai_canonname };
+    let canonname = (!canonname.is_null())
+        .then(|| unsafe { CStr::from_ptr(canonname) }.to_str().unwrap().to_owned());
+    let mut info = res;
+    while !info.is_null() {
+        let info_ref = unsafe { &*info };
+        assert_eq!(info_ref.ai_family, libc::AF_INET);
+        assert_eq!(info_ref.ai_addrlen as usize, mem::size_of::<libc::sockaddr_in>());
+        let addr = unsafe { &*info_ref.ai_addr.cast::<libc::sockaddr_in>() };
+        assert_eq!(i32::from(addr.sin_family), libc::AF_INET);
+        let ip = addr.sin_addr.s_addr.to_ne_bytes();
+        entries.push((ip, u16::from_be(addr.sin_port), info_ref.ai_socktype));
+        info = info_ref.ai_next;
+    }
+    unsafe { libc::freeaddrinfo(res) };
+    Ok((entries, canonname))
+}
+
+fn test_libc() {
+    // Without a socket type, there is an entry for TCP and one for UDP.
+    let (entries, canonname) =
+        getaddrinfo(Some(b"localhost\0"), Some(b"8080\0"), libc::AI_CANONNAME, 0).unwrap();
+    assert_eq!(
+        entries,
+        [([127, 0, 0, 1], 8080, libc::SOCK_STREAM), ([127, 0, 0, 1], 8080, libc::SOCK_DGRAM)]
+    );
+    assert_eq!(canonname.as_deref(), Some("localhost"));
+
+    let resolve = |node: &[u8], flags| {
+        getaddrinfo(Some(node), None, flags, libc::SOCK_STREAM).map(|(entries, _)| entries)
+    };
+    assert_eq!(resolve(b"10.0.0.1\0", 0), Ok(vec![([10, 0, 0, 1], 0, libc::SOCK_STREAM)]));
+    assert_eq!(resolve(b"EXAMPLE.com\0", 0), Ok(vec![([93, 184, 216, 34], 0, libc::SOCK_STREAM)]));
+    assert_eq!(resolve(b"example.com\0", libc::AI_NUMERICHOST), Err(libc::EAI_NONAME));
+    assert_eq!(resolve(b"example.org\0", 0), Err(libc::EAI_NONAME));
+
+    // Without a node, the address depends on whether it is for binding.
+    let bind = getaddrinfo(None, Some(b"80\0"), libc::AI_PASSIVE, libc::SOCK_STREAM);
+    assert_eq!(bind.unwrap().0, [([0; 4], 80, libc::SOCK_STREAM)]);
+    let connect = getaddrinfo(None, Some(b"80\0"), 0, libc::SOCK_STREAM);
+    assert_eq!(connect.unwrap().0, [([127, 0, 0, 1], 80, libc::SOCK_STREAM)]);
+    assert_eq!(getaddrinfo(None, None, 0, 0), Err(libc::EAI_NONAME));
+    assert_eq!(getaddrinfo(None, Some(b"http\0"), 0, 0), Err(libc::EAI_SERVICE));
+
+    let message = unsafe { CStr::from_ptr(libc::gai_strerror(libc::EAI_NONAME)) };
+    assert_eq!(message.to_str().unwrap(), "Name or service not known");
+}
+
+fn test_std() {
+    let addrs: Vec<SocketAddr> = ("localhost", 80).to_socket_addrs().unwrap().collect();
+    assert_eq!(addrs, ["127.0.0.1:80".parse().unwrap()]);
+    let addrs: Vec<SocketAddr> = "example.com:443".to_socket_addrs().unwrap().collect();
+    assert_eq!(addrs, ["93.184.216.34:443".parse().unwrap()]);
+    assert!("example.org:443".to_socket_addrs().is_err());
+}
+
+fn main() {
+    test_libc();
+    test_std();
+}
@@ -0,0 +1,32 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-isatty=true -Zmiri-terminal-size=100x40
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn main() {
+    unsafe {
+        // The standard streams are terminals, even with isolation.
+        for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            assert_eq!(libc::isatty(fd), 1);
+            let mut winsize: libc::winsize = std::mem::zeroed();
+            assert_eq!(libc::ioctl(fd, libc::TIOCGWINSZ, &mut winsize), 0);
+            assert_eq!((winsize.ws_col, winsize.ws_row), (100, 40));
+        }
+
+        // Other file descriptors are not.
+        let mut fds = [-1, -1];
+        assert_eq!(libc::pipe(fds.as_mut_ptr()), 0);
+        assert_eq!(libc::isatty(fds[0]), 0);
+        assert_eq!(errno(), libc::ENOTTY);
+        let mut winsize: libc::winsize = std::mem::zeroed();
+        assert_eq!(libc::ioctl(fds[0], libc::TIOCGWINSZ, &mut winsize), -1);
+        assert_eq!(errno(), libc::ENOTTY);
+        assert_eq!(libc::close(fds[0]), 0);
+        assert_eq!(libc::close(fds[1]), 0);
+
+        assert_eq!(libc::isatty(-1), 0);
+        assert_eq!(errno(), libc::EBADF);
+    }
+}
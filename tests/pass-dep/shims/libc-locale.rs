@@ -0,0 +1,73 @@
+//@ignore-target-windows: No libc on Windows
+
+use std::ffi::CStr;
+use std::ptr;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn setlocale(category: i32, locale: &[u8]) -> Option<&'static CStr> {
+    let locale = if locale.is_empty() { ptr::null() } else { locale.as_ptr().cast() };
+    let name = unsafe { libc::setlocale(category, locale) };
+    (!name.is_null()).then(|| unsafe { CStr::from_ptr(name) })
+}
+
+fn test_setlocale() {
+    let c = Some(CStr::from_bytes_with_nul(b"C\0").unwrap());
+    assert_eq!(setlocale(libc::LC_ALL, b"\0"), c);
+    assert_eq!(setlocale(libc::LC_NUMERIC, b"POSIX\0"), c);
+    assert_eq!(setlocale(libc::LC_CTYPE, b"C\0"), c);
+    // Querying the current locale.
+    assert_eq!(setlocale(libc::LC_TIME, b""), c);
+    // There are no other locales.
+    assert_eq!(setlocale(libc::LC_ALL, b"de_DE.UTF-8\0"), None);
+    assert_eq!(setlocale(12345, b"C\0"), None);
+}
+
+fn test_localeconv() {
+    unsafe {
+        let lconv = libc::localeconv();
+        assert_eq!(CStr::from_ptr((*lconv).decimal_point).to_bytes(), b".");
+        assert_eq!(CStr::from_ptr((*lconv).thousands_sep).to_bytes(), b"");
+        assert_eq!(CStr::from_ptr((*lconv).currency_symbol).to_bytes(), b"");
+        assert_eq!((*lconv).frac_digits, 127);
+        assert_eq!((*lconv).int_n_sign_posn, 127);
+        assert_eq!(libc::localeconv(), lconv);
+    }
+}
+
+fn test_newlocale() {
+    let global = -1isize as libc::locale_t;
+    unsafe {
+        let locale = libc::newlocale(libc::LC_ALL_MASK, b"C\0".as_ptr().cast(), ptr::null_mut());
+        assert!(!locale.is_null());
+        let other = libc::newlocale(libc::LC_NUMERIC_MASK, b"POSIX\0".as_ptr().cast(), locale);
+        assert_eq!(other, locale);
+        let missing = b"fr_FR\0".as_ptr().cast();
+        assert!(libc::newlocale(libc::LC_ALL_MASK, missing, ptr::null_mut()).is_null());
+        assert_eq!(errno(), libc::ENOENT);
+
+        // Threads start out with the global locale.
+        assert_eq!(libc::uselocale(ptr::null_mut()), global);
+        assert_eq!(libc::uselocale(locale), global);
+        assert_eq!(libc::uselocale(ptr::null_mut()), locale);
+        let locale_ptr = locale as usize;
+        std::thread::spawn(move || {
+            assert_eq!(libc::uselocale(ptr::null_mut()), global);
+            assert_eq!(libc::duplocale(locale_ptr as libc::locale_t) as usize, locale_ptr);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(libc::uselocale(global), locale);
+        assert_eq!(libc::uselocale(ptr::null_mut()), global);
+
+        libc::freelocale(locale);
+    }
+}
+
+fn main() {
+    test_setlocale();
+    test_localeconv();
+    test_newlocale();
+}
@@ -73,6 +73,34 @@ fn test_posix_realpath_noalloc() {
     remove_file(&path).unwrap();
 }
 
+/// Test that `realpath` resolves symbolic links.
+fn test_posix_realpath_symlink() {
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = tmp().join("miri_test_libc_posix_realpath_target");
+    let link = tmp().join("miri_test_libc_posix_realpath_link");
+    // Cleanup before test.
+    remove_file(&path).ok();
+    remove_file(&link).ok();
+    drop(File::create(&path).unwrap());
+    std::os::unix::fs::symlink(&path, &link).unwrap();
+
+    let c_link = CString::new(link.as_os_str().as_bytes()).expect("CString::new failed");
+    let resolved = unsafe {
+        let r = libc::realpath(c_link.as_ptr(), std::ptr::null_mut());
+        assert!(!r.is_null());
+        let resolved = CStr::from_ptr(r).to_bytes().to_vec();
+        libc::free(r as *mut _);
+        resolved
+    };
+    assert_eq!(resolved, std::fs::canonicalize(&path).unwrap().as_os_str().as_bytes());
+
+    // Cleanup after test.
+    remove_file(&link).unwrap();
+    remove_file(&path).unwrap();
+}
+
 /// Test failure cases for `realpath`.
 fn test_posix_realpath_errors() {
     use std::ffi::CString;
@@ -282,15 +310,54 @@ fn test_posix_mkstemp() {
     }
 }
 
+fn test_posix_mkostemp_mkdtemp() {
+    use std::ffi::{CString, OsStr};
+    use std::io::Write;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::io::FromRawFd;
+    use std::path::Path;
+
+    let ptr = CString::new("fooXXXXXX").unwrap().into_raw();
+    let fd = unsafe { libc::mkostemp(ptr, libc::O_APPEND | libc::O_CLOEXEC) };
+    let template = unsafe { CString::from_raw(ptr) };
+    assert!(fd > 0);
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(b"hello").unwrap();
+    let path = std::env::temp_dir().join(OsStr::from_bytes(template.to_bytes()));
+    assert_eq!(path.metadata().unwrap().permissions().mode() & 0o777, 0o600);
+    drop(file);
+    remove_file(&path).unwrap();
+
+    let ptr = CString::new("barXXXXXX").unwrap().into_raw();
+    // The template itself is returned.
+    assert_eq!(unsafe { libc::mkdtemp(ptr) }, ptr);
+    let template = unsafe { CString::from_raw(ptr) };
+    let name: &Path = OsStr::from_bytes(template.to_bytes()).as_ref();
+    assert!(name.to_str().unwrap().starts_with("bar"));
+    assert_ne!(name.to_str().unwrap(), "barXXXXXX");
+    let path = std::env::temp_dir().join(name);
+    assert!(path.is_dir());
+    assert_eq!(path.metadata().unwrap().permissions().mode() & 0o777, 0o700);
+    std::fs::remove_dir(&path).unwrap();
+
+    let ptr = CString::new("bazXX").unwrap().into_raw();
+    assert!(unsafe { libc::mkdtemp(ptr) }.is_null());
+    let _ = unsafe { CString::from_raw(ptr) };
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EINVAL));
+}
+
 fn main() {
     #[cfg(any(target_os = "linux"))]
     test_posix_fadvise();
 
     test_posix_gettimeofday();
     test_posix_mkstemp();
+    test_posix_mkostemp_mkdtemp();
 
     test_posix_realpath_alloc();
     test_posix_realpath_noalloc();
+    test_posix_realpath_symlink();
     test_posix_realpath_errors();
 
     #[cfg(any(target_os = "linux"))]
@@ -0,0 +1,70 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-num-cpus=70
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn test_sysconf() {
+    assert_eq!(unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) }, 70);
+    assert_eq!(unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) }, 70);
+}
+
+#[cfg(target_os = "linux")]
+fn test_sched_getaffinity() {
+    use std::mem::{size_of, zeroed};
+
+    let mut set: libc::cpu_set_t = unsafe { zeroed() };
+    assert_eq!(unsafe { libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut set) }, 0);
+    assert_eq!(unsafe { libc::CPU_COUNT(&set) }, 70);
+    assert!(unsafe { libc::CPU_ISSET(69, &set) });
+    assert!(!unsafe { libc::CPU_ISSET(70, &set) });
+
+    // The mask has to have room for all CPUs.
+    let res = unsafe { libc::sched_getaffinity(0, size_of::<libc::c_ulong>(), &mut set) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EINVAL);
+}
+
+#[cfg(target_os = "macos")]
+fn test_sysctlbyname() {
+    use std::mem::size_of;
+
+    let mut ncpu: libc::c_int = 0;
+    let mut len = size_of::<libc::c_int>();
+    let res = unsafe {
+        libc::sysctlbyname(
+            "hw.logicalcpu\0".as_ptr().cast(),
+            (&mut ncpu as *mut libc::c_int).cast(),
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    assert_eq!(res, 0);
+    assert_eq!((ncpu, len), (70, size_of::<libc::c_int>()));
+
+    // The buffer has to be large enough.
+    let mut len = 1;
+    let res = unsafe {
+        libc::sysctlbyname(
+            "hw.ncpu\0".as_ptr().cast(),
+            (&mut ncpu as *mut libc::c_int).cast(),
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::ENOMEM);
+}
+
+fn main() {
+    test_sysconf();
+    #[cfg(target_os = "linux")]
+    test_sched_getaffinity();
+    #[cfg(target_os = "macos")]
+    test_sysctlbyname();
+    assert_eq!(std::thread::available_parallelism().unwrap().get(), 70);
+}
@@ -0,0 +1,79 @@
+//@ignore-target-windows: File handling is not implemented yet
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::fs::{remove_file, File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn tmp() -> PathBuf {
+    std::env::var("MIRI_TEMP")
+        .map(|tmp| {
+            // MIRI_TEMP is set outside of our emulated
+            // program, so it may have path separators that don't
+            // correspond to our target platform. We normalize them here
+            // before constructing a `PathBuf`
+            PathBuf::from(tmp.replace("\\", "/"))
+        })
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+fn iovec(buf: &[u8]) -> libc::iovec {
+    libc::iovec { iov_base: buf.as_ptr() as *mut _, iov_len: buf.len() }
+}
+
+fn iovec_mut(buf: &mut [u8]) -> libc::iovec {
+    libc::iovec { iov_base: buf.as_mut_ptr().cast(), iov_len: buf.len() }
+}
+
+fn main() {
+    let path = tmp().join("miri_test_libc_pread.txt");
+    remove_file(&path).ok();
+    File::create(&path).unwrap().write_all(b"hello world").unwrap();
+
+    let mut file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+    let fd = file.as_raw_fd();
+    unsafe {
+        let mut buf = [0u8; 5];
+        assert_eq!(libc::pread(fd, buf.as_mut_ptr().cast(), 5, 6), 5);
+        assert_eq!(&buf, b"world");
+        assert_eq!(libc::pwrite(fd, b"W".as_ptr().cast(), 1, 6), 1);
+
+        // The vectored versions fill and drain the buffers in order.
+        let (mut a, mut b) = ([0u8; 3], [0u8; 4]);
+        let iov = [iovec_mut(&mut a), iovec_mut(&mut b)];
+        assert_eq!(libc::preadv(fd, iov.as_ptr(), 2, 4), 7);
+        assert_eq!((&a, &b), (b"o W", b"orld"));
+        let iov = [iovec(b"HE"), iovec(b"LLO")];
+        assert_eq!(libc::pwritev(fd, iov.as_ptr(), 2, 0), 5);
+
+        assert_eq!(libc::pread(fd, buf.as_mut_ptr().cast(), 5, -1), -1);
+        assert_eq!(errno(), libc::EINVAL);
+        assert_eq!(libc::preadv(fd, iov.as_ptr(), -1, 0), -1);
+        assert_eq!(errno(), libc::EINVAL);
+        assert_eq!(libc::pread(-1, buf.as_mut_ptr().cast(), 5, 0), -1);
+        assert_eq!(errno(), libc::EBADF);
+
+        #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+        {
+            assert_eq!(libc::syscall(libc::SYS_pread64, fd, buf.as_mut_ptr(), 5usize, 0i64), 5);
+            assert_eq!(&buf, b"HELLO");
+            assert_eq!(libc::syscall(libc::SYS_pwrite64, fd, b"!".as_ptr(), 1usize, 11i64), 1);
+        }
+    }
+
+    // None of this moved the file offset.
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+    assert_eq!(contents, "HELLO World!");
+    #[cfg(not(all(target_os = "linux", target_pointer_width = "64")))]
+    assert_eq!(contents, "HELLO World");
+
+    drop(file);
+    remove_file(&path).unwrap();
+}
@@ -0,0 +1,95 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-rlimit=nofile=8:16 -Zmiri-enforce-rlimit-nofile
+
+use std::mem::MaybeUninit;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn getrlimit(resource: libc::c_int) -> libc::rlimit {
+    let mut rlim = MaybeUninit::uninit();
+    assert_eq!(unsafe { libc::getrlimit(resource as _, rlim.as_mut_ptr()) }, 0);
+    unsafe { rlim.assume_init() }
+}
+
+fn setrlimit(resource: libc::c_int, rlim_cur: libc::rlim_t, rlim_max: libc::rlim_t) -> i32 {
+    unsafe { libc::setrlimit(resource as _, &libc::rlimit { rlim_cur, rlim_max }) }
+}
+
+fn test_limits() {
+    let stack = getrlimit(libc::RLIMIT_STACK as _);
+    assert_eq!(stack.rlim_cur, 8 * 1024 * 1024);
+    assert_eq!(stack.rlim_max, libc::RLIM_INFINITY);
+
+    let nofile = getrlimit(libc::RLIMIT_NOFILE as _);
+    assert_eq!((nofile.rlim_cur, nofile.rlim_max), (8, 16));
+
+    // The soft limit cannot exceed the hard limit, and the hard limit cannot be raised.
+    assert_eq!(setrlimit(libc::RLIMIT_NOFILE as _, 32, 16), -1);
+    assert_eq!(errno(), libc::EINVAL);
+    assert_eq!(setrlimit(libc::RLIMIT_NOFILE as _, 16, 32), -1);
+    assert_eq!(errno(), libc::EPERM);
+    assert_eq!(setrlimit(libc::RLIMIT_NOFILE as _, 16, 16), 0);
+    assert_eq!(setrlimit(libc::RLIMIT_NOFILE as _, 8, 12), 0);
+    let nofile = getrlimit(libc::RLIMIT_NOFILE as _);
+    assert_eq!((nofile.rlim_cur, nofile.rlim_max), (8, 12));
+
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let new_limit = libc::rlimit64 { rlim_cur: 0, rlim_max: libc::RLIM64_INFINITY };
+        let mut old_limit = MaybeUninit::uninit();
+        assert_eq!(libc::prlimit64(0, libc::RLIMIT_CORE, &new_limit, old_limit.as_mut_ptr()), 0);
+        let old_limit = old_limit.assume_init();
+        assert_eq!((old_limit.rlim_cur, old_limit.rlim_max), (0, libc::RLIM64_INFINITY));
+        // The process ID returned by `getpid` refers to the current process, even under isolation.
+        let pid = libc::getpid();
+        assert_eq!(libc::prlimit64(pid, libc::RLIMIT_CORE, &new_limit, std::ptr::null_mut()), 0);
+        assert_eq!(libc::prlimit64(1, libc::RLIMIT_CORE, &new_limit, std::ptr::null_mut()), -1);
+        assert_eq!(errno(), libc::ESRCH);
+    }
+}
+
+fn test_enforce_nofile() {
+    // With the standard streams, there is room for five more file descriptors.
+    let mut fds = [[0; 2]; 3];
+    unsafe {
+        assert_eq!(libc::pipe(fds[0].as_mut_ptr()), 0);
+        assert_eq!(libc::pipe(fds[1].as_mut_ptr()), 0);
+        assert_eq!(libc::pipe(fds[2].as_mut_ptr()), -1);
+        assert_eq!(errno(), libc::EMFILE);
+        let fd = libc::dup(fds[0][0]);
+        assert_eq!(fd, 7);
+        assert_eq!(libc::dup(fd), -1);
+        assert_eq!(errno(), libc::EMFILE);
+
+        // `F_DUPFD` has to stay below the limit as well.
+        assert_eq!(libc::close(fd), 0);
+        assert_eq!(libc::fcntl(fds[0][0], libc::F_DUPFD, 8), -1);
+        assert_eq!(errno(), libc::EINVAL);
+        let fd = libc::fcntl(fds[0][0], libc::F_DUPFD, 5);
+        assert_eq!(fd, 7);
+        assert_eq!(libc::fcntl(fds[0][0], libc::F_DUPFD, 5), -1);
+        assert_eq!(errno(), libc::EMFILE);
+
+        // Closing a file descriptor makes room again.
+        assert_eq!(libc::close(fd), 0);
+        for fd in fds[..2].iter().flatten() {
+            assert_eq!(libc::close(*fd), 0);
+        }
+        assert_eq!(libc::pipe(fds[2].as_mut_ptr()), 0);
+        assert_eq!(libc::close(fds[2][0]), 0);
+        assert_eq!(libc::close(fds[2][1]), 0);
+
+        // Raising the limit at runtime allows more file descriptors.
+        assert_eq!(setrlimit(libc::RLIMIT_NOFILE as _, 12, 12), 0);
+        let fd = libc::fcntl(0, libc::F_DUPFD, 10);
+        assert_eq!(fd, 10);
+        assert_eq!(libc::close(fd), 0);
+    }
+}
+
+fn main() {
+    test_limits();
+    test_enforce_nofile();
+}
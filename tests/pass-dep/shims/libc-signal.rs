@@ -0,0 +1,159 @@
+//@ignore-target-windows: No libc on Windows
+
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+static LAST_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn handler(sig: libc::c_int) {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+    LAST_SIGNAL.store(sig, Ordering::Relaxed);
+}
+
+extern "C" fn info_handler(sig: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    assert_eq!(unsafe { (*info).si_signo }, sig);
+    CALLS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Raises the signal again while it is being handled; the second delivery has to wait until the
+/// handler returns.
+extern "C" fn reraise_handler(sig: libc::c_int) {
+    let calls = CALLS.fetch_add(1, Ordering::Relaxed);
+    if calls == 0 {
+        assert_eq!(unsafe { libc::raise(sig) }, 0);
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    }
+}
+
+fn calls() -> usize {
+    CALLS.swap(0, Ordering::Relaxed)
+}
+
+fn set_action(sig: i32, handler: libc::sighandler_t, flags: i32) -> libc::sigaction {
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = flags;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut old_action: libc::sigaction = mem::zeroed();
+        assert_eq!(libc::sigaction(sig, &action, &mut old_action), 0);
+        old_action
+    }
+}
+
+fn sigset(signals: &[i32]) -> libc::sigset_t {
+    unsafe {
+        let mut set: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut set);
+        for &sig in signals {
+            libc::sigaddset(&mut set, sig);
+        }
+        set
+    }
+}
+
+fn test_sigset() {
+    unsafe {
+        let mut set = sigset(&[libc::SIGUSR1]);
+        assert_eq!(libc::sigismember(&set, libc::SIGUSR1), 1);
+        assert_eq!(libc::sigismember(&set, libc::SIGUSR2), 0);
+        libc::sigdelset(&mut set, libc::SIGUSR1);
+        assert_eq!(libc::sigismember(&set, libc::SIGUSR1), 0);
+        libc::sigfillset(&mut set);
+        assert_eq!(libc::sigismember(&set, libc::SIGUSR2), 1);
+        assert_eq!(libc::sigaddset(&mut set, 0), -1);
+    }
+}
+
+fn test_raise() {
+    let handler = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    let old_action = set_action(libc::SIGUSR1, handler, 0);
+    assert_eq!(old_action.sa_sigaction, libc::SIG_DFL);
+
+    // The handler runs before `raise` returns.
+    assert_eq!(unsafe { libc::raise(libc::SIGUSR1) }, 0);
+    assert_eq!(calls(), 1);
+    assert_eq!(LAST_SIGNAL.load(Ordering::Relaxed), libc::SIGUSR1);
+    assert_eq!(unsafe { libc::pthread_kill(libc::pthread_self(), libc::SIGUSR1) }, 0);
+    assert_eq!(calls(), 1);
+
+    // The handler is reported back.
+    let old_action = set_action(libc::SIGUSR1, libc::SIG_IGN, 0);
+    assert_eq!(old_action.sa_sigaction, handler);
+    assert_eq!(unsafe { libc::raise(libc::SIGUSR1) }, 0);
+    assert_eq!(calls(), 0);
+
+    // `signal` works just the same.
+    assert_eq!(unsafe { libc::signal(libc::SIGUSR1, handler) }, libc::SIG_IGN);
+    assert_eq!(unsafe { libc::raise(libc::SIGUSR1) }, 0);
+    assert_eq!(calls(), 1);
+    assert_eq!(unsafe { libc::signal(libc::SIGUSR1, libc::SIG_DFL) }, handler);
+
+    // Signals that are ignored by default do nothing.
+    assert_eq!(unsafe { libc::raise(libc::SIGCHLD) }, 0);
+}
+
+fn test_flags() {
+    let info_handler = info_handler
+        as extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void)
+        as libc::sighandler_t;
+    set_action(libc::SIGUSR2, info_handler, libc::SA_SIGINFO | libc::SA_RESETHAND);
+    assert_eq!(unsafe { libc::raise(libc::SIGUSR2) }, 0);
+    assert_eq!(calls(), 1);
+    // The handler was reset when the signal was delivered.
+    let old_action = set_action(libc::SIGUSR2, libc::SIG_DFL, 0);
+    assert_eq!(old_action.sa_sigaction, libc::SIG_DFL);
+
+    // A signal is blocked while its handler runs.
+    let reraise_handler = reraise_handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    set_action(libc::SIGUSR1, reraise_handler, 0);
+    assert_eq!(unsafe { libc::raise(libc::SIGUSR1) }, 0);
+    assert_eq!(calls(), 2);
+    set_action(libc::SIGUSR1, libc::SIG_DFL, 0);
+}
+
+fn test_mask() {
+    let handler = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    set_action(libc::SIGUSR1, handler, 0);
+
+    unsafe {
+        // A blocked signal stays pending.
+        let set = sigset(&[libc::SIGUSR1]);
+        assert_eq!(libc::sigprocmask(libc::SIG_BLOCK, &set, ptr::null_mut()), 0);
+        assert_eq!(libc::raise(libc::SIGUSR1), 0);
+        assert_eq!(calls(), 0);
+        let mut pending: libc::sigset_t = mem::zeroed();
+        assert_eq!(libc::sigpending(&mut pending), 0);
+        assert_eq!(libc::sigismember(&pending, libc::SIGUSR1), 1);
+
+        // New threads inherit the mask.
+        std::thread::spawn(|| {
+            let mut mask: libc::sigset_t = mem::zeroed();
+            assert_eq!(libc::pthread_sigmask(libc::SIG_BLOCK, ptr::null(), &mut mask), 0);
+            assert_eq!(libc::sigismember(&mask, libc::SIGUSR1), 1);
+        })
+        .join()
+        .unwrap();
+
+        // It is delivered once it is unblocked.
+        let mut old_set: libc::sigset_t = mem::zeroed();
+        assert_eq!(libc::pthread_sigmask(libc::SIG_UNBLOCK, &set, &mut old_set), 0);
+        assert_eq!(libc::sigismember(&old_set, libc::SIGUSR1), 1);
+        assert_eq!(calls(), 1);
+        assert_eq!(libc::sigpending(&mut pending), 0);
+        assert_eq!(libc::sigismember(&pending, libc::SIGUSR1), 0);
+
+        assert_eq!(libc::pthread_sigmask(12345, &set, ptr::null_mut()), libc::EINVAL);
+    }
+
+    set_action(libc::SIGUSR1, libc::SIG_DFL, 0);
+}
+
+fn main() {
+    test_sigset();
+    test_raise();
+    test_flags();
+    test_mask();
+}
@@ -0,0 +1,38 @@
+//@ignore-target-windows: No libc on Windows
+
+extern "Rust" {
+    fn miri_get_syslog(buf: *mut u8, len: usize) -> usize;
+}
+
+fn syslog() -> String {
+    let mut buf = [0u8; 256];
+    let len = unsafe { miri_get_syslog(buf.as_mut_ptr(), buf.len()) };
+    String::from_utf8(buf[..len].to_vec()).unwrap()
+}
+
+fn main() {
+    unsafe {
+        libc::openlog(b"daemon\0".as_ptr().cast(), libc::LOG_PID, libc::LOG_DAEMON);
+        let (fmt, name) = (b"%s has %d workers\0", b"server\0");
+        libc::syslog(libc::LOG_INFO, fmt.as_ptr().cast(), name.as_ptr(), 4);
+        let fmt = b"[%5d|%-4x|%#o|%04u|%.2f|%c|%%]\0";
+        libc::syslog(libc::LOG_ERR, fmt.as_ptr().cast(), -12, 255u32, 8u32, 7u32, 1.5f64, 65);
+
+        // Messages with a priority that is not in the mask are dropped.
+        let upto_info = (1 << (libc::LOG_INFO + 1)) - 1;
+        assert_eq!(libc::setlogmask(upto_info), 0xff);
+        libc::syslog(libc::LOG_DEBUG, b"dropped\0".as_ptr().cast());
+        assert_eq!(libc::setlogmask(0), upto_info);
+
+        libc::closelog();
+        libc::syslog(libc::LOG_NOTICE, b"%s\0".as_ptr().cast(), b"done\0".as_ptr());
+    }
+
+    let log = "daemon: server has 4 workers\ndaemon: [  -12|ff  |010|0007|1.50|A|%]\ndone\n";
+    assert_eq!(syslog(), log);
+
+    // Only the requested part of the log is written.
+    let mut buf = [0u8; 6];
+    assert_eq!(unsafe { miri_get_syslog(buf.as_mut_ptr(), buf.len()) }, log.len());
+    assert_eq!(&buf, b"daemon");
+}
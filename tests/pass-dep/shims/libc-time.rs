@@ -0,0 +1,88 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-tz-offset=3600
+
+use std::ffi::CStr;
+use std::mem;
+
+/// 2022-03-01 12:34:56 UTC, a Tuesday.
+const TIME: libc::time_t = 1646138096;
+
+extern "C" {
+    fn tzset();
+}
+
+fn zone(tm: &libc::tm) -> &str {
+    unsafe { CStr::from_ptr(tm.tm_zone) }.to_str().unwrap()
+}
+
+fn test_gmtime() {
+    unsafe {
+        let mut tm: libc::tm = mem::zeroed();
+        assert_eq!(libc::gmtime_r(&TIME, &mut tm), &mut tm as *mut _);
+        assert_eq!((tm.tm_year, tm.tm_mon, tm.tm_mday), (122, 2, 1));
+        assert_eq!((tm.tm_hour, tm.tm_min, tm.tm_sec), (12, 34, 56));
+        assert_eq!((tm.tm_wday, tm.tm_yday, tm.tm_isdst), (2, 59, 0));
+        assert_eq!(tm.tm_gmtoff, 0);
+        assert_eq!(zone(&tm), "GMT");
+        let gmt = tm.tm_zone;
+
+        // Times before the epoch work too: this is 1969-12-31 23:59:59, a Wednesday.
+        assert!(!libc::gmtime_r(&-1, &mut tm).is_null());
+        // The abbreviation of the time zone is not allocated again.
+        assert_eq!(tm.tm_zone, gmt);
+        assert_eq!((tm.tm_year, tm.tm_mon, tm.tm_mday), (69, 11, 31));
+        assert_eq!((tm.tm_hour, tm.tm_min, tm.tm_sec), (23, 59, 59));
+        assert_eq!((tm.tm_wday, tm.tm_yday), (3, 364));
+
+        // A leap day.
+        assert!(!libc::gmtime_r(&951782400, &mut tm).is_null());
+        assert_eq!((tm.tm_year, tm.tm_mon, tm.tm_mday, tm.tm_yday), (100, 1, 29, 59));
+    }
+}
+
+fn test_localtime() {
+    unsafe {
+        let mut tm: libc::tm = mem::zeroed();
+        tzset();
+        assert_eq!(libc::localtime_r(&TIME, &mut tm), &mut tm as *mut _);
+        assert_eq!((tm.tm_year, tm.tm_mon, tm.tm_mday), (122, 2, 1));
+        assert_eq!((tm.tm_hour, tm.tm_min, tm.tm_sec), (13, 34, 56));
+        assert_eq!(tm.tm_isdst, 0);
+        assert_eq!(tm.tm_gmtoff, 3600);
+        assert_eq!(zone(&tm), "+0100");
+    }
+}
+
+fn test_mktime() {
+    unsafe {
+        let mut tm: libc::tm = mem::zeroed();
+        assert!(!libc::localtime_r(&TIME, &mut tm).is_null());
+        assert_eq!(libc::mktime(&mut tm), TIME);
+        assert!(!libc::gmtime_r(&TIME, &mut tm).is_null());
+        assert_eq!(libc::timegm(&mut tm), TIME);
+
+        // Fields out of their range are normalized: 2022-02-30 24:00:00 is 2022-03-03 00:00:00.
+        let mut tm: libc::tm = mem::zeroed();
+        tm.tm_year = 122;
+        tm.tm_mon = 1;
+        tm.tm_mday = 30;
+        tm.tm_hour = 24;
+        tm.tm_isdst = -1;
+        assert_eq!(libc::mktime(&mut tm), 1646262000);
+        assert_eq!((tm.tm_year, tm.tm_mon, tm.tm_mday, tm.tm_hour), (122, 2, 3, 0));
+        assert_eq!((tm.tm_wday, tm.tm_yday), (4, 61));
+        assert_eq!(tm.tm_gmtoff, 3600);
+
+        // A negative month counts back into the year before.
+        tm.tm_mon = -1;
+        tm.tm_mday = 1;
+        assert_eq!(libc::timegm(&mut tm), 1638316800);
+        assert_eq!((tm.tm_year, tm.tm_mon, tm.tm_mday), (121, 11, 1));
+    }
+}
+
+fn main() {
+    test_gmtime();
+    test_localtime();
+    test_mktime();
+}
@@ -0,0 +1,45 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-uid=1234 -Zmiri-gid=5678
+
+use std::ffi::CStr;
+use std::mem::MaybeUninit;
+use std::ptr;
+
+fn main() {
+    unsafe {
+        assert_eq!(libc::getuid(), 1234);
+        assert_eq!(libc::geteuid(), 1234);
+        assert_eq!(libc::getgid(), 5678);
+        assert_eq!(libc::getegid(), 5678);
+
+        let mut pwd = MaybeUninit::<libc::passwd>::uninit();
+        let mut buf = [0 as libc::c_char; 64];
+        let mut result = ptr::null_mut();
+        let error =
+            libc::getpwuid_r(1234, pwd.as_mut_ptr(), buf.as_mut_ptr(), buf.len(), &mut result);
+        assert_eq!(error, 0);
+        assert_eq!(result, pwd.as_mut_ptr());
+        let pwd = pwd.assume_init();
+        assert_eq!(CStr::from_ptr(pwd.pw_name).to_bytes(), b"miri");
+        assert_eq!(CStr::from_ptr(pwd.pw_dir).to_bytes(), b"/home/miri");
+        assert_eq!((pwd.pw_uid, pwd.pw_gid), (1234, 5678));
+
+        // The strings do not fit.
+        let mut pwd = MaybeUninit::<libc::passwd>::uninit();
+        let error = libc::getpwuid_r(1234, pwd.as_mut_ptr(), buf.as_mut_ptr(), 8, &mut result);
+        assert_eq!(error, libc::ERANGE);
+        assert!(result.is_null());
+
+        // Other users do not exist.
+        let error =
+            libc::getpwuid_r(0, pwd.as_mut_ptr(), buf.as_mut_ptr(), buf.len(), &mut result);
+        assert_eq!(error, 0);
+        assert!(result.is_null());
+    }
+
+    // `home_dir` falls back to the user's home directory.
+    std::env::remove_var("HOME");
+    #[allow(deprecated)]
+    let home = std::env::home_dir().unwrap();
+    assert_eq!(home.to_str().unwrap(), "/home/miri");
+}
@@ -0,0 +1,63 @@
+//@only-target-linux
+// Isolation is enabled, so all sleeps use Miri's virtual clock.
+
+use std::ptr;
+use std::time::{Duration, Instant};
+
+fn timespec(duration: Duration) -> libc::timespec {
+    libc::timespec { tv_sec: duration.as_secs() as _, tv_nsec: duration.subsec_nanos() as _ }
+}
+
+fn monotonic_now() -> Duration {
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    assert_eq!(unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) }, 0);
+    Duration::new(now.tv_sec as u64, now.tv_nsec as u32)
+}
+
+fn test_relative() {
+    for clock in [libc::CLOCK_MONOTONIC, libc::CLOCK_REALTIME] {
+        let before = Instant::now();
+        let req = timespec(Duration::from_secs(3600));
+        assert_eq!(unsafe { libc::clock_nanosleep(clock, 0, &req, ptr::null_mut()) }, 0);
+        let elapsed = before.elapsed();
+        // Nobody else is running, so the virtual clock jumps right to the end of the sleep.
+        assert!(elapsed >= Duration::from_secs(3600) && elapsed < Duration::from_secs(3601));
+    }
+}
+
+fn test_absolute() {
+    let deadline = monotonic_now() + Duration::from_millis(100);
+    let req = timespec(deadline);
+    let res = unsafe {
+        libc::clock_nanosleep(libc::CLOCK_MONOTONIC, libc::TIMER_ABSTIME, &req, ptr::null_mut())
+    };
+    assert_eq!(res, 0);
+    assert!(monotonic_now() >= deadline);
+
+    // A deadline in the past returns right away.
+    let before = Instant::now();
+    let req = timespec(Duration::ZERO);
+    let res = unsafe {
+        libc::clock_nanosleep(libc::CLOCK_MONOTONIC, libc::TIMER_ABSTIME, &req, ptr::null_mut())
+    };
+    assert_eq!(res, 0);
+    assert!(before.elapsed() < Duration::from_millis(1));
+}
+
+fn test_errors() {
+    // Errors are returned rather than stored in `errno`.
+    let req = libc::timespec { tv_sec: 0, tv_nsec: 1_000_000_000 };
+    let res = unsafe { libc::clock_nanosleep(libc::CLOCK_MONOTONIC, 0, &req, ptr::null_mut()) };
+    assert_eq!(res, libc::EINVAL);
+    let req = timespec(Duration::from_millis(1));
+    let res = unsafe { libc::clock_nanosleep(libc::CLOCK_MONOTONIC, 0x100, &req, ptr::null_mut()) };
+    assert_eq!(res, libc::EINVAL);
+    let res = unsafe { libc::clock_nanosleep(12345, 0, &req, ptr::null_mut()) };
+    assert_eq!(res, libc::EINVAL);
+}
+
+fn main() {
+    test_relative();
+    test_absolute();
+    test_errors();
+}
@@ -0,0 +1,29 @@
+//@only-target-linux
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn main() {
+    unsafe {
+        let page_size = libc::getauxval(libc::AT_PAGESZ);
+        assert_eq!(page_size, libc::sysconf(libc::_SC_PAGESIZE) as libc::c_ulong);
+
+        // The hardware capabilities match the target features.
+        let hwcap = libc::getauxval(libc::AT_HWCAP);
+        if cfg!(target_arch = "x86_64") {
+            assert_eq!(hwcap & (1 << 26) != 0, cfg!(target_feature = "sse2"));
+        }
+        if cfg!(target_arch = "aarch64") {
+            assert_eq!(hwcap & (1 << 1) != 0, cfg!(target_feature = "neon"));
+            assert_eq!(hwcap & (1 << 3) != 0, cfg!(target_feature = "aes"));
+        }
+
+        assert_eq!(libc::getauxval(libc::AT_UID), libc::c_ulong::from(libc::getuid()));
+        assert_eq!(libc::getauxval(libc::AT_SECURE), 0);
+
+        // Entries that do not exist.
+        assert_eq!(libc::getauxval(libc::AT_EXECFN), 0);
+        assert_eq!(errno(), libc::ENOENT);
+    }
+}
@@ -0,0 +1,89 @@
+//@only-target-linux
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::ffi::{CStr, CString};
+use std::fs::{create_dir, remove_dir_all, File};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn tmp() -> PathBuf {
+    std::env::var("MIRI_TEMP")
+        .map(|tmp| {
+            // MIRI_TEMP is set outside of our emulated
+            // program, so it may have path separators that don't
+            // correspond to our target platform. We normalize them here
+            // before constructing a `PathBuf`
+            PathBuf::from(tmp.replace("\\", "/"))
+        })
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+fn getdents64(fd: i32, buf: &mut [u64]) -> isize {
+    let len = std::mem::size_of_val(buf);
+    unsafe { libc::syscall(libc::SYS_getdents64, fd, buf.as_mut_ptr(), len) as isize }
+}
+
+/// Returns the names of the `linux_dirent64` records in the first `len` bytes of `buf`.
+fn names(buf: &[u64], len: isize) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut offset = 0;
+    while offset < len as usize {
+        unsafe {
+            let record = buf.as_ptr().cast::<u8>().add(offset);
+            let reclen = record.add(16).cast::<u16>().read();
+            assert_eq!(reclen % 8, 0);
+            let name = CStr::from_ptr(record.add(19).cast());
+            names.push(name.to_str().unwrap().to_owned());
+            offset += reclen as usize;
+        }
+    }
+    names
+}
+
+fn main() {
+    let dir_path = tmp().join("miri_test_linux_getdents64");
+    remove_dir_all(&dir_path).ok();
+    create_dir(&dir_path).unwrap();
+    for name in ["a", "bb", "ccc"] {
+        File::create(dir_path.join(name)).unwrap();
+    }
+
+    let c_path = CString::new(dir_path.as_os_str().as_bytes()).unwrap();
+    let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY) };
+    assert!(fd > 0);
+
+    // A buffer too small for any record is an error.
+    let mut buf = [0u64; 2];
+    assert_eq!(getdents64(fd, &mut buf), -1);
+    assert_eq!(errno(), libc::EINVAL);
+
+    // Records only take up as much space as they need, and are not lost when they do not fit.
+    // Each of the names fits into a 24 byte record.
+    let mut buf = [0u64; 3];
+    let len = getdents64(fd, &mut buf);
+    assert_eq!(len, 24);
+    let mut all = names(&buf, len);
+    let mut buf = [0u64; 128];
+    let len = getdents64(fd, &mut buf);
+    all.extend(names(&buf, len));
+    all.sort();
+    assert_eq!(all, ["a", "bb", "ccc"]);
+    // The end of the directory is reported as a length of 0.
+    assert_eq!(getdents64(fd, &mut buf), 0);
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+
+    // Files are not directories.
+    let c_path = CString::new(dir_path.join("a").as_os_str().as_bytes()).unwrap();
+    let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_RDONLY) };
+    assert_eq!(getdents64(fd, &mut buf), -1);
+    assert_eq!(errno(), libc::ENOTDIR);
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+    assert_eq!(unsafe { libc::open(c_path.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY) }, -1);
+    assert_eq!(errno(), libc::ENOTDIR);
+
+    remove_dir_all(&dir_path).unwrap();
+}
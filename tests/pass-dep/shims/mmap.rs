@@ -0,0 +1,255 @@
+//@ignore-target-windows: No libc on Windows
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::fs::{remove_file, File};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::{ptr, slice};
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn tmp() -> PathBuf {
+    std::env::var("MIRI_TEMP")
+        .map(|tmp| {
+            // MIRI_TEMP is set outside of our emulated
+            // program, so it may have path separators that don't
+            // correspond to our target platform. We normalize them here
+            // before constructing a `PathBuf`
+            PathBuf::from(tmp.replace("\\", "/"))
+        })
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+fn test_anonymous() {
+    let page_size = page_size();
+    unsafe {
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            3 * page_size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_ne!(ptr, libc::MAP_FAILED);
+        assert_eq!(ptr as usize % page_size, 0);
+        let bytes = slice::from_raw_parts_mut(ptr.cast::<u8>(), 3 * page_size);
+        assert!(bytes.iter().all(|&b| b == 0));
+        bytes.fill(1);
+
+        // Unmapping the middle page leaves the others usable.
+        assert_eq!(libc::munmap(ptr.cast::<u8>().add(page_size).cast(), page_size), 0);
+        let first = ptr.cast::<u8>();
+        let last = first.add(2 * page_size);
+        assert_eq!(*first, 1);
+        *last.add(page_size - 1) = 2;
+        assert_eq!(*last.add(page_size - 1), 2);
+
+        // The length is rounded up to whole pages.
+        assert_eq!(libc::munmap(first.cast(), 1), 0);
+        assert_eq!(libc::munmap(last.cast(), page_size), 0);
+    }
+}
+
+fn test_mprotect() {
+    let page_size = page_size();
+    unsafe {
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            2 * page_size,
+            libc::PROT_NONE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_ne!(ptr, libc::MAP_FAILED);
+        let first = ptr.cast::<u8>();
+        let second = first.add(page_size);
+
+        // Reserved memory can be made accessible page by page.
+        assert_eq!(libc::mprotect(ptr, page_size, libc::PROT_READ | libc::PROT_WRITE), 0);
+        *first = 1;
+        assert_eq!(libc::mprotect(ptr, page_size, libc::PROT_READ), 0);
+        assert_eq!(*first, 1);
+        assert_eq!(libc::mprotect(second.cast(), 1, libc::PROT_READ | libc::PROT_WRITE), 0);
+        *second.add(page_size - 1) = 2;
+
+        // Unmapped pages cannot be protected.
+        assert_eq!(libc::munmap(ptr, page_size), 0);
+        assert_eq!(libc::mprotect(ptr, 2 * page_size, libc::PROT_READ), -1);
+        assert_eq!(errno(), libc::ENOMEM);
+        assert_eq!(libc::mprotect(second.add(1).cast(), 1, libc::PROT_READ), -1);
+        assert_eq!(errno(), libc::EINVAL);
+
+        assert_eq!(libc::munmap(second.cast(), page_size), 0);
+    }
+}
+
+fn test_madvise() {
+    let page_size = page_size();
+    unsafe {
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            3 * page_size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_ne!(ptr, libc::MAP_FAILED);
+        let bytes = slice::from_raw_parts_mut(ptr.cast::<u8>(), 3 * page_size);
+        bytes.fill(1);
+
+        // Hints leave the contents alone.
+        assert_eq!(libc::madvise(ptr, 3 * page_size, libc::MADV_WILLNEED), 0);
+        assert_eq!(libc::posix_madvise(ptr, 3 * page_size, libc::POSIX_MADV_DONTNEED), 0);
+        assert!(bytes.iter().all(|&b| b == 1));
+
+        // Discarded pages read as zeros, even if they are read-only.
+        let second = ptr.cast::<u8>().add(page_size);
+        assert_eq!(libc::mprotect(second.cast(), page_size, libc::PROT_READ), 0);
+        assert_eq!(libc::madvise(second.cast(), page_size, libc::MADV_DONTNEED), 0);
+        let bytes = slice::from_raw_parts(ptr.cast::<u8>(), 3 * page_size);
+        assert!(bytes[..page_size].iter().all(|&b| b == 1));
+        assert!(bytes[page_size..2 * page_size].iter().all(|&b| b == 0));
+        assert!(bytes[2 * page_size..].iter().all(|&b| b == 1));
+
+        // The range has to start at a page and be mapped.
+        assert_eq!(libc::madvise(second.add(1).cast(), 1, libc::MADV_NORMAL), -1);
+        assert_eq!(errno(), libc::EINVAL);
+        let res = libc::posix_madvise(second.add(1).cast(), 1, libc::POSIX_MADV_NORMAL);
+        assert_eq!(res, libc::EINVAL);
+        assert_eq!(libc::madvise(ptr, page_size, 12345), -1);
+        assert_eq!(errno(), libc::EINVAL);
+        assert_eq!(libc::munmap(second.cast(), page_size), 0);
+        assert_eq!(libc::madvise(ptr, 3 * page_size, libc::MADV_NORMAL), -1);
+        assert_eq!(errno(), libc::ENOMEM);
+        assert_eq!(libc::posix_madvise(ptr, 3 * page_size, libc::POSIX_MADV_NORMAL), libc::ENOMEM);
+
+        assert_eq!(libc::munmap(ptr, 3 * page_size), 0);
+    }
+}
+
+fn test_errors() {
+    let page_size = page_size();
+    unsafe {
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            0,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_eq!(ptr, libc::MAP_FAILED);
+        assert_eq!(errno(), libc::EINVAL);
+
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            page_size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_eq!(ptr, libc::MAP_FAILED);
+        assert_eq!(errno(), libc::EINVAL);
+
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            page_size,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            -1,
+            0,
+        );
+        assert_eq!(ptr, libc::MAP_FAILED);
+        assert_eq!(errno(), libc::EBADF);
+
+        // A mapping that is too large to allocate fails instead of aborting Miri.
+        #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+        {
+            let ptr = libc::mmap(
+                ptr::null_mut(),
+                1 << 60,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            );
+            assert_eq!(ptr, libc::MAP_FAILED);
+            assert_eq!(errno(), libc::ENOMEM);
+        }
+
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            page_size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_ne!(ptr, libc::MAP_FAILED);
+        // The address has to be page-aligned.
+        assert_eq!(libc::munmap(ptr.cast::<u8>().add(1).cast(), page_size - 1), -1);
+        assert_eq!(errno(), libc::EINVAL);
+        assert_eq!(libc::munmap(ptr, page_size), 0);
+    }
+}
+
+fn test_file() {
+    let page_size = page_size();
+    let path = tmp().join("miri_test_mmap.txt");
+    remove_file(&path).ok();
+    let mut contents = vec![b'a'; page_size];
+    contents.extend_from_slice(b"hello");
+    File::create(&path).unwrap().write_all(&contents).unwrap();
+
+    let file = File::open(&path).unwrap();
+    unsafe {
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            page_size + 5,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            page_size as libc::off_t,
+        );
+        assert_ne!(ptr, libc::MAP_FAILED);
+        // The rest of the last page is zeroed.
+        let bytes = slice::from_raw_parts(ptr.cast::<u8>(), 2 * page_size);
+        assert_eq!(&bytes[..5], b"hello");
+        assert!(bytes[5..].iter().all(|&b| b == 0));
+        assert_eq!(libc::munmap(ptr, 2 * page_size), 0);
+
+        // The offset has to be page-aligned.
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            page_size,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            1,
+        );
+        assert_eq!(ptr, libc::MAP_FAILED);
+        assert_eq!(errno(), libc::EINVAL);
+    }
+
+    drop(file);
+    remove_file(&path).unwrap();
+}
+
+fn main() {
+    test_anonymous();
+    test_mprotect();
+    test_madvise();
+    test_errors();
+    test_file();
+}
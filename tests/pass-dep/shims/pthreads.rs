@@ -5,9 +5,13 @@ fn main() {
     test_mutex_libc_init_normal();
     test_mutex_libc_init_errorcheck();
     test_rwlock_libc_static_initializer();
+    test_rwlock_libc_init();
+    test_pthread_kill_signal_0();
 
     #[cfg(any(target_os = "linux"))]
     test_mutex_libc_static_initializer_recursive();
+    #[cfg(any(target_os = "linux"))]
+    test_prctl_thread_name();
 }
 
 fn test_mutex_libc_init_recursive() {
@@ -125,3 +129,73 @@ fn test_rwlock_libc_static_initializer() {
         assert_eq!(libc::pthread_rwlock_destroy(rw.get()), 0);
     }
 }
+
+fn test_rwlock_libc_init() {
+    unsafe {
+        let mut attr: libc::pthread_rwlockattr_t = std::mem::zeroed();
+        assert_eq!(libc::pthread_rwlockattr_init(&mut attr as *mut _), 0);
+        let mut pshared = -1;
+        assert_eq!(libc::pthread_rwlockattr_getpshared(&attr as *const _, &mut pshared), 0);
+        assert_eq!(pshared, libc::PTHREAD_PROCESS_PRIVATE);
+        assert_eq!(
+            libc::pthread_rwlockattr_setpshared(&mut attr as *mut _, 0x12345678),
+            libc::EINVAL,
+        );
+        assert_eq!(
+            libc::pthread_rwlockattr_setpshared(&mut attr as *mut _, libc::PTHREAD_PROCESS_SHARED),
+            0,
+        );
+        assert_eq!(libc::pthread_rwlockattr_getpshared(&attr as *const _, &mut pshared), 0);
+        assert_eq!(pshared, libc::PTHREAD_PROCESS_SHARED);
+
+        let mut rw: libc::pthread_rwlock_t = std::mem::zeroed();
+        assert_eq!(libc::pthread_rwlock_init(&mut rw as *mut _, &attr as *const _), 0);
+        assert_eq!(libc::pthread_rwlockattr_destroy(&mut attr as *mut _), 0);
+        assert_eq!(libc::pthread_rwlock_rdlock(&mut rw as *mut _), 0);
+        assert_eq!(libc::pthread_rwlock_trywrlock(&mut rw as *mut _), libc::EBUSY);
+        assert_eq!(libc::pthread_rwlock_unlock(&mut rw as *mut _), 0);
+        assert_eq!(libc::pthread_rwlock_wrlock(&mut rw as *mut _), 0);
+        assert_eq!(libc::pthread_rwlock_unlock(&mut rw as *mut _), 0);
+        assert_eq!(libc::pthread_rwlock_destroy(&mut rw as *mut _), 0);
+
+        // A NULL attribute gives the default attributes.
+        assert_eq!(libc::pthread_rwlock_init(&mut rw as *mut _, std::ptr::null()), 0);
+        assert_eq!(libc::pthread_rwlock_wrlock(&mut rw as *mut _), 0);
+        assert_eq!(libc::pthread_rwlock_unlock(&mut rw as *mut _), 0);
+        assert_eq!(libc::pthread_rwlock_destroy(&mut rw as *mut _), 0);
+    }
+}
+
+fn test_pthread_kill_signal_0() {
+    use std::sync::mpsc;
+
+    unsafe {
+        // Signal 0 checks whether the thread is still running.
+        assert_eq!(libc::pthread_kill(libc::pthread_self(), 0), 0);
+
+        let (id_tx, id_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            id_tx.send(libc::pthread_self()).unwrap();
+            done_rx.recv().unwrap();
+        });
+        let thread = id_rx.recv().unwrap();
+        assert_eq!(libc::pthread_kill(thread, 0), 0);
+        done_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+}
+
+#[cfg(any(target_os = "linux"))]
+fn test_prctl_thread_name() {
+    std::thread::spawn(|| unsafe {
+        // The name is truncated to 15 bytes.
+        let name = b"a very long thread name\0";
+        assert_eq!(libc::prctl(libc::PR_SET_NAME, name.as_ptr()), 0);
+        let mut buf = [0u8; 16];
+        assert_eq!(libc::prctl(libc::PR_GET_NAME, buf.as_mut_ptr()), 0);
+        assert_eq!(&buf, b"a very long thr\0");
+    })
+    .join()
+    .unwrap();
+}
@@ -0,0 +1,13 @@
+//@compile-flags: -Zmiri-disable-isolation -Zmiri-permissive-provenance
+//@only-target-linux: the runtime uses epoll, which is only supported on Linux
+
+use std::time::Duration;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let task = tokio::spawn(async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        42
+    });
+    assert_eq!(task.await.unwrap(), 42);
+}
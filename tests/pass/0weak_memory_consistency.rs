@@ -286,6 +286,135 @@ fn test_iriw_sc_rlx() {
     assert!(c || d);
 }
 
+// The following tests are the classic litmus tests with relaxed accesses and SC fences, see
+// Repairing Sequential Consistency in C/C++11 by Lahav et al.
+// https://plv.mpi-sws.org/scfix/paper.pdf
+
+// Test case SB with SC fences
+fn test_sc_fence_store_buffering() {
+    let x = static_atomic(0);
+    let y = static_atomic(0);
+
+    let j1 = spawn(move || {
+        x.store(1, Relaxed);
+        fence(SeqCst);
+        y.load(Relaxed)
+    });
+
+    let j2 = spawn(move || {
+        y.store(1, Relaxed);
+        fence(SeqCst);
+        x.load(Relaxed)
+    });
+
+    let a = j1.join().unwrap();
+    let b = j2.join().unwrap();
+
+    assert_ne!((a, b), (0, 0));
+}
+
+// Test case MP with SC fences
+fn test_sc_fence_message_passing() {
+    let x = static_atomic(0);
+    let y = static_atomic(0);
+
+    let j1 = spawn(move || {
+        x.store(1, Relaxed);
+        fence(SeqCst);
+        y.store(1, Relaxed);
+    });
+
+    let j2 = spawn(move || {
+        while y.load(Relaxed) != 1 {
+            std::hint::spin_loop();
+        }
+        fence(SeqCst);
+        x.load(Relaxed)
+    });
+
+    j1.join().unwrap();
+    let r2 = j2.join().unwrap();
+
+    assert_eq!(r2, 1);
+}
+
+// Test case IRIW with SC fences between the loads
+fn test_iriw_sc_fences() {
+    let x = static_atomic_bool(false);
+    let y = static_atomic_bool(false);
+
+    let a = spawn(move || x.store(true, Relaxed));
+    let b = spawn(move || y.store(true, Relaxed));
+    let c = spawn(move || {
+        while !x.load(Relaxed) {}
+        fence(SeqCst);
+        y.load(Relaxed)
+    });
+    let d = spawn(move || {
+        while !y.load(Relaxed) {}
+        fence(SeqCst);
+        x.load(Relaxed)
+    });
+
+    a.join().unwrap();
+    b.join().unwrap();
+    let c = c.join().unwrap();
+    let d = d.join().unwrap();
+
+    // Both threads observed one store, so the other thread must have observed it, too,
+    // whichever SC fence came first.
+    assert!(c || d);
+}
+
+// Test case SB with SC fences, where the stores and loads are read-modify-write operations
+fn test_sc_fence_store_buffering_rmw() {
+    let x = static_atomic(0);
+    let y = static_atomic(0);
+
+    let j1 = spawn(move || {
+        x.swap(1, Relaxed);
+        fence(SeqCst);
+        y.load(Relaxed)
+    });
+
+    let j2 = spawn(move || {
+        y.store(1, Relaxed);
+        fence(SeqCst);
+        x.fetch_add(0, Relaxed)
+    });
+
+    let a = j1.join().unwrap();
+    let b = j2.join().unwrap();
+
+    assert_ne!((a, b), (0, 0));
+}
+
+// Test case IRIW with SC fences, where the first load of each reader is a read-modify-write
+fn test_iriw_sc_fences_rmw() {
+    let x = static_atomic(0);
+    let y = static_atomic(0);
+
+    let a = spawn(move || x.store(1, Relaxed));
+    let b = spawn(move || y.store(1, Relaxed));
+    let c = spawn(move || {
+        while x.fetch_add(0, Relaxed) == 0 {}
+        fence(SeqCst);
+        y.load(Relaxed)
+    });
+    let d = spawn(move || {
+        while y.fetch_add(0, Relaxed) == 0 {}
+        fence(SeqCst);
+        x.load(Relaxed)
+    });
+
+    a.join().unwrap();
+    b.join().unwrap();
+    let c = c.join().unwrap();
+    let d = d.join().unwrap();
+
+    assert!(c == 1 || d == 1);
+}
+
 pub fn main() {
     for _ in 0..50 {
         test_single_thread();
@@ -297,5 +426,10 @@ pub fn main() {
         test_sc_store_buffering();
         test_sync_through_rmw_and_fences();
         test_iriw_sc_rlx();
+        test_sc_fence_store_buffering();
+        test_sc_fence_message_passing();
+        test_iriw_sc_fences();
+        test_sc_fence_store_buffering_rmw();
+        test_iriw_sc_fences_rmw();
     }
 }
@@ -0,0 +1,5 @@
+//@compile-flags: -Zmiri-num-cpus=4
+
+fn main() {
+    assert_eq!(std::thread::available_parallelism().unwrap().get(), 4);
+}
@@ -0,0 +1,66 @@
+//@ignore-32bit
+//@compile-flags: -Zinline-mir
+
+#[inline(never)]
+fn func_a() -> Box<[*mut ()]> {
+    func_b()
+}
+#[inline(always)]
+fn func_b() -> Box<[*mut ()]> {
+    func_c()
+}
+#[inline(never)]
+fn func_c() -> Box<[*mut ()]> {
+    unsafe {
+        let count = miri_backtrace_size(1);
+        let mut buf = vec![std::ptr::null_mut(); count];
+        miri_get_backtrace(2, buf.as_mut_ptr());
+        buf.into()
+    }
+}
+
+fn resolve(frame: *mut ()) -> (String, u32, bool) {
+    let miri_frame = unsafe { miri_resolve_frame(frame, 1) };
+    let mut name = vec![0; miri_frame.name_len];
+    let mut filename = vec![0; miri_frame.filename_len];
+    unsafe {
+        miri_resolve_frame_names(frame, 0, name.as_mut_ptr(), filename.as_mut_ptr());
+    }
+    if miri_frame.inlined {
+        assert_eq!(miri_frame.fn_ptr, func_b as *mut ());
+    }
+    (String::from_utf8(name).unwrap(), miri_frame.lineno, miri_frame.inlined)
+}
+
+fn main() {
+    let frames: Vec<_> = func_a().iter().map(|&frame| resolve(frame)).take(4).collect();
+    // `func_b` was inlined into `func_a`, but still gets its own frame, with the line of the call
+    // in `func_b` itself. The frame of `func_a` then has the line where `func_b` is called.
+    assert_eq!(frames, [
+        ("func_c".to_owned(), 17, false),
+        ("func_b".to_owned(), 10, true),
+        ("func_a".to_owned(), 6, false),
+        ("main".to_owned(), 36, false),
+    ]);
+}
+
+// This goes at the bottom of the file so that we can change it
+// without disturbing line numbers of the functions in the backtrace.
+
+extern "Rust" {
+    fn miri_backtrace_size(flags: u64) -> usize;
+    fn miri_get_backtrace(flags: u64, buf: *mut *mut ());
+    fn miri_resolve_frame(ptr: *mut (), flags: u64) -> MiriFrame;
+    fn miri_resolve_frame_names(ptr: *mut (), flags: u64, name_buf: *mut u8, filename_buf: *mut u8);
+}
+
+#[derive(Debug)]
+#[repr(C)]
+struct MiriFrame {
+    name_len: usize,
+    filename_len: usize,
+    lineno: u32,
+    colno: u32,
+    fn_ptr: *mut (),
+    inlined: bool,
+}
@@ -0,0 +1,34 @@
+//@ignore-target-windows: Concurrency on Windows is not supported yet.
+// `spin_loop` only lets other threads run where Miri treats the pause instruction as a yield.
+//@only-target-x86_64
+// Spin loops that let other threads run make progress and must not be reported.
+//@compile-flags: -Zmiri-preemption-rate=0 -Zmiri-livelock-threshold=100
+
+use std::hint;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+fn spin_with_yield_now() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+
+    let t = thread::spawn(|| FLAG.store(true, Ordering::Release));
+    while !FLAG.load(Ordering::Acquire) {
+        thread::yield_now();
+    }
+    t.join().unwrap();
+}
+
+fn spin_with_spin_loop() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+
+    let t = thread::spawn(|| FLAG.store(true, Ordering::Release));
+    while !FLAG.load(Ordering::Acquire) {
+        hint::spin_loop();
+    }
+    t.join().unwrap();
+}
+
+fn main() {
+    spin_with_yield_now();
+    spin_with_spin_loop();
+}
@@ -0,0 +1,20 @@
+//@ignore-target-windows: Concurrency on Windows is not supported yet.
+// Without preemption, the other thread cannot run while the main thread spins.
+//@compile-flags: -Zmiri-preemption-rate=0 -Zmiri-livelock-threshold=1000
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+static FLAG: AtomicBool = AtomicBool::new(false);
+
+fn main() {
+    let t = thread::spawn(|| FLAG.store(true, Ordering::Relaxed));
+    // This would spin forever, so we give up after a while.
+    let mut spins = 0;
+    while !FLAG.load(Ordering::Relaxed) && spins < 1000 {
+        spins += 1;
+    }
+    assert!(!FLAG.load(Ordering::Relaxed));
+    t.join().unwrap();
+    assert!(FLAG.load(Ordering::Relaxed));
+}
@@ -0,0 +1,101 @@
+//@only-target-apple: Uses libdispatch functions
+
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type DispatchSemaphore = *mut c_void;
+
+extern "C" {
+    fn dispatch_once_f(
+        predicate: *mut isize,
+        context: *mut c_void,
+        function: extern "C" fn(*mut c_void),
+    );
+    fn dispatch_time(when: u64, delta: i64) -> u64;
+    fn dispatch_semaphore_create(value: isize) -> DispatchSemaphore;
+    fn dispatch_semaphore_wait(semaphore: DispatchSemaphore, timeout: u64) -> isize;
+    fn dispatch_semaphore_signal(semaphore: DispatchSemaphore) -> isize;
+    fn dispatch_retain(object: *mut c_void);
+    fn dispatch_release(object: *mut c_void);
+}
+
+const DISPATCH_TIME_NOW: u64 = 0;
+const DISPATCH_TIME_FOREVER: u64 = !0;
+
+struct Semaphore(DispatchSemaphore);
+unsafe impl Send for Semaphore {}
+unsafe impl Sync for Semaphore {}
+
+fn once() {
+    static mut PREDICATE: isize = 0;
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    extern "C" fn init(context: *mut c_void) {
+        assert_eq!(context as usize, 0);
+        // Give the other threads a chance to call `dispatch_once_f` while we are running.
+        thread::yield_now();
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let threads: Vec<_> = (0..3)
+        .map(|_| {
+            thread::spawn(|| unsafe {
+                dispatch_once_f(ptr::addr_of_mut!(PREDICATE), ptr::null_mut(), init);
+                // Everyone sees the initialization as completed.
+                assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    unsafe { dispatch_once_f(ptr::addr_of_mut!(PREDICATE), ptr::null_mut(), init) };
+    assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+}
+
+fn semaphore_timeout() {
+    unsafe {
+        assert!(dispatch_semaphore_create(-1).is_null());
+
+        let semaphore = dispatch_semaphore_create(1);
+        assert!(!semaphore.is_null());
+        assert_eq!(dispatch_semaphore_wait(semaphore, DISPATCH_TIME_NOW), 0);
+        assert_ne!(dispatch_semaphore_wait(semaphore, DISPATCH_TIME_NOW), 0);
+
+        let start = Instant::now();
+        let deadline = dispatch_time(DISPATCH_TIME_NOW, 100_000_000);
+        assert_ne!(dispatch_semaphore_wait(semaphore, deadline), 0);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        assert_eq!(dispatch_semaphore_signal(semaphore), 0);
+        assert_eq!(dispatch_semaphore_wait(semaphore, DISPATCH_TIME_FOREVER), 0);
+
+        dispatch_retain(semaphore);
+        dispatch_release(semaphore);
+        dispatch_release(semaphore);
+    }
+}
+
+fn semaphore_signal() {
+    let semaphore = Semaphore(unsafe { dispatch_semaphore_create(0) });
+    let data = AtomicUsize::new(0);
+    thread::scope(|s| {
+        s.spawn(|| {
+            data.store(42, Ordering::Relaxed);
+            unsafe { dispatch_semaphore_signal(semaphore.0) };
+        });
+        // Waiting for the semaphore synchronizes with the signal.
+        assert_eq!(unsafe { dispatch_semaphore_wait(semaphore.0, DISPATCH_TIME_FOREVER) }, 0);
+        assert_eq!(data.load(Ordering::Relaxed), 42);
+    });
+    unsafe { dispatch_release(semaphore.0) };
+}
+
+fn main() {
+    once();
+    semaphore_timeout();
+    semaphore_signal();
+}
@@ -0,0 +1,50 @@
+//@ignore-target-windows: Concurrency on Windows is not supported yet.
+// Isolation is enabled, so the timeouts use Miri's virtual clock.
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn park_timeout() {
+    let start = Instant::now();
+    thread::park_timeout(Duration::from_millis(200));
+    // Nobody unparks us, so we wake up once the timeout has passed.
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+fn park_timeout_unpark() {
+    let main = thread::current();
+    let t = thread::spawn(move || {
+        main.unpark();
+    });
+    let start = Instant::now();
+    thread::park_timeout(Duration::from_secs(3600));
+    // We are unparked long before the timeout.
+    assert!(start.elapsed() < Duration::from_secs(3600));
+    t.join().unwrap();
+}
+
+fn recv_timeout() {
+    let (_sender, receiver) = mpsc::channel::<i32>();
+    let start = Instant::now();
+    assert_eq!(receiver.recv_timeout(Duration::from_millis(100)), Err(RecvTimeoutError::Timeout));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+fn recv_huge_timeout() {
+    let (sender, receiver) = mpsc::channel();
+    let t = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        sender.send(42).unwrap();
+    });
+    // The deadline cannot be represented, so this waits without a timeout.
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(1 << 40)), Ok(42));
+    t.join().unwrap();
+}
+
+fn main() {
+    park_timeout();
+    park_timeout_unpark();
+    recv_timeout();
+    recv_huge_timeout();
+}
@@ -0,0 +1,46 @@
+// Preemption is disabled globally, and only enabled where the program asks for it.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+extern "Rust" {
+    fn miri_set_preemption_rate(rate: f64) -> f64;
+    fn miri_set_thread_preemption_rate(rate: f64) -> f64;
+}
+
+fn global_rate() {
+    unsafe {
+        assert_eq!(miri_set_preemption_rate(0.5), 0.0);
+        assert_eq!(miri_set_preemption_rate(0.0), 0.5);
+    }
+}
+
+fn thread_rate() {
+    unsafe {
+        assert!(miri_set_thread_preemption_rate(1.0) < 0.0);
+        assert_eq!(miri_set_thread_preemption_rate(-1.0), 1.0);
+        // The override is per-thread.
+        thread::spawn(|| assert!(miri_set_thread_preemption_rate(0.5) < 0.0)).join().unwrap();
+        assert!(miri_set_thread_preemption_rate(-1.0) < 0.0);
+    }
+}
+
+/// Without preemption, this spin loop would never let the other thread run.
+fn spin_with_preemption() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+
+    let old = unsafe { miri_set_thread_preemption_rate(1.0) };
+    let setter = thread::spawn(|| FLAG.store(true, Ordering::Release));
+    while !FLAG.load(Ordering::Acquire) {
+        // spin without yielding
+    }
+    unsafe { miri_set_thread_preemption_rate(old) };
+    setter.join().unwrap();
+}
+
+fn main() {
+    global_rate();
+    thread_rate();
+    spin_with_preemption();
+}
@@ -0,0 +1,25 @@
+//@compile-flags: -Zmiri-race-report-only -Zmiri-preemption-rate=0
+
+use std::thread::spawn;
+
+#[derive(Copy, Clone)]
+struct EvilSend<T>(pub T);
+
+unsafe impl<T> Send for EvilSend<T> {}
+unsafe impl<T> Sync for EvilSend<T> {}
+
+fn main() {
+    let mut a = 0u32;
+    let b = EvilSend(&mut a as *mut u32);
+    // Both iterations race in the same way, so this is only shown once.
+    for _ in 0..2 {
+        let j1 = spawn(move || unsafe {
+            let _val = *b.0;
+        });
+        let j2 = spawn(move || unsafe {
+            *b.0 = 64;
+        });
+        j1.join().unwrap();
+        j2.join().unwrap();
+    }
+}
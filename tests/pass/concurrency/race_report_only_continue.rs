@@ -0,0 +1,24 @@
+//@compile-flags: -Zmiri-race-report-only -Zmiri-preemption-rate=0
+
+use std::thread::spawn;
+
+#[derive(Copy, Clone)]
+struct EvilSend<T>(pub T);
+
+unsafe impl<T> Send for EvilSend<T> {}
+unsafe impl<T> Sync for EvilSend<T> {}
+
+fn main() {
+    let mut a = 0u32;
+    let b = EvilSend(&mut a as *mut u32);
+    let j1 = spawn(move || unsafe {
+        *b.0 = 1;
+    });
+    let j2 = spawn(move || unsafe {
+        *b.0 = 2;
+        // The racing write above still took effect, so this read does not race.
+        let _val = *b.0;
+    });
+    j1.join().unwrap();
+    j2.join().unwrap();
+}
@@ -0,0 +1,31 @@
+//@ignore-target-windows: Concurrency on Windows is not supported yet.
+//@compile-flags: -Zmiri-scheduler=delay
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Yielding threads are delayed, but they still make progress eventually.
+fn main() {
+    static TURN: AtomicUsize = AtomicUsize::new(0);
+
+    let counter = Arc::new(Mutex::new(0));
+    let handles: Vec<_> = (0..3)
+        .map(|id| {
+            let counter = Arc::clone(&counter);
+            thread::spawn(move || {
+                for _ in 0..5 {
+                    while TURN.load(Ordering::Acquire) % 3 != id {
+                        thread::yield_now();
+                    }
+                    *counter.lock().unwrap() += 1;
+                    TURN.fetch_add(1, Ordering::Release);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(*counter.lock().unwrap(), 15);
+}
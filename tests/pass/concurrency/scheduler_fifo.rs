@@ -0,0 +1,43 @@
+//@ignore-target-windows: Concurrency on Windows is not supported yet.
+//@compile-flags: -Zmiri-scheduler=fifo
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Threads run in the order in which they became ready, and a yielding thread goes to the back of
+/// the queue.
+fn run_in_ready_order() {
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let handles: Vec<_> = (1..=3)
+        .map(|id| {
+            let order = Arc::clone(&order);
+            thread::spawn(move || {
+                order.lock().unwrap().push(id);
+                thread::yield_now();
+                order.lock().unwrap().push(id * 10);
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(*order.lock().unwrap(), [1, 2, 3, 10, 20, 30]);
+}
+
+/// Threads are never preempted, so this thread finishes its work before the other one gets to
+/// look at it.
+fn no_preemption() {
+    static DONE: Mutex<bool> = Mutex::new(false);
+
+    let observer = thread::spawn(|| *DONE.lock().unwrap());
+    for _ in 0..1000 {
+        *DONE.lock().unwrap() = false;
+    }
+    *DONE.lock().unwrap() = true;
+    assert!(observer.join().unwrap());
+}
+
+fn main() {
+    run_in_ready_order();
+    no_preemption();
+}
@@ -0,0 +1,37 @@
+//@ignore-target-windows: Concurrency on Windows is not supported yet.
+//@compile-flags: -Zmiri-scheduler=priority
+
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+
+extern "Rust" {
+    fn miri_set_thread_priority(priority: i32) -> i32;
+}
+
+fn main() {
+    unsafe {
+        assert_eq!(miri_set_thread_priority(5), 0);
+        assert_eq!(miri_set_thread_priority(0), 5);
+    }
+
+    let barrier = Arc::new(Barrier::new(2));
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let handles: Vec<_> = [1, 2]
+        .into_iter()
+        .map(|priority| {
+            let barrier = Arc::clone(&barrier);
+            let order = Arc::clone(&order);
+            thread::spawn(move || {
+                unsafe { miri_set_thread_priority(priority) };
+                barrier.wait();
+                // Yielding only lets threads with at least the same priority run.
+                thread::yield_now();
+                order.lock().unwrap().push(priority);
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(*order.lock().unwrap(), [2, 1]);
+}
@@ -0,0 +1,28 @@
+use std::thread;
+
+extern "Rust" {
+    fn miri_get_thread_name(buf: *mut u8, len: usize) -> usize;
+}
+
+fn thread_name() -> String {
+    let mut buf = [0u8; 32];
+    let len = unsafe { miri_get_thread_name(buf.as_mut_ptr(), buf.len()) };
+    String::from_utf8(buf[..len].to_vec()).unwrap()
+}
+
+fn main() {
+    assert_eq!(thread_name(), "main");
+
+    // Only the requested part of the name is written.
+    let mut buf = [0u8; 2];
+    assert_eq!(unsafe { miri_get_thread_name(buf.as_mut_ptr(), buf.len()) }, 4);
+    assert_eq!(&buf, b"ma");
+
+    thread::Builder::new()
+        .name("worker".into())
+        .spawn(|| assert_eq!(thread_name(), "worker"))
+        .unwrap()
+        .join()
+        .unwrap();
+    thread::spawn(|| assert_eq!(thread_name(), "<unnamed>")).join().unwrap();
+}
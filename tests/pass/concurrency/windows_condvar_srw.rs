@@ -0,0 +1,125 @@
+//@only-target-windows: Uses win32 api functions
+
+use std::cell::UnsafeCell;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[repr(C)]
+struct Srw(UnsafeCell<usize>);
+#[repr(C)]
+struct CondVar(UnsafeCell<usize>);
+
+unsafe impl Sync for Srw {}
+unsafe impl Sync for CondVar {}
+
+extern "system" {
+    fn AcquireSRWLockExclusive(lock: *mut usize);
+    fn ReleaseSRWLockExclusive(lock: *mut usize);
+    fn AcquireSRWLockShared(lock: *mut usize);
+    fn ReleaseSRWLockShared(lock: *mut usize);
+    fn TryAcquireSRWLockExclusive(lock: *mut usize) -> u8;
+    fn TryAcquireSRWLockShared(lock: *mut usize) -> u8;
+    fn SleepConditionVariableSRW(
+        cv: *mut usize,
+        lock: *mut usize,
+        timeout: u32,
+        flags: u32,
+    ) -> i32;
+    fn WakeAllConditionVariable(cv: *mut usize);
+    fn GetLastError() -> u32;
+}
+
+const INFINITE: u32 = u32::MAX;
+const ERROR_TIMEOUT: u32 = 1460;
+const CONDITION_VARIABLE_LOCKMODE_SHARED: u32 = 1;
+
+fn try_acquire() {
+    let lock = Srw(UnsafeCell::new(0));
+    unsafe {
+        assert_ne!(TryAcquireSRWLockExclusive(lock.0.get()), 0);
+        assert_eq!(TryAcquireSRWLockExclusive(lock.0.get()), 0);
+        assert_eq!(TryAcquireSRWLockShared(lock.0.get()), 0);
+        ReleaseSRWLockExclusive(lock.0.get());
+
+        assert_ne!(TryAcquireSRWLockShared(lock.0.get()), 0);
+        assert_ne!(TryAcquireSRWLockShared(lock.0.get()), 0);
+        assert_eq!(TryAcquireSRWLockExclusive(lock.0.get()), 0);
+        ReleaseSRWLockShared(lock.0.get());
+        ReleaseSRWLockShared(lock.0.get());
+    }
+}
+
+fn sleep_timeout() {
+    let lock = Srw(UnsafeCell::new(0));
+    let cv = CondVar(UnsafeCell::new(0));
+    unsafe {
+        AcquireSRWLockExclusive(lock.0.get());
+        let start = Instant::now();
+        assert_eq!(SleepConditionVariableSRW(cv.0.get(), lock.0.get(), 100, 0), 0);
+        assert_eq!(GetLastError(), ERROR_TIMEOUT);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        // We hold the lock again.
+        assert_eq!(TryAcquireSRWLockShared(lock.0.get()), 0);
+        ReleaseSRWLockExclusive(lock.0.get());
+    }
+}
+
+fn sleep_shared() {
+    static LOCK: Srw = Srw(UnsafeCell::new(0));
+    static CV: CondVar = CondVar(UnsafeCell::new(0));
+    static mut READY: bool = false;
+
+    let waiters: Vec<_> = (0..2)
+        .map(|_| {
+            thread::spawn(|| unsafe {
+                AcquireSRWLockShared(LOCK.0.get());
+                while !READY {
+                    let res = SleepConditionVariableSRW(
+                        CV.0.get(),
+                        LOCK.0.get(),
+                        INFINITE,
+                        CONDITION_VARIABLE_LOCKMODE_SHARED,
+                    );
+                    assert_ne!(res, 0);
+                }
+                ReleaseSRWLockShared(LOCK.0.get());
+            })
+        })
+        .collect();
+
+    thread::yield_now();
+    unsafe {
+        AcquireSRWLockExclusive(LOCK.0.get());
+        READY = true;
+        ReleaseSRWLockExclusive(LOCK.0.get());
+        WakeAllConditionVariable(CV.0.get());
+    }
+    for waiter in waiters {
+        waiter.join().unwrap();
+    }
+}
+
+fn std_condvar() {
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let pair2 = Arc::clone(&pair);
+    let t = thread::spawn(move || {
+        let (lock, cvar) = &*pair2;
+        *lock.lock().unwrap() = true;
+        cvar.notify_one();
+    });
+    let (lock, cvar) = &*pair;
+    let mut started = lock.lock().unwrap();
+    while !*started {
+        started = cvar.wait(started).unwrap();
+    }
+    drop(started);
+    t.join().unwrap();
+}
+
+fn main() {
+    try_acquire();
+    sleep_timeout();
+    sleep_shared();
+    std_condvar();
+}
@@ -0,0 +1,76 @@
+//@only-target-windows: Uses win32 api functions
+
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+extern "system" {
+    fn CreateThread(
+        security: *const u8,
+        stack_size: usize,
+        start: extern "system" fn(*mut u8) -> u32,
+        arg: *mut u8,
+        flags: u32,
+        thread_id: *mut u32,
+    ) -> usize;
+    fn ExitThread(code: u32) -> !;
+    fn WaitForSingleObject(handle: usize, timeout: u32) -> u32;
+    fn GetCurrentThreadId() -> u32;
+    fn CloseHandle(handle: usize) -> i32;
+}
+
+const INFINITE: u32 = u32::MAX;
+const WAIT_OBJECT_0: u32 = 0;
+const WAIT_TIMEOUT: u32 = 258;
+
+static THREAD_ID: AtomicU32 = AtomicU32::new(0);
+static LOCK: Mutex<()> = Mutex::new(());
+
+extern "system" fn record_id(_arg: *mut u8) -> u32 {
+    THREAD_ID.store(unsafe { GetCurrentThreadId() }, Ordering::Relaxed);
+    0
+}
+
+fn exit_early() {
+    unsafe { ExitThread(0) }
+}
+
+extern "system" fn exit_thread(_arg: *mut u8) -> u32 {
+    exit_early();
+    unreachable!();
+}
+
+extern "system" fn wait_for_lock(_arg: *mut u8) -> u32 {
+    drop(LOCK.lock().unwrap());
+    0
+}
+
+fn create(start: extern "system" fn(*mut u8) -> u32, thread_id: &mut u32) -> usize {
+    let handle = unsafe { CreateThread(ptr::null(), 0, start, ptr::null_mut(), 0, thread_id) };
+    assert_ne!(handle, 0);
+    handle
+}
+
+fn main() {
+    let main_id = unsafe { GetCurrentThreadId() };
+
+    let mut thread_id = 0;
+    let handle = create(record_id, &mut thread_id);
+    assert_eq!(unsafe { WaitForSingleObject(handle, INFINITE) }, WAIT_OBJECT_0);
+    assert_eq!(THREAD_ID.load(Ordering::Relaxed), thread_id);
+    assert_ne!(thread_id, main_id);
+    assert_ne!(unsafe { CloseHandle(handle) }, 0);
+
+    let handle = create(exit_thread, &mut thread_id);
+    assert_eq!(unsafe { WaitForSingleObject(handle, INFINITE) }, WAIT_OBJECT_0);
+    assert_ne!(unsafe { CloseHandle(handle) }, 0);
+
+    // The thread cannot terminate while we hold the lock, so waiting for it times out.
+    let guard = LOCK.lock().unwrap();
+    let handle = create(wait_for_lock, &mut thread_id);
+    assert_eq!(unsafe { WaitForSingleObject(handle, 0) }, WAIT_TIMEOUT);
+    assert_eq!(unsafe { WaitForSingleObject(handle, 100) }, WAIT_TIMEOUT);
+    drop(guard);
+    assert_eq!(unsafe { WaitForSingleObject(handle, INFINITE) }, WAIT_OBJECT_0);
+    assert_ne!(unsafe { CloseHandle(handle) }, 0);
+}
@@ -0,0 +1,187 @@
+//@only-target-windows: Uses win32 api functions
+
+use std::os::windows::io::IntoRawHandle;
+use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+extern "system" {
+    fn SleepEx(milliseconds: u32, alertable: i32) -> u32;
+    fn CreateEventW(
+        attributes: *const u8,
+        manual_reset: i32,
+        signaled: i32,
+        name: *const u16,
+    ) -> usize;
+    fn SetEvent(event: usize) -> i32;
+    fn ResetEvent(event: usize) -> i32;
+    fn CreateMutexW(attributes: *const u8, initial_owner: i32, name: *const u16) -> usize;
+    fn ReleaseMutex(mutex: usize) -> i32;
+    fn WaitForSingleObject(handle: usize, timeout: u32) -> u32;
+    fn WaitForMultipleObjects(
+        count: u32,
+        handles: *const usize,
+        wait_all: i32,
+        timeout: u32,
+    ) -> u32;
+    fn CloseHandle(handle: usize) -> i32;
+    fn GetLastError() -> u32;
+}
+
+const INFINITE: u32 = u32::MAX;
+const WAIT_OBJECT_0: u32 = 0;
+const WAIT_TIMEOUT: u32 = 258;
+const WAIT_FAILED: u32 = u32::MAX;
+const ERROR_INVALID_PARAMETER: u32 = 87;
+const ERROR_NOT_OWNER: u32 = 288;
+
+fn create_event(manual_reset: bool, signaled: bool) -> usize {
+    unsafe { CreateEventW(ptr::null(), manual_reset.into(), signaled.into(), ptr::null()) }
+}
+
+fn sleep_ex() {
+    let start = Instant::now();
+    assert_eq!(unsafe { SleepEx(100, 1) }, 0);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+fn event_timeout() {
+    let event = create_event(false, false);
+    assert_eq!(unsafe { WaitForSingleObject(event, 0) }, WAIT_TIMEOUT);
+    let start = Instant::now();
+    assert_eq!(unsafe { WaitForSingleObject(event, 100) }, WAIT_TIMEOUT);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(unsafe { CloseHandle(event) }, 1);
+}
+
+fn auto_reset_event() {
+    let event = create_event(false, true);
+    unsafe {
+        assert_eq!(WaitForSingleObject(event, 0), WAIT_OBJECT_0);
+        // Releasing the thread reset the event.
+        assert_eq!(WaitForSingleObject(event, 0), WAIT_TIMEOUT);
+        assert_eq!(SetEvent(event), 1);
+        assert_eq!(WaitForSingleObject(event, INFINITE), WAIT_OBJECT_0);
+    }
+}
+
+fn manual_reset_event() {
+    let event = create_event(true, false);
+    unsafe {
+        assert_eq!(SetEvent(event), 1);
+        assert_eq!(WaitForSingleObject(event, 0), WAIT_OBJECT_0);
+        assert_eq!(WaitForSingleObject(event, 0), WAIT_OBJECT_0);
+        assert_eq!(ResetEvent(event), 1);
+        assert_eq!(WaitForSingleObject(event, 0), WAIT_TIMEOUT);
+    }
+}
+
+fn event_wakes_threads() {
+    static mut VALUE: u32 = 0;
+
+    let event = create_event(true, false);
+    let waiters: Vec<_> = (0..2)
+        .map(|_| {
+            thread::spawn(move || unsafe {
+                assert_eq!(WaitForSingleObject(event, INFINITE), WAIT_OBJECT_0);
+                // Setting the event happens-before the wait returns.
+                assert_eq!(VALUE, 1);
+            })
+        })
+        .collect();
+
+    unsafe {
+        VALUE = 1;
+        assert_eq!(SetEvent(event), 1);
+    }
+    for waiter in waiters {
+        waiter.join().unwrap();
+    }
+}
+
+fn recursive_mutex() {
+    let mutex = unsafe { CreateMutexW(ptr::null(), 1, ptr::null()) };
+    unsafe {
+        assert_eq!(WaitForSingleObject(mutex, 0), WAIT_OBJECT_0);
+        assert_eq!(ReleaseMutex(mutex), 1);
+        assert_eq!(ReleaseMutex(mutex), 1);
+        assert_eq!(ReleaseMutex(mutex), 0);
+        assert_eq!(GetLastError(), ERROR_NOT_OWNER);
+    }
+}
+
+fn mutex_excludes_threads() {
+    static mut COUNTER: u32 = 0;
+
+    let mutex = unsafe { CreateMutexW(ptr::null(), 1, ptr::null()) };
+    let threads: Vec<_> = (0..2)
+        .map(|_| {
+            thread::spawn(move || unsafe {
+                assert_eq!(WaitForSingleObject(mutex, INFINITE), WAIT_OBJECT_0);
+                COUNTER += 1;
+                assert_eq!(ReleaseMutex(mutex), 1);
+            })
+        })
+        .collect();
+
+    thread::yield_now();
+    assert_eq!(unsafe { ReleaseMutex(mutex) }, 1);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(unsafe { COUNTER }, 2);
+}
+
+fn wait_for_any() {
+    let events = [create_event(false, false), create_event(false, false)];
+    let setter = thread::spawn(move || unsafe {
+        assert_eq!(SetEvent(events[1]), 1);
+    });
+    unsafe {
+        assert_eq!(WaitForMultipleObjects(2, events.as_ptr(), 0, INFINITE), WAIT_OBJECT_0 + 1);
+        assert_eq!(WaitForMultipleObjects(2, events.as_ptr(), 0, 0), WAIT_TIMEOUT);
+    }
+    setter.join().unwrap();
+}
+
+fn wait_for_all() {
+    let event = create_event(false, false);
+    let thread = thread::spawn(move || unsafe {
+        assert_eq!(SetEvent(event), 1);
+    })
+    .into_raw_handle() as usize;
+    let handles = [thread, event];
+    unsafe {
+        assert_eq!(WaitForMultipleObjects(2, handles.as_ptr(), 1, INFINITE), WAIT_OBJECT_0);
+        // The thread stays terminated, but the event was reset.
+        assert_eq!(WaitForMultipleObjects(2, handles.as_ptr(), 1, 0), WAIT_TIMEOUT);
+        assert_eq!(WaitForSingleObject(thread, 0), WAIT_OBJECT_0);
+        assert_eq!(CloseHandle(thread), 1);
+    }
+}
+
+fn wait_for_multiple_invalid() {
+    let event = create_event(false, true);
+    let handles = [event, event];
+    unsafe {
+        assert_eq!(WaitForMultipleObjects(0, handles.as_ptr(), 0, 0), WAIT_FAILED);
+        assert_eq!(GetLastError(), ERROR_INVALID_PARAMETER);
+        assert_eq!(WaitForMultipleObjects(2, handles.as_ptr(), 1, 0), WAIT_FAILED);
+        assert_eq!(GetLastError(), ERROR_INVALID_PARAMETER);
+        // Waiting for any of them is fine.
+        assert_eq!(WaitForMultipleObjects(2, handles.as_ptr(), 0, 0), WAIT_OBJECT_0);
+    }
+}
+
+fn main() {
+    sleep_ex();
+    event_timeout();
+    auto_reset_event();
+    manual_reset_event();
+    event_wakes_threads();
+    recursive_mutex();
+    mutex_excludes_threads();
+    wait_for_any();
+    wait_for_all();
+    wait_for_multiple_invalid();
+}
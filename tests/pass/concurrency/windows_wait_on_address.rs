@@ -0,0 +1,88 @@
+//@only-target-windows: Uses win32 api functions
+
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+extern "system" {
+    fn WaitOnAddress(address: *const u8, compare: *const u8, size: usize, timeout: u32) -> i32;
+    fn WakeByAddressSingle(address: *const u8);
+    fn WakeByAddressAll(address: *const u8);
+    fn GetLastError() -> u32;
+}
+
+const INFINITE: u32 = u32::MAX;
+const ERROR_TIMEOUT: u32 = 1460;
+const ERROR_INVALID_PARAMETER: u32 = 87;
+
+fn wait(atomic: &AtomicU32, compare: u32, timeout: u32) -> bool {
+    let ptr = atomic as *const AtomicU32 as *const u8;
+    let compare = &compare as *const u32 as *const u8;
+    unsafe { WaitOnAddress(ptr, compare, 4, timeout) != 0 }
+}
+
+fn wait_value_mismatch() {
+    let atomic = AtomicU32::new(1);
+    // The value differs, so this returns right away.
+    assert!(wait(&atomic, 0, INFINITE));
+}
+
+fn wait_timeout() {
+    let atomic = AtomicU32::new(0);
+    let start = Instant::now();
+    assert!(!wait(&atomic, 0, 200));
+    assert_eq!(unsafe { GetLastError() }, ERROR_TIMEOUT);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+fn wait_invalid_size() {
+    let value = AtomicU8::new(0);
+    let ptr = &value as *const AtomicU8 as *const u8;
+    assert_eq!(unsafe { WaitOnAddress(ptr, ptr, 3, INFINITE) }, 0);
+    assert_eq!(unsafe { GetLastError() }, ERROR_INVALID_PARAMETER);
+}
+
+fn wake_single() {
+    let atomic = Arc::new(AtomicU32::new(0));
+    let t = {
+        let atomic = Arc::clone(&atomic);
+        thread::spawn(move || {
+            thread::yield_now();
+            atomic.store(1, Ordering::Release);
+            unsafe { WakeByAddressSingle(&*atomic as *const AtomicU32 as *const u8) };
+        })
+    };
+    while atomic.load(Ordering::Acquire) == 0 {
+        assert!(wait(&atomic, 0, INFINITE));
+    }
+    t.join().unwrap();
+}
+
+fn wake_all() {
+    let atomic = Arc::new(AtomicU32::new(0));
+    let waiters: Vec<_> = (0..3)
+        .map(|_| {
+            let atomic = Arc::clone(&atomic);
+            thread::spawn(move || {
+                while atomic.load(Ordering::Acquire) == 0 {
+                    assert!(wait(&atomic, 0, INFINITE));
+                }
+            })
+        })
+        .collect();
+    thread::yield_now();
+    atomic.store(1, Ordering::Release);
+    unsafe { WakeByAddressAll(&*atomic as *const AtomicU32 as *const u8) };
+    for waiter in waiters {
+        waiter.join().unwrap();
+    }
+}
+
+fn main() {
+    wait_value_mismatch();
+    wait_timeout();
+    wait_invalid_size();
+    wake_single();
+    wake_all();
+}
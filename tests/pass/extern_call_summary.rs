@@ -0,0 +1,14 @@
+//@compile-flags: -Zmiri-extern-call-summary -Zmiri-extern-stub-missing
+
+extern "C" {
+    fn missing_a();
+    fn missing_b();
+}
+
+fn main() {
+    unsafe {
+        missing_b();
+        missing_a();
+        missing_b();
+    }
+}
@@ -0,0 +1,16 @@
+//@compile-flags: -Zmiri-extern-call-summary -Zmiri-extern-stub-missing -Zmiri-many-seeds=0..2
+
+// The summary is printed once, and counts the calls of both runs.
+
+extern "C" {
+    fn missing_a();
+    fn missing_b();
+}
+
+fn main() {
+    unsafe {
+        missing_b();
+        missing_a();
+        missing_b();
+    }
+}
@@ -0,0 +1,17 @@
+//@compile-flags: -Zmiri-extern-stub-missing
+
+extern "C" {
+    fn missing_int(x: i32) -> i32;
+    fn missing_ptr() -> *const u8;
+    fn missing_unit();
+}
+
+fn main() {
+    unsafe {
+        assert_eq!(missing_int(1), 0);
+        // We only warn the first time.
+        assert_eq!(missing_int(2), 0);
+        assert!(missing_ptr().is_null());
+        missing_unit();
+    }
+}
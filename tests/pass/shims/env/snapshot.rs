@@ -0,0 +1,31 @@
+use std::env;
+
+extern "Rust" {
+    fn miri_env_snapshot() -> usize;
+    fn miri_env_restore(snapshot: usize);
+}
+
+fn main() {
+    env::set_var("MIRI_TEST_KEPT", "before");
+    env::set_var("MIRI_TEST_REMOVED", "before");
+    let snapshot = unsafe { miri_env_snapshot() };
+
+    env::set_var("MIRI_TEST_KEPT", "after");
+    env::remove_var("MIRI_TEST_REMOVED");
+    env::set_var("MIRI_TEST_ADDED", "after");
+
+    unsafe { miri_env_restore(snapshot) };
+    assert_eq!(env::var("MIRI_TEST_KEPT"), Ok("before".to_owned()));
+    assert_eq!(env::var("MIRI_TEST_REMOVED"), Ok("before".to_owned()));
+    assert!(env::var("MIRI_TEST_ADDED").is_err());
+    assert_eq!(env::vars().filter(|(name, _)| name.starts_with("MIRI_TEST_")).count(), 2);
+
+    // A snapshot can be restored again.
+    env::remove_var("MIRI_TEST_KEPT");
+    let empty = unsafe { miri_env_snapshot() };
+    unsafe { miri_env_restore(snapshot) };
+    assert_eq!(env::var("MIRI_TEST_KEPT"), Ok("before".to_owned()));
+    unsafe { miri_env_restore(empty) };
+    assert!(env::var("MIRI_TEST_KEPT").is_err());
+    assert_eq!(env::var("MIRI_TEST_REMOVED"), Ok("before".to_owned()));
+}
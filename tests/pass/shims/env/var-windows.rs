@@ -0,0 +1,68 @@
+//@only-target-windows: tests the Windows environment block
+
+use std::env;
+
+extern "system" {
+    fn GetEnvironmentStringsW() -> *mut u16;
+    fn FreeEnvironmentStringsW(block: *mut u16) -> i32;
+    fn SetEnvironmentVariableW(name: *const u16, value: *const u16) -> i32;
+    fn GetLastError() -> u32;
+}
+
+const ERROR_INVALID_PARAMETER: u32 = 87;
+
+/// Reads the environment block as a list of `NAME=value` strings.
+fn env_block() -> Vec<String> {
+    unsafe {
+        let block = GetEnvironmentStringsW();
+        assert!(!block.is_null());
+        let mut vars = Vec::new();
+        let mut ptr = block;
+        loop {
+            let len = (0..).find(|&i| *ptr.add(i) == 0).unwrap();
+            if len == 0 {
+                break;
+            }
+            vars.push(String::from_utf16(std::slice::from_raw_parts(ptr, len)).unwrap());
+            ptr = ptr.add(len + 1);
+        }
+        assert_eq!(FreeEnvironmentStringsW(block), 1);
+        vars
+    }
+}
+
+fn main() {
+    // Names are not case-sensitive.
+    env::set_var("MIRI_WINDOWS_VAR", "a");
+    assert_eq!(env::var("miri_windows_var"), Ok("a".to_owned()));
+    env::set_var("Miri_Windows_Var", "b");
+    assert_eq!(env::var("MIRI_WINDOWS_VAR"), Ok("b".to_owned()));
+    let vars: Vec<_> = env::vars()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("MIRI_WINDOWS_VAR"))
+        .collect();
+    assert_eq!(vars, [("Miri_Windows_Var".to_owned(), "b".to_owned())]);
+
+    // The block is sorted by name, and changes show up in it.
+    env::set_var("MIRI_A", "1");
+    env::set_var("miri_b", "2");
+    let block = env_block();
+    let names: Vec<_> =
+        block.iter().map(|var| var.split('=').next().unwrap().to_uppercase()).collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+    assert!(block.contains(&"MIRI_A=1".to_owned()));
+    assert!(block.contains(&"miri_b=2".to_owned()));
+
+    env::remove_var("MIRI_WINDOWS_VAR");
+    assert!(env::var("Miri_Windows_Var").is_err());
+    assert!(!env_block().iter().any(|var| var.starts_with("Miri_Windows_Var=")));
+
+    // Invalid names are an error.
+    let name: Vec<u16> = "A=B\0".encode_utf16().collect();
+    let value: Vec<u16> = "1\0".encode_utf16().collect();
+    unsafe {
+        assert_eq!(SetEnvironmentVariableW(name.as_ptr(), value.as_ptr()), 0);
+        assert_eq!(GetLastError(), ERROR_INVALID_PARAMETER);
+    }
+}
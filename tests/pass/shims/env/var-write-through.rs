@@ -0,0 +1,23 @@
+//@compile-flags: -Zmiri-disable-isolation -Zmiri-env-write-through
+
+use std::env;
+
+extern "Rust" {
+    fn miri_env_snapshot() -> usize;
+    fn miri_env_restore(snapshot: usize);
+}
+
+fn main() {
+    // The variable comes from the host, and changing it works like without write-through.
+    assert_eq!(env::var("MIRI_ENV_VAR_TEST"), Ok("0".to_owned()));
+    let snapshot = unsafe { miri_env_snapshot() };
+    env::set_var("MIRI_ENV_VAR_TEST", "1");
+    assert_eq!(env::var("MIRI_ENV_VAR_TEST"), Ok("1".to_owned()));
+    env::remove_var("MIRI_ENV_VAR_TEST");
+    assert!(env::var("MIRI_ENV_VAR_TEST").is_err());
+    env::set_var("MIRI_WRITE_THROUGH_TEST", "new");
+
+    unsafe { miri_env_restore(snapshot) };
+    assert_eq!(env::var("MIRI_ENV_VAR_TEST"), Ok("0".to_owned()));
+    assert!(env::var("MIRI_WRITE_THROUGH_TEST").is_err());
+}
@@ -0,0 +1,9 @@
+//@ignore-target-windows: Windows does not support reading from stdin yet
+//@rustc-env: MIRI_STDIN=hello
+use std::io::{self, Read};
+
+fn main() {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input).unwrap();
+    assert_eq!(input, "hello");
+}
@@ -0,0 +1,16 @@
+//@ignore-target-windows: Windows does not support reading from stdin yet
+//@compile-flags: -Zmiri-stdin-file=tests/pass/shims/stdin-file.txt
+use std::io::{self, BufRead, Read};
+
+fn main() {
+    // The input is available even with isolation.
+    let mut stdin = io::stdin().lock();
+    let mut line = String::new();
+    stdin.read_line(&mut line).unwrap();
+    assert_eq!(line, "first line\n");
+    let mut rest = String::new();
+    stdin.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "second line\n");
+    // After the end of the input, reads return nothing.
+    assert_eq!(stdin.read(&mut [0; 8]).unwrap(), 0);
+}
@@ -0,0 +1,12 @@
+//@compile-flags: -Zmiri-disable-isolation -Zmiri-virtual-clock
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn main() {
+    // The sleep does not wait for the host, the virtual clock jumps right to its end.
+    let before = Instant::now();
+    thread::sleep(Duration::from_secs(3600));
+    let elapsed = before.elapsed();
+    assert!(elapsed >= Duration::from_secs(3600) && elapsed < Duration::from_secs(3601));
+}
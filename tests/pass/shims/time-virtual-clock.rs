@@ -0,0 +1,20 @@
+//@compile-flags: -Zmiri-clock-tick=0 -Zmiri-clock-read-tick=1000
+
+use std::time::{Duration, Instant};
+
+fn main() {
+    // Time only passes when the clock is read.
+    let before = Instant::now();
+    let mut x = 0u64;
+    for i in 0..1000 {
+        x = x.wrapping_add(i);
+    }
+    assert_eq!(x, 499500);
+    let after = Instant::now();
+    assert_eq!(after - before, Duration::from_micros(1));
+
+    // Every read sees a later time.
+    let times: Vec<_> = (0..3).map(|_| Instant::now()).collect();
+    assert_eq!(times[1] - times[0], Duration::from_micros(1));
+    assert_eq!(times[2] - times[1], Duration::from_micros(1));
+}
@@ -0,0 +1,65 @@
+//@ignore-target-windows: Concurrency on Windows is not supported yet.
+// Isolation is enabled, so all of this uses Miri's virtual clock.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn test_instant() {
+    let before = Instant::now();
+    let mut x = 0u64;
+    for i in 0..1000 {
+        x = x.wrapping_add(i);
+    }
+    assert_eq!(x, 499500);
+    let after = Instant::now();
+    // The virtual clock advances while the program runs, but not by much.
+    assert!(after > before);
+    assert!((after - before).as_millis() < 100);
+}
+
+fn test_sleep() {
+    let before = Instant::now();
+    thread::sleep(Duration::from_secs(3600));
+    let after = Instant::now();
+    // Nobody else is running, so the virtual clock jumps right to the end of the sleep.
+    assert!((after - before).as_secs() >= 3600);
+    assert!((after - before).as_secs() < 3601);
+}
+
+fn test_condvar_timeout() {
+    let lock = Mutex::new(());
+    let cvar = Condvar::new();
+    let guard = lock.lock().unwrap();
+    let before = Instant::now();
+    let (_guard, timeout) = cvar.wait_timeout(guard, Duration::from_millis(100)).unwrap();
+    assert!(timeout.timed_out());
+    let elapsed = before.elapsed().as_millis();
+    assert!(100 <= elapsed && elapsed < 200);
+}
+
+fn test_sleep_order() {
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let handles: Vec<_> = [300, 100, 200]
+        .into_iter()
+        .map(|millis| {
+            let order = Arc::clone(&order);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(millis));
+                order.lock().unwrap().push(millis);
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    // The threads wake up in the order of their timeouts.
+    assert_eq!(*order.lock().unwrap(), [100, 200, 300]);
+}
+
+fn main() {
+    test_instant();
+    test_sleep();
+    test_condvar_timeout();
+    test_sleep_order();
+}
@@ -0,0 +1,182 @@
+//@only-target-windows: tests the Win32 file APIs
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::time::{Duration, SystemTime};
+
+type HANDLE = isize;
+
+const GENERIC_READ: u32 = 0x80000000;
+const GENERIC_WRITE: u32 = 0x40000000;
+const FILE_SHARE_READ: u32 = 0x1;
+const CREATE_ALWAYS: u32 = 2;
+const OPEN_EXISTING: u32 = 3;
+const INVALID_HANDLE_VALUE: HANDLE = -1;
+const ERROR_FILE_NOT_FOUND: u32 = 2;
+const ERROR_ALREADY_EXISTS: u32 = 183;
+const FILE_STANDARD_INFO: u32 = 1;
+
+#[repr(C)]
+#[derive(Default)]
+struct FileStandardInfo {
+    allocation_size: i64,
+    end_of_file: i64,
+    number_of_links: u32,
+    delete_pending: u8,
+    directory: u8,
+}
+
+extern "system" {
+    fn CreateFileW(
+        file_name: *const u16,
+        desired_access: u32,
+        share_mode: u32,
+        security_attributes: *mut u8,
+        creation_disposition: u32,
+        flags_and_attributes: u32,
+        template_file: HANDLE,
+    ) -> HANDLE;
+    fn ReadFile(handle: HANDLE, buf: *mut u8, len: u32, read: *mut u32, overlapped: *mut u8) -> i32;
+    fn WriteFile(
+        handle: HANDLE,
+        buf: *const u8,
+        len: u32,
+        written: *mut u32,
+        overlapped: *mut u8,
+    ) -> i32;
+    fn CloseHandle(handle: HANDLE) -> i32;
+    fn GetFileInformationByHandleEx(
+        handle: HANDLE,
+        file_information_class: u32,
+        file_information: *mut u8,
+        buffer_size: u32,
+    ) -> i32;
+    fn GetLastError() -> u32;
+}
+
+fn tmp() -> PathBuf {
+    std::env::var("MIRI_TEMP")
+        .map(|tmp| PathBuf::from(tmp.replace("/", "\\")))
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+fn create_file(path: &Path, access: u32, creation_disposition: u32) -> HANDLE {
+    let path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    unsafe {
+        CreateFileW(
+            path.as_ptr(),
+            access,
+            FILE_SHARE_READ,
+            ptr::null_mut(),
+            creation_disposition,
+            0,
+            0,
+        )
+    }
+}
+
+fn test_win32() {
+    let path = tmp().join("miri_test_windows_fs_win32.txt");
+    unsafe {
+        let handle = create_file(&path, GENERIC_WRITE, CREATE_ALWAYS);
+        assert_ne!(handle, INVALID_HANDLE_VALUE);
+        let mut written = 0;
+        assert_eq!(WriteFile(handle, b"hello".as_ptr(), 5, &mut written, ptr::null_mut()), 1);
+        assert_eq!(written, 5);
+        assert_eq!(CloseHandle(handle), 1);
+
+        let handle = create_file(&path, GENERIC_READ, OPEN_EXISTING);
+        assert_ne!(handle, INVALID_HANDLE_VALUE);
+        let mut buf = [0u8; 16];
+        let mut read = 0;
+        assert_eq!(ReadFile(handle, buf.as_mut_ptr(), 16, &mut read, ptr::null_mut()), 1);
+        assert_eq!(&buf[..read as usize], b"hello");
+        // Reading at the end of the file succeeds with nothing.
+        assert_eq!(ReadFile(handle, buf.as_mut_ptr(), 16, &mut read, ptr::null_mut()), 1);
+        assert_eq!(read, 0);
+        assert_eq!(CloseHandle(handle), 1);
+
+        // Creating a file that exists succeeds, but tells about it.
+        let handle = create_file(&path, GENERIC_WRITE, CREATE_ALWAYS);
+        assert_ne!(handle, INVALID_HANDLE_VALUE);
+        assert_eq!(GetLastError(), ERROR_ALREADY_EXISTS);
+        assert_eq!(CloseHandle(handle), 1);
+
+        let missing = tmp().join("miri_test_windows_fs_missing.txt");
+        assert_eq!(create_file(&missing, GENERIC_READ, OPEN_EXISTING), INVALID_HANDLE_VALUE);
+        assert_eq!(GetLastError(), ERROR_FILE_NOT_FOUND);
+    }
+}
+
+fn read_all(path: &Path) -> Vec<u8> {
+    let mut file = File::open(path).unwrap();
+    let mut contents = Vec::new();
+    let mut buf = [0u8; 4];
+    loop {
+        let read = file.read(&mut buf).unwrap();
+        if read == 0 {
+            return contents;
+        }
+        contents.extend_from_slice(&buf[..read]);
+    }
+}
+
+fn test_std() {
+    let path = tmp().join("miri_test_windows_fs_std.txt");
+    File::create(&path).unwrap().write_all(b"Hello, World").unwrap();
+    assert_eq!(read_all(&path), b"Hello, World");
+
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"!").unwrap();
+    drop(file);
+    assert_eq!(read_all(&path), b"Hello, World!");
+
+    let error = OpenOptions::new().write(true).create_new(true).open(&path).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+    let error = File::open(tmp().join("miri_test_windows_fs_missing.txt")).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+}
+
+fn test_metadata() {
+    let path = tmp().join("miri_test_windows_fs_metadata.txt");
+    File::create(&path).unwrap().write_all(b"abc").unwrap();
+
+    let metadata = std::fs::metadata(&path).unwrap();
+    assert!(metadata.is_file());
+    assert_eq!(metadata.len(), 3);
+    // The file was just written, so this is clearly after 2001.
+    let modified = metadata.modified().unwrap();
+    assert!(modified > SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000));
+    assert!(!metadata.permissions().readonly());
+
+    let metadata = File::open(&path).unwrap().metadata().unwrap();
+    assert_eq!(metadata.len(), 3);
+    let metadata = std::fs::symlink_metadata(&path).unwrap();
+    assert!(metadata.is_file());
+    assert_eq!(metadata.len(), 3);
+    assert!(std::fs::metadata(tmp()).unwrap().is_dir());
+
+    unsafe {
+        let handle = create_file(&path, GENERIC_READ, OPEN_EXISTING);
+        let mut info = FileStandardInfo::default();
+        let info_ptr = &mut info as *mut FileStandardInfo as *mut u8;
+        let size = std::mem::size_of::<FileStandardInfo>() as u32;
+        assert_eq!(GetFileInformationByHandleEx(handle, FILE_STANDARD_INFO, info_ptr, size), 1);
+        assert_eq!(info.end_of_file, 3);
+        assert_eq!(info.number_of_links, 1);
+        assert_eq!((info.delete_pending, info.directory), (0, 0));
+        // The buffer has to be large enough.
+        assert_eq!(GetFileInformationByHandleEx(handle, FILE_STANDARD_INFO, info_ptr, 8), 0);
+        assert_eq!(CloseHandle(handle), 1);
+    }
+}
+
+fn main() {
+    test_win32();
+    test_std();
+    test_metadata();
+}
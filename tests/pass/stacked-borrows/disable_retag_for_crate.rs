@@ -0,0 +1,15 @@
+//@compile-flags: -Zmiri-disable-retag-for-crate=disable_retag_for_crate
+// This would be a Stacked Borrows violation (see `fail/stacked_borrows/illegal_read1.rs`), but
+// references created in this crate are not retagged.
+
+fn main() {
+    let mut x = 15;
+    let xraw = &mut x as *mut _;
+    let xref = unsafe { &mut *xraw };
+    callee(xraw);
+    let _val = *xref;
+}
+
+fn callee(xraw: *mut i32) {
+    let _val = unsafe { *xraw };
+}
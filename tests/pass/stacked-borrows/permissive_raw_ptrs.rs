@@ -0,0 +1,10 @@
+//@compile-flags: -Zmiri-sb-permissive-raw-ptrs
+// Writing through a pointer that was created as `*const` would be a Stacked Borrows violation,
+// but here all raw pointers grant `SharedReadWrite`.
+
+fn main() {
+    let mut x = 0;
+    let ptr = &mut x as *const i32 as *mut i32;
+    unsafe { *ptr = 1 };
+    assert_eq!(x, 1);
+}
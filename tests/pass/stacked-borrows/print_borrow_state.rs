@@ -0,0 +1,11 @@
+extern "Rust" {
+    fn miri_print_borrow_state(ptr: *const u8, size: usize);
+}
+
+fn main() {
+    let mut x = 0u32;
+    let xraw = &mut x as *mut u32;
+    // Only the first two bytes get a shared reference on their stack.
+    let _half = unsafe { &*(xraw as *const u16) };
+    unsafe { miri_print_borrow_state(xraw as *const u8, 4) };
+}
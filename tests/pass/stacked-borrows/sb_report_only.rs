@@ -0,0 +1,26 @@
+//@compile-flags: -Zmiri-sb-report-only
+
+fn main() {
+    let mut x = 15;
+    let xraw = &mut x as *mut _;
+    let xref = unsafe { &mut *xraw };
+    callee(xraw);
+    // This is reported, but execution continues...
+    let _val = *xref;
+    // ...and does not report the same problem again.
+    *xref = 16;
+    assert_eq!(x, 16);
+
+    // An error that happens in every iteration of a loop is only shown once.
+    for _ in 0..3 {
+        let mut y = 0;
+        let yraw = &mut y as *mut i32;
+        let yref = unsafe { &mut *yraw };
+        unsafe { *yraw = 1 };
+        let _val = *yref;
+    }
+}
+
+fn callee(xraw: *mut i32) {
+    let _val = unsafe { *xraw };
+}
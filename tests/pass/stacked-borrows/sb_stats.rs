@@ -0,0 +1,15 @@
+//@compile-flags: -Zmiri-sb-stats
+// The totals include the setup of the program arguments, so only the invalidations are exact.
+//@normalize-stderr-test: "(retags|accesses checked|borrow stack items|maximal borrow stack depth): .*" -> "$1: N"
+//@normalize-stderr-test: "(allocations with the most retags and accesses:\n)(    [a-zA-Z0-9]+: [0-9]+\n)+" -> "$1"
+#![feature(start)]
+
+#[start]
+fn start(_: isize, _: *const *const u8) -> isize {
+    let mut x = 0u8;
+    let xraw = &mut x as *mut u8;
+    let _xref = unsafe { &mut *xraw };
+    // Writing through the raw pointer pops `_xref` from the borrow stack.
+    unsafe { *xraw = 1 };
+    0
+}
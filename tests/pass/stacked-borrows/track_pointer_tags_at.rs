@@ -0,0 +1,11 @@
+//@compile-flags: -Zmiri-track-pointer-tags-at=track_pointer_tags_at.rs:7
+//@normalize-stderr-test: "created tag [0-9]+" -> "created tag TAG"
+
+fn main() {
+    let mut x = 0u32;
+    // Only the tag created on this line is tracked.
+    let r = &mut x;
+    *r = 1;
+    // Reading `x` directly pops the tag of `r`.
+    let _val = x;
+}